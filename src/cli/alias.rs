@@ -0,0 +1,171 @@
+//! Branch aliases.
+//!
+//! This module implements the `alias` subcommand and the alias resolution used by `resume`.
+//! Aliases map friendly names to (often unmemorable) branch names and are stored in the
+//! amg state file. Resolution is explicit-only: a name that is not an alias is used as a
+//! literal branch name, never fuzzily matched.
+
+use super::args::AliasCommand;
+use super::output;
+use super::policy;
+use super::prelude::*;
+use super::state::{self, State};
+
+/// Handles the `alias` subcommand.
+///
+/// # Errors
+///
+/// Returns an error if the alias name is invalid, the alias to remove does not exist, or the
+/// state file cannot be read or written.
+pub(super) fn run_alias(command: AliasCommand) -> Result<ExitCode> {
+    match command {
+        AliasCommand::Add { name, branch } => {
+            state::update(|state| add_alias(state, &name, &branch))?;
+            info!(alias = %name, branch = %branch, "added alias");
+        }
+        AliasCommand::List => {
+            let state = State::load(&state::state_path()?)?;
+            for (name, branch) in &state.aliases {
+                output::line(format_args!("{name}\t{branch}"))?;
+            }
+        }
+        AliasCommand::Rm { name } => {
+            let branch = state::update(|state| remove_alias(state, &name))?;
+            info!(alias = %name, branch = %branch, "removed alias");
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Resolves `name` to its aliased branch, or returns it unchanged if it is not an alias.
+///
+/// A state file that cannot be loaded is logged as a warning and treated as having no aliases,
+/// so a broken state file never blocks resuming by literal branch name.
+///
+/// In portable mode no state file is read, so `name` is returned unchanged.
+pub(super) fn resolve_branch(name: &str) -> String {
+    if policy::is_portable() {
+        return name.to_owned();
+    }
+    let state = state::state_path().and_then(|path| State::load(&path));
+    match state {
+        Ok(state) => resolve_in(&state, name),
+        Err(err) => {
+            warn!("ignoring aliases: {err:#}");
+            name.to_owned()
+        }
+    }
+}
+
+fn resolve_in(state: &State, name: &str) -> String {
+    match state.aliases.get(name) {
+        Some(branch) => {
+            info!(alias = %name, branch = %branch, "resolved branch alias");
+            branch.clone()
+        }
+        None => name.to_owned(),
+    }
+}
+
+fn add_alias(state: &mut State, name: &str, branch: &str) -> Result<()> {
+    validate_alias_name(name)?;
+    if branch.trim().is_empty() {
+        bail!("alias target branch must not be empty");
+    }
+    state.aliases.insert(name.to_owned(), branch.to_owned());
+    Ok(())
+}
+
+fn remove_alias(state: &mut State, name: &str) -> Result<String> {
+    state
+        .aliases
+        .remove(name)
+        .with_context(|| format!("no such alias: {name:?}"))
+}
+
+fn validate_alias_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        bail!("alias name must not be empty");
+    }
+    if name.chars().any(char::is_whitespace) {
+        bail!("alias name must not contain whitespace: {name:?}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn state_with(aliases: &[(&str, &str)]) -> State {
+        let mut state = State::default();
+        for (name, branch) in aliases {
+            add_alias(&mut state, name, branch).expect("alias should be valid");
+        }
+        state
+    }
+
+    #[test]
+    fn resolves_known_alias() {
+        let state = state_with(&[("ingestion", "feature/PROJ-4821-rework-ingestion")]);
+
+        assert_eq!(
+            resolve_in(&state, "ingestion"),
+            "feature/PROJ-4821-rework-ingestion"
+        );
+    }
+
+    #[rstest]
+    #[case("ingestoin")]
+    #[case("ingest")]
+    #[case("main")]
+    fn unknown_names_are_literal_branches(#[case] name: &str) {
+        let state = state_with(&[("ingestion", "feature/PROJ-4821")]);
+
+        assert_eq!(resolve_in(&state, name), name);
+    }
+
+    #[test]
+    fn add_overwrites_existing_alias() {
+        let mut state = state_with(&[("x", "old")]);
+
+        add_alias(&mut state, "x", "new").unwrap();
+
+        assert_eq!(resolve_in(&state, "x"), "new");
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("two words")]
+    #[case("tab\tname")]
+    fn rejects_invalid_alias_names(#[case] name: &str) {
+        let mut state = State::default();
+
+        assert!(add_alias(&mut state, name, "main").is_err());
+    }
+
+    #[test]
+    fn rejects_empty_target_branch() {
+        let mut state = State::default();
+
+        assert!(add_alias(&mut state, "x", " ").is_err());
+    }
+
+    #[test]
+    fn remove_returns_target_branch() {
+        let mut state = state_with(&[("x", "feature/x")]);
+
+        assert_eq!(remove_alias(&mut state, "x").unwrap(), "feature/x");
+        assert!(state.aliases.is_empty());
+    }
+
+    #[test]
+    fn removing_unknown_alias_fails_clearly() {
+        let mut state = State::default();
+
+        let err = remove_alias(&mut state, "nope").unwrap_err();
+
+        assert!(err.to_string().contains("no such alias: \"nope\""));
+    }
+}
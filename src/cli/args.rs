@@ -3,7 +3,7 @@
 //! This module defines the CLI structure using `clap` for argument parsing. It handles
 //! subcommands, flags, environment variable integration, and help text generation.
 
-use clap::{Parser, Subcommand};
+use clap::{Args as ClapArgs, Parser, Subcommand, ValueEnum};
 
 use super::prelude::*;
 
@@ -15,81 +15,1993 @@ use super::prelude::*;
 #[command(name = "amg")]
 #[command(about = "Manage and resume Codex sessions")]
 pub struct Args {
+    /// Also append log events (with timestamps) to this file.
+    #[arg(long, global = true, env = "AMG_LOG_FILE", value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// Append one JSON line describing this run (command, duration, files scanned, whether a
+    /// session matched, exit code) to this file on exit. Failing to write it only warns.
+    #[arg(long, global = true, env = "AMG_STATS_FILE", value_name = "PATH")]
+    pub stats_file: Option<PathBuf>,
+
+    /// Exit above N (default 100) when amg itself fails, instead of 1.
+    ///
+    /// When codex runs inline, amg exits with codex's own exit code. Without this flag an amg
+    /// failure (no matching session, missing directories, tmux errors, ...) also exits 1,
+    /// which a wrapper script cannot tell apart from codex exiting 1. With
+    /// `--exit-code-offset[=N]`, amg failures exit with N+1, or N+2 when no session matched,
+    /// so N is at most 253. Codex's code is still forwarded verbatim, so a codex run that
+    /// itself exits N+1 or N+2 looks like an amg failure: pick an N that codex does not use.
+    /// Usage errors keep clap's exit code 2; a cancelled prompt exits 130.
+    #[arg(
+        long,
+        global = true,
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = DEFAULT_EXIT_CODE_OFFSET,
+        value_parser = clap::value_parser!(u8).range(..=253),
+    )]
+    pub exit_code_offset: Option<u8>,
+
+    /// Skip the environment checks run before every subcommand (an unset `$PATH`, no
+    /// `$HOME` and no codexdir, closed stdin and stdout), for exotic setups.
+    #[arg(long, global = true)]
+    pub no_env_check: bool,
+
+    /// Use nothing under `$HOME` beyond what is passed in: no config files, last-hit cache,
+    /// session index, or state file (aliases, tags, launches, remembered options), and no home
+    /// directories granted to the sandbox. The codexdir must be given.
+    #[arg(
+        long,
+        global = true,
+        env = "AMG_PORTABLE",
+        value_parser = clap::builder::FalseyValueParser::new(),
+    )]
+    pub portable: bool,
+
     /// The subcommand to execute.
     #[command(subcommand)]
     pub command: Commands,
 }
 
-/// Available subcommands.
-///
-/// Each variant represents a different operation the CLI can perform.
-#[derive(Subcommand, Debug)]
-pub enum Commands {
-    /// Resume the first Codex session whose first JSONL line has `.payload.git.branch == branch`.
-    ///
-    /// Usage:
-    ///     amg resume BRANCH
-    ///
-    /// Required environment variables:
-    ///     CODEX_REPO=/path/to/repo
-    ///
-    /// Optional environment variables:
-    ///     CODEX_CODEXDIR=/path/to/.codex   (defaults to $HOME/.codex)
-    #[command(name = "resume")]
-    #[command(visible_alias = "rb")]
-    ResumeBranch {
-        /// Git branch to resume (matches `.payload.git.branch` in the first JSONL line).
-        branch: String,
+/// A usage example shown at the end of a subcommand's `--help`.
+///
+/// The args tests parse every example, so they cannot go stale.
+struct Example {
+    /// What the example does, shown as a comment above it.
+    about: &'static str,
+    /// Environment variables set for the example, as `(name, value)` pairs.
+    env: &'static [(&'static str, &'static str)],
+    /// The command line, starting with `amg`.
+    argv: &'static [&'static str],
+}
+
+/// The examples of `amg resume --help`.
+const RESUME_EXAMPLES: &[Example] = &[
+    Example {
+        about: "Resume the first session recorded on a branch",
+        env: &[],
+        argv: &["amg", "resume", "feature/a", "--repo", "~/src/app"],
+    },
+    Example {
+        about: "Print the codex command instead of running it",
+        env: &[],
+        argv: &[
+            "amg",
+            "resume",
+            "feature/a",
+            "--repo",
+            "~/src/app",
+            "--dry-run",
+        ],
+    },
+    Example {
+        about: "Run codex in this terminal, even inside tmux",
+        env: &[],
+        argv: &[
+            "amg",
+            "resume",
+            "feature/a",
+            "--repo",
+            "~/src/app",
+            "--tmux",
+            "never",
+        ],
+    },
+    Example {
+        about: "Look for the session in another Codex home",
+        env: &[],
+        argv: &[
+            "amg",
+            "resume",
+            "feature/a",
+            "--repo",
+            "~/src/app",
+            "--codexdir",
+            "/mnt/laptop/.codex",
+        ],
+    },
+    Example {
+        about: "The same, with the directories taken from the environment",
+        env: &[
+            ("CODEX_REPO", "~/src/app"),
+            ("CODEX_CODEXDIR", "/mnt/laptop/.codex"),
+        ],
+        argv: &["amg", "resume", "feature/a"],
+    },
+    Example {
+        about: "Only consider sessions of the last two days, recorded as me/feature-a too",
+        env: &[("CODEX_REPO", "~/src/app")],
+        argv: &[
+            "amg",
+            "resume",
+            "feature-a",
+            "--since",
+            "2d",
+            "--strip-prefix",
+            "me/",
+        ],
+    },
+    Example {
+        about: "Resume a session by id prefix, granting its own repo",
+        env: &[],
+        argv: &["amg", "resume", "--id", "0199a6b2", "--dry-run"],
+    },
+];
+
+/// The examples of `amg resume-all --help`.
+const RESUME_ALL_EXAMPLES: &[Example] = &[
+    Example {
+        about: "Open a window for each of three branches",
+        env: &[("CODEX_REPO", "~/src/app")],
+        argv: &[
+            "amg",
+            "resume-all",
+            "--branches",
+            "main,feature/a,feature/b",
+        ],
+    },
+    Example {
+        about: "Take the branches from a file, one per line",
+        env: &[("CODEX_REPO", "~/src/app")],
+        argv: &["amg", "resume-all", "--from-file", "branches.txt"],
+    },
+];
+
+/// The examples of `amg watch --help`.
+const WATCH_EXAMPLES: &[Example] = &[
+    Example {
+        about: "Resume the next session for a branch, then exit",
+        env: &[],
+        argv: &["amg", "watch", "feature/a", "--repo", ".", "--once"],
+    },
+    Example {
+        about: "Keep watching for two hours, with a desktop notification per session",
+        env: &[],
+        argv: &[
+            "amg",
+            "watch",
+            "feature/a",
+            "--repo",
+            ".",
+            "--interval",
+            "30s",
+            "--timeout",
+            "2h",
+            "--notify",
+        ],
+    },
+];
+
+/// The examples of `amg remembered --help`.
+const REMEMBERED_EXAMPLES: &[Example] = &[
+    Example {
+        about: "List the branches with remembered options",
+        env: &[],
+        argv: &["amg", "remembered"],
+    },
+    Example {
+        about: "Forget a branch's remembered options",
+        env: &[],
+        argv: &["amg", "remembered", "feature/a", "--clear"],
+    },
+];
+
+/// The examples of `amg which --help`.
+const WHICH_EXAMPLES: &[Example] = &[
+    Example {
+        about: "Print the session file for a branch",
+        env: &[],
+        argv: &["amg", "which", "feature/a"],
+    },
+    Example {
+        about: "Print how old the branch's newest session is, in seconds",
+        env: &[],
+        argv: &["amg", "which", "feature/a", "--age-only"],
+    },
+];
+
+/// The example of `amg prompt-hint --help`, a shell prompt rather than a command line.
+const PROMPT_HINT_EXAMPLE: &str = "Examples:
+  # Show the hint in a zsh prompt
+  PROMPT='$(amg prompt-hint 2>/dev/null) %~ %# '";
+
+/// Renders `examples` as an `Examples:` help section.
+fn examples_help(examples: &[Example]) -> String {
+    let mut help = String::from("Examples:");
+    for example in examples {
+        let env = example
+            .env
+            .iter()
+            .map(|(name, value)| format!("{name}={value} "))
+            .collect::<String>();
+        help.push_str(&format!(
+            "\n  # {}\n  {env}{}\n",
+            example.about,
+            example.argv.join(" ")
+        ));
+    }
+    help.trim_end().to_owned()
+}
+
+/// Available subcommands.
+///
+/// Each variant represents a different operation the CLI can perform.
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Resume the first Codex session whose first JSONL line has `.payload.git.branch == branch`.
+    ///
+    /// `--repo` may come from `CODEX_REPO` and `--codexdir` from `CODEX_CODEXDIR` (which
+    /// defaults to `$HOME/.codex`).
+    #[command(name = "resume")]
+    #[command(visible_alias = "rb")]
+    #[command(after_long_help = examples_help(RESUME_EXAMPLES))]
+    ResumeBranch(ResumeArgs),
+
+    /// Open one tmux window per branch, resuming each branch's session.
+    ///
+    /// All branches are matched in a single scan of the codex directory. Exits non-zero only
+    /// if no branch could be resumed.
+    #[command(after_long_help = examples_help(RESUME_ALL_EXAMPLES))]
+    ResumeAll(ResumeAllArgs),
+
+    /// Wait for a new session for a branch to appear in the codexdir, then resume it.
+    ///
+    /// Sessions already present when the watch starts are not resumed. Exits 124 when
+    /// `--timeout` passes first and 130 on Ctrl-C.
+    #[command(after_long_help = examples_help(WATCH_EXAMPLES))]
+    Watch(WatchArgs),
+
+    /// Close the tmux window (or terminate the inline process) amg opened for a session.
+    ///
+    /// Having nothing to kill is not an error.
+    Kill(KillArgs),
+
+    /// Show the options remembered from a branch's last successful resume, or forget them
+    /// with `--clear`.
+    ///
+    /// `resume BRANCH` reuses remembered options the command line and config leave unset;
+    /// `resume --fresh` ignores them.
+    #[command(after_long_help = examples_help(REMEMBERED_EXAMPLES))]
+    Remembered(RememberedArgs),
+
+    /// Print the session file that `resume` would use for a branch.
+    ///
+    /// Repeated lookups for the same branch are served from a last-hit cache after
+    /// re-validating the cached file (see `--no-cache`). `--age-only` prints how old the
+    /// branch's newest session is instead, for preflight checks.
+    #[command(after_long_help = examples_help(WHICH_EXAMPLES))]
+    Which(WhichArgs),
+
+    /// Print whether the current branch has a session, for a shell prompt: `yes:<age>`, `no`,
+    /// or `unknown`.
+    ///
+    /// Never walks the codexdir and never runs git: the branch comes from `.git/HEAD` and the
+    /// answer from the last-hit cache or the session index, so it takes a few milliseconds.
+    /// Always exits 0.
+    #[command(after_long_help = PROMPT_HINT_EXAMPLE)]
+    PromptHint(PromptHintArgs),
+
+    /// Print what amg parsed from the session `resume` would use for a branch.
+    ///
+    /// `--raw` also prints the session file's first line as read, for when the parsed fields
+    /// look wrong; `--raw-only` prints only that line.
+    Show(ShowArgs),
+
+    /// List the branches that have sessions, most recent session first.
+    ///
+    /// `--porcelain` prints only the names, one per line, for shell completion. Its format is
+    /// stable; its scan stops after 200 ms and then exits 3 with what it found.
+    Branches(BranchesArgs),
+
+    /// Find branches whose name contains a fragment (ignoring case), best match first.
+    ///
+    /// Matches that start a word (after `/`, `-`, `_`, or `.`), cover whole words, or start
+    /// the name rank higher; each branch is listed with its session count and the age of its
+    /// newest session. `--resume-top` resumes the best match, refusing on a tie.
+    Find(FindArgs),
+
+    /// Stream one NDJSON object per session file to stdout, for external tooling.
+    ///
+    /// Each line has `path`, `size`, `mtime` (RFC 3339), and the first-line fields that are
+    /// present (`id`, `cwd`, `branch`, `repository_url`, `originator`, `model`), or
+    /// `parse_error`.
+    Dump(DumpArgs),
+
+    /// Count session files, optionally per day over a recent window (`--trend 30d`).
+    ///
+    /// Days come from the rollout file name, or the modification time, in UTC.
+    Stats(StatsArgs),
+
+    /// Copy a session file received from elsewhere into the codexdir's dated
+    /// `sessions/YYYY/MM/DD/` layout, printing the new path and the session's branch.
+    Import(ImportArgs),
+
+    /// Move session files left flat by older Codex versions (`rollout-*.jsonl` at the top of
+    /// the codexdir, or directly under `sessions/`) into the dated `sessions/YYYY/MM/DD/`
+    /// layout, never replacing an existing file.
+    MigrateLayout(MigrateLayoutArgs),
+
+    /// Run a plan written by `amg resume --plan-out`, after checking that its session file is
+    /// unchanged and its directories still exist.
+    ExecPlan(ExecPlanArgs),
+
+    /// Manage branch aliases (friendly names resolved by `resume` before scanning).
+    Alias {
+        /// The alias operation to perform.
+        #[command(subcommand)]
+        command: AliasCommand,
+    },
+
+    /// Manage session tags (labels selectable with `--tag`).
+    Tag {
+        /// The tag operation to perform.
+        #[command(subcommand)]
+        command: TagCommand,
+    },
+
+    /// Read and edit the global config file, or a repository's `.amg.toml` with `--repo`.
+    ///
+    /// Edits keep comments and unknown keys, are checked like the file is when loaded, and
+    /// replace the file atomically.
+    Config {
+        /// The config operation to perform.
+        #[command(subcommand)]
+        command: ConfigCommand,
+
+        /// Edit `<REPO>/.amg.toml` instead of the global config file.
+        #[arg(long, global = true, value_name = "REPO")]
+        repo: Option<PathBuf>,
+    },
+
+    /// Write the global config file interactively, then run the `doctor` checks.
+    ///
+    /// Every question can be answered with a flag; `--yes` takes the defaults for the rest and
+    /// never prompts, for scripted installs.
+    Init(InitArgs),
+
+    /// Check the setup: the global config file, the codexdir, and `codex` and `tmux` on
+    /// `$PATH`. Exits 1 if a check fails.
+    Doctor(DoctorArgs),
+}
+
+/// Operations of the `config` subcommand.
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Set a key. List keys take any number of values; `wrap_command` takes one shell-style
+    /// string or several arguments.
+    Set {
+        /// The config key (e.g. `write_marker`).
+        key: String,
+        /// The value (`true`/`false` for boolean keys).
+        #[arg(allow_hyphen_values = true)]
+        values: Vec<String>,
+    },
+    /// Print a key's value (list values one per line).
+    Get {
+        /// The config key.
+        key: String,
+    },
+    /// Remove a key (a key that is not set is only a warning).
+    Unset {
+        /// The config key.
+        key: String,
+    },
+    /// List the keys set in the file as `key = value` lines.
+    List,
+}
+
+/// Operations of the `tag` subcommand.
+#[derive(Subcommand, Debug)]
+pub enum TagCommand {
+    /// Tag a session.
+    Add {
+        /// Session id to tag.
+        session_id: String,
+        /// Tag name (no whitespace).
+        tag: String,
+    },
+    /// List tags as `<session-id>\t<tag>` lines.
+    List {
+        /// Only list the tags of this session.
+        session_id: Option<String>,
+    },
+    /// Remove a tag from a session (a missing tag is only a warning).
+    Rm {
+        /// Session id to untag.
+        session_id: String,
+        /// Tag name to remove.
+        tag: String,
+    },
+}
+
+/// Operations of the `alias` subcommand.
+#[derive(Subcommand, Debug)]
+pub enum AliasCommand {
+    /// Add (or replace) an alias for a branch.
+    Add {
+        /// Friendly alias name (no whitespace).
+        name: String,
+        /// Git branch the alias stands for.
+        branch: String,
+    },
+    /// List all aliases as `<name>\t<branch>` lines.
+    List,
+    /// Remove an alias.
+    Rm {
+        /// Alias name to remove.
+        name: String,
+    },
+}
+
+/// Arguments for the `resume` subcommand.
+#[derive(ClapArgs, Debug)]
+pub struct ResumeArgs {
+    /// Git branch to resume (matches `.payload.git.branch` in the first JSONL line).
+    /// Branch aliases (see `amg alias`) are resolved first. Names starting with `-` are
+    /// accepted; `--` also ends option parsing (`amg resume --repo R -- -wip`).
+    #[arg(allow_hyphen_values = true, required_unless_present_any = ["any", "id", "file"])]
+    pub branch: Option<String>,
+
+    /// Resume the newest session in the codexdir, whatever its branch.
+    #[arg(long, conflicts_with_all = ["branch", "pick", "pick_tmux", "explain"])]
+    pub any: bool,
+
+    /// Resume the session whose id starts with this prefix (at least 4 characters, like an
+    /// abbreviated git hash), whatever its branch. Fails if the prefix is ambiguous.
+    #[arg(
+        long,
+        value_name = "PREFIX",
+        conflicts_with_all = ["branch", "any", "pick", "pick_tmux", "explain", "relax_on_empty"]
+    )]
+    pub id: Option<String>,
+
+    /// Resume this session file directly (a path or a `file://` URL, as found with `rg` or
+    /// `amg dump`), skipping the scan. Its first line must have an id and a cwd.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["branch", "any", "id", "pick", "pick_tmux", "explain", "relax_on_empty"]
+    )]
+    pub file: Option<String>,
+
+    /// With `--file`, resume a file outside the codexdir (codex may not find it).
+    // clap skips `requires` when the required arg conflicts with a present one, so the
+    // conflicts of `--file` are repeated here.
+    #[arg(long, requires = "file", conflicts_with_all = ["branch", "any", "id"])]
+    pub force: bool,
+
+    /// Match the first lines of session files piped on stdin instead of scanning the
+    /// codexdir, for sessions on another machine. The first matching line wins, in input
+    /// order. Codex starts in the session's recorded cwd without `--add-dir` grants and only
+    /// runs (inline) through `--wrap` or `wrap_command`, such as `'ssh -t host'`; otherwise
+    /// pass `--dry-run`.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "any", "id", "file", "pick", "pick_tmux", "explain", "relax_on_empty",
+            "repo_from_session", "codexdir_readonly", "chdir", "cd_into_repo", "plan_out",
+            "result_json",
+            "tmux", "fallback_resume", "tmux_detach", "tmux_annotate", "notify",
+            "verify_commit", "require_branch_exists", "redact_home", "dangerously_full_access",
+            "write_marker", "backup", "print_id",
+        ]
+    )]
+    pub stdin_jsonl: bool,
+
+    /// With `--stdin-jsonl`, where the piped sessions live, shown before their cwd (such as
+    /// `host:` for `host:/home/dev/app`).
+    #[arg(long, value_name = "PREFIX", requires = "stdin_jsonl")]
+    pub path_prefix: Option<String>,
+
+    /// Repo to grant Codex sandbox access to. With `--any`, `--id`, or `--file`, defaults to
+    /// the git root of the session's cwd.
+    #[arg(
+        short,
+        long,
+        env = "CODEX_REPO",
+        required_unless_present_any = ["any", "id", "file", "stdin_jsonl", "print_id"]
+    )]
+    pub repo: Option<PathBuf>,
+
+    /// Codexdir location options.
+    #[command(flatten)]
+    pub location: LocationArgs,
+
+    /// Codexdir overlap options.
+    #[command(flatten)]
+    pub overlap: OverlapArgs,
+
+    /// Session selection options.
+    #[command(flatten)]
+    pub selection: SelectionArgs,
+
+    /// If the filtered scan finds nothing, retry without `--since`, then also without
+    /// `--under`, logging which relaxation produced the match. The branch must always match.
+    #[arg(long, conflicts_with_all = ["pick", "pick_tmux", "explain", "any"])]
+    pub relax_on_empty: bool,
+
+    /// Last-hit cache options.
+    #[command(flatten)]
+    pub cache: CacheArgs,
+
+    /// Ignore the options remembered from the branch's last successful resume (see `amg
+    /// remembered`); this run's options are remembered in their place.
+    #[arg(long)]
+    pub fresh: bool,
+
+    /// Choose interactively among all sessions matching the branch (with fzf when it is on
+    /// `$PATH`) instead of resuming the first one.
+    #[arg(long)]
+    pub pick: bool,
+
+    /// Like `--pick`, but inside tmux offer the sessions (at most 10) in a `tmux display-menu`;
+    /// choosing one opens it in a new window. With `--dry-run`, prints the `display-menu`
+    /// command. Outside tmux, the plain picker is used. amg exits once the menu is shown, so
+    /// options acting on the launch itself are not available.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "explain", "codexdir_readonly", "dangerously_full_access", "backup", "write_marker",
+            "result_json", "timeout", "fallback_resume",
+        ]
+    )]
+    pub pick_tmux: bool,
+
+    /// With `--pick`, use the plain numbered prompt even if fzf is available.
+    #[arg(long, requires = "pick")]
+    pub no_fzf: bool,
+
+    /// Print (to stderr) every file the scan considered up to the chosen session, and why each
+    /// was skipped or chosen. Implies a full scan.
+    #[arg(long, conflicts_with_all = ["pick", "pick_tmux"])]
+    pub explain: bool,
+
+    /// With `--explain`, print the explanation as JSON.
+    #[arg(long, requires = "explain")]
+    pub json: bool,
+
+    /// With `--explain`, list at most this many skipped files (the rest are counted).
+    #[arg(long, value_name = "N", default_value_t = super::explain::DEFAULT_LIMIT, requires = "explain")]
+    pub explain_limit: usize,
+
+    /// Codex launch options.
+    #[command(flatten)]
+    pub codex: CodexArgs,
+
+    /// Execution options.
+    #[command(flatten)]
+    pub execution: ExecutionArgs,
+
+    /// Sandbox directory options.
+    #[command(flatten)]
+    pub sandbox: SandboxArgs,
+
+    /// Session file backup options.
+    #[command(flatten)]
+    pub backup: BackupArgs,
+}
+
+/// When `resume` opens a tmux window for codex (`--tmux`).
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TmuxMode {
+    /// Open a window when running inside tmux (`$TMUX` is set), else run inline.
+    Auto,
+    /// Always open a window, from outside tmux too.
+    Always,
+    /// Never open a window; run codex inline.
+    Never,
+}
+
+/// Options controlling how `resume` launches codex on the matched session, and the checks
+/// before it.
+#[derive(ClapArgs, Debug, Clone, Default)]
+pub struct CodexArgs {
+    /// Grant the matched session's git repository root instead of `--repo` when they differ.
+    #[arg(long)]
+    pub repo_from_session: bool,
+
+    /// Treat the codexdir as read-only: resume a copy of the session staged under
+    /// `$XDG_STATE_HOME/amg/codex-home` (via `CODEX_HOME`) and grant that instead.
+    #[arg(long)]
+    pub codexdir_readonly: bool,
+
+    /// Start codex in this directory instead of the session cwd.
+    /// (The session cwd is still granted to the sandbox via `--add-dir`.)
+    #[arg(long, value_name = "DIR")]
+    pub chdir: Option<PathBuf>,
+
+    /// Start codex in the granted repo (`--repo`, or the session's own repo with `--any`,
+    /// `--id`, or `--file`) instead of the session cwd, which stays granted via `--add-dir`.
+    #[arg(long, conflicts_with = "chdir")]
+    pub cd_into_repo: bool,
+
+    /// Check that the session's recorded commit is an ancestor of `HEAD` in `--repo`,
+    /// warning when it isn't (e.g. a session recorded during a rebase).
+    #[arg(long)]
+    pub verify_commit: bool,
+
+    /// Fail instead of warning when the matched session's branch no longer exists in the
+    /// repo (`refs/heads/<branch>`, for example after it was merged and deleted).
+    #[arg(long)]
+    pub require_branch_exists: bool,
+
+    /// Treat verification warnings (such as `--verify-commit` failures or unreadable `.git`
+    /// entries) as errors.
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Codex wrapper options.
+    #[command(flatten)]
+    pub wrapper: WrapArgs,
+
+    /// Run codex with `-s danger-full-access` and approvals disabled (`-a never`).
+    /// Only for throwaway machines; refused when a config sets `forbid_full_access = true`.
+    #[arg(long)]
+    pub dangerously_full_access: bool,
+
+    /// Confirm `--dangerously-full-access` (required when stdin is a terminal).
+    #[arg(long, short = 'y', requires = "dangerously_full_access")]
+    pub yes: bool,
+
+    /// Before launching, write a `.amg-session` JSON marker (session id, branch, session
+    /// file, time) into the session cwd for editor tooling. Overrides `write_marker`.
+    #[arg(long, conflicts_with = "no_marker")]
+    pub write_marker: bool,
+
+    /// Do not write the `.amg-session` marker, even if a config sets `write_marker = true`.
+    #[arg(long)]
+    pub no_marker: bool,
+}
+
+/// Options controlling what `resume` does with the codex command: print it, write a plan,
+/// or run it inline or in a tmux window.
+#[derive(ClapArgs, Debug, Clone, Default)]
+pub struct ExecutionArgs {
+    /// Print the exact command that would be executed and exit without running.
+    /// (When codex would run in a tmux window, this prints the `tmux new-window ...` command.)
+    #[arg(long, short = 'n')]
+    pub dry_run: bool,
+
+    /// Print only the matched session's id and exit, for wrappers that start codex
+    /// themselves. Every selection option applies, so the id is the one a full resume would
+    /// use, but no repo is needed and nothing is built, run, or recorded. Exits 2 if no
+    /// session matches.
+    #[arg(
+        long,
+        conflicts_with_all = ["dry_run", "plan_out", "pick_tmux", "copy", "result_json"]
+    )]
+    pub print_id: bool,
+
+    /// With `--dry-run`, write the command to this file (created with mode 0600) instead of
+    /// stdout.
+    #[arg(long, value_name = "PATH", requires = "dry_run")]
+    pub output: Option<PathBuf>,
+
+    /// Write the resolved plan (session, start directory, program and arguments, and whether
+    /// codex runs inline or in tmux) to this JSON file instead of acting; run it later with
+    /// `amg exec-plan`.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "dry_run", "pick_tmux", "explain", "codexdir_readonly", "result_json", "timeout",
+            "fallback_resume",
+        ]
+    )]
+    pub plan_out: Option<PathBuf>,
+
+    /// With `--dry-run`, break a command longer than the terminal (`$COLUMNS`) into one
+    /// option per line with trailing backslashes, still valid to paste into a shell.
+    #[arg(long, requires = "dry_run")]
+    pub pretty: bool,
+
+    /// Also copy the command to the clipboard (`pbcopy`, `wl-copy`, or `xclip`). Without
+    /// `--dry-run`, copies the command that is about to be executed.
+    #[arg(long)]
+    pub copy: bool,
+
+    /// Replace the home directory with `~` in the dry-run command, the `--result-json` cwd,
+    /// and the `--explain --json` paths, as logs always do. (The spawned process still
+    /// receives absolute paths.)
+    #[arg(long)]
+    pub redact_home: bool,
+
+    /// After the action completes, print one JSON line with the action, session id, branch,
+    /// cwd, tmux window id, and codex's exit code. A dry run printing to stdout writes it to
+    /// file descriptor 3 instead.
+    #[arg(long)]
+    pub result_json: bool,
+
+    /// When to open a tmux window for codex: `auto` (when `$TMUX` is set and stdin or stderr
+    /// is a terminal), `always` (from outside tmux or a script too, in a detached session if
+    /// no tmux server is running), or `never` (run inline). Overrides the config's `tmux` key.
+    #[arg(long, value_enum, value_name = "WHEN")]
+    pub tmux: Option<TmuxMode>,
+
+    /// Same as `--tmux never`.
+    #[arg(long, hide = true, conflicts_with = "tmux")]
+    pub no_tmux: bool,
+
+    /// When codex runs inline and fails because it does not know the session id (its stderr
+    /// matches `fallback_resume_pattern`), retry once with `codex resume --last`. Captures
+    /// codex's stderr while still showing it, so it is only done with this flag.
+    #[arg(long)]
+    pub fallback_resume: bool,
+
+    /// When codex runs inline, stop it after this long (`90s`, `15m`, `2h`): `SIGTERM`, then
+    /// `SIGKILL` if it has not exited 5 seconds later. amg then exits with 124.
+    #[arg(long, value_name = "DURATION", value_parser = super::util::parse_duration)]
+    pub timeout: Option<Duration>,
+
+    /// Open the tmux window in the background instead of switching to it, and confirm the
+    /// launch with `tmux display-message`.
+    #[arg(long, conflicts_with = "no_tmux")]
+    pub tmux_detach: bool,
+
+    /// Set the session id, branch, and cwd on the new tmux window as the user options
+    /// `@amg_session_id`, `@amg_branch`, and `@amg_cwd`, for status lines. The default is the
+    /// `tmux_annotate` config key, or on when amg runs inside tmux.
+    #[arg(long, conflicts_with = "no_tmux_annotate")]
+    pub tmux_annotate: bool,
+
+    /// Do not set session data on the new tmux window (see `--tmux-annotate`).
+    #[arg(long)]
+    pub no_tmux_annotate: bool,
+
+    /// After opening a tmux window, also show a desktop notification (`notify-send` or
+    /// `osascript`).
+    #[arg(long)]
+    pub notify: bool,
+}
+
+/// Arguments for the `resume-all` subcommand.
+#[derive(ClapArgs, Debug)]
+pub struct ResumeAllArgs {
+    /// Comma-separated branches to resume (aliases are resolved first).
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "BRANCHES",
+        required_unless_present = "from_file"
+    )]
+    pub branches: Vec<String>,
+
+    /// Read branches from a file, one per line (blank lines and `#` comments are ignored).
+    #[arg(long, value_name = "FILE")]
+    pub from_file: Option<PathBuf>,
+
+    /// Repo to grant Codex sandbox access to.
+    #[arg(short, long, env = "CODEX_REPO")]
+    pub repo: PathBuf,
+
+    /// Codexdir location options.
+    #[command(flatten)]
+    pub location: LocationArgs,
+
+    /// Codexdir overlap options.
+    #[command(flatten)]
+    pub overlap: OverlapArgs,
+
+    /// Print the `tmux new-window ...` commands that would be executed without running them.
+    #[arg(long, short = 'n')]
+    pub dry_run: bool,
+
+    /// After opening each tmux window, also show a desktop notification (`notify-send` or
+    /// `osascript`).
+    #[arg(long)]
+    pub notify: bool,
+
+    /// When a window with a branch's name already exists in the tmux session, give the new
+    /// one the first free numbered name (`main-2`, `main-3`, ...) instead of the same name.
+    #[arg(long)]
+    pub force_new_window: bool,
+
+    /// Codex wrapper options.
+    #[command(flatten)]
+    pub wrapper: WrapArgs,
+
+    /// Session selection options.
+    #[command(flatten)]
+    pub selection: SelectionArgs,
+
+    /// Sandbox directory options.
+    #[command(flatten)]
+    pub sandbox: SandboxArgs,
+}
+
+/// Arguments for the `watch` subcommand.
+#[derive(ClapArgs, Debug)]
+pub struct WatchArgs {
+    /// Git branch to wait for (aliases are resolved first; may start with `-`).
+    #[arg(allow_hyphen_values = true)]
+    pub branch: String,
+
+    /// Repo to grant Codex sandbox access to.
+    #[arg(short, long, env = "CODEX_REPO")]
+    pub repo: PathBuf,
+
+    /// Codexdir location options.
+    #[command(flatten)]
+    pub location: LocationArgs,
+
+    /// Codexdir overlap options.
+    #[command(flatten)]
+    pub overlap: OverlapArgs,
+
+    /// How long to wait between polls of the codexdir (e.g. `5s`, `2m`).
+    #[arg(long, value_name = "DURATION", default_value = "5s", value_parser = super::util::parse_duration)]
+    pub interval: Duration,
+
+    /// Give up after this long (exit code 124 if no session was resumed by then).
+    #[arg(long, value_name = "DURATION", value_parser = super::util::parse_duration)]
+    pub timeout: Option<Duration>,
+
+    /// Exit after resuming the first new session (with its exit code) instead of watching
+    /// for newer ones and reporting them.
+    #[arg(long)]
+    pub once: bool,
+
+    /// Print the command that would resume the session instead of running it.
+    #[arg(long, short = 'n')]
+    pub dry_run: bool,
+
+    /// When to open a tmux window for codex (see `amg resume --tmux`).
+    #[arg(long, value_enum, value_name = "WHEN")]
+    pub tmux: Option<TmuxMode>,
+
+    /// Open the tmux window in the background instead of switching to it.
+    #[arg(long)]
+    pub tmux_detach: bool,
+
+    /// Also show a desktop notification (`notify-send` or `osascript`) when a tmux window
+    /// opens and whenever a newer session appears afterwards.
+    #[arg(long)]
+    pub notify: bool,
+
+    /// Codex wrapper options.
+    #[command(flatten)]
+    pub wrapper: WrapArgs,
+
+    /// Session selection options.
+    #[command(flatten)]
+    pub selection: SelectionArgs,
+
+    /// Sandbox directory options.
+    #[command(flatten)]
+    pub sandbox: SandboxArgs,
+}
+
+/// Arguments for the `remembered` subcommand.
+#[derive(ClapArgs, Debug)]
+pub struct RememberedArgs {
+    /// Branch whose remembered options to show (aliases are resolved first); lists every
+    /// branch when omitted.
+    #[arg(allow_hyphen_values = true)]
+    pub branch: Option<String>,
+
+    /// Forget the branch's remembered options.
+    #[arg(long, requires = "branch")]
+    pub clear: bool,
+}
+
+/// Arguments for the `kill` subcommand.
+#[derive(ClapArgs, Debug)]
+pub struct KillArgs {
+    /// Branch whose launched sessions to kill (aliases are resolved first; may start with `-`).
+    #[arg(required_unless_present = "id", allow_hyphen_values = true)]
+    pub branch: Option<String>,
+
+    /// Kill the launch for this session id instead of selecting by branch.
+    #[arg(long, value_name = "ID", conflicts_with = "branch")]
+    pub id: Option<String>,
+}
+
+/// Arguments for the `which` subcommand.
+#[derive(ClapArgs, Debug)]
+pub struct WhichArgs {
+    /// Git branch to look up (aliases are resolved first; may start with `-`).
+    #[arg(allow_hyphen_values = true)]
+    pub branch: String,
+
+    /// Codexdir location options.
+    #[command(flatten)]
+    pub location: LocationArgs,
+
+    /// Session selection options.
+    #[command(flatten)]
+    pub selection: SelectionArgs,
+
+    /// Last-hit cache options.
+    #[command(flatten)]
+    pub cache: CacheArgs,
+
+    /// Print only the age of the branch's newest session, in whole seconds since its file
+    /// was last written, or `none` (exit code 2). The codexdir is walked newest first and
+    /// the walk stops at the first match; the last-hit cache is not used.
+    #[arg(long)]
+    pub age_only: bool,
+
+    /// Match the first lines of session files piped on stdin instead of scanning the
+    /// codexdir, taking the first match in input order, and print `<cwd>\t<id>`.
+    #[arg(long, conflicts_with_all = ["no_cache", "age_only"])]
+    pub stdin_jsonl: bool,
+
+    /// With `--stdin-jsonl`, print this before the cwd (such as `host:`).
+    #[arg(long, value_name = "PREFIX", requires = "stdin_jsonl")]
+    pub path_prefix: Option<String>,
+}
+
+/// Arguments for the `prompt-hint` subcommand.
+#[derive(ClapArgs, Debug)]
+pub struct PromptHintArgs {
+    /// Codexdir location options.
+    #[command(flatten)]
+    pub location: LocationArgs,
+
+    /// Directory whose work tree's branch is looked up.
+    #[arg(long, default_value = ".")]
+    pub dir: PathBuf,
+}
+
+/// Arguments for the `show` subcommand.
+#[derive(ClapArgs, Debug)]
+pub struct ShowArgs {
+    /// Git branch to look up (aliases are resolved first; may start with `-`).
+    #[arg(allow_hyphen_values = true)]
+    pub branch: String,
+
+    /// Codexdir location options.
+    #[command(flatten)]
+    pub location: LocationArgs,
+
+    /// Session selection options.
+    #[command(flatten)]
+    pub selection: SelectionArgs,
+
+    /// Last-hit cache options.
+    #[command(flatten)]
+    pub cache: CacheArgs,
+
+    /// Also print the first line of the session file as read.
+    #[arg(long)]
+    pub raw: bool,
+
+    /// Print only the first line of the session file as read.
+    #[arg(long, conflicts_with_all = ["raw", "json"])]
+    pub raw_only: bool,
+
+    /// Print the summary as one JSON object (with `--raw`, the line is in `raw`).
+    #[arg(long)]
+    pub json: bool,
+
+    /// Print only the directory containing the session file, for scripting.
+    #[arg(long, conflicts_with_all = ["raw", "raw_only", "json"])]
+    pub print_session_dir: bool,
+
+    /// Open the directory containing the session file in the file manager (`open` on
+    /// macOS, `xdg-open` elsewhere) instead of printing the summary. Without an opener, the
+    /// directory is printed instead.
+    #[arg(long, conflicts_with_all = ["raw", "raw_only", "json", "print_session_dir"])]
+    pub open_dir: bool,
+}
+
+/// Arguments for the `branches` subcommand.
+#[derive(ClapArgs, Debug)]
+pub struct BranchesArgs {
+    /// Codexdir location options.
+    #[command(flatten)]
+    pub location: LocationArgs,
+
+    /// Print only branch names, one per line (stable format; exits 3 if the 200 ms scan
+    /// budget ran out first).
+    #[arg(long)]
+    pub porcelain: bool,
+
+    /// Answer from the session index instead of scanning, building the index first if there
+    /// is none yet.
+    #[arg(long)]
+    pub cached: bool,
+
+    /// With `--cached`, rebuild the index first when it is older than this (e.g. `1h`).
+    #[arg(long, value_name = "DURATION", requires = "cached", value_parser = super::util::parse_duration)]
+    pub max_age: Option<Duration>,
+
+    /// Stop after listing this many branches (default: no limit). If more have sessions,
+    /// amg says so on stderr and exits with 3, as when the porcelain budget runs out.
+    #[arg(long, value_name = "N")]
+    pub max_matches: Option<usize>,
+}
+
+/// Arguments for the `dump` subcommand.
+#[derive(ClapArgs, Debug)]
+pub struct DumpArgs {
+    /// Codexdir location options.
+    #[command(flatten)]
+    pub location: LocationArgs,
+
+    /// Only dump sessions whose first line has this `.payload.git.branch`.
+    #[arg(long)]
+    pub branch: Option<String>,
+
+    /// Only dump session files modified within this long ago (e.g. `90m`, `36h`, `7d`).
+    #[arg(long, value_name = "DURATION", value_parser = super::util::parse_duration)]
+    pub since: Option<Duration>,
+
+    /// Stop after dumping this many session files (default: no limit). If more matched,
+    /// amg says so on stderr and exits with 3.
+    #[arg(long, value_name = "N")]
+    pub max_matches: Option<usize>,
+
+    /// Codexdir walk options.
+    #[command(flatten)]
+    pub walk: WalkArgs,
+}
+
+/// Arguments for the `find` subcommand.
+#[derive(ClapArgs, Debug)]
+pub struct FindArgs {
+    /// Part of the branch name to look for, matched case-insensitively (may start with `-`).
+    #[arg(allow_hyphen_values = true)]
+    pub fragment: String,
+
+    /// Codexdir location options.
+    #[command(flatten)]
+    pub location: LocationArgs,
+
+    /// Codexdir walk options.
+    #[command(flatten)]
+    pub walk: WalkArgs,
+
+    /// Resume the newest session of the best-matching branch instead of listing the matches.
+    /// Refuses, and lists the tied branches, when several match equally well.
+    #[arg(long)]
+    pub resume_top: bool,
+
+    /// Repo to grant Codex sandbox access to when resuming with `--resume-top`.
+    #[arg(short, long, env = "CODEX_REPO")]
+    pub repo: Option<PathBuf>,
+
+    /// With `--resume-top`, print the command that would resume the session instead of
+    /// running it.
+    #[arg(long, short = 'n', requires = "resume_top")]
+    pub dry_run: bool,
+
+    /// With `--resume-top`, when to open a tmux window for codex (see `amg resume --tmux`).
+    #[arg(long, value_enum, value_name = "WHEN", requires = "resume_top")]
+    pub tmux: Option<TmuxMode>,
+}
+
+/// Arguments for the `stats` subcommand.
+#[derive(ClapArgs, Debug)]
+pub struct StatsArgs {
+    /// Codexdir location options.
+    #[command(flatten)]
+    pub location: LocationArgs,
+
+    /// Only count sessions whose first line has this `.payload.git.branch`.
+    #[arg(long)]
+    pub branch: Option<String>,
+
+    /// Also print a per-day session count with a bar for each day in this window (e.g.
+    /// `30d`), ending today (UTC).
+    #[arg(long, value_name = "DURATION", value_parser = super::util::parse_duration)]
+    pub trend: Option<Duration>,
+
+    /// Print the counts (and the raw per-day counts with `--trend`) as one JSON object.
+    #[arg(long)]
+    pub json: bool,
+
+    /// Codexdir walk options.
+    #[command(flatten)]
+    pub walk: WalkArgs,
+}
+
+/// Arguments for the `import` subcommand.
+#[derive(ClapArgs, Debug)]
+pub struct ImportArgs {
+    /// The session JSONL file to import.
+    pub file: PathBuf,
+
+    /// Codex directory to import into (defaults to `$HOME/.codex`).
+    #[arg(long, env = "CODEX_CODEXDIR")]
+    pub codexdir: Option<PathBuf>,
+
+    /// Record this directory as the session cwd instead of the sender's.
+    #[arg(long, value_name = "PATH")]
+    pub rewrite_cwd: Option<PathBuf>,
+
+    /// Replace an existing session with the same id.
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// Arguments for the `migrate-layout` subcommand.
+#[derive(ClapArgs, Debug)]
+pub struct MigrateLayoutArgs {
+    /// Codex directory to migrate (defaults to `$HOME/.codex`).
+    #[arg(long, env = "CODEX_CODEXDIR")]
+    pub codexdir: Option<PathBuf>,
+
+    /// List every planned move without moving anything.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Arguments for the `exec-plan` subcommand.
+#[derive(ClapArgs, Debug)]
+pub struct ExecPlanArgs {
+    /// The plan file written by `amg resume --plan-out`.
+    pub plan: PathBuf,
+}
+
+/// Arguments for the `doctor` subcommand.
+#[derive(ClapArgs, Debug)]
+pub struct DoctorArgs {
+    /// Also check whether `--narrow-codexdir` would be enough: fails if codex recently wrote
+    /// to the codexdir outside `sessions/` and `log/`.
+    #[arg(long)]
+    pub narrow_codexdir: bool,
+}
+
+/// Arguments for the `init` subcommand.
+#[derive(ClapArgs, Debug)]
+pub struct InitArgs {
+    /// Do not prompt: use the given flags, the current config, or the defaults, and overwrite
+    /// an existing config file.
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// Default Codex directory (offered from `CODEX_CODEXDIR`, `CODEX_HOME`, and
+    /// `$HOME/.codex`).
+    #[arg(long)]
+    pub codexdir: Option<PathBuf>,
+
+    /// Whether `resume` opens a tmux window when run inside tmux.
+    #[arg(long, value_name = "BOOL", action = clap::ArgAction::Set)]
+    pub tmux: Option<bool>,
+
+    /// Model codex is started with.
+    #[arg(long)]
+    pub model: Option<String>,
+
+    /// Whether codex's workspace-write sandbox allows network access.
+    #[arg(long, value_name = "BOOL", action = clap::ArgAction::Set)]
+    pub network_access: Option<bool>,
+}
+
+/// The codexdir a subcommand reads sessions from.
+#[derive(ClapArgs, Debug, Clone, Default)]
+pub struct LocationArgs {
+    /// Codex directory containing JSONL sessions (defaults to `$HOME/.codex`).
+    #[arg(long, env = "CODEX_CODEXDIR")]
+    pub codexdir: Option<PathBuf>,
+}
+
+/// Options controlling whether the codexdir is walked when it does not look like one.
+#[derive(ClapArgs, Debug, Clone, Default)]
+pub struct WalkArgs {
+    /// Walk the codexdir even if a quick look finds nothing Codex-like in it (no `sessions/`
+    /// and no `rollout-*.jsonl`), for example a deliberately unusual layout.
+    #[arg(long)]
+    pub force_scan: bool,
+}
+
+/// Options controlling whether the codexdir is scanned when it overlaps the repo.
+#[derive(ClapArgs, Debug, Clone, Default)]
+pub struct OverlapArgs {
+    /// Scan the codexdir even when it is the repo or contains it (which walks the whole repo).
+    #[arg(long)]
+    pub allow_overlap: bool,
+}
+
+/// Options controlling the last-hit cache of branch lookups.
+#[derive(ClapArgs, Debug, Clone, Default)]
+pub struct CacheArgs {
+    /// Always do a full scan; do not read or update the last-hit cache.
+    #[arg(long)]
+    pub no_cache: bool,
+}
+
+/// Options controlling the command codex is run through.
+#[derive(ClapArgs, Debug, Clone, Default)]
+pub struct WrapArgs {
+    /// Run codex through a wrapper such as `'docker exec -it devbox'`, split like a shell
+    /// command line. Overrides `wrap_command` from the config.
+    #[arg(long, value_name = "COMMAND")]
+    pub wrap: Option<String>,
+}
+
+/// Options narrowing which session files are considered.
+#[derive(ClapArgs, Debug, Clone, Default)]
+pub struct SelectionArgs {
+    /// Accept session files with any `.jsonl` name, not only Codex's `rollout-*.jsonl`.
+    #[arg(long)]
+    pub any_filename: bool,
+
+    /// Also match sessions whose recorded branch is the requested one behind this prefix
+    /// (with `me/`, a session on `me/feature-x` matches `feature-x`); an exact match is
+    /// preferred. Repeatable, and added to the config's `strip_prefixes`.
+    #[arg(long, value_name = "PREFIX")]
+    pub strip_prefix: Vec<String>,
+
+    /// Skip files and directories matching this gitignore-like glob during the walk (a name
+    /// at any depth, or a path relative to the codexdir when it contains `/`). Repeatable,
+    /// and combined with the codexdir's `.amgignore`.
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Translate recorded cwds starting with OLD to start with NEW instead (`C:\Users\dev=/home/me`
+    /// for sessions synced from Windows). Repeatable; the first matching mapping wins.
+    /// Sessions whose cwd stays a path of another platform are skipped.
+    #[arg(long, value_name = "OLD=NEW", value_parser = super::util::parse_path_mapping)]
+    pub map_path: Vec<super::util::PathMapping>,
+
+    /// Look for other files recording the chosen session's id (conflicted copies left by sync
+    /// tools) in the whole codexdir, not only in the chosen file's directory. The newest copy
+    /// is resumed either way.
+    #[arg(long)]
+    pub dedup_ids: bool,
+
+    /// Only match sessions whose cwd is under this directory (symlinks are resolved).
+    #[arg(long, value_name = "DIR")]
+    pub under: Option<PathBuf>,
+
+    /// Only consider session files modified within this long ago (e.g. `90m`, `36h`, `7d`).
+    #[arg(long, value_name = "DURATION", value_parser = super::util::parse_duration)]
+    pub since: Option<Duration>,
+
+    /// Only match sessions tagged with this tag (see `amg tag`).
+    #[arg(long, value_name = "TAG")]
+    pub tag: Option<String>,
+
+    /// Only match sessions created by this originator (`.payload.originator`, e.g.
+    /// `codex_cli_rs`), to skip sessions a CI bot created on the same branch. Sessions that
+    /// do not record one are kept unless `--require-metadata` is set.
+    #[arg(long, value_name = "NAME")]
+    pub originator: Option<String>,
+
+    /// Only match sessions created with this model (`.payload.model`). Sessions that do not
+    /// record one are kept unless `--require-metadata` is set.
+    #[arg(long, value_name = "NAME")]
+    pub model_was: Option<String>,
+
+    /// With `--originator` or `--model-was`, also skip sessions that do not record the
+    /// filtered field.
+    #[arg(long)]
+    pub require_metadata: bool,
+
+    /// Codexdir walk options.
+    #[command(flatten)]
+    pub walk: WalkArgs,
+
+    /// Also consider session files owned by other users (by default only files owned by the
+    /// current user are matched).
+    #[arg(long)]
+    pub any_owner: bool,
+
+    /// Accept sessions whose recorded cwd is relative or whose cwd or id contains control
+    /// characters (such sessions are skipped with a warning by default).
+    #[arg(long)]
+    pub allow_weird_paths: bool,
+
+    /// Treat every `.jsonl` entry in the codexdir as a regular file without checking whether
+    /// it is a symlink. Only for stores known to be symlink-free; saves a `stat` per session
+    /// file on network mounts whose directory listings carry no entry types.
+    #[arg(long)]
+    pub no_symlink_check: bool,
+}
+
+/// Options controlling which optional directories are granted to the Codex sandbox.
+#[derive(ClapArgs, Debug, Clone, Default)]
+pub struct SandboxArgs {
+    /// Do not grant home-directory caches (`~/.cargo`, `~/.npm`, ...) nor the extra system
+    /// directories (`/tmp`, `/var/folders`) to the sandbox, like `home_sandbox_dirs = []` in
+    /// the global config.
+    #[arg(long)]
+    pub no_home_dirs: bool,
+
+    /// Do not grant the extra system directories (`/tmp`, `/var/folders`) to the sandbox.
+    #[arg(long)]
+    pub no_extra_dirs: bool,
+
+    /// Grant the repo, codexdir, and session cwd as given instead of resolving symlinks first
+    /// (for sandboxes that key off the symlink path).
+    #[arg(long)]
+    pub no_canonicalize: bool,
+
+    /// Grant `--repo` literally: when it has no `.git` entry, do not look for the git worktree
+    /// it is a subdirectory of.
+    #[arg(long)]
+    pub no_repo_discovery: bool,
+
+    /// Drop `--add-dir` grants that another grant already covers (a directory nested in a
+    /// broader one, or a duplicate) instead of only warning about them.
+    #[arg(long)]
+    pub minimize_dirs: bool,
+
+    /// Instead of the whole codexdir, grant only the directory holding the session file and
+    /// the codexdir's `log/` (if it exists). Run `amg doctor --narrow-codexdir` first to see
+    /// whether codex recently wrote anywhere else.
+    #[arg(long)]
+    pub narrow_codexdir: bool,
+
+    /// Also grant every other worktree of the repo's repository (its main checkout and the
+    /// linked worktrees `git worktree list` shows), read from the git directory without
+    /// running git. Worktrees whose directory is gone are skipped.
+    #[arg(long)]
+    pub add_worktrees: bool,
+}
+
+/// Options controlling the backup of the matched session file before resuming.
+#[derive(ClapArgs, Debug, Clone)]
+pub struct BackupArgs {
+    /// Copy the matched session file to a timestamped sibling (`<file>.<timestamp>.bak`)
+    /// before launching codex.
+    #[arg(long)]
+    pub backup: bool,
+
+    /// Number of backups to keep per session file; older ones are pruned.
+    #[arg(long, value_name = "N", default_value_t = 3, value_parser = clap::value_parser!(u16).range(1..))]
+    pub backup_keep: u16,
+
+    /// Continue resuming (with a warning) if the backup fails instead of aborting.
+    #[arg(long)]
+    pub backup_best_effort: bool,
+}
+
+/// Parses command-line arguments.
+///
+/// Uses `clap` to parse arguments from the command line, environment variables, and defaults.
+///
+/// # Returns
+///
+/// Returns an [`Args`] structure containing the parsed arguments.
+///
+/// # Panics
+///
+/// This function may panic if:
+/// * Required arguments are missing
+/// * Invalid argument combinations are provided
+/// * Help or version flags are used (clap handles these automatically)
+pub(super) fn parse_args() -> Args {
+    Args::parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use rstest::rstest;
+    use std::path::PathBuf;
+
+    fn parse_args_from<I, T>(args: I) -> Args
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        Args::parse_from(args)
+    }
+
+    fn unexpected(command: Commands) -> ! {
+        panic!("unexpected command: {command:?}")
+    }
+
+    /// Returns the example's arguments with its environment variables passed as the flags
+    /// reading them, so it parses the same without touching the environment.
+    fn example_argv(example: &Example) -> Vec<String> {
+        let command = Args::command();
+        let subcommand = command
+            .find_subcommand(example.argv[1])
+            .unwrap_or_else(|| panic!("{}: unknown subcommand", example.about));
+        let mut argv: Vec<String> = example.argv.iter().map(|arg| (*arg).to_owned()).collect();
+        for (name, value) in example.env {
+            let flag = subcommand
+                .get_arguments()
+                .find(|arg| arg.get_env() == Some(OsStr::new(name)))
+                .and_then(clap::Arg::get_long)
+                .unwrap_or_else(|| panic!("{}: nothing reads ${name}", example.about));
+            argv.push(format!("--{flag}={value}"));
+        }
+        argv
+    }
+
+    #[test]
+    fn every_help_example_parses() {
+        let examples = [
+            RESUME_EXAMPLES,
+            RESUME_ALL_EXAMPLES,
+            WATCH_EXAMPLES,
+            REMEMBERED_EXAMPLES,
+            WHICH_EXAMPLES,
+        ];
+        for example in examples.into_iter().flatten() {
+            if let Err(err) = Args::try_parse_from(example_argv(example)) {
+                panic!("example {:?} does not parse: {err}", example.about);
+            }
+        }
+    }
+
+    #[rstest]
+    #[case("resume-all")]
+    #[case("watch")]
+    #[case("remembered")]
+    #[case("which")]
+    #[case("prompt-hint")]
+    fn help_has_one_usage_line_and_the_examples_at_the_end(#[case] name: &str) {
+        let help = Args::command()
+            .find_subcommand_mut(name)
+            .unwrap()
+            .render_long_help()
+            .to_string();
+
+        assert_eq!(help.matches("Usage:").count(), 1, "{help}");
+        let examples = help.find("Examples:").expect("help should list examples");
+        assert!(help[examples..].contains(&format!("amg {name}")), "{help}");
+    }
+
+    #[test]
+    fn resume_help_lists_the_examples() {
+        let help = Args::command()
+            .find_subcommand_mut("resume")
+            .unwrap()
+            .render_long_help()
+            .to_string();
+
+        assert!(help.contains("Examples:"), "{help}");
+        assert!(
+            help.contains(
+                "  # Print the codex command instead of running it\n  amg resume feature/a \
+                 --repo ~/src/app --dry-run\n"
+            ),
+            "{help}"
+        );
+        assert!(
+            help.contains("  CODEX_REPO=~/src/app CODEX_CODEXDIR=/mnt/laptop/.codex amg resume"),
+            "{help}"
+        );
+    }
+
+    #[rstest]
+    #[case(&["amg", "resume-all", "--repo", "/r", "--branches", "a,b", "--branches", "c"], &["a", "b", "c"], None)]
+    #[case(&["amg", "resume-all", "--repo", "/r", "--from-file", "list.txt"], &[], Some("list.txt"))]
+    fn test_resume_all_options(
+        #[case] argv: &[&str],
+        #[case] expected_branches: &[&str],
+        #[case] expected_file: Option<&str>,
+    ) {
+        match parse_args_from(argv).command {
+            Commands::ResumeAll(all) => {
+                assert_eq!(all.branches, expected_branches);
+                assert_eq!(all.from_file, expected_file.map(PathBuf::from));
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&[], false)]
+    #[case(&["--force-new-window"], true)]
+    fn test_resume_all_force_new_window(#[case] extra: &[&str], #[case] expected: bool) {
+        let mut argv = vec!["amg", "resume-all", "--repo", "/r", "--branches", "main"];
+        argv.extend_from_slice(extra);
+        match parse_args_from(argv).command {
+            Commands::ResumeAll(all) => assert_eq!(all.force_new_window, expected),
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn test_resume_all_requires_branches_or_file() {
+        assert!(Args::try_parse_from(["amg", "resume-all", "--repo", "/r"]).is_err());
+    }
+
+    #[rstest]
+    #[case(&[], false, false)]
+    #[case(&["--pick"], true, false)]
+    #[case(&["--pick", "--no-fzf"], true, true)]
+    fn test_pick_options(
+        #[case] extra: &[&str],
+        #[case] expected_pick: bool,
+        #[case] expected_no_fzf: bool,
+    ) {
+        let mut argv = vec!["amg", "resume", "main", "--repo", "/r"];
+        argv.extend(extra);
+        match parse_args_from(argv).command {
+            Commands::ResumeBranch(resume) => {
+                assert_eq!(resume.pick, expected_pick);
+                assert_eq!(resume.no_fzf, expected_no_fzf);
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn pick_tmux_is_a_pick_that_cannot_change_the_launch() {
+        let argv = ["amg", "resume", "main", "--repo", "/r", "--pick-tmux"];
+        match parse_args_from(argv).command {
+            Commands::ResumeBranch(resume) => {
+                assert!(resume.pick_tmux);
+                assert!(!resume.pick);
+            }
+            other => unexpected(other),
+        }
+        for extra in ["--backup", "--any", "--explain", "--result-json"] {
+            let argv = [
+                "amg",
+                "resume",
+                "main",
+                "--repo",
+                "/r",
+                "--pick-tmux",
+                extra,
+            ];
+            assert!(Args::try_parse_from(argv).is_err(), "{extra}");
+        }
+    }
+
+    #[rstest]
+    #[case(&["--explain"], true, false, 50)]
+    #[case(&["--explain", "--json", "--explain-limit", "5"], true, true, 5)]
+    fn test_explain_options(
+        #[case] extra: &[&str],
+        #[case] expected_explain: bool,
+        #[case] expected_json: bool,
+        #[case] expected_limit: usize,
+    ) {
+        let mut argv = vec!["amg", "resume", "main", "--repo", "/r"];
+        argv.extend(extra);
+        match parse_args_from(argv).command {
+            Commands::ResumeBranch(resume) => {
+                assert_eq!(resume.explain, expected_explain);
+                assert_eq!(resume.json, expected_json);
+                assert_eq!(resume.explain_limit, expected_limit);
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&["--json"])]
+    #[case(&["--explain-limit", "3"])]
+    #[case(&["--explain", "--pick"])]
+    fn test_explain_flag_conflicts(#[case] extra: &[&str]) {
+        let mut argv = vec!["amg", "resume", "main", "--repo", "/r"];
+        argv.extend(extra);
+        assert!(Args::try_parse_from(argv).is_err());
+    }
+
+    #[test]
+    fn test_no_fzf_requires_pick() {
+        assert!(
+            Args::try_parse_from(["amg", "resume", "main", "--repo", "/r", "--no-fzf"]).is_err()
+        );
+    }
+
+    #[rstest]
+    #[case(&["-n", "--copy"], true)]
+    #[case(&["--copy"], true)]
+    #[case(&["-n"], false)]
+    fn test_copy_flag(#[case] extra: &[&str], #[case] expected: bool) {
+        let mut argv = vec!["amg", "resume", "main", "--repo", "/r"];
+        argv.extend(extra);
+        match parse_args_from(argv).command {
+            Commands::ResumeBranch(resume) => assert_eq!(resume.execution.copy, expected),
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn test_print_id_flag() {
+        match parse_args_from(["amg", "resume", "main", "--print-id"]).command {
+            Commands::ResumeBranch(resume) => {
+                assert!(resume.execution.print_id);
+                assert!(resume.repo.is_none());
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&["-n"])]
+    #[case(&["--copy"])]
+    #[case(&["--result-json"])]
+    #[case(&["--plan-out", "/tmp/plan.json"])]
+    #[case(&["--pick-tmux"])]
+    #[case(&["--stdin-jsonl"])]
+    fn test_print_id_conflicts(#[case] extra: &[&str]) {
+        let mut argv = vec!["amg", "resume", "main", "--print-id"];
+        argv.extend(extra);
+        assert!(Args::try_parse_from(argv).is_err());
+    }
+
+    #[test]
+    fn test_output_requires_dry_run() {
+        let args = parse_args_from([
+            "amg",
+            "resume",
+            "main",
+            "--repo",
+            "/r",
+            "-n",
+            "--output",
+            "/tmp/cmd.sh",
+        ]);
+        match args.command {
+            Commands::ResumeBranch(resume) => {
+                assert_eq!(resume.execution.output, Some(PathBuf::from("/tmp/cmd.sh")));
+            }
+            other => unexpected(other),
+        }
+
+        assert!(
+            Args::try_parse_from([
+                "amg", "resume", "main", "--repo", "/r", "--output", "/tmp/x"
+            ])
+            .is_err()
+        );
+    }
+
+    #[rstest]
+    #[case(&["amg", "kill", "main"], Some("main"), None)]
+    #[case(&["amg", "kill", "--id", "0199a6b2"], None, Some("0199a6b2"))]
+    fn test_kill_options(
+        #[case] argv: &[&str],
+        #[case] expected_branch: Option<&str>,
+        #[case] expected_id: Option<&str>,
+    ) {
+        match parse_args_from(argv).command {
+            Commands::Kill(kill) => {
+                assert_eq!(kill.branch.as_deref(), expected_branch);
+                assert_eq!(kill.id.as_deref(), expected_id);
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&["amg", "kill"])]
+    #[case(&["amg", "kill", "main", "--id", "x"])]
+    fn test_kill_requires_exactly_one_selector(#[case] argv: &[&str]) {
+        assert!(Args::try_parse_from(argv).is_err());
+    }
+
+    #[rstest]
+    #[case(&["amg", "which", "main"], false, false, false)]
+    #[case(&["amg", "which", "main", "--no-cache", "--any-filename"], true, true, false)]
+    #[case(&["amg", "which", "main", "--age-only"], false, false, true)]
+    fn test_which_options(
+        #[case] argv: &[&str],
+        #[case] expected_no_cache: bool,
+        #[case] expected_any_filename: bool,
+        #[case] expected_age_only: bool,
+    ) {
+        match parse_args_from(argv).command {
+            Commands::Which(which) => {
+                assert_eq!(which.branch, "main");
+                assert_eq!(which.cache.no_cache, expected_no_cache);
+                assert_eq!(which.selection.any_filename, expected_any_filename);
+                assert_eq!(which.age_only, expected_age_only);
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&["amg", "prompt-hint"], ".")]
+    #[case(&["amg", "prompt-hint", "--dir", "/work/app"], "/work/app")]
+    fn test_prompt_hint_dir(#[case] argv: &[&str], #[case] expected: &str) {
+        match parse_args_from(argv).command {
+            Commands::PromptHint(hint) => assert_eq!(hint.dir, Path::new(expected)),
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn stdin_jsonl_needs_no_repo() {
+        let argv = [
+            "amg",
+            "resume",
+            "main",
+            "--stdin-jsonl",
+            "--path-prefix",
+            "host:",
+            "-n",
+        ];
+
+        match parse_args_from(argv).command {
+            Commands::ResumeBranch(resume) => {
+                assert!(resume.stdin_jsonl);
+                assert_eq!(resume.repo, None);
+                assert_eq!(resume.path_prefix.as_deref(), Some("host:"));
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&["amg", "resume", "main", "--stdin-jsonl", "--file", "/s.jsonl"])]
+    #[case(&["amg", "resume", "main", "--stdin-jsonl", "--pick"])]
+    #[case(&["amg", "resume", "main", "--repo", "/r", "--path-prefix", "host:"])]
+    #[case(&["amg", "which", "main", "--stdin-jsonl", "--age-only"])]
+    fn stdin_jsonl_rejects_local_only_options(#[case] argv: &[&str]) {
+        assert!(Args::try_parse_from(argv).is_err());
+    }
+
+    #[rstest]
+    #[case(&[], false)]
+    #[case(&["--repo-from-session"], true)]
+    fn test_repo_from_session_flag(#[case] flags: &[&str], #[case] expected: bool) {
+        let mut cmd_args = vec!["amg", "resume", "main", "--repo", "/tmp/repo"];
+        cmd_args.extend_from_slice(flags);
+
+        match parse_args_from(cmd_args).command {
+            Commands::ResumeBranch(ResumeArgs {
+                codex: CodexArgs {
+                    repo_from_session, ..
+                },
+                ..
+            }) => assert_eq!(repo_from_session, expected),
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&[], false, false)]
+    #[case(&["--dangerously-full-access"], true, false)]
+    #[case(&["--dangerously-full-access", "--yes"], true, true)]
+    #[case(&["--dangerously-full-access", "-y"], true, true)]
+    fn test_full_access_flags(
+        #[case] flags: &[&str],
+        #[case] expected_full_access: bool,
+        #[case] expected_yes: bool,
+    ) {
+        let mut cmd_args = vec!["amg", "resume", "main", "--repo", "/tmp/repo"];
+        cmd_args.extend_from_slice(flags);
+
+        match parse_args_from(cmd_args).command {
+            Commands::ResumeBranch(ResumeArgs {
+                codex:
+                    CodexArgs {
+                        dangerously_full_access,
+                        yes,
+                        ..
+                    },
+                ..
+            }) => {
+                assert_eq!(dangerously_full_access, expected_full_access);
+                assert_eq!(yes, expected_yes);
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn test_yes_requires_full_access() {
+        assert!(Args::try_parse_from(["amg", "resume", "main", "--repo", "/r", "--yes"]).is_err());
+    }
+
+    #[rstest]
+    #[case(&[], false)]
+    #[case(&["--no-cache"], true)]
+    fn test_resume_no_cache_flag(#[case] flags: &[&str], #[case] expected: bool) {
+        let mut cmd_args = vec!["amg", "resume", "main", "--repo", "/tmp/repo"];
+        cmd_args.extend_from_slice(flags);
+
+        match parse_args_from(cmd_args).command {
+            Commands::ResumeBranch(ResumeArgs { cache, .. }) => {
+                assert_eq!(cache.no_cache, expected)
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&["amg", "dump"], None, None, None)]
+    #[case(&["amg", "dump", "--codexdir", "/c", "--branch", "main", "--since", "2h"], Some("/c"), Some("main"), Some(7_200))]
+    fn test_dump_options(
+        #[case] argv: &[&str],
+        #[case] codexdir: Option<&str>,
+        #[case] branch: Option<&str>,
+        #[case] since_secs: Option<u64>,
+    ) {
+        match parse_args_from(argv).command {
+            Commands::Dump(dump) => {
+                assert_eq!(dump.location.codexdir, codexdir.map(PathBuf::from));
+                assert_eq!(dump.branch.as_deref(), branch);
+                assert_eq!(dump.since, since_secs.map(Duration::from_secs));
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn test_dump_rejects_invalid_since() {
+        assert!(Args::try_parse_from(["amg", "dump", "--since", "soon"]).is_err());
+    }
+
+    #[rstest]
+    #[case(&["amg", "resume", "--file", "/s/rollout.jsonl"], true)]
+    #[case(&["amg", "resume", "--file", "file:///s/r.jsonl", "--force", "--repo", "/r"], true)]
+    #[case(&["amg", "resume", "main", "--file", "/s/r.jsonl"], false)]
+    #[case(&["amg", "resume", "--file", "/s/r.jsonl", "--id", "7f3a"], false)]
+    #[case(&["amg", "resume", "main", "--repo", "/r", "--force"], false)]
+    fn test_resume_file(#[case] argv: &[&str], #[case] valid: bool) {
+        assert_eq!(Args::try_parse_from(argv).is_ok(), valid);
+    }
+
+    #[rstest]
+    #[case(&["amg", "config", "set", "wrap_command", "docker", "exec", "-it"], None)]
+    #[case(&["amg", "config", "--repo", "/r", "get", "write_marker"], Some("/r"))]
+    #[case(&["amg", "config", "list", "--repo", "/r"], Some("/r"))]
+    fn test_config_options(#[case] argv: &[&str], #[case] expected_repo: Option<&str>) {
+        match parse_args_from(argv).command {
+            Commands::Config { command, repo } => {
+                assert_eq!(repo, expected_repo.map(PathBuf::from));
+                if let ConfigCommand::Set { values, .. } = command {
+                    assert_eq!(values, ["docker", "exec", "-it"]);
+                }
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&["amg", "show", "main", "--raw", "--json"], true)]
+    #[case(&["amg", "show", "-x", "--raw-only"], true)]
+    #[case(&["amg", "show", "main", "--raw-only", "--json"], false)]
+    #[case(&["amg", "show", "main", "--raw-only", "--raw"], false)]
+    #[case(&["amg", "show", "main", "--print-session-dir"], true)]
+    #[case(&["amg", "show", "main", "--open-dir"], true)]
+    #[case(&["amg", "show", "main", "--print-session-dir", "--json"], false)]
+    #[case(&["amg", "show", "main", "--open-dir", "--print-session-dir"], false)]
+    fn test_show_options(#[case] argv: &[&str], #[case] valid: bool) {
+        assert_eq!(Args::try_parse_from(argv).is_ok(), valid);
+    }
+
+    #[rstest]
+    #[case(&["amg", "branches"], false)]
+    #[case(&["amg", "branches", "--porcelain", "--codexdir", "/c"], true)]
+    fn test_branches_options(#[case] argv: &[&str], #[case] porcelain: bool) {
+        match parse_args_from(argv).command {
+            Commands::Branches(branches) => {
+                assert_eq!(branches.porcelain, porcelain);
+                assert!(!branches.cached);
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn test_branches_cached_options() {
+        match parse_args_from(["amg", "branches", "--cached", "--max-age", "1h"]).command {
+            Commands::Branches(branches) => {
+                assert!(branches.cached);
+                assert_eq!(branches.max_age, Some(Duration::from_secs(3_600)));
+            }
+            other => unexpected(other),
+        }
+        assert!(Args::try_parse_from(["amg", "branches", "--max-age", "1h"]).is_err());
+    }
+
+    #[rstest]
+    #[case(&["amg", "find", "pay"], "pay", false)]
+    #[case(&["amg", "find", "-v2", "--resume-top", "--repo", "/r", "-n"], "-v2", true)]
+    fn test_find_options(#[case] argv: &[&str], #[case] fragment: &str, #[case] resume: bool) {
+        match parse_args_from(argv).command {
+            Commands::Find(find) => {
+                assert_eq!(find.fragment, fragment);
+                assert_eq!(find.resume_top, resume);
+                assert_eq!(find.dry_run, resume);
+            }
+            other => unexpected(other),
+        }
+        assert!(Args::try_parse_from(["amg", "find", "pay", "--dry-run"]).is_err());
+    }
+
+    #[rstest]
+    #[case(&["amg", "init"], false, None, None)]
+    #[case(&["amg", "init", "-y", "--tmux", "false", "--model", "o3"], true, Some(false), Some("o3"))]
+    #[case(&["amg", "init", "--yes", "--tmux=true"], true, Some(true), None)]
+    fn test_init_options(
+        #[case] argv: &[&str],
+        #[case] yes: bool,
+        #[case] tmux: Option<bool>,
+        #[case] model: Option<&str>,
+    ) {
+        match parse_args_from(argv).command {
+            Commands::Init(init) => {
+                assert_eq!(init.yes, yes);
+                assert_eq!(init.tmux, tmux);
+                assert_eq!(init.model.as_deref(), model);
+            }
+            other => unexpected(other),
+        }
+    }
 
-        /// Repo to grant Codex sandbox access to.
-        #[arg(short, long, env = "CODEX_REPO")]
-        repo: PathBuf,
+    #[test]
+    fn test_init_rejects_a_non_boolean_tmux() {
+        assert!(Args::try_parse_from(["amg", "init", "--tmux", "maybe"]).is_err());
+    }
 
-        /// Codex directory containing JSONL sessions (defaults to `$HOME/.codex`).
-        #[arg(long, env = "CODEX_CODEXDIR")]
-        codexdir: Option<PathBuf>,
+    #[rstest]
+    #[case(&["amg", "stats"], None, false)]
+    #[case(&["amg", "stats", "--trend", "30d", "--json"], Some(30 * 86_400), true)]
+    fn test_stats_options(
+        #[case] argv: &[&str],
+        #[case] trend_secs: Option<u64>,
+        #[case] json: bool,
+    ) {
+        match parse_args_from(argv).command {
+            Commands::Stats(stats) => {
+                assert_eq!(stats.trend, trend_secs.map(Duration::from_secs));
+                assert_eq!(stats.json, json);
+            }
+            other => unexpected(other),
+        }
+    }
 
-        /// Print the exact command that would be executed and exit without running.
-        /// (If `$TMUX` is set and `--no-tmux` is not, this prints the `tmux new-window ...` command.)
-        #[arg(long, short = 'n')]
-        dry_run: bool,
+    #[rstest]
+    #[case(&["amg", "resume", "main", "--repo", "/tmp/repo"], None)]
+    #[case(&["amg", "--log-file", "/tmp/amg.log", "resume", "main", "--repo", "/tmp/repo"], Some("/tmp/amg.log"))]
+    #[case(&["amg", "resume", "main", "--repo", "/tmp/repo", "--log-file", "/tmp/amg.log"], Some("/tmp/amg.log"))]
+    fn test_log_file_option(#[case] argv: &[&str], #[case] expected: Option<&str>) {
+        let args = parse_args_from(argv);
+        assert_eq!(args.log_file, expected.map(PathBuf::from));
+    }
 
-        /// If `$TMUX` is set, do NOT open a new tmux window; run inline instead.
-        #[arg(long)]
-        no_tmux: bool,
-    },
-}
+    #[rstest]
+    #[case(&["amg", "which", "main"], None)]
+    #[case(&["amg", "--stats-file", "/tmp/s.jsonl", "which", "main"], Some("/tmp/s.jsonl"))]
+    #[case(&["amg", "which", "main", "--stats-file", "/tmp/s.jsonl"], Some("/tmp/s.jsonl"))]
+    fn test_stats_file_option(#[case] argv: &[&str], #[case] expected: Option<&str>) {
+        let args = parse_args_from(argv);
+        assert_eq!(args.stats_file, expected.map(PathBuf::from));
+    }
 
-/// Parses command-line arguments.
-///
-/// Uses `clap` to parse arguments from the command line, environment variables, and defaults.
-///
-/// # Returns
-///
-/// Returns an [`Args`] structure containing the parsed arguments.
-///
-/// # Panics
-///
-/// This function may panic if:
-/// * Required arguments are missing
-/// * Invalid argument combinations are provided
-/// * Help or version flags are used (clap handles these automatically)
-pub(super) fn parse_args() -> Args {
-    Args::parse()
-}
+    #[rstest]
+    #[case(&["amg", "which", "main"], false)]
+    #[case(&["amg", "--portable", "which", "main"], true)]
+    #[case(&["amg", "branches", "--portable"], true)]
+    fn test_portable_option(#[case] argv: &[&str], #[case] expected: bool) {
+        assert_eq!(parse_args_from(argv).portable, expected);
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use rstest::rstest;
-    use std::path::PathBuf;
+    #[rstest]
+    #[case(&["amg", "resume", "main", "--repo", "/r"], None)]
+    #[case(&["amg", "--exit-code-offset", "resume", "main", "--repo", "/r"], Some(100))]
+    #[case(&["amg", "resume", "main", "--repo", "/r", "--exit-code-offset=120"], Some(120))]
+    fn test_exit_code_offset(#[case] argv: &[&str], #[case] expected: Option<u8>) {
+        assert_eq!(parse_args_from(argv).exit_code_offset, expected);
+    }
 
-    fn parse_args_from<I, T>(args: I) -> Args
-    where
-        I: IntoIterator<Item = T>,
-        T: Into<std::ffi::OsString> + Clone,
-    {
-        Args::parse_from(args)
+    #[rstest]
+    #[case("--exit-code-offset=254")]
+    #[case("--exit-code-offset=256")]
+    #[case("--exit-code-offset=x")]
+    fn test_exit_code_offset_rejects_invalid_codes(#[case] flag: &str) {
+        assert!(Args::try_parse_from(["amg", flag, "resume", "main", "--repo", "/r"]).is_err());
     }
 
     #[rstest]
@@ -98,9 +2010,279 @@ mod tests {
     fn test_subcommand_aliases(#[case] subcommand: &str) {
         let args = parse_args_from(["amg", subcommand, "test-branch", "--repo", "/tmp/repo"]);
         match args.command {
-            Commands::ResumeBranch { branch, .. } => {
-                assert_eq!(branch, "test-branch");
+            Commands::ResumeBranch(ResumeArgs { branch, .. }) => {
+                assert_eq!(branch.as_deref(), Some("test-branch"));
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn test_resume_by_id_prefix_needs_no_branch_or_repo() {
+        match parse_args_from(["amg", "resume", "--id", "7f3a"]).command {
+            Commands::ResumeBranch(ResumeArgs { id, branch, .. }) => {
+                assert_eq!(id.as_deref(), Some("7f3a"));
+                assert_eq!(branch, None);
+            }
+            other => unexpected(other),
+        }
+        assert!(Args::try_parse_from(["amg", "resume", "main", "--id", "7f3a"]).is_err());
+    }
+
+    #[test]
+    fn test_import() {
+        let args = parse_args_from([
+            "amg",
+            "import",
+            "escalation.jsonl",
+            "--rewrite-cwd",
+            "/work/app",
+            "--force",
+        ]);
+        match args.command {
+            Commands::Import(ImportArgs {
+                file,
+                rewrite_cwd,
+                force,
+                ..
+            }) => {
+                assert_eq!(file, PathBuf::from("escalation.jsonl"));
+                assert_eq!(rewrite_cwd, Some(PathBuf::from("/work/app")));
+                assert!(force);
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn test_migrate_layout() {
+        let args = parse_args_from(["amg", "migrate-layout", "--dry-run", "--codexdir", "/c"]);
+        match args.command {
+            Commands::MigrateLayout(MigrateLayoutArgs { codexdir, dry_run }) => {
+                assert_eq!(codexdir, Some(PathBuf::from("/c")));
+                assert!(dry_run);
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn test_plan_out_and_exec_plan() {
+        let argv = [
+            "amg",
+            "resume",
+            "main",
+            "--repo",
+            "/r",
+            "--plan-out",
+            "plan.json",
+        ];
+        match parse_args_from(argv).command {
+            Commands::ResumeBranch(resume) => {
+                assert_eq!(resume.execution.plan_out, Some(PathBuf::from("plan.json")));
+            }
+            other => unexpected(other),
+        }
+        let argv = ["amg", "resume", "main", "--plan-out", "p.json", "--dry-run"];
+        assert!(Args::try_parse_from(argv).is_err());
+        match parse_args_from(["amg", "exec-plan", "plan.json"]).command {
+            Commands::ExecPlan(ExecPlanArgs { plan }) => {
+                assert_eq!(plan, PathBuf::from("plan.json"));
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn remembered_and_fresh() {
+        match parse_args_from(["amg", "remembered"]).command {
+            Commands::Remembered(RememberedArgs { branch, clear }) => {
+                assert_eq!(branch, None);
+                assert!(!clear);
+            }
+            other => unexpected(other),
+        }
+        match parse_args_from(["amg", "remembered", "feature/a", "--clear"]).command {
+            Commands::Remembered(RememberedArgs { branch, clear }) => {
+                assert_eq!(branch.as_deref(), Some("feature/a"));
+                assert!(clear);
+            }
+            other => unexpected(other),
+        }
+        assert!(Args::try_parse_from(["amg", "remembered", "--clear"]).is_err());
+        match parse_args_from(["amg", "resume", "main", "--repo", "/r", "--fresh"]).command {
+            Commands::ResumeBranch(resume) => assert!(resume.fresh),
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn narrow_codexdir_is_a_sandbox_option() {
+        let argv = ["amg", "resume", "main", "--repo", "/r", "--narrow-codexdir"];
+        match parse_args_from(argv).command {
+            Commands::ResumeBranch(resume) => assert!(resume.sandbox.narrow_codexdir),
+            other => unexpected(other),
+        }
+        match parse_args_from(["amg", "doctor", "--narrow-codexdir"]).command {
+            Commands::Doctor(DoctorArgs { narrow_codexdir }) => assert!(narrow_codexdir),
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn add_worktrees_is_a_sandbox_option() {
+        match parse_args_from(["amg", "resume", "main", "--repo", "/r", "--add-worktrees"]).command
+        {
+            Commands::ResumeBranch(resume) => assert!(resume.sandbox.add_worktrees),
+            other => unexpected(other),
+        }
+        match parse_args_from(["amg", "watch", "main", "--repo", "/r", "--add-worktrees"]).command {
+            Commands::Watch(watch) => assert!(watch.sandbox.add_worktrees),
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn watch_defaults_and_options() {
+        match parse_args_from(["amg", "watch", "main", "--repo", "/r"]).command {
+            Commands::Watch(watch) => {
+                assert_eq!(watch.branch, "main");
+                assert_eq!(watch.interval, Duration::from_secs(5));
+                assert_eq!(watch.timeout, None);
+                assert!(!watch.once);
+            }
+            other => unexpected(other),
+        }
+        let argv = [
+            "amg",
+            "watch",
+            "-wip",
+            "--repo",
+            "/r",
+            "--interval",
+            "30s",
+            "--timeout",
+            "2h",
+            "--once",
+        ];
+        match parse_args_from(argv).command {
+            Commands::Watch(watch) => {
+                assert_eq!(watch.branch, "-wip");
+                assert_eq!(watch.interval, Duration::from_secs(30));
+                assert_eq!(watch.timeout, Some(Duration::from_secs(7_200)));
+                assert!(watch.once);
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn exclude_is_repeatable() {
+        let argv = [
+            "amg",
+            "which",
+            "main",
+            "--exclude",
+            "archived/",
+            "--exclude",
+            "*.bak",
+        ];
+        match parse_args_from(argv).command {
+            Commands::Which(which) => {
+                assert_eq!(which.selection.exclude, ["archived/", "*.bak"]);
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn strip_prefix_is_repeatable() {
+        let argv = [
+            "amg",
+            "which",
+            "main",
+            "--strip-prefix",
+            "me/",
+            "--strip-prefix",
+            "you/",
+        ];
+        match parse_args_from(argv).command {
+            Commands::Which(which) => {
+                assert_eq!(which.selection.strip_prefix, ["me/", "you/"]);
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn map_path_is_repeatable_and_validated() {
+        let argv = [
+            "amg",
+            "which",
+            "main",
+            "--map-path",
+            r"C:\Users\dev=/home/me",
+            "--map-path",
+            "/mnt/share=/srv/share",
+        ];
+        match parse_args_from(argv).command {
+            Commands::Which(which) => assert_eq!(which.selection.map_path.len(), 2),
+            other => unexpected(other),
+        }
+        assert!(Args::try_parse_from(["amg", "which", "main", "--map-path", "/no-new"]).is_err());
+    }
+
+    #[test]
+    fn test_alias_add() {
+        let args = parse_args_from(["amg", "alias", "add", "ingestion", "feature/PROJ-1"]);
+        match args.command {
+            Commands::Alias {
+                command: AliasCommand::Add { name, branch },
+            } => {
+                assert_eq!(name, "ingestion");
+                assert_eq!(branch, "feature/PROJ-1");
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&["amg", "tag", "add", "0199-id", "demo-prep"], "add")]
+    #[case(&["amg", "tag", "rm", "0199-id", "demo-prep"], "rm")]
+    #[case(&["amg", "tag", "list"], "list")]
+    #[case(&["amg", "tag", "list", "0199-id"], "list")]
+    fn test_tag_commands(#[case] argv: &[&str], #[case] expected: &str) {
+        match parse_args_from(argv).command {
+            Commands::Tag {
+                command: TagCommand::Add { session_id, tag } | TagCommand::Rm { session_id, tag },
+            } => {
+                assert_eq!(argv[2], expected);
+                assert_eq!(session_id, "0199-id");
+                assert_eq!(tag, "demo-prep");
+            }
+            Commands::Tag {
+                command: TagCommand::List { session_id },
+            } => {
+                assert_eq!(expected, "list");
+                assert_eq!(session_id.as_deref(), argv.get(3).copied());
             }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&["amg", "alias", "list"])]
+    #[case(&["amg", "alias", "rm", "ingestion"])]
+    fn test_alias_list_and_rm(#[case] argv: &[&str]) {
+        let args = parse_args_from(argv);
+        match args.command {
+            Commands::Alias {
+                command: AliasCommand::List,
+            } => assert_eq!(argv[2], "list"),
+            Commands::Alias {
+                command: AliasCommand::Rm { name },
+            } => assert_eq!(name, "ingestion"),
+            other => unexpected(other),
         }
     }
 
@@ -112,9 +2294,10 @@ mod tests {
     fn test_branch_names(#[case] branch_name: &str) {
         let args = parse_args_from(["amg", "resume", branch_name, "--repo", "/tmp/repo"]);
         match args.command {
-            Commands::ResumeBranch { branch, .. } => {
-                assert_eq!(branch, branch_name);
+            Commands::ResumeBranch(ResumeArgs { branch, .. }) => {
+                assert_eq!(branch.as_deref(), Some(branch_name));
             }
+            other => unexpected(other),
         }
     }
 
@@ -125,9 +2308,10 @@ mod tests {
     fn test_repo_paths(#[case] repo_path: &str) {
         let args = parse_args_from(["amg", "resume", "main", "--repo", repo_path]);
         match args.command {
-            Commands::ResumeBranch { repo, .. } => {
-                assert_eq!(repo, PathBuf::from(repo_path));
+            Commands::ResumeBranch(ResumeArgs { repo, .. }) => {
+                assert_eq!(repo, Some(PathBuf::from(repo_path)));
             }
+            other => unexpected(other),
         }
     }
 
@@ -144,11 +2328,134 @@ mod tests {
 
         let args = parse_args_from(cmd_args);
         match args.command {
-            Commands::ResumeBranch {
-                codexdir: result, ..
-            } => {
+            Commands::ResumeBranch(ResumeArgs {
+                location: LocationArgs { codexdir: result },
+                ..
+            }) => {
                 assert_eq!(result, codexdir.map(PathBuf::from));
             }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(None)]
+    #[case(Some("/tmp/worktree"))]
+    #[case(Some("/home/user/sibling checkout"))]
+    fn test_chdir_option(#[case] chdir: Option<&str>) {
+        let mut cmd_args = vec!["amg", "resume", "main", "--repo", "/tmp/repo"];
+        if let Some(dir) = chdir {
+            cmd_args.push("--chdir");
+            cmd_args.push(dir);
+        }
+
+        let args = parse_args_from(cmd_args);
+        match args.command {
+            Commands::ResumeBranch(ResumeArgs {
+                codex: CodexArgs { chdir: result, .. },
+                ..
+            }) => {
+                assert_eq!(result, chdir.map(PathBuf::from));
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&[], false, false)]
+    #[case(&["--verify-commit"], true, false)]
+    #[case(&["--verify-commit", "--strict"], true, true)]
+    fn test_verify_commit_flags(
+        #[case] flags: &[&str],
+        #[case] expected_verify: bool,
+        #[case] expected_strict: bool,
+    ) {
+        let mut cmd_args = vec!["amg", "resume", "main", "--repo", "/tmp/repo"];
+        cmd_args.extend_from_slice(flags);
+
+        let args = parse_args_from(cmd_args);
+        match args.command {
+            Commands::ResumeBranch(ResumeArgs {
+                codex:
+                    CodexArgs {
+                        verify_commit,
+                        strict,
+                        ..
+                    },
+                ..
+            }) => {
+                assert_eq!(verify_commit, expected_verify);
+                assert_eq!(strict, expected_strict);
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[test]
+    fn require_branch_exists_is_local_only() {
+        let argv = [
+            "amg",
+            "resume",
+            "main",
+            "--repo",
+            "/r",
+            "--require-branch-exists",
+        ];
+        match parse_args_from(argv).command {
+            Commands::ResumeBranch(resume) => assert!(resume.codex.require_branch_exists),
+            other => unexpected(other),
+        }
+        let piped = [
+            "amg",
+            "resume",
+            "main",
+            "--stdin-jsonl",
+            "--require-branch-exists",
+        ];
+        assert!(Args::try_parse_from(piped).is_err());
+    }
+
+    #[rstest]
+    #[case(&[], false)]
+    #[case(&["--redact-home"], true)]
+    fn test_redact_home_flag(#[case] flags: &[&str], #[case] expected: bool) {
+        let mut cmd_args = vec!["amg", "resume", "main", "--repo", "/tmp/repo"];
+        cmd_args.extend_from_slice(flags);
+
+        let args = parse_args_from(cmd_args);
+        match args.command {
+            Commands::ResumeBranch(ResumeArgs {
+                execution: ExecutionArgs { redact_home, .. },
+                ..
+            }) => {
+                assert_eq!(redact_home, expected);
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&[], false, None, false)]
+    #[case(&["--any-filename"], true, None, false)]
+    #[case(&["--under", "/work/acme/"], false, Some("/work/acme/"), false)]
+    #[case(&["--any-owner"], false, None, true)]
+    fn test_selection_flags(
+        #[case] flags: &[&str],
+        #[case] expected_any_filename: bool,
+        #[case] expected_under: Option<&str>,
+        #[case] expected_any_owner: bool,
+    ) {
+        let mut cmd_args = vec!["amg", "resume", "main", "--repo", "/tmp/repo"];
+        cmd_args.extend_from_slice(flags);
+
+        let args = parse_args_from(cmd_args);
+        match args.command {
+            Commands::ResumeBranch(ResumeArgs { selection, .. }) => {
+                assert_eq!(selection.any_filename, expected_any_filename);
+                assert_eq!(selection.under, expected_under.map(PathBuf::from));
+                assert_eq!(selection.any_owner, expected_any_owner);
+            }
+            other => unexpected(other),
         }
     }
 
@@ -164,12 +2471,274 @@ mod tests {
     ) {
         let args = parse_args_from(["amg", "resume", "main", "--repo", "/tmp/repo", flag]);
         match args.command {
-            Commands::ResumeBranch {
-                dry_run, no_tmux, ..
-            } => {
+            Commands::ResumeBranch(ResumeArgs {
+                execution:
+                    ExecutionArgs {
+                        dry_run, no_tmux, ..
+                    },
+                ..
+            }) => {
                 assert_eq!(dry_run, expected_dry_run);
                 assert_eq!(no_tmux, expected_no_tmux);
             }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&["amg", "resume", "--repo", "/r", "--", "-wip-redesign"])]
+    #[case(&["amg", "resume", "-wip-redesign", "--repo", "/r"])]
+    #[case(&["amg", "resume", "--repo", "/r", "-wip-redesign"])]
+    #[case(&["amg", "resume", "-n", "--repo", "/r", "-wip-redesign"])]
+    #[case(&["amg", "rb", "--repo", "/r", "--", "-wip-redesign"])]
+    fn test_branch_may_start_with_a_dash(#[case] argv: &[&str]) {
+        match parse_args_from(argv).command {
+            Commands::ResumeBranch(resume) => {
+                assert_eq!(resume.branch.as_deref(), Some("-wip-redesign"));
+                assert_eq!(resume.repo, Some(PathBuf::from("/r")));
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&["amg", "resume", "--any"], None)]
+    #[case(&["amg", "resume", "--any", "--repo", "/r", "-n"], Some("/r"))]
+    fn test_any_needs_neither_branch_nor_repo(
+        #[case] argv: &[&str],
+        #[case] expected_repo: Option<&str>,
+    ) {
+        match parse_args_from(argv).command {
+            Commands::ResumeBranch(resume) => {
+                assert!(resume.any);
+                assert_eq!(resume.branch, None);
+                assert_eq!(resume.repo, expected_repo.map(PathBuf::from));
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&["amg", "resume", "main", "--any"])]
+    #[case(&["amg", "resume", "--any", "--pick"])]
+    #[case(&["amg", "resume", "--any", "--explain"])]
+    #[case(&["amg", "resume", "--repo", "/r"])]
+    fn test_any_conflicts_and_branch_requirement(#[case] argv: &[&str]) {
+        assert!(Args::try_parse_from(argv).is_err());
+    }
+
+    #[test]
+    fn test_hyphen_branch_does_not_swallow_flags() {
+        match parse_args_from(["amg", "resume", "-n", "main", "--repo", "/r"]).command {
+            Commands::ResumeBranch(resume) => {
+                assert!(resume.execution.dry_run);
+                assert_eq!(resume.branch.as_deref(), Some("main"));
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&["amg", "which", "--", "-wip"])]
+    #[case(&["amg", "which", "-wip"])]
+    fn test_which_accepts_hyphen_branch(#[case] argv: &[&str]) {
+        match parse_args_from(argv).command {
+            Commands::Which(which) => assert_eq!(which.branch, "-wip"),
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&[], false, false)]
+    #[case(&["--tmux-detach"], true, false)]
+    #[case(&["--tmux-detach", "--notify"], true, true)]
+    fn test_detach_and_notify(
+        #[case] extra: &[&str],
+        #[case] expected_detach: bool,
+        #[case] expected_notify: bool,
+    ) {
+        let mut argv = vec!["amg", "resume", "main", "--repo", "/r"];
+        argv.extend(extra);
+        match parse_args_from(argv).command {
+            Commands::ResumeBranch(resume) => {
+                assert_eq!(resume.execution.tmux_detach, expected_detach);
+                assert_eq!(resume.execution.notify, expected_notify);
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case(&["--relax-on-empty"], true)]
+    #[case(&["--relax-on-empty", "--since", "7d", "--under", "/w"], true)]
+    #[case(&["--relax-on-empty", "--pick"], false)]
+    #[case(&["--relax-on-empty", "--explain"], false)]
+    fn test_relax_on_empty(#[case] extra: &[&str], #[case] accepted: bool) {
+        let mut argv = vec!["amg", "resume", "main", "--repo", "/r"];
+        argv.extend(extra);
+        match Args::try_parse_from(argv) {
+            Ok(Args {
+                command: Commands::ResumeBranch(resume),
+                ..
+            }) => {
+                assert!(accepted);
+                assert!(resume.relax_on_empty);
+            }
+            Ok(other) => unexpected(other.command),
+            Err(_) => assert!(!accepted),
+        }
+    }
+
+    #[rstest]
+    #[case(&[], None, false)]
+    #[case(&["--tmux", "auto"], Some(TmuxMode::Auto), false)]
+    #[case(&["--tmux=always"], Some(TmuxMode::Always), false)]
+    #[case(&["--tmux", "never"], Some(TmuxMode::Never), false)]
+    #[case(&["--no-tmux"], None, true)]
+    fn test_tmux_mode(
+        #[case] flags: &[&str],
+        #[case] expected_tmux: Option<TmuxMode>,
+        #[case] expected_no_tmux: bool,
+    ) {
+        let mut argv = vec!["amg", "resume", "main", "--repo", "/r"];
+        argv.extend_from_slice(flags);
+        match parse_args_from(argv).command {
+            Commands::ResumeBranch(ResumeArgs {
+                execution: ExecutionArgs { tmux, no_tmux, .. },
+                ..
+            }) => {
+                assert_eq!(tmux, expected_tmux);
+                assert_eq!(no_tmux, expected_no_tmux);
+            }
+            other => unexpected(other),
+        }
+    }
+
+    #[rstest]
+    #[case("90s", Some(Duration::from_secs(90)))]
+    #[case("15m", Some(Duration::from_secs(900)))]
+    #[case("2h", Some(Duration::from_secs(7_200)))]
+    #[case("soon", None)]
+    fn test_timeout(#[case] value: &str, #[case] expected: Option<Duration>) {
+        let argv = ["amg", "resume", "main", "--repo", "/r", "--timeout", value];
+        match Args::try_parse_from(argv) {
+            Ok(Args {
+                command:
+                    Commands::ResumeBranch(ResumeArgs {
+                        execution: ExecutionArgs { timeout, .. },
+                        ..
+                    }),
+                ..
+            }) => assert_eq!(timeout, expected),
+            Ok(other) => unexpected(other.command),
+            Err(_) => assert!(expected.is_none()),
+        }
+    }
+
+    #[rstest]
+    #[case(&["--tmux", "sometimes"])]
+    #[case(&["--tmux", "always", "--no-tmux"])]
+    fn test_tmux_mode_rejects(#[case] flags: &[&str]) {
+        let mut argv = vec!["amg", "resume", "main", "--repo", "/r"];
+        argv.extend_from_slice(flags);
+        assert!(Args::try_parse_from(argv).is_err());
+    }
+
+    #[test]
+    fn test_no_tmux_is_hidden() {
+        let help = <Args as clap::CommandFactory>::command()
+            .find_subcommand_mut("resume")
+            .unwrap()
+            .render_long_help()
+            .to_string();
+
+        assert!(help.contains("--tmux <WHEN>"), "{help}");
+        assert!(
+            !help.lines().any(|line| line.trim() == "--no-tmux"),
+            "{help}"
+        );
+    }
+
+    #[test]
+    fn test_tmux_detach_conflicts_with_no_tmux() {
+        assert!(
+            Args::try_parse_from([
+                "amg",
+                "resume",
+                "main",
+                "--repo",
+                "/r",
+                "--tmux-detach",
+                "--no-tmux"
+            ])
+            .is_err()
+        );
+    }
+
+    #[rstest]
+    #[case(&[], false, false)]
+    #[case(&["--tmux-annotate"], true, false)]
+    #[case(&["--no-tmux-annotate"], false, true)]
+    fn test_tmux_annotate_flags(
+        #[case] flags: &[&str],
+        #[case] expected_on: bool,
+        #[case] expected_off: bool,
+    ) {
+        let argv = [&["amg", "resume", "main", "--repo", "/r"], flags].concat();
+        match parse_args_from(argv).command {
+            Commands::ResumeBranch(resume) => {
+                assert_eq!(
+                    (
+                        resume.execution.tmux_annotate,
+                        resume.execution.no_tmux_annotate
+                    ),
+                    (expected_on, expected_off)
+                );
+            }
+            other => unexpected(other),
+        }
+        let both = [
+            "amg",
+            "resume",
+            "main",
+            "--tmux-annotate",
+            "--no-tmux-annotate",
+        ];
+        assert!(Args::try_parse_from(both).is_err());
+    }
+
+    #[rstest]
+    #[case(&["amg", "resume", "main", "--repo", "/r", "--no-canonicalize"])]
+    #[case(&["amg", "resume-all", "--branches", "a", "--repo", "/r", "--no-canonicalize"])]
+    fn test_no_canonicalize(#[case] argv: &[&str]) {
+        let sandbox = match parse_args_from(argv).command {
+            Commands::ResumeBranch(resume) => resume.sandbox,
+            Commands::ResumeAll(all) => all.sandbox,
+            other => unexpected(other),
+        };
+        assert!(sandbox.no_canonicalize);
+    }
+
+    #[rstest]
+    #[case(&[], false, false)]
+    #[case(&["--no-home-dirs"], true, false)]
+    #[case(&["--no-extra-dirs"], false, true)]
+    #[case(&["--no-home-dirs", "--no-extra-dirs"], true, true)]
+    fn test_sandbox_flags(
+        #[case] flags: &[&str],
+        #[case] expected_no_home_dirs: bool,
+        #[case] expected_no_extra_dirs: bool,
+    ) {
+        let mut cmd_args = vec!["amg", "resume", "main", "--repo", "/tmp/repo"];
+        cmd_args.extend_from_slice(flags);
+
+        let args = parse_args_from(cmd_args);
+        match args.command {
+            Commands::ResumeBranch(ResumeArgs { sandbox, .. }) => {
+                assert_eq!(sandbox.no_home_dirs, expected_no_home_dirs);
+                assert_eq!(sandbox.no_extra_dirs, expected_no_extra_dirs);
+            }
+            other => unexpected(other),
         }
     }
 
@@ -203,19 +2772,28 @@ mod tests {
 
         let args = parse_args_from(cmd_args);
         match args.command {
-            Commands::ResumeBranch {
+            Commands::ResumeBranch(ResumeArgs {
                 branch: result_branch,
                 repo: result_repo,
-                codexdir: result_codexdir,
-                dry_run: result_dry_run,
-                no_tmux: result_no_tmux,
-            } => {
-                assert_eq!(result_branch, branch);
-                assert_eq!(result_repo, PathBuf::from(repo));
+                location:
+                    LocationArgs {
+                        codexdir: result_codexdir,
+                    },
+                execution:
+                    ExecutionArgs {
+                        dry_run: result_dry_run,
+                        no_tmux: result_no_tmux,
+                        ..
+                    },
+                ..
+            }) => {
+                assert_eq!(result_branch.as_deref(), Some(branch));
+                assert_eq!(result_repo, Some(PathBuf::from(repo)));
                 assert_eq!(result_codexdir, codexdir.map(PathBuf::from));
                 assert_eq!(result_dry_run, dry_run);
                 assert_eq!(result_no_tmux, no_tmux);
             }
+            other => unexpected(other),
         }
     }
 }
@@ -0,0 +1,154 @@
+//! Session file backups.
+//!
+//! This module copies a matched session file to a timestamped sibling before it is resumed,
+//! pruning the oldest backups so at most a fixed number are kept per session file.
+//!
+//! Backups are named `<file>.<timestamp>.bak`, so they never carry a `.jsonl` extension and
+//! are not picked up by the session scan.
+
+use std::time::SystemTime;
+
+use super::datetime::UtcDateTime;
+use super::prelude::*;
+use super::util;
+
+/// File name suffix used for session backups.
+const BACKUP_SUFFIX: &str = ".bak";
+
+/// Copies `source` to a timestamped sibling and prunes old backups.
+///
+/// # Arguments
+///
+/// * `source` - The session file to back up
+/// * `keep` - Maximum number of backups to keep for this file (including the new one)
+///
+/// # Returns
+///
+/// Returns the path of the newly created backup.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be copied or old backups cannot be removed.
+pub(super) fn backup_session(source: &Path, keep: usize) -> Result<PathBuf> {
+    let (dir, file_name) = split_file_name(source)?;
+    let stamp = UtcDateTime::from_system_time(SystemTime::now()).compact();
+    let backup = dir.join(format!("{file_name}.{stamp}{BACKUP_SUFFIX}"));
+
+    fs::copy(source, &backup).with_context(|| {
+        format!(
+            "failed to copy {} to {}",
+            util::human_path(source),
+            util::human_path(&backup)
+        )
+    })?;
+    prune_backups(dir, &file_name, keep)?;
+    Ok(backup)
+}
+
+/// Splits a path into its parent directory and UTF-8 (lossy) file name.
+fn split_file_name(path: &Path) -> Result<(&Path, String)> {
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("not a file path: {}", util::human_path(path)))?
+        .to_string_lossy()
+        .into_owned();
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    Ok((dir, file_name))
+}
+
+/// Removes the oldest backups of `file_name` in `dir`, keeping the newest `keep`.
+///
+/// Backup names embed a sortable timestamp, so lexicographic order is chronological.
+fn prune_backups(dir: &Path, file_name: &str, keep: usize) -> Result<()> {
+    let prefix = format!("{file_name}.");
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("failed to read directory {}", util::human_path(dir)))?
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with(&prefix) && name.ends_with(BACKUP_SUFFIX)
+        })
+        .map(|entry| entry.path())
+        .collect();
+    backups.sort();
+
+    let excess = backups.len().saturating_sub(keep);
+    for old in &backups[..excess] {
+        debug!(path = %util::human_path(old), "pruning old session backup");
+        fs::remove_file(old)
+            .with_context(|| format!("failed to remove old backup {}", util::human_path(old)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::temp_dir;
+    use rstest::rstest;
+
+    fn backups_in(dir: &Path) -> Vec<PathBuf> {
+        let mut paths: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.to_string_lossy().ends_with(BACKUP_SUFFIX))
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[rstest]
+    fn copies_session_to_timestamped_sibling(#[from(temp_dir)] dir: PathBuf) {
+        let source = dir.join("rollout-a.jsonl");
+        fs::write(&source, "{\"payload\":{}}\n").unwrap();
+
+        let backup = backup_session(&source, 3).expect("backup should succeed");
+
+        assert_eq!(backup.parent(), Some(dir.as_path()));
+        let name = backup.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(name.starts_with("rollout-a.jsonl."), "got {name}");
+        assert!(name.ends_with(".bak"), "got {name}");
+        assert_ne!(backup.extension(), Some(OsStr::new("jsonl")));
+        assert_eq!(fs::read(&backup).unwrap(), fs::read(&source).unwrap());
+    }
+
+    #[rstest]
+    fn prunes_oldest_backups_beyond_keep(#[from(temp_dir)] dir: PathBuf) {
+        let source = dir.join("rollout-a.jsonl");
+        fs::write(&source, "line\n").unwrap();
+        for stamp in ["20240101T000000.000Z", "20250101T000000.000Z"] {
+            fs::write(dir.join(format!("rollout-a.jsonl.{stamp}.bak")), "old").unwrap();
+        }
+
+        let newest = backup_session(&source, 2).expect("backup should succeed");
+
+        assert_eq!(
+            backups_in(&dir),
+            [dir.join("rollout-a.jsonl.20250101T000000.000Z.bak"), newest]
+        );
+    }
+
+    #[rstest]
+    fn leaves_other_sessions_backups_alone(#[from(temp_dir)] dir: PathBuf) {
+        let source = dir.join("rollout-a.jsonl");
+        fs::write(&source, "line\n").unwrap();
+        let other = dir.join("rollout-b.jsonl.20240101T000000.000Z.bak");
+        fs::write(&other, "other").unwrap();
+
+        backup_session(&source, 1).expect("backup should succeed");
+
+        assert!(other.exists());
+    }
+
+    #[rstest]
+    fn fails_for_missing_source(#[from(temp_dir)] dir: PathBuf) {
+        let result = backup_session(&dir.join("missing.jsonl"), 3);
+
+        assert!(result.is_err());
+    }
+}
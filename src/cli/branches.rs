@@ -0,0 +1,179 @@
+//! Branch listing.
+//!
+//! This module implements the `branches` subcommand, which lists the branches that have
+//! sessions, most recent session first. `--porcelain` is the fast path for shell completion:
+//! the scan gets a time budget of [`PORCELAIN_BUDGET`] and the output format is stable.
+//!
+//! ## Porcelain Format
+//!
+//! One branch name per line, most recent session first, with no header, no colors, and
+//! nothing else on stdout (logs go to stderr). Names containing a line break are left out.
+//! The exit code is 0 for a complete listing and [`EXIT_TRUNCATED`] when the budget ran out
+//! first, in which case the lines printed are still valid but branches may be missing.
+//!
+//! ## Capped Listing
+//!
+//! `--max-matches N` lists at most N branches. The scan stops as soon as one more branch turns
+//! up, logs a notice, and exits with [`EXIT_TRUNCATED`] too.
+//!
+//! ## Cached Listing
+//!
+//! With `--cached`, the branches come from the session index (see [`index`]) and the session
+//! tree is not read at all. The index's age is logged, and the table ends with an `as of ...
+//! ago` line; the porcelain output is unchanged. `--max-age` rebuilds an older index first.
+
+use std::io::Write;
+use std::time::Instant;
+
+use super::args::BranchesArgs;
+use super::config;
+use super::index;
+use super::pick;
+use super::prelude::*;
+use super::scan::{self, BranchList};
+use super::util;
+
+/// How long `--porcelain` scans before returning what it has found.
+const PORCELAIN_BUDGET: Duration = Duration::from_millis(200);
+
+/// Handles the `branches` subcommand.
+///
+/// # Errors
+///
+/// Returns an error if the codexdir is invalid or stdout cannot be written.
+pub(super) fn run_branches(args: BranchesArgs) -> Result<ExitCode> {
+    let codexdir = args
+        .location
+        .codexdir
+        .map(Ok)
+        .unwrap_or_else(config::default_codexdir)?;
+    util::require_dir(&codexdir, "codexdir", Some("CODEX_CODEXDIR"))?;
+
+    let now = SystemTime::now();
+    let (list, as_of) = if args.cached {
+        let index = index::load_or_build(&codexdir, args.max_age)?;
+        let age = index.age(now);
+        info!("branches as of {} ago", pick::format_age(age));
+        let mut list = index.branches();
+        list.cap(args.max_matches);
+        (list, Some(age))
+    } else {
+        let deadline = args.porcelain.then(|| Instant::now() + PORCELAIN_BUDGET);
+        (
+            scan::recent_branches(&codexdir, deadline, args.max_matches)?,
+            None,
+        )
+    };
+    let mut out = io::stdout().lock();
+    if args.porcelain {
+        write_porcelain(&mut out, &list)
+    } else {
+        write_table(&mut out, &list, now, as_of)
+    }
+    .context("failed to write the branches")?;
+
+    if list.truncated && args.max_matches == Some(list.branches.len()) {
+        warn!(
+            max_matches = list.branches.len(),
+            "stopped at --max-matches; more branches have sessions"
+        );
+        return Ok(ExitCode::from(EXIT_TRUNCATED));
+    }
+    if list.truncated {
+        debug!(found = list.branches.len(), "branch scan ran out of time");
+        return Ok(ExitCode::from(EXIT_TRUNCATED));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the porcelain format: one branch per line.
+fn write_porcelain(out: &mut impl Write, list: &BranchList) -> io::Result<()> {
+    for (branch, _) in &list.branches {
+        if !branch.contains(['\n', '\r']) {
+            writeln!(out, "{branch}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints a table of branches and the age of their newest session, followed by the index's
+/// age (`as_of`) when the listing came from the index.
+fn write_table(
+    out: &mut impl Write,
+    list: &BranchList,
+    now: SystemTime,
+    as_of: Option<Duration>,
+) -> io::Result<()> {
+    writeln!(out, "{:<6} BRANCH", "LAST")?;
+    for (branch, modified) in &list.branches {
+        let age = modified
+            .and_then(|m| now.duration_since(m).ok())
+            .map_or_else(|| "?".to_owned(), pick::format_age);
+        writeln!(out, "{age:<6} {branch}")?;
+    }
+    if let Some(age) = as_of {
+        writeln!(out, "as of {} ago", pick::format_age(age))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(branches: &[(&str, Option<SystemTime>)]) -> BranchList {
+        BranchList {
+            branches: branches
+                .iter()
+                .map(|&(name, modified)| (name.to_owned(), modified))
+                .collect(),
+            truncated: false,
+        }
+    }
+
+    #[test]
+    fn porcelain_prints_only_names() {
+        let list = list(&[("main", None), ("bad\nname", None), ("feature/a", None)]);
+        let mut out = Vec::new();
+
+        write_porcelain(&mut out, &list).unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), "main\nfeature/a\n");
+    }
+
+    #[test]
+    fn table_shows_ages() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(100_000);
+        let list = list(&[
+            ("main", Some(now - Duration::from_secs(7_200))),
+            ("old", None),
+        ]);
+        let mut out = Vec::new();
+
+        write_table(&mut out, &list, now, None).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "LAST   BRANCH\n2h     main\n?      old\n"
+        );
+    }
+
+    #[test]
+    fn cached_table_ends_with_the_index_age() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(100_000);
+        let mut out = Vec::new();
+
+        write_table(
+            &mut out,
+            &list(&[("main", None)]),
+            now,
+            Some(Duration::from_secs(720)),
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "LAST   BRANCH\n?      main\nas of 12m ago\n"
+        );
+    }
+}
@@ -0,0 +1,456 @@
+//! Last-hit cache.
+//!
+//! This module remembers, per branch, the session file that last matched, so repeated
+//! invocations (such as a prompt widget calling `amg which` on every render) can skip the full
+//! scan. A cached hit is only trusted after re-validating that one file: it must still be under
+//! the codexdir, have the same modification time, and its first line must still match the
+//! branch. It must also have been found with the same filters ([`ScanOptions::cache_key`]): a
+//! hit found with `--under` may not be the first match of a plain lookup. And a walk must
+//! still visit it (see [`scan::walk_visits`]), so a path since excluded by `--exclude` or
+//! `.amgignore` is not reused. Otherwise amg falls back to a full scan and refreshes the entry.
+//!
+//! The cache lives at `$XDG_CACHE_HOME/amg/last-hit.toml`, falling back to
+//! `$HOME/.cache/amg/last-hit.toml`. Cache problems are never fatal: an unreadable cache is
+//! ignored and a failed write is logged at debug level.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use toml_edit::DocumentMut;
+
+use super::prelude::*;
+use super::scan::{self, ScanOptions, ScanOutcome, ScanStats, Session};
+use super::{policy, usage, util};
+
+/// Cached hits keyed by branch name.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct HitCache {
+    hits: BTreeMap<String, CachedHit>,
+}
+
+/// The session file that last matched a branch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CachedHit {
+    /// The matched session file.
+    path: PathBuf,
+    /// Its modification time (nanoseconds since the Unix epoch) when it matched.
+    mtime_ns: i64,
+    /// The [`ScanOptions::cache_key`] of the lookup that found it.
+    filters: String,
+}
+
+impl HitCache {
+    /// Loads the cache from `path`, returning an empty cache if the file does not exist.
+    fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => Self::parse(&content)
+                .with_context(|| format!("invalid cache file {}", util::human_path(path))),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err)
+                .with_context(|| format!("failed to read cache file {}", util::human_path(path))),
+        }
+    }
+
+    fn parse(content: &str) -> Result<Self> {
+        let doc: DocumentMut = content.parse()?;
+        let mut cache = Self::default();
+        let Some(hits) = doc.get("hits") else {
+            return Ok(cache);
+        };
+        let hits = hits.as_table_like().context("`hits` must be a table")?;
+        for (branch, item) in hits.iter() {
+            let entry = item.as_table_like();
+            let path = entry.and_then(|t| t.get("path")).and_then(|v| v.as_str());
+            let mtime_ns = entry
+                .and_then(|t| t.get("mtime_ns"))
+                .and_then(|v| v.as_integer());
+            let filters = entry
+                .and_then(|t| t.get("filters"))
+                .and_then(|v| v.as_str())
+                .unwrap_or_default();
+            let (Some(path), Some(mtime_ns)) = (path, mtime_ns) else {
+                bail!("malformed entry for branch {branch:?}");
+            };
+            cache.hits.insert(
+                branch.to_owned(),
+                CachedHit {
+                    path: PathBuf::from(path),
+                    mtime_ns,
+                    filters: filters.to_owned(),
+                },
+            );
+        }
+        Ok(cache)
+    }
+
+    /// Renders the cache as TOML.
+    fn to_toml(&self) -> String {
+        let mut out = String::from("# amg last-hit cache; safe to delete.\n");
+        for (branch, hit) in &self.hits {
+            let path = hit.path.to_string_lossy();
+            let _ = write!(
+                out,
+                "\n[hits.{}]\npath = {}\nmtime_ns = {}\n",
+                toml_string(branch),
+                toml_string(&path),
+                hit.mtime_ns
+            );
+            if !hit.filters.is_empty() {
+                let _ = writeln!(out, "filters = {}", toml_string(&hit.filters));
+            }
+        }
+        out
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        util::write_atomic(path, &self.to_toml())
+    }
+
+    /// Returns the cached session for `branch` if it is still valid.
+    fn validated_hit(
+        &self,
+        codexdir: &Path,
+        branch: &str,
+        options: &ScanOptions,
+    ) -> Option<Session> {
+        let hit = self.hits.get(branch)?;
+        if !scan::walk_visits(codexdir, &hit.path, options)
+            || mtime_ns(&hit.path) != Some(hit.mtime_ns)
+        {
+            return None;
+        }
+        if hit.filters != options.cache_key() {
+            debug!(branch = %branch, "the cached hit was found with other filters");
+            return None;
+        }
+        scan::session_from_jsonl(hit.path.clone(), branch, options)
+            .filter(|session| options.admits(session))
+    }
+
+    /// Records `path` as the hit for `branch` found with `options`, returning whether the cache
+    /// changed.
+    ///
+    /// Paths that are not valid UTF-8 or whose modification time is unavailable are not cached.
+    fn record(&mut self, branch: &str, path: &Path, options: &ScanOptions) -> bool {
+        let (Some(_), Some(mtime_ns)) = (path.to_str(), mtime_ns(path)) else {
+            return false;
+        };
+        let hit = CachedHit {
+            path: path.to_owned(),
+            mtime_ns,
+            filters: options.cache_key(),
+        };
+        self.hits.insert(branch.to_owned(), hit.clone()) != Some(hit)
+    }
+}
+
+/// Finds the first session matching `branch`, consulting the last-hit cache when enabled.
+///
+/// # Arguments
+///
+/// * `codexdir` - The Codex directory to search in
+/// * `branch` - The git branch name to match against
+/// * `options` - Options narrowing which files count as sessions
+/// * `use_cache` - Whether to read and update the last-hit cache
+///
+/// # Errors
+///
+/// Returns an error if the full scan fails. Cache errors are logged and ignored.
+pub(super) fn find_session(
+    codexdir: &Path,
+    branch: &str,
+    options: &ScanOptions,
+    use_cache: bool,
+) -> Result<ScanOutcome> {
+    let cache_file = use_cache.then(cache_path).flatten();
+    let outcome = find_session_with_cache(cache_file.as_deref(), codexdir, branch, options)?;
+    usage::note_scan(&outcome.stats, outcome.session.is_some());
+    Ok(outcome)
+}
+
+fn find_session_with_cache(
+    cache_file: Option<&Path>,
+    codexdir: &Path,
+    branch: &str,
+    options: &ScanOptions,
+) -> Result<ScanOutcome> {
+    let Some(cache_file) = cache_file else {
+        return scan::find_first_session(codexdir, branch, options);
+    };
+    let mut cache = HitCache::load(cache_file).unwrap_or_else(|err| {
+        debug!("ignoring last-hit cache: {err:#}");
+        HitCache::default()
+    });
+
+    if let Some(session) = cache.validated_hit(codexdir, branch, options) {
+        debug!(branch = %branch, path = %util::human_path(&session.source_jsonl), "using cached hit");
+        // Only the cached file was read.
+        return Ok(ScanOutcome {
+            session: Some(session),
+            stats: ScanStats {
+                files_scanned: 1,
+                files_parsed: 1,
+                ..ScanStats::default()
+            },
+        });
+    }
+
+    let outcome = scan::find_first_session(codexdir, branch, options)?;
+    if let Some(session) = &outcome.session
+        && cache.record(branch, &session.source_jsonl, options)
+        && let Err(err) = cache.save(cache_file)
+    {
+        debug!("failed to update last-hit cache: {err:#}");
+    }
+    Ok(outcome)
+}
+
+/// Returns the modification time of the file cached as `branch`'s last hit in `cache_file`,
+/// without re-validating its first line or scanning.
+///
+/// The entry only has to be under `codexdir` and its file still present. Returns `None` when
+/// there is no such entry or the cache cannot be read.
+pub(super) fn cached_hit_modified(
+    cache_file: &Path,
+    codexdir: &Path,
+    branch: &str,
+) -> Option<SystemTime> {
+    let cache = match HitCache::load(cache_file) {
+        Ok(cache) => cache,
+        Err(err) => {
+            debug!("ignoring last-hit cache: {err:#}");
+            return None;
+        }
+    };
+    let hit = cache.hits.get(branch)?;
+    if !hit.path.starts_with(codexdir) {
+        return None;
+    }
+    fs::metadata(&hit.path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// Resolves the last-hit cache path, if a location can be determined.
+fn cache_path() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join(LAST_HIT_CACHE_FILE))
+}
+
+/// Resolves amg's cache directory, `$XDG_CACHE_HOME/amg` or `$HOME/.cache/amg`, if a
+/// location can be determined (never in portable mode).
+pub(super) fn cache_dir() -> Option<PathBuf> {
+    if policy::is_portable() {
+        return None;
+    }
+    util::non_empty_env(ENV_XDG_CACHE_HOME)
+        .or_else(|| util::non_empty_env(ENV_HOME).map(|home| home.join(".cache")))
+        .map(|dir| dir.join(AMG_DIR))
+}
+
+fn mtime_ns(path: &Path) -> Option<i64> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    let since_epoch = modified.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+    i64::try_from(since_epoch.as_nanos()).ok()
+}
+
+/// Renders `value` as a TOML basic string.
+fn toml_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04X}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::{SessionFile, temp_dir};
+    use rstest::rstest;
+
+    const ID: &str = "0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b";
+    const OTHER_ID: &str = "0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6c";
+
+    fn set_mtime(path: &Path, secs: u64) {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    fn cached(codexdir: &Path, cache_file: &Path, branch: &str) -> Option<PathBuf> {
+        cached_with(codexdir, cache_file, branch, &ScanOptions::default())
+    }
+
+    fn cached_with(
+        codexdir: &Path,
+        cache_file: &Path,
+        branch: &str,
+        options: &ScanOptions,
+    ) -> Option<PathBuf> {
+        find_session_with_cache(Some(cache_file), codexdir, branch, options)
+            .unwrap()
+            .session
+            .map(|s| s.source_jsonl)
+    }
+
+    #[rstest]
+    #[case("main")]
+    #[case("feature/quote\"and\\backslash")]
+    #[case("weird\nnewline ünïcode")]
+    fn round_trips_through_toml(#[case] branch: &str) {
+        let mut cache = HitCache::default();
+        cache.hits.insert(
+            branch.to_owned(),
+            CachedHit {
+                path: PathBuf::from("/codex/rollout-\"x\".jsonl"),
+                mtime_ns: 1_760_000_000_123_456_789,
+                filters: "owner=1000\nexclude=a \"b\"".to_owned(),
+            },
+        );
+
+        assert_eq!(HitCache::parse(&cache.to_toml()).unwrap(), cache);
+    }
+
+    #[rstest]
+    #[case("hits = 3")]
+    #[case("[hits.main]\npath = \"/x\"")]
+    #[case("[hits")]
+    fn rejects_malformed_caches(#[case] content: &str) {
+        assert!(HitCache::parse(content).is_err());
+    }
+
+    #[rstest]
+    fn writes_cache_after_full_scan_and_reuses_it(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = dir.join("codex");
+        let cache_file = dir.join("cache/last-hit.toml");
+        let session =
+            SessionFile::new("main", ID).write(codexdir.join(SESSIONS_DIR).join("rollout-a.jsonl"));
+
+        assert_eq!(
+            cached(&codexdir, &cache_file, "main"),
+            Some(session.clone())
+        );
+
+        let cache = HitCache::load(&cache_file).unwrap();
+        let hit = cache
+            .validated_hit(&codexdir, "main", &ScanOptions::default())
+            .expect("cached hit should be valid");
+        assert_eq!(hit.source_jsonl, session);
+    }
+
+    #[rstest]
+    fn modified_file_invalidates_hit(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = dir.join("codex");
+        let cache_file = dir.join("last-hit.toml");
+        let session =
+            SessionFile::new("main", ID).write(codexdir.join(SESSIONS_DIR).join("rollout-a.jsonl"));
+        cached(&codexdir, &cache_file, "main");
+
+        set_mtime(&session, 1_000);
+
+        let cache = HitCache::load(&cache_file).unwrap();
+        assert!(
+            cache
+                .validated_hit(&codexdir, "main", &ScanOptions::default())
+                .is_none()
+        );
+        assert_eq!(cached(&codexdir, &cache_file, "main"), Some(session));
+    }
+
+    #[rstest]
+    fn a_filtered_hit_is_not_reused_without_the_filter(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = dir.join("codex");
+        let cache_file = dir.join("last-hit.toml");
+        let project = dir.join("project");
+        fs::create_dir_all(&project).unwrap();
+        let project = fs::canonicalize(project).unwrap();
+        let first =
+            SessionFile::new("main", ID).write(codexdir.join(SESSIONS_DIR).join("rollout-a.jsonl"));
+        let under = SessionFile::new("main", OTHER_ID)
+            .cwd(&project)
+            .write(codexdir.join(SESSIONS_DIR).join("rollout-b.jsonl"));
+        let filtered = ScanOptions {
+            under: Some(project),
+            ..ScanOptions::default()
+        };
+
+        assert_eq!(
+            cached_with(&codexdir, &cache_file, "main", &filtered),
+            Some(under.clone())
+        );
+        assert_eq!(cached(&codexdir, &cache_file, "main"), Some(first));
+        assert_eq!(
+            cached_with(&codexdir, &cache_file, "main", &filtered),
+            Some(under)
+        );
+    }
+
+    #[rstest]
+    fn an_excluded_hit_is_not_reused(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = dir.join("codex");
+        let cache_file = dir.join("last-hit.toml");
+        let day = codexdir.join(SESSIONS_DIR).join("2026/10");
+        let first = SessionFile::new("main", ID).write(day.join("01/rollout-a.jsonl"));
+        let second = SessionFile::new("main", OTHER_ID).write(day.join("02/rollout-b.jsonl"));
+        assert_eq!(cached(&codexdir, &cache_file, "main"), Some(first));
+
+        fs::write(codexdir.join(AMGIGNORE_FILE), "sessions/2026/10/01\n").unwrap();
+
+        assert_eq!(cached(&codexdir, &cache_file, "main"), Some(second));
+    }
+
+    #[rstest]
+    fn falls_back_to_scan_when_cached_file_is_gone(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = dir.join("codex");
+        let cache_file = dir.join("last-hit.toml");
+        let first =
+            SessionFile::new("main", ID).write(codexdir.join(SESSIONS_DIR).join("rollout-a.jsonl"));
+        cached(&codexdir, &cache_file, "main");
+        let second =
+            SessionFile::new("main", ID).write(codexdir.join(SESSIONS_DIR).join("rollout-b.jsonl"));
+
+        fs::remove_file(&first).unwrap();
+
+        assert_eq!(cached(&codexdir, &cache_file, "main"), Some(second));
+    }
+
+    #[rstest]
+    fn ignores_hits_outside_codexdir(#[from(temp_dir)] dir: PathBuf) {
+        let cache_file = dir.join("last-hit.toml");
+        SessionFile::new("main", ID)
+            .write(dir.join("a").join(SESSIONS_DIR).join("rollout-a.jsonl"));
+        cached(&dir.join("a"), &cache_file, "main");
+        fs::create_dir_all(dir.join("b")).unwrap();
+
+        assert_eq!(cached(&dir.join("b"), &cache_file, "main"), None);
+    }
+
+    #[rstest]
+    fn corrupt_cache_is_replaced(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = dir.join("codex");
+        let cache_file = dir.join("last-hit.toml");
+        fs::write(&cache_file, "not [valid toml").unwrap();
+        let session =
+            SessionFile::new("main", ID).write(codexdir.join(SESSIONS_DIR).join("rollout-a.jsonl"));
+
+        assert_eq!(cached(&codexdir, &cache_file, "main"), Some(session));
+        assert!(
+            HitCache::load(&cache_file)
+                .unwrap()
+                .hits
+                .contains_key("main")
+        );
+    }
+}
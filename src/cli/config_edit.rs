@@ -0,0 +1,369 @@
+//! Config file editing.
+//!
+//! This module implements the `config` subcommand, which reads and edits a single config file:
+//! the global one, or a repository's `.amg.toml` with `--repo`. Edits are made on the file's
+//! text, so comments, formatting, and keys amg does not know survive: `set` replaces the value
+//! of an existing entry (or adds one before the first table), and `unset` removes the entry's
+//! line. An edited file is checked with [`Config::parse`], the parser that loads it, before it
+//! atomically replaces the original, so a value of the wrong type never reaches the file.
+
+use toml_edit::{Document, Item};
+
+use super::args::ConfigCommand;
+use super::config::{self, Config, ValueKind};
+use super::prelude::*;
+use super::{output, util};
+
+/// Handles the `config` subcommand.
+///
+/// # Errors
+///
+/// Returns an error if the config file cannot be located, read, parsed, or written, a key to
+/// set is unknown or given a value of the wrong type, or a key to get is not set.
+pub(super) fn run_config(command: ConfigCommand, repo: Option<PathBuf>) -> Result<ExitCode> {
+    let path = match repo {
+        Some(repo) => {
+            util::require_dir(&repo, "--repo", None)?;
+            repo.join(REPO_CONFIG_FILE)
+        }
+        None => config::locate_global_config()?,
+    };
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(err).with_context(|| {
+                format!("failed to read config file {}", util::human_path(&path))
+            });
+        }
+    };
+
+    match command {
+        ConfigCommand::Get { key } => {
+            let doc = parse(&content, &path)?;
+            let item = doc
+                .get(&key)
+                .with_context(|| format!("{key} is not set in {}", util::human_path(&path)))?;
+            for line in display_value(doc.raw(), item) {
+                output::line(format_args!("{line}"))?;
+            }
+        }
+        ConfigCommand::List => {
+            let doc = parse(&content, &path)?;
+            for (key, item) in doc.iter() {
+                if let Some(raw) = raw_value(doc.raw(), item) {
+                    output::line(format_args!("{key} = {raw}"))?;
+                }
+            }
+        }
+        ConfigCommand::Set { key, values } => {
+            let updated = set_value(&content, &path, &key, &values)?;
+            util::write_atomic(&path, &updated)?;
+            info!(path = %util::human_path(&path), key = %key, "set config key");
+        }
+        ConfigCommand::Unset { key } => match unset_value(&content, &path, &key)? {
+            Some(updated) => {
+                util::write_atomic(&path, &updated)?;
+                info!(path = %util::human_path(&path), key = %key, "unset config key");
+            }
+            None => warn!("{key} is not set in {}", util::human_path(&path)),
+        },
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Parses a config file, keeping the byte spans of its entries.
+fn parse<'a>(content: &'a str, path: &Path) -> Result<Document<&'a str>> {
+    Document::parse(content)
+        .with_context(|| format!("invalid config file {}", util::human_path(path)))
+}
+
+/// Returns `content` with `key` set to `values`.
+///
+/// # Errors
+///
+/// Returns an error if the key is unknown, the values do not fit its type, the file is not
+/// valid TOML, or the key is a table.
+pub(super) fn set_value(
+    content: &str,
+    path: &Path,
+    key: &str,
+    values: &[String],
+) -> Result<String> {
+    let kind = config::key_kind(key).with_context(|| {
+        let known: Vec<&str> = config::KEYS.iter().map(|(name, _)| *name).collect();
+        format!(
+            "unknown config key {key:?} (known keys: {})",
+            known.join(", ")
+        )
+    })?;
+    let rendered = render(kind, key, values)?;
+
+    let doc = parse(content, path)?;
+    let mut updated = content.to_owned();
+    match doc.get(key) {
+        Some(item) => {
+            let span = value_span(item)
+                .with_context(|| format!("{key} is a table in {}", util::human_path(path)))?;
+            updated.replace_range(span, &rendered);
+        }
+        None => {
+            let at = insertion_point(&doc);
+            let separator = if content[..at].is_empty() || content[..at].ends_with('\n') {
+                ""
+            } else {
+                "\n"
+            };
+            updated.insert_str(at, &format!("{separator}{key} = {rendered}\n"));
+        }
+    }
+    Config::parse(&updated, path).with_context(|| format!("refusing to set {key}"))?;
+    Ok(updated)
+}
+
+/// Returns `content` without the line declaring `key`, or `None` if the key is not set.
+///
+/// # Errors
+///
+/// Returns an error if the file is not valid TOML or the key is a table.
+fn unset_value(content: &str, path: &Path, key: &str) -> Result<Option<String>> {
+    let doc = parse(content, path)?;
+    let Some((name, item)) = doc.get_key_value(key) else {
+        return Ok(None);
+    };
+    let (Some(name), Some(value)) = (name.span(), value_span(item)) else {
+        bail!("{key} is a table in {}", util::human_path(path));
+    };
+    let start = content[..name.start].rfind('\n').map_or(0, |i| i + 1);
+    let end = content[value.end..]
+        .find('\n')
+        .map_or(content.len(), |i| value.end + i + 1);
+    let mut updated = content.to_owned();
+    updated.replace_range(start..end, "");
+    Ok(Some(updated))
+}
+
+/// Renders command-line values as the TOML value `key` expects.
+fn render(kind: ValueKind, key: &str, values: &[String]) -> Result<String> {
+    let single = || match values {
+        [value] => Ok(value.as_str()),
+        _ => Err(anyhow::anyhow!(
+            "config key {key:?} takes exactly one value, got {}",
+            values.len()
+        )),
+    };
+    Ok(match kind {
+        ValueKind::Boolean => match single()? {
+            value @ ("true" | "false") => value.to_owned(),
+            other => bail!("config key {key:?} must be a boolean (true or false), not {other:?}"),
+        },
+        ValueKind::Integer => {
+            let value = single()?;
+            value
+                .parse::<i32>()
+                .with_context(|| format!("config key {key:?} must be an integer, not {value:?}"))?
+                .to_string()
+        }
+        ValueKind::String => toml_string(single()?),
+        ValueKind::StringList => toml_array(values),
+        ValueKind::Command => match values {
+            [line] => toml_string(line),
+            _ => toml_array(values),
+        },
+    })
+}
+
+/// Quotes a value as a TOML basic string.
+fn toml_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04X}", u32::from(c))),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Renders values as a TOML array of strings.
+fn toml_array(values: &[String]) -> String {
+    let items: Vec<String> = values.iter().map(|value| toml_string(value)).collect();
+    format!("[{}]", items.join(", "))
+}
+
+/// Returns the span of a plain value (`None` for a table).
+fn value_span(item: &Item) -> Option<std::ops::Range<usize>> {
+    item.is_value().then(|| item.span()).flatten()
+}
+
+/// Returns a value as written in the file (`None` for a table).
+fn raw_value<'a>(raw: &'a str, item: &Item) -> Option<&'a str> {
+    value_span(item).and_then(|span| raw.get(span))
+}
+
+/// Returns the lines `config get` prints: a string unquoted, an array of strings one element
+/// per line, anything else as written in the file.
+fn display_value(raw: &str, item: &Item) -> Vec<String> {
+    if let Some(value) = item.as_str() {
+        return vec![value.to_owned()];
+    }
+    if let Some(array) = item.as_array()
+        && array.iter().all(|value| value.is_str())
+    {
+        return array
+            .iter()
+            .filter_map(|value| value.as_str())
+            .map(str::to_owned)
+            .collect();
+    }
+    raw_value(raw, item)
+        .map(str::to_owned)
+        .into_iter()
+        .collect()
+}
+
+/// Returns where a new top-level entry goes: before the first table header, or at the end.
+fn insertion_point(doc: &Document<&str>) -> usize {
+    let raw = doc.raw();
+    if doc.iter().all(|(_, item)| item.is_value()) {
+        return raw.len();
+    }
+    let values: Vec<_> = doc
+        .iter()
+        .filter_map(|(_, item)| value_span(item))
+        .collect();
+    let mut offset = 0;
+    for line in raw.split_inclusive('\n') {
+        if line.trim_start().starts_with('[') && !values.iter().any(|span| span.contains(&offset)) {
+            return offset;
+        }
+        offset += line.len();
+    }
+    raw.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const PATH: &str = "/cfg/config.toml";
+
+    fn set(content: &str, key: &str, values: &[&str]) -> Result<String> {
+        let values: Vec<String> = values.iter().map(|v| (*v).to_owned()).collect();
+        set_value(content, Path::new(PATH), key, &values)
+    }
+
+    #[test]
+    fn replaces_a_value_and_keeps_comments() {
+        let content = "# mine\nwrite_marker = true # inline\nfuture_key = 1\n";
+
+        let updated = set(content, "write_marker", &["false"]).unwrap();
+
+        assert_eq!(
+            updated,
+            "# mine\nwrite_marker = false # inline\nfuture_key = 1\n"
+        );
+    }
+
+    #[rstest]
+    #[case::empty("", "notify_template = \"hi\"\n")]
+    #[case::no_newline(
+        "write_marker = true",
+        "write_marker = true\nnotify_template = \"hi\"\n"
+    )]
+    #[case::before_tables(
+        "write_marker = true\n\n[other]\nx = 1\n",
+        "write_marker = true\n\nnotify_template = \"hi\"\n[other]\nx = 1\n"
+    )]
+    fn adds_missing_keys(#[case] content: &str, #[case] expected: &str) {
+        assert_eq!(set(content, "notify_template", &["hi"]).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::boolean("write_marker", &["banana"], "must be a boolean")]
+    #[case::integer("fallback_resume_exit_code", &["x"], "must be an integer")]
+    #[case::arity("notify_template", &["a", "b"], "exactly one value")]
+    #[case::unknown("no_tmux", &["true"], "unknown config key")]
+    #[case::loader("wrap_command", &["docker 'exec"], "unterminated single quote")]
+    fn rejects_values_the_loader_would_reject(
+        #[case] key: &str,
+        #[case] values: &[&str],
+        #[case] expected: &str,
+    ) {
+        let err = set("", key, values).unwrap_err();
+
+        assert!(format!("{err:#}").contains(expected), "{err:#}");
+    }
+
+    #[rstest]
+    #[case::list("extra_add_dirs", &["/a", "${repo}/b"], "[\"/a\", \"${repo}/b\"]")]
+    #[case::empty_list("extra_add_dirs", &[], "[]")]
+    #[case::command_line("wrap_command", &["docker exec -it devbox"], "\"docker exec -it devbox\"")]
+    #[case::command_argv("wrap_command", &["docker", "exec"], "[\"docker\", \"exec\"]")]
+    #[case::escaped("notify_template", &["say \"hi\"\\\n"], "\"say \\\"hi\\\"\\\\\\n\"")]
+    fn renders_values(#[case] key: &str, #[case] values: &[&str], #[case] expected: &str) {
+        assert_eq!(
+            set("", key, values).unwrap(),
+            format!("{key} = {expected}\n")
+        );
+    }
+
+    #[test]
+    fn rendered_strings_round_trip() {
+        let value = "tab\there \"quoted\" \\ \u{1} ünï";
+        let updated = set("", "notify_template", &[value]).unwrap();
+
+        let config = Config::parse(&updated, Path::new(PATH)).unwrap();
+
+        assert_eq!(config.notify_template.unwrap().value, value);
+    }
+
+    #[rstest]
+    #[case::middle(
+        "write_marker",
+        "a = 1\nwrite_marker = true\nb = 2\n",
+        "a = 1\nb = 2\n"
+    )]
+    #[case::no_newline("write_marker", "a = 1\nwrite_marker = true", "a = 1\n")]
+    #[case::multiline(
+        "extra_add_dirs",
+        "extra_add_dirs = [\n  \"/a\",\n]\n# keep\n",
+        "# keep\n"
+    )]
+    fn removes_the_whole_entry(#[case] key: &str, #[case] content: &str, #[case] expected: &str) {
+        let updated = unset_value(content, Path::new(PATH), key).unwrap();
+
+        assert_eq!(updated.as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn unsetting_a_missing_key_changes_nothing() {
+        assert_eq!(
+            unset_value("a = 1\n", Path::new(PATH), "write_marker").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn shows_values_for_get_and_list() {
+        let content = "notify_template = \"hi\"\nextra_add_dirs = [\"/a\", \"/b\"]\nx = 3\n";
+        let doc = parse(content, Path::new(PATH)).unwrap();
+        let shown = |key: &str| display_value(doc.raw(), doc.get(key).unwrap());
+
+        assert_eq!(shown("notify_template"), ["hi"]);
+        assert_eq!(shown("extra_add_dirs"), ["/a", "/b"]);
+        assert_eq!(shown("x"), ["3"]);
+        assert_eq!(
+            raw_value(doc.raw(), doc.get("extra_add_dirs").unwrap()),
+            Some("[\"/a\", \"/b\"]")
+        );
+    }
+}
@@ -6,15 +6,101 @@
 /// Default Codex directory name (relative to home directory).
 pub(super) const DOT_CODEX_DIR: &str = ".codex";
 
+/// Directory holding session files inside a Codex home.
+#[cfg(feature = "cli")]
+pub(super) const SESSIONS_DIR: &str = "sessions";
+
+/// Minimum length of a session id prefix accepted by `resume --id`.
+#[cfg(feature = "cli")]
+pub(super) const MIN_ID_PREFIX_LEN: usize = 4;
+
+/// Maximum number of candidates listed when a session id prefix is ambiguous.
+#[cfg(feature = "cli")]
+pub(super) const MAX_LISTED_ID_MATCHES: usize = 10;
+
 /// Git directory name.
 pub(super) const DOT_GIT: &str = ".git";
 
+/// Maximum number of parent directories searched for the worktree root of a `--repo` that
+/// has no `.git` entry of its own.
+pub(super) const MAX_REPO_DISCOVERY_DEPTH: usize = 16;
+
 /// Environment variable name for the home directory.
 pub(super) const ENV_HOME: &str = "HOME";
 
+/// Environment variable name overriding the amg state file location.
+#[cfg(feature = "cli")]
+pub(super) const ENV_AMG_STATE_FILE: &str = "AMG_STATE_FILE";
+
+/// Environment variable name for the XDG data directory.
+#[cfg(feature = "cli")]
+pub(super) const ENV_XDG_DATA_HOME: &str = "XDG_DATA_HOME";
+
+/// Directory name used for amg's own files under XDG base directories.
+#[cfg(feature = "cli")]
+pub(super) const AMG_DIR: &str = "amg";
+
+/// File name of the amg state file.
+#[cfg(feature = "cli")]
+pub(super) const STATE_FILE: &str = "state.json";
+
+/// Environment variable name for the XDG cache directory.
+#[cfg(feature = "cli")]
+pub(super) const ENV_XDG_CACHE_HOME: &str = "XDG_CACHE_HOME";
+
+/// File name of the last-hit cache (under `$XDG_CACHE_HOME/amg`).
+#[cfg(feature = "cli")]
+pub(super) const LAST_HIT_CACHE_FILE: &str = "last-hit.toml";
+
+/// File name of the session index (under `$XDG_CACHE_HOME/amg`).
+#[cfg(feature = "cli")]
+pub(super) const SESSION_INDEX_FILE: &str = "index.json";
+
+/// Environment variable name overriding the global config file location.
+#[cfg(feature = "cli")]
+pub(super) const ENV_AMG_CONFIG: &str = "AMG_CONFIG";
+
+/// Environment variable name for the XDG config directory.
+#[cfg(feature = "cli")]
+pub(super) const ENV_XDG_CONFIG_HOME: &str = "XDG_CONFIG_HOME";
+
+/// File name of the global config file (under `$XDG_CONFIG_HOME/amg`).
+#[cfg(feature = "cli")]
+pub(super) const CONFIG_FILE: &str = "config.toml";
+
+/// File name of the exclusion file read from the top of the codexdir.
+pub(super) const AMGIGNORE_FILE: &str = ".amgignore";
+
+/// File name of the per-repo config file (at the repo root).
+#[cfg(feature = "cli")]
+pub(super) const REPO_CONFIG_FILE: &str = ".amg.toml";
+
+/// Environment variable name for the default Codex directory.
+#[cfg(feature = "cli")]
+pub(super) const ENV_CODEX_CODEXDIR: &str = "CODEX_CODEXDIR";
+
+/// Log file codex writes inside its home directory.
+pub(super) const CODEX_TUI_LOG: &str = "log/codex-tui.log";
+
+/// Environment variable codex reads its home directory from.
+#[cfg(feature = "cli")]
+pub(super) const ENV_CODEX_HOME: &str = "CODEX_HOME";
+
+/// Model codex is started with unless a config file sets `model`.
+pub(super) const DEFAULT_MODEL: &str = "gpt-5.2-codex";
+
+/// Environment variable name for the executable search path.
+#[cfg(feature = "cli")]
+pub(super) const ENV_PATH: &str = "PATH";
+
 /// Environment variable name for tmux session detection.
+#[cfg(feature = "cli")]
 pub(super) const ENV_TMUX: &str = "TMUX";
 
+/// Name of the session `--tmux always` creates when no tmux server is running.
+#[cfg(feature = "cli")]
+pub(super) const TMUX_DETACHED_SESSION: &str = "amg";
+
 /// Home directory subdirectories to include in Codex sandbox.
 ///
 /// These directories are added to the sandbox if they exist in the user's home directory.
@@ -29,3 +115,28 @@ pub(super) const HOME_SANDBOX_DIRS: [&str; 4] = [
 ///
 /// These directories are added to the sandbox if they exist.
 pub(super) const EXTRA_SANDBOX_DIRS: [&str; 2] = ["/tmp", "/var/folders"];
+
+/// Exit code used when the user cancels an interactive prompt (as for Ctrl-C).
+#[cfg(feature = "cli")]
+pub(super) const EXIT_CANCELLED: u8 = 130;
+
+/// Exit code when an inline codex run was stopped by `--timeout` (as for coreutils `timeout`).
+#[cfg(feature = "cli")]
+pub(super) const EXIT_TIMED_OUT: u8 = 124;
+
+/// Exit code of `amg branches` and `amg dump` when the listing was cut short: the porcelain
+/// time budget ran out before the scan ended, or `--max-matches` was reached.
+#[cfg(feature = "cli")]
+pub(super) const EXIT_TRUNCATED: u8 = 3;
+
+/// Exit code of `amg which --age-only` when the branch has no session.
+#[cfg(feature = "cli")]
+pub(super) const EXIT_NO_SESSION: u8 = 2;
+
+/// Exit code for a failure of amg itself (as [`std::process::ExitCode::FAILURE`]).
+#[cfg(feature = "cli")]
+pub(super) const EXIT_FAILURE: u8 = 1;
+
+/// Base for amg's own failure codes when `--exit-code-offset` is given without a value.
+#[cfg(feature = "cli")]
+pub(super) const DEFAULT_EXIT_CODE_OFFSET: &str = "100";
@@ -0,0 +1,181 @@
+//! Minimal UTC calendar conversion.
+//!
+//! This module converts [`SystemTime`] values into UTC calendar fields without pulling in a
+//! date/time dependency. It is used for timestamped file names and RFC 3339 output.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A point in time broken down into UTC calendar fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct UtcDateTime {
+    pub(super) year: i64,
+    pub(super) month: u32,
+    pub(super) day: u32,
+    pub(super) hour: u32,
+    pub(super) minute: u32,
+    pub(super) second: u32,
+    pub(super) millis: u32,
+}
+
+impl UtcDateTime {
+    /// Converts a [`SystemTime`] into UTC calendar fields.
+    ///
+    /// Times before the Unix epoch are clamped to the epoch.
+    pub(super) fn from_system_time(time: SystemTime) -> Self {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs = since_epoch.as_secs();
+        let days = i64::try_from(secs / 86_400).unwrap_or(i64::MAX);
+        let secs_of_day = secs % 86_400;
+        let (year, month, day) = civil_from_days(days);
+
+        Self {
+            year,
+            month,
+            day,
+            hour: (secs_of_day / 3_600) as u32,
+            minute: (secs_of_day % 3_600 / 60) as u32,
+            second: (secs_of_day % 60) as u32,
+            millis: since_epoch.subsec_millis(),
+        }
+    }
+
+    /// Parses the leading `YYYY-MM-DDTHH:MM:SS` of an RFC 3339 timestamp.
+    ///
+    /// Fractional seconds and the UTC offset are ignored, so the fields are taken as written.
+    /// Returns `None` if the prefix is malformed or out of range.
+    pub(super) fn parse_rfc3339(value: &str) -> Option<Self> {
+        let bytes = value.as_bytes();
+        let separators = [(4, b'-'), (7, b'-'), (13, b':'), (16, b':')];
+        if bytes.len() < 19
+            || !matches!(bytes[10], b'T' | b't' | b' ')
+            || separators.iter().any(|&(at, sep)| bytes[at] != sep)
+        {
+            return None;
+        }
+        let field = |from: usize, to: usize| {
+            let digits = value.get(from..to)?;
+            digits
+                .bytes()
+                .all(|b| b.is_ascii_digit())
+                .then(|| digits.parse::<u32>().ok())?
+        };
+        let parsed = Self {
+            year: i64::from(field(0, 4)?),
+            month: field(5, 7).filter(|m| (1..=12).contains(m))?,
+            day: field(8, 10).filter(|d| (1..=31).contains(d))?,
+            hour: field(11, 13).filter(|h| *h < 24)?,
+            minute: field(14, 16).filter(|m| *m < 60)?,
+            second: field(17, 19).filter(|s| *s <= 60)?,
+            millis: 0,
+        };
+        Some(parsed)
+    }
+
+    /// Formats as an RFC 3339 timestamp with second precision (`2026-10-14T09:15:30Z`).
+    pub(super) fn rfc3339(&self) -> String {
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+
+    /// Formats as a compact, lexicographically sortable stamp (`20261014T091530.250Z`).
+    ///
+    /// Safe to embed in file names on all platforms.
+    pub(super) fn compact(&self) -> String {
+        format!(
+            "{:04}{:02}{:02}T{:02}{:02}{:02}.{:03}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, self.millis
+        )
+    }
+}
+
+/// Converts days since the Unix epoch into a proleptic Gregorian `(year, month, day)`.
+///
+/// Howard Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::time::Duration;
+
+    fn at(secs: u64, millis: u64) -> UtcDateTime {
+        UtcDateTime::from_system_time(
+            UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(millis),
+        )
+    }
+
+    #[rstest]
+    #[case(0, (1970, 1, 1))]
+    #[case(951_782_400, (2000, 2, 29))]
+    #[case(1_709_164_800, (2024, 2, 29))]
+    #[case(1_735_689_599, (2024, 12, 31))]
+    #[case(1_792_000_000, (2026, 10, 14))]
+    fn converts_dates(#[case] secs: u64, #[case] expected: (i64, u32, u32)) {
+        let dt = at(secs, 0);
+        assert_eq!((dt.year, dt.month, dt.day), expected);
+    }
+
+    #[test]
+    fn converts_time_of_day() {
+        let dt = at(1_735_689_599, 250);
+        assert_eq!(
+            (dt.hour, dt.minute, dt.second, dt.millis),
+            (23, 59, 59, 250)
+        );
+    }
+
+    #[test]
+    fn rfc3339_format_drops_subseconds() {
+        assert_eq!(at(1_735_689_599, 250).rfc3339(), "2024-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn compact_format_is_zero_padded() {
+        assert_eq!(at(0, 7).compact(), "19700101T000000.007Z");
+    }
+
+    #[test]
+    fn compact_format_sorts_chronologically() {
+        let earlier = at(1_709_164_800, 999).compact();
+        let later = at(1_709_164_801, 0).compact();
+        assert!(earlier < later);
+    }
+
+    #[rstest]
+    #[case("2025-10-14T12:34:56.789Z", Some((2025, 10, 14, 12, 34, 56)))]
+    #[case("2025-10-14T12:34:56+02:00", Some((2025, 10, 14, 12, 34, 56)))]
+    #[case("2025-10-14 00:00:00", Some((2025, 10, 14, 0, 0, 0)))]
+    #[case("2025-13-14T12:34:56Z", None)]
+    #[case("2025-10-14T24:00:00Z", None)]
+    #[case("2025-10-14", None)]
+    #[case("2025-+1-14T12:34:56Z", None)]
+    #[case("not a timestamp at all", None)]
+    fn parses_rfc3339_prefixes(
+        #[case] value: &str,
+        #[case] expected: Option<(i64, u32, u32, u32, u32, u32)>,
+    ) {
+        let parsed = UtcDateTime::parse_rfc3339(value)
+            .map(|dt| (dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.second));
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn clamps_times_before_epoch() {
+        let dt = UtcDateTime::from_system_time(UNIX_EPOCH - Duration::from_secs(10));
+        assert_eq!(dt, at(0, 0));
+    }
+}
@@ -0,0 +1,298 @@
+//! Setup checks.
+//!
+//! This module implements the `doctor` subcommand, which checks the things amg needs before it
+//! can resume anything: a valid global config file, a codexdir that looks like a Codex home,
+//! `codex` on `$PATH`, and `tmux` when tmux windows are wanted. `amg init` runs the same checks
+//! after writing the config. With `--narrow-codexdir`, it also checks whether codex recently
+//! wrote to the codexdir outside what `resume --narrow-codexdir` grants.
+
+use std::io::Write;
+
+use super::args::DoctorArgs;
+use super::config::{self, Config};
+use super::prelude::*;
+use super::{process, scan, util};
+
+/// How far back `--narrow-codexdir` looks for codex writes outside the narrow grants.
+const NARROW_CHECK_WINDOW: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// The outcome of one setup check.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct Check {
+    /// What was checked (`config`, `codexdir`, ...).
+    pub(super) name: &'static str,
+    /// What was found, or the problem to fix.
+    pub(super) result: std::result::Result<String, String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            result: Ok(detail.into()),
+        }
+    }
+
+    fn failed(name: &'static str, problem: impl Into<String>) -> Self {
+        Self {
+            name,
+            result: Err(problem.into()),
+        }
+    }
+}
+
+/// Handles the `doctor` subcommand.
+///
+/// # Errors
+///
+/// Returns an error if stdout cannot be written.
+pub(super) fn run_doctor(args: DoctorArgs) -> Result<ExitCode> {
+    let checks = run_checks(args.narrow_codexdir);
+    write_checks(&mut io::stdout().lock(), &checks).context("failed to write the checks")?;
+    Ok(if checks.iter().all(|check| check.result.is_ok()) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// Runs every check against the current environment and global config, plus the
+/// `--narrow-codexdir` check when `narrow` is set.
+pub(super) fn run_checks(narrow: bool) -> Vec<Check> {
+    let (config_check, config) = match config::locate_global_config() {
+        Ok(path) => check_config(&path),
+        Err(err) => (Check::failed("config", format!("{err:#}")), None),
+    };
+    let codexdir = util::non_empty_env(ENV_CODEX_CODEXDIR)
+        .map(Ok)
+        .unwrap_or_else(|| match config.as_ref().and_then(|c| c.codexdir.as_ref()) {
+            Some(entry) => Ok(PathBuf::from(&entry.value)),
+            None => config::home_codexdir(),
+        });
+    let wants_tmux = config.as_ref().is_none_or(|c| c.tmux != Some(false));
+    let mut checks = vec![
+        config_check,
+        match &codexdir {
+            Ok(codexdir) => check_codexdir(codexdir),
+            Err(err) => Check::failed("codexdir", format!("{err:#}")),
+        },
+        check_program("codex", process::on_path("codex"), true),
+        check_program("tmux", process::on_path("tmux"), wants_tmux),
+    ];
+    if narrow && let Ok(codexdir) = &codexdir {
+        checks.push(check_narrow_grants(codexdir, SystemTime::now()));
+    }
+    checks
+}
+
+/// Checks that the global config file, if any, is valid, returning it for the other checks.
+fn check_config(path: &Path) -> (Check, Option<Config>) {
+    match fs::read_to_string(path) {
+        Ok(content) => match Config::parse(&content, path) {
+            Ok(config) => (Check::ok("config", util::human_path(path)), Some(config)),
+            Err(err) => (Check::failed("config", format!("{err:#}")), None),
+        },
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            let detail = format!(
+                "{} (not created yet; defaults apply)",
+                util::human_path(path)
+            );
+            (Check::ok("config", detail), Some(Config::default()))
+        }
+        Err(err) => (
+            Check::failed(
+                "config",
+                format!("cannot read {}: {err}", util::human_path(path)),
+            ),
+            None,
+        ),
+    }
+}
+
+/// Checks that `codexdir` is a directory that looks like a Codex home.
+fn check_codexdir(codexdir: &Path) -> Check {
+    let shown = util::human_path(codexdir);
+    match scan::probe_codexdir(codexdir) {
+        Ok(scan::Probe::Codex) => Check::ok("codexdir", shown.to_string()),
+        Ok(scan::Probe::Unrecognized {
+            nested: Some(nested),
+        }) => Check::failed(
+            "codexdir",
+            format!(
+                "{shown} has no sessions; did you mean {}?",
+                util::human_path(&nested)
+            ),
+        ),
+        Ok(scan::Probe::Unrecognized { nested: None }) => Check::failed(
+            "codexdir",
+            format!("{shown} has no sessions/ or rollout-*.jsonl yet (has codex been run?)"),
+        ),
+        Err(err) => Check::failed("codexdir", format!("cannot read {shown}: {err}")),
+    }
+}
+
+/// Checks that codex wrote nothing in `codexdir` outside `sessions/` and `log/` during the
+/// [`NARROW_CHECK_WINDOW`] before `now`, since `--narrow-codexdir` would deny such writes.
+///
+/// Only the top level is looked at: a directory's modification time changes when an entry is
+/// created or removed in it, not when a file below it is rewritten.
+fn check_narrow_grants(codexdir: &Path, now: SystemTime) -> Check {
+    let entries = match fs::read_dir(codexdir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            let problem = format!("cannot read {}: {err}", util::human_path(codexdir));
+            return Check::failed("narrow", problem);
+        }
+    };
+    let granted = [
+        Some(OsStr::new(SESSIONS_DIR)),
+        Path::new(CODEX_TUI_LOG).iter().next(),
+    ];
+    let mut written: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !granted.contains(&Some(entry.file_name().as_os_str())))
+        .filter(|entry| {
+            entry
+                .metadata()
+                .and_then(|meta| meta.modified())
+                .is_ok_and(|modified| {
+                    now.duration_since(modified)
+                        .is_ok_and(|age| age <= NARROW_CHECK_WINDOW)
+                })
+        })
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    if written.is_empty() {
+        return Check::ok(
+            "narrow",
+            "codex only wrote to sessions/ and log/ in the last 7 days",
+        );
+    }
+    written.sort();
+    Check::failed(
+        "narrow",
+        format!(
+            "codex wrote {} in {} in the last 7 days; --narrow-codexdir would deny such writes",
+            written.join(", "),
+            util::human_path(codexdir)
+        ),
+    )
+}
+
+/// Checks that `program` is on `$PATH` when it is `needed`.
+fn check_program(name: &'static str, found: bool, needed: bool) -> Check {
+    match (found, needed) {
+        (true, _) => Check::ok(name, "found on $PATH"),
+        (false, false) => Check::ok(name, "not found on $PATH (not needed)"),
+        (false, true) => Check::failed(name, "not found on $PATH"),
+    }
+}
+
+/// Prints one `ok` or `FAIL` line per check.
+pub(super) fn write_checks(out: &mut impl Write, checks: &[Check]) -> io::Result<()> {
+    for check in checks {
+        match &check.result {
+            Ok(detail) => writeln!(out, "ok    {:<9} {detail}", check.name)?,
+            Err(problem) => writeln!(out, "FAIL  {:<9} {problem}", check.name)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::temp_dir;
+    use rstest::rstest;
+
+    #[rstest]
+    fn accepts_a_codex_home(#[from(temp_dir)] dir: PathBuf) {
+        fs::create_dir_all(dir.join(SESSIONS_DIR)).unwrap();
+
+        assert!(check_codexdir(&dir).result.is_ok());
+    }
+
+    #[rstest]
+    fn points_at_a_nested_codex_home(#[from(temp_dir)] dir: PathBuf) {
+        fs::create_dir_all(dir.join(DOT_CODEX_DIR).join(SESSIONS_DIR)).unwrap();
+
+        let problem = check_codexdir(&dir).result.unwrap_err();
+
+        assert!(problem.contains("did you mean"), "{problem}");
+    }
+
+    #[rstest]
+    fn reports_a_missing_codexdir(#[from(temp_dir)] dir: PathBuf) {
+        let problem = check_codexdir(&dir.join("missing")).result.unwrap_err();
+
+        assert!(problem.starts_with("cannot read"), "{problem}");
+    }
+
+    #[rstest]
+    fn narrow_check_flags_recent_writes_outside_the_grants(#[from(temp_dir)] dir: PathBuf) {
+        fs::create_dir_all(dir.join(SESSIONS_DIR)).unwrap();
+        fs::create_dir_all(dir.join("log")).unwrap();
+        let now = SystemTime::now();
+
+        assert!(check_narrow_grants(&dir, now).result.is_ok());
+
+        fs::write(dir.join("history.jsonl"), "{}\n").unwrap();
+        let problem = check_narrow_grants(&dir, now + Duration::from_secs(60))
+            .result
+            .unwrap_err();
+
+        assert!(
+            problem.starts_with("codex wrote history.jsonl in"),
+            "{problem}"
+        );
+        let later = now + NARROW_CHECK_WINDOW + Duration::from_secs(3_600);
+        assert!(check_narrow_grants(&dir, later).result.is_ok());
+    }
+
+    #[rstest]
+    fn reports_an_invalid_config(#[from(temp_dir)] dir: PathBuf) {
+        let path = dir.join(CONFIG_FILE);
+        fs::write(&path, "tmux = \"banana\"").unwrap();
+
+        let (check, config) = check_config(&path);
+
+        assert!(check.result.unwrap_err().contains("must be a boolean"));
+        assert!(config.is_none());
+    }
+
+    #[rstest]
+    fn a_missing_config_uses_defaults(#[from(temp_dir)] dir: PathBuf) {
+        let (check, config) = check_config(&dir.join(CONFIG_FILE));
+
+        assert!(check.result.unwrap().contains("defaults apply"));
+        assert!(config.is_some());
+    }
+
+    #[rstest]
+    #[case(true, true, true)]
+    #[case(false, false, true)]
+    #[case(false, true, false)]
+    fn programs_are_only_required_when_needed(
+        #[case] found: bool,
+        #[case] needed: bool,
+        #[case] ok: bool,
+    ) {
+        assert_eq!(check_program("tmux", found, needed).result.is_ok(), ok);
+    }
+
+    #[test]
+    fn writes_one_line_per_check() {
+        let checks = [
+            Check::ok("config", "/c.toml"),
+            Check::failed("codex", "not found on $PATH"),
+        ];
+        let mut out = Vec::new();
+
+        write_checks(&mut out, &checks).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "ok    config    /c.toml\nFAIL  codex     not found on $PATH\n"
+        );
+    }
+}
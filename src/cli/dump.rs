@@ -0,0 +1,229 @@
+//! Machine-readable scan dump.
+//!
+//! This module implements the `dump` subcommand, which streams one NDJSON object per session
+//! file to stdout so external tools (`jq`, dashboards, scripts) can consume amg's view of the
+//! codex directory without re-implementing the walk.
+//!
+//! Each line has `path`, `size`, and `mtime` (RFC 3339, UTC), plus the first-line fields
+//! that are present (`id`, `cwd`, `branch`, `repository_url`, `originator`, `model`) or a
+//! `parse_error` string.
+//!
+//! `--max-matches N` stops the walk after N lines. When more files matched, a notice goes to
+//! stderr and amg exits with [`EXIT_TRUNCATED`]; the lines printed are still complete.
+
+use std::io::Write;
+
+use serde::Serialize;
+
+use super::args::DumpArgs;
+use super::config;
+use super::datetime::UtcDateTime;
+use super::prelude::*;
+use super::scan::{self, SessionFile};
+use super::util;
+
+/// One NDJSON line of `amg dump` output.
+#[derive(Debug, Serialize)]
+struct DumpRecord<'a> {
+    path: &'a Path,
+    size: u64,
+    mtime: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cwd: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    branch: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repository_url: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    originator: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parse_error: Option<&'a str>,
+}
+
+impl<'a> DumpRecord<'a> {
+    fn new(file: &'a SessionFile) -> Self {
+        let (fields, parse_error) = match &file.fields {
+            Ok(fields) => (Some(fields), None),
+            Err(err) => (None, Some(err.as_str())),
+        };
+        Self {
+            path: &file.path,
+            size: file.size,
+            mtime: file
+                .modified
+                .map(|t| UtcDateTime::from_system_time(t).rfc3339()),
+            id: fields.and_then(|f| f.id.as_deref()),
+            cwd: fields.and_then(|f| f.cwd.as_deref()),
+            branch: fields.and_then(|f| f.branch.as_deref()),
+            repository_url: fields.and_then(|f| f.repository_url.as_deref()),
+            originator: fields.and_then(|f| f.originator.as_deref()),
+            model: fields.and_then(|f| f.model.as_deref()),
+            parse_error,
+        }
+    }
+}
+
+/// Filters applied to the dumped session files.
+#[derive(Debug, Default)]
+struct DumpFilter {
+    branch: Option<String>,
+    modified_after: Option<SystemTime>,
+}
+
+impl DumpFilter {
+    fn matches(&self, file: &SessionFile) -> bool {
+        let branch_ok = self.branch.as_deref().is_none_or(|want| {
+            file.fields
+                .as_ref()
+                .is_ok_and(|f| f.branch.as_deref() == Some(want))
+        });
+        let time_ok = self
+            .modified_after
+            .is_none_or(|after| file.modified.is_some_and(|t| t >= after));
+        branch_ok && time_ok
+    }
+}
+
+/// Handles the `dump` subcommand.
+///
+/// # Errors
+///
+/// Returns an error if the codexdir is invalid or stdout cannot be written.
+pub(super) fn run_dump(args: DumpArgs) -> Result<ExitCode> {
+    let codexdir = args
+        .location
+        .codexdir
+        .map(Ok)
+        .unwrap_or_else(config::default_codexdir)?;
+    util::require_dir(&codexdir, "codexdir", Some("CODEX_CODEXDIR"))?;
+    super::check_codexdir(&codexdir, args.walk.force_scan)?;
+
+    let filter = DumpFilter {
+        branch: args.branch,
+        modified_after: args
+            .since
+            .and_then(|since| SystemTime::now().checked_sub(since)),
+    };
+    let mut out = io::stdout().lock();
+    let mut files = util::Capped::new(
+        scan::session_files(&codexdir)?.filter(|f| filter.matches(f)),
+        args.max_matches,
+    );
+    for file in files.by_ref() {
+        write_record(&mut out, &file)?;
+    }
+    if files.truncated() {
+        warn!(
+            max_matches = args.max_matches,
+            "stopped at --max-matches; more session files matched"
+        );
+        return Ok(ExitCode::from(EXIT_TRUNCATED));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn write_record(out: &mut impl Write, file: &SessionFile) -> Result<()> {
+    serde_json::to_writer(&mut *out, &DumpRecord::new(file))?;
+    writeln!(out).context("failed to write dump output")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::scan::SessionFields;
+    use rstest::rstest;
+
+    fn session_file(fields: std::result::Result<SessionFields, String>) -> SessionFile {
+        SessionFile {
+            path: PathBuf::from("/codex/sessions/a.jsonl"),
+            size: 42,
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(86_400)),
+            fields,
+        }
+    }
+
+    fn with_branch(branch: &str) -> SessionFile {
+        session_file(Ok(SessionFields {
+            branch: Some(branch.to_owned()),
+            ..Default::default()
+        }))
+    }
+
+    fn dumped(file: &SessionFile) -> serde_json::Value {
+        let mut buf = Vec::new();
+        write_record(&mut buf, file).unwrap();
+        let line = String::from_utf8(buf).unwrap();
+        assert!(line.ends_with('\n') && line.matches('\n').count() == 1);
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn writes_present_fields_only() {
+        let file = session_file(Ok(SessionFields {
+            id: Some("id-1".into()),
+            branch: Some("main".into()),
+            originator: Some("codex_exec".into()),
+            ..Default::default()
+        }));
+
+        assert_eq!(
+            dumped(&file),
+            serde_json::json!({
+                "path": "/codex/sessions/a.jsonl",
+                "size": 42,
+                "mtime": "1970-01-02T00:00:00Z",
+                "id": "id-1",
+                "branch": "main",
+                "originator": "codex_exec",
+            })
+        );
+    }
+
+    #[test]
+    fn writes_parse_error_instead_of_fields() {
+        let value = dumped(&session_file(Err("expected value".into())));
+
+        assert_eq!(value["parse_error"], "expected value");
+        assert!(value.get("id").is_none());
+    }
+
+    #[rstest]
+    #[case(None, true)]
+    #[case(Some("main"), true)]
+    #[case(Some("dev"), false)]
+    fn filters_by_branch(#[case] branch: Option<&str>, #[case] expected: bool) {
+        let filter = DumpFilter {
+            branch: branch.map(str::to_owned),
+            ..Default::default()
+        };
+
+        assert_eq!(filter.matches(&with_branch("main")), expected);
+    }
+
+    #[test]
+    fn branch_filter_skips_unparseable_files() {
+        let filter = DumpFilter {
+            branch: Some("main".into()),
+            ..Default::default()
+        };
+
+        assert!(!filter.matches(&session_file(Err("bad".into()))));
+    }
+
+    #[rstest]
+    #[case(0, true)]
+    #[case(86_400, true)]
+    #[case(86_401, false)]
+    fn filters_by_modification_time(#[case] after_secs: u64, #[case] expected: bool) {
+        let filter = DumpFilter {
+            modified_after: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(after_secs)),
+            ..Default::default()
+        };
+
+        assert_eq!(filter.matches(&with_branch("main")), expected);
+    }
+}
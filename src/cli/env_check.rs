@@ -0,0 +1,271 @@
+//! Environment sanity checks run before any subcommand.
+//!
+//! Started from a bare environment (a systemd unit, a cron job, an `env -i` wrapper), amg
+//! used to fail on the first symptom: tmux not found, then codex not found, then `$HOME`
+//! unset, one run at a time. [`check`] runs first instead and reports, in one error, every
+//! obviously broken part of the environment:
+//!
+//! * `$PATH` is unset or empty, so neither codex nor tmux (nor git) can be found
+//! * `$HOME` is unset or empty and the subcommand needs a codexdir that nothing else provides
+//!   (`--codexdir`, `$CODEX_CODEXDIR`, or the global config's `codexdir`)
+//! * stdin and stdout are both closed
+//!
+//! Each check is a pure function over a [`Snapshot`] of the environment, taken once.
+//! `--no-env-check` skips them all for exotic setups.
+
+use super::args::Commands;
+use super::config;
+use super::prelude::*;
+
+/// Whether a subcommand needs a codexdir, and whether one was given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codexdir {
+    /// The subcommand does not read a codexdir.
+    NotNeeded,
+    /// `--codexdir` (or `$CODEX_CODEXDIR`) was given.
+    Given,
+    /// The subcommand falls back to the default codexdir.
+    Default,
+}
+
+/// The parts of the environment the checks look at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Snapshot {
+    /// `$PATH`, if set.
+    path: Option<OsString>,
+    /// `$HOME`, if set.
+    home: Option<OsString>,
+    codexdir: Codexdir,
+    /// Whether the global config provides a `codexdir` (only looked up when `$HOME` is
+    /// missing and the subcommand needs the default codexdir).
+    config_codexdir: bool,
+    stdin_open: bool,
+    stdout_open: bool,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of the process environment for running `command`.
+    pub(super) fn capture(command: &Commands) -> Self {
+        let home = std::env::var_os(ENV_HOME);
+        let codexdir = codexdir_need(command);
+        let config_codexdir = codexdir == Codexdir::Default
+            && is_unset(home.as_deref())
+            // A config that cannot be read is reported by the subcommand itself.
+            && config::Config::load_global().map_or(true, |config| config.codexdir.is_some());
+        Self {
+            path: std::env::var_os(ENV_PATH),
+            home,
+            codexdir,
+            config_codexdir,
+            stdin_open: fd_open(0),
+            stdout_open: fd_open(1),
+        }
+    }
+}
+
+/// Checks the environment, listing everything wrong with it in a single error.
+///
+/// # Errors
+///
+/// Returns an error if any check fails.
+pub(super) fn check(snapshot: &Snapshot) -> Result<()> {
+    let problems = problems(snapshot);
+    if problems.is_empty() {
+        return Ok(());
+    }
+    bail!(
+        "this environment cannot run amg:\n  - {}\npass --no-env-check to run anyway",
+        problems.join("\n  - ")
+    )
+}
+
+/// Runs every check, in a fixed order.
+fn problems(snapshot: &Snapshot) -> Vec<String> {
+    [
+        check_path(snapshot.path.as_deref()),
+        check_home(
+            snapshot.home.as_deref(),
+            snapshot.codexdir,
+            snapshot.config_codexdir,
+        ),
+        check_stdio(snapshot.stdin_open, snapshot.stdout_open),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+fn check_path(path: Option<&OsStr>) -> Option<String> {
+    is_unset(path).then(|| {
+        format!(
+            "${ENV_PATH} is {}, so codex, tmux, and git cannot be found",
+            describe(path)
+        )
+    })
+}
+
+fn check_home(home: Option<&OsStr>, codexdir: Codexdir, config_codexdir: bool) -> Option<String> {
+    let needed = codexdir == Codexdir::Default && !config_codexdir;
+    (needed && is_unset(home)).then(|| {
+        format!(
+            "${ENV_HOME} is {} and no codexdir is provided; pass --codexdir or set \
+             ${ENV_CODEX_CODEXDIR}",
+            describe(home)
+        )
+    })
+}
+
+fn check_stdio(stdin_open: bool, stdout_open: bool) -> Option<String> {
+    (!stdin_open && !stdout_open).then(|| "stdin and stdout are both closed".to_owned())
+}
+
+fn is_unset(value: Option<&OsStr>) -> bool {
+    value.is_none_or(OsStr::is_empty)
+}
+
+fn describe(value: Option<&OsStr>) -> &'static str {
+    match value {
+        None => "unset",
+        Some(_) => "empty",
+    }
+}
+
+/// Returns whether `command` reads a codexdir, and whether it was given one.
+fn codexdir_need(command: &Commands) -> Codexdir {
+    let given = |codexdir: &Option<PathBuf>| match codexdir {
+        Some(_) => Codexdir::Given,
+        None => Codexdir::Default,
+    };
+    match command {
+        Commands::ResumeBranch(args) if args.stdin_jsonl => Codexdir::NotNeeded,
+        Commands::Which(args) if args.stdin_jsonl => Codexdir::NotNeeded,
+        Commands::ResumeBranch(args) => given(&args.location.codexdir),
+        Commands::ResumeAll(args) => given(&args.location.codexdir),
+        Commands::Watch(args) => given(&args.location.codexdir),
+        Commands::Which(args) => given(&args.location.codexdir),
+        Commands::Show(args) => given(&args.location.codexdir),
+        Commands::Branches(args) => given(&args.location.codexdir),
+        Commands::Find(args) => given(&args.location.codexdir),
+        Commands::Dump(args) => given(&args.location.codexdir),
+        Commands::Stats(args) => given(&args.location.codexdir),
+        Commands::Import(args) => given(&args.codexdir),
+        Commands::MigrateLayout(args) => given(&args.codexdir),
+        Commands::Init(args) => given(&args.codexdir),
+        // `doctor` reports a missing codexdir itself.
+        Commands::Doctor(_)
+        | Commands::Kill(_)
+        | Commands::Remembered(_)
+        | Commands::PromptHint(_)
+        | Commands::ExecPlan(_)
+        | Commands::Alias { .. }
+        | Commands::Tag { .. }
+        | Commands::Config { .. } => Codexdir::NotNeeded,
+    }
+}
+
+/// Returns whether file descriptor `fd` is open.
+#[cfg(unix)]
+fn fd_open(fd: libc::c_int) -> bool {
+    // SAFETY: F_GETFD only queries the descriptor's flags.
+    unsafe { libc::fcntl(fd, libc::F_GETFD) != -1 }
+}
+
+/// Returns whether file descriptor `fd` is open (always assumed off Unix).
+#[cfg(not(unix))]
+fn fd_open(_fd: i32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Args;
+    use clap::Parser;
+    use rstest::rstest;
+
+    fn healthy() -> Snapshot {
+        Snapshot {
+            path: Some("/usr/bin:/bin".into()),
+            home: Some("/home/me".into()),
+            codexdir: Codexdir::Default,
+            config_codexdir: false,
+            stdin_open: true,
+            stdout_open: true,
+        }
+    }
+
+    #[test]
+    fn a_healthy_environment_passes() {
+        assert!(check(&healthy()).is_ok());
+    }
+
+    #[rstest]
+    #[case(None, Some("$PATH is unset, so codex, tmux, and git cannot be found"))]
+    #[case(
+        Some(""),
+        Some("$PATH is empty, so codex, tmux, and git cannot be found")
+    )]
+    #[case(Some("/bin"), None)]
+    fn checks_path(#[case] path: Option<&str>, #[case] expected: Option<&str>) {
+        assert_eq!(check_path(path.map(OsStr::new)).as_deref(), expected);
+    }
+
+    #[rstest]
+    #[case::home_set(Some("/home/me"), Codexdir::Default, false, false)]
+    #[case::no_home(None, Codexdir::Default, false, true)]
+    #[case::empty_home(Some(""), Codexdir::Default, false, true)]
+    #[case::codexdir_given(None, Codexdir::Given, false, false)]
+    #[case::codexdir_in_config(None, Codexdir::Default, true, false)]
+    #[case::not_needed(None, Codexdir::NotNeeded, false, false)]
+    fn checks_home(
+        #[case] home: Option<&str>,
+        #[case] codexdir: Codexdir,
+        #[case] config_codexdir: bool,
+        #[case] fails: bool,
+    ) {
+        assert_eq!(
+            check_home(home.map(OsStr::new), codexdir, config_codexdir).is_some(),
+            fails
+        );
+    }
+
+    #[rstest]
+    #[case(true, true, false)]
+    #[case(false, true, false)]
+    #[case(true, false, false)]
+    #[case(false, false, true)]
+    fn checks_stdio(#[case] stdin: bool, #[case] stdout: bool, #[case] fails: bool) {
+        assert_eq!(check_stdio(stdin, stdout).is_some(), fails);
+    }
+
+    #[test]
+    fn lists_every_problem_in_one_error() {
+        let broken = Snapshot {
+            path: None,
+            home: None,
+            stdin_open: false,
+            stdout_open: false,
+            ..healthy()
+        };
+
+        let err = check(&broken).unwrap_err().to_string();
+
+        assert_eq!(err.matches("\n  - ").count(), 3, "{err}");
+        assert!(err.contains("$HOME is unset"), "{err}");
+        assert!(err.ends_with("pass --no-env-check to run anyway"), "{err}");
+    }
+
+    #[test]
+    fn only_codexdir_subcommands_need_one() {
+        let args = |argv: &[&str]| Args::try_parse_from(argv).unwrap().command;
+
+        assert_eq!(
+            codexdir_need(&args(&["amg", "alias", "list"])),
+            Codexdir::NotNeeded
+        );
+        assert_eq!(
+            codexdir_need(&args(&["amg", "which", "main", "--codexdir", "/c"])),
+            Codexdir::Given
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! Selection explanations (`resume --explain`).
+//!
+//! Collects the [`Decision`] the scan made for each file it walked, up to and including the
+//! winner, and renders them to stderr as text or JSON. Only the first `limit` skipped files are
+//! kept, so explaining a lookup on a huge store stays readable; the rest are counted.
+//!
+//! Explanations never go to stdout, which stays reserved for the dry-run command.
+
+use std::fmt::Write as _;
+
+use serde::Serialize;
+
+use super::prelude::*;
+use super::scan::{self, Decision, ScanOptions, ScanOutcome};
+use super::util;
+
+/// Default number of skipped files kept in an explanation.
+pub(super) const DEFAULT_LIMIT: usize = 50;
+
+/// One walked file and the scan's decision about it.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct Candidate {
+    path: PathBuf,
+    #[serde(flatten)]
+    decision: Decision,
+}
+
+/// The ordered decisions behind a branch lookup.
+#[derive(Debug, Serialize)]
+pub(super) struct Explanation {
+    branch: String,
+    candidates: Vec<Candidate>,
+    /// Number of skipped files beyond the limit that are not listed.
+    omitted_skipped: usize,
+    /// `.jsonl` files walked up to the match (see [`scan::ScanStats`]).
+    files_scanned: usize,
+    /// How many of those had their first line parsed.
+    files_parsed: usize,
+    #[serde(skip)]
+    listed_skipped: usize,
+    #[serde(skip)]
+    limit: usize,
+}
+
+impl Explanation {
+    fn new(branch: &str, limit: usize) -> Self {
+        Self {
+            branch: branch.to_owned(),
+            candidates: Vec::new(),
+            omitted_skipped: 0,
+            files_scanned: 0,
+            files_parsed: 0,
+            listed_skipped: 0,
+            limit,
+        }
+    }
+
+    fn record(&mut self, path: &Path, decision: &Decision) {
+        if *decision != Decision::Matched {
+            if self.listed_skipped >= self.limit {
+                self.omitted_skipped += 1;
+                return;
+            }
+            self.listed_skipped += 1;
+        }
+        self.candidates.push(Candidate {
+            path: path.to_owned(),
+            decision: decision.clone(),
+        });
+    }
+
+    /// Renders the explanation as human-readable lines.
+    fn to_text(&self) -> String {
+        let mut out = format!("candidates considered for branch {:?}:\n", self.branch);
+        for candidate in &self.candidates {
+            let verdict = if candidate.decision == Decision::Matched {
+                "match"
+            } else {
+                "skip "
+            };
+            let _ = writeln!(
+                out,
+                "  {verdict} {}: {}",
+                util::human_path(&candidate.path),
+                candidate.decision
+            );
+        }
+        if self.omitted_skipped > 0 {
+            let _ = writeln!(
+                out,
+                "  ... {} more skipped file(s) not shown",
+                self.omitted_skipped
+            );
+        }
+        if !self
+            .candidates
+            .iter()
+            .any(|c| c.decision == Decision::Matched)
+        {
+            out.push_str("  no file matched\n");
+        }
+        let _ = writeln!(
+            out,
+            "  {} .jsonl file(s) scanned, {} parsed",
+            self.files_scanned, self.files_parsed
+        );
+        out
+    }
+}
+
+/// Finds the first session for `branch` like [`scan::find_first_session`] and prints the
+/// explanation of the choice to stderr.
+///
+/// # Arguments
+///
+/// * `codexdir` - The Codex directory to search in
+/// * `branch` - The git branch name to match against
+/// * `options` - Options narrowing which files count as sessions
+/// * `limit` - Maximum number of skipped files to list
+/// * `json` - Whether to print JSON instead of text
+/// * `redacted_home` - A home directory to collapse to `~` in the listed paths (`--redact-home`)
+///
+/// # Errors
+///
+/// Returns an error if the scan fails or the explanation cannot be serialized.
+pub(super) fn find_and_explain(
+    codexdir: &Path,
+    branch: &str,
+    options: &ScanOptions,
+    limit: usize,
+    json: bool,
+    redacted_home: Option<&Path>,
+) -> Result<ScanOutcome> {
+    let mut explanation = Explanation::new(branch, limit);
+    let outcome =
+        scan::find_first_session_observed(codexdir, branch, options, |path, decision| {
+            explanation.record(
+                &PathBuf::from(util::display_path(path, redacted_home)),
+                decision,
+            );
+        })?;
+    explanation.files_scanned = outcome.stats.files_scanned;
+    explanation.files_parsed = outcome.stats.files_parsed;
+    if json {
+        eprintln!("{}", serde_json::to_string(&explanation)?);
+    } else {
+        eprint!("{}", explanation.to_text());
+    }
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn explanation(decisions: &[(&str, Decision)], limit: usize) -> Explanation {
+        let mut explanation = Explanation::new("main", limit);
+        for (path, decision) in decisions {
+            explanation.record(Path::new(path), decision);
+        }
+        explanation
+    }
+
+    #[test]
+    fn keeps_the_winner_beyond_the_limit() {
+        let explanation = explanation(
+            &[
+                ("/c/a.txt", Decision::NotJsonl),
+                ("/c/b.txt", Decision::NotJsonl),
+                ("/c/rollout-c.jsonl", Decision::Empty),
+                ("/c/rollout-d.jsonl", Decision::Matched),
+            ],
+            2,
+        );
+
+        let paths: Vec<_> = explanation
+            .candidates
+            .iter()
+            .map(|c| c.path.to_str().unwrap())
+            .collect();
+        assert_eq!(paths, ["/c/a.txt", "/c/b.txt", "/c/rollout-d.jsonl"]);
+        assert_eq!(explanation.omitted_skipped, 1);
+    }
+
+    #[test]
+    fn renders_text() {
+        let explanation = explanation(
+            &[
+                ("/c/a.txt", Decision::NotJsonl),
+                (
+                    "/c/rollout-b.jsonl",
+                    Decision::BranchMismatch {
+                        found: Some("dev".into()),
+                    },
+                ),
+                ("/c/rollout-c.jsonl", Decision::Matched),
+            ],
+            1,
+        );
+
+        assert_eq!(
+            explanation.to_text(),
+            "candidates considered for branch \"main\":\n  skip  /c/a.txt: not a .jsonl file\n  match /c/rollout-c.jsonl: first matching session\n  ... 1 more skipped file(s) not shown\n  0 .jsonl file(s) scanned, 0 parsed\n"
+        );
+    }
+
+    #[test]
+    fn text_reports_a_miss() {
+        let explanation = explanation(&[("/c/a.txt", Decision::NotJsonl)], DEFAULT_LIMIT);
+
+        assert!(explanation.to_text().contains("  no file matched\n"));
+    }
+
+    #[test]
+    fn serializes_decisions_as_tagged_objects() {
+        let explanation = explanation(
+            &[
+                ("/c/rollout-a.jsonl", Decision::NotUuid { id: "x".into() }),
+                ("/c/rollout-b.jsonl", Decision::Matched),
+            ],
+            DEFAULT_LIMIT,
+        );
+        let explanation = Explanation {
+            files_scanned: 2,
+            files_parsed: 2,
+            ..explanation
+        };
+
+        let value = serde_json::to_value(&explanation).unwrap();
+
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "branch": "main",
+                "candidates": [
+                    { "path": "/c/rollout-a.jsonl", "decision": "not_uuid", "id": "x" },
+                    { "path": "/c/rollout-b.jsonl", "decision": "matched" },
+                ],
+                "omitted_skipped": 0,
+                "files_scanned": 2,
+                "files_parsed": 2,
+            })
+        );
+    }
+}
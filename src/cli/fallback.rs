@@ -0,0 +1,165 @@
+//! Fallback resume for session ids codex has forgotten (`--fallback-resume`).
+//!
+//! Occasionally the session file still exists but codex's own index no longer knows its id,
+//! and `codex resume <id>` fails. With `--fallback-resume`, an inline run captures codex's
+//! stderr (while still streaming it to the terminal) and, when the failure looks like an
+//! unknown-id error, retries once with `codex resume --last`. What counts as that error is
+//! configurable with `fallback_resume_pattern` and `fallback_resume_exit_code`.
+
+use regex::Regex;
+
+use super::config::Config;
+use super::prelude::*;
+use super::process::Cmd;
+use super::util;
+
+/// Default pattern for codex's unknown-session error, matched against its stderr.
+pub(super) const DEFAULT_UNKNOWN_SESSION_PATTERN: &str = concat!(
+    r"(?i)no (such |saved )?(session|rollout)",
+    r"|(session|conversation|rollout)\b.*\b(not found|unknown|does not exist)",
+);
+
+/// Recognizes codex failing because it does not know the session id.
+#[derive(Debug)]
+pub(super) struct UnknownSession {
+    /// Matched against the captured stderr.
+    pattern: Regex,
+    /// When set, codex must also have exited with this code.
+    exit_code: Option<i32>,
+}
+
+impl UnknownSession {
+    /// Builds the matcher from `fallback_resume_pattern` and `fallback_resume_exit_code`,
+    /// falling back to [`DEFAULT_UNKNOWN_SESSION_PATTERN`] and any failing exit code.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the config file if the configured pattern is not a valid regex.
+    pub(super) fn from_config(config: &Config) -> Result<Self> {
+        let pattern = match &config.fallback_resume_pattern {
+            Some(entry) => Regex::new(&entry.value).with_context(|| {
+                format!(
+                    "invalid fallback_resume_pattern {:?} in {}",
+                    entry.value,
+                    util::human_path(&entry.source)
+                )
+            })?,
+            None => Regex::new(DEFAULT_UNKNOWN_SESSION_PATTERN)?,
+        };
+        Ok(Self {
+            pattern,
+            exit_code: config.fallback_resume_exit_code,
+        })
+    }
+
+    /// Checks whether a finished codex run failed because the session id was unknown.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - codex's exit code (`None` if it was killed by a signal, which never matches)
+    /// * `stderr` - The captured tail of codex's stderr
+    pub(super) fn matches(&self, code: Option<i32>, stderr: &str) -> bool {
+        let Some(code) = code.filter(|&code| code != 0) else {
+            return false;
+        };
+        self.exit_code.is_none_or(|want| want == code) && self.pattern.is_match(stderr)
+    }
+}
+
+/// Turns `... resume <id>` into `... resume --last`, keeping every other argument (and any
+/// wrapper) unchanged.
+///
+/// # Returns
+///
+/// Returns `None` if the command does not end with `resume <id>`.
+pub(super) fn resume_last(cmd: &Cmd) -> Option<Cmd> {
+    let [.., resume, _id] = cmd.args.as_slice() else {
+        return None;
+    };
+    if resume != "resume" {
+        return None;
+    }
+    let mut fallback = cmd.clone();
+    *fallback.args.last_mut()? = "--last".into();
+    Some(fallback)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::config::ConfigEntry;
+    use rstest::rstest;
+
+    fn configured(pattern: Option<&str>, exit_code: Option<i32>) -> Config {
+        Config {
+            fallback_resume_pattern: pattern.map(|value| ConfigEntry {
+                value: value.to_owned(),
+                source: PathBuf::from("/c/config.toml"),
+            }),
+            fallback_resume_exit_code: exit_code,
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case::default_pattern(
+        None,
+        None,
+        Some(1),
+        "Error: No saved session found with ID 0199",
+        true
+    )]
+    #[case::not_found(None, None, Some(1), "error: session 0199 not found", true)]
+    #[case::other_error(None, None, Some(1), "error: not logged in", false)]
+    #[case::success(None, None, Some(0), "no session found", false)]
+    #[case::signal(None, None, None, "no session found", false)]
+    #[case::custom_pattern(Some("forgot"), None, Some(2), "codex forgot it", true)]
+    #[case::exit_code_matches(None, Some(3), Some(3), "no such session", true)]
+    #[case::exit_code_differs(None, Some(3), Some(1), "no such session", false)]
+    fn recognizes_unknown_session_failures(
+        #[case] pattern: Option<&str>,
+        #[case] exit_code: Option<i32>,
+        #[case] code: Option<i32>,
+        #[case] stderr: &str,
+        #[case] expected: bool,
+    ) {
+        let matcher = UnknownSession::from_config(&configured(pattern, exit_code)).unwrap();
+
+        assert_eq!(matcher.matches(code, stderr), expected);
+    }
+
+    #[test]
+    fn rejects_an_invalid_pattern() {
+        let err = UnknownSession::from_config(&configured(Some("("), None)).unwrap_err();
+
+        assert!(format!("{err:#}").contains("/c/config.toml"), "{err:#}");
+    }
+
+    #[test]
+    fn retries_with_resume_last() {
+        let cmd = Cmd {
+            program: "env".into(),
+            args: ["CODEX_HOME=/h", "codex", "--search", "resume", "0199"]
+                .map(OsString::from)
+                .to_vec(),
+        };
+
+        let fallback = resume_last(&cmd).unwrap();
+
+        assert_eq!(fallback.program, "env");
+        assert_eq!(
+            fallback.args,
+            ["CODEX_HOME=/h", "codex", "--search", "resume", "--last"]
+        );
+    }
+
+    #[test]
+    fn needs_a_trailing_resume() {
+        let cmd = Cmd {
+            program: "codex".into(),
+            args: vec!["exec".into(), "hi".into()],
+        };
+
+        assert!(resume_last(&cmd).is_none());
+    }
+}
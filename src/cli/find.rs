@@ -0,0 +1,346 @@
+//! Partial branch search.
+//!
+//! This module implements the `find` subcommand, which looks for branches by a fragment of
+//! their name, like SQL's `ILIKE '%fragment%'`: every session file is read once (see
+//! [`scan::session_files`]), and each recorded branch that contains the fragment,
+//! ignoring case, is scored by [`score`] and listed best match first with its session count
+//! and the age of its newest session.
+//!
+//! `--resume-top` resumes the best-matching branch instead, exactly like `amg resume BRANCH`
+//! would. When several branches share the best score, it refuses and lists them rather than
+//! guessing which one was meant.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use super::args::{
+    CacheArgs, CodexArgs, ExecutionArgs, FindArgs, LocationArgs, OverlapArgs, ResumeArgs,
+    SelectionArgs,
+};
+use super::prelude::*;
+use super::scan::{self, SessionFile};
+use super::{config, explain, pick, usage, util};
+
+/// Characters that separate the words of a branch name (`feature/pay-v2_fix.1`).
+const WORD_SEPARATORS: [char; 4] = ['/', '-', '_', '.'];
+
+/// Score for a branch that contains the fragment anywhere.
+const SCORE_SUBSTRING: u32 = 1;
+
+/// Bonus for a match that starts a word.
+const BONUS_WORD_START: u32 = 2;
+
+/// Bonus for a match that also ends a word, so it covers whole words.
+const BONUS_WORD_END: u32 = 1;
+
+/// Bonus for a match at the start of the branch name.
+const BONUS_PREFIX: u32 = 4;
+
+/// A branch whose name contains the fragment.
+#[derive(Debug, PartialEq, Eq)]
+struct Hit {
+    branch: String,
+    /// What [`score`] gave the branch.
+    score: u32,
+    /// Session files recorded on the branch.
+    sessions: usize,
+    /// Modification time of the branch's newest session file, if known.
+    newest: Option<SystemTime>,
+}
+
+/// Scores how well `branch` matches `fragment`, ignoring case.
+///
+/// A branch that contains the fragment scores [`SCORE_SUBSTRING`], plus
+/// [`BONUS_WORD_START`] when the match starts a word (see [`WORD_SEPARATORS`]),
+/// [`BONUS_WORD_END`] when it also ends one, and [`BONUS_PREFIX`] when it starts the name.
+/// When the fragment occurs more than once, the best occurrence counts; an exact match gets
+/// every bonus.
+///
+/// # Returns
+///
+/// Returns `None` if `branch` does not contain `fragment` or the fragment is empty.
+fn score(branch: &str, fragment: &str) -> Option<u32> {
+    if fragment.is_empty() {
+        return None;
+    }
+    let branch = branch.to_lowercase();
+    let fragment = fragment.to_lowercase();
+    let is_boundary = |c: Option<char>| c.is_none_or(|c| WORD_SEPARATORS.contains(&c));
+    branch
+        .match_indices(&fragment)
+        .map(|(start, matched)| {
+            let end = start + matched.len();
+            let mut score = SCORE_SUBSTRING;
+            if is_boundary(branch[..start].chars().next_back()) {
+                score += BONUS_WORD_START;
+                if is_boundary(branch[end..].chars().next()) {
+                    score += BONUS_WORD_END;
+                }
+            }
+            if start == 0 {
+                score += BONUS_PREFIX;
+            }
+            score
+        })
+        .max()
+}
+
+/// Collects the branches of `files` that contain `fragment`, best match first.
+///
+/// Hits with the same score are ordered by their newest session (most recent first), then
+/// by session count, then by name. Files whose first line cannot be parsed or records no
+/// branch are skipped.
+fn rank(files: impl IntoIterator<Item = SessionFile>, fragment: &str) -> Vec<Hit> {
+    let mut hits: HashMap<String, Hit> = HashMap::new();
+    for file in files {
+        let Some(branch) = file.fields.ok().and_then(|fields| fields.branch) else {
+            continue;
+        };
+        if let Some(hit) = hits.get_mut(&branch) {
+            hit.sessions += 1;
+            hit.newest = hit.newest.max(file.modified);
+            continue;
+        }
+        let Some(score) = score(&branch, fragment) else {
+            continue;
+        };
+        hits.insert(
+            branch.clone(),
+            Hit {
+                branch,
+                score,
+                sessions: 1,
+                newest: file.modified,
+            },
+        );
+    }
+    let mut hits: Vec<Hit> = hits.into_values().collect();
+    hits.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then(b.newest.cmp(&a.newest))
+            .then(b.sessions.cmp(&a.sessions))
+            .then_with(|| a.branch.cmp(&b.branch))
+    });
+    hits
+}
+
+/// Returns the leading hits that share the best score (empty if there are no hits).
+fn best(hits: &[Hit]) -> &[Hit] {
+    let tied = hits
+        .iter()
+        .take_while(|hit| hit.score == hits[0].score)
+        .count();
+    &hits[..tied]
+}
+
+/// Handles the `find` subcommand.
+///
+/// # Returns
+///
+/// Returns success after listing the matches, [`EXIT_NO_SESSION`] if no branch matches, or,
+/// with `--resume-top`, what resuming the best match returned.
+///
+/// # Errors
+///
+/// Returns an error if the fragment is empty, the codexdir is invalid, stdout cannot be
+/// written, or with `--resume-top`, several branches match equally well or the resume fails.
+pub(super) fn run_find(args: FindArgs) -> Result<ExitCode> {
+    if args.fragment.is_empty() {
+        bail!("the fragment to find must not be empty");
+    }
+    let codexdir = args
+        .location
+        .codexdir
+        .clone()
+        .map(Ok)
+        .unwrap_or_else(config::default_codexdir)?;
+    util::require_dir(&codexdir, "codexdir", Some("CODEX_CODEXDIR"))?;
+    super::check_codexdir(&codexdir, args.walk.force_scan)?;
+
+    let hits = rank(scan::session_files(&codexdir)?, &args.fragment);
+    usage::note_match(!hits.is_empty());
+    if hits.is_empty() {
+        warn!(fragment = %args.fragment, "no branch with sessions contains the fragment");
+        return Ok(ExitCode::from(EXIT_NO_SESSION));
+    }
+    let now = SystemTime::now();
+    if !args.resume_top {
+        write_table(&mut io::stdout().lock(), &hits, now)
+            .context("failed to write the matching branches")?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let top = best(&hits);
+    if let [hit] = top {
+        info!(branch = %hit.branch, score = hit.score, "resuming the best-matching branch");
+        return super::run_resume_branch(resume_args(&args, &codexdir, &hit.branch));
+    }
+    write_table(&mut io::stdout().lock(), top, now)
+        .context("failed to write the matching branches")?;
+    let names: Vec<&str> = top.iter().map(|hit| hit.branch.as_str()).collect();
+    bail!(
+        "{} branches match {:?} equally well ({}); pass a longer fragment or resume one by name",
+        top.len(),
+        args.fragment,
+        names.join(", ")
+    )
+}
+
+/// Prints a table of the hits with their session counts and the age of their newest session.
+fn write_table(out: &mut impl Write, hits: &[Hit], now: SystemTime) -> io::Result<()> {
+    writeln!(out, "{:<6} {:<8} BRANCH", "LAST", "SESSIONS")?;
+    for hit in hits {
+        let age = hit
+            .newest
+            .and_then(|m| now.duration_since(m).ok())
+            .map_or_else(|| "?".to_owned(), pick::format_age);
+        writeln!(out, "{age:<6} {:<8} {}", hit.sessions, hit.branch)?;
+    }
+    Ok(())
+}
+
+/// Builds the `resume` arguments that resume `branch` with the launch options of `args`.
+fn resume_args(args: &FindArgs, codexdir: &Path, branch: &str) -> ResumeArgs {
+    ResumeArgs {
+        branch: Some(branch.to_owned()),
+        any: false,
+        id: None,
+        file: None,
+        force: false,
+        stdin_jsonl: false,
+        path_prefix: None,
+        repo: args.repo.clone(),
+        location: LocationArgs {
+            codexdir: Some(codexdir.to_owned()),
+        },
+        overlap: OverlapArgs::default(),
+        selection: SelectionArgs {
+            walk: args.walk.clone(),
+            ..SelectionArgs::default()
+        },
+        relax_on_empty: false,
+        cache: CacheArgs::default(),
+        fresh: false,
+        pick: false,
+        pick_tmux: false,
+        no_fzf: false,
+        explain: false,
+        json: false,
+        explain_limit: explain::DEFAULT_LIMIT,
+        codex: CodexArgs::default(),
+        execution: ExecutionArgs {
+            dry_run: args.dry_run,
+            tmux: args.tmux,
+            ..ExecutionArgs::default()
+        },
+        sandbox: super::args::SandboxArgs::default(),
+        backup: super::args::BackupArgs {
+            backup: false,
+            backup_keep: 1,
+            backup_best_effort: false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::{SessionFile, temp_dir};
+    use rstest::rstest;
+
+    const ID: &str = "0199a1b2-c3d4-7e5f-8a9b-0c1d2e3f4a5b";
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn hit(branch: &str, score: u32, sessions: usize, newest: Option<u64>) -> Hit {
+        Hit {
+            branch: branch.to_owned(),
+            score,
+            sessions,
+            newest: newest.map(at),
+        }
+    }
+
+    #[rstest]
+    #[case("payments", "payments", Some(8))]
+    #[case("Payments-v2", "payments", Some(8))]
+    #[case("payments-v2", "PAY", Some(7))]
+    #[case("feature/payments", "payments", Some(4))]
+    #[case("feature/payments-v2", "payments", Some(4))]
+    #[case("feature/paymentsv2", "payments", Some(3))]
+    #[case("feature/repayments", "payments", Some(1))]
+    #[case("repayments/payments", "payments", Some(4))]
+    #[case("feature/billing", "payments", None)]
+    #[case("main", "", None)]
+    fn scores_the_best_occurrence(
+        #[case] branch: &str,
+        #[case] fragment: &str,
+        #[case] expected: Option<u32>,
+    ) {
+        assert_eq!(score(branch, fragment), expected);
+    }
+
+    #[rstest]
+    fn ranks_by_score_then_recency(#[from(temp_dir)] dir: PathBuf) {
+        let day = dir.join(SESSIONS_DIR).join("2025/03/09");
+        let sessions = [
+            ("feature/repay", 500),
+            ("fix/pay-retry", 100),
+            ("fix/pay-retry", 300),
+            ("feature/pay", 200),
+            ("pay", 50),
+            ("main", 900),
+        ];
+        for (n, (branch, secs)) in sessions.into_iter().enumerate() {
+            SessionFile::new(branch, ID)
+                .modified(at(secs))
+                .write(day.join(format!("rollout-{}.jsonl", n + 1)));
+        }
+        fs::write(dir.join(SESSIONS_DIR).join("broken.jsonl"), "garbage\n").unwrap();
+
+        let hits = rank(scan::session_files(&dir).unwrap(), "pay");
+
+        assert_eq!(
+            hits,
+            [
+                hit("pay", 8, 1, Some(50)),
+                hit("fix/pay-retry", 4, 2, Some(300)),
+                hit("feature/pay", 4, 1, Some(200)),
+                hit("feature/repay", 1, 1, Some(500)),
+            ]
+        );
+    }
+
+    #[test]
+    fn best_keeps_every_hit_sharing_the_top_score() {
+        let hits = [
+            hit("fix/pay-retry", 4, 2, Some(300)),
+            hit("feature/pay", 4, 1, Some(200)),
+            hit("feature/repay", 1, 1, Some(500)),
+        ];
+
+        assert_eq!(best(&hits), &hits[..2]);
+        assert_eq!(best(&hits[1..]), &hits[1..2]);
+        assert!(best(&[]).is_empty());
+    }
+
+    #[test]
+    fn table_shows_counts_and_ages() {
+        let now = at(100_000);
+        let hits = [
+            hit("fix/pay-retry", 4, 12, Some(100_000 - 7_200)),
+            hit("feature/pay", 4, 1, None),
+        ];
+        let mut out = Vec::new();
+
+        write_table(&mut out, &hits, now).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "LAST   SESSIONS BRANCH\n2h     12       fix/pay-retry\n?      1        feature/pay\n"
+        );
+    }
+}
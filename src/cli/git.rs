@@ -0,0 +1,372 @@
+//! Git invocation helpers.
+//!
+//! This module wraps the few `git` commands amg shells out to and interprets their output
+//! and exit statuses. Interpretation is kept in pure functions so it can be unit-tested
+//! without a git binary. [`head_branch`] reads `.git/HEAD` directly instead, for callers that
+//! cannot afford to start git, and [`branch_exists`] reads the refs the same way.
+
+use std::path::Component;
+
+use super::prelude::*;
+use super::{codex_cmd, util};
+
+/// Returns the commit `HEAD` points to in `repo`.
+///
+/// # Errors
+///
+/// Returns an error if `git` cannot be run, fails, or prints something that isn't a commit id.
+pub(super) fn head_commit(repo: &Path) -> Result<String> {
+    let output = git(repo)
+        .args(["rev-parse", "--verify", "HEAD"])
+        .output()
+        .context("failed to run git rev-parse")?;
+    if !output.status.success() {
+        bail!(
+            "git rev-parse HEAD failed in {}: {}",
+            util::human_path(repo),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    parse_commit_id(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Checks whether `commit` is an ancestor of (or equal to) `descendant` in `repo`.
+///
+/// Runs `git merge-base --is-ancestor`.
+///
+/// # Errors
+///
+/// Returns an error if `git` cannot be run or reports a failure other than "not an ancestor"
+/// (for example, an unknown commit).
+pub(super) fn is_ancestor(repo: &Path, commit: &str, descendant: &str) -> Result<bool> {
+    let output = git(repo)
+        .args(["merge-base", "--is-ancestor", commit, descendant])
+        .output()
+        .context("failed to run git merge-base")?;
+    interpret_is_ancestor(
+        output.status.code(),
+        &String::from_utf8_lossy(&output.stderr),
+    )
+}
+
+/// Checks whether git ignores `name` in `dir` (`git check-ignore`).
+///
+/// Returns `None` if `dir` is not inside a git work tree or git cannot be run, since there is
+/// nothing to ignore the file from.
+pub(super) fn is_ignored(dir: &Path, name: &str) -> Option<bool> {
+    let status = git(dir)
+        .args(["check-ignore", "-q", "--", name])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .ok()?;
+    interpret_check_ignore(status.code())
+}
+
+/// Returns the branch checked out in the work tree containing `dir`, read from its `HEAD`
+/// file without running git.
+///
+/// The nearest `.git` at or above `dir` is used: a directory, or a file pointing at one
+/// (`gitdir: ...`, as in linked worktrees and submodules). Returns `None` outside a work tree,
+/// on a detached `HEAD`, or if a file cannot be read.
+pub(super) fn head_branch(dir: &Path) -> Option<String> {
+    parse_head_ref(&fs::read_to_string(git_dir(dir)?.join("HEAD")).ok()?)
+}
+
+/// Returns whether the local branch `branch` (`refs/heads/<branch>`) exists in the repository
+/// of the work tree containing `dir`, read from its loose refs and `packed-refs` without
+/// running git.
+///
+/// Linked worktrees share the refs of their repository's common git directory. Returns `None`
+/// outside a work tree, for a name that is not a plain ref path, for a repository that stores
+/// its refs in a reftable, or if the refs cannot be read.
+pub(super) fn branch_exists(dir: &Path, branch: &str) -> Option<bool> {
+    let plain = Path::new(branch)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if branch.is_empty() || !plain {
+        return None;
+    }
+    let common = codex_cmd::common_git_dir(&git_dir(dir)?);
+    if common.join("reftable").is_dir() {
+        return None;
+    }
+    match fs::metadata(common.join("refs/heads").join(branch)) {
+        Ok(meta) if meta.is_file() => return Some(true),
+        Ok(_) => {}
+        Err(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::NotADirectory
+            ) => {}
+        Err(_) => return None,
+    }
+    match fs::read_to_string(common.join("packed-refs")) {
+        Ok(content) => Some(packed_refs_contain(
+            &content,
+            &format!("refs/heads/{branch}"),
+        )),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Some(false),
+        Err(_) => None,
+    }
+}
+
+/// Returns the git directory of the work tree containing `dir`: the nearest `.git` at or above
+/// `dir`, or the directory a `.git` file points to (`gitdir: ...`, as in linked worktrees and
+/// submodules).
+fn git_dir(dir: &Path) -> Option<PathBuf> {
+    let (parent, meta) = dir
+        .ancestors()
+        .find_map(|parent| Some((parent, fs::metadata(parent.join(".git")).ok()?)))?;
+    let dot_git = parent.join(".git");
+    if meta.is_dir() {
+        Some(dot_git)
+    } else {
+        Some(parent.join(parse_gitdir(&fs::read_to_string(&dot_git).ok()?)?))
+    }
+}
+
+fn git(repo: &Path) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(repo);
+    cmd
+}
+
+/// Parses the output of `git rev-parse` into a commit id.
+fn parse_commit_id(stdout: &str) -> Result<String> {
+    let id = stdout.trim();
+    let is_hex = !id.is_empty() && id.chars().all(|c| c.is_ascii_hexdigit());
+    if is_hex && matches!(id.len(), 40 | 64) {
+        Ok(id.to_ascii_lowercase())
+    } else {
+        bail!("unexpected git rev-parse output: {id:?}");
+    }
+}
+
+/// Parses the `gitdir: <path>` line of a `.git` file (the path may be relative to the file).
+fn parse_gitdir(content: &str) -> Option<PathBuf> {
+    let path = content.trim_end().strip_prefix("gitdir:")?.trim();
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+/// Parses a `HEAD` file into the branch it points to (`ref: refs/heads/<branch>`), or `None`
+/// for a detached `HEAD`.
+fn parse_head_ref(content: &str) -> Option<String> {
+    let branch = content
+        .trim_end()
+        .strip_prefix("ref:")?
+        .trim_start()
+        .strip_prefix("refs/heads/")?;
+    (!branch.is_empty()).then(|| branch.to_owned())
+}
+
+/// Returns whether the `packed-refs` file `content` lists the ref `name`.
+///
+/// Each ref is a `<object id> <name>` line; `#` lines are headers, and `^` lines carry the
+/// commit a tag above them peels to.
+fn packed_refs_contain(content: &str, name: &str) -> bool {
+    content
+        .lines()
+        .filter(|line| !line.starts_with(['#', '^']))
+        .filter_map(|line| line.split_once(' '))
+        .any(|(_, packed)| packed.trim_end() == name)
+}
+
+/// Interprets the exit status of `git check-ignore -q`.
+///
+/// Exit code 0 means ignored, 1 means not ignored; anything else (128 outside a work tree)
+/// means the question does not apply.
+fn interpret_check_ignore(code: Option<i32>) -> Option<bool> {
+    match code {
+        Some(0) => Some(true),
+        Some(1) => Some(false),
+        _ => None,
+    }
+}
+
+/// Interprets the exit status of `git merge-base --is-ancestor`.
+///
+/// Exit code 0 means ancestor, 1 means not an ancestor; anything else is an error.
+fn interpret_is_ancestor(code: Option<i32>, stderr: &str) -> Result<bool> {
+    match code {
+        Some(0) => Ok(true),
+        Some(1) => Ok(false),
+        Some(code) => bail!(
+            "git merge-base --is-ancestor failed with exit code {code}: {}",
+            stderr.trim()
+        ),
+        None => bail!("git merge-base --is-ancestor was terminated by a signal"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::temp_dir;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("0123456789abcdef0123456789abcdef01234567\n")]
+    #[case("  0123456789ABCDEF0123456789ABCDEF01234567  ")]
+    #[case("0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef\n")]
+    fn parses_full_commit_ids(#[case] stdout: &str) {
+        let id = parse_commit_id(stdout).expect("should parse");
+
+        assert_eq!(id, stdout.trim().to_ascii_lowercase());
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("HEAD\n")]
+    #[case("0123456\n")]
+    #[case("fatal: not a git repository\n")]
+    fn rejects_unexpected_rev_parse_output(#[case] stdout: &str) {
+        assert!(parse_commit_id(stdout).is_err());
+    }
+
+    #[rstest]
+    #[case(Some(0), true)]
+    #[case(Some(1), false)]
+    fn interprets_ancestor_exit_codes(#[case] code: Option<i32>, #[case] expected: bool) {
+        assert_eq!(interpret_is_ancestor(code, "").unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(Some(0), Some(true))]
+    #[case(Some(1), Some(false))]
+    #[case(Some(128), None)]
+    #[case(None, None)]
+    fn interprets_check_ignore_exit_codes(
+        #[case] code: Option<i32>,
+        #[case] expected: Option<bool>,
+    ) {
+        assert_eq!(interpret_check_ignore(code), expected);
+    }
+
+    #[test]
+    fn reports_git_failure_with_stderr() {
+        let err = interpret_is_ancestor(Some(128), "fatal: Not a valid commit name deadbeef\n")
+            .unwrap_err();
+
+        let msg = err.to_string();
+        assert!(msg.contains("exit code 128"), "{msg}");
+        assert!(msg.contains("Not a valid commit name"), "{msg}");
+    }
+
+    #[test]
+    fn reports_signal_termination() {
+        assert!(interpret_is_ancestor(None, "").is_err());
+    }
+
+    #[rstest]
+    #[case("ref: refs/heads/main\n", Some("main"))]
+    #[case("ref:refs/heads/feature/x", Some("feature/x"))]
+    #[case("ref: refs/remotes/origin/main\n", None)]
+    #[case("0123456789abcdef0123456789abcdef01234567\n", None)]
+    #[case("", None)]
+    fn parses_head_refs(#[case] content: &str, #[case] expected: Option<&str>) {
+        assert_eq!(parse_head_ref(content).as_deref(), expected);
+    }
+
+    #[rstest]
+    fn reads_the_branch_from_the_nearest_work_tree(#[from(temp_dir)] dir: PathBuf) {
+        let repo = dir.join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(repo.join("src/deep")).unwrap();
+        fs::write(repo.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+
+        assert_eq!(head_branch(&repo.join("src/deep")).as_deref(), Some("main"));
+    }
+
+    #[rstest]
+    fn follows_a_worktree_gitdir_file(#[from(temp_dir)] dir: PathBuf) {
+        let git_dir = dir.join("repo/.git/worktrees/wt");
+        fs::create_dir_all(&git_dir).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/feature/a\n").unwrap();
+        let worktree = dir.join("wt");
+        fs::create_dir_all(&worktree).unwrap();
+        fs::write(worktree.join(".git"), "gitdir: ../repo/.git/worktrees/wt\n").unwrap();
+
+        assert_eq!(head_branch(&worktree).as_deref(), Some("feature/a"));
+    }
+
+    const PACKED_REFS: &str = "# pack-refs with: peeled fully-peeled sorted \n\
+        0123456789abcdef0123456789abcdef01234567 refs/heads/packed\n\
+        1123456789abcdef0123456789abcdef01234567 refs/tags/v1\n\
+        ^2123456789abcdef0123456789abcdef01234567\n";
+
+    #[rstest]
+    #[case("refs/heads/packed", true)]
+    #[case("refs/tags/v1", true)]
+    #[case("refs/heads/pack", false)]
+    #[case("2123456789abcdef0123456789abcdef01234567", false)]
+    fn reads_packed_refs(#[case] name: &str, #[case] expected: bool) {
+        assert_eq!(packed_refs_contain(PACKED_REFS, name), expected);
+    }
+
+    /// Creates a repository at `dir/repo` with the loose branch `feature/loose` and the
+    /// packed branch `packed`.
+    fn repo_with_refs(dir: &Path) -> PathBuf {
+        let repo = dir.join("repo");
+        fs::create_dir_all(repo.join(".git/refs/heads/feature")).unwrap();
+        fs::write(
+            repo.join(".git/refs/heads/feature/loose"),
+            "0123456789abcdef0123456789abcdef01234567\n",
+        )
+        .unwrap();
+        fs::write(repo.join(".git/packed-refs"), PACKED_REFS).unwrap();
+        repo
+    }
+
+    #[rstest]
+    #[case::loose("feature/loose", Some(true))]
+    #[case::packed("packed", Some(true))]
+    #[case::missing("gone", Some(false))]
+    #[case::ref_directory("feature", Some(false))]
+    #[case::below_a_loose_ref("feature/loose/x", Some(false))]
+    #[case::a_tag("v1", Some(false))]
+    #[case::escaping("../../HEAD", None)]
+    #[case::empty("", None)]
+    fn finds_local_branches(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] branch: &str,
+        #[case] expected: Option<bool>,
+    ) {
+        let repo = repo_with_refs(&dir);
+
+        assert_eq!(branch_exists(&repo.join("src"), branch), expected);
+    }
+
+    #[rstest]
+    fn without_packed_refs_only_loose_refs_exist(#[from(temp_dir)] dir: PathBuf) {
+        let repo = repo_with_refs(&dir);
+        fs::remove_file(repo.join(".git/packed-refs")).unwrap();
+
+        assert_eq!(branch_exists(&repo, "feature/loose"), Some(true));
+        assert_eq!(branch_exists(&repo, "packed"), Some(false));
+        assert_eq!(branch_exists(&dir.join("elsewhere"), "packed"), None);
+    }
+
+    #[rstest]
+    fn linked_worktrees_read_the_common_refs(#[from(temp_dir)] dir: PathBuf) {
+        let repo = repo_with_refs(&dir);
+        let admin = repo.join(".git/worktrees/wt");
+        fs::create_dir_all(&admin).unwrap();
+        fs::write(admin.join("commondir"), "../..\n").unwrap();
+        let worktree = dir.join("wt");
+        fs::create_dir_all(&worktree).unwrap();
+        fs::write(worktree.join(".git"), "gitdir: ../repo/.git/worktrees/wt\n").unwrap();
+
+        assert_eq!(branch_exists(&worktree, "feature/loose"), Some(true));
+        assert_eq!(branch_exists(&worktree, "packed"), Some(true));
+        assert_eq!(branch_exists(&worktree, "gone"), Some(false));
+    }
+
+    #[rstest]
+    fn reftable_repositories_are_not_read(#[from(temp_dir)] dir: PathBuf) {
+        let repo = repo_with_refs(&dir);
+        fs::create_dir_all(repo.join(".git/reftable")).unwrap();
+
+        assert_eq!(branch_exists(&repo, "packed"), None);
+    }
+}
@@ -0,0 +1,250 @@
+//! Exclusion patterns for the codexdir walk.
+//!
+//! Paths can be kept out of every scan in two ways: `--exclude <GLOB>` on the command line,
+//! and a `.amgignore` file at the top of the codexdir (for subtrees such as archived
+//! experiments that should stay off-limits without a flag on every call). Both use the same
+//! gitignore-like syntax and the same matcher, and a path excluded by either is skipped:
+//!
+//! * One glob per line; blank lines and lines starting with `#` are ignored
+//! * A pattern without a `/` matches the name of a file or directory at any depth
+//!   (`archived` skips every directory named `archived`)
+//! * A pattern containing a `/` matches the path relative to the codexdir (`sessions/2023/**`);
+//!   a leading `/` only anchors it
+//! * A trailing `/` matches directories only
+//! * `*` does not cross `/`, while `**` does
+//!
+//! An excluded directory is not descended into, so nothing below it is read. `!` negation is
+//! not supported. An unreadable `.amgignore`, or an invalid pattern in it, is warned about
+//! and otherwise ignored; an invalid `--exclude` is an error.
+
+use glob::{MatchOptions, Pattern};
+
+use super::prelude::*;
+use super::util;
+
+/// One parsed exclusion pattern.
+#[derive(Debug, Clone)]
+struct Exclusion {
+    /// The pattern as written.
+    #[cfg_attr(not(feature = "cli"), expect(dead_code))]
+    source: String,
+    pattern: Pattern,
+    /// Matches the path relative to the root rather than the entry's name.
+    anchored: bool,
+    /// Matches directories only (the pattern ended in `/`).
+    dir_only: bool,
+}
+
+/// A set of exclusion patterns, matched against paths relative to a walk's root.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Exclusions {
+    patterns: Vec<Exclusion>,
+}
+
+impl Exclusions {
+    /// Parses the `--exclude` patterns.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the first pattern that is empty or not a valid glob.
+    #[cfg(feature = "cli")]
+    pub(super) fn from_args(patterns: &[String]) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| parse(pattern).with_context(|| format!("invalid --exclude {pattern:?}")))
+            .collect::<Result<_>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// Reads `<root>/.amgignore`, if there is one.
+    ///
+    /// A missing file yields no patterns. An unreadable file and invalid lines are warned
+    /// about and skipped, so a broken ignore file never stops a scan.
+    pub(super) fn load(root: &Path) -> Self {
+        let path = root.join(AMGIGNORE_FILE);
+        match fs::read_to_string(&path) {
+            Ok(content) => Self::parse_file(&content, &path),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(err) => {
+                warn!(path = %util::human_path(&path), "cannot read the ignore file; ignoring it: {err}");
+                Self::default()
+            }
+        }
+    }
+
+    /// Parses the content of an ignore file read from `source`.
+    fn parse_file(content: &str, source: &Path) -> Self {
+        let mut patterns = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match parse(line) {
+                Ok(exclusion) => patterns.push(exclusion),
+                Err(err) => warn!(
+                    path = %util::human_path(source),
+                    line = number + 1,
+                    "skipping ignore pattern {line:?}: {err:#}"
+                ),
+            }
+        }
+        Self { patterns }
+    }
+
+    /// Adds the patterns of `other` to these.
+    pub(super) fn extend(&mut self, other: &Self) {
+        self.patterns.extend(other.patterns.iter().cloned());
+    }
+
+    /// Returns the patterns as written, in order.
+    #[cfg(feature = "cli")]
+    pub(super) fn sources(&self) -> impl Iterator<Item = &str> {
+        self.patterns
+            .iter()
+            .map(|exclusion| exclusion.source.as_str())
+    }
+
+    /// Returns whether there are no patterns.
+    pub(super) fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Returns whether a walk skips the file at `relative` (relative to the walk's root),
+    /// because the file or one of the directories above it is excluded.
+    #[cfg(feature = "cli")]
+    pub(super) fn excludes_file(&self, relative: &Path) -> bool {
+        self.excludes(relative, false)
+            || relative
+                .ancestors()
+                .skip(1)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .any(|dir| self.excludes(dir, true))
+    }
+
+    /// Returns whether the entry at `relative` (relative to the walk's root) is excluded.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative` - The entry's path relative to the root
+    /// * `is_dir` - Whether the entry is a directory
+    pub(super) fn excludes(&self, relative: &Path, is_dir: bool) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        let name = relative.file_name().map(Path::new);
+        self.patterns.iter().any(|exclusion| {
+            if exclusion.dir_only && !is_dir {
+                return false;
+            }
+            match (exclusion.anchored, name) {
+                (true, _) => exclusion.pattern.matches_path_with(relative, options),
+                (false, Some(name)) => exclusion.pattern.matches_path_with(name, options),
+                (false, None) => false,
+            }
+        })
+    }
+}
+
+/// Parses one gitignore-like pattern.
+fn parse(line: &str) -> Result<Exclusion> {
+    let (body, dir_only) = match line.strip_suffix('/') {
+        Some(body) => (body, true),
+        None => (line, false),
+    };
+    let anchored = body.contains('/');
+    let body = body.strip_prefix('/').unwrap_or(body);
+    if body.is_empty() {
+        bail!("the pattern is empty");
+    }
+    Ok(Exclusion {
+        source: line.to_owned(),
+        pattern: Pattern::new(body)?,
+        anchored,
+        dir_only,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::temp_dir;
+    use rstest::rstest;
+
+    fn file(content: &str) -> Exclusions {
+        Exclusions::parse_file(content, Path::new("/codex/.amgignore"))
+    }
+
+    #[test]
+    fn skips_comments_blank_lines_and_invalid_patterns() {
+        let exclusions = file("# archived experiments\n\n   \narchived\n[\n/\n  # indented\n");
+
+        assert_eq!(exclusions.patterns.len(), 1);
+        assert!(exclusions.excludes(Path::new("sessions/archived"), true));
+    }
+
+    #[rstest]
+    #[case("archived", "sessions/2024/archived", true, true)]
+    #[case("archived", "archived", false, true)]
+    #[case("archived", "sessions/archived-2", true, false)]
+    #[case("*.bak.jsonl", "sessions/2024/01/01/a.bak.jsonl", false, true)]
+    #[case("sessions/2023", "sessions/2023", true, true)]
+    #[case("sessions/2023", "other/sessions/2023", true, false)]
+    #[case("/sessions/*", "sessions/2023", true, true)]
+    #[case("/sessions/*", "sessions/2023/01", true, false)]
+    #[case("sessions/**/old.jsonl", "sessions/2023/01/02/old.jsonl", false, true)]
+    #[case("old/", "sessions/old", true, true)]
+    #[case("old/", "sessions/old", false, false)]
+    #[case("sessions/2023/", "sessions/2023", true, true)]
+    fn matches_like_gitignore(
+        #[case] pattern: &str,
+        #[case] relative: &str,
+        #[case] is_dir: bool,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(
+            file(pattern).excludes(Path::new(relative), is_dir),
+            expected
+        );
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    #[case::the_file("*.bak.jsonl", true)]
+    #[case::its_directory("sessions/2026/10/01", true)]
+    #[case::a_directory_above("2026/", true)]
+    #[case::a_directory_pattern_naming_the_file("a.bak.jsonl/", false)]
+    #[case::another_day("sessions/2026/10/02", false)]
+    fn excludes_files_below_excluded_directories(#[case] pattern: &str, #[case] expected: bool) {
+        let relative = Path::new("sessions/2026/10/01/a.bak.jsonl");
+
+        assert_eq!(file(pattern).excludes_file(relative), expected);
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    #[case("")]
+    #[case("/")]
+    #[case("[")]
+    fn rejects_invalid_cli_patterns(#[case] pattern: &str) {
+        let err = Exclusions::from_args(&[pattern.to_owned()]).unwrap_err();
+        assert!(err.to_string().contains("invalid --exclude"), "{err}");
+    }
+
+    #[rstest]
+    fn loads_the_ignore_file_when_present(#[from(temp_dir)] dir: PathBuf) {
+        assert!(Exclusions::load(&dir).is_empty());
+
+        fs::write(dir.join(AMGIGNORE_FILE), "archived/\n").unwrap();
+
+        assert!(Exclusions::load(&dir).excludes(Path::new("archived"), true));
+    }
+
+    #[rstest]
+    fn an_unreadable_ignore_file_is_ignored(#[from(temp_dir)] dir: PathBuf) {
+        fs::create_dir(dir.join(AMGIGNORE_FILE)).unwrap();
+
+        assert!(Exclusions::load(&dir).is_empty());
+    }
+}
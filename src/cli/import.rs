@@ -0,0 +1,341 @@
+//! Session import.
+//!
+//! This module implements the `import` subcommand, which drops a session file received from
+//! elsewhere (a teammate's support escalation, another machine) into the local store where
+//! amg and codex look for it: `sessions/YYYY/MM/DD/` under the codexdir, dated by the
+//! session's own timestamp. The first line must parse as a session with a UUID id, and an
+//! existing session with the same id is only replaced with `--force`.
+//!
+//! `--rewrite-cwd` replaces the recorded cwd (the sender's paths rarely exist locally); the
+//! rest of the file is copied unchanged.
+
+use serde_json::Value;
+
+use super::args::ImportArgs;
+use super::config;
+use super::datetime::UtcDateTime;
+use super::jsonl;
+use super::output;
+use super::prelude::*;
+use super::scan;
+use super::util;
+
+/// Resolved options for [`import_session`].
+#[derive(Debug)]
+struct ImportOptions<'a> {
+    /// The cwd to record instead of the sender's (`--rewrite-cwd`).
+    rewrite_cwd: Option<&'a Path>,
+    /// Replace an existing session with the same id (`--force`).
+    force: bool,
+    /// Fallback date for sessions without a timestamp.
+    now: SystemTime,
+}
+
+/// Where a session was imported.
+#[derive(Debug, PartialEq, Eq)]
+struct Imported {
+    /// The path of the imported file inside the codexdir.
+    path: PathBuf,
+    /// The session's branch, if it records one.
+    branch: Option<String>,
+}
+
+/// Handles the `import` subcommand, printing the imported path and branch.
+///
+/// # Errors
+///
+/// Returns an error if the codexdir is missing, the file is not a valid session, a session
+/// with the same id already exists (without `--force`), or the file cannot be copied.
+pub(super) fn run_import(args: ImportArgs) -> Result<ExitCode> {
+    let ImportArgs {
+        file,
+        codexdir,
+        rewrite_cwd,
+        force,
+    } = args;
+    let codexdir = codexdir.map(Ok).unwrap_or_else(config::default_codexdir)?;
+    util::require_dir(&codexdir, "codexdir", Some("CODEX_CODEXDIR"))?;
+
+    let options = ImportOptions {
+        rewrite_cwd: rewrite_cwd.as_deref(),
+        force,
+        now: SystemTime::now(),
+    };
+    let imported = import_session(&file, &codexdir, &options)
+        .with_context(|| format!("failed to import {}", util::human_path(&file)))?;
+    info!(path = %util::human_path(&imported.path), "imported session");
+    output::line(format_args!(
+        "{}\t{}",
+        imported.path.display(),
+        imported.branch.as_deref().unwrap_or("-")
+    ))?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Validates `file` and copies it into `codexdir`.
+fn import_session(file: &Path, codexdir: &Path, options: &ImportOptions<'_>) -> Result<Imported> {
+    let contents = fs::read_to_string(file).context("failed to read the session file")?;
+    let (first, rest) = contents.split_once('\n').unwrap_or((&contents, ""));
+    let first = jsonl::normalize_line(first.to_owned());
+    let fields = scan::parse_session_fields(&first)
+        .map_err(|err| anyhow::anyhow!("the first line is {err}"))?;
+    let id = fields.id.as_deref().map(str::trim).unwrap_or_default();
+    if !scan::is_plausible_uuid(id) {
+        bail!("the first line has no UUID session id (.payload.id)");
+    }
+
+    let mut event: Value = serde_json::from_str(&first)?;
+    let first = match options.rewrite_cwd {
+        Some(cwd) => {
+            rewrite_cwd(&mut event, cwd)?;
+            serde_json::to_string(&event)?
+        }
+        None => first,
+    };
+
+    let dest = destination(
+        codexdir,
+        file,
+        id,
+        session_time(&event, options.now),
+        options,
+    )?;
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", util::human_path(parent)))?;
+    }
+    let body = if rest.is_empty() {
+        format!("{first}\n")
+    } else {
+        format!("{first}\n{rest}")
+    };
+    fs::write(&dest, body)
+        .with_context(|| format!("failed to write {}", util::human_path(&dest)))?;
+
+    Ok(Imported {
+        path: dest,
+        branch: fields.branch.filter(|b| !b.trim().is_empty()),
+    })
+}
+
+/// Chooses where the session goes: over an existing copy of the same session with `--force`,
+/// otherwise a new file under `sessions/YYYY/MM/DD/`.
+fn destination(
+    codexdir: &Path,
+    file: &Path,
+    id: &str,
+    time: UtcDateTime,
+    options: &ImportOptions<'_>,
+) -> Result<PathBuf> {
+    let existing = scan::session_files(codexdir)?.find(|candidate| {
+        candidate
+            .fields
+            .as_ref()
+            .is_ok_and(|fields| fields.id.as_deref().map(str::trim) == Some(id))
+    });
+    if let Some(existing) = existing {
+        if !options.force {
+            bail!(
+                "session {id} already exists at {} (pass --force to replace it)",
+                util::human_path(&existing.path)
+            );
+        }
+        warn!(path = %util::human_path(&existing.path), "replacing existing session");
+        return Ok(existing.path);
+    }
+
+    let dest = codexdir
+        .join(SESSIONS_DIR)
+        .join(format!("{:04}", time.year))
+        .join(format!("{:02}", time.month))
+        .join(format!("{:02}", time.day))
+        .join(file_name(file, id, time));
+    if dest.exists() && !options.force {
+        bail!(
+            "{} already exists (pass --force to replace it)",
+            util::human_path(&dest)
+        );
+    }
+    Ok(dest)
+}
+
+/// Keeps a Codex rollout file name, or builds one (`rollout-<time>-<id>.jsonl`) so the
+/// imported file passes the scan's naming check.
+fn file_name(file: &Path, id: &str, time: UtcDateTime) -> String {
+    match file.file_name().and_then(OsStr::to_str) {
+        Some(name) if name.starts_with("rollout-") && name.ends_with(".jsonl") => name.to_owned(),
+        _ => format!(
+            "rollout-{:04}-{:02}-{:02}T{:02}-{:02}-{:02}-{id}.jsonl",
+            time.year, time.month, time.day, time.hour, time.minute, time.second
+        ),
+    }
+}
+
+/// Returns the session's timestamp (`.timestamp` or `.payload.timestamp`), or `now`.
+fn session_time(event: &Value, now: SystemTime) -> UtcDateTime {
+    embedded_time(event).unwrap_or_else(|| UtcDateTime::from_system_time(now))
+}
+
+/// Returns the timestamp recorded in a session's first-line `event` (`.timestamp` or
+/// `.payload.timestamp`), if it has a valid one.
+pub(super) fn embedded_time(event: &Value) -> Option<UtcDateTime> {
+    ["/timestamp", "/payload/timestamp"]
+        .into_iter()
+        .filter_map(|pointer| event.pointer(pointer)?.as_str())
+        .find_map(UtcDateTime::parse_rfc3339)
+}
+
+/// Replaces `.payload.cwd` in the first-line event.
+fn rewrite_cwd(event: &mut Value, cwd: &Path) -> Result<()> {
+    if let Some(problem) = util::cwd_problem(cwd) {
+        bail!("invalid --rewrite-cwd: {problem}");
+    }
+    let cwd = cwd.to_str().with_context(|| {
+        format!(
+            "--rewrite-cwd is not valid UTF-8: {}",
+            util::human_path(cwd)
+        )
+    })?;
+    let payload = event
+        .get_mut("payload")
+        .and_then(Value::as_object_mut)
+        .context("the first line has no payload object to rewrite")?;
+    payload.insert("cwd".into(), cwd.into());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::temp_dir;
+    use rstest::rstest;
+    use std::time::Duration;
+
+    const ID: &str = "0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b";
+
+    fn options() -> ImportOptions<'static> {
+        ImportOptions {
+            rewrite_cwd: None,
+            force: false,
+            // Some time on 2026-10-14 (UTC).
+            now: SystemTime::UNIX_EPOCH + Duration::from_secs(1_792_000_000),
+        }
+    }
+
+    fn codexdir(dir: &Path) -> PathBuf {
+        let codexdir = dir.join("codex");
+        fs::create_dir_all(&codexdir).unwrap();
+        codexdir
+    }
+
+    fn write_incoming(dir: &Path, name: &str, timestamp: Option<&str>) -> PathBuf {
+        let mut line = serde_json::json!({
+            "payload": { "git": { "branch": "fix/login" }, "cwd": "/home/alice/app", "id": ID }
+        });
+        if let Some(timestamp) = timestamp {
+            line["timestamp"] = timestamp.into();
+        }
+        let path = dir.join("incoming").join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, format!("{line}\n{{\"type\":\"message\"}}\n")).unwrap();
+        path
+    }
+
+    #[rstest]
+    fn imports_into_the_dated_sessions_dir(#[from(temp_dir)] dir: PathBuf) {
+        let file = write_incoming(&dir, "escalation.jsonl", Some("2025-03-09T08:07:06.5Z"));
+        let codexdir = codexdir(&dir);
+
+        let imported = import_session(&file, &codexdir, &options()).unwrap();
+
+        assert_eq!(
+            imported,
+            Imported {
+                path: codexdir.join(format!(
+                    "sessions/2025/03/09/rollout-2025-03-09T08-07-06-{ID}.jsonl"
+                )),
+                branch: Some("fix/login".into()),
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(&imported.path).unwrap(),
+            fs::read_to_string(&file).unwrap()
+        );
+        let found = scan::find_first_session(&codexdir, "fix/login", &Default::default()).unwrap();
+        assert_eq!(found.session.expect("should be found").id, ID);
+    }
+
+    #[rstest]
+    fn keeps_rollout_names_and_falls_back_to_now(#[from(temp_dir)] dir: PathBuf) {
+        let file = write_incoming(&dir, "rollout-original.jsonl", None);
+
+        let imported = import_session(&file, &codexdir(&dir), &options()).unwrap();
+
+        assert_eq!(
+            imported.path,
+            dir.join("codex/sessions/2026/10/14/rollout-original.jsonl")
+        );
+    }
+
+    #[rstest]
+    fn refuses_a_duplicate_id_unless_forced(#[from(temp_dir)] dir: PathBuf) {
+        let file = write_incoming(&dir, "a.jsonl", Some("2025-03-09T08:07:06Z"));
+        let codexdir = codexdir(&dir);
+        let first = import_session(&file, &codexdir, &options()).unwrap();
+
+        let other = write_incoming(&dir, "b.jsonl", Some("2025-04-01T00:00:00Z"));
+        let err = import_session(&other, &codexdir, &options()).unwrap_err();
+        assert!(err.to_string().contains("already exists"), "{err}");
+
+        let forced = ImportOptions {
+            force: true,
+            ..options()
+        };
+        assert_eq!(
+            import_session(&other, &codexdir, &forced).unwrap().path,
+            first.path
+        );
+    }
+
+    #[rstest]
+    fn rewrites_the_cwd(#[from(temp_dir)] dir: PathBuf) {
+        let file = write_incoming(&dir, "a.jsonl", Some("2025-03-09T08:07:06Z"));
+        let rewritten = ImportOptions {
+            rewrite_cwd: Some(Path::new("/work/app")),
+            ..options()
+        };
+
+        let imported = import_session(&file, &codexdir(&dir), &rewritten).unwrap();
+
+        let contents = fs::read_to_string(imported.path).unwrap();
+        let (first, rest) = contents.split_once('\n').unwrap();
+        let first: Value = serde_json::from_str(first).unwrap();
+        assert_eq!(first["payload"]["cwd"], "/work/app");
+        assert_eq!(first["payload"]["id"], ID);
+        assert_eq!(first["timestamp"], "2025-03-09T08:07:06Z");
+        assert_eq!(rest, "{\"type\":\"message\"}\n");
+    }
+
+    #[rstest]
+    #[case::not_json("not json\n")]
+    #[case::no_id("{\"payload\":{\"cwd\":\"/w\"}}\n")]
+    #[case::empty("")]
+    fn rejects_files_that_are_not_sessions(#[from(temp_dir)] dir: PathBuf, #[case] body: &str) {
+        let file = dir.join("bad.jsonl");
+        fs::write(&file, body).unwrap();
+
+        assert!(import_session(&file, &codexdir(&dir), &options()).is_err());
+        assert!(!dir.join("codex").join(SESSIONS_DIR).exists());
+    }
+
+    #[rstest]
+    fn rejects_a_relative_rewrite(#[from(temp_dir)] dir: PathBuf) {
+        let file = write_incoming(&dir, "a.jsonl", None);
+        let rewritten = ImportOptions {
+            rewrite_cwd: Some(Path::new("work/app")),
+            ..options()
+        };
+
+        assert!(import_session(&file, &codexdir(&dir), &rewritten).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+//! Session index.
+//!
+//! The index is a snapshot of the codexdir: one entry per rollout file with the fields a
+//! listing needs (path, branch, id, cwd, modification time, size), newest first. With
+//! `--cached`, `amg branches` answers from it without touching the session tree, and says how
+//! old the answer is. An index older than `--max-age`, built for another codexdir, or written
+//! in another format version is rebuilt by a full walk first.
+//!
+//! The index lives at `$XDG_CACHE_HOME/amg/index.json`, falling back to
+//! `$HOME/.cache/amg/index.json`. Like the last-hit cache, it is never required: an
+//! unreadable index is rebuilt and a failed write is logged at debug level.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::cache;
+use super::prelude::*;
+use super::scan::{self, BranchList};
+use super::util;
+
+/// Format version written to new indexes; indexes with another version are rebuilt.
+const INDEX_VERSION: u32 = 1;
+
+/// A snapshot of the session files of one codexdir.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct Index {
+    /// The index format version ([`INDEX_VERSION`]).
+    version: u32,
+    /// The codexdir indexed, canonicalized.
+    codexdir: PathBuf,
+    /// When the index was built, in seconds since the Unix epoch.
+    built_at: u64,
+    /// Every rollout file with a readable first line, newest first.
+    sessions: Vec<IndexedSession>,
+}
+
+/// One session file in the index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct IndexedSession {
+    path: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    branch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cwd: Option<PathBuf>,
+    /// Modification time in nanoseconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mtime_ns: Option<u64>,
+    size: u64,
+}
+
+impl Index {
+    /// Indexes `codexdir` with a full walk.
+    ///
+    /// Files whose first line cannot be read or parsed are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the codexdir cannot be read.
+    pub(super) fn build(codexdir: &Path, now: SystemTime) -> Result<Self> {
+        let sessions = scan::rollout_files_newest_first(codexdir)?
+            .into_iter()
+            .filter_map(|path| {
+                let line = scan::read_first_line(&path).ok().flatten()?;
+                let fields = scan::parse_session_fields(&line).ok()?;
+                let meta = fs::metadata(&path).ok()?;
+                Some(IndexedSession {
+                    branch: fields.branch.filter(|branch| !branch.is_empty()),
+                    id: fields.id,
+                    cwd: fields.cwd.map(PathBuf::from),
+                    mtime_ns: meta.modified().ok().map(unix_nanos),
+                    size: meta.len(),
+                    path,
+                })
+            })
+            .collect();
+        Ok(Self {
+            version: INDEX_VERSION,
+            codexdir: canonical(codexdir),
+            built_at: unix_secs(now),
+            sessions,
+        })
+    }
+
+    /// Returns how long ago the index was built (zero for an index from the future).
+    pub(super) fn age(&self, now: SystemTime) -> Duration {
+        Duration::from_secs(unix_secs(now).saturating_sub(self.built_at))
+    }
+
+    /// Lists the indexed branches, most recent session first, like
+    /// [`scan::recent_branches`].
+    pub(super) fn branches(&self) -> BranchList {
+        let mut list = BranchList::default();
+        let mut seen = BTreeSet::new();
+        for session in &self.sessions {
+            let Some(branch) = &session.branch else {
+                continue;
+            };
+            if seen.insert(branch) {
+                let modified = session
+                    .mtime_ns
+                    .map(|ns| SystemTime::UNIX_EPOCH + Duration::from_nanos(ns));
+                list.branches.push((branch.clone(), modified));
+            }
+        }
+        list
+    }
+
+    /// Reads the index at `path` if it is for `codexdir` and in the current format.
+    pub(super) fn load(path: &Path, codexdir: &Path) -> Option<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+            Err(err) => {
+                debug!(path = %util::human_path(path), "cannot read the session index: {err}");
+                return None;
+            }
+        };
+        let index: Self = match serde_json::from_str(&content) {
+            Ok(index) => index,
+            Err(err) => {
+                debug!(path = %util::human_path(path), "ignoring an invalid session index: {err}");
+                return None;
+            }
+        };
+        if index.version != INDEX_VERSION || index.codexdir != canonical(codexdir) {
+            debug!(path = %util::human_path(path), "the session index is for another codexdir or format");
+            return None;
+        }
+        Some(index)
+    }
+
+    pub(super) fn save(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string(self)?;
+        util::write_atomic(path, &(content + "\n"))
+            .with_context(|| format!("failed to write session index {}", util::human_path(path)))
+    }
+}
+
+/// Returns the index of `codexdir`, rebuilding it first when there is none yet or it is
+/// older than `max_age`.
+///
+/// # Errors
+///
+/// Returns an error if the index has to be rebuilt and the codexdir cannot be read.
+pub(super) fn load_or_build(codexdir: &Path, max_age: Option<Duration>) -> Result<Index> {
+    let now = SystemTime::now();
+    let path = cache::cache_dir().map(|dir| dir.join(SESSION_INDEX_FILE));
+    if let Some(index) = path.as_deref().and_then(|path| Index::load(path, codexdir)) {
+        let age = index.age(now);
+        if max_age.is_none_or(|max_age| age <= max_age) {
+            debug!(age_secs = age.as_secs(), "using the session index");
+            return Ok(index);
+        }
+        info!("the session index is older than --max-age; rebuilding it");
+    }
+    let index = Index::build(codexdir, now)?;
+    match &path {
+        Some(path) => {
+            if let Err(err) = index.save(path) {
+                debug!("{err:#}");
+            }
+        }
+        None => debug!("no cache directory; the session index is not saved"),
+    }
+    Ok(index)
+}
+
+fn canonical(dir: &Path) -> PathBuf {
+    fs::canonicalize(dir).unwrap_or_else(|_| dir.to_owned())
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+fn unix_nanos(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| {
+            u64::try_from(since.as_nanos()).unwrap_or(u64::MAX)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::{SessionFile, temp_dir};
+    use rstest::rstest;
+
+    fn codexdir(dir: &Path) -> PathBuf {
+        let codexdir = dir.join("codex");
+        SessionFile::new("main", "a").write(
+            codexdir
+                .join(SESSIONS_DIR)
+                .join("2025/03/08/rollout-a.jsonl"),
+        );
+        SessionFile::new("feature/x", "b").write(
+            codexdir
+                .join(SESSIONS_DIR)
+                .join("2025/03/09/rollout-b.jsonl"),
+        );
+        SessionFile::new("main", "c").write(
+            codexdir
+                .join(SESSIONS_DIR)
+                .join("2025/03/10/rollout-c.jsonl"),
+        );
+        fs::write(codexdir.join(SESSIONS_DIR).join("rollout-bad.jsonl"), "{").unwrap();
+        codexdir
+    }
+
+    #[rstest]
+    fn indexes_every_session_newest_first(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = codexdir(&dir);
+
+        let index = Index::build(&codexdir, SystemTime::now()).unwrap();
+
+        let ids: Vec<_> = index.sessions.iter().map(|s| s.id.as_deref()).collect();
+        assert_eq!(ids, [Some("c"), Some("b"), Some("a")]);
+        assert_eq!(index.sessions[0].cwd.as_deref(), Some(Path::new("/w")));
+        assert!(index.sessions[0].size > 0);
+        let branches: Vec<_> = index
+            .branches()
+            .branches
+            .into_iter()
+            .map(|(b, _)| b)
+            .collect();
+        assert_eq!(branches, ["main", "feature/x"]);
+    }
+
+    #[rstest]
+    fn round_trips_for_the_same_codexdir_only(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = codexdir(&dir);
+        let path = dir.join("index.json");
+        let index = Index::build(&codexdir, SystemTime::now()).unwrap();
+
+        index.save(&path).unwrap();
+
+        assert_eq!(Index::load(&path, &codexdir), Some(index));
+        assert_eq!(Index::load(&path, &dir), None);
+    }
+
+    #[rstest]
+    fn ignores_other_format_versions(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = codexdir(&dir);
+        let path = dir.join("index.json");
+        let mut index = Index::build(&codexdir, SystemTime::now()).unwrap();
+        index.version = INDEX_VERSION + 1;
+        index.save(&path).unwrap();
+
+        assert_eq!(Index::load(&path, &codexdir), None);
+    }
+
+    #[rstest]
+    fn answers_without_reading_the_tree(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = codexdir(&dir);
+        let path = dir.join("index.json");
+        Index::build(&codexdir, SystemTime::now())
+            .unwrap()
+            .save(&path)
+            .unwrap();
+        fs::remove_dir_all(codexdir.join(SESSIONS_DIR)).unwrap();
+
+        let index = Index::load(&path, &codexdir).unwrap();
+
+        assert_eq!(index.branches().branches.len(), 2);
+    }
+
+    #[test]
+    fn age_counts_from_the_build() {
+        let built = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let index = Index {
+            version: INDEX_VERSION,
+            codexdir: PathBuf::from("/codex"),
+            built_at: unix_secs(built),
+            sessions: Vec::new(),
+        };
+
+        assert_eq!(
+            index.age(built + Duration::from_secs(720)),
+            Duration::from_secs(720)
+        );
+        assert_eq!(index.age(built - Duration::from_secs(5)), Duration::ZERO);
+    }
+}
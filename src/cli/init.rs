@@ -0,0 +1,491 @@
+//! First-run setup (`amg init`).
+//!
+//! This module implements the `init` subcommand, which asks for the settings new users
+//! otherwise discover by trial and error (the default codexdir, whether to open tmux windows,
+//! and the model and sandbox codex is started with), writes them to the global config file,
+//! and then runs the [`doctor`](super::doctor) checks. Questions go to stderr and answers are
+//! read from stdin; each one is skipped when its flag is given, and `--yes` skips them all.
+//!
+//! The values are written with the same text edits as `amg config set`, so re-running keeps
+//! the file's comments and other keys. When an existing file would change, the user is offered
+//! a diff and asked before it is overwritten.
+
+use std::io::Write;
+
+use super::args::InitArgs;
+use super::config::{self, Config};
+use super::prelude::*;
+use super::{config_edit, doctor, util};
+
+/// The settings `init` writes.
+#[derive(Debug, PartialEq, Eq)]
+struct Answers {
+    codexdir: PathBuf,
+    tmux: bool,
+    model: String,
+    network_access: bool,
+}
+
+impl Answers {
+    /// Returns the config keys and values to set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the codexdir is not valid UTF-8.
+    fn entries(&self) -> Result<[(&'static str, String); 4]> {
+        let codexdir = self.codexdir.to_str().with_context(|| {
+            format!(
+                "codexdir {} is not valid UTF-8",
+                util::human_path(&self.codexdir)
+            )
+        })?;
+        Ok([
+            ("codexdir", codexdir.to_owned()),
+            ("tmux", self.tmux.to_string()),
+            ("model", self.model.clone()),
+            ("network_access", self.network_access.to_string()),
+        ])
+    }
+}
+
+/// Handles the `init` subcommand.
+///
+/// # Errors
+///
+/// Returns an error if the global config file cannot be located, read, or written, is
+/// invalid, or a question cannot be asked (stdin is not a terminal without `--yes`, or closes
+/// before it is answered).
+pub(super) fn run_init(args: InitArgs) -> Result<ExitCode> {
+    let path = config::locate_global_config()?;
+    let existing = match fs::read_to_string(&path) {
+        Ok(content) => Some(content),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => {
+            return Err(err).with_context(|| {
+                format!("failed to read config file {}", util::human_path(&path))
+            });
+        }
+    };
+    let content = existing.as_deref().unwrap_or_default();
+    let current = Config::parse(content, &path)
+        .context("fix or remove the config file before running `amg init`")?;
+    if !args.yes && !io::stdin().is_terminal() {
+        bail!("stdin is not a terminal; pass --yes (with --codexdir, --model, ... as needed)");
+    }
+
+    let candidates = codexdir_candidates(util::non_empty_env);
+    let defaults = defaults(&args, &current, &candidates)?;
+    let mut prompter = Prompter {
+        input: io::stdin().lock(),
+        output: io::stderr(),
+    };
+    let answers = if args.yes {
+        defaults
+    } else {
+        prompter.ask_all(&args, defaults, &candidates)?
+    };
+    let updated = render_config(content, &path, &answers)?;
+
+    let mut out = io::stdout().lock();
+    match &existing {
+        Some(old) if *old == updated => writeln!(out, "{} is up to date", util::human_path(&path))?,
+        Some(old) if !args.yes => {
+            if prompter.confirm("Show the changes?", true)? {
+                write!(prompter.output, "{}", line_diff(old, &updated))?;
+            }
+            if !prompter.confirm(&format!("Overwrite {}?", util::human_path(&path)), false)? {
+                eprintln!("left {} unchanged", util::human_path(&path));
+                return Ok(ExitCode::from(EXIT_CANCELLED));
+            }
+            util::write_atomic(&path, &updated)?;
+            writeln!(out, "wrote {}", util::human_path(&path))?;
+        }
+        _ => {
+            util::write_atomic(&path, &updated)?;
+            writeln!(out, "wrote {}", util::human_path(&path))?;
+        }
+    }
+    for (key, value) in answers.entries()? {
+        writeln!(out, "  {key:<14} = {value}")?;
+    }
+
+    let checks = doctor::run_checks(false);
+    writeln!(out)?;
+    doctor::write_checks(&mut out, &checks)?;
+    if checks.iter().any(|check| check.result.is_err()) {
+        warn!("some checks failed; fix them and run `amg doctor` again");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Returns the existing directories among `$CODEX_CODEXDIR`, `$CODEX_HOME`, and
+/// `$HOME/.codex`, in that order and without duplicates.
+///
+/// # Arguments
+///
+/// * `env` - Looks up a non-empty environment variable
+fn codexdir_candidates(env: impl Fn(&str) -> Option<PathBuf>) -> Vec<PathBuf> {
+    let found = [
+        env(ENV_CODEX_CODEXDIR),
+        env(ENV_CODEX_HOME),
+        env(ENV_HOME).map(|home| home.join(DOT_CODEX_DIR)),
+    ];
+    let mut candidates = Vec::new();
+    for dir in found.into_iter().flatten() {
+        if dir.is_dir() && !candidates.contains(&dir) {
+            candidates.push(dir);
+        }
+    }
+    candidates
+}
+
+/// Returns the answers `--yes` uses and the prompts offer: each flag, else the current
+/// config, else the built-in default.
+///
+/// # Errors
+///
+/// Returns an error if no codexdir is given, configured, or found, and `$HOME` is not set.
+fn defaults(args: &InitArgs, current: &Config, candidates: &[PathBuf]) -> Result<Answers> {
+    let codexdir = match (&args.codexdir, &current.codexdir, candidates.first()) {
+        (Some(dir), _, _) => dir.clone(),
+        (None, Some(entry), _) => PathBuf::from(&entry.value),
+        (None, None, Some(candidate)) => candidate.clone(),
+        (None, None, None) => config::home_codexdir()?,
+    };
+    Ok(Answers {
+        codexdir,
+        tmux: args.tmux.or(current.tmux).unwrap_or(true),
+        model: args
+            .model
+            .clone()
+            .unwrap_or_else(|| current.model().to_owned()),
+        network_access: args
+            .network_access
+            .or(current.network_access)
+            .unwrap_or(true),
+    })
+}
+
+/// Returns `content` with every answer set, keeping its comments and other keys.
+///
+/// # Errors
+///
+/// Returns an error if the file is not valid TOML or an answer is rejected by the loader.
+fn render_config(content: &str, path: &Path, answers: &Answers) -> Result<String> {
+    answers
+        .entries()?
+        .into_iter()
+        .try_fold(content.to_owned(), |content, (key, value)| {
+            config_edit::set_value(&content, path, key, &[value])
+        })
+}
+
+/// Returns a line diff from `old` to `new`: unchanged lines prefixed with a space, removed
+/// lines with `-`, and added lines with `+`.
+fn line_diff(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..].
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut diff = String::new();
+    while i < old.len() || j < new.len() {
+        let line = if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+            format!(" {}", old[i - 1])
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            i += 1;
+            format!("-{}", old[i - 1])
+        } else {
+            j += 1;
+            format!("+{}", new[j - 1])
+        };
+        diff.push_str(&line);
+        diff.push('\n');
+    }
+    diff
+}
+
+/// Asks questions on `output` and reads the answers from `input`.
+struct Prompter<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Prompter<R, W> {
+    /// Asks every question whose flag was not given, offering `defaults`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a prompt cannot be written or input closes early.
+    fn ask_all(
+        &mut self,
+        args: &InitArgs,
+        defaults: Answers,
+        candidates: &[PathBuf],
+    ) -> Result<Answers> {
+        Ok(Answers {
+            codexdir: match args.codexdir {
+                Some(_) => defaults.codexdir,
+                None => self.choose_codexdir(candidates, &defaults.codexdir)?,
+            },
+            tmux: match args.tmux {
+                Some(tmux) => tmux,
+                None => self.confirm(
+                    "Open a tmux window when resuming inside tmux?",
+                    defaults.tmux,
+                )?,
+            },
+            model: match args.model {
+                Some(_) => defaults.model,
+                None => self.ask("Model codex starts with", &defaults.model)?,
+            },
+            network_access: match args.network_access {
+                Some(network_access) => network_access,
+                None => self.confirm(
+                    "Allow network access in codex's workspace-write sandbox?",
+                    defaults.network_access,
+                )?,
+            },
+        })
+    }
+
+    /// Lists `candidates` and asks for a number or a path.
+    fn choose_codexdir(&mut self, candidates: &[PathBuf], default: &Path) -> Result<PathBuf> {
+        for (n, candidate) in candidates.iter().enumerate() {
+            writeln!(self.output, "  {}) {}", n + 1, util::human_path(candidate))?;
+        }
+        let answer = self.ask(
+            "Default codexdir (number or path)",
+            &default.display().to_string(),
+        )?;
+        Ok(match answer.parse::<usize>() {
+            Ok(n) if (1..=candidates.len()).contains(&n) => candidates[n - 1].clone(),
+            _ => PathBuf::from(answer),
+        })
+    }
+
+    /// Asks `question`, returning `default` for an empty answer.
+    fn ask(&mut self, question: &str, default: &str) -> Result<String> {
+        write!(self.output, "{question} [{default}]: ")?;
+        let answer = self.read_answer(question)?;
+        Ok(if answer.is_empty() {
+            default.to_owned()
+        } else {
+            answer
+        })
+    }
+
+    /// Asks a yes/no `question` until it is answered, returning `default` for an empty answer.
+    fn confirm(&mut self, question: &str, default: bool) -> Result<bool> {
+        let hint = if default { "Y/n" } else { "y/N" };
+        loop {
+            write!(self.output, "{question} [{hint}]: ")?;
+            match self.read_answer(question)?.to_ascii_lowercase().as_str() {
+                "" => return Ok(default),
+                "y" | "yes" => return Ok(true),
+                "n" | "no" => return Ok(false),
+                _ => writeln!(self.output, "please answer y or n")?,
+            }
+        }
+    }
+
+    /// Reads one trimmed line of input.
+    fn read_answer(&mut self, question: &str) -> Result<String> {
+        self.output.flush()?;
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            bail!("input closed before {question:?} was answered");
+        }
+        Ok(line.trim().to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::config::ConfigEntry;
+    use crate::cli::test_support::temp_dir;
+    use rstest::rstest;
+    use std::io::Cursor;
+
+    fn no_flags() -> InitArgs {
+        InitArgs {
+            yes: false,
+            codexdir: None,
+            tmux: None,
+            model: None,
+            network_access: None,
+        }
+    }
+
+    fn prompter(input: &str) -> Prompter<Cursor<&[u8]>, Vec<u8>> {
+        Prompter {
+            input: Cursor::new(input.as_bytes()),
+            output: Vec::new(),
+        }
+    }
+
+    fn answers() -> Answers {
+        Answers {
+            codexdir: PathBuf::from("/h/.codex"),
+            tmux: true,
+            model: DEFAULT_MODEL.to_owned(),
+            network_access: true,
+        }
+    }
+
+    #[test]
+    fn asks_each_question() {
+        let candidates = [PathBuf::from("/a"), PathBuf::from("/b")];
+        let mut prompter = prompter("2\nn\no3\n\n");
+
+        let answers = prompter
+            .ask_all(&no_flags(), answers(), &candidates)
+            .unwrap();
+
+        assert_eq!(
+            answers,
+            Answers {
+                codexdir: PathBuf::from("/b"),
+                tmux: false,
+                model: "o3".to_owned(),
+                network_access: true,
+            }
+        );
+        let shown = String::from_utf8(prompter.output).unwrap();
+        assert!(shown.contains("  1) /a\n  2) /b\n"), "{shown}");
+        assert!(shown.contains(&format!("[{DEFAULT_MODEL}]")), "{shown}");
+    }
+
+    #[test]
+    fn empty_answers_keep_the_defaults() {
+        let answers = prompter("\n\n\n\n")
+            .ask_all(&no_flags(), answers(), &[])
+            .unwrap();
+
+        assert_eq!(answers, self::answers());
+    }
+
+    #[test]
+    fn accepts_a_typed_codexdir() {
+        let dir = prompter("/elsewhere/.codex\n")
+            .choose_codexdir(&[PathBuf::from("/a")], Path::new("/a"))
+            .unwrap();
+
+        assert_eq!(dir, PathBuf::from("/elsewhere/.codex"));
+    }
+
+    #[test]
+    fn flags_skip_their_questions() {
+        let args = InitArgs {
+            codexdir: Some(PathBuf::from("/c")),
+            tmux: Some(false),
+            model: Some("o3".to_owned()),
+            network_access: Some(false),
+            ..no_flags()
+        };
+        let defaults = defaults(&args, &Config::default(), &[]).unwrap();
+
+        let mut prompter = prompter("");
+        let answers = prompter.ask_all(&args, defaults, &[]).unwrap();
+
+        assert_eq!(answers.codexdir, PathBuf::from("/c"));
+        assert!(!answers.tmux && !answers.network_access);
+        assert!(prompter.output.is_empty());
+    }
+
+    #[test]
+    fn repeats_unclear_confirmations() {
+        let mut prompter = prompter("maybe\nyes\n");
+
+        assert!(prompter.confirm("Overwrite?", false).unwrap());
+        let shown = String::from_utf8(prompter.output).unwrap();
+        assert_eq!(shown.matches("Overwrite? [y/N]: ").count(), 2);
+        assert!(shown.contains("please answer y or n"));
+    }
+
+    #[test]
+    fn closed_input_is_an_error() {
+        let err = prompter("").ask("Model", "o3").unwrap_err();
+
+        assert!(err.to_string().contains("closed"), "{err}");
+    }
+
+    #[test]
+    fn defaults_prefer_flags_then_the_current_config() {
+        let current = Config {
+            codexdir: Some(ConfigEntry {
+                value: "/configured".to_owned(),
+                source: PathBuf::from("/c/config.toml"),
+            }),
+            tmux: Some(false),
+            network_access: Some(false),
+            ..Default::default()
+        };
+        let args = InitArgs {
+            network_access: Some(true),
+            ..no_flags()
+        };
+
+        let answers = defaults(&args, &current, &[PathBuf::from("/found")]).unwrap();
+
+        assert_eq!(answers.codexdir, PathBuf::from("/configured"));
+        assert!(!answers.tmux);
+        assert!(answers.network_access);
+        assert_eq!(answers.model, DEFAULT_MODEL);
+    }
+
+    #[rstest]
+    fn offers_existing_codexdirs_once(#[from(temp_dir)] dir: PathBuf) {
+        let home = dir.join("home");
+        fs::create_dir_all(home.join(DOT_CODEX_DIR)).unwrap();
+        let codex_home = dir.join("codex-home");
+        fs::create_dir_all(&codex_home).unwrap();
+        let env = |name: &str| match name {
+            ENV_CODEX_CODEXDIR => Some(home.join(DOT_CODEX_DIR)),
+            ENV_CODEX_HOME => Some(codex_home.clone()),
+            ENV_HOME => Some(home.clone()),
+            _ => None,
+        };
+
+        assert_eq!(
+            codexdir_candidates(env),
+            [home.join(DOT_CODEX_DIR), codex_home.clone()]
+        );
+        assert!(codexdir_candidates(|_| Some(dir.join("missing"))).is_empty());
+    }
+
+    #[test]
+    fn keeps_comments_and_other_keys() {
+        let content = "# mine\nwrite_marker = true\ntmux = false\n";
+
+        let updated = render_config(content, Path::new("/c/config.toml"), &answers()).unwrap();
+
+        assert_eq!(
+            updated,
+            format!(
+                "# mine\nwrite_marker = true\ntmux = true\ncodexdir = \"/h/.codex\"\n\
+                 model = \"{DEFAULT_MODEL}\"\nnetwork_access = true\n"
+            )
+        );
+    }
+
+    #[test]
+    fn diffs_changed_lines() {
+        let diff = line_diff("a = 1\nb = 2\nc = 3\n", "a = 1\nb = 5\nc = 3\nd = 4\n");
+
+        assert_eq!(diff, " a = 1\n-b = 2\n+b = 5\n c = 3\n+d = 4\n");
+    }
+}
@@ -0,0 +1,535 @@
+//! Streaming reader for JSONL files.
+//!
+//! Session files are JSONL: one JSON record per line. [`JsonlReader`] reads them line by line
+//! from any buffered input and yields `(line_number, line)` pairs, so every feature that reads
+//! past the first line shares the same caps and the same idea of a line:
+//!
+//! * A leading UTF-8 byte order mark and a trailing `\r` (CRLF line endings) are removed from
+//!   every line, so files written on Windows read like their Unix equivalents
+//! * A last line without a trailing newline (a file codex was still writing) is yielded like
+//!   any other
+//! * A line longer than [`Limits::max_line_bytes`] is skipped without being held in memory,
+//!   and reported as [`LineError::TooLong`]; a line that is not UTF-8 is reported as
+//!   [`LineError::NotUtf8`]. Reading continues with the next line after either
+//! * Reading stops with [`LineError::OverBudget`] once [`Limits::max_total_bytes`] would be
+//!   exceeded, and with [`LineError::Io`] when the input fails
+//!
+//! [`parse_record`] deserializes a line into a known record type, telling a line that is not
+//! JSON apart from one holding another kind of JSON value ([`RecordError`]).
+
+use std::fmt;
+use std::io::BufRead;
+
+use serde::de::DeserializeOwned;
+
+use super::prelude::*;
+
+/// Longest line read by default, in bytes (without the line break).
+///
+/// Codex's first lines carry the session's instructions and are rarely more than a few
+/// hundred kilobytes; a line beyond this is almost certainly not a session record.
+pub(super) const DEFAULT_MAX_LINE_BYTES: usize = 16 * 1024 * 1024;
+
+/// The caps a [`JsonlReader`] applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Limits {
+    /// Longest line yielded, in bytes, not counting the line break.
+    pub(super) max_line_bytes: usize,
+    /// Most bytes read from the input in total, line breaks included (`None` for no cap).
+    pub(super) max_total_bytes: Option<u64>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_line_bytes: DEFAULT_MAX_LINE_BYTES,
+            max_total_bytes: None,
+        }
+    }
+}
+
+/// Why a line could not be yielded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum LineError {
+    /// The line is longer than [`Limits::max_line_bytes`]; it was skipped.
+    TooLong { line: usize, limit: usize },
+    /// The line is not valid UTF-8 after its first `valid_up_to` bytes; it was skipped.
+    NotUtf8 { line: usize, valid_up_to: usize },
+    /// Reading the line would exceed [`Limits::max_total_bytes`]; no more lines follow.
+    OverBudget { line: usize, limit: u64 },
+    /// The input failed while reading the line; no more lines follow.
+    Io {
+        line: usize,
+        kind: io::ErrorKind,
+        message: String,
+    },
+}
+
+impl LineError {
+    /// Returns whether no more lines follow the error.
+    #[cfg(feature = "cli")]
+    pub(super) fn is_fatal(&self) -> bool {
+        matches!(self, Self::OverBudget { .. } | Self::Io { .. })
+    }
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLong { line, limit } => {
+                write!(f, "line {line} is longer than {limit} bytes")
+            }
+            Self::NotUtf8 { line, valid_up_to } => {
+                write!(
+                    f,
+                    "line {line} is not valid UTF-8 (after byte {valid_up_to})"
+                )
+            }
+            Self::OverBudget { line, limit } => {
+                write!(
+                    f,
+                    "stopped at line {line}: read limit of {limit} bytes reached"
+                )
+            }
+            Self::Io { line, message, .. } => write!(f, "failed to read line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for LineError {}
+
+impl From<LineError> for io::Error {
+    fn from(err: LineError) -> Self {
+        match &err {
+            LineError::Io { kind, message, .. } => Self::new(*kind, message.clone()),
+            _ => Self::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+/// Why a line could not be read as a record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum RecordError {
+    /// The line is not valid JSON, or does not have the record's shape.
+    InvalidJson(String),
+    /// The line is valid JSON, but not an object; the kind of value it is (`an array`).
+    NotAnObject(&'static str),
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidJson(error) => write!(f, "not valid JSON: {error}"),
+            Self::NotAnObject(found) => write!(f, "valid JSON but not an object ({found})"),
+        }
+    }
+}
+
+/// Reads a JSONL input line by line (see the [module docs](self)).
+pub(super) struct JsonlReader<R> {
+    input: R,
+    limits: Limits,
+    /// The number of the last line read (1-based).
+    line: usize,
+    /// Bytes consumed from the input so far.
+    consumed: u64,
+    /// Set once the input ended or a fatal error was yielded.
+    done: bool,
+}
+
+/// How reading the bytes of one line ended.
+enum RawLine {
+    /// The input ended before the line started.
+    Eof,
+    /// The line was read; its bytes were dropped if it is too long.
+    Read { too_long: bool },
+    /// The line would exceed the total byte cap.
+    OverBudget,
+}
+
+impl JsonlReader<io::BufReader<fs::File>> {
+    /// Opens `path` for reading with `limits`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened.
+    pub(super) fn open(path: &Path, limits: Limits) -> io::Result<Self> {
+        Ok(Self::new(io::BufReader::new(fs::File::open(path)?), limits))
+    }
+}
+
+impl<R: BufRead> JsonlReader<R> {
+    /// Reads lines from `input` with `limits`.
+    pub(super) fn new(input: R, limits: Limits) -> Self {
+        Self {
+            input,
+            limits,
+            line: 0,
+            consumed: 0,
+            done: false,
+        }
+    }
+
+    /// Reads the bytes of the next line, without its `\n`, into `raw`.
+    fn read_raw(&mut self, raw: &mut Vec<u8>) -> io::Result<RawLine> {
+        let mut started = false;
+        let mut too_long = false;
+        loop {
+            let available = match self.input.fill_buf() {
+                Ok(available) => available,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            if available.is_empty() {
+                return Ok(if started {
+                    RawLine::Read { too_long }
+                } else {
+                    RawLine::Eof
+                });
+            }
+            let newline = available.iter().position(|&byte| byte == b'\n');
+            let chunk = newline.map_or(available.len(), |at| at + 1);
+            if self
+                .limits
+                .max_total_bytes
+                .is_some_and(|max| self.consumed + chunk as u64 > max)
+            {
+                return Ok(RawLine::OverBudget);
+            }
+            let content = &available[..newline.unwrap_or(chunk)];
+            if !too_long {
+                if raw.len() + content.len() > self.limits.max_line_bytes {
+                    too_long = true;
+                    raw.clear();
+                } else {
+                    raw.extend_from_slice(content);
+                }
+            }
+            started = true;
+            self.input.consume(chunk);
+            self.consumed += chunk as u64;
+            if newline.is_some() {
+                return Ok(RawLine::Read { too_long });
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for JsonlReader<R> {
+    type Item = std::result::Result<(usize, String), LineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut raw = Vec::new();
+        let read = self.read_raw(&mut raw);
+        let line = self.line + 1;
+        let too_long = match read {
+            Ok(RawLine::Read { too_long }) => too_long,
+            Ok(RawLine::Eof) => {
+                self.done = true;
+                return None;
+            }
+            Ok(RawLine::OverBudget) => {
+                self.done = true;
+                let limit = self.limits.max_total_bytes.unwrap_or_default();
+                return Some(Err(LineError::OverBudget { line, limit }));
+            }
+            Err(err) => {
+                self.done = true;
+                return Some(Err(LineError::Io {
+                    line,
+                    kind: err.kind(),
+                    message: err.to_string(),
+                }));
+            }
+        };
+        self.line = line;
+        if too_long {
+            let limit = self.limits.max_line_bytes;
+            return Some(Err(LineError::TooLong { line, limit }));
+        }
+        Some(match String::from_utf8(raw) {
+            Ok(text) => Ok((line, normalize_line(text))),
+            Err(err) => Err(LineError::NotUtf8 {
+                line,
+                valid_up_to: err.utf8_error().valid_up_to(),
+            }),
+        })
+    }
+}
+
+/// Removes a leading byte order mark and a trailing carriage return from a line.
+pub(super) fn normalize_line(mut line: String) -> String {
+    if line.ends_with('\r') {
+        line.pop();
+    }
+    match line.strip_prefix('\u{feff}') {
+        Some(rest) => rest.to_owned(),
+        None => line,
+    }
+}
+
+/// Deserializes `line` as a `T` record, telling a line that is not JSON apart from one holding
+/// another kind of JSON value.
+///
+/// A line that does not start with `{` is only checked for being valid JSON, since serde
+/// would otherwise read an array as a struct's fields in order.
+///
+/// # Errors
+///
+/// Returns [`RecordError::NotAnObject`] for valid JSON other than an object, and
+/// [`RecordError::InvalidJson`] for anything else that does not deserialize.
+pub(super) fn parse_record<T: DeserializeOwned>(line: &str) -> std::result::Result<T, RecordError> {
+    let value = line.trim_start();
+    if value.starts_with('{') {
+        return serde_json::from_str(line).map_err(|err| RecordError::InvalidJson(err.to_string()));
+    }
+    match serde_json::from_str::<serde::de::IgnoredAny>(line) {
+        Ok(_) => Err(RecordError::NotAnObject(json_kind(value))),
+        Err(err) => Err(RecordError::InvalidJson(err.to_string())),
+    }
+}
+
+/// Names the kind of a valid JSON value that is not an object, from its first character.
+fn json_kind(value: &str) -> &'static str {
+    match value.chars().next() {
+        Some('[') => "an array",
+        Some('"') => "a string",
+        Some('t' | 'f') => "a boolean",
+        Some('n') => "null",
+        _ => "a number",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::temp_dir;
+    use rstest::rstest;
+    use serde::Deserialize;
+
+    /// A reader whose buffer holds `capacity` bytes, so lines span several fills.
+    fn reader(input: &[u8], capacity: usize, limits: Limits) -> JsonlReader<impl BufRead + '_> {
+        JsonlReader::new(io::BufReader::with_capacity(capacity, input), limits)
+    }
+
+    fn read_all(
+        input: &[u8],
+        limits: Limits,
+    ) -> Vec<std::result::Result<(usize, String), LineError>> {
+        reader(input, 4, limits).collect()
+    }
+
+    fn ok(line: usize, text: &str) -> std::result::Result<(usize, String), LineError> {
+        Ok((line, text.to_owned()))
+    }
+
+    fn max_line(max_line_bytes: usize) -> Limits {
+        Limits {
+            max_line_bytes,
+            ..Limits::default()
+        }
+    }
+
+    #[test]
+    fn reads_a_clean_file() {
+        let input = b"{\"a\":1}\n{\"b\":2}\n\n{\"c\":3}\n";
+
+        assert_eq!(
+            read_all(input, Limits::default()),
+            [
+                ok(1, r#"{"a":1}"#),
+                ok(2, r#"{"b":2}"#),
+                ok(3, ""),
+                ok(4, r#"{"c":3}"#)
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case(b"")]
+    #[case(b"\n")]
+    fn an_empty_input_has_at_most_an_empty_line(#[case] input: &[u8]) {
+        let lines = read_all(input, Limits::default());
+
+        assert_eq!(lines, &[ok(1, "")][..input.len()]);
+    }
+
+    #[test]
+    fn yields_a_truncated_last_line() {
+        let input = b"{\"a\":1}\n{\"b\":";
+
+        assert_eq!(
+            read_all(input, Limits::default()),
+            [ok(1, r#"{"a":1}"#), ok(2, r#"{"b":"#)]
+        );
+    }
+
+    #[test]
+    fn normalizes_bom_and_crlf() {
+        let input = "\u{feff}{\"a\":1}\r\n{\"b\":\r2}\r\n\u{feff}x";
+
+        assert_eq!(
+            read_all(input.as_bytes(), Limits::default()),
+            [ok(1, r#"{"a":1}"#), ok(2, "{\"b\":\r2}"), ok(3, "x")]
+        );
+    }
+
+    #[rstest]
+    #[case("abc", "abc")]
+    #[case("\u{feff}abc\r", "abc")]
+    #[case("ab\rc", "ab\rc")]
+    #[case("\u{feff}", "")]
+    fn normalizes_lines(#[case] line: &str, #[case] expected: &str) {
+        assert_eq!(normalize_line(line.to_owned()), expected);
+    }
+
+    #[test]
+    fn skips_a_huge_line_and_reads_on() {
+        let huge = format!("{}\n", "x".repeat(1_000));
+        let input = format!("ok\n{huge}exactly10!\nafter");
+
+        assert_eq!(
+            read_all(input.as_bytes(), max_line(10)),
+            [
+                ok(1, "ok"),
+                Err(LineError::TooLong { line: 2, limit: 10 }),
+                ok(3, "exactly10!"),
+                ok(4, "after"),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_a_huge_line_without_a_line_break() {
+        let input = io::BufReader::new(io::Read::take(io::repeat(b'x'), 8 * 1024 * 1024));
+        let mut lines = JsonlReader::new(input, max_line(16));
+
+        assert_eq!(
+            lines.next(),
+            Some(Err(LineError::TooLong { line: 1, limit: 16 }))
+        );
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn reports_binary_garbage_and_reads_on() {
+        let input = b"\xff\xfe\x00garbage\nok\n{\"a\":\"\xc3\x28\"}\n";
+
+        assert_eq!(
+            read_all(input, Limits::default()),
+            [
+                Err(LineError::NotUtf8 {
+                    line: 1,
+                    valid_up_to: 0
+                }),
+                ok(2, "ok"),
+                Err(LineError::NotUtf8 {
+                    line: 3,
+                    valid_up_to: 6
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_at_the_total_byte_cap() {
+        let limits = Limits {
+            max_total_bytes: Some(8),
+            ..Limits::default()
+        };
+
+        assert_eq!(
+            read_all(b"abc\ndef\nghi\n", limits),
+            [
+                ok(1, "abc"),
+                ok(2, "def"),
+                Err(LineError::OverBudget { line: 3, limit: 8 })
+            ]
+        );
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn stops_after_a_read_error() {
+        struct Failing;
+        impl io::Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk on fire"))
+            }
+        }
+        let input = io::BufReader::new(io::Read::chain(&b"ok\npart"[..], Failing));
+        let mut lines = JsonlReader::new(input, Limits::default());
+
+        assert_eq!(lines.next(), Some(ok(1, "ok")));
+        let err = lines.next().unwrap().unwrap_err();
+        assert!(err.is_fatal());
+        assert_eq!(err.to_string(), "failed to read line 2: disk on fire");
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::Other);
+        assert_eq!(lines.next(), None);
+    }
+
+    #[rstest]
+    fn opens_files(#[from(temp_dir)] dir: PathBuf) {
+        let path = dir.join("a.jsonl");
+        fs::write(&path, "one\r\ntwo").unwrap();
+
+        let lines: Vec<_> = JsonlReader::open(&path, Limits::default())
+            .unwrap()
+            .collect();
+
+        assert_eq!(lines, [ok(1, "one"), ok(2, "two")]);
+        assert!(JsonlReader::open(&dir.join("missing.jsonl"), Limits::default()).is_err());
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn describes_line_errors() {
+        let too_long = LineError::TooLong { line: 2, limit: 10 };
+        let not_utf8 = LineError::NotUtf8 {
+            line: 3,
+            valid_up_to: 6,
+        };
+
+        assert_eq!(too_long.to_string(), "line 2 is longer than 10 bytes");
+        assert_eq!(
+            not_utf8.to_string(),
+            "line 3 is not valid UTF-8 (after byte 6)"
+        );
+        assert!(!too_long.is_fatal() && !not_utf8.is_fatal());
+        assert_eq!(io::Error::from(not_utf8).kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Record {
+        id: String,
+    }
+
+    #[rstest]
+    #[case(r#"{"id":"a"}"#, Ok(Record { id: "a".into() }))]
+    #[case(r#"  {"id":"a"}"#, Ok(Record { id: "a".into() }))]
+    #[case(r#"["a"]"#, Err(RecordError::NotAnObject("an array")))]
+    #[case(r#""a""#, Err(RecordError::NotAnObject("a string")))]
+    #[case("true", Err(RecordError::NotAnObject("a boolean")))]
+    #[case("null", Err(RecordError::NotAnObject("null")))]
+    #[case("-1.5", Err(RecordError::NotAnObject("a number")))]
+    fn parses_records(
+        #[case] line: &str,
+        #[case] expected: std::result::Result<Record, RecordError>,
+    ) {
+        assert_eq!(parse_record::<Record>(line), expected);
+    }
+
+    #[rstest]
+    #[case("not json")]
+    #[case(r#"{"id":42}"#)]
+    #[case(r#"{"id":"a""#)]
+    fn classifies_invalid_records(#[case] line: &str) {
+        let err = parse_record::<Record>(line).unwrap_err();
+
+        assert!(matches!(err, RecordError::InvalidJson(_)), "{err:?}");
+        assert!(err.to_string().starts_with("not valid JSON: "), "{err}");
+    }
+}
@@ -0,0 +1,183 @@
+//! Launch bookkeeping and the `kill` subcommand.
+//!
+//! When amg launches codex it records where the instance runs (the tmux window id, or the pid
+//! of an inline process) in the state file, keyed by session id. `amg kill` uses these records
+//! to close the window (`tmux kill-window`) or terminate the process. Bookkeeping is
+//! best-effort: failing to update the state file never blocks a launch.
+
+use super::args::KillArgs;
+use super::prelude::*;
+use super::state::{self, Launch, LaunchTarget, State};
+use super::{alias, policy, process};
+
+/// Records a launched codex instance for `session_id`, resumed for `branch` and recording
+/// `recorded_branch` in its file, replacing any previous record.
+///
+/// Failures are logged as warnings. In portable mode nothing is recorded.
+pub(super) fn record_launch(
+    session_id: &str,
+    branch: &str,
+    recorded_branch: Option<&str>,
+    target: LaunchTarget,
+) {
+    if policy::is_portable() {
+        return;
+    }
+    let launch = Launch {
+        branch: branch.to_owned(),
+        recorded_branch: recorded_branch.map(str::to_owned),
+        target,
+    };
+    if let Err(err) = state::update(|state| {
+        state.launches.insert(session_id.to_owned(), launch);
+        Ok(())
+    }) {
+        warn!("failed to record launch: {err:#}");
+    }
+}
+
+/// Removes the launch record for `session_id` (for example, after an inline run exits).
+///
+/// Failures are logged at debug level. In portable mode there is nothing to forget.
+pub(super) fn forget_launch(session_id: &str) {
+    if policy::is_portable() {
+        return;
+    }
+    if let Err(err) = state::update(|state| {
+        state.launches.remove(session_id);
+        Ok(())
+    }) {
+        debug!("failed to forget launch: {err:#}");
+    }
+}
+
+/// Which launch records `kill` targets.
+#[derive(Debug, PartialEq, Eq)]
+enum Selector {
+    Branch(String),
+    Id(String),
+}
+
+/// Handles the `kill` subcommand.
+///
+/// Having nothing to kill is not an error: a notice is logged and the exit code is success.
+///
+/// # Errors
+///
+/// Returns an error if the state file cannot be read or written, or `tmux` / `kill` cannot be
+/// executed.
+pub(super) fn run_kill(args: KillArgs) -> Result<ExitCode> {
+    let selector = match (args.id, args.branch) {
+        (Some(id), _) => Selector::Id(id),
+        (None, Some(branch)) => Selector::Branch(alias::resolve_branch(&branch)),
+        (None, None) => bail!("pass a branch or --id"),
+    };
+
+    state::update(|state| {
+        let selected = select_launches(state, &selector);
+        if selected.is_empty() {
+            info!("nothing to kill for {selector:?}");
+        }
+        for session_id in selected {
+            if let Some(launch) = state.launches.remove(&session_id) {
+                kill_launch(&session_id, &launch)?;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Returns the session ids of the launch records matching `selector`.
+fn select_launches(state: &State, selector: &Selector) -> Vec<String> {
+    state
+        .launches
+        .iter()
+        .filter(|(id, launch)| match selector {
+            Selector::Id(want) => *id == want,
+            Selector::Branch(want) => launch.branch == *want,
+        })
+        .map(|(id, _)| id.clone())
+        .collect()
+}
+
+fn kill_launch(session_id: &str, launch: &Launch) -> Result<()> {
+    match &launch.target {
+        LaunchTarget::TmuxWindow { window_id } => {
+            if process::kill_tmux_window(window_id)? {
+                info!(id = session_id, branch = %launch.branch, window_id, "closed tmux window");
+            } else {
+                info!(id = session_id, window_id, "tmux window is already gone");
+            }
+        }
+        LaunchTarget::Process { pid } => {
+            // A stale record's pid may have been reused; only signal codex processes.
+            let name = process::process_name(*pid);
+            if name.as_deref().is_some_and(is_codex_process) && process::terminate_process(*pid)? {
+                info!(id = session_id, branch = %launch.branch, pid, "terminated codex process");
+            } else {
+                info!(id = session_id, pid, "codex process is no longer running");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_codex_process(name: &str) -> bool {
+    Path::new(name)
+        .file_name()
+        .and_then(OsStr::to_str)
+        .is_some_and(|base| base.starts_with("codex"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn state_with(launches: &[(&str, &str, LaunchTarget)]) -> State {
+        let mut state = State::default();
+        for (id, branch, target) in launches {
+            state.launches.insert(
+                (*id).to_owned(),
+                Launch {
+                    branch: (*branch).to_owned(),
+                    recorded_branch: None,
+                    target: target.clone(),
+                },
+            );
+        }
+        state
+    }
+
+    fn window(id: &str) -> LaunchTarget {
+        LaunchTarget::TmuxWindow {
+            window_id: id.to_owned(),
+        }
+    }
+
+    #[rstest]
+    #[case(Selector::Branch("main".into()), &["a", "c"])]
+    #[case(Selector::Id("b".into()), &["b"])]
+    #[case(Selector::Branch("gone".into()), &[])]
+    #[case(Selector::Id("zzz".into()), &[])]
+    fn selects_launches(#[case] selector: Selector, #[case] expected: &[&str]) {
+        let state = state_with(&[
+            ("a", "main", window("@1")),
+            ("b", "dev", LaunchTarget::Process { pid: 42 }),
+            ("c", "main", window("@3")),
+        ]);
+
+        assert_eq!(select_launches(&state, &selector), expected);
+    }
+
+    #[rstest]
+    #[case("codex", true)]
+    #[case("/usr/local/bin/codex", true)]
+    #[case("codex-x86_64-unknown-linux-musl", true)]
+    #[case("bash", false)]
+    #[case("/opt/notcodex", false)]
+    fn recognizes_codex_processes(#[case] name: &str, #[case] expected: bool) {
+        assert_eq!(is_codex_process(name), expected);
+    }
+}
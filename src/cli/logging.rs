@@ -1,9 +1,15 @@
 //! Logging initialization and configuration.
 //!
 //! This module provides initialization for structured logging using the `tracing` crate.
-//! Logging is configured to use environment-based filtering and output to stderr.
+//! Logging is configured to use environment-based filtering and output to stderr, optionally
+//! teeing events to a log file (`--log-file` / `AMG_LOG_FILE`).
 
-use std::sync::OnceLock;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+use super::util;
 
 /// Initializes the tracing subscriber for structured logging.
 ///
@@ -14,18 +20,92 @@ use std::sync::OnceLock;
 /// * Filter level is controlled by the `RUST_LOG` environment variable (defaults to `info`)
 /// * Output goes to stderr
 /// * Target information is disabled
-/// * Timestamps are disabled
-pub(super) fn init_tracing() {
+/// * Timestamps are disabled on stderr
+/// * If `log_file` is given, events are also appended to it with timestamps
+///
+/// If the log file cannot be opened, logging falls back to stderr only and a single warning
+/// is emitted.
+pub(super) fn init_tracing(log_file: Option<&Path>) {
     use tracing_subscriber::EnvFilter;
+    use tracing_subscriber::fmt;
+    use tracing_subscriber::prelude::*;
 
     static INIT: OnceLock<()> = OnceLock::new();
     INIT.get_or_init(|| {
         let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-        let _ = tracing_subscriber::fmt()
-            .with_env_filter(filter)
-            .with_writer(std::io::stderr)
+        let stderr_layer = fmt::layer()
+            .with_writer(io::stderr)
             .with_target(false)
-            .without_time()
+            .without_time();
+
+        let (file, file_error) = match log_file.map(|path| (path, open_log_file(path))) {
+            Some((_, Ok(file))) => (Some(file), None),
+            Some((path, Err(err))) => (None, Some((path, err))),
+            None => (None, None),
+        };
+        let file_layer = file.map(|file| {
+            fmt::layer()
+                .with_writer(Mutex::new(file))
+                .with_ansi(false)
+                .with_target(false)
+        });
+
+        let _ = tracing_subscriber::registry()
+            .with(filter)
+            .with(stderr_layer)
+            .with(file_layer)
             .try_init();
+
+        if let Some((path, err)) = file_error {
+            tracing::warn!(
+                path = %util::human_path(path),
+                "failed to open log file, logging to stderr only: {err}"
+            );
+        }
     });
 }
+
+/// Opens a log file for appending, creating it and its parent directories as needed.
+fn open_log_file(path: &Path) -> io::Result<File> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::temp_dir;
+    use rstest::rstest;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    #[rstest]
+    fn creates_missing_parent_directories(#[from(temp_dir)] dir: PathBuf) {
+        let path = dir.join("nested/logs/amg.log");
+
+        open_log_file(&path).expect("log file should open");
+
+        assert!(path.is_file());
+    }
+
+    #[rstest]
+    fn appends_to_existing_file(#[from(temp_dir)] dir: PathBuf) {
+        let path = dir.join("amg.log");
+        fs::write(&path, "first\n").unwrap();
+
+        let mut file = open_log_file(&path).expect("log file should open");
+        file.write_all(b"second\n").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first\nsecond\n");
+    }
+
+    #[rstest]
+    fn fails_when_parent_is_a_file(#[from(temp_dir)] dir: PathBuf) {
+        let blocker = dir.join("not-a-dir");
+        fs::write(&blocker, "").unwrap();
+
+        assert!(open_log_file(&blocker.join("amg.log")).is_err());
+    }
+}
@@ -0,0 +1,131 @@
+//! Session marker files (`--write-marker`).
+//!
+//! Editor tooling that has a directory open wants to know which codex session belongs to it.
+//! With `--write-marker` (or `write_marker = true` in a config file), `resume` writes a small
+//! JSON file named [`MARKER_FILE`] into the session cwd once a session is matched and before
+//! codex starts. The file is replaced atomically, so readers never see a partial marker.
+
+use std::io::Write;
+
+use serde::Serialize;
+
+use super::datetime::UtcDateTime;
+use super::git;
+use super::prelude::*;
+use super::util;
+
+/// File name of the marker written into the session cwd.
+pub(super) const MARKER_FILE: &str = ".amg-session";
+
+/// The contents of a marker file.
+#[derive(Debug, Serialize)]
+pub(super) struct Marker<'a> {
+    /// The resumed session's id.
+    pub(super) session_id: &'a str,
+    /// The branch the session was matched for (empty for an `--any` session without one).
+    pub(super) branch: &'a str,
+    /// The branch the session file records, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) recorded_branch: Option<&'a str>,
+    /// The session file being resumed.
+    pub(super) source_jsonl: &'a Path,
+    /// When the marker was written (RFC 3339, UTC).
+    pub(super) written_at: String,
+}
+
+impl<'a> Marker<'a> {
+    /// Creates a marker stamped with the current time.
+    pub(super) fn new(
+        session_id: &'a str,
+        branch: &'a str,
+        recorded_branch: Option<&'a str>,
+        source_jsonl: &'a Path,
+    ) -> Self {
+        Self {
+            session_id,
+            branch,
+            recorded_branch,
+            source_jsonl,
+            written_at: UtcDateTime::from_system_time(SystemTime::now()).rfc3339(),
+        }
+    }
+}
+
+/// Writes `marker` to [`MARKER_FILE`] in `dir`, replacing any previous marker.
+///
+/// The marker is written to a temporary sibling and renamed into place. When `dir` is in a
+/// git work tree that does not ignore the marker, a hint to exclude it is logged.
+///
+/// # Returns
+///
+/// Returns the path of the marker file.
+///
+/// # Errors
+///
+/// Returns an error if the marker cannot be serialized, written, or renamed into place.
+pub(super) fn write_marker(dir: &Path, marker: &Marker<'_>) -> Result<PathBuf> {
+    let path = dir.join(MARKER_FILE);
+    let tmp = dir.join(format!("{MARKER_FILE}.{}.tmp", std::process::id()));
+    let json = serde_json::to_string_pretty(marker).context("failed to serialize the marker")?;
+
+    let written = fs::File::create(&tmp)
+        .and_then(|mut file| {
+            writeln!(file, "{json}")?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&tmp, &path));
+    if let Err(err) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(err).with_context(|| format!("failed to write {}", util::human_path(&path)));
+    }
+
+    if git::is_ignored(dir, MARKER_FILE) == Some(false) {
+        info!(
+            "{MARKER_FILE} is not ignored by git in {}; add it to .git/info/exclude (or a \
+             .gitignore) to keep it out of `git status`",
+            util::human_path(dir)
+        );
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::temp_dir;
+    use rstest::rstest;
+
+    #[rstest]
+    fn writes_and_replaces_the_marker(#[from(temp_dir)] dir: PathBuf) {
+        let source = dir.join("rollout-a.jsonl");
+        fs::write(dir.join(MARKER_FILE), "stale").unwrap();
+
+        let path = write_marker(
+            &dir,
+            &Marker::new("id-1", "main", Some("origin/main"), &source),
+        )
+        .unwrap();
+
+        let marker: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(path, dir.join(MARKER_FILE));
+        assert_eq!(marker["session_id"], "id-1");
+        assert_eq!(marker["branch"], "main");
+        assert_eq!(marker["recorded_branch"], "origin/main");
+        assert_eq!(marker["source_jsonl"], source.to_str().unwrap());
+        assert!(marker["written_at"].as_str().unwrap().ends_with('Z'));
+        let leftovers: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
+    #[rstest]
+    fn fails_for_a_missing_directory(#[from(temp_dir)] dir: PathBuf) {
+        let missing = dir.join("missing");
+
+        assert!(write_marker(&missing, &Marker::new("id", "main", None, &missing)).is_err());
+    }
+}
@@ -0,0 +1,397 @@
+//! Session store layout migration.
+//!
+//! This module implements the `migrate-layout` subcommand. Older Codex versions wrote session
+//! files flat, as `rollout-*.jsonl` at the top of the codexdir or directly under `sessions/`;
+//! newer ones use `sessions/YYYY/MM/DD/`, which amg's newest-first walks rely on. The command
+//! moves every flat file into its dated directory, dated by the session's own timestamp (or
+//! else its rollout file name), and never replaces an existing file.
+//!
+//! Each move hard-links the file at its destination and then unlinks the old name, so a
+//! destination that appeared after planning (codex writing concurrently, a second run) makes
+//! the move fail instead of being replaced. When the destination is on another file system, the
+//! file is copied to a temporary sibling of the destination, synced, linked into place the same
+//! way, and only then removed from its old location, so an interrupted move never leaves a
+//! partial session file.
+
+use std::collections::HashSet;
+use std::io::Write;
+
+use serde_json::Value;
+
+use super::args::MigrateLayoutArgs;
+use super::config;
+use super::prelude::*;
+use super::stats::{self, Day};
+use super::{import, scan, util};
+
+/// What `migrate-layout` does with one flat session file.
+#[derive(Debug, PartialEq, Eq)]
+enum Step {
+    /// Move the file to its dated destination.
+    Move { from: PathBuf, to: PathBuf },
+    /// Leave the file: its destination already exists (or another file is moving there).
+    Exists { from: PathBuf, to: PathBuf },
+    /// Leave the file: neither its first line nor its name gives a date.
+    Undated { from: PathBuf },
+}
+
+/// Handles the `migrate-layout` subcommand.
+///
+/// # Errors
+///
+/// Returns an error if the codexdir is invalid, a move fails (earlier moves are kept), or
+/// stdout cannot be written.
+pub(super) fn run_migrate_layout(args: MigrateLayoutArgs) -> Result<ExitCode> {
+    let codexdir = args
+        .codexdir
+        .map(Ok)
+        .unwrap_or_else(config::default_codexdir)?;
+    util::require_dir(&codexdir, "codexdir", Some("CODEX_CODEXDIR"))?;
+
+    let steps = plan(&codexdir)?;
+    let mut out = io::stdout().lock();
+    let mut moved = 0;
+    for step in &steps {
+        match step {
+            Step::Move { from, to } if args.dry_run => {
+                writeln!(
+                    out,
+                    "would move {} -> {}",
+                    util::human_path(from),
+                    util::human_path(to)
+                )?;
+            }
+            Step::Move { from, to } => {
+                move_file(from, to)
+                    .with_context(|| format!("failed to move {}", util::human_path(from)))?;
+                moved += 1;
+                writeln!(
+                    out,
+                    "moved {} -> {}",
+                    util::human_path(from),
+                    util::human_path(to)
+                )?;
+            }
+            Step::Exists { from, to } => writeln!(
+                out,
+                "skipped {}: {} already exists",
+                util::human_path(from),
+                util::human_path(to)
+            )?,
+            Step::Undated { from } => {
+                writeln!(
+                    out,
+                    "skipped {}: no timestamp to date it by",
+                    util::human_path(from)
+                )?;
+            }
+        }
+    }
+    let planned = steps
+        .iter()
+        .filter(|step| matches!(step, Step::Move { .. }))
+        .count();
+    let skipped = steps.len() - planned;
+    if args.dry_run {
+        writeln!(out, "{planned} file(s) to move, {skipped} skipped")?;
+    } else {
+        writeln!(out, "{moved} file(s) moved, {skipped} skipped")?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Plans a step for every flat session file in `codexdir`, in path order.
+///
+/// # Errors
+///
+/// Returns an error if the codexdir or its `sessions/` directory cannot be read.
+fn plan(codexdir: &Path) -> Result<Vec<Step>> {
+    let mut claimed = HashSet::new();
+    let mut steps = Vec::new();
+    for from in flat_files(codexdir)? {
+        let Some(to) = dated_destination(codexdir, &from) else {
+            steps.push(Step::Undated { from });
+            continue;
+        };
+        if to.exists() || !claimed.insert(to.clone()) {
+            steps.push(Step::Exists { from, to });
+        } else {
+            steps.push(Step::Move { from, to });
+        }
+    }
+    Ok(steps)
+}
+
+/// Lists the session files outside the dated layout: `rollout-*.jsonl` files at the top of
+/// `codexdir` and `.jsonl` files directly under `sessions/`, sorted.
+fn flat_files(codexdir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = jsonl_files_in(codexdir)?
+        .into_iter()
+        .filter(|path| scan::is_rollout_file_name(path))
+        .collect::<Vec<_>>();
+    let sessions = codexdir.join(SESSIONS_DIR);
+    if sessions.is_dir() {
+        files.extend(jsonl_files_in(&sessions)?);
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Lists the regular `.jsonl` files directly in `dir`.
+fn jsonl_files_in(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries =
+        fs::read_dir(dir).with_context(|| format!("cannot read {}", util::human_path(dir)))?;
+    Ok(entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+        .map(|entry| entry.path())
+        .filter(|path| scan::is_jsonl(path))
+        .collect())
+}
+
+/// Returns where `file` belongs in the dated layout, `sessions/YYYY/MM/DD/<name>`, or `None`
+/// if it cannot be dated.
+fn dated_destination(codexdir: &Path, file: &Path) -> Option<PathBuf> {
+    let (year, month, day) = session_day(file)?;
+    Some(
+        codexdir
+            .join(SESSIONS_DIR)
+            .join(format!("{year:04}"))
+            .join(format!("{month:02}"))
+            .join(format!("{day:02}"))
+            .join(file.file_name()?),
+    )
+}
+
+/// Returns the UTC day of a session: its first line's timestamp, or else the date in its
+/// rollout file name.
+fn session_day(file: &Path) -> Option<Day> {
+    let embedded = scan::read_first_line(file)
+        .ok()
+        .flatten()
+        .and_then(|line| serde_json::from_str::<Value>(&line).ok())
+        .and_then(|event| import::embedded_time(&event))
+        .map(|time| (time.year, time.month, time.day));
+    embedded.or_else(|| {
+        file.file_name()
+            .and_then(OsStr::to_str)
+            .and_then(stats::day_from_file_name)
+    })
+}
+
+/// Moves `from` to `to`, creating the destination directory. Falls back to
+/// [`copy_then_remove`] when the two are on different file systems.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be created, `to` already exists, or the file
+/// cannot be moved; `from` is then left in place.
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", util::human_path(parent)))?;
+    }
+    match link_new(from, to) {
+        Ok(()) => remove_original(from, to),
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+            debug!(from = %util::human_path(from), "link crosses file systems; copying instead");
+            copy_then_remove(from, to)
+        }
+        Err(err) => Err(err).with_context(|| format!("failed to link {}", util::human_path(to))),
+    }
+}
+
+/// Copies `from` to a temporary sibling of `to`, syncs it, links it to `to`, and then
+/// removes `from`.
+///
+/// # Errors
+///
+/// Returns an error if any step fails, including when `to` already exists. The temporary copy
+/// is always removed; if only the final removal fails, both files exist and the error says so.
+fn copy_then_remove(from: &Path, to: &Path) -> Result<()> {
+    let tmp = to.with_extension(format!("tmp.{}", std::process::id()));
+    let copied = fs::copy(from, &tmp)
+        .and_then(|_| fs::File::open(&tmp)?.sync_all())
+        .and_then(|()| link_new(&tmp, to));
+    let _ = fs::remove_file(&tmp);
+    if let Err(err) = copied {
+        return Err(err).with_context(|| format!("failed to copy to {}", util::human_path(to)));
+    }
+    remove_original(from, to)
+}
+
+/// Gives `from` the additional name `to`, failing with [`io::ErrorKind::AlreadyExists`]
+/// instead of replacing an existing `to`.
+fn link_new(from: &Path, to: &Path) -> io::Result<()> {
+    fs::hard_link(from, to).map_err(|err| {
+        if err.kind() == io::ErrorKind::AlreadyExists {
+            io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", util::human_path(to)),
+            )
+        } else {
+            err
+        }
+    })
+}
+
+/// Removes `from` once its contents are in place at `to`.
+fn remove_original(from: &Path, to: &Path) -> Result<()> {
+    fs::remove_file(from).with_context(|| {
+        format!(
+            "{} is in place but failed to remove the original",
+            util::human_path(to)
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::{SessionFile, temp_dir};
+    use rstest::rstest;
+
+    const ID: &str = "0199a1b2-c3d4-7e5f-8a9b-0c1d2e3f4a5b";
+    const NAME: &str = "rollout-2025-03-09T08-07-06-0199a1b2-c3d4-7e5f-8a9b-0c1d2e3f4a5b.jsonl";
+
+    fn dated(dir: &Path, date: &str, name: &str) -> PathBuf {
+        date.split('/')
+            .fold(dir.join(SESSIONS_DIR), |path, part| path.join(part))
+            .join(name)
+    }
+
+    #[rstest]
+    fn plans_moves_for_flat_files_only(#[from(temp_dir)] dir: PathBuf) {
+        let top = dir.join(NAME);
+        let flat = dir.join(SESSIONS_DIR).join("escalation.jsonl");
+        SessionFile::new("main", ID).write(&top);
+        SessionFile::new("main", ID)
+            .timestamp("2024-12-31T23:59:59.5Z")
+            .write(&flat);
+        SessionFile::new("main", ID).write(dated(&dir, "2025/01/01", "rollout-x.jsonl"));
+        fs::write(dir.join("notes.jsonl"), "{}\n").unwrap();
+
+        let steps = plan(&dir).unwrap();
+
+        assert_eq!(
+            steps,
+            [
+                Step::Move {
+                    to: dated(&dir, "2025/03/09", NAME),
+                    from: top,
+                },
+                Step::Move {
+                    to: dated(&dir, "2024/12/31", "escalation.jsonl"),
+                    from: flat,
+                },
+            ]
+        );
+    }
+
+    #[rstest]
+    fn prefers_the_embedded_timestamp_over_the_name(#[from(temp_dir)] dir: PathBuf) {
+        let file = dir.join(SESSIONS_DIR).join(NAME);
+        SessionFile::new("main", ID)
+            .timestamp("2025-03-10T01:00:00+02:00")
+            .write(&file);
+
+        assert_eq!(
+            dated_destination(&dir, &file),
+            Some(dated(&dir, "2025/03/10", NAME))
+        );
+    }
+
+    #[rstest]
+    fn skips_collisions_and_undated_files(#[from(temp_dir)] dir: PathBuf) {
+        let top = dir.join(NAME);
+        let flat = dir.join(SESSIONS_DIR).join(NAME);
+        let undated = dir.join(SESSIONS_DIR).join("escalation.jsonl");
+        SessionFile::new("main", ID).write(&top);
+        SessionFile::new("main", ID).write(&flat);
+        SessionFile::new("main", ID).write(&undated);
+
+        let steps = plan(&dir).unwrap();
+
+        let to = dated(&dir, "2025/03/09", NAME);
+        assert_eq!(
+            steps,
+            [
+                Step::Move {
+                    from: top,
+                    to: to.clone(),
+                },
+                Step::Undated { from: undated },
+                Step::Exists { from: flat, to },
+            ]
+        );
+    }
+
+    #[rstest]
+    fn never_replaces_an_existing_file(#[from(temp_dir)] dir: PathBuf) {
+        SessionFile::new("main", ID).write(dir.join(NAME));
+        SessionFile::new("main", ID).write(dated(&dir, "2025/03/09", NAME));
+
+        assert!(matches!(&plan(&dir).unwrap()[..], [Step::Exists { .. }]));
+    }
+
+    #[rstest]
+    #[case::rename(false)]
+    #[case::copy(true)]
+    fn moves_files_into_place(#[from(temp_dir)] dir: PathBuf, #[case] copy: bool) {
+        let from = dir.join(NAME);
+        SessionFile::new("main", ID).write(&from);
+        let contents = fs::read(&from).unwrap();
+        let to = dated(&dir, "2025/03/09", NAME);
+
+        if copy {
+            fs::create_dir_all(to.parent().unwrap()).unwrap();
+            copy_then_remove(&from, &to).unwrap();
+        } else {
+            move_file(&from, &to).unwrap();
+        }
+
+        assert!(!from.exists());
+        assert_eq!(fs::read(&to).unwrap(), contents);
+        let leftovers = fs::read_dir(to.parent().unwrap()).unwrap().count();
+        assert_eq!(leftovers, 1);
+    }
+
+    #[rstest]
+    #[case::rename(false)]
+    #[case::copy(true)]
+    fn a_destination_created_after_planning_is_kept(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] copy: bool,
+    ) {
+        let from = dir.join(NAME);
+        SessionFile::new("main", ID).write(&from);
+        let [Step::Move { to, .. }] = &plan(&dir).unwrap()[..] else {
+            panic!("expected a single move");
+        };
+        SessionFile::new("other", ID).write(to);
+        let (original, existing) = (fs::read(&from).unwrap(), fs::read(to).unwrap());
+
+        let err = if copy {
+            copy_then_remove(&from, to).unwrap_err()
+        } else {
+            move_file(&from, to).unwrap_err()
+        };
+
+        assert!(format!("{err:#}").contains("already exists"), "{err:#}");
+        assert_eq!(fs::read(&from).unwrap(), original);
+        assert_eq!(fs::read(to).unwrap(), existing);
+        let leftovers = fs::read_dir(to.parent().unwrap()).unwrap().count();
+        assert_eq!(leftovers, 1);
+    }
+
+    #[rstest]
+    fn a_failed_copy_keeps_the_original(#[from(temp_dir)] dir: PathBuf) {
+        let from = dir.join(NAME);
+        SessionFile::new("main", ID).write(&from);
+        let to = dir.join("missing").join(NAME);
+
+        assert!(copy_then_remove(&from, &to).is_err());
+        assert!(from.exists());
+        assert!(!to.exists());
+    }
+}
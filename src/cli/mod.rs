@@ -8,51 +8,203 @@
 //! The CLI is organized into several submodules:
 //!
 //! * Command-line argument parsing using `clap`
+//! * Environment sanity checks run before any subcommand
 //! * Session scanning and matching logic
+//! * Streaming JSONL reading with line and size caps
+//! * Matching session lines piped on stdin (`--stdin-jsonl`)
+//! * Exclusion patterns for the walk (`--exclude`, `.amgignore`)
+//! * Branch listing (`amg branches`, with a porcelain mode for completion)
+//! * Partial branch search with ranked results (`amg find`)
+//! * Last-hit cache for repeated lookups
+//! * Session hints for shell prompts (`amg prompt-hint`, no walk)
+//! * Session index for listings without a scan (`--cached`)
+//! * Session lookup and resume commands for embedders (async lookup with the `async` feature)
+//! * Selection explanations (`--explain`)
+//! * Matched session details (`amg show`, with the raw first line)
+//! * Machine-readable scan dumps (NDJSON)
+//! * Session statistics and per-day trends
+//! * Session import into the dated store layout
+//! * Migration of flat session files into the dated layout
 //! * Codex command building
 //! * Process execution and tmux integration
+//! * Interactive session picker (fzf or a numbered prompt)
+//! * Batch resume of several branches
+//! * Options remembered per branch (`amg remembered`)
+//! * Waiting for a branch's next session (`amg watch`)
+//! * Fallback resume for session ids codex has forgotten
+//! * Machine-readable resume results (`--result-json`)
+//! * Resume plans written for later execution (`--plan-out`, `amg exec-plan`)
+//! * Session file backups
+//! * Session marker files for editor tooling
+//! * Scratch Codex home for read-only session stores
+//! * Persistent state (branch aliases, session tags, launch records)
+//! * The state policy for portable runs that use no home-directory files (`--portable`)
+//! * Session tags
+//! * Launch bookkeeping and `kill`
+//! * Configuration files and `amg config` editing
+//! * First-run setup (`amg init`) and setup checks (`amg doctor`)
+//! * Git invocation helpers
+//! * Usage records appended to a stats file (`--stats-file`)
 //! * Utility functions for paths, environment variables, etc.
 //! * Constants and configuration values
 //! * Logging initialization
+//! * Writing to stdout, exiting quietly once its reader went away
 //!
 //! ## Entry Point
 //!
-//! The main entry point is [`entry`], which initializes logging, parses arguments, and dispatches
+//! The main entry point is [`entry`], which parses arguments, initializes logging, and dispatches
 //! to the appropriate subcommand handler.
 //!
+//! ## Features
+//!
+//! Everything but [`sessions`] and the modules it builds on (scanning, codex command building,
+//! process and path helpers) needs the default `cli` feature. Helpers in those modules that
+//! only the command-line code calls are gated on the feature too.
+//!
 //! ## Example
 //!
 //! ```no_run
+//! # #[cfg(feature = "cli")]
 //! use amg::cli::entry;
 //!
+//! # #[cfg(feature = "cli")]
 //! fn main() -> std::process::ExitCode {
 //!     entry()
 //! }
+//! # #[cfg(not(feature = "cli"))]
+//! # fn main() {}
 //! ```
 
+#[cfg(feature = "cli")]
+mod alias;
+#[cfg(feature = "cli")]
 mod args;
+#[cfg(feature = "cli")]
+mod backup;
+#[cfg(feature = "cli")]
+mod branches;
+#[cfg(feature = "cli")]
+mod cache;
 mod codex_cmd;
+#[cfg(feature = "cli")]
+mod config;
+#[cfg(feature = "cli")]
+mod config_edit;
 mod constants;
+#[cfg(feature = "cli")]
+mod datetime;
+#[cfg(feature = "cli")]
+mod doctor;
+#[cfg(feature = "cli")]
+mod dump;
+#[cfg(feature = "cli")]
+mod env_check;
+#[cfg(feature = "cli")]
+mod explain;
+#[cfg(feature = "cli")]
+mod fallback;
+#[cfg(feature = "cli")]
+mod find;
+#[cfg(feature = "cli")]
+mod git;
+mod ignore;
+#[cfg(feature = "cli")]
+mod import;
+#[cfg(feature = "cli")]
+mod index;
+#[cfg(feature = "cli")]
+mod init;
+mod jsonl;
+#[cfg(feature = "cli")]
+mod launch;
+#[cfg(feature = "cli")]
 mod logging;
+#[cfg(feature = "cli")]
+mod marker;
+#[cfg(feature = "cli")]
+mod migrate;
+#[cfg(feature = "cli")]
+mod output;
+#[cfg(feature = "cli")]
+mod pick;
+#[cfg(feature = "cli")]
+mod plan;
+#[cfg(feature = "cli")]
+mod policy;
 mod prelude;
 mod process;
+#[cfg(feature = "cli")]
+mod prompt_hint;
+#[cfg(feature = "cli")]
+mod remember;
+#[cfg(feature = "cli")]
+mod result_json;
+#[cfg(feature = "cli")]
+mod resume_all;
 mod scan;
+#[cfg(feature = "cli")]
+mod scratch;
+pub mod sessions;
+#[cfg(feature = "cli")]
+mod show;
+#[cfg(feature = "cli")]
+mod state;
+#[cfg(feature = "cli")]
+mod stats;
+#[cfg(feature = "cli")]
+mod stdin_jsonl;
+#[cfg(feature = "cli")]
+mod tag;
+#[cfg(test)]
+mod test_support;
+#[cfg(feature = "cli")]
+mod usage;
 mod util;
+#[cfg(feature = "cli")]
+mod watch;
 
 // Re-export Args and Commands for testing
-pub use args::{Args, Commands};
+#[cfg(feature = "cli")]
+pub use args::{
+    AliasCommand, Args, BackupArgs, BranchesArgs, Commands, ConfigCommand, DoctorArgs, DumpArgs,
+    ExecPlanArgs, FindArgs, ImportArgs, InitArgs, KillArgs, MigrateLayoutArgs, PromptHintArgs,
+    RememberedArgs, ResumeAllArgs, ResumeArgs, SandboxArgs, SelectionArgs, ShowArgs, StatsArgs,
+    TagCommand, TmuxMode, WatchArgs, WhichArgs,
+};
 
+#[cfg(feature = "cli")]
+use std::time::Instant;
+
+#[cfg(feature = "cli")]
 use prelude::*;
 
+/// How soon after starting an inline codex run a failure counts as codex refusing to start.
+#[cfg(feature = "cli")]
+const QUICK_FAILURE: Duration = Duration::from_secs(10);
+
+/// How many bytes at the end of codex's log are read after a quick failure.
+#[cfg(feature = "cli")]
+const CODEX_LOG_TAIL_BYTES: u64 = 64 * 1024;
+
+/// How many lines at the end of codex's log are searched for errors.
+#[cfg(feature = "cli")]
+const CODEX_LOG_TAIL_LINES: usize = 200;
+
+/// How many error lines from codex's log are shown.
+#[cfg(feature = "cli")]
+const CODEX_LOG_ERROR_LINES: usize = 5;
+
 /// Main entry point for the CLI application.
 ///
-/// Initializes logging, parses command-line arguments, and executes the appropriate subcommand.
+/// Parses command-line arguments, initializes logging, and executes the appropriate subcommand.
 /// Returns an [`ExitCode`] indicating success or failure.
 ///
 /// # Returns
 ///
 /// * [`ExitCode::SUCCESS`] - Command executed successfully
-/// * [`ExitCode::FAILURE`] - Command failed (errors are logged to stderr)
+/// * [`ExitCode::FAILURE`] - Command failed (errors are logged to stderr), or the
+///   `--exit-code-offset` base plus [`EXIT_FAILURE`] or [`EXIT_NO_SESSION`] when one is given
+/// * The exit code of codex itself when it ran inline
 ///
 /// # Examples
 ///
@@ -63,20 +215,63 @@ use prelude::*;
 ///     entry()
 /// }
 /// ```
+#[cfg(feature = "cli")]
 pub fn entry() -> ExitCode {
-    logging::init_tracing();
-    match run() {
+    let args = args::parse_args();
+    policy::init(if args.portable {
+        policy::StatePolicy::Portable
+    } else {
+        policy::StatePolicy::Home
+    });
+    result_json::probe_result_fd();
+    logging::init_tracing(args.log_file.as_deref());
+    let recorder = usage::Recorder::start(args.stats_file.clone(), &args.command);
+    let exit_code_offset = args.exit_code_offset;
+    // A prompt hint always succeeds, even where nothing else could run.
+    let skip_env_check = args.no_env_check || matches!(args.command, args::Commands::PromptHint(_));
+    let checked = if skip_env_check {
+        Ok(())
+    } else {
+        env_check::check(&env_check::Snapshot::capture(&args.command))
+    };
+    let code = match checked.and_then(|()| run(args)) {
         Ok(code) => code,
+        Err(err) if output::is_broken_pipe(&err) => {
+            debug!("stdout was closed: {err:#}");
+            ExitCode::SUCCESS
+        }
         Err(err) => {
             error!("{err:#}");
-            ExitCode::FAILURE
+            failure_code(exit_code_offset, &err)
         }
+    };
+    if let Some(recorder) = recorder {
+        recorder.finish(code);
     }
+    code
+}
+
+/// Returns the exit code for a failure of amg itself.
+///
+/// This is [`ExitCode::FAILURE`] unless `--exit-code-offset` reserved codes above its base,
+/// so amg failures cannot be confused with codex's own exit codes: the base plus
+/// [`EXIT_NO_SESSION`] for a [`NoSession`] error, or plus [`EXIT_FAILURE`] for any other.
+#[cfg(feature = "cli")]
+fn failure_code(exit_code_offset: Option<u8>, err: &anyhow::Error) -> ExitCode {
+    let Some(base) = exit_code_offset else {
+        return ExitCode::FAILURE;
+    };
+    let category = if err.is::<NoSession>() {
+        EXIT_NO_SESSION
+    } else {
+        EXIT_FAILURE
+    };
+    ExitCode::from(base + category)
 }
 
 /// Internal function that runs the CLI logic.
 ///
-/// Parses arguments and dispatches to the appropriate subcommand handler.
+/// Dispatches parsed arguments to the appropriate subcommand handler.
 ///
 /// # Returns
 ///
@@ -84,20 +279,30 @@ pub fn entry() -> ExitCode {
 ///
 /// # Errors
 ///
-/// Returns an error if:
-/// * Argument parsing fails
-/// * Subcommand execution fails
-fn run() -> Result<ExitCode> {
-    let args = args::parse_args();
-
+/// Returns an error if subcommand execution fails.
+#[cfg(feature = "cli")]
+fn run(args: args::Args) -> Result<ExitCode> {
     match args.command {
-        args::Commands::ResumeBranch {
-            branch,
-            repo,
-            codexdir,
-            dry_run,
-            no_tmux,
-        } => run_resume_branch(branch, repo, codexdir, dry_run, no_tmux),
+        args::Commands::ResumeBranch(resume) => run_resume_branch(resume),
+        args::Commands::ResumeAll(all) => resume_all::run_resume_all(all),
+        args::Commands::Watch(watch) => watch::run_watch(watch),
+        args::Commands::Kill(kill) => launch::run_kill(kill),
+        args::Commands::Remembered(remembered) => remember::run_remembered(remembered),
+        args::Commands::Which(which) => run_which(which),
+        args::Commands::PromptHint(hint) => Ok(prompt_hint::run_prompt_hint(hint)),
+        args::Commands::Show(show) => show::run_show(show),
+        args::Commands::Branches(list) => branches::run_branches(list),
+        args::Commands::Find(find) => find::run_find(find),
+        args::Commands::Dump(dump) => dump::run_dump(dump),
+        args::Commands::Stats(stats) => stats::run_stats(stats),
+        args::Commands::Import(import) => import::run_import(import),
+        args::Commands::MigrateLayout(migrate) => migrate::run_migrate_layout(migrate),
+        args::Commands::ExecPlan(exec) => plan::run_exec_plan(exec),
+        args::Commands::Alias { command } => alias::run_alias(command),
+        args::Commands::Tag { command } => tag::run_tag(command),
+        args::Commands::Config { command, repo } => config_edit::run_config(command, repo),
+        args::Commands::Init(init) => init::run_init(init),
+        args::Commands::Doctor(doctor) => doctor::run_doctor(doctor),
     }
 }
 
@@ -107,11 +312,7 @@ fn run() -> Result<ExitCode> {
 ///
 /// # Arguments
 ///
-/// * `branch` - Git branch name to match against session files
-/// * `repo` - Repository path to grant Codex sandbox access to
-/// * `codexdir` - Optional Codex directory path (defaults to `$HOME/.codex`)
-/// * `dry_run` - If `true`, print the command without executing it
-/// * `no_tmux` - If `true`, disable automatic tmux window creation
+/// * `args` - Parsed `resume` arguments (branch, repo, codexdir, and execution options)
 ///
 /// # Returns
 ///
@@ -120,76 +321,2795 @@ fn run() -> Result<ExitCode> {
 /// # Errors
 ///
 /// Returns an error if:
-/// * The repository or codexdir is not a valid directory
+/// * The repository, codexdir, or chdir override is not a valid directory
+/// * A config file is invalid
 /// * No matching session is found for the branch
 /// * Session directory validation fails
+/// * `--verify-commit --strict` is set and the session commit cannot be verified
+/// * The session backup fails (unless `--backup-best-effort` is set)
 /// * Command execution fails
 ///
+/// With `--print-id`, a missing session is reported with [`EXIT_NO_SESSION`] instead, so
+/// a wrapper can tell "nothing matched" from a failure.
+///
 /// # See Also
 ///
 /// * [`scan::find_first_session`] - Session matching logic
 /// * [`codex_cmd::build_codex_cmd`] - Command building
 /// * [`process::run_tmux_new_window`] - Tmux execution
 /// * [`process::run_in_dir`] - Inline execution
-fn run_resume_branch(
-    branch: String,
-    repo: PathBuf,
-    codexdir: Option<PathBuf>,
-    dry_run: bool,
-    no_tmux: bool,
-) -> Result<ExitCode> {
-    let codexdir = codexdir.map(Ok).unwrap_or_else(util::default_codexdir)?;
+#[cfg(feature = "cli")]
+fn run_resume_branch(args: args::ResumeArgs) -> Result<ExitCode> {
+    let print_id = args.execution.print_id;
+    let result = resume_branch(args);
+    if let Err(err) = &result
+        && err.is::<NoSession>()
+    {
+        usage::note_match(false);
+    }
+    match result {
+        Err(err) if print_id && err.is::<NoSession>() => {
+            error!("{err:#}");
+            Ok(ExitCode::from(EXIT_NO_SESSION))
+        }
+        result => result,
+    }
+}
 
-    util::require_dir(&repo, "repo", Some("CODEX_REPO"))?;
+/// Selects the session for [`run_resume_branch`] and resumes it.
+///
+/// # Errors
+///
+/// See [`run_resume_branch`]; a missing session is a [`NoSession`] error.
+#[cfg(feature = "cli")]
+fn resume_branch(mut args: args::ResumeArgs) -> Result<ExitCode> {
+    if args.stdin_jsonl {
+        return stdin_jsonl::run_resume(&args);
+    }
+    let codexdir = args
+        .location
+        .codexdir
+        .clone()
+        .map(Ok)
+        .unwrap_or_else(config::default_codexdir)?;
+    let codex = &args.codex;
+    let execution = &args.execution;
+    // Writing a plan resolves everything a dry run does and acts on nothing.
+    let dry_run = execution.dry_run || execution.plan_out.is_some();
+
+    // `--print-id` only selects, so the directories it would run in are not checked.
+    let repo_checked = args.repo.as_ref().filter(|_| !execution.print_id);
+    if let Some(repo) = repo_checked {
+        util::require_dir(repo, "repo", Some("CODEX_REPO"))?;
+    }
     util::require_dir(&codexdir, "codexdir", Some("CODEX_CODEXDIR"))?;
+    if let Some(repo) = repo_checked {
+        check_overlap(repo, &codexdir, args.overlap.allow_overlap)?;
+    }
+    check_codexdir(&codexdir, args.selection.walk.force_scan)?;
+    if let Some(dir) = codex.chdir.as_ref().filter(|_| !execution.print_id) {
+        util::require_dir(dir, "chdir", None)?;
+    }
 
-    let session = scan::find_first_session(&codexdir, &branch)?.with_context(|| {
-        format!(
-            "No matching session found for branch {:?} under {}",
-            branch,
-            codexdir.display()
-        )
-    })?;
-    util::require_dir(&session.cwd, "session cwd", None)?;
+    // Only a resume by branch has a branch to remember options for.
+    let remembered_branch = match (&args.file, &args.id, args.any) {
+        (None, None, false) => Some(alias::resolve_branch(
+            args.branch.as_deref().unwrap_or_default(),
+        )),
+        _ => None,
+    };
+    let remembered = match &remembered_branch {
+        Some(branch) if !args.fresh => remember::load(branch),
+        _ => remember::Remembered::default(),
+    };
+    remembered.fill_selection(&mut args.selection);
+    let cli_tmux = cli_tmux_mode(execution);
+    let remember = remembered_branch.map(|branch| {
+        let options = remembered.capture(&args.selection, cli_tmux, codex.wrapper.wrap.as_deref());
+        (branch, options)
+    });
+
+    let home = util::home_dir();
+    let redacted_home = home.as_deref().filter(|_| execution.redact_home);
+    let context = LaunchContext {
+        repo: args.repo.as_deref(),
+        codexdir: &codexdir,
+        home: home.as_deref(),
+        codex,
+        sandbox: &args.sandbox,
+        remembered_wrap: remembered.wrap(),
+        dry_run,
+        output: execution.output.as_deref(),
+    };
 
-    let cmd = codex_cmd::build_codex_cmd(&repo, &codexdir, &session, util::home_dir().as_deref());
+    let scan_options = scan::ScanOptions {
+        strict_file_ids: codex.strict,
+        ..scan_options(&args.selection)?
+    };
+    let lookup = Lookup {
+        codexdir: &codexdir,
+        options: &scan_options,
+        use_cache: !args.cache.no_cache,
+    };
+    let branch = remember.as_ref().map_or("", |(branch, _)| branch.as_str());
+    let (branch, session, stats) =
+        match select_session(&args, lookup, &context, branch, redacted_home)? {
+            Selection::Found {
+                branch,
+                session,
+                stats,
+            } => (branch, *session, stats),
+            Selection::Done(code) => return Ok(code),
+        };
+    usage::note_match(true);
+    if execution.print_id {
+        info!(
+            branch = session.branch_label(),
+            looked_up = session.other_branch(&branch),
+            id = %session.id,
+            file = %util::human_path(&session.source_jsonl),
+            "matched session"
+        );
+        output::line(format_args!("{}", session.id))?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    let selected = match (&args.file, &args.id) {
+        (Some(_), _) => Selected::Fixed,
+        (None, Some(prefix)) => Selected::IdPrefix(prefix),
+        (None, None) if args.any => Selected::Newest,
+        (None, None) if args.pick || args.pick_tmux => Selected::Fixed,
+        (None, None) => Selected::Branch {
+            branch: &branch,
+            relax: args.relax_on_empty,
+        },
+    };
+    let mut launch = prepare_launch(session, &context)?;
+    // A sync tool may rotate or delete the file after the scan read it; codex would then fail
+    // only after its window opened.
+    if !dry_run && let Err(change) = scan::verify_unchanged(&launch.session) {
+        let session = find_again(lookup, selected, &change)?;
+        launch = prepare_launch(session, &context)?;
+    }
+    check_branch_exists(&launch, &branch, codex.require_branch_exists)?;
+    let session = &launch.session;
+    let config = &launch.config;
+    let start_dir = launch.start_dir.as_path();
 
     info!(
+        branch = session.branch_label(),
+        looked_up = session.other_branch(&branch),
         id = %session.id,
-        cwd = %session.cwd.display(),
-        source_jsonl = %session.source_jsonl.display(),
+        cwd = %util::human_path(&session.cwd),
+        start_dir = %util::human_path(start_dir),
+        source_jsonl = %util::human_path(&session.source_jsonl),
+        size = session.size,
+        mtime = session.modified_rfc3339(),
+        files_scanned = stats.as_ref().map(|stats| stats.files_scanned),
+        files_parsed = stats.as_ref().map(|stats| stats.files_parsed),
         "matched session"
     );
 
-    enum Action {
-        Print(process::Cmd),
-        RunTmux(process::Cmd),
-        RunInline(process::Cmd),
+    if args.backup.backup && !dry_run {
+        backup_session(session, &args.backup)?;
+    }
+    if !codex.no_marker && (codex.write_marker || config.write_marker == Some(true)) && !dry_run {
+        let marker = marker::Marker::new(
+            &session.id,
+            &branch,
+            session.branch.as_deref(),
+            &session.source_jsonl,
+        );
+        match marker::write_marker(&session.cwd, &marker) {
+            Ok(path) => info!(marker = %util::human_path(&path), "wrote session marker"),
+            Err(err) => warn!("failed to write the session marker, continuing: {err:#}"),
+        }
+    }
+    if launch.sandbox_mode == codex_cmd::SandboxMode::DangerFullAccess && !dry_run {
+        full_access_warning();
+    }
+
+    let target = util::should_use_tmux(tmux_mode(cli_tmux, config.tmux, remembered.tmux_mode()));
+    if execution.timeout.is_some() && (dry_run || target != util::TmuxTarget::Inline) {
+        warn!("--timeout only applies when codex runs inline; ignoring it");
+    }
+    let unknown_session = if !execution.fallback_resume {
+        None
+    } else if dry_run || target != util::TmuxTarget::Inline {
+        warn!("--fallback-resume only applies when codex runs inline; ignoring it");
+        None
+    } else {
+        Some(fallback::UnknownSession::from_config(config)?)
+    };
+    if let Some(path) = &execution.plan_out {
+        let resume = plan::PlannedResume {
+            session,
+            branch: &branch,
+            start_dir,
+            cmd: &launch.cmd,
+        };
+        plan::Plan::new(resume, target, execution.tmux_detach)?.write(path)?;
+        info!(plan = %util::human_path(path), "wrote the resume plan");
+        return Ok(ExitCode::SUCCESS);
+    }
+    let window = process::WindowOptions {
+        name: None,
+        detach: execution.tmux_detach,
+    };
+    let tmux_cmd = |cmd: &process::Cmd| match target {
+        util::TmuxTarget::Detached => process::tmux_detached_window_cmd(
+            start_dir,
+            window,
+            cmd,
+            process::tmux_server_running(),
+        ),
+        _ => process::tmux_new_window_cmd(start_dir, window, cmd),
+    };
+
+    let action = ActionContext {
+        context: &context,
+        launch: &launch,
+        branch: &branch,
+        stats: stats.as_ref(),
+        execution,
+        redacted_home,
+        target,
+    };
+    match (dry_run, target) {
+        (true, util::TmuxTarget::Inline) => print_command(&action, &launch.cmd),
+        (true, _) => print_command(&action, &tmux_cmd(&launch.cmd)),
+        (false, util::TmuxTarget::Inline) => {
+            run_inline(&action, unknown_session.as_ref(), remember)
+        }
+        (false, _) => run_in_tmux(&action, &tmux_cmd(&launch.cmd), remember),
+    }
+}
+
+/// The outcome of [`select_session`].
+#[cfg(feature = "cli")]
+enum Selection {
+    /// The session to resume.
+    Found {
+        /// The branch the session was looked up for, or the one it records when it was
+        /// chosen by file, id, or age (empty if it records none).
+        branch: String,
+        session: Box<scan::Session>,
+        /// The branch scan's statistics, when one ran.
+        stats: Option<scan::ScanStats>,
+    },
+    /// Nothing is left to resume: the picker was cancelled, or a tmux menu took over.
+    Done(ExitCode),
+}
+
+/// Chooses the session `resume` acts on, by `--file`, `--id`, `--any`, or `branch`, the
+/// latter picked interactively (`--pick`, `--pick-tmux`), explained (`--explain`), or relaxed
+/// (`--relax-on-empty`).
+///
+/// # Arguments
+///
+/// * `args` - The `resume` arguments
+/// * `lookup` - Where and how the session is looked up
+/// * `context` - The launch options, for the entries of a `--pick-tmux` menu
+/// * `branch` - The branch to look up, when no other selection applies
+/// * `redacted_home` - The home directory to redact from `--explain --json` paths
+///
+/// # Errors
+///
+/// Returns a [`NoSession`] error if nothing matches, or an error if the session file, the
+/// scan, or the picker fails.
+#[cfg(feature = "cli")]
+fn select_session(
+    args: &args::ResumeArgs,
+    lookup: Lookup<'_>,
+    context: &LaunchContext<'_>,
+    branch: &str,
+    redacted_home: Option<&Path>,
+) -> Result<Selection> {
+    let codexdir = lookup.codexdir;
+    let scan_options = lookup.options;
+    if let Some(file) = &args.file {
+        let (session, branch) = session_from_file(codexdir, file, args.force)?;
+        info!(
+            branch = branch.as_deref().unwrap_or("(none)"),
+            id = %session.id,
+            "session from file"
+        );
+        return Ok(Selection::Found {
+            branch: branch.unwrap_or_default(),
+            session: Box::new(session),
+            stats: None,
+        });
+    }
+    if let Some(prefix) = &args.id {
+        let (session, branch) = find_by_id_prefix(lookup, prefix)?;
+        info!(
+            branch = branch.as_deref().unwrap_or("(none)"),
+            id = %session.id,
+            "session matching id prefix"
+        );
+        return Ok(Selection::Found {
+            branch: branch.unwrap_or_default(),
+            session: Box::new(session),
+            stats: None,
+        });
+    }
+    if args.any {
+        let Some((session, branch)) = scan::find_latest_session(codexdir, scan_options)? else {
+            return Err(NoSession(format!(
+                "no sessions found in {}",
+                util::human_path(codexdir)
+            ))
+            .into());
+        };
+        info!(
+            branch = branch.as_deref().unwrap_or("(none)"),
+            cwd = %util::human_path(&session.cwd),
+            "newest session"
+        );
+        return Ok(Selection::Found {
+            branch: branch.unwrap_or_default(),
+            session: Box::new(session),
+            stats: None,
+        });
+    }
+
+    let (session, stats) = if args.pick || args.pick_tmux {
+        let (sessions, stats) = scan::find_sessions(codexdir, branch, scan_options)?;
+        usage::note_scan(&stats, !sessions.is_empty());
+        if args.pick_tmux && sessions.len() > 1 {
+            match util::should_use_tmux(TmuxMode::Auto) {
+                util::TmuxTarget::CurrentSession => {
+                    return pick_from_tmux_menu(branch, sessions, context).map(Selection::Done);
+                }
+                _ => debug!("not inside tmux; using the plain picker instead of a menu"),
+            }
+        }
+        match pick::pick_session(sessions, !args.no_fzf)? {
+            pick::Picked::Session(session) => (Some(*session), stats),
+            pick::Picked::Empty => (None, stats),
+            pick::Picked::Cancelled => {
+                info!("selection cancelled");
+                return Ok(Selection::Done(ExitCode::from(EXIT_CANCELLED)));
+            }
+        }
+    } else if args.explain {
+        let scan::ScanOutcome { session, stats } = explain::find_and_explain(
+            codexdir,
+            branch,
+            scan_options,
+            args.explain_limit,
+            args.json,
+            redacted_home,
+        )?;
+        usage::note_scan(&stats, session.is_some());
+        (session, stats)
+    } else if args.relax_on_empty {
+        let (scan::ScanOutcome { session, stats }, _) = find_relaxed(lookup, branch)?;
+        (session, stats)
+    } else {
+        let scan::ScanOutcome { session, stats } =
+            cache::find_session(codexdir, branch, scan_options, lookup.use_cache)?;
+        (session, stats)
+    };
+    let Some(session) = session else {
+        return Err(NoSession(no_session_message(codexdir, branch, &stats)).into());
+    };
+    Ok(Selection::Found {
+        branch: branch.to_owned(),
+        session: Box::new(session),
+        stats: Some(stats),
+    })
+}
+
+/// What printing or running a prepared [`Launch`] needs besides the command itself.
+#[cfg(feature = "cli")]
+struct ActionContext<'a> {
+    context: &'a LaunchContext<'a>,
+    launch: &'a Launch,
+    /// The branch the session was matched for.
+    branch: &'a str,
+    /// The branch scan's statistics, when one ran.
+    stats: Option<&'a scan::ScanStats>,
+    execution: &'a args::ExecutionArgs,
+    /// The home directory to redact from printed paths (`--redact-home`).
+    redacted_home: Option<&'a Path>,
+    /// Where codex runs.
+    target: util::TmuxTarget,
+}
+
+/// Emits the `--result-json` line for `action`, if it was asked for.
+///
+/// # Arguments
+///
+/// * `action` - The resume that acted
+/// * `taken` - What it did
+/// * `window_id` - The new tmux window's id, if tmux reported one
+/// * `exit` - How the inline codex ended
+/// * `stdout_taken` - Whether stdout already carries the dry-run command
+///
+/// # Errors
+///
+/// Returns an error if the line cannot be written (see [`result_json::emit_result`]).
+#[cfg(feature = "cli")]
+fn report_result(
+    action: &ActionContext<'_>,
+    taken: result_json::ResultAction,
+    window_id: Option<String>,
+    exit: Option<process::Exit>,
+    stdout_taken: bool,
+) -> Result<()> {
+    if !action.execution.result_json {
+        return Ok(());
+    }
+    let session = &action.launch.session;
+    let cwd = PathBuf::from(util::display_path(&session.cwd, action.redacted_home));
+    let result = result_json::ResumeResult {
+        action: taken,
+        session_id: &session.id,
+        branch: action.branch,
+        recorded_branch: session.branch.as_deref(),
+        cwd: &cwd,
+        size: session.size,
+        mtime: session.modified_rfc3339(),
+        files_scanned: action.stats.map(|stats| stats.files_scanned),
+        files_parsed: action.stats.map(|stats| stats.files_parsed),
+        window_id,
+        exit_code: match exit {
+            Some(process::Exit::Status(status)) => status.code(),
+            _ => None,
+        },
+        timed_out: exit == Some(process::Exit::TimedOut),
+    };
+    result_json::emit_result(&result, stdout_taken)
+}
+
+/// Prints `cmd` instead of running it (`--dry-run`), to `--output` or stdout.
+///
+/// # Errors
+///
+/// Returns an error if the command or the result line cannot be written.
+#[cfg(feature = "cli")]
+fn print_command(action: &ActionContext<'_>, cmd: &process::Cmd) -> Result<ExitCode> {
+    let execution = action.execution;
+    let command = if execution.pretty {
+        cmd.as_pretty_shell_string(util::terminal_width(), action.redacted_home)
+    } else {
+        match action.redacted_home {
+            Some(home) => cmd.as_shell_string_redacted(home),
+            None => cmd.as_shell_string(),
+        }
+    };
+    info!(command = %command, "dry-run");
+    if execution.copy {
+        copy_command(&command);
+    }
+    match action.context.output {
+        Some(path) => emit_command(&mut util::create_private_file(path)?, &command)
+            .with_context(|| format!("failed to write {}", util::human_path(path)))?,
+        None => {
+            emit_command(&mut io::stdout().lock(), &command).context("failed to write to stdout")?
+        }
+    }
+    report_result(
+        action,
+        result_json::ResultAction::DryRun,
+        None,
+        None,
+        action.context.output.is_none(),
+    )?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Opens codex in a tmux window with the `tmux new-window` command `tmux`, then annotates the
+/// window, records the launch, sends the notices, and saves the `remember`ed options.
+///
+/// # Errors
+///
+/// Returns an error if tmux fails or the result line cannot be written.
+#[cfg(feature = "cli")]
+fn run_in_tmux(
+    action: &ActionContext<'_>,
+    tmux: &process::Cmd,
+    remember: Option<(String, remember::Remembered)>,
+) -> Result<ExitCode> {
+    let execution = action.execution;
+    let Launch {
+        session, config, ..
+    } = action.launch;
+    debug!(args = ?tmux.args, "running via tmux");
+    if execution.copy {
+        copy_command(&tmux.as_shell_string());
+    }
+    let window_id = process::run_tmux(tmux)?;
+    if let Some(window_id) = window_id.clone() {
+        let cli = match (execution.tmux_annotate, execution.no_tmux_annotate) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        };
+        if annotate_window(cli, config.tmux_annotate, action.target) {
+            let annotations = process::WindowAnnotations {
+                session_id: &session.id,
+                branch: action.branch,
+                cwd: &session.cwd,
+            };
+            process::annotate_window(&window_id, annotations);
+        }
+        launch::record_launch(
+            &session.id,
+            action.branch,
+            session.branch.as_deref(),
+            state::LaunchTarget::TmuxWindow { window_id },
+        );
+    }
+    if execution.tmux_detach || execution.notify {
+        let notice = config::NoticeVars {
+            branch: action.branch,
+            id: &session.id,
+            cwd: &session.cwd,
+        };
+        let notices = Notices {
+            status_line: execution.tmux_detach && action.target == util::TmuxTarget::CurrentSession,
+            desktop: execution.notify,
+        };
+        notify_launch(config, notice, notices);
+    }
+    if action.target == util::TmuxTarget::Detached {
+        info!("opened codex in a background tmux window; run `tmux attach` to see it");
+    }
+    if let Some((branch, options)) = remember {
+        remember::save(&branch, options);
+    }
+    report_result(
+        action,
+        result_json::ResultAction::Tmux,
+        window_id,
+        None,
+        false,
+    )?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Runs the launch's codex command inline and waits for it, retrying with `codex resume
+/// --last` when `unknown_session` is set (`--fallback-resume`). The `remember`ed options are
+/// saved only if codex succeeds.
+///
+/// # Returns
+///
+/// Returns codex's exit code, or [`EXIT_TIMED_OUT`] if `--timeout` stopped it.
+///
+/// # Errors
+///
+/// Returns an error if codex cannot be run or the result line cannot be written.
+#[cfg(feature = "cli")]
+fn run_inline(
+    action: &ActionContext<'_>,
+    unknown_session: Option<&fallback::UnknownSession>,
+    remember: Option<(String, remember::Remembered)>,
+) -> Result<ExitCode> {
+    let execution = action.execution;
+    let Launch {
+        session,
+        start_dir,
+        cmd,
+        ..
+    } = action.launch;
+    debug!("running inline");
+    if execution.copy {
+        copy_command(&cmd.as_shell_string());
+    }
+    let record = |pid| {
+        launch::record_launch(
+            &session.id,
+            action.branch,
+            session.branch.as_deref(),
+            state::LaunchTarget::Process { pid },
+        );
+    };
+    let started = Instant::now();
+    let exit = match unknown_session {
+        Some(unknown_session) => run_with_fallback(
+            InlineRun {
+                start_dir,
+                cmd,
+                timeout: execution.timeout,
+                id: &session.id,
+            },
+            unknown_session,
+            &record,
+        ),
+        None => process::run_in_dir(start_dir, cmd, execution.timeout, record),
+    }
+    .context("failed to run codex")?;
+    launch::forget_launch(&session.id);
+    if let process::Exit::Status(status) = exit {
+        report_quick_failure(action.context.codexdir, status, started.elapsed());
+        if status.success()
+            && let Some((branch, options)) = remember
+        {
+            remember::save(&branch, options);
+        }
+    }
+    report_result(
+        action,
+        result_json::ResultAction::Inline,
+        None,
+        Some(exit),
+        false,
+    )?;
+    match exit {
+        process::Exit::Status(status) => Ok(process::exit_code(status)),
+        process::Exit::TimedOut => {
+            error!("codex was stopped after running longer than --timeout");
+            Ok(ExitCode::from(EXIT_TIMED_OUT))
+        }
+    }
+}
+
+/// An inline codex run.
+#[cfg(feature = "cli")]
+#[derive(Clone, Copy)]
+struct InlineRun<'a> {
+    /// The directory codex runs in.
+    start_dir: &'a Path,
+    cmd: &'a process::Cmd,
+    /// `--timeout`, if given.
+    timeout: Option<Duration>,
+    /// The id of the session `cmd` resumes.
+    id: &'a str,
+}
+
+/// Runs codex inline, retrying once with `codex resume --last` if it fails because it does
+/// not know the session id (`--fallback-resume`).
+///
+/// Each run gets the full `timeout`; a run that timed out is not retried.
+///
+/// # Returns
+///
+/// Returns how the last run ended.
+///
+/// # Errors
+///
+/// Returns an error if codex cannot be spawned, or if the command does not end with
+/// `resume <id>` (so there is nothing to retry).
+#[cfg(feature = "cli")]
+fn run_with_fallback(
+    run: InlineRun<'_>,
+    unknown_session: &fallback::UnknownSession,
+    record: &impl Fn(u32),
+) -> Result<process::Exit> {
+    let InlineRun {
+        start_dir,
+        cmd,
+        timeout,
+        id,
+    } = run;
+    let (exit, stderr) = process::run_in_dir_capturing_stderr(start_dir, cmd, timeout, record)?;
+    let process::Exit::Status(status) = exit else {
+        return Ok(exit);
+    };
+    if !unknown_session.matches(status.code(), &stderr) {
+        return Ok(exit);
+    }
+    let retry = fallback::resume_last(cmd).context("cannot build a fallback resume command")?;
+    warn!("codex does not know session {id}; retrying with `codex resume --last`");
+    process::run_in_dir(start_dir, &retry, timeout, record)
+}
+
+/// Logs the last error lines of codex's own log when an inline run failed within
+/// [`QUICK_FAILURE`] of starting.
+///
+/// A quick failure is usually codex refusing to start (most often because the sandbox denies
+/// a directory that was not granted), and the reason only shows up in its log.
+#[cfg(feature = "cli")]
+fn report_quick_failure(codexdir: &Path, status: ExitStatus, elapsed: Duration) {
+    let log = codexdir.join(CODEX_TUI_LOG);
+    let errors = quick_failure_errors(&log, status, elapsed);
+    if errors.is_empty() {
+        return;
+    }
+    error!(
+        "codex failed after {:.1}s; last errors in {}:",
+        elapsed.as_secs_f64(),
+        util::human_path(&log)
+    );
+    for line in errors {
+        error!("  {line}");
+    }
+    info!(
+        "if codex was denied a directory, grant it with `extra_add_dirs` in {REPO_CONFIG_FILE} \
+         or the global config"
+    );
+}
+
+/// Returns the last [`CODEX_LOG_ERROR_LINES`] `ERROR` lines near the end of codex's `log` if
+/// the run ended with a failing `status` after `elapsed`, or nothing if it did not fail quickly
+/// or the log cannot be read.
+#[cfg(feature = "cli")]
+fn quick_failure_errors(log: &Path, status: ExitStatus, elapsed: Duration) -> Vec<String> {
+    if status.success() || elapsed >= QUICK_FAILURE {
+        return Vec::new();
+    }
+    let lines = match util::tail_lines(log, CODEX_LOG_TAIL_BYTES, CODEX_LOG_TAIL_LINES) {
+        Ok(lines) => lines,
+        Err(err) => {
+            debug!(log = %util::human_path(log), %err, "cannot read the codex log");
+            return Vec::new();
+        }
+    };
+    let mut errors: Vec<String> = lines
+        .into_iter()
+        .filter(|line| line.contains("ERROR"))
+        .collect();
+    let skip = errors.len().saturating_sub(CODEX_LOG_ERROR_LINES);
+    errors.drain(..skip);
+    errors
+}
+
+/// Where and how `resume` looks its session up.
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy)]
+struct Lookup<'a> {
+    codexdir: &'a Path,
+    options: &'a scan::ScanOptions,
+    /// Read and update the last-hit cache (not `--no-cache`).
+    use_cache: bool,
+}
+
+/// How `resume` chose its session, so [`find_again`] can repeat the choice.
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Selected<'a> {
+    /// `--file`, or picked interactively: the choice is not repeated.
+    Fixed,
+    /// The newest session (`--any`).
+    Newest,
+    /// The session whose id starts with this prefix (`--id`).
+    IdPrefix(&'a str),
+    /// The first session for the branch, relaxing filters when `relax` is set.
+    Branch { branch: &'a str, relax: bool },
+}
+
+/// Repeats the session lookup once after the matched file changed before the launch.
+///
+/// The scan bypasses the last-hit cache, which may still point at the changed file.
+///
+/// # Arguments
+///
+/// * `lookup` - The first lookup
+/// * `selected` - How the session was chosen the first time
+/// * `change` - What changed, from [`scan::verify_unchanged`]
+///
+/// # Errors
+///
+/// Returns an error naming `change` if the choice cannot be repeated or the new lookup finds
+/// nothing, or if the scan fails.
+#[cfg(feature = "cli")]
+fn find_again(lookup: Lookup<'_>, selected: Selected<'_>, change: &str) -> Result<scan::Session> {
+    if selected == Selected::Fixed {
+        bail!("{change} since it was matched; resume it again once it is back");
+    }
+    info!("{change} since it was matched; scanning again");
+    let lookup = Lookup {
+        use_cache: false,
+        ..lookup
+    };
+    let found = match selected {
+        Selected::Fixed => None,
+        Selected::Newest => {
+            scan::find_latest_session(lookup.codexdir, lookup.options)?.map(|(session, _)| session)
+        }
+        Selected::IdPrefix(prefix) => Some(
+            find_by_id_prefix(lookup, prefix)
+                .with_context(|| format!("{change} since it was matched"))?
+                .0,
+        ),
+        Selected::Branch {
+            branch,
+            relax: true,
+        } => find_relaxed(lookup, branch)?.0.session,
+        Selected::Branch {
+            branch,
+            relax: false,
+        } => cache::find_session(lookup.codexdir, branch, lookup.options, false)?.session,
+    };
+    let session = found.with_context(|| {
+        format!("{change} since it was matched, and scanning again found no session")
+    })?;
+    info!(id = %session.id, file = %util::human_path(&session.source_jsonl), "matched again");
+    Ok(session)
+}
+
+/// Reads the session to resume from `file` (`--file`), a path or a `file://` URL.
+///
+/// # Returns
+///
+/// Returns the session and its branch, if it records one.
+///
+/// # Errors
+///
+/// Returns an error if the file is not a usable session (naming the missing field), or if it
+/// lies outside `codexdir` and `force` is not set.
+#[cfg(feature = "cli")]
+fn session_from_file(
+    codexdir: &Path,
+    file: &str,
+    force: bool,
+) -> Result<(scan::Session, Option<String>)> {
+    let path = file_argument(file)?;
+    let found = scan::session_from_file(&path)?;
+    let codexdir_real = fs::canonicalize(codexdir).unwrap_or_else(|_| codexdir.to_owned());
+    if !util::is_under(&path, &codexdir_real) {
+        if !force {
+            bail!(
+                "{} is outside the codexdir {}, so codex may not find the session; pass --force \
+                 to resume it anyway",
+                util::human_path(&path),
+                util::human_path(codexdir)
+            );
+        }
+        warn!(
+            "{} is outside the codexdir {}; codex may not find the session",
+            util::human_path(&path),
+            util::human_path(codexdir)
+        );
     }
+    Ok(found)
+}
 
-    let use_tmux = util::should_use_tmux(no_tmux);
-    let action = match (dry_run, use_tmux) {
-        (true, true) => Action::Print(process::tmux_new_window_cmd(&session.cwd, &cmd)),
-        (true, false) => Action::Print(cmd),
-        (false, true) => Action::RunTmux(cmd),
-        (false, false) => Action::RunInline(cmd),
+/// Turns a `--file` argument into a path, accepting `file:///abs/path` (and
+/// `file://localhost/abs/path`) URLs as well as plain paths.
+#[cfg(feature = "cli")]
+fn file_argument(file: &str) -> Result<PathBuf> {
+    let Some(rest) = file.strip_prefix("file://") else {
+        return Ok(PathBuf::from(file));
     };
+    let path = rest.strip_prefix("localhost").unwrap_or(rest);
+    if !path.starts_with('/') {
+        bail!("unsupported file URL {file:?}; expected file:///absolute/path");
+    }
+    Ok(PathBuf::from(path))
+}
+
+/// Finds the single session whose id starts with `prefix` (`--id`).
+///
+/// # Returns
+///
+/// Returns the session and its branch, if it records one.
+///
+/// # Errors
+///
+/// Returns an error if the prefix is shorter than [`MIN_ID_PREFIX_LEN`] or not hexadecimal,
+/// the scan fails, no session matches, or several do (listing them with branches and ages).
+#[cfg(feature = "cli")]
+fn find_by_id_prefix(lookup: Lookup<'_>, prefix: &str) -> Result<(scan::Session, Option<String>)> {
+    let Lookup {
+        codexdir, options, ..
+    } = lookup;
+    let prefix = prefix.trim();
+    if prefix.len() < MIN_ID_PREFIX_LEN {
+        bail!(
+            "session id prefix {prefix:?} is too short; give at least {MIN_ID_PREFIX_LEN} characters"
+        );
+    }
+    if !prefix.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+        bail!("{prefix:?} is not a session id prefix (expected hexadecimal digits)");
+    }
+    let mut matches =
+        scan::find_sessions_by_id_prefix(codexdir, prefix, options, MAX_LISTED_ID_MATCHES)?;
+    match matches.len() {
+        0 => Err(NoSession(format!(
+            "no session id starts with {prefix:?} in {}",
+            util::human_path(codexdir)
+        ))
+        .into()),
+        1 => Ok(matches.remove(0)),
+        n => {
+            let now = SystemTime::now();
+            let mut msg = format!("session id prefix {prefix:?} is ambiguous; candidates:");
+            for (session, branch) in matches.iter().take(MAX_LISTED_ID_MATCHES) {
+                msg.push_str(&format!(
+                    "\n  {}  {}  {} ago",
+                    session.id,
+                    branch.as_deref().unwrap_or("(no branch)"),
+                    pick::session_age(session, now)
+                ));
+            }
+            if n > MAX_LISTED_ID_MATCHES {
+                msg.push_str("\n  ...");
+            }
+            bail!("{msg}")
+        }
+    }
+}
 
-    match action {
-        Action::Print(cmd) => {
-            let command = cmd.as_shell_string();
-            info!(command = %command, "dry-run");
-            println!("{command}");
-            Ok(ExitCode::SUCCESS)
+/// Finds the first session for `branch`, progressively dropping optional filters while the
+/// scan comes up empty (`--relax-on-empty`).
+///
+/// The filters are dropped in the order given by [`relaxations`]; the branch itself is never
+/// relaxed. Each retry and the final set of dropped filters are logged.
+///
+/// # Returns
+///
+/// Returns the outcome of the last scan and the filters dropped to get it.
+///
+/// # Errors
+///
+/// Returns an error if a scan fails.
+#[cfg(feature = "cli")]
+fn find_relaxed(
+    lookup: Lookup<'_>,
+    branch: &str,
+) -> Result<(scan::ScanOutcome, Vec<&'static str>)> {
+    let Lookup {
+        codexdir,
+        options,
+        use_cache,
+    } = lookup;
+    let mut outcome = cache::find_session(codexdir, branch, options, use_cache)?;
+    let mut dropped = Vec::new();
+    for (filter, relaxed) in relaxations(options) {
+        if outcome.session.is_some() {
+            break;
         }
-        Action::RunTmux(cmd) => {
-            debug!("running via tmux new-window");
-            process::run_tmux_new_window(&session.cwd, &cmd)?;
-            Ok(ExitCode::SUCCESS)
+        info!("no session matched branch {branch:?}; retrying without {filter}");
+        dropped.push(filter);
+        outcome = cache::find_session(codexdir, branch, &relaxed, use_cache)?;
+        if outcome.session.is_some() {
+            info!("dropping {filter} produced a match");
         }
-        Action::RunInline(cmd) => {
-            debug!("running inline");
-            process::run_in_dir(&session.cwd, &cmd).context("failed to run codex")
+    }
+    if !dropped.is_empty() {
+        let summary = dropped.join(", ");
+        if outcome.session.is_some() {
+            info!("relaxed filters: dropped {summary}");
+        } else {
+            info!("no match even after dropping {summary}");
         }
     }
+    Ok((outcome, dropped))
+}
+
+/// Lists the progressively relaxed variants of `options`: first without `--since`, then also
+/// without `--under`. Filters that are not set are skipped.
+#[cfg(feature = "cli")]
+fn relaxations(options: &scan::ScanOptions) -> Vec<(&'static str, scan::ScanOptions)> {
+    let mut stages = Vec::new();
+    let mut relaxed = options.clone();
+    if relaxed.modified_after.take().is_some() {
+        stages.push(("--since", relaxed.clone()));
+    }
+    if relaxed.under.take().is_some() {
+        stages.push(("--under", relaxed.clone()));
+    }
+    stages
+}
+
+/// Writes a dry-run command to `out`.
+///
+/// This is the only stdout output of a dry run: the command followed by exactly one newline.
+/// Logs go to stderr.
+#[cfg(feature = "cli")]
+fn emit_command(out: &mut impl io::Write, command: &str) -> io::Result<()> {
+    writeln!(out, "{command}")?;
+    out.flush()
+}
+
+/// The `resume` options that turn a matched session into a codex command.
+#[cfg(feature = "cli")]
+struct LaunchContext<'a> {
+    /// `--repo`, if given.
+    repo: Option<&'a Path>,
+    codexdir: &'a Path,
+    home: Option<&'a Path>,
+    codex: &'a args::CodexArgs,
+    sandbox: &'a args::SandboxArgs,
+    /// The `--wrap` remembered for the branch, used only when neither `--wrap` nor the
+    /// config's `wrap_command` is set.
+    remembered_wrap: Option<&'a str>,
+    /// `--dry-run`, or `--plan-out`.
+    dry_run: bool,
+    /// Where a dry run writes its command (`--output`) instead of stdout.
+    output: Option<&'a Path>,
+}
+
+/// A matched session with the codex command that resumes it.
+#[cfg(feature = "cli")]
+struct Launch {
+    /// The session, pointing at its staged copy with `--codexdir-readonly`.
+    session: scan::Session,
+    /// The repo granted to the sandbox.
+    repo: PathBuf,
+    /// The configuration of the granted repo.
+    config: config::Config,
+    /// The directory codex starts in.
+    start_dir: PathBuf,
+    /// The codex command, wrappers included.
+    cmd: process::Cmd,
+    sandbox_mode: codex_cmd::SandboxMode,
+}
+
+/// Builds the codex command for `session`: picks the repo to grant, loads its config, checks
+/// the sandbox options, and stages the session for `--codexdir-readonly`.
+///
+/// # Errors
+///
+/// Returns an error if the session cwd is missing, the repo is not allowed, a requested check
+/// fails (full access, `--verify-commit`), or the command cannot be built.
+#[cfg(feature = "cli")]
+fn prepare_launch(session: scan::Session, context: &LaunchContext<'_>) -> Result<Launch> {
+    util::require_dir(&session.cwd, "session cwd", None)?;
+    let codex = context.codex;
+    let repo = match context.repo {
+        Some(repo) => choose_repo(repo.to_owned(), &session, codex.repo_from_session),
+        // Only `--any`, `--id`, and `--file` may omit `--repo`.
+        None => codex_cmd::worktree_root(&session.cwd).unwrap_or_else(|| session.cwd.clone()),
+    };
+
+    let config = config::Config::load(&repo)?;
+    config.check_allowed(&repo, "repo")?;
+    let sandbox_mode = if codex.dangerously_full_access {
+        check_full_access(
+            config.forbid_full_access,
+            !context.dry_run && io::stdin().is_terminal(),
+            codex.yes,
+        )?;
+        codex_cmd::SandboxMode::DangerFullAccess
+    } else {
+        codex_cmd::SandboxMode::WorkspaceWrite
+    };
+    if codex.verify_commit {
+        verify_session_commit(&repo, &session, codex.strict)?;
+    }
+    let (codex_home, session) = if codex.codexdir_readonly {
+        stage_readonly(context.codexdir, session, context.dry_run)?
+    } else {
+        warn_if_readonly(context.codexdir);
+        (context.codexdir.to_owned(), session)
+    };
+
+    let start_dir = if codex.cd_into_repo {
+        repo.clone()
+    } else {
+        codex.chdir.as_deref().unwrap_or(&session.cwd).to_owned()
+    };
+    let sandbox = context.sandbox;
+    let optional_dirs = optional_dirs(
+        sandbox,
+        &config,
+        config::Vars {
+            repo: &repo,
+            home: context.home,
+            cwd: &session.cwd,
+        },
+    )?;
+    let options = codex_cmd::CodexOptions {
+        mode: sandbox_mode,
+        model: config.model(),
+        network_access: config.network_access.unwrap_or(true),
+        strict: codex.strict,
+        canonicalize: !sandbox.no_canonicalize,
+        repo_discovery: !sandbox.no_repo_discovery,
+        minimize_dirs: sandbox.minimize_dirs,
+        narrow_codexdir: sandbox.narrow_codexdir,
+        add_worktrees: sandbox.add_worktrees,
+    };
+    check_repo_grants(&repo, options, &config)?;
+    let cmd = codex_cmd::build_codex_cmd(
+        codex_cmd::ResumeTarget {
+            repo: &repo,
+            codexdir: &codex_home,
+            session: &session,
+            start_dir: &start_dir,
+            optional_dirs: &optional_dirs,
+        },
+        options,
+    )?;
+    let cmd = if codex.codexdir_readonly {
+        cmd.wrapped(&scratch::env_wrapper(&codex_home)?)
+    } else {
+        cmd
+    }
+    .wrapped(&wrapper(
+        codex.wrapper.wrap.as_deref(),
+        context.remembered_wrap,
+        &config,
+    )?);
+    Ok(Launch {
+        session,
+        repo,
+        config,
+        start_dir,
+        cmd,
+        sandbox_mode,
+    })
+}
+
+/// Offers the sessions for `branch` in a `tmux display-menu` (`--pick-tmux`), where choosing
+/// one opens it in a new window; with `--dry-run`, prints the `display-menu` command instead.
+///
+/// amg exits once the menu is shown, so only the first [`process::MAX_MENU_ENTRIES`] sessions
+/// are offered.
+///
+/// # Errors
+///
+/// Returns an error if a session's command cannot be built, or tmux or the output fails.
+#[cfg(feature = "cli")]
+fn pick_from_tmux_menu(
+    branch: &str,
+    sessions: Vec<scan::Session>,
+    context: &LaunchContext<'_>,
+) -> Result<ExitCode> {
+    if sessions.len() > process::MAX_MENU_ENTRIES {
+        warn!(
+            found = sessions.len(),
+            "offering only the first {} sessions in the tmux menu",
+            process::MAX_MENU_ENTRIES
+        );
+    }
+    let now = SystemTime::now();
+    let entries = sessions
+        .into_iter()
+        .take(process::MAX_MENU_ENTRIES)
+        .enumerate()
+        .map(|(index, session)| {
+            let label = pick::menu_label(branch, &session, now);
+            let launch = prepare_launch(session, context)?;
+            // Every entry is a session of `branch`; one notice is enough.
+            if index == 0 {
+                check_branch_exists(&launch, branch, context.codex.require_branch_exists)?;
+            }
+            let window = process::WindowOptions::default();
+            let open = process::tmux_menu_window_cmd(&launch.start_dir, window, &launch.cmd);
+            Ok((label, open))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let menu = process::tmux_display_menu_cmd(&format!("amg: {branch}"), &entries);
+
+    if !context.dry_run {
+        debug!(entries = entries.len(), "showing tmux menu");
+        process::run_tmux(&menu)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    let command = menu.as_shell_string();
+    info!(command = %command, "dry-run");
+    match context.output {
+        Some(path) => emit_command(&mut util::create_private_file(path)?, &command)
+            .with_context(|| format!("failed to write {}", util::human_path(path)))?,
+        None => {
+            emit_command(&mut io::stdout().lock(), &command).context("failed to write to stdout")?
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Returns whether a new tmux window gets session data as user options: `--tmux-annotate` or
+/// `--no-tmux-annotate`, else the `tmux_annotate` config key, else only when amg runs inside
+/// tmux (the window opens in the current session).
+#[cfg(feature = "cli")]
+fn annotate_window(cli: Option<bool>, config: Option<bool>, target: util::TmuxTarget) -> bool {
+    cli.or(config)
+        .unwrap_or(target == util::TmuxTarget::CurrentSession)
+}
+
+/// Returns the tmux mode given on the command line: `--tmux`, where the hidden `--no-tmux`
+/// means `never`.
+#[cfg(feature = "cli")]
+fn cli_tmux_mode(execution: &args::ExecutionArgs) -> Option<args::TmuxMode> {
+    if execution.no_tmux {
+        Some(args::TmuxMode::Never)
+    } else {
+        execution.tmux
+    }
+}
+
+/// Resolves the tmux mode: the one given on the command line (see [`cli_tmux_mode`]) first,
+/// then the config's `tmux` key, where `false` means `never` and `true` means `auto`, then
+/// the mode remembered for the branch; `auto` otherwise.
+#[cfg(feature = "cli")]
+fn tmux_mode(
+    cli: Option<args::TmuxMode>,
+    config: Option<bool>,
+    remembered: Option<args::TmuxMode>,
+) -> args::TmuxMode {
+    match (cli, config, remembered) {
+        (Some(mode), ..) => mode,
+        (None, Some(false), _) => args::TmuxMode::Never,
+        (None, Some(true), _) => args::TmuxMode::Auto,
+        (None, None, Some(mode)) => mode,
+        (None, None, None) => args::TmuxMode::Auto,
+    }
+}
+
+/// Where [`notify_launch`] announces a new tmux window.
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy)]
+struct Notices {
+    /// In the tmux status line.
+    status_line: bool,
+    /// As a desktop notification.
+    desktop: bool,
+}
+
+/// Announces a tmux window opened for a session.
+///
+/// Shows the configured notification message where `notices` asks for it. Failures are
+/// logged and ignored.
+#[cfg(feature = "cli")]
+fn notify_launch(config: &config::Config, notice: config::NoticeVars<'_>, notices: Notices) {
+    let message = match config.notification(notice) {
+        Ok(message) => message,
+        Err(err) => {
+            warn!("{err:#}");
+            return;
+        }
+    };
+    if notices.status_line
+        && let Err(err) = process::tmux_display_message(&message)
+    {
+        warn!("could not show tmux notification: {err:#}");
+    }
+    if notices.desktop
+        && let Err(err) = process::desktop_notify(&message)
+    {
+        warn!("could not show desktop notification: {err:#}");
+    }
+}
+
+/// Copies a command to the clipboard, warning instead of failing.
+#[cfg(feature = "cli")]
+fn copy_command(command: &str) {
+    match process::copy_to_clipboard(command) {
+        Ok(()) => debug!("copied command to clipboard"),
+        Err(err) => warn!("could not copy the command to the clipboard: {err:#}"),
+    }
+}
+
+/// Resolves the command wrapper: `--wrap` if given, otherwise the global config's `wrap_command`,
+/// otherwise the `--wrap` remembered for the branch.
+///
+/// Returns an empty argv when no wrapper is configured.
+///
+/// # Errors
+///
+/// Returns an error if `--wrap` (or the remembered one) has unbalanced quotes or is empty.
+#[cfg(feature = "cli")]
+fn wrapper(
+    cli: Option<&str>,
+    remembered: Option<&str>,
+    config: &config::Config,
+) -> Result<Vec<String>> {
+    let line = match (cli, &config.wrap_command, remembered) {
+        (Some(line), ..) | (None, None, Some(line)) => line,
+        (None, configured, _) => return Ok(configured.clone().unwrap_or_default()),
+    };
+    let argv = util::split_words(line).context("invalid --wrap")?;
+    if argv.is_empty() {
+        bail!("--wrap must not be empty");
+    }
+    Ok(argv)
+}
+
+/// Lists the optional sandbox directories for a session.
+///
+/// These are the home-directory and system directories (unless disabled via `sandbox`, or by
+/// an empty `home_sandbox_dirs` in the config) followed by the config's expanded
+/// `extra_add_dirs`. A non-empty `home_sandbox_dirs` replaces [`HOME_SANDBOX_DIRS`].
+///
+/// # Errors
+///
+/// Returns an error if an `extra_add_dirs` entry is malformed or not allowed by
+/// `allowed_repos`.
+#[cfg(feature = "cli")]
+fn optional_dirs(
+    sandbox: &args::SandboxArgs,
+    config: &config::Config,
+    vars: config::Vars<'_>,
+) -> Result<Vec<PathBuf>> {
+    let home_dirs: Vec<&str> = match &config.home_sandbox_dirs {
+        Some(entries) => entries.iter().map(|entry| entry.value.as_str()).collect(),
+        None => HOME_SANDBOX_DIRS.to_vec(),
+    };
+    let mut dirs = if sandbox.no_home_dirs || home_dirs.is_empty() {
+        Vec::new()
+    } else {
+        // Portable mode grants no home directories, but still the absolute extra ones.
+        let home = vars.home.filter(|_| !policy::is_portable());
+        codex_cmd::optional_sandbox_dirs(home, &home_dirs, !sandbox.no_extra_dirs)
+    };
+    for dir in config.expanded_extra_add_dirs(vars)? {
+        config.check_allowed(&dir, "extra_add_dirs entry")?;
+        dirs.push(dir);
+    }
+    Ok(dirs)
+}
+
+/// Checks the directories the repo's git layout adds to the grants (the worktree above a
+/// subdirectory repo, and the sibling worktrees) against `allowed_repos`.
+///
+/// # Errors
+///
+/// Returns an error naming the first directory that is not allowed.
+#[cfg(feature = "cli")]
+fn check_repo_grants(
+    repo: &Path,
+    options: codex_cmd::CodexOptions<'_>,
+    config: &config::Config,
+) -> Result<()> {
+    let codex_cmd::RepoGrants { root, worktrees } = codex_cmd::repo_grants(repo, options);
+    if let Some(root) = root {
+        config.check_allowed(&root, "worktree root")?;
+    }
+    for worktree in worktrees {
+        config.check_allowed(&worktree, "sibling worktree")?;
+    }
+    Ok(())
+}
+
+/// Handles the `which` subcommand.
+///
+/// Prints the path of the session file `resume` would use for the branch, or with
+/// `--age-only` the age of its newest session.
+///
+/// # Returns
+///
+/// With `--age-only`, returns [`EXIT_NO_SESSION`] (after printing `none`) if the branch has
+/// no session.
+///
+/// # Errors
+///
+/// Returns an error if the codexdir is invalid or, without `--age-only`, no matching session
+/// is found.
+#[cfg(feature = "cli")]
+fn run_which(args: args::WhichArgs) -> Result<ExitCode> {
+    if args.stdin_jsonl {
+        return stdin_jsonl::run_which(&args);
+    }
+    let codexdir = args
+        .location
+        .codexdir
+        .map(Ok)
+        .unwrap_or_else(config::default_codexdir)?;
+    util::require_dir(&codexdir, "codexdir", Some("CODEX_CODEXDIR"))?;
+    check_codexdir(&codexdir, args.selection.walk.force_scan)?;
+
+    let branch = alias::resolve_branch(&args.branch);
+    let scan_options = scan_options(&args.selection)?;
+    if args.age_only {
+        let newest = scan::find_newest_session(&codexdir, &branch, &scan_options)?.session;
+        output::line(format_args!(
+            "{}",
+            session_age(newest.as_ref(), SystemTime::now())
+        ))?;
+        return Ok(match newest {
+            Some(_) => ExitCode::SUCCESS,
+            None => ExitCode::from(EXIT_NO_SESSION),
+        });
+    }
+    let scan::ScanOutcome { session, stats } =
+        cache::find_session(&codexdir, &branch, &scan_options, !args.cache.no_cache)?;
+    let Some(session) = session else {
+        bail!("{}", no_session_message(&codexdir, &branch, &stats));
+    };
+    output::line(format_args!("{}", session.source_jsonl.display()))?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Renders the `which --age-only` answer: whole seconds since `session`'s file was last
+/// written (zero for a time in the future or an unknown one), or `none`.
+#[cfg(feature = "cli")]
+fn session_age(session: Option<&scan::Session>, now: SystemTime) -> String {
+    match session {
+        Some(session) => session
+            .modified
+            .and_then(|modified| now.duration_since(modified).ok())
+            .map_or(0, |age| age.as_secs())
+            .to_string(),
+        None => "none".to_owned(),
+    }
+}
+
+/// Refuses to deep-walk a codexdir that does not look like one, unless `force_scan` is set.
+///
+/// Pointing `--codexdir` at a large unrelated tree (such as `$HOME`) would otherwise crawl all
+/// of it. See [`scan::probe_codexdir`].
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be read, or it does not look like a codexdir and
+/// `force_scan` is not set.
+#[cfg(feature = "cli")]
+fn check_codexdir(codexdir: &Path, force_scan: bool) -> Result<()> {
+    let probe = scan::probe_codexdir(codexdir)
+        .with_context(|| format!("failed to read codexdir {}", util::human_path(codexdir)))?;
+    let scan::Probe::Unrecognized { nested } = probe else {
+        return Ok(());
+    };
+    let mut problem = format!(
+        "{} does not look like a Codex directory (no sessions/ and no rollout-*.jsonl)",
+        util::human_path(codexdir)
+    );
+    if let Some(nested) = nested {
+        problem.push_str(&format!(
+            "; did you mean --codexdir {}?",
+            util::human_path(&nested)
+        ));
+    }
+    if force_scan {
+        warn!("{problem}; walking it anyway (--force-scan)");
+        return Ok(());
+    }
+    bail!(
+        "{problem}. Point --codexdir (or CODEX_CODEXDIR) at the Codex home directory, \
+         or pass --force-scan to walk it anyway"
+    )
+}
+
+/// Warns when the repo and the codexdir overlap, and refuses to scan a codexdir that is the
+/// repo or contains it unless `allow_overlap` is set.
+///
+/// Pointing both at one "everything" directory makes the scan walk the whole repo for JSONL
+/// files. A codexdir inside the repo is only warned about, since the walk stays small.
+///
+/// # Errors
+///
+/// Returns an error if the codexdir is the repo or one of its ancestors and `allow_overlap`
+/// is not set.
+#[cfg(feature = "cli")]
+fn check_overlap(repo: &Path, codexdir: &Path, allow_overlap: bool) -> Result<()> {
+    let (repo_shown, codexdir_shown) = (util::human_path(repo), util::human_path(codexdir));
+    let problem = match util::overlap(repo, codexdir) {
+        None => return Ok(()),
+        Some(util::Overlap::CodexdirInRepo) => {
+            warn!(
+                "the codexdir {codexdir_shown} is inside the repo {repo_shown}; granting the repo \
+                 also exposes every Codex session to the sandbox (is --codexdir or --repo wrong?)"
+            );
+            return Ok(());
+        }
+        Some(util::Overlap::Same) => {
+            format!("the repo and the codexdir are the same directory ({repo_shown})")
+        }
+        Some(util::Overlap::RepoInCodexdir) => {
+            format!("the codexdir {codexdir_shown} contains the repo {repo_shown}")
+        }
+    };
+    if allow_overlap {
+        warn!("{problem}; scanning it walks the whole repo (--allow-overlap)");
+        return Ok(());
+    }
+    bail!(
+        "{problem}, so finding a session would walk the whole repo. Point --codexdir (or \
+         CODEX_CODEXDIR) at the Codex home directory, or pass --allow-overlap to scan anyway"
+    )
+}
+
+/// Stages a session from a read-only codexdir for `--codexdir-readonly`.
+///
+/// Returns the scratch Codex home to grant and run codex with, and the session pointing at
+/// its writable copy. Nothing is copied for a dry run.
+///
+/// # Errors
+///
+/// Returns an error if the scratch home cannot be located or the session cannot be staged.
+#[cfg(feature = "cli")]
+fn stage_readonly(
+    codexdir: &Path,
+    session: scan::Session,
+    dry_run: bool,
+) -> Result<(PathBuf, scan::Session)> {
+    let home = scratch::scratch_home()?;
+    if dry_run {
+        return Ok((home, session));
+    }
+    let staged = scratch::stage_session(codexdir, &session, &home)
+        .context("failed to stage the session for --codexdir-readonly")?;
+    info!(staged = %util::human_path(&staged), "staged session from read-only codexdir");
+    Ok((
+        home,
+        scan::Session {
+            source_jsonl: staged,
+            ..session
+        },
+    ))
+}
+
+/// Warns when the codexdir looks read-only, since codex will fail to append to the session.
+#[cfg(feature = "cli")]
+fn warn_if_readonly(codexdir: &Path) {
+    if fs::metadata(codexdir).is_ok_and(|m| m.permissions().readonly()) {
+        warn!(
+            "{} is read-only; codex may fail to save the session (see --codexdir-readonly)",
+            util::human_path(codexdir)
+        );
+    }
+}
+
+/// Checks whether `--dangerously-full-access` may be used.
+///
+/// # Errors
+///
+/// Returns an error if a config file sets `forbid_full_access = true`, or if `interactive`
+/// (stdin is a terminal and codex is about to launch) and `--yes` was not given.
+#[cfg(feature = "cli")]
+fn check_full_access(forbidden: bool, interactive: bool, yes: bool) -> Result<()> {
+    if forbidden {
+        bail!(
+            "--dangerously-full-access is forbidden by `forbid_full_access = true` in a config file"
+        );
+    }
+    if interactive && !yes {
+        bail!(
+            "--dangerously-full-access disables the sandbox and approvals; pass --yes to confirm"
+        );
+    }
+    Ok(())
+}
+
+/// Prints a prominent (red, when stderr is a terminal) warning before a full-access launch.
+#[cfg(feature = "cli")]
+fn full_access_warning() {
+    let message = "WARNING: launching codex with -s danger-full-access and approvals disabled";
+    if io::stderr().is_terminal() {
+        eprintln!("\x1b[1;31m{message}\x1b[0m");
+    } else {
+        eprintln!("{message}");
+    }
+}
+
+/// How `--repo` relates to the git repository containing the session cwd.
+#[cfg(feature = "cli")]
+#[derive(Debug, PartialEq, Eq)]
+enum RepoCheck {
+    /// The session cwd is inside `--repo`'s worktree.
+    Same,
+    /// The session cwd belongs to a different worktree, rooted at the given path.
+    Differs(PathBuf),
+    /// The session cwd is not inside a git worktree.
+    NotGit,
+}
+
+/// Compares the worktree roots of `repo` and the session `cwd`.
+#[cfg(feature = "cli")]
+fn check_session_repo(repo: &Path, cwd: &Path) -> RepoCheck {
+    let Some(session_root) = codex_cmd::worktree_root(cwd) else {
+        return RepoCheck::NotGit;
+    };
+    let repo_root = codex_cmd::worktree_root(repo)
+        .unwrap_or_else(|| fs::canonicalize(repo).unwrap_or_else(|_| repo.to_owned()));
+    if session_root == repo_root {
+        RepoCheck::Same
+    } else {
+        RepoCheck::Differs(session_root)
+    }
+}
+
+/// Returns the repository to grant to the sandbox, warning when `--repo` looks wrong.
+///
+/// When the session cwd lives in a different worktree than `--repo`, a warning names both
+/// paths; with `repo_from_session` the session's worktree root is used instead.
+#[cfg(feature = "cli")]
+fn choose_repo(repo: PathBuf, session: &scan::Session, repo_from_session: bool) -> PathBuf {
+    match check_session_repo(&repo, &session.cwd) {
+        RepoCheck::Same => repo,
+        RepoCheck::Differs(session_root) if repo_from_session => {
+            info!(repo = %util::human_path(&session_root), "using the session's repository root");
+            session_root
+        }
+        RepoCheck::Differs(session_root) => {
+            warn!(
+                "session repository {} differs from --repo {}; codex will be granted --repo \
+                 (pass --repo-from-session to use the session's repository instead)",
+                util::human_path(&session_root),
+                util::human_path(&repo)
+            );
+            repo
+        }
+        RepoCheck::NotGit => {
+            if repo_from_session {
+                warn!(
+                    cwd = %util::human_path(&session.cwd),
+                    "session cwd is not in a git worktree; keeping --repo"
+                );
+            }
+            repo
+        }
+    }
+}
+
+/// Converts the session selection flags into [`scan::ScanOptions`].
+///
+/// # Errors
+///
+/// Returns an error if `--under` is not an existing directory, or `--tag` is invalid or
+/// matches no session.
+#[cfg(feature = "cli")]
+fn scan_options(selection: &args::SelectionArgs) -> Result<scan::ScanOptions> {
+    let under =
+        match &selection.under {
+            Some(dir) => {
+                util::require_dir(dir, "--under", None)?;
+                Some(fs::canonicalize(dir).with_context(|| {
+                    format!("failed to resolve --under {}", util::human_path(dir))
+                })?)
+            }
+            None => None,
+        };
+    let tagged = match &selection.tag {
+        Some(tag) => Some(scan::TagFilter {
+            tag: tag.clone(),
+            ids: tag::tagged_sessions(tag)?,
+        }),
+        None => None,
+    };
+    if selection.require_metadata && selection.originator.is_none() && selection.model_was.is_none()
+    {
+        bail!("--require-metadata needs --originator or --model-was");
+    }
+    let mut strip_prefixes = config::Config::load_global()?
+        .strip_prefixes
+        .into_iter()
+        .map(|entry| entry.value)
+        .collect::<Vec<_>>();
+    strip_prefixes.extend(selection.strip_prefix.iter().cloned());
+    Ok(scan::ScanOptions {
+        any_filename: selection.any_filename,
+        under,
+        tagged,
+        originator: selection.originator.clone(),
+        model: selection.model_was.clone(),
+        require_metadata: selection.require_metadata,
+        modified_after: selection
+            .since
+            .and_then(|since| SystemTime::now().checked_sub(since)),
+        owner: if selection.any_owner {
+            None
+        } else {
+            util::current_uid()
+        },
+        allow_weird_paths: selection.allow_weird_paths,
+        skip_symlink_check: selection.no_symlink_check,
+        keep_first_line: false,
+        strict_file_ids: false,
+        strip_prefixes,
+        exclude: ignore::Exclusions::from_args(&selection.exclude)?,
+        map_paths: selection.map_path.clone(),
+        dedup_ids: selection.dedup_ids,
+    })
+}
+
+/// Checks that the session's recorded commit is an ancestor of `HEAD` in `repo`.
+///
+/// Sessions recorded during a rebase or on a detached head may carry a commit that is not
+/// part of the current branch history; this is reported as a warning, or as an error when
+/// `strict` is set.
+///
+/// # Errors
+///
+/// Returns an error if `strict` is set and the commit is missing, not an ancestor, or cannot
+/// be checked.
+#[cfg(feature = "cli")]
+fn verify_session_commit(repo: &Path, session: &scan::Session, strict: bool) -> Result<()> {
+    let problem = match &session.commit_hash {
+        None => Some(format!(
+            "session {} has no recorded commit; cannot verify it against {}",
+            session.id,
+            util::human_path(repo)
+        )),
+        Some(commit) => {
+            match git::head_commit(repo).and_then(|head| git::is_ancestor(repo, commit, &head)) {
+                Ok(true) => {
+                    debug!(commit = %commit, "session commit is an ancestor of HEAD");
+                    None
+                }
+                Ok(false) => Some(format!(
+                    "session commit {commit} is not an ancestor of HEAD in {}; \
+                     the session may have been recorded during a rebase or on another branch",
+                    util::human_path(repo)
+                )),
+                Err(err) => Some(format!("could not verify session commit {commit}: {err:#}")),
+            }
+        }
+    };
+
+    match problem {
+        Some(msg) if strict => bail!("{msg}"),
+        Some(msg) => {
+            warn!("{msg}");
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Checks that `branch` still exists in the repo granted to `launch`, since resuming the
+/// session of a branch that was merged and deleted usually means redoing finished work.
+///
+/// A missing branch is reported as a warning naming when the session file was last written,
+/// or as an error with `require`. Nothing is checked without a branch (a session matched by
+/// `--file`, `--id`, or `--any` that records none) or when the refs cannot be read (see
+/// [`git::branch_exists`]).
+///
+/// # Errors
+///
+/// Returns an error if `require` is set and the branch does not exist.
+#[cfg(feature = "cli")]
+fn check_branch_exists(launch: &Launch, branch: &str, require: bool) -> Result<()> {
+    if branch.is_empty() {
+        return Ok(());
+    }
+    let Launch { repo, session, .. } = launch;
+    match git::branch_exists(repo, branch) {
+        Some(true) => Ok(()),
+        None => {
+            debug!(branch = %branch, repo = %util::human_path(repo), "cannot tell whether the branch exists");
+            Ok(())
+        }
+        Some(false) => {
+            let written = session
+                .modified_rfc3339()
+                .map_or_else(String::new, |at| format!(" (last written {at})"));
+            let message = format!(
+                "branch {branch} no longer exists in {} (it may have been merged); session \
+                 {}{written} may cover finished work",
+                util::human_path(repo),
+                session.id
+            );
+            if require {
+                bail!("{message}");
+            }
+            warn!("{message} (pass --require-branch-exists to refuse such resumes)");
+            Ok(())
+        }
+    }
+}
+
+/// Backs up the matched session file before it is resumed.
+///
+/// # Errors
+///
+/// Returns an error if the backup fails, unless `--backup-best-effort` is set, in which case
+/// the failure is logged as a warning.
+#[cfg(feature = "cli")]
+fn backup_session(session: &scan::Session, opts: &args::BackupArgs) -> Result<()> {
+    match backup::backup_session(&session.source_jsonl, usize::from(opts.backup_keep)) {
+        Ok(path) => {
+            info!(backup = %util::human_path(&path), "backed up session file");
+            Ok(())
+        }
+        Err(err) if opts.backup_best_effort => {
+            warn!("session backup failed, continuing: {err:#}");
+            Ok(())
+        }
+        Err(err) => Err(err.context(
+            "failed to back up session file (use --backup-best-effort to continue anyway)",
+        )),
+    }
+}
+
+/// The error for a selection that matched no session, carrying its message.
+///
+/// [`run_resume_branch`] looks for it to exit with [`EXIT_NO_SESSION`] under `--print-id`.
+#[cfg(feature = "cli")]
+#[derive(Debug)]
+struct NoSession(String);
+
+#[cfg(feature = "cli")]
+impl std::fmt::Display for NoSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(feature = "cli")]
+impl std::error::Error for NoSession {}
+
+/// Builds the error message for a scan that found no matching session.
+///
+/// Distinguishes a codexdir with no JSONL files at all (most likely the wrong directory)
+/// from a genuine miss, listing a few of the files that were seen instead.
+#[cfg(feature = "cli")]
+fn no_session_message(codexdir: &Path, branch: &str, stats: &scan::ScanStats) -> String {
+    if stats.files_scanned > 0 {
+        let mut msg = format!(
+            "No matching session found for branch {:?} under {}",
+            branch,
+            util::human_path(codexdir)
+        );
+        if stats.excluded_by_prefix > 0 {
+            msg.push_str(&format!(
+                " ({} matching session(s) excluded by --under)",
+                stats.excluded_by_prefix
+            ));
+        }
+        if stats.excluded_by_age > 0 {
+            msg.push_str(&format!(
+                " ({} session file(s) older than --since skipped)",
+                stats.excluded_by_age
+            ));
+        }
+        if let Some(tag) = &stats.excluded_by_tag {
+            msg.push_str(&format!(
+                " ({} matching session(s) not tagged {:?})",
+                tag.1, tag.0
+            ));
+        }
+        if stats.excluded_by_metadata > 0 {
+            msg.push_str(&format!(
+                " ({} matching session(s) from another originator or model)",
+                stats.excluded_by_metadata
+            ));
+        }
+        if stats.skipped_by_owner > 0 {
+            msg.push_str(&format!(
+                " ({} file(s) owned by other users skipped; pass --any-owner to include them)",
+                stats.skipped_by_owner
+            ));
+        }
+        return msg;
+    }
+
+    let mut msg = format!(
+        "No .jsonl session files found under {}; is this the right codexdir? \
+         Point --codexdir (or CODEX_CODEXDIR) at the Codex home directory containing `sessions/`",
+        util::human_path(codexdir)
+    );
+    if !stats.sample_files.is_empty() {
+        let examples = stats
+            .sample_files
+            .iter()
+            .map(|p| p.strip_prefix(codexdir).unwrap_or(p).display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        msg.push_str(&format!(" (saw files such as: {examples})"));
+    }
+    msg
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::{matched_session, temp_dir};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(false, false, false)]
+    #[case(false, true, true)]
+    #[case(false, false, true)]
+    fn full_access_allowed(#[case] forbidden: bool, #[case] interactive: bool, #[case] yes: bool) {
+        assert!(check_full_access(forbidden, interactive, yes).is_ok());
+    }
+
+    #[rstest]
+    #[case(true, false, true)]
+    #[case(true, true, true)]
+    fn full_access_refused_by_config(
+        #[case] forbidden: bool,
+        #[case] interactive: bool,
+        #[case] yes: bool,
+    ) {
+        let err = check_full_access(forbidden, interactive, yes).unwrap_err();
+        assert!(err.to_string().contains("forbid_full_access"), "{err}");
+    }
+
+    #[test]
+    fn full_access_requires_yes_when_interactive() {
+        let err = check_full_access(false, true, false).unwrap_err();
+        assert!(err.to_string().contains("--yes"), "{err}");
+    }
+
+    #[test]
+    fn extra_add_dirs_must_be_allowed() {
+        let config = config::Config::parse(
+            "allowed_repos = [\"/srv/repos/*\"]\nextra_add_dirs = [\"${repo}/../data\", \"/opt/x\"]",
+            Path::new("/etc/amg/config.toml"),
+        )
+        .unwrap();
+        let sandbox = args::SandboxArgs {
+            no_home_dirs: true,
+            ..Default::default()
+        };
+        let vars = |repo| config::Vars {
+            repo,
+            home: None,
+            cwd: repo,
+        };
+
+        let err = optional_dirs(&sandbox, &config, vars(Path::new("/srv/repos/app"))).unwrap_err();
+
+        assert!(
+            err.to_string().starts_with("extra_add_dirs entry /opt/x"),
+            "{err}"
+        );
+        assert!(
+            config
+                .check_allowed(Path::new("/srv/repos/app"), "repo")
+                .is_ok()
+        );
+    }
+
+    #[rstest]
+    #[case::default(false, "", true)]
+    #[case::flag(true, "", false)]
+    #[case::empty_config(false, "home_sandbox_dirs = []", false)]
+    #[case::listed_config(false, "home_sandbox_dirs = [\".cargo\"]", true)]
+    fn home_dirs_are_granted_unless_disabled(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] no_home_dirs: bool,
+        #[case] content: &str,
+        #[case] granted: bool,
+    ) {
+        let home = dir.join("home");
+        fs::create_dir_all(home.join(".cargo")).unwrap();
+        let repo = dir.join("repo");
+        let config = config::Config::parse(content, Path::new("/cfg.toml")).unwrap();
+        let sandbox = args::SandboxArgs {
+            no_home_dirs,
+            ..Default::default()
+        };
+        let vars = config::Vars {
+            repo: &repo,
+            home: Some(&home),
+            cwd: &repo,
+        };
+
+        let dirs = optional_dirs(&sandbox, &config, vars).unwrap();
+
+        assert_eq!(dirs.contains(&home.join(".cargo")), granted, "{dirs:?}");
+        if !granted {
+            assert!(dirs.iter().all(|d| !d.starts_with(&home)), "{dirs:?}");
+        }
+    }
+
+    #[test]
+    fn listed_home_sandbox_dirs_replace_the_defaults() {
+        let config = config::Config::parse(
+            "home_sandbox_dirs = [\"work/cache\"]",
+            Path::new("/cfg.toml"),
+        )
+        .unwrap();
+        let vars = config::Vars {
+            repo: Path::new("/src/repo"),
+            home: Some(Path::new("/home/me")),
+            cwd: Path::new("/src/repo"),
+        };
+
+        let dirs = optional_dirs(&args::SandboxArgs::default(), &config, vars).unwrap();
+
+        let home_dirs: Vec<_> = dirs.iter().filter(|d| d.starts_with("/home/me")).collect();
+        assert_eq!(home_dirs, [Path::new("/home/me/work/cache")]);
+    }
+
+    fn grant_options(add_worktrees: bool) -> codex_cmd::CodexOptions<'static> {
+        codex_cmd::CodexOptions {
+            mode: codex_cmd::SandboxMode::WorkspaceWrite,
+            model: DEFAULT_MODEL,
+            network_access: true,
+            strict: false,
+            canonicalize: true,
+            repo_discovery: true,
+            minimize_dirs: false,
+            narrow_codexdir: false,
+            add_worktrees,
+        }
+    }
+
+    fn allowing(pattern: &Path) -> config::Config {
+        let content = format!("allowed_repos = [{:?}]", pattern.to_str().unwrap());
+        config::Config::parse(&content, Path::new("/etc/amg/config.toml")).unwrap()
+    }
+
+    #[rstest]
+    fn a_discovered_root_must_be_allowed(#[from(temp_dir)] dir: PathBuf) {
+        let root = fs::canonicalize(&dir).unwrap().join("mono");
+        let crate_dir = root.join("crates/core");
+        fs::create_dir_all(root.join(DOT_GIT)).unwrap();
+        fs::create_dir_all(&crate_dir).unwrap();
+        let config = allowing(&root.join("crates/*"));
+        config.check_allowed(&crate_dir, "repo").unwrap();
+
+        let err = check_repo_grants(&crate_dir, grant_options(false), &config).unwrap_err();
+
+        assert!(err.to_string().starts_with("worktree root"), "{err}");
+        assert!(check_repo_grants(&crate_dir, grant_options(false), &allowing(&root)).is_ok());
+    }
+
+    #[rstest]
+    fn a_sibling_worktree_must_be_allowed(#[from(temp_dir)] dir: PathBuf) {
+        let dir = fs::canonicalize(&dir).unwrap();
+        let main = dir.join("main");
+        let sibling = dir.join("wt");
+        let admin = main.join(".git/worktrees/wt");
+        fs::create_dir_all(&admin).unwrap();
+        fs::create_dir_all(&sibling).unwrap();
+        fs::write(
+            admin.join("gitdir"),
+            format!("{}\n", sibling.join(".git").display()),
+        )
+        .unwrap();
+        fs::write(
+            sibling.join(".git"),
+            format!("gitdir: {}\n", admin.display()),
+        )
+        .unwrap();
+        let config = allowing(&main);
+
+        let err = check_repo_grants(&main, grant_options(true), &config).unwrap_err();
+
+        assert!(err.to_string().starts_with("sibling worktree"), "{err}");
+        assert!(check_repo_grants(&main, grant_options(false), &config).is_ok());
+        assert!(check_repo_grants(&main, grant_options(true), &allowing(&dir.join("*"))).is_ok());
+    }
+
+    #[test]
+    fn emit_command_writes_exactly_one_line() {
+        let mut out = Vec::new();
+
+        emit_command(&mut out, "'codex' 'resume' 'x'").unwrap();
+
+        assert_eq!(out, b"'codex' 'resume' 'x'\n");
+    }
+
+    fn git_worktree(dir: &Path) -> PathBuf {
+        fs::create_dir_all(dir.join(DOT_GIT)).unwrap();
+        fs::canonicalize(dir).unwrap()
+    }
+
+    #[rstest]
+    fn repo_check_accepts_cwd_inside_repo(#[from(temp_dir)] dir: PathBuf) {
+        let repo = git_worktree(&dir.join("repo"));
+        fs::create_dir_all(repo.join("crates/api")).unwrap();
+
+        assert_eq!(
+            check_session_repo(&repo, &repo.join("crates/api")),
+            RepoCheck::Same
+        );
+    }
+
+    #[rstest]
+    fn repo_check_reports_differing_repo(#[from(temp_dir)] dir: PathBuf) {
+        let repo = git_worktree(&dir.join("repo"));
+        let sibling = git_worktree(&dir.join("sibling"));
+
+        assert_eq!(
+            check_session_repo(&repo, &sibling),
+            RepoCheck::Differs(sibling)
+        );
+    }
+
+    #[rstest]
+    fn repo_check_reports_non_git_cwd(#[from(temp_dir)] dir: PathBuf) {
+        let repo = git_worktree(&dir.join("repo"));
+        fs::create_dir_all(dir.join("scratch")).unwrap();
+
+        assert_eq!(
+            check_session_repo(&repo, &dir.join("scratch")),
+            RepoCheck::NotGit
+        );
+    }
+
+    #[rstest]
+    #[case(false, "repo")]
+    #[case(true, "sibling")]
+    fn repo_from_session_selects_session_repo(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] repo_from_session: bool,
+        #[case] expected: &str,
+    ) {
+        let repo = git_worktree(&dir.join("repo"));
+        let sibling = git_worktree(&dir.join("sibling"));
+        let session = scan::Session {
+            cwd: sibling,
+            ..matched_session("0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b")
+        };
+
+        let chosen = choose_repo(repo, &session, repo_from_session);
+
+        assert_eq!(chosen.file_name(), Some(OsStr::new(expected)));
+    }
+
+    #[rstest]
+    #[case::existing("main", false, true)]
+    #[case::deleted_warns("gone", false, true)]
+    #[case::deleted_fails("gone", true, false)]
+    #[case::no_branch("", true, true)]
+    fn missing_branches_warn_or_fail(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] branch: &str,
+        #[case] require: bool,
+        #[case] ok: bool,
+    ) {
+        let repo = git_worktree(&dir.join("repo"));
+        fs::create_dir_all(repo.join(".git/refs/heads")).unwrap();
+        fs::write(repo.join(".git/refs/heads/main"), "0123456789abcdef\n").unwrap();
+        let session = scan::Session {
+            cwd: repo.clone(),
+            modified: Some(SystemTime::UNIX_EPOCH),
+            ..matched_session("0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b")
+        };
+
+        let launch = Launch {
+            session,
+            repo: repo.clone(),
+            config: config::Config::default(),
+            start_dir: repo,
+            cmd: process::Cmd {
+                program: "codex".into(),
+                args: Vec::new(),
+            },
+            sandbox_mode: codex_cmd::SandboxMode::WorkspaceWrite,
+        };
+
+        let checked = check_branch_exists(&launch, branch, require);
+
+        assert_eq!(checked.is_ok(), ok, "{checked:?}");
+        if let Err(err) = checked {
+            let msg = err.to_string();
+            assert!(msg.contains("branch gone no longer exists"), "{msg}");
+            assert!(msg.contains("last written 1970-01-01T00:00:00Z"), "{msg}");
+        }
+    }
+
+    #[rstest]
+    #[case::written_a_minute_ago(Some(-60), "60")]
+    #[case::written_in_the_future(Some(5), "0")]
+    #[case::unknown(None, "0")]
+    fn session_age_counts_from_the_last_write(
+        #[case] written_offset_secs: Option<i64>,
+        #[case] expected: &str,
+    ) {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let session = scan::Session {
+            modified: written_offset_secs.map(|offset| match offset {
+                ..0 => now - Duration::from_secs(offset.unsigned_abs()),
+                _ => now + Duration::from_secs(offset.unsigned_abs()),
+            }),
+            ..matched_session("0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b")
+        };
+
+        assert_eq!(session_age(Some(&session), now), expected);
+        assert_eq!(session_age(None, now), "none");
+    }
+
+    #[test]
+    fn no_session_message_reports_genuine_miss() {
+        let stats = scan::ScanStats {
+            files_scanned: 3,
+            ..Default::default()
+        };
+        let msg = no_session_message(Path::new("/home/u/.codex"), "main", &stats);
+        assert!(msg.starts_with("No matching session found for branch \"main\""));
+        assert!(!msg.contains("--under"));
+    }
+
+    #[rstest]
+    #[case(None, None, None, &[])]
+    #[case(None, Some(&["docker", "exec", "box"][..]), None, &["docker", "exec", "box"])]
+    #[case(Some("ssh 'my host'"), Some(&["docker"][..]), None, &["ssh", "my host"])]
+    #[case(None, None, Some("nice -n 5"), &["nice", "-n", "5"])]
+    #[case(None, Some(&["docker"][..]), Some("nice"), &["docker"])]
+    #[case(Some("ssh host"), None, Some("nice"), &["ssh", "host"])]
+    fn wrapper_prefers_the_flag_over_the_config(
+        #[case] cli: Option<&str>,
+        #[case] configured: Option<&[&str]>,
+        #[case] remembered: Option<&str>,
+        #[case] expected: &[&str],
+    ) {
+        let config = config::Config {
+            wrap_command: configured.map(|argv| argv.iter().map(|a| (*a).to_owned()).collect()),
+            ..Default::default()
+        };
+
+        assert_eq!(wrapper(cli, remembered, &config).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("  ")]
+    #[case("docker \"exec")]
+    fn wrapper_rejects_invalid_flags(#[case] cli: &str) {
+        assert!(wrapper(Some(cli), None, &config::Config::default()).is_err());
+    }
+
+    #[rstest]
+    #[case::default(None, false, None, None, TmuxMode::Auto)]
+    #[case::config_true(None, false, Some(true), None, TmuxMode::Auto)]
+    #[case::config_false(None, false, Some(false), None, TmuxMode::Never)]
+    #[case::cli_over_config(Some(TmuxMode::Always), false, Some(false), None, TmuxMode::Always)]
+    #[case::cli_auto_over_config(Some(TmuxMode::Auto), false, Some(false), None, TmuxMode::Auto)]
+    #[case::no_tmux_alias(None, true, Some(true), None, TmuxMode::Never)]
+    #[case::remembered(None, false, None, Some(TmuxMode::Never), TmuxMode::Never)]
+    #[case::config_over_remembered(None, false, Some(true), Some(TmuxMode::Never), TmuxMode::Auto)]
+    #[case::cli_over_remembered(
+        Some(TmuxMode::Auto),
+        false,
+        None,
+        Some(TmuxMode::Always),
+        TmuxMode::Auto
+    )]
+    #[case::no_tmux_over_remembered(None, true, None, Some(TmuxMode::Always), TmuxMode::Never)]
+    fn resolves_the_tmux_mode(
+        #[case] cli: Option<TmuxMode>,
+        #[case] no_tmux: bool,
+        #[case] config: Option<bool>,
+        #[case] remembered: Option<TmuxMode>,
+        #[case] expected: TmuxMode,
+    ) {
+        let execution = args::ExecutionArgs {
+            tmux: cli,
+            no_tmux,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            tmux_mode(cli_tmux_mode(&execution), config, remembered),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::inside_tmux(None, None, util::TmuxTarget::CurrentSession, true)]
+    #[case::outside_tmux(None, None, util::TmuxTarget::Detached, false)]
+    #[case::config_off(None, Some(false), util::TmuxTarget::CurrentSession, false)]
+    #[case::config_on(None, Some(true), util::TmuxTarget::Detached, true)]
+    #[case::cli_over_config(Some(true), Some(false), util::TmuxTarget::Detached, true)]
+    #[case::cli_off(Some(false), Some(true), util::TmuxTarget::CurrentSession, false)]
+    fn resolves_whether_to_annotate_the_window(
+        #[case] cli: Option<bool>,
+        #[case] config: Option<bool>,
+        #[case] target: util::TmuxTarget,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(annotate_window(cli, config, target), expected);
+    }
+
+    #[rstest]
+    #[case::same("all", "all", true)]
+    #[case::codexdir_contains_repo("all/api", "all", true)]
+    #[case::repo_contains_codexdir("api", "api/.codex", false)]
+    #[case::disjoint("api", "codex", false)]
+    fn refuses_to_scan_a_codexdir_covering_the_repo(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] repo: &str,
+        #[case] codexdir: &str,
+        #[case] refused: bool,
+    ) {
+        let (repo, codexdir) = (dir.join(repo), dir.join(codexdir));
+        fs::create_dir_all(&repo).unwrap();
+        fs::create_dir_all(&codexdir).unwrap();
+
+        let result = check_overlap(&repo, &codexdir, false);
+
+        assert_eq!(result.is_err(), refused);
+        if let Err(err) = result {
+            assert!(err.to_string().contains("--allow-overlap"), "{err}");
+        }
+        assert!(check_overlap(&repo, &codexdir, true).is_ok());
+    }
+
+    #[rstest]
+    fn refuses_unrecognized_codexdir_without_force_scan(#[from(temp_dir)] dir: PathBuf) {
+        fs::create_dir_all(dir.join(".codex/sessions")).unwrap();
+        fs::write(dir.join(".bashrc"), "").unwrap();
+
+        let err = check_codexdir(&dir, false).unwrap_err().to_string();
+
+        assert!(
+            err.contains("does not look like a Codex directory"),
+            "{err}"
+        );
+        assert!(err.contains("--force-scan"), "{err}");
+        assert!(
+            err.contains(&format!(
+                "did you mean --codexdir {}",
+                dir.join(".codex").display()
+            )),
+            "{err}"
+        );
+        assert!(check_codexdir(&dir, true).is_ok());
+        assert!(check_codexdir(&dir.join(".codex"), false).is_ok());
+    }
+
+    #[test]
+    fn relaxes_since_before_under() {
+        let options = scan::ScanOptions {
+            under: Some(PathBuf::from("/w")),
+            modified_after: Some(SystemTime::UNIX_EPOCH),
+            any_filename: true,
+            ..Default::default()
+        };
+
+        let stages = relaxations(&options);
+
+        let filters: Vec<_> = stages.iter().map(|(filter, _)| *filter).collect();
+        assert_eq!(filters, ["--since", "--under"]);
+        assert!(stages[0].1.modified_after.is_none());
+        assert!(stages[0].1.under.is_some());
+        assert!(stages[1].1.under.is_none());
+        assert!(stages.iter().all(|(_, relaxed)| relaxed.any_filename));
+    }
+
+    #[test]
+    fn relaxations_skip_unset_filters() {
+        let under_only = scan::ScanOptions {
+            under: Some(PathBuf::from("/w")),
+            ..Default::default()
+        };
+
+        assert!(relaxations(&scan::ScanOptions::default()).is_empty());
+        assert_eq!(relaxations(&under_only)[0].0, "--under");
+        assert_eq!(relaxations(&under_only).len(), 1);
+    }
+
+    #[rstest]
+    #[case::strict_match(false, true, "main", Some(&[][..]))]
+    #[case::without_since(true, true, "main", Some(&["--since"][..]))]
+    #[case::without_since_and_under(true, false, "main", Some(&["--since", "--under"][..]))]
+    #[case::branch_is_never_relaxed(false, true, "other", None)]
+    fn relaxation_stages(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] old: bool,
+        #[case] inside: bool,
+        #[case] branch: &str,
+        #[case] expected_dropped: Option<&[&str]>,
+    ) {
+        let under = dir.join("work/acme");
+        let cwd = if inside {
+            under.join("api")
+        } else {
+            dir.join("work/other")
+        };
+        fs::create_dir_all(&cwd).unwrap();
+        fs::create_dir_all(&under).unwrap();
+        let session_file = dir.join("codex/rollout-a.jsonl");
+        fs::create_dir_all(session_file.parent().unwrap()).unwrap();
+        let line = serde_json::json!({
+            "payload": {
+                "git": { "branch": "main" },
+                "cwd": cwd,
+                "id": "0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b"
+            }
+        });
+        fs::write(&session_file, format!("{line}\n")).unwrap();
+        if old {
+            let month_ago = SystemTime::now() - Duration::from_secs(30 * 86_400);
+            fs::File::options()
+                .write(true)
+                .open(&session_file)
+                .and_then(|file| file.set_modified(month_ago))
+                .unwrap();
+        }
+        let options = scan::ScanOptions {
+            under: Some(fs::canonicalize(&under).unwrap()),
+            modified_after: Some(SystemTime::now() - Duration::from_secs(7 * 86_400)),
+            ..Default::default()
+        };
+
+        let codexdir = dir.join("codex");
+        let (outcome, dropped) = find_relaxed(lookup(&codexdir, &options), branch).unwrap();
+
+        match expected_dropped {
+            Some(expected) => {
+                assert!(outcome.session.is_some());
+                assert_eq!(dropped, expected);
+            }
+            None => {
+                assert!(outcome.session.is_none());
+                assert_eq!(dropped, ["--since", "--under"]);
+            }
+        }
+    }
+
+    /// A lookup without the last-hit cache.
+    fn lookup<'a>(codexdir: &'a Path, options: &'a scan::ScanOptions) -> Lookup<'a> {
+        Lookup {
+            codexdir,
+            options,
+            use_cache: false,
+        }
+    }
+
+    fn write_id_sessions(codexdir: &Path, ids: &[&str]) {
+        fs::create_dir_all(codexdir).unwrap();
+        for (n, id) in ids.iter().enumerate() {
+            let line = serde_json::json!({
+                "payload": { "git": { "branch": format!("b{n}") }, "cwd": "/w", "id": id }
+            });
+            fs::write(
+                codexdir.join(format!("rollout-{n}.jsonl")),
+                format!("{line}\n"),
+            )
+            .unwrap();
+        }
+    }
+
+    #[rstest]
+    #[case::unique("7f3a", Ok("b0"))]
+    #[case::case_insensitive("7F3A9C", Ok("b0"))]
+    #[case::full_group("7f3a9c1d", Ok("b0"))]
+    #[case::ambiguous("0199", Err("ambiguous"))]
+    #[case::missing("abcd", Err("no session id"))]
+    #[case::too_short("7f3", Err("too short"))]
+    #[case::not_hex("7f3z", Err("not a session id prefix"))]
+    fn id_prefixes_must_match_exactly_one_session(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] prefix: &str,
+        #[case] expected: std::result::Result<&str, &str>,
+    ) {
+        let codexdir = dir.join("codex");
+        write_id_sessions(
+            &codexdir,
+            &[
+                "7f3a9c1d-3c4d-7e8f-9a0b-1c2d3e4f5a6b",
+                "0199a6b2-3c4d-7e8f-9a0b-000000000001",
+                "0199a6b2-3c4d-7e8f-9a0b-000000000002",
+            ],
+        );
+
+        let options = scan::ScanOptions::default();
+        let found = find_by_id_prefix(lookup(&codexdir, &options), prefix);
+
+        match expected {
+            Ok(branch) => assert_eq!(found.unwrap().1.as_deref(), Some(branch)),
+            Err(message) => {
+                let err = found.unwrap_err().to_string();
+                assert!(err.contains(message), "{err}");
+            }
+        }
+    }
+
+    #[rstest]
+    fn ambiguous_prefixes_list_candidates_with_branches(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = dir.join("codex");
+        let ids: Vec<String> = (0..MAX_LISTED_ID_MATCHES + 2)
+            .map(|n| format!("0199a6b2-3c4d-7e8f-9a0b-{n:012x}"))
+            .collect();
+        write_id_sessions(
+            &codexdir,
+            &ids.iter().map(String::as_str).collect::<Vec<_>>(),
+        );
+
+        let options = scan::ScanOptions::default();
+        let err = find_by_id_prefix(lookup(&codexdir, &options), "0199a6b2")
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains(&format!("{}  b1  ", ids[1])), "{err}");
+        assert_eq!(err.matches(" ago").count(), MAX_LISTED_ID_MATCHES);
+        assert!(err.ends_with("\n  ..."), "{err}");
+    }
+
+    #[rstest]
+    fn a_session_swapped_after_the_scan_is_matched_again(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = dir.join("codex");
+        let ids = [
+            "7f3a9c1d-3c4d-7e8f-9a0b-1c2d3e4f5a6b",
+            "0199a6b2-3c4d-7e8f-9a0b-000000000001",
+        ];
+        write_id_sessions(&codexdir, &ids);
+        let options = scan::ScanOptions::default();
+        let first = cache::find_session(&codexdir, "b0", &options, false)
+            .unwrap()
+            .session
+            .unwrap();
+        assert_eq!(scan::verify_unchanged(&first), Ok(()));
+
+        // A sync tool rotates the file and puts another session in its place.
+        let rotated = codexdir.join("rollout-9.jsonl");
+        fs::rename(&first.source_jsonl, &rotated).unwrap();
+        fs::copy(codexdir.join("rollout-1.jsonl"), &first.source_jsonl).unwrap();
+        let change = scan::verify_unchanged(&first).unwrap_err();
+        assert!(
+            change.contains(&format!("now records session {}", ids[1])),
+            "{change}"
+        );
+
+        let selected = Selected::Branch {
+            branch: "b0",
+            relax: false,
+        };
+        let again = find_again(lookup(&codexdir, &options), selected, &change).unwrap();
+
+        assert_eq!(again.id, ids[0]);
+        assert_eq!(again.source_jsonl, rotated);
+    }
+
+    #[rstest]
+    fn a_deleted_session_fails_when_nothing_matches_again(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = dir.join("codex");
+        write_id_sessions(&codexdir, &["7f3a9c1d-3c4d-7e8f-9a0b-1c2d3e4f5a6b"]);
+        let options = scan::ScanOptions::default();
+        let first = cache::find_session(&codexdir, "b0", &options, false)
+            .unwrap()
+            .session
+            .unwrap();
+        fs::remove_file(&first.source_jsonl).unwrap();
+        let change = scan::verify_unchanged(&first).unwrap_err();
+        assert!(change.ends_with("no longer exists"), "{change}");
+
+        for selected in [
+            Selected::Fixed,
+            Selected::Branch {
+                branch: "b0",
+                relax: true,
+            },
+        ] {
+            let err = find_again(lookup(&codexdir, &options), selected, &change).unwrap_err();
+            assert!(err.to_string().starts_with(&change), "{err}");
+        }
+    }
+
+    #[rstest]
+    #[case::plain("/tmp/a b/s.jsonl", Ok("/tmp/a b/s.jsonl"))]
+    #[case::url("file:///tmp/s.jsonl", Ok("/tmp/s.jsonl"))]
+    #[case::localhost("file://localhost/tmp/s.jsonl", Ok("/tmp/s.jsonl"))]
+    #[case::remote_host("file://host/tmp/s.jsonl", Err(()))]
+    fn parses_file_arguments(#[case] file: &str, #[case] expected: std::result::Result<&str, ()>) {
+        assert_eq!(
+            file_argument(file).map_err(drop),
+            expected.map(PathBuf::from)
+        );
+    }
+
+    #[rstest]
+    fn files_outside_the_codexdir_need_force(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = dir.join("codex");
+        let id = "7f3a9c1d-3c4d-7e8f-9a0b-1c2d3e4f5a6b";
+        write_id_sessions(&codexdir, &[id]);
+        let outside = dir.join("elsewhere.jsonl");
+        fs::copy(codexdir.join("rollout-0.jsonl"), &outside).unwrap();
+        let inside = codexdir.join("rollout-0.jsonl");
+
+        assert_eq!(
+            session_from_file(&codexdir, inside.to_str().unwrap(), false)
+                .unwrap()
+                .0
+                .id,
+            id
+        );
+        let err = session_from_file(&codexdir, outside.to_str().unwrap(), false)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("--force"), "{err}");
+        let (session, branch) =
+            session_from_file(&codexdir, outside.to_str().unwrap(), true).unwrap();
+        assert_eq!(session.source_jsonl, outside);
+        assert_eq!(branch.as_deref(), Some("b0"));
+    }
+
+    #[cfg(unix)]
+    fn exit_status(code: i32) -> ExitStatus {
+        use std::os::unix::process::ExitStatusExt;
+        ExitStatus::from_raw(code << 8)
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    #[case(1, 2, 2)]
+    #[case(1, 30, 0)]
+    #[case(0, 2, 0)]
+    fn surfaces_codex_log_errors_after_quick_failures(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] code: i32,
+        #[case] secs: u64,
+        #[case] expected: usize,
+    ) {
+        let log = dir.join(CODEX_TUI_LOG);
+        fs::create_dir_all(log.parent().unwrap()).unwrap();
+        fs::write(
+            &log,
+            "2026-10-14T09:00:00Z  INFO codex_tui: starting\n\
+             2026-10-14T09:00:01Z ERROR codex_core::exec: sandbox denied write to /data\n\
+             2026-10-14T09:00:01Z  WARN codex_core: retrying\n\
+             2026-10-14T09:00:02Z ERROR codex_tui: exiting\n",
+        )
+        .unwrap();
+
+        let errors = quick_failure_errors(&log, exit_status(code), Duration::from_secs(secs));
+
+        assert_eq!(errors.len(), expected);
+        if expected > 0 {
+            assert!(errors[0].contains("sandbox denied"), "{errors:?}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    fn shows_only_the_last_codex_log_errors(#[from(temp_dir)] dir: PathBuf) {
+        let log = dir.join("codex-tui.log");
+        let lines: String = (0..20).map(|i| format!("ERROR line {i}\n")).collect();
+        fs::write(&log, lines).unwrap();
+
+        let errors = quick_failure_errors(&log, exit_status(1), Duration::ZERO);
+
+        assert_eq!(errors.len(), CODEX_LOG_ERROR_LINES);
+        assert_eq!(errors.last().unwrap(), "ERROR line 19");
+        let missing =
+            quick_failure_errors(&dir.join("missing.log"), exit_status(1), Duration::ZERO);
+        assert!(missing.is_empty());
+    }
+
+    #[rstest]
+    fn dry_run_shows_the_wrapped_command(#[from(temp_dir)] dir: PathBuf) {
+        let repo = dir.join("repo");
+        let codexdir = dir.join("codex");
+        fs::create_dir_all(&repo).unwrap();
+        fs::create_dir_all(codexdir.join("sessions")).unwrap();
+        let id = "0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b";
+        let line = serde_json::json!({
+            "payload": { "git": { "branch": "main" }, "cwd": repo, "id": id }
+        });
+        fs::write(
+            codexdir.join("sessions/rollout-a.jsonl"),
+            format!("{line}\n"),
+        )
+        .unwrap();
+        let output = dir.join("cmd.sh");
+        let argv = [
+            "amg",
+            "resume",
+            "main",
+            "--repo",
+            repo.to_str().unwrap(),
+            "--codexdir",
+            codexdir.to_str().unwrap(),
+            "--no-cache",
+            "--no-tmux",
+            "--dry-run",
+            "--output",
+            output.to_str().unwrap(),
+            "--wrap",
+            "docker exec -it 'dev box'",
+        ];
+        let args::Commands::ResumeBranch(resume) =
+            <args::Args as clap::Parser>::parse_from(argv).command
+        else {
+            panic!("expected the resume subcommand");
+        };
+
+        run_resume_branch(resume).unwrap();
+
+        let command = fs::read_to_string(&output).unwrap();
+        assert!(
+            command.starts_with("'docker' 'exec' '-it' 'dev box' 'codex' "),
+            "{command}"
+        );
+        assert!(
+            command.trim_end().ends_with(&format!("'resume' '{id}'")),
+            "{command}"
+        );
+    }
+
+    #[rstest]
+    #[case(false, "scratch")]
+    #[case(true, "repo")]
+    fn cd_into_repo_starts_codex_and_the_window_in_the_repo(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] cd_into_repo: bool,
+        #[case] start: &str,
+    ) {
+        let repo = dir.join("repo");
+        let scratch = dir.join("scratch");
+        let codexdir = dir.join("codex");
+        fs::create_dir_all(&repo).unwrap();
+        fs::create_dir_all(&scratch).unwrap();
+        fs::create_dir_all(codexdir.join("sessions")).unwrap();
+        let id = "0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b";
+        let line = serde_json::json!({
+            "payload": { "git": { "branch": "main" }, "cwd": scratch, "id": id }
+        });
+        fs::write(
+            codexdir.join("sessions/rollout-a.jsonl"),
+            format!("{line}\n"),
+        )
+        .unwrap();
+        let output = dir.join("cmd.sh");
+        let mut argv = vec![
+            "amg",
+            "resume",
+            "main",
+            "--repo",
+            repo.to_str().unwrap(),
+            "--codexdir",
+            codexdir.to_str().unwrap(),
+            "--no-cache",
+            "--tmux",
+            "always",
+            "--dry-run",
+            "--output",
+            output.to_str().unwrap(),
+        ];
+        if cd_into_repo {
+            argv.push("--cd-into-repo");
+        }
+        let args::Commands::ResumeBranch(resume) =
+            <args::Args as clap::Parser>::parse_from(argv).command
+        else {
+            panic!("expected the resume subcommand");
+        };
+
+        run_resume_branch(resume).unwrap();
+
+        let command = fs::read_to_string(&output).unwrap();
+        let start = dir.join(start);
+        let start = process::sh_quote_lossy(start.as_os_str());
+        assert!(command.contains(&format!("'-c' {start}")), "{command}");
+        let scratch = process::sh_quote_lossy(scratch.as_os_str());
+        assert!(
+            command.contains(&format!("'--add-dir' {scratch}")),
+            "{command}"
+        );
+        assert!(command.contains(&format!("'--cd' {start}")), "{command}");
+    }
+
+    #[test]
+    fn no_session_message_counts_prefix_exclusions() {
+        let stats = scan::ScanStats {
+            files_scanned: 3,
+            excluded_by_prefix: 2,
+            ..Default::default()
+        };
+        let msg = no_session_message(Path::new("/home/u/.codex"), "main", &stats);
+        assert!(
+            msg.ends_with("(2 matching session(s) excluded by --under)"),
+            "{msg}"
+        );
+    }
+
+    #[test]
+    fn no_session_message_names_the_tag_filter() {
+        let stats = scan::ScanStats {
+            files_scanned: 3,
+            excluded_by_tag: Some(("demo-prep".into(), 2)),
+            ..Default::default()
+        };
+        let msg = no_session_message(Path::new("/home/u/.codex"), "main", &stats);
+        assert!(
+            msg.ends_with("(2 matching session(s) not tagged \"demo-prep\")"),
+            "{msg}"
+        );
+    }
+
+    #[test]
+    fn no_session_message_counts_metadata_exclusions() {
+        let stats = scan::ScanStats {
+            files_scanned: 3,
+            excluded_by_metadata: 1,
+            ..Default::default()
+        };
+        let msg = no_session_message(Path::new("/home/u/.codex"), "main", &stats);
+        assert!(
+            msg.ends_with("(1 matching session(s) from another originator or model)"),
+            "{msg}"
+        );
+    }
+
+    #[test]
+    fn no_session_message_counts_ownership_skips() {
+        let stats = scan::ScanStats {
+            files_scanned: 3,
+            skipped_by_owner: 3,
+            ..Default::default()
+        };
+        let msg = no_session_message(Path::new("/home/u/.codex"), "main", &stats);
+        assert!(
+            msg.contains("3 file(s) owned by other users skipped"),
+            "{msg}"
+        );
+        assert!(msg.contains("--any-owner"), "{msg}");
+    }
+
+    #[test]
+    fn no_session_message_flags_codexdir_without_jsonl_files() {
+        let stats = scan::ScanStats {
+            files_scanned: 0,
+            sample_files: vec![
+                PathBuf::from("/home/u/.codex/log/codex-tui.log"),
+                PathBuf::from("/home/u/.codex/log/old/trace.txt"),
+            ],
+            ..Default::default()
+        };
+        let msg = no_session_message(Path::new("/home/u/.codex/log"), "main", &stats);
+        assert!(msg.contains("No .jsonl session files found under /home/u/.codex/log"));
+        assert!(msg.contains("`sessions/`"));
+        assert!(msg.contains("codex-tui.log, old/trace.txt"));
+    }
 }
@@ -0,0 +1,57 @@
+//! Writing to stdout.
+//!
+//! `println!` panics when stdout is a pipe whose reader went away (`amg alias list | head -1`
+//! once the listing outgrows the pipe buffer). Every write to stdout goes through [`line`], or
+//! through a locked stdout with errors propagated, instead. [`entry`](super::entry) then
+//! recognizes the failed write with [`is_broken_pipe`] and exits successfully without a
+//! message, like other well-behaved command-line tools.
+
+use std::fmt;
+use std::io::Write;
+
+use super::prelude::*;
+
+/// Writes `args` and a newline to stdout.
+///
+/// # Errors
+///
+/// Returns an error if stdout cannot be written, including [`io::ErrorKind::BrokenPipe`]
+/// when its reader went away.
+pub(super) fn line(args: fmt::Arguments<'_>) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    out.write_fmt(args)?;
+    out.write_all(b"\n")
+}
+
+/// Returns whether `err` comes from writing to a pipe whose reader went away, directly or
+/// through `serde_json`.
+pub(super) fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        let kind = match cause.downcast_ref::<serde_json::Error>() {
+            Some(json) => json.io_error_kind(),
+            None => cause.downcast_ref::<io::Error>().map(io::Error::kind),
+        };
+        kind == Some(io::ErrorKind::BrokenPipe)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn recognizes_a_broken_pipe_behind_context() {
+        let closed = anyhow::Error::new(io::Error::from(io::ErrorKind::BrokenPipe))
+            .context("failed to write the table");
+        let json = anyhow::Error::new(serde_json::Error::io(io::Error::from(
+            io::ErrorKind::BrokenPipe,
+        )));
+        let denied = anyhow::Error::new(io::Error::from(io::ErrorKind::PermissionDenied));
+
+        assert!(is_broken_pipe(&closed));
+        assert!(is_broken_pipe(&json));
+        assert!(!is_broken_pipe(&denied));
+        assert!(!is_broken_pipe(&anyhow!("no session")));
+    }
+}
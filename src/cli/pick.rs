@@ -0,0 +1,234 @@
+//! Interactive session picker.
+//!
+//! With `resume --pick`, every session matching the branch is offered for selection instead of
+//! resuming the first one. If `fzf` is on `$PATH` (and `--no-fzf` is not given) its fuzzy UI
+//! is used; otherwise a numbered list is printed to stderr and a number is read from stdin.
+//! Cancelling either kind of prompt ends amg without resuming anything.
+//!
+//! With `--pick-tmux` inside tmux, the candidates are offered in a `tmux display-menu`
+//! instead, labelled by [`menu_label`].
+
+use super::prelude::*;
+use super::process::{self, FzfOutcome};
+use super::scan::Session;
+use super::util;
+
+/// Result of offering candidates to the user.
+#[derive(Debug)]
+pub(super) enum Picked {
+    /// The user chose this session.
+    Session(Box<Session>),
+    /// There was nothing to choose from.
+    Empty,
+    /// The user aborted the selection.
+    Cancelled,
+}
+
+/// Lets the user choose one of `sessions`.
+///
+/// A single candidate is returned without prompting.
+///
+/// # Arguments
+///
+/// * `sessions` - The candidates, in scan order
+/// * `use_fzf` - Whether to use fzf when it is available
+///
+/// # Errors
+///
+/// Returns an error if fzf fails, stdin cannot be read, or the answer is not a valid choice.
+pub(super) fn pick_session(mut sessions: Vec<Session>, use_fzf: bool) -> Result<Picked> {
+    match sessions.len() {
+        0 => return Ok(Picked::Empty),
+        1 => {
+            return Ok(sessions
+                .pop()
+                .map_or(Picked::Empty, |session| Picked::Session(Box::new(session))));
+        }
+        _ => {}
+    }
+    let now = SystemTime::now();
+    let lines: Vec<String> = sessions
+        .iter()
+        .map(|session| candidate_line(session, now))
+        .collect();
+
+    let index = if use_fzf && process::on_path("fzf") {
+        match process::run_fzf(&lines)? {
+            FzfOutcome::Selected(line) => selected_index(&sessions, &line)
+                .with_context(|| format!("fzf returned an unknown line: {line:?}"))?,
+            FzfOutcome::Cancelled => return Ok(Picked::Cancelled),
+        }
+    } else {
+        let Some(index) = prompt(&lines)? else {
+            return Ok(Picked::Cancelled);
+        };
+        index
+    };
+    Ok(Picked::Session(Box::new(sessions.swap_remove(index))))
+}
+
+/// Formats a candidate as `<id>\t<age>\t<cwd>\t<session file>`.
+///
+/// Tabs and newlines inside fields are replaced with spaces so the line stays parseable.
+fn candidate_line(session: &Session, now: SystemTime) -> String {
+    [
+        session.id.clone(),
+        session_age(session, now),
+        session.cwd.to_string_lossy().into_owned(),
+        session.source_jsonl.to_string_lossy().into_owned(),
+    ]
+    .map(|field| field.replace(['\t', '\n', '\r'], " "))
+    .join("\t")
+}
+
+/// Formats a candidate's `tmux display-menu` label as `<branch>  <age>  <cwd>`.
+pub(super) fn menu_label(branch: &str, session: &Session, now: SystemTime) -> String {
+    format!(
+        "{branch}  {}  {}",
+        session_age(session, now),
+        util::human_path(&session.cwd)
+    )
+    .replace(['\t', '\n', '\r'], " ")
+}
+
+/// Renders an age with its largest whole unit (`45s`, `12m`, `3h`, `2d`).
+pub(super) fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3_600 => format!("{}m", secs / 60),
+        3_600..86_400 => format!("{}h", secs / 3_600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+/// Renders how long before `now` the session file was last modified (`?` if unknown).
+pub(super) fn session_age(session: &Session, now: SystemTime) -> String {
+    session
+        .modified
+        .and_then(|m| now.duration_since(m).ok())
+        .map_or_else(|| "?".to_owned(), format_age)
+}
+
+/// Maps a selected line back to its candidate by the session id in its first field.
+fn selected_index(sessions: &[Session], line: &str) -> Option<usize> {
+    let id = line.split('\t').next()?;
+    sessions.iter().position(|session| session.id == id)
+}
+
+/// Prints a numbered list to stderr and reads the choice from stdin.
+///
+/// Returns `None` if the user enters nothing or stdin is closed.
+fn prompt(lines: &[String]) -> Result<Option<usize>> {
+    for (n, line) in lines.iter().enumerate() {
+        let shown: Vec<&str> = line.split('\t').skip(1).collect();
+        eprintln!("{:>3}) {}", n + 1, shown.join("  "));
+    }
+    eprint!("pick a session [1-{}]: ", lines.len());
+    let mut answer = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .context("failed to read the selection")?;
+    parse_choice(&answer, lines.len())
+}
+
+fn parse_choice(answer: &str, count: usize) -> Result<Option<usize>> {
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Ok(None);
+    }
+    match answer.parse::<usize>() {
+        Ok(n) if (1..=count).contains(&n) => Ok(Some(n - 1)),
+        _ => bail!("invalid selection {answer:?}; expected a number from 1 to {count}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::matched_session;
+    use rstest::rstest;
+
+    fn session(id: &str, cwd: &str) -> Session {
+        Session {
+            cwd: PathBuf::from(cwd),
+            ..matched_session(id)
+        }
+    }
+
+    #[rstest]
+    #[case(0, "0s")]
+    #[case(59, "59s")]
+    #[case(60, "1m")]
+    #[case(3_599, "59m")]
+    #[case(7_200, "2h")]
+    #[case(3 * 86_400 + 5, "3d")]
+    fn formats_ages(#[case] secs: u64, #[case] expected: &str) {
+        assert_eq!(format_age(Duration::from_secs(secs)), expected);
+    }
+
+    #[test]
+    fn formats_candidate_lines() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(10_000);
+        let session = Session {
+            modified: Some(now - Duration::from_secs(7_200)),
+            ..session("a1", "/work/my\tproj")
+        };
+
+        let line = candidate_line(&session, now);
+
+        assert_eq!(line, "a1\t2h\t/work/my proj\t/codex/rollout-a1.jsonl");
+    }
+
+    #[test]
+    fn formats_menu_labels() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(10_000);
+        let session = Session {
+            modified: Some(now - Duration::from_secs(120)),
+            ..session("a1", "/work/my\nproj")
+        };
+
+        assert_eq!(menu_label("main", &session, now), "main  2m  /work/my proj");
+    }
+
+    #[test]
+    fn unknown_age_is_a_question_mark() {
+        let line = candidate_line(&session("a1", "/w"), SystemTime::now());
+
+        assert_eq!(line.split('\t').nth(1), Some("?"));
+    }
+
+    #[test]
+    fn maps_selected_lines_back_to_sessions() {
+        let sessions = [session("a1", "/a"), session("b2", "/b")];
+
+        assert_eq!(selected_index(&sessions, "b2\t1h\t/b\t/f"), Some(1));
+        assert_eq!(selected_index(&sessions, "zz\t1h\t/b\t/f"), None);
+    }
+
+    #[rstest]
+    #[case("2\n", Ok(Some(1)))]
+    #[case("  1 ", Ok(Some(0)))]
+    #[case("\n", Ok(None))]
+    #[case("", Ok(None))]
+    #[case("4", Err(()))]
+    #[case("0", Err(()))]
+    #[case("x", Err(()))]
+    fn parses_prompt_answers(#[case] answer: &str, #[case] expected: Result<Option<usize>, ()>) {
+        assert_eq!(parse_choice(answer, 3).map_err(|_| ()), expected);
+    }
+
+    #[rstest]
+    #[case(Vec::new())]
+    #[case(vec![session("a1", "/a")])]
+    fn does_not_prompt_without_a_choice(#[case] sessions: Vec<Session>) {
+        let expected_empty = sessions.is_empty();
+
+        match pick_session(sessions, false).unwrap() {
+            Picked::Empty => assert!(expected_empty),
+            Picked::Session(session) => assert_eq!(session.id, "a1"),
+            Picked::Cancelled => panic!("nothing should be cancelled"),
+        }
+    }
+}
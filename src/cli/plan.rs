@@ -0,0 +1,392 @@
+//! Resume plans (`--plan-out` and `exec-plan`).
+//!
+//! `amg resume BRANCH --plan-out plan.json` resolves everything a resume would do (the
+//! session, the directory codex starts in, the exact program and arguments, and whether it
+//! runs inline or in a tmux window) and writes it to a JSON file instead of acting.
+//! `amg exec-plan plan.json` later runs that plan verbatim, so what runs can be reviewed
+//! before it runs.
+//!
+//! A plan records the session file's size and modification time. Before executing, the
+//! session file, its cwd, and the start directory must still exist and the file must be
+//! unchanged; otherwise the plan is stale and refused with every difference listed.
+
+use serde::{Deserialize, Serialize};
+
+use super::args::ExecPlanArgs;
+use super::datetime::UtcDateTime;
+use super::prelude::*;
+use super::scan::Session;
+use super::{launch, process, state, util};
+
+/// Format version written to new plans; other versions are refused.
+const PLAN_VERSION: u32 = 1;
+
+/// A resolved resume, as written by `--plan-out`.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct Plan {
+    /// The plan format version ([`PLAN_VERSION`]).
+    version: u32,
+    /// The session to resume.
+    session: PlannedSession,
+    /// How codex is launched.
+    launch: PlannedLaunch,
+    /// Create the tmux window in the background (`--tmux-detach`).
+    #[serde(default)]
+    detach: bool,
+    /// The directory codex starts in.
+    start_dir: PathBuf,
+    /// The program to run.
+    program: String,
+    /// Its arguments.
+    args: Vec<String>,
+}
+
+/// What a new plan resumes, and the command that resumes it.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct PlannedResume<'a> {
+    /// The matched session.
+    pub(super) session: &'a Session,
+    /// The branch the session was looked up by.
+    pub(super) branch: &'a str,
+    /// The directory codex starts in.
+    pub(super) start_dir: &'a Path,
+    /// The codex command, wrappers included.
+    pub(super) cmd: &'a process::Cmd,
+}
+
+/// The session a plan resumes, with what is needed to tell whether it changed.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct PlannedSession {
+    id: String,
+    branch: String,
+    /// The branch the session file records, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recorded_branch: Option<String>,
+    cwd: PathBuf,
+    file: PathBuf,
+    size: u64,
+    /// RFC 3339 modification time, for people reading the plan.
+    mtime: String,
+    /// Modification time in nanoseconds since the Unix epoch, for the staleness check.
+    mtime_ns: u64,
+}
+
+/// Where a planned codex runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum PlannedLaunch {
+    /// In the foreground, in the terminal running `exec-plan`.
+    Inline,
+    /// In a new window of the current tmux session.
+    Tmux,
+    /// In a background tmux window (`--tmux always` outside tmux).
+    TmuxDetached,
+}
+
+impl Plan {
+    /// Builds the plan for `resume`, run as `target` decides (in a background window with
+    /// `detach`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session file cannot be inspected, or if a path or argument is
+    /// not valid UTF-8 (plans are JSON).
+    pub(super) fn new(
+        resume: PlannedResume<'_>,
+        target: util::TmuxTarget,
+        detach: bool,
+    ) -> Result<Self> {
+        let PlannedResume {
+            session,
+            branch,
+            start_dir,
+            cmd,
+        } = resume;
+        let (size, modified) = file_state(&session.source_jsonl)?;
+        let utf8 = |arg: &OsStr| {
+            arg.to_str()
+                .map(str::to_owned)
+                .with_context(|| format!("{} is not valid UTF-8", arg.to_string_lossy()))
+        };
+        Ok(Self {
+            version: PLAN_VERSION,
+            session: PlannedSession {
+                id: session.id.clone(),
+                branch: branch.to_owned(),
+                recorded_branch: session.branch.clone(),
+                cwd: session.cwd.clone(),
+                file: session.source_jsonl.clone(),
+                size,
+                mtime: UtcDateTime::from_system_time(modified).rfc3339(),
+                mtime_ns: unix_nanos(modified),
+            },
+            launch: match target {
+                util::TmuxTarget::Inline => PlannedLaunch::Inline,
+                util::TmuxTarget::CurrentSession => PlannedLaunch::Tmux,
+                util::TmuxTarget::Detached => PlannedLaunch::TmuxDetached,
+            },
+            detach,
+            start_dir: start_dir.to_owned(),
+            program: utf8(&cmd.program)?,
+            args: cmd
+                .args
+                .iter()
+                .map(|arg| utf8(arg))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Writes the plan to `path` as pretty-printed JSON, replacing the file atomically.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub(super) fn write(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        util::write_atomic(path, &(content + "\n"))
+            .with_context(|| format!("failed to write plan {}", util::human_path(path)))
+    }
+
+    /// Reads a plan written by [`Plan::write`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed, or has another format version.
+    fn read(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read plan {}", util::human_path(path)))?;
+        let plan: Self = serde_json::from_str(&content)
+            .with_context(|| format!("failed to parse plan {}", util::human_path(path)))?;
+        if plan.version != PLAN_VERSION {
+            bail!(
+                "plan {} has format version {}, but this amg reads version {PLAN_VERSION}",
+                util::human_path(path),
+                plan.version
+            );
+        }
+        Ok(plan)
+    }
+
+    /// Lists what changed since the plan was written, in the order it is checked.
+    fn staleness(&self) -> Vec<String> {
+        let session = &self.session;
+        let mut changes = Vec::new();
+        match file_state(&session.file) {
+            Err(_) => changes.push(format!(
+                "session file {} no longer exists or cannot be read",
+                util::human_path(&session.file)
+            )),
+            Ok((size, modified)) => {
+                if size != session.size {
+                    changes.push(format!(
+                        "session file {} changed size: {} bytes when planned, {size} now",
+                        util::human_path(&session.file),
+                        session.size
+                    ));
+                }
+                if unix_nanos(modified) != session.mtime_ns {
+                    changes.push(format!(
+                        "session file {} was modified: {} when planned, {} now",
+                        util::human_path(&session.file),
+                        session.mtime,
+                        UtcDateTime::from_system_time(modified).rfc3339()
+                    ));
+                }
+            }
+        }
+        for (what, dir) in [
+            ("session cwd", &session.cwd),
+            ("start dir", &self.start_dir),
+        ] {
+            if !dir.is_dir() {
+                changes.push(format!("{what} {} no longer exists", util::human_path(dir)));
+            }
+        }
+        changes
+    }
+
+    fn cmd(&self) -> process::Cmd {
+        process::Cmd {
+            program: OsString::from(&self.program),
+            args: self.args.iter().map(OsString::from).collect(),
+        }
+    }
+}
+
+/// Handles the `exec-plan` subcommand: re-validates the plan and runs it.
+///
+/// # Returns
+///
+/// Returns codex's exit code when it runs inline, or success once the tmux window exists.
+///
+/// # Errors
+///
+/// Returns an error if the plan cannot be read, is stale, or codex (or tmux) cannot be run.
+pub(super) fn run_exec_plan(args: ExecPlanArgs) -> Result<ExitCode> {
+    let plan = Plan::read(&args.plan)?;
+    let changes = plan.staleness();
+    if !changes.is_empty() {
+        bail!(
+            "plan {} is stale:\n  {}",
+            util::human_path(&args.plan),
+            changes.join("\n  ")
+        );
+    }
+
+    let session = &plan.session;
+    info!(id = %session.id, branch = %session.branch, launch = ?plan.launch, "executing plan");
+    let cmd = plan.cmd();
+    let window = process::WindowOptions {
+        name: None,
+        detach: plan.detach,
+    };
+    let tmux = match plan.launch {
+        PlannedLaunch::Inline => {
+            let record = |pid| {
+                launch::record_launch(
+                    &session.id,
+                    &session.branch,
+                    session.recorded_branch.as_deref(),
+                    state::LaunchTarget::Process { pid },
+                );
+            };
+            let exit = process::run_in_dir(&plan.start_dir, &cmd, None, record)
+                .context("failed to run codex")?;
+            launch::forget_launch(&session.id);
+            return Ok(match exit {
+                process::Exit::Status(status) => process::exit_code(status),
+                process::Exit::TimedOut => ExitCode::from(EXIT_TIMED_OUT),
+            });
+        }
+        PlannedLaunch::Tmux => process::tmux_new_window_cmd(&plan.start_dir, window, &cmd),
+        PlannedLaunch::TmuxDetached => process::tmux_detached_window_cmd(
+            &plan.start_dir,
+            window,
+            &cmd,
+            process::tmux_server_running(),
+        ),
+    };
+    if let Some(window_id) = process::run_tmux(&tmux)? {
+        launch::record_launch(
+            &session.id,
+            &session.branch,
+            session.recorded_branch.as_deref(),
+            state::LaunchTarget::TmuxWindow { window_id },
+        );
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Returns the size and modification time of `file`.
+fn file_state(file: &Path) -> io::Result<(u64, SystemTime)> {
+    let meta = fs::metadata(file)?;
+    Ok((meta.len(), meta.modified()?))
+}
+
+/// Returns `time` in nanoseconds since the Unix epoch (0 before it, saturating far after).
+fn unix_nanos(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| {
+            u64::try_from(since.as_nanos()).unwrap_or(u64::MAX)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::{matched_session, temp_dir};
+    use rstest::rstest;
+
+    fn session(dir: &Path) -> Session {
+        let file = dir.join("rollout-a.jsonl");
+        fs::write(&file, "{}\n").unwrap();
+        Session {
+            cwd: dir.to_owned(),
+            source_jsonl: file,
+            ..matched_session("0199")
+        }
+    }
+
+    fn plan(dir: &Path) -> Plan {
+        let cmd = process::Cmd {
+            program: "codex".into(),
+            args: vec!["resume".into(), "0199".into()],
+        };
+        let session = session(dir);
+        let resume = PlannedResume {
+            session: &session,
+            branch: "main",
+            start_dir: dir,
+            cmd: &cmd,
+        };
+        Plan::new(resume, util::TmuxTarget::Detached, false).unwrap()
+    }
+
+    #[rstest]
+    fn round_trips_through_the_plan_file(#[from(temp_dir)] dir: PathBuf) {
+        let plan = plan(&dir);
+        let path = dir.join("plan.json");
+
+        plan.write(&path).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["launch"], "tmux-detached");
+        assert_eq!(json["session"]["size"], 3);
+        assert_eq!(json["args"], serde_json::json!(["resume", "0199"]));
+        assert_eq!(Plan::read(&path).unwrap(), plan);
+        assert!(plan.staleness().is_empty());
+        assert_eq!(plan.cmd().args, ["resume", "0199"]);
+    }
+
+    #[rstest]
+    fn refuses_other_format_versions(#[from(temp_dir)] dir: PathBuf) {
+        let path = dir.join("plan.json");
+        let mut plan = plan(&dir);
+        plan.version = PLAN_VERSION + 1;
+        plan.write(&path).unwrap();
+
+        let err = Plan::read(&path).unwrap_err().to_string();
+
+        assert!(err.contains("format version 2"), "{err}");
+    }
+
+    #[rstest]
+    fn reports_a_changed_session_file(#[from(temp_dir)] dir: PathBuf) {
+        let mut plan = plan(&dir);
+        plan.session.mtime_ns -= 1;
+        fs::write(&plan.session.file, "{}\n{}\n").unwrap();
+
+        let changes = plan.staleness();
+
+        assert_eq!(changes.len(), 2, "{changes:?}");
+        assert!(
+            changes[0].contains("3 bytes when planned, 6 now"),
+            "{changes:?}"
+        );
+        assert!(changes[1].contains("was modified"), "{changes:?}");
+    }
+
+    #[rstest]
+    fn reports_missing_files_and_directories(#[from(temp_dir)] dir: PathBuf) {
+        let mut plan = plan(&dir);
+        fs::remove_file(&plan.session.file).unwrap();
+        plan.session.cwd = dir.join("gone");
+
+        let changes = plan.staleness();
+
+        assert_eq!(
+            changes,
+            [
+                format!(
+                    "session file {} no longer exists or cannot be read",
+                    plan.session.file.display()
+                ),
+                format!(
+                    "session cwd {} no longer exists",
+                    dir.join("gone").display()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! The state policy: whether amg may use its files under the home directory.
+//!
+//! By default amg reads its config files, keeps a last-hit cache and a session index, stores
+//! aliases, tags, launches, and remembered options in its state file, and grants a few
+//! home directories (`~/.cargo`, `~/.rustup`, ...) to the sandbox. With `--portable` (or
+//! `AMG_PORTABLE=1`), as when amg runs from a USB stick on a locked-down machine, it does none
+//! of that and uses only its command-line flags and environment variables: nothing under
+//! `$HOME` is read or written beyond the directories passed in.
+//!
+//! The policy is resolved once in [`entry`](super::entry) and consulted by every module that
+//! resolves a default home path:
+//!
+//! * The global and per-repo config files are not loaded, and `amg init` and `amg config`
+//!   refuse to write them
+//! * The codexdir must be given (`--codexdir` or `CODEX_CODEXDIR`) instead of defaulting to
+//!   `$HOME/.codex`
+//! * The last-hit cache and the session index are neither read nor written
+//! * The state file is not used: aliases and remembered options are not applied, launches are
+//!   not recorded, and the commands that work on the state (`alias`, `tag`, `kill`,
+//!   `remembered`, `--tag`) fail
+//! * No home directories are granted to the sandbox, as with `--no-home-dirs`
+//! * `--codexdir-readonly` has no scratch Codex home to copy into, so it fails
+
+use std::sync::OnceLock;
+
+use super::prelude::*;
+
+/// Where amg may keep its own files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) enum StatePolicy {
+    /// Config, caches, and state live under the home directory (the default).
+    #[default]
+    Home,
+    /// Nothing under the home directory is used (`--portable`).
+    Portable,
+}
+
+/// The policy set by [`init`].
+static POLICY: OnceLock<StatePolicy> = OnceLock::new();
+
+/// Explains where a portable run takes its input from, for error messages.
+pub(super) const PORTABLE_HINT: &str =
+    "portable mode only uses command-line flags and environment variables";
+
+/// Sets the policy for the rest of the process; a second call is ignored.
+pub(super) fn init(policy: StatePolicy) {
+    if POLICY.set(policy).is_err() {
+        debug!("the state policy was already set");
+    }
+    if policy == StatePolicy::Portable {
+        debug!("portable mode: no config, cache, index, or state files are used");
+    }
+}
+
+/// Returns the policy set by [`init`], or [`StatePolicy::Home`] when it was never set (as in
+/// unit tests).
+pub(super) fn current() -> StatePolicy {
+    POLICY.get().copied().unwrap_or_default()
+}
+
+/// Returns whether amg runs in portable mode.
+pub(super) fn is_portable() -> bool {
+    current() == StatePolicy::Portable
+}
+
+/// Fails in portable mode, naming `what` would have needed a file under the home directory.
+///
+/// # Errors
+///
+/// Returns an error if the policy is [`StatePolicy::Portable`].
+pub(super) fn require_home_files(what: &str) -> Result<()> {
+    match current() {
+        StatePolicy::Home => Ok(()),
+        StatePolicy::Portable => bail!("{what} is not available with --portable ({PORTABLE_HINT})"),
+    }
+}
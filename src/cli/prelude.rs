@@ -17,22 +17,41 @@ pub(super) use std::ffi::{OsStr, OsString};
 pub(super) use std::path::{Path, PathBuf};
 
 // Standard library - Process
-pub(super) use std::process::{Command, ExitCode, ExitStatus};
+pub(super) use std::process::Command;
+#[cfg(feature = "cli")]
+pub(super) use std::process::{ExitCode, ExitStatus, Stdio};
 
 // Standard library - File system
 pub(super) use std::fs;
 
 // Standard library - IO
-pub(super) use std::io::{self, BufRead};
+pub(super) use std::io;
+#[cfg(feature = "cli")]
+pub(super) use std::io::{BufRead, IsTerminal};
+
+// Standard library - Time
+#[cfg(feature = "cli")]
+pub(super) use std::time::Duration;
+pub(super) use std::time::SystemTime;
 
 // Standard library - Collections
-pub(super) use std::cmp::Reverse;
 pub(super) use std::collections::BinaryHeap;
 
 // Logging
-pub(super) use tracing::{debug, error, info};
+#[cfg(feature = "cli")]
+pub(super) use tracing::error;
+pub(super) use tracing::{debug, info, warn};
 
 // Re-export internal constants for convenient access across modules.
+#[cfg(feature = "cli")]
+pub(super) use super::constants::{
+    AMG_DIR, CONFIG_FILE, DEFAULT_EXIT_CODE_OFFSET, ENV_AMG_CONFIG, ENV_AMG_STATE_FILE,
+    ENV_CODEX_CODEXDIR, ENV_CODEX_HOME, ENV_PATH, ENV_TMUX, ENV_XDG_CACHE_HOME,
+    ENV_XDG_CONFIG_HOME, ENV_XDG_DATA_HOME, EXIT_CANCELLED, EXIT_FAILURE, EXIT_NO_SESSION,
+    EXIT_TIMED_OUT, EXIT_TRUNCATED, LAST_HIT_CACHE_FILE, MAX_LISTED_ID_MATCHES, MIN_ID_PREFIX_LEN,
+    REPO_CONFIG_FILE, SESSION_INDEX_FILE, SESSIONS_DIR, STATE_FILE, TMUX_DETACHED_SESSION,
+};
 pub(super) use super::constants::{
-    DOT_CODEX_DIR, DOT_GIT, ENV_HOME, ENV_TMUX, EXTRA_SANDBOX_DIRS, HOME_SANDBOX_DIRS,
+    AMGIGNORE_FILE, CODEX_TUI_LOG, DEFAULT_MODEL, DOT_CODEX_DIR, DOT_GIT, ENV_HOME,
+    EXTRA_SANDBOX_DIRS, HOME_SANDBOX_DIRS, MAX_REPO_DISCOVERY_DEPTH,
 };
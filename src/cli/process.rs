@@ -3,7 +3,24 @@
 //! This module provides functionality for executing commands, either directly or through
 //! tmux. It handles command construction, shell quoting, and process management.
 
+#[cfg(feature = "cli")]
+use std::io::{Read, Write};
+#[cfg(feature = "cli")]
+use std::process::Child;
+#[cfg(feature = "cli")]
+use std::sync::{Arc, Mutex, PoisonError};
+#[cfg(feature = "cli")]
+use std::thread::{self, JoinHandle};
+#[cfg(feature = "cli")]
+use std::time::Instant;
+
 use super::prelude::*;
+#[cfg(feature = "cli")]
+use super::util;
+
+/// Maximum number of bytes of a child's stderr kept by [`run_in_dir_capturing_stderr`].
+#[cfg(feature = "cli")]
+const MAX_CAPTURED_STDERR: usize = 64 * 1024;
 
 /// Represents a command to be executed.
 ///
@@ -26,15 +43,106 @@ impl Cmd {
     /// # Returns
     ///
     /// Returns a [`String`] containing the shell-quoted command.
+    #[cfg(feature = "cli")]
     pub(super) fn as_shell_string(&self) -> String {
+        self.render(sh_quote_lossy)
+    }
+
+    /// Converts the command to a shell string with the home directory collapsed to `~`.
+    ///
+    /// Arguments that are `home` or a path beneath it are rendered as an unquoted `~`
+    /// followed by the quoted remainder, so the string stays valid for the shell. Only
+    /// whole path components are matched (see [`util::display_path`](super::util::display_path)).
+    ///
+    /// # Arguments
+    ///
+    /// * `home` - The home directory to redact
+    #[cfg(feature = "cli")]
+    pub(super) fn as_shell_string_redacted(&self, home: &Path) -> String {
+        self.render(|arg| redacted_quote(arg, home))
+    }
+
+    /// Converts the command to a shell string that stays readable in a terminal `width`
+    /// columns wide.
+    ///
+    /// A command that fits on one line is rendered like [`Cmd::as_shell_string`]. Otherwise
+    /// the program is printed on the first line and each argument, or option and value pair
+    /// (`--add-dir <path>`, `--config <kv>`), on its own indented continuation line ending in
+    /// a backslash, so the output can still be pasted into a shell.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The terminal width in columns
+    /// * `home` - A home directory to collapse to `~`, as in [`Cmd::as_shell_string_redacted`]
+    #[cfg(feature = "cli")]
+    pub(super) fn as_pretty_shell_string(&self, width: usize, home: Option<&Path>) -> String {
+        let quote = |arg: &OsStr| match home {
+            Some(home) => redacted_quote(arg, home),
+            None => sh_quote_lossy(arg),
+        };
+        let one_line = self.render(quote);
+        if one_line.chars().count() <= width {
+            return one_line;
+        }
+        let mut lines = vec![quote(&self.program)];
+        let mut args = self.args.iter().peekable();
+        while let Some(arg) = args.next() {
+            let mut line = format!("    {}", quote(arg));
+            if is_option(arg)
+                && let Some(value) = args.next_if(|value| !is_option(value))
+            {
+                line.push(' ');
+                line.push_str(&quote(value));
+            }
+            lines.push(line);
+        }
+        lines.join(" \\\n")
+    }
+
+    /// Prefixes the command with a wrapper argv, e.g. `docker exec -it devbox`.
+    ///
+    /// The wrapper's first word becomes the program and the original program and arguments
+    /// follow the wrapper's own arguments. An empty wrapper leaves the command unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `wrapper` - The wrapper program and its arguments
+    #[cfg(feature = "cli")]
+    pub(super) fn wrapped(self, wrapper: &[String]) -> Self {
+        let Some((program, args)) = wrapper.split_first() else {
+            return self;
+        };
+        Self {
+            program: program.into(),
+            args: args
+                .iter()
+                .map(OsString::from)
+                .chain(std::iter::once(self.program))
+                .chain(self.args)
+                .collect(),
+        }
+    }
+
+    #[cfg(feature = "cli")]
+    fn render(&self, quote: impl Fn(&OsStr) -> String) -> String {
         std::iter::once(self.program.as_os_str())
             .chain(self.args.iter().map(OsString::as_os_str))
-            .map(sh_quote_lossy)
+            .map(quote)
             .collect::<Vec<_>>()
             .join(" ")
     }
 }
 
+/// How a new tmux window is created.
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct WindowOptions<'a> {
+    /// Name for the new window (`tmux new-window -n`).
+    pub(super) name: Option<&'a str>,
+    /// Create the window in the background instead of switching to it (`tmux new-window -d`).
+    pub(super) detach: bool,
+}
+
 /// Executes a command in a new tmux window.
 ///
 /// Creates a new tmux window with the specified working directory and executes the command
@@ -43,66 +151,692 @@ impl Cmd {
 /// # Arguments
 ///
 /// * `start_dir` - The working directory for the new tmux window
+/// * `window` - The window name and whether to create it detached
 /// * `cmd` - The command to execute
 ///
 /// # Returns
 ///
-/// Returns [`Result<()>`] indicating success or failure.
+/// Returns [`Result<Option<String>>`] containing the new window's id (such as `@12`) when tmux
+/// reported one.
 ///
 /// # Errors
 ///
 /// Returns an error if:
 /// * The `tmux` command cannot be executed
 /// * The tmux command fails (non-zero exit status)
-pub(super) fn run_tmux_new_window(start_dir: &Path, cmd: &Cmd) -> Result<()> {
+#[cfg(feature = "cli")]
+pub(super) fn run_tmux_new_window(
+    start_dir: &Path,
+    window: WindowOptions<'_>,
+    cmd: &Cmd,
+) -> Result<Option<String>> {
     debug!(
         program = ?cmd.program,
         args = ?cmd.args,
-        start_dir = %start_dir.display(),
+        start_dir = %util::human_path(start_dir),
+        window_name = ?window.name,
+        detach = window.detach,
         "spawning tmux new-window"
     );
-    let status = Command::new("tmux")
-        .arg("new-window")
-        .arg("-c")
-        .arg(start_dir)
-        .arg(&cmd.program)
-        .args(&cmd.args)
-        .status()
-        .context("failed to launch tmux new-window")?;
+    run_tmux(&tmux_new_window_cmd(start_dir, window, cmd))
+}
+
+/// Runs a tmux command built by [`tmux_new_window_cmd`] or [`tmux_detached_window_cmd`].
+///
+/// # Returns
+///
+/// Returns the new window's id when tmux reported one.
+///
+/// # Errors
+///
+/// Returns an error if tmux cannot be executed or fails.
+#[cfg(feature = "cli")]
+pub(super) fn run_tmux(tmux: &Cmd) -> Result<Option<String>> {
+    let output = Command::new(&tmux.program)
+        .args(&tmux.args)
+        .stderr(Stdio::inherit())
+        .output()
+        .context("failed to launch tmux")?;
 
-    if status.success() {
-        Ok(())
+    if output.status.success() {
+        Ok(parse_window_id(&String::from_utf8_lossy(&output.stdout)))
     } else {
-        bail!("tmux exited with status {status}");
+        bail!("tmux exited with status {}", output.status);
     }
 }
 
 /// Creates a command that would execute in a new tmux window.
 ///
-/// This is used for dry-run mode to show what command would be executed.
+/// This is used both for running and for dry-run mode to show what would be executed. The
+/// command prints the new window's id (`-P -F '#{window_id}'`) so it can be tracked.
 ///
 /// # Arguments
 ///
 /// * `start_dir` - The working directory for the new tmux window
+/// * `window` - The window name and whether to create it detached
 /// * `cmd` - The command to wrap
 ///
 /// # Returns
 ///
 /// Returns a [`Cmd`] representing the tmux command that would be executed.
-pub(super) fn tmux_new_window_cmd(start_dir: &Path, cmd: &Cmd) -> Cmd {
+#[cfg(feature = "cli")]
+pub(super) fn tmux_new_window_cmd(start_dir: &Path, window: WindowOptions<'_>, cmd: &Cmd) -> Cmd {
+    new_window_cmd(start_dir, window, cmd, true)
+}
+
+/// Creates the `new-window` command a `tmux display-menu` entry runs: like
+/// [`tmux_new_window_cmd`], but without printing the window id, which tmux would show in the
+/// client that chose the entry.
+#[cfg(feature = "cli")]
+pub(super) fn tmux_menu_window_cmd(start_dir: &Path, window: WindowOptions<'_>, cmd: &Cmd) -> Cmd {
+    new_window_cmd(start_dir, window, cmd, false)
+}
+
+#[cfg(feature = "cli")]
+fn new_window_cmd(start_dir: &Path, window: WindowOptions<'_>, cmd: &Cmd, print_id: bool) -> Cmd {
+    let mut args: Vec<OsString> = vec!["new-window".into()];
+    if window.detach {
+        args.push("-d".into());
+    }
+    if print_id {
+        args.extend(["-P".into(), "-F".into(), "#{window_id}".into()]);
+    }
+    if let Some(name) = window.name {
+        args.extend(["-n".into(), name.into()]);
+    }
+    args.extend(["-c".into(), start_dir.as_os_str().to_owned()]);
+    args.push(cmd.program.clone());
+    args.extend(cmd.args.iter().cloned());
+    Cmd {
+        program: "tmux".into(),
+        args,
+    }
+}
+
+/// The matched session's data set on its tmux window as user options, so a status line can
+/// show it (`#{@amg_session_id}`).
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy)]
+pub(super) struct WindowAnnotations<'a> {
+    /// Set as `@amg_session_id`.
+    pub(super) session_id: &'a str,
+    /// Set as `@amg_branch`.
+    pub(super) branch: &'a str,
+    /// Set as `@amg_cwd`.
+    pub(super) cwd: &'a Path,
+}
+
+/// Creates the command setting `annotations` as user options on the window `window_id`: one
+/// tmux invocation running a `set-option -w` per option.
+#[cfg(feature = "cli")]
+pub(super) fn tmux_annotate_cmd(window_id: &str, annotations: WindowAnnotations<'_>) -> Cmd {
+    let options = [
+        ("@amg_session_id", OsStr::new(annotations.session_id)),
+        ("@amg_branch", OsStr::new(annotations.branch)),
+        ("@amg_cwd", annotations.cwd.as_os_str()),
+    ];
+    let mut args: Vec<OsString> = Vec::new();
+    for (name, value) in options {
+        if !args.is_empty() {
+            args.push(";".into());
+        }
+        args.extend([
+            "set-option".into(),
+            "-w".into(),
+            "-t".into(),
+            window_id.into(),
+            name.into(),
+            value.to_owned(),
+        ]);
+    }
+    Cmd {
+        program: "tmux".into(),
+        args,
+    }
+}
+
+/// Sets `annotations` on the window `window_id` (see [`tmux_annotate_cmd`]).
+///
+/// Failures are logged as warnings: the window is already open, and the options only feed
+/// status lines.
+#[cfg(feature = "cli")]
+pub(super) fn annotate_window(window_id: &str, annotations: WindowAnnotations<'_>) {
+    annotate_window_with(OsStr::new("tmux"), window_id, annotations);
+}
+
+#[cfg(feature = "cli")]
+fn annotate_window_with(tmux: &OsStr, window_id: &str, annotations: WindowAnnotations<'_>) {
+    let cmd = tmux_annotate_cmd(window_id, annotations);
+    debug!(window_id, args = ?cmd.args, "annotating the tmux window");
+    let status = Command::new(tmux)
+        .args(&cmd.args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!(window_id, "tmux set-option exited with status {status}"),
+        Err(err) => warn!(window_id, "failed to run tmux set-option: {err}"),
+    }
+}
+
+/// Keys of the `tmux display-menu` entries, in order.
+#[cfg(feature = "cli")]
+const MENU_KEYS: [char; 10] = ['1', '2', '3', '4', '5', '6', '7', '8', '9', '0'];
+
+/// Maximum number of entries in a menu built by [`tmux_display_menu_cmd`].
+#[cfg(feature = "cli")]
+pub(super) const MAX_MENU_ENTRIES: usize = MENU_KEYS.len();
+
+/// Creates a `tmux display-menu` command offering `entries`, centered in the client.
+///
+/// Each entry is a label and the tmux command (such as [`tmux_menu_window_cmd`]) run when it
+/// is chosen, with keys `1` to `9` and then `0`; entries beyond the tenth are left out. tmux
+/// expands formats in the title, labels, and commands, so each `#` is doubled, and the
+/// commands are quoted for tmux's parser (see [`tmux_command_string`]).
+///
+/// # Arguments
+///
+/// * `title` - The menu title
+/// * `entries` - Each entry's label and command
+#[cfg(feature = "cli")]
+pub(super) fn tmux_display_menu_cmd(title: &str, entries: &[(String, Cmd)]) -> Cmd {
     let mut args: Vec<OsString> = vec![
-        "new-window".into(),
-        "-c".into(),
-        start_dir.as_os_str().to_owned(),
-        cmd.program.clone(),
+        "display-menu".into(),
+        "-T".into(),
+        escape_tmux_format(title).into(),
+        "-x".into(),
+        "C".into(),
+        "-y".into(),
+        "C".into(),
     ];
-    args.extend(cmd.args.iter().cloned());
+    for ((label, command), key) in entries.iter().zip(MENU_KEYS) {
+        // A label starting with `-` would be shown as a disabled entry.
+        let label = if label.starts_with('-') {
+            format!(" {label}")
+        } else {
+            label.clone()
+        };
+        args.extend([
+            escape_tmux_format(&label).into(),
+            key.to_string().into(),
+            escape_tmux_format(&tmux_command_string(command)).into(),
+        ]);
+    }
     Cmd {
         program: "tmux".into(),
         args,
     }
 }
 
+/// Renders a tmux command's arguments as one string for tmux's own command parser, as menu
+/// entries and key bindings take.
+///
+/// tmux's parser treats single quotes like a POSIX shell (no escapes inside, `\'` outside),
+/// so each argument is quoted like [`Cmd::as_shell_string`] does. The `tmux` program itself
+/// is left out.
+#[cfg(feature = "cli")]
+fn tmux_command_string(tmux: &Cmd) -> String {
+    tmux.args
+        .iter()
+        .map(|arg| sh_quote_lossy(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Escapes text that tmux expands as a format (`#{...}`, `#[...]`) by doubling each `#`.
+#[cfg(feature = "cli")]
+fn escape_tmux_format(text: &str) -> String {
+    text.replace('#', "##")
+}
+
+/// Creates the command that opens a window from outside tmux (`--tmux always`).
+///
+/// With a running tmux server, the window is added in the background (`new-window -d`) to
+/// the session tmux considers current; otherwise a detached session named
+/// [`TMUX_DETACHED_SESSION`] is created to hold it. Either way it prints the window id, like
+/// [`tmux_new_window_cmd`].
+///
+/// # Arguments
+///
+/// * `start_dir` - The working directory for the new tmux window
+/// * `window` - The window name (`detach` is implied)
+/// * `cmd` - The command to wrap
+/// * `server_running` - Whether a tmux server is running (see [`tmux_server_running`])
+#[cfg(feature = "cli")]
+pub(super) fn tmux_detached_window_cmd(
+    start_dir: &Path,
+    window: WindowOptions<'_>,
+    cmd: &Cmd,
+    server_running: bool,
+) -> Cmd {
+    let window = WindowOptions {
+        detach: true,
+        ..window
+    };
+    let mut tmux = tmux_new_window_cmd(start_dir, window, cmd);
+    if !server_running {
+        tmux.args.splice(
+            ..2,
+            [
+                "new-session".into(),
+                "-d".into(),
+                "-s".into(),
+                TMUX_DETACHED_SESSION.into(),
+            ],
+        );
+    }
+    tmux
+}
+
+/// Checks whether a tmux server with at least one session is running (`tmux has-session`).
+#[cfg(feature = "cli")]
+pub(super) fn tmux_server_running() -> bool {
+    Command::new("tmux")
+        .arg("has-session")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Lists the window names of the current tmux session (`tmux list-windows`).
+///
+/// Returns an empty list when tmux cannot be asked, leaving any name collision to tmux.
+#[cfg(feature = "cli")]
+pub(super) fn tmux_window_names() -> Vec<String> {
+    let output = Command::new("tmux")
+        .args(["list-windows", "-F", "#{window_name}"])
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            parse_window_names(&String::from_utf8_lossy(&output.stdout))
+        }
+        Ok(output) => {
+            debug!(status = %output.status, "tmux list-windows failed; not checking window names");
+            Vec::new()
+        }
+        Err(err) => {
+            debug!("cannot run tmux list-windows: {err}");
+            Vec::new()
+        }
+    }
+}
+
+/// Parses `tmux list-windows -F '#{window_name}'` output: one name per line.
+#[cfg(feature = "cli")]
+fn parse_window_names(stdout: &str) -> Vec<String> {
+    stdout
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Returns `name` if no window in `existing` has it, or else the first of `name-2`,
+/// `name-3`, ... that is free, so a second window for a branch can be told apart.
+#[cfg(feature = "cli")]
+pub(super) fn unique_window_name(name: &str, existing: &[String]) -> String {
+    let taken = |candidate: &str| existing.iter().any(|window| window == candidate);
+    if !taken(name) {
+        return name.to_owned();
+    }
+    let mut n = 2;
+    loop {
+        let candidate = format!("{name}-{n}");
+        if !taken(&candidate) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+/// Extracts a tmux window id (`@<n>`) from `tmux new-window -P` output.
+#[cfg(feature = "cli")]
+fn parse_window_id(stdout: &str) -> Option<String> {
+    let id = stdout.trim();
+    let digits = id.strip_prefix('@')?;
+    (!digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())).then(|| id.to_owned())
+}
+
+/// Closes a tmux window by id.
+///
+/// # Returns
+///
+/// Returns `true` if the window was closed, `false` if tmux reported a failure (typically
+/// because the window no longer exists).
+///
+/// # Errors
+///
+/// Returns an error if the `tmux` command cannot be executed.
+#[cfg(feature = "cli")]
+pub(super) fn kill_tmux_window(window_id: &str) -> Result<bool> {
+    let output = Command::new("tmux")
+        .args(["kill-window", "-t", window_id])
+        .output()
+        .context("failed to run tmux kill-window")?;
+    if !output.status.success() {
+        debug!(
+            window_id,
+            stderr = %String::from_utf8_lossy(&output.stderr).trim(),
+            "tmux kill-window failed"
+        );
+    }
+    Ok(output.status.success())
+}
+
+/// Returns the command name of a running process, or `None` if it isn't running.
+#[cfg(feature = "cli")]
+pub(super) fn process_name(pid: u32) -> Option<String> {
+    let output = Command::new("ps")
+        .args(["-p", &pid.to_string(), "-o", "comm="])
+        .output()
+        .ok()?;
+    let name = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    (output.status.success() && !name.is_empty()).then_some(name)
+}
+
+/// Sends `SIGTERM` to a process.
+///
+/// # Returns
+///
+/// Returns `true` if the signal was delivered.
+///
+/// # Errors
+///
+/// Returns an error if the `kill` command cannot be executed.
+#[cfg(feature = "cli")]
+pub(super) fn terminate_process(pid: u32) -> Result<bool> {
+    let status = Command::new("kill")
+        .args(["-TERM", &pid.to_string()])
+        .status()
+        .context("failed to run kill")?;
+    Ok(status.success())
+}
+
+/// Exit status fzf uses when the user aborts the selection (Esc or Ctrl-C).
+#[cfg(feature = "cli")]
+const FZF_CANCELLED: i32 = 130;
+
+/// Result of an fzf selection.
+#[cfg(feature = "cli")]
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum FzfOutcome {
+    /// The selected line, without its trailing newline.
+    Selected(String),
+    /// The user aborted the selection.
+    Cancelled,
+}
+
+/// Clipboard tools tried by [`copy_to_clipboard`], in order of preference.
+#[cfg(feature = "cli")]
+const CLIPBOARD_TOOLS: [(&str, &[&str]); 3] = [
+    ("pbcopy", &[]),
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+];
+
+/// Returns `true` if an executable named `program` is on `$PATH`.
+#[cfg(feature = "cli")]
+pub(super) fn on_path(program: &str) -> bool {
+    find_in_path(&search_path(), program).is_some()
+}
+
+#[cfg(feature = "cli")]
+fn search_path() -> OsString {
+    std::env::var_os(ENV_PATH).unwrap_or_default()
+}
+
+/// Finds `program` in a `$PATH`-style list of directories.
+#[cfg(feature = "cli")]
+fn find_in_path(search_path: &OsStr, program: &str) -> Option<PathBuf> {
+    std::env::split_paths(search_path)
+        .map(|dir| dir.join(program))
+        .find(|path| is_executable(path))
+}
+
+#[cfg(feature = "cli")]
+fn is_executable(path: &Path) -> bool {
+    let Ok(meta) = fs::metadata(path) else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        meta.is_file() && meta.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    meta.is_file()
+}
+
+/// Lets the user choose one of `lines` with fzf.
+///
+/// Lines are tab-separated fields; the first field is hidden (`--with-nth=2..`) and the
+/// preview shows the head of the file named by the fourth field.
+///
+/// # Errors
+///
+/// Returns an error if fzf cannot be executed or exits with a status other than success or
+/// cancellation.
+#[cfg(feature = "cli")]
+pub(super) fn run_fzf(lines: &[String]) -> Result<FzfOutcome> {
+    let mut child = Command::new("fzf")
+        .args([
+            "--delimiter=\t",
+            "--with-nth=2..",
+            "--preview",
+            "head -c 2000 {4}",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("failed to launch fzf")?;
+    {
+        let mut stdin = child.stdin.take().context("fzf stdin is not piped")?;
+        for line in lines {
+            // fzf may exit before reading everything; a broken pipe is not an error here.
+            if io::Write::write_all(&mut stdin, format!("{line}\n").as_bytes()).is_err() {
+                break;
+            }
+        }
+    }
+    let output = child.wait_with_output().context("failed to wait for fzf")?;
+    parse_fzf_output(output.status.code(), &output.stdout)
+}
+
+#[cfg(feature = "cli")]
+fn parse_fzf_output(code: Option<i32>, stdout: &[u8]) -> Result<FzfOutcome> {
+    match code {
+        Some(0) => {
+            let selected = String::from_utf8_lossy(stdout);
+            let line = selected.lines().next().unwrap_or_default();
+            Ok(FzfOutcome::Selected(line.to_owned()))
+        }
+        Some(FZF_CANCELLED) => Ok(FzfOutcome::Cancelled),
+        Some(code) => bail!("fzf exited with status {code}"),
+        None => bail!("fzf was terminated by a signal"),
+    }
+}
+
+/// Shows `message` in the tmux status line (`tmux display-message`).
+///
+/// # Errors
+///
+/// Returns an error if tmux cannot be executed or fails.
+#[cfg(feature = "cli")]
+pub(super) fn tmux_display_message(message: &str) -> Result<()> {
+    let status = Command::new("tmux")
+        .args(["display-message", message])
+        .status()
+        .context("failed to run tmux display-message")?;
+    if !status.success() {
+        bail!("tmux display-message exited with status {status}");
+    }
+    Ok(())
+}
+
+/// Shows a desktop notification with `notify-send` or, on macOS, `osascript`.
+///
+/// # Errors
+///
+/// Returns an error if neither tool is on `$PATH` or the tool fails.
+#[cfg(feature = "cli")]
+pub(super) fn desktop_notify(message: &str) -> Result<()> {
+    let cmd = desktop_notify_cmd(&search_path(), message)
+        .context("no notification tool found (notify-send or osascript)")?;
+    debug!(program = ?cmd.program, "sending desktop notification");
+    let status = Command::new(&cmd.program)
+        .args(&cmd.args)
+        .stdout(Stdio::null())
+        .status()
+        .with_context(|| format!("failed to launch {}", cmd.program.to_string_lossy()))?;
+    if !status.success() {
+        bail!(
+            "{} exited with status {status}",
+            cmd.program.to_string_lossy()
+        );
+    }
+    Ok(())
+}
+
+/// Builds the desktop notification command for the first tool found in `search_path`.
+#[cfg(feature = "cli")]
+fn desktop_notify_cmd(search_path: &OsStr, message: &str) -> Option<Cmd> {
+    if let Some(program) = find_in_path(search_path, "notify-send") {
+        return Some(Cmd {
+            program: program.into_os_string(),
+            args: vec!["amg".into(), message.into()],
+        });
+    }
+    find_in_path(search_path, "osascript").map(|program| Cmd {
+        program: program.into_os_string(),
+        args: vec![
+            "-e".into(),
+            format!(
+                "display notification {} with title \"amg\"",
+                applescript_string(message)
+            )
+            .into(),
+        ],
+    })
+}
+
+/// Quotes `s` as an AppleScript string literal.
+#[cfg(feature = "cli")]
+fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// File managers tried by [`open_directory`]: `open` on macOS, `xdg-open` elsewhere.
+#[cfg(feature = "cli")]
+const DIRECTORY_OPENERS: &[&str] = if cfg!(target_os = "macos") {
+    &["open"]
+} else {
+    &["xdg-open"]
+};
+
+/// Opens `dir` in the platform file manager.
+///
+/// # Errors
+///
+/// Returns an error if no opener is on `$PATH` or the opener fails.
+#[cfg(feature = "cli")]
+pub(super) fn open_directory(dir: &Path) -> Result<()> {
+    let cmd = open_directory_cmd(&search_path(), dir).with_context(|| {
+        format!(
+            "no file manager opener found ({})",
+            DIRECTORY_OPENERS.join(" or ")
+        )
+    })?;
+    debug!(program = ?cmd.program, dir = %util::human_path(dir), "opening directory");
+    let status = Command::new(&cmd.program)
+        .args(&cmd.args)
+        .stdout(Stdio::null())
+        .status()
+        .with_context(|| format!("failed to launch {}", cmd.program.to_string_lossy()))?;
+    if !status.success() {
+        bail!(
+            "{} exited with status {status}",
+            cmd.program.to_string_lossy()
+        );
+    }
+    Ok(())
+}
+
+/// Builds the command that opens `dir` with the first opener found in `search_path`.
+#[cfg(feature = "cli")]
+fn open_directory_cmd(search_path: &OsStr, dir: &Path) -> Option<Cmd> {
+    DIRECTORY_OPENERS
+        .iter()
+        .find_map(|name| find_in_path(search_path, name))
+        .map(|program| Cmd {
+            program: program.into_os_string(),
+            args: vec![dir.into()],
+        })
+}
+
+/// Copies `text` to the system clipboard.
+///
+/// Uses the first of `pbcopy`, `wl-copy`, or `xclip -selection clipboard` found on `$PATH`.
+///
+/// # Errors
+///
+/// Returns an error if no clipboard tool is found or the tool fails.
+#[cfg(feature = "cli")]
+pub(super) fn copy_to_clipboard(text: &str) -> Result<()> {
+    copy_to_clipboard_in(&search_path(), text)
+}
+
+#[cfg(feature = "cli")]
+fn copy_to_clipboard_in(search_path: &OsStr, text: &str) -> Result<()> {
+    let (program, args) = CLIPBOARD_TOOLS
+        .iter()
+        .find_map(|(name, args)| find_in_path(search_path, name).map(|path| (path, *args)))
+        .context("no clipboard tool found (pbcopy, wl-copy, or xclip)")?;
+    debug!(program = %util::human_path(&program), "copying to clipboard");
+    let mut child = Command::new(&program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .with_context(|| format!("failed to launch {}", util::human_path(&program)))?;
+    {
+        let mut stdin = child.stdin.take().context("clipboard stdin is not piped")?;
+        io::Write::write_all(&mut stdin, text.as_bytes())
+            .with_context(|| format!("failed to write to {}", util::human_path(&program)))?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("{} exited with status {status}", util::human_path(&program));
+    }
+    Ok(())
+}
+
+/// How long a timed-out child has to exit after `SIGTERM` before it is killed.
+#[cfg(feature = "cli")]
+const KILL_GRACE: Duration = Duration::from_secs(5);
+
+/// How often a child with a timeout is checked for having exited.
+#[cfg(feature = "cli")]
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long the stderr of a child stopped at its timeout may stay open before amg stops
+/// copying it. A process the child left behind can hold the pipe open indefinitely.
+#[cfg(feature = "cli")]
+const STDERR_DRAIN_GRACE: Duration = Duration::from_millis(500);
+
+/// How an inline child ended.
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Exit {
+    /// The child exited on its own (or was killed by someone other than amg).
+    Status(ExitStatus),
+    /// The child ran past its timeout and was stopped.
+    TimedOut,
+}
+
 /// Executes a command in the specified directory.
 ///
 /// Runs the command synchronously and returns its exit code.
@@ -111,48 +845,283 @@ pub(super) fn tmux_new_window_cmd(start_dir: &Path, cmd: &Cmd) -> Cmd {
 ///
 /// * `cwd` - The working directory for the command
 /// * `cmd` - The command to execute
+/// * `timeout` - How long the child may run before it is stopped (see [`wait_with_timeout`])
+/// * `on_spawn` - Called with the child's pid once it has started
 ///
 /// # Returns
 ///
-/// Returns [`Result<ExitCode>`] containing the command's exit code.
+/// Returns the child's [`ExitStatus`] (see [`exit_code`] to forward it), or
+/// [`Exit::TimedOut`].
 ///
 /// # Errors
 ///
 /// Returns an error if:
 /// * The command cannot be executed
 /// * Process creation fails
-pub(super) fn run_in_dir(cwd: &Path, cmd: &Cmd) -> Result<ExitCode> {
+#[cfg(feature = "cli")]
+pub(super) fn run_in_dir(
+    cwd: &Path,
+    cmd: &Cmd,
+    timeout: Option<Duration>,
+    on_spawn: impl FnOnce(u32),
+) -> Result<Exit> {
     debug!(
         program = ?cmd.program,
         args = ?cmd.args,
-        cwd = %cwd.display(),
+        cwd = %util::human_path(cwd),
+        ?timeout,
         "spawning command"
     );
-    let status = Command::new(&cmd.program)
+    let mut child = Command::new(&cmd.program)
         .args(&cmd.args)
         .current_dir(cwd)
-        .status()?;
-    Ok(exit_code(status))
+        .spawn()?;
+    on_spawn(child.id());
+    let exit = wait_with_timeout(&mut child, timeout, KILL_GRACE)?;
+    log_exit(cmd, exit);
+    Ok(exit)
 }
 
-/// Converts an [`ExitStatus`] to an [`ExitCode`].
+/// Like [`run_in_dir`], but the child reads stdin from the terminal instead of amg's stdin,
+/// which carried amg's own input (`--stdin-jsonl`). Without a terminal, its stdin is empty.
+///
+/// # Errors
+///
+/// Returns an error if the command cannot be executed.
+#[cfg(feature = "cli")]
+pub(super) fn run_on_terminal(cwd: &Path, cmd: &Cmd, timeout: Option<Duration>) -> Result<Exit> {
+    let terminal = if cfg!(windows) { "CONIN$" } else { "/dev/tty" };
+    let stdin = match fs::File::open(terminal) {
+        Ok(tty) => Stdio::from(tty),
+        Err(err) => {
+            debug!("cannot open {terminal} for the child's stdin: {err}");
+            Stdio::null()
+        }
+    };
+    debug!(
+        program = ?cmd.program,
+        args = ?cmd.args,
+        cwd = %util::human_path(cwd),
+        ?timeout,
+        "spawning command on the terminal"
+    );
+    let mut child = Command::new(&cmd.program)
+        .args(&cmd.args)
+        .current_dir(cwd)
+        .stdin(stdin)
+        .spawn()?;
+    let exit = wait_with_timeout(&mut child, timeout, KILL_GRACE)?;
+    log_exit(cmd, exit);
+    Ok(exit)
+}
+
+/// Waits for `child`, stopping it once `timeout` has passed.
+///
+/// A child still running at the deadline gets `SIGTERM`, then `SIGKILL` if it has not exited
+/// `grace` later. Without a timeout this is a plain blocking wait.
+///
+/// # Errors
+///
+/// Returns an error if the child's state cannot be queried.
+#[cfg(feature = "cli")]
+fn wait_with_timeout(
+    child: &mut Child,
+    timeout: Option<Duration>,
+    grace: Duration,
+) -> io::Result<Exit> {
+    let Some(timeout) = timeout else {
+        return child.wait().map(Exit::Status);
+    };
+    if let Some(status) = wait_until(child, Instant::now() + timeout)? {
+        return Ok(Exit::Status(status));
+    }
+    warn!(
+        pid = child.id(),
+        ?timeout,
+        "child ran past --timeout; terminating it"
+    );
+    terminate(child);
+    if wait_until(child, Instant::now() + grace)?.is_none() {
+        warn!(
+            pid = child.id(),
+            ?grace,
+            "child ignored SIGTERM; killing it"
+        );
+        child.kill()?;
+        child.wait()?;
+    }
+    Ok(Exit::TimedOut)
+}
+
+/// Polls `child` until it exits or `deadline` passes, returning its status if it exited.
+#[cfg(feature = "cli")]
+fn wait_until(child: &mut Child, deadline: Instant) -> io::Result<Option<ExitStatus>> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(None);
+        }
+        thread::sleep(WAIT_POLL_INTERVAL.min(deadline - now));
+    }
+}
+
+/// Asks `child` to exit (`SIGTERM`); elsewhere than Unix it is killed outright.
+#[cfg(feature = "cli")]
+fn terminate(child: &mut Child) {
+    #[cfg(unix)]
+    if let Ok(pid) = libc::pid_t::try_from(child.id()) {
+        // SAFETY: kill has no memory-safety preconditions; the pid is our unreaped child, so
+        // it cannot have been reused by another process.
+        unsafe { libc::kill(pid, libc::SIGTERM) };
+        return;
+    }
+    let _ = child.kill();
+}
+
+#[cfg(feature = "cli")]
+fn log_exit(cmd: &Cmd, exit: Exit) {
+    match exit {
+        Exit::Status(status) => info!(program = ?cmd.program, %status, "child exited"),
+        Exit::TimedOut => info!(program = ?cmd.program, "child stopped after timing out"),
+    }
+}
+
+/// Like [`run_in_dir`], but also returns the tail of the child's stderr.
+///
+/// Only stderr is piped: it is copied to amg's stderr as it arrives, and the last
+/// [`MAX_CAPTURED_STDERR`] bytes are kept. stdin and stdout stay attached to the terminal, so
+/// interactive programs keep working. After a timeout, amg waits at most
+/// [`STDERR_DRAIN_GRACE`] for the pipe to close and then returns what was captured, leaving
+/// the copying thread to any process still holding the pipe.
+///
+/// # Returns
+///
+/// Returns how the child ended and its captured stderr (lossily decoded).
+///
+/// # Errors
 ///
+/// Returns an error if the command cannot be executed.
+#[cfg(feature = "cli")]
+pub(super) fn run_in_dir_capturing_stderr(
+    cwd: &Path,
+    cmd: &Cmd,
+    timeout: Option<Duration>,
+    on_spawn: impl FnOnce(u32),
+) -> Result<(Exit, String)> {
+    debug!(
+        program = ?cmd.program,
+        args = ?cmd.args,
+        cwd = %util::human_path(cwd),
+        ?timeout,
+        "spawning command, capturing stderr"
+    );
+    let mut child = Command::new(&cmd.program)
+        .args(&cmd.args)
+        .current_dir(cwd)
+        .stderr(Stdio::piped())
+        .spawn()?;
+    on_spawn(child.id());
+    let mut pipe = child.stderr.take().context("child stderr was not piped")?;
+    let tail = Arc::new(Mutex::new(Vec::new()));
+    let tee = {
+        let tail = Arc::clone(&tail);
+        thread::spawn(move || tee_tail(&mut pipe, &mut io::stderr(), &tail, MAX_CAPTURED_STDERR))
+    };
+    let exit = wait_with_timeout(&mut child, timeout, KILL_GRACE)?;
+    let copied = match exit {
+        Exit::Status(_) => Some(tee.join()),
+        Exit::TimedOut => join_within(tee, STDERR_DRAIN_GRACE),
+    };
+    match copied {
+        Some(copied) => {
+            copied.map_err(|_| anyhow::anyhow!("the stderr copying thread panicked"))?;
+        }
+        None => debug!(
+            program = ?cmd.program,
+            "the timed-out child's stderr is still open; no longer copying it"
+        ),
+    }
+    log_exit(cmd, exit);
+    let captured = tail.lock().unwrap_or_else(PoisonError::into_inner);
+    Ok((exit, String::from_utf8_lossy(&captured).into_owned()))
+}
+
+/// Joins `handle` if its thread finishes within `timeout`, or returns `None` and leaves it
+/// running.
+#[cfg(feature = "cli")]
+fn join_within<T>(handle: JoinHandle<T>, timeout: Duration) -> Option<thread::Result<T>> {
+    let deadline = Instant::now() + timeout;
+    while !handle.is_finished() {
+        let now = Instant::now();
+        if now >= deadline {
+            return None;
+        }
+        thread::sleep(WAIT_POLL_INTERVAL.min(deadline - now));
+    }
+    Some(handle.join())
+}
+
+/// Copies `from` to `to` until end of input, keeping the last `limit` bytes read in `tail`.
+///
+/// Write errors are ignored so a closed terminal does not block the child on a full pipe.
+#[cfg(feature = "cli")]
+fn tee_tail(from: &mut impl Read, to: &mut impl Write, tail: &Mutex<Vec<u8>>, limit: usize) {
+    let mut buf = [0; 8 * 1024];
+    loop {
+        let n = match from.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+        let _ = to.write_all(&buf[..n]).and_then(|()| to.flush());
+        let mut tail = tail.lock().unwrap_or_else(PoisonError::into_inner);
+        tail.extend_from_slice(&buf[..n]);
+        if tail.len() > limit {
+            let excess = tail.len() - limit;
+            tail.drain(..excess);
+        }
+    }
+}
+
 /// Converts an [`ExitStatus`] to an [`ExitCode`].
 ///
 /// Returns [`ExitCode::FAILURE`] if the status code cannot be converted to a `u8`,
 /// otherwise returns the corresponding [`ExitCode`].
-fn exit_code(status: ExitStatus) -> ExitCode {
+#[cfg(feature = "cli")]
+pub(super) fn exit_code(status: ExitStatus) -> ExitCode {
     match status.code().and_then(|c| u8::try_from(c).ok()) {
         Some(code) => ExitCode::from(code),
         None => ExitCode::FAILURE,
     }
 }
 
+/// Quotes an argument, collapsing `home` (or a path beneath it) to an unquoted `~`.
+#[cfg(feature = "cli")]
+fn redacted_quote(arg: &OsStr, home: &Path) -> String {
+    match Path::new(arg).strip_prefix(home) {
+        Ok(rest) if rest.as_os_str().is_empty() => "~".to_owned(),
+        Ok(rest) => format!("~/{}", sh_quote_lossy(rest.as_os_str())),
+        Err(_) => sh_quote_lossy(arg),
+    }
+}
+
+/// Checks whether an argument looks like an option (`-a`, `--add-dir`), which may take the
+/// following argument as its value.
+#[cfg(feature = "cli")]
+fn is_option(arg: &OsStr) -> bool {
+    arg.to_string_lossy().starts_with('-')
+}
+
 /// Quotes a string for safe shell execution.
 ///
 /// Uses single quotes for quoting, with proper escaping for strings containing quotes.
 /// Empty strings are quoted as `''`.
-fn sh_quote_lossy(s: &OsStr) -> String {
+#[cfg(feature = "cli")]
+pub(super) fn sh_quote_lossy(s: &OsStr) -> String {
     let s = s.to_string_lossy();
     if s.is_empty() {
         return "''".to_owned();
@@ -162,3 +1131,543 @@ fn sh_quote_lossy(s: &OsStr) -> String {
     }
     format!("'{}'", s.replace('\'', "'\\''"))
 }
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn cmd(program: &str, args: &[&str]) -> Cmd {
+        Cmd {
+            program: program.into(),
+            args: args.iter().map(Into::into).collect(),
+        }
+    }
+
+    #[rstest]
+    #[case("", "''")]
+    #[case("plain", "'plain'")]
+    #[case("with space", "'with space'")]
+    #[case("it's", "'it'\\''s'")]
+    fn quotes_arguments(#[case] arg: &str, #[case] expected: &str) {
+        assert_eq!(sh_quote_lossy(OsStr::new(arg)), expected);
+    }
+
+    #[test]
+    fn pretty_keeps_short_commands_on_one_line() {
+        let short = cmd("codex", &["resume", "x"]);
+
+        assert_eq!(
+            short.as_pretty_shell_string(80, None),
+            short.as_shell_string()
+        );
+    }
+
+    #[test]
+    fn pretty_puts_each_option_on_its_own_line() {
+        let long = cmd(
+            "codex",
+            &[
+                "--search",
+                "-a",
+                "never",
+                "--add-dir",
+                "/home/u/w",
+                "resume",
+                "x",
+            ],
+        );
+
+        assert_eq!(
+            long.as_pretty_shell_string(20, Some(Path::new("/home/u"))),
+            "'codex' \\\n    '--search' \\\n    '-a' 'never' \\\n    '--add-dir' ~/'w' \\\n    \
+             'resume' \\\n    'x'"
+        );
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    #[case(&["--config", "model=x", "-s", "workspace-write"])]
+    #[case(&["--add-dir", "/with space", "--add-dir", "it's", "--flag"])]
+    #[case(&["--cd", "", "resume", "a\\b"])]
+    fn pretty_output_round_trips_through_the_shell(#[case] args: &[&str]) {
+        let mut printf = vec!["%s\\n"];
+        printf.extend_from_slice(args);
+        let pretty = cmd("printf", &printf).as_pretty_shell_string(10, None);
+        assert!(pretty.contains('\n'), "{pretty}");
+
+        let syntax = Command::new("sh")
+            .args(["-n", "-c", &pretty])
+            .status()
+            .unwrap();
+        assert!(syntax.success(), "{pretty}");
+
+        let output = Command::new("sh").args(["-c", &pretty]).output().unwrap();
+        let expected: String = args.iter().map(|arg| format!("{arg}\n")).collect();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(
+        None,
+        false,
+        "'tmux' 'new-window' '-P' '-F' '#{window_id}' '-c' '/w' 'codex' 'resume' 'x'"
+    )]
+    #[case(
+        Some("feature/a"),
+        false,
+        "'tmux' 'new-window' '-P' '-F' '#{window_id}' '-n' 'feature/a' '-c' '/w' 'codex' 'resume' 'x'"
+    )]
+    #[case(
+        None,
+        true,
+        "'tmux' 'new-window' '-d' '-P' '-F' '#{window_id}' '-c' '/w' 'codex' 'resume' 'x'"
+    )]
+    fn tmux_command_names_window_when_requested(
+        #[case] name: Option<&str>,
+        #[case] detach: bool,
+        #[case] expected: &str,
+    ) {
+        let tmux = tmux_new_window_cmd(
+            Path::new("/w"),
+            WindowOptions { name, detach },
+            &cmd("codex", &["resume", "x"]),
+        );
+
+        assert_eq!(tmux.as_shell_string(), expected);
+    }
+
+    #[test]
+    fn menu_entries_carry_quoted_tmux_commands() {
+        let codex = cmd("codex", &["resume", "it's #1"]);
+        let open = tmux_menu_window_cmd(Path::new("/w d"), WindowOptions::default(), &codex);
+        let entries = [
+            ("main  2h  /w d".to_owned(), open.clone()),
+            ("-wip  #3".to_owned(), open),
+        ];
+
+        let menu = tmux_display_menu_cmd("amg: main", &entries);
+
+        let entry = r#"'new-window' '-c' '/w d' 'codex' 'resume' 'it'\''s ##1'"#;
+        assert_eq!(
+            menu.args,
+            [
+                "display-menu",
+                "-T",
+                "amg: main",
+                "-x",
+                "C",
+                "-y",
+                "C",
+                "main  2h  /w d",
+                "1",
+                entry,
+                " -wip  ##3",
+                "2",
+                entry,
+            ]
+        );
+    }
+
+    #[test]
+    fn menus_stop_at_ten_entries() {
+        let entry = ("x".to_owned(), cmd("tmux", &["new-window"]));
+        let entries = vec![entry; 12];
+
+        let menu = tmux_display_menu_cmd("t", &entries);
+
+        assert_eq!(menu.args.len(), 7 + 3 * MAX_MENU_ENTRIES);
+        assert_eq!(menu.args.last().unwrap(), "'new-window'");
+        assert_eq!(menu.args[menu.args.len() - 2], "0");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn menu_commands_parse_back_to_the_same_arguments() {
+        // tmux's parser quotes like sh, so sh can check the round trip.
+        let codex = cmd("codex", &["resume", "it's", "$HOME", "a b"]);
+        let open = tmux_menu_window_cmd(Path::new("/w"), WindowOptions::default(), &codex);
+
+        let line = format!("printf '%s\\n' {}", tmux_command_string(&open));
+        let output = Command::new("sh").args(["-c", &line]).output().unwrap();
+
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "new-window\n-c\n/w\ncodex\nresume\nit's\n$HOME\na b\n"
+        );
+    }
+
+    #[rstest]
+    #[case::server_running(
+        true,
+        "'tmux' 'new-window' '-d' '-P' '-F' '#{window_id}' '-n' 'main' '-c' '/w' 'codex' 'resume' 'x'"
+    )]
+    #[case::no_server(
+        false,
+        "'tmux' 'new-session' '-d' '-s' 'amg' '-P' '-F' '#{window_id}' '-n' 'main' '-c' '/w' 'codex' 'resume' 'x'"
+    )]
+    fn detached_window_starts_a_session_when_needed(
+        #[case] server_running: bool,
+        #[case] expected: &str,
+    ) {
+        let window = WindowOptions {
+            name: Some("main"),
+            detach: false,
+        };
+
+        let tmux = tmux_detached_window_cmd(
+            Path::new("/w"),
+            window,
+            &cmd("codex", &["resume", "x"]),
+            server_running,
+        );
+
+        assert_eq!(tmux.as_shell_string(), expected);
+    }
+
+    #[rstest]
+    #[case("@12\n", Some("@12"))]
+    #[case("  @3  ", Some("@3"))]
+    #[case("", None)]
+    #[case("@", None)]
+    #[case("%4\n", None)]
+    fn parses_tmux_window_ids(#[case] stdout: &str, #[case] expected: Option<&str>) {
+        assert_eq!(parse_window_id(stdout).as_deref(), expected);
+    }
+
+    #[rstest]
+    #[case::no_windows("", "main", "main")]
+    #[case::free("zsh\nfeature/a\n", "main", "main")]
+    #[case::taken("zsh\nmain\n", "main", "main-2")]
+    #[case::suffixes_taken("main\nmain-2\nmain-3\n", "main", "main-4")]
+    #[case::first_gap("main\nmain-3\n", "main", "main-2")]
+    #[case::only_a_suffix_taken("main-2\n", "main", "main")]
+    #[case::prefix_is_not_a_match("main-old\nmainline\n", "main", "main")]
+    #[case::crlf("main\r\nmain-2\r\n", "main", "main-3")]
+    #[case::name_with_a_suffix("fix-2\n", "fix-2", "fix-2-2")]
+    fn picks_a_unique_window_name(
+        #[case] list_windows: &str,
+        #[case] name: &str,
+        #[case] expected: &str,
+    ) {
+        let existing = parse_window_names(list_windows);
+
+        assert_eq!(unique_window_name(name, &existing), expected);
+    }
+
+    #[rstest]
+    #[case(Some(0), "id\t1h\t/w\t/f\n", FzfOutcome::Selected("id\t1h\t/w\t/f".into()))]
+    #[case(Some(130), "", FzfOutcome::Cancelled)]
+    fn parses_fzf_results(
+        #[case] code: Option<i32>,
+        #[case] stdout: &str,
+        #[case] expected: FzfOutcome,
+    ) {
+        assert_eq!(parse_fzf_output(code, stdout.as_bytes()).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(Some(1))]
+    #[case(Some(2))]
+    #[case(None)]
+    fn fzf_failures_are_errors(#[case] code: Option<i32>) {
+        assert!(parse_fzf_output(code, b"").is_err());
+    }
+
+    #[cfg(unix)]
+    fn fake_tool(dir: &Path, name: &str) {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join(name);
+        fs::write(
+            &path,
+            "#!/bin/sh\necho \"$*\" > \"$0.args\"\ncat > \"$0.out\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    #[case(&["xclip"], "xclip", "-selection clipboard")]
+    #[case(&["xclip", "wl-copy"], "wl-copy", "")]
+    #[case(&["xclip", "wl-copy", "pbcopy"], "pbcopy", "")]
+    fn copies_with_the_preferred_clipboard_tool(
+        #[from(crate::cli::test_support::temp_dir)] dir: PathBuf,
+        #[case] installed: &[&str],
+        #[case] expected_tool: &str,
+        #[case] expected_args: &str,
+    ) {
+        for tool in installed {
+            fake_tool(&dir, tool);
+        }
+
+        copy_to_clipboard_in(dir.as_os_str(), "'codex' 'resume' 'x'").unwrap();
+
+        let out = dir.join(format!("{expected_tool}.out"));
+        assert_eq!(fs::read_to_string(out).unwrap(), "'codex' 'resume' 'x'");
+        let args = dir.join(format!("{expected_tool}.args"));
+        assert_eq!(fs::read_to_string(args).unwrap().trim_end(), expected_args);
+    }
+
+    fn annotations() -> WindowAnnotations<'static> {
+        WindowAnnotations {
+            session_id: "0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b",
+            branch: "feature/a",
+            cwd: Path::new("/work/my repo"),
+        }
+    }
+
+    #[test]
+    fn annotates_the_window_in_one_tmux_call() {
+        let cmd = tmux_annotate_cmd("@12", annotations());
+
+        assert_eq!(
+            cmd.as_shell_string(),
+            "'tmux' 'set-option' '-w' '-t' '@12' '@amg_session_id' \
+             '0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b' ';' \
+             'set-option' '-w' '-t' '@12' '@amg_branch' 'feature/a' ';' \
+             'set-option' '-w' '-t' '@12' '@amg_cwd' '/work/my repo'"
+        );
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    fn runs_the_annotation_through_tmux(#[from(crate::cli::test_support::temp_dir)] dir: PathBuf) {
+        fake_tool(&dir, "tmux");
+
+        annotate_window_with(dir.join("tmux").as_os_str(), "@12", annotations());
+
+        let args = fs::read_to_string(dir.join("tmux.args")).unwrap();
+        assert!(
+            args.starts_with("set-option -w -t @12 @amg_session_id 0199a6b2-"),
+            "{args}"
+        );
+        assert!(
+            args.trim_end().ends_with("@amg_cwd /work/my repo"),
+            "{args}"
+        );
+    }
+
+    #[rstest]
+    fn a_failed_annotation_is_not_an_error(
+        #[from(crate::cli::test_support::temp_dir)] dir: PathBuf,
+    ) {
+        annotate_window_with(dir.join("missing-tmux").as_os_str(), "@12", annotations());
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    fn prefers_notify_send(#[from(crate::cli::test_support::temp_dir)] dir: PathBuf) {
+        fake_tool(&dir, "osascript");
+        fake_tool(&dir, "notify-send");
+
+        let cmd = desktop_notify_cmd(dir.as_os_str(), "amg: resumed main").unwrap();
+
+        assert_eq!(cmd.program, dir.join("notify-send").into_os_string());
+        assert_eq!(cmd.args, ["amg", "amg: resumed main"]);
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    fn falls_back_to_osascript_with_quoting(
+        #[from(crate::cli::test_support::temp_dir)] dir: PathBuf,
+    ) {
+        fake_tool(&dir, "osascript");
+
+        let cmd = desktop_notify_cmd(dir.as_os_str(), r#"say "hi" \o/"#).unwrap();
+
+        assert_eq!(cmd.program, dir.join("osascript").into_os_string());
+        assert_eq!(
+            cmd.args,
+            [
+                "-e",
+                r#"display notification "say \"hi\" \\o/" with title "amg""#
+            ]
+        );
+    }
+
+    #[rstest]
+    fn no_notification_tool(#[from(crate::cli::test_support::temp_dir)] dir: PathBuf) {
+        assert!(desktop_notify_cmd(dir.as_os_str(), "x").is_none());
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    fn opens_directories_with_the_platform_opener(
+        #[from(crate::cli::test_support::temp_dir)] dir: PathBuf,
+    ) {
+        fake_tool(&dir, DIRECTORY_OPENERS[0]);
+        let sessions = Path::new("/codex/sessions/2025/03/09");
+
+        let cmd = open_directory_cmd(dir.as_os_str(), sessions).unwrap();
+
+        assert_eq!(cmd.program, dir.join(DIRECTORY_OPENERS[0]).into_os_string());
+        assert_eq!(cmd.args, [sessions.as_os_str()]);
+    }
+
+    #[rstest]
+    fn no_directory_opener(#[from(crate::cli::test_support::temp_dir)] dir: PathBuf) {
+        assert!(open_directory_cmd(dir.as_os_str(), Path::new("/codex")).is_none());
+    }
+
+    #[rstest]
+    fn copy_fails_without_a_clipboard_tool(
+        #[from(crate::cli::test_support::temp_dir)] dir: PathBuf,
+    ) {
+        assert!(copy_to_clipboard_in(dir.as_os_str(), "x").is_err());
+    }
+
+    #[test]
+    fn shell_string_quotes_program_and_args() {
+        let c = cmd("codex", &["--add-dir", "/home/me/a b"]);
+        assert_eq!(c.as_shell_string(), "'codex' '--add-dir' '/home/me/a b'");
+    }
+
+    #[rstest]
+    #[case(&[], "'codex' 'resume' 'it'\\''s'")]
+    #[case(
+        &["docker", "exec", "-it", "devbox"],
+        "'docker' 'exec' '-it' 'devbox' 'codex' 'resume' 'it'\\''s'"
+    )]
+    #[case(
+        &["ssh", "my host", "-o", "a='b'"],
+        "'ssh' 'my host' '-o' 'a='\\''b'\\''' 'codex' 'resume' 'it'\\''s'"
+    )]
+    fn wrapper_is_prepended_and_quoted(#[case] wrapper: &[&str], #[case] expected: &str) {
+        let wrapper: Vec<String> = wrapper.iter().map(|w| (*w).to_owned()).collect();
+
+        let wrapped = cmd("codex", &["resume", "it's"]).wrapped(&wrapper);
+
+        assert_eq!(wrapped.as_shell_string(), expected);
+    }
+
+    #[test]
+    fn split_wrapper_round_trips_through_shell_quoting() {
+        let wrapper =
+            crate::cli::util::split_words("docker exec -it 'dev box' \"a\\\"b\"").unwrap();
+
+        let wrapped = cmd("codex", &["resume", "x"]).wrapped(&wrapper);
+        let tmux = tmux_new_window_cmd(Path::new("/w"), WindowOptions::default(), &wrapped);
+
+        assert_eq!(
+            wrapped.as_shell_string(),
+            "'docker' 'exec' '-it' 'dev box' 'a\"b' 'codex' 'resume' 'x'"
+        );
+        assert_eq!(
+            crate::cli::util::split_words(&wrapped.as_shell_string()).unwrap(),
+            [
+                "docker", "exec", "-it", "dev box", "a\"b", "codex", "resume", "x"
+            ]
+        );
+        assert!(
+            tmux.as_shell_string()
+                .ends_with("'-c' '/w' 'docker' 'exec' '-it' 'dev box' 'a\"b' 'codex' 'resume' 'x'")
+        );
+    }
+
+    #[rstest]
+    #[case("/home/me", "~")]
+    #[case("/home/me/proj", "~/'proj'")]
+    #[case("/home/me/a b", "~/'a b'")]
+    #[case("/home/mex/proj", "'/home/mex/proj'")]
+    #[case("model=gpt", "'model=gpt'")]
+    fn redacted_shell_string_collapses_home_paths(#[case] arg: &str, #[case] expected: &str) {
+        let c = cmd("codex", &[arg]);
+        assert_eq!(
+            c.as_shell_string_redacted(Path::new("/home/me")),
+            format!("'codex' {expected}")
+        );
+    }
+
+    #[test]
+    fn redaction_does_not_touch_spawned_args() {
+        let c = cmd("codex", &["/home/me/proj"]);
+        let _ = c.as_shell_string_redacted(Path::new("/home/me"));
+        assert_eq!(c.args, [OsString::from("/home/me/proj")]);
+    }
+
+    #[rstest]
+    #[case::short(100, "line one\nline two\n")]
+    #[case::truncated(5, " two\n")]
+    fn tee_copies_everything_and_keeps_the_tail(#[case] limit: usize, #[case] tail: &str) {
+        let input = "line one\nline two\n";
+        let mut copied = Vec::new();
+        let kept = Mutex::new(Vec::new());
+
+        tee_tail(&mut input.as_bytes(), &mut copied, &kept, limit);
+
+        assert_eq!(copied, input.as_bytes());
+        assert_eq!(kept.into_inner().unwrap(), tail.as_bytes());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn captures_stderr_without_touching_the_exit_status() {
+        let c = cmd("sh", &["-c", "echo oops >&2; exit 4"]);
+
+        let (exit, stderr) = run_in_dir_capturing_stderr(Path::new("/"), &c, None, |_| {}).unwrap();
+
+        let Exit::Status(status) = exit else {
+            panic!("no timeout was set: {exit:?}");
+        };
+        assert_eq!(status.code(), Some(4));
+        assert_eq!(stderr, "oops\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_timeout_does_not_wait_for_stderr_held_by_a_leftover_process() {
+        let c = cmd("sh", &["-c", "echo oops >&2; sleep 5 & exec sleep 10"]);
+        let started = Instant::now();
+
+        let (exit, stderr) = run_in_dir_capturing_stderr(
+            Path::new("/"),
+            &c,
+            Some(Duration::from_millis(200)),
+            |_| {},
+        )
+        .unwrap();
+
+        assert_eq!(exit, Exit::TimedOut);
+        assert!(
+            started.elapsed() < Duration::from_secs(3),
+            "{:?}",
+            started.elapsed()
+        );
+        assert_eq!(stderr, "oops\n");
+    }
+
+    #[cfg(unix)]
+    fn spawn(script: &str) -> Child {
+        Command::new("sh").args(["-c", script]).spawn().unwrap()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_child_within_its_timeout_keeps_its_status() {
+        let mut child = spawn("exit 3");
+
+        let exit = wait_with_timeout(&mut child, Some(Duration::from_secs(10)), KILL_GRACE);
+
+        assert!(matches!(exit.unwrap(), Exit::Status(status) if status.code() == Some(3)));
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    #[case::exits_on_sigterm("exec sleep 10")]
+    #[case::ignores_sigterm("trap '' TERM; exec sleep 10")]
+    fn stops_a_child_past_its_timeout(#[case] script: &str) {
+        let mut child = spawn(script);
+        let started = Instant::now();
+
+        let exit = wait_with_timeout(
+            &mut child,
+            Some(Duration::from_millis(200)),
+            Duration::from_millis(200),
+        );
+
+        assert_eq!(exit.unwrap(), Exit::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(child.try_wait().unwrap().is_some());
+    }
+}
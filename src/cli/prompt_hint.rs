@@ -0,0 +1,193 @@
+//! Session hints for shell prompts (`amg prompt-hint`).
+//!
+//! A prompt runs this on every render, so it has to answer in a few milliseconds however large
+//! the session tree is. It never walks the codexdir and never runs git: the branch comes from
+//! `.git/HEAD` ([`git::head_branch`]), and the answer from the branch's last-hit cache entry
+//! (see [`cache`]) or, failing that, the session index (see [`index`]). It prints one token
+//! and always exits successfully:
+//!
+//! * `yes:<age>` - the branch has a session, last written `<age>` ago (`12m`, `3h`, `2d`)
+//! * `no` - the index has no session for the branch
+//! * `unknown` - there is no branch (outside a work tree, detached `HEAD`), or neither the
+//!   last-hit cache nor an index for the codexdir exists
+//!
+//! The answer is only as fresh as those caches: `amg which` refreshes the branch's last-hit
+//! entry, and `amg branches --cached` (with `--max-age`) the index.
+
+use std::fmt;
+
+use super::args::PromptHintArgs;
+use super::prelude::*;
+use super::{cache, config, git, index, output, pick, util};
+
+/// The answer printed for the prompt.
+#[derive(Debug, PartialEq, Eq)]
+enum Hint {
+    /// The branch has a session, last written this long ago (if known).
+    Yes(Option<Duration>),
+    /// The index has no session for the branch.
+    No,
+    /// Nothing cheap can tell.
+    Unknown,
+}
+
+impl fmt::Display for Hint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Yes(age) => {
+                let age = age.map_or_else(|| "?".to_owned(), pick::format_age);
+                write!(f, "yes:{age}")
+            }
+            Self::No => f.write_str("no"),
+            Self::Unknown => f.write_str("unknown"),
+        }
+    }
+}
+
+/// Handles the `prompt-hint` subcommand.
+///
+/// # Returns
+///
+/// Always returns success: anything that goes wrong is logged at debug level and answered
+/// with `unknown`.
+pub(super) fn run_prompt_hint(args: PromptHintArgs) -> ExitCode {
+    let hint = current_hint(args);
+    if let Err(err) = output::line(format_args!("{hint}")) {
+        debug!("failed to print the prompt hint: {err}");
+    }
+    ExitCode::SUCCESS
+}
+
+/// Looks up the hint for the branch checked out at `args.dir`.
+fn current_hint(args: PromptHintArgs) -> Hint {
+    let Some(branch) = git::head_branch(&args.dir) else {
+        debug!(dir = %util::human_path(&args.dir), "no branch is checked out here");
+        return Hint::Unknown;
+    };
+    let codexdir = match args
+        .location
+        .codexdir
+        .map(Ok)
+        .unwrap_or_else(config::default_codexdir)
+    {
+        Ok(codexdir) => codexdir,
+        Err(err) => {
+            debug!("{err:#}");
+            return Hint::Unknown;
+        }
+    };
+    hint(
+        &codexdir,
+        &branch,
+        cache::cache_dir().as_deref(),
+        SystemTime::now(),
+    )
+}
+
+/// Answers for `branch` from the caches in `cache_dir`, reading at most the cache files and
+/// one session file's metadata.
+fn hint(codexdir: &Path, branch: &str, cache_dir: Option<&Path>, now: SystemTime) -> Hint {
+    let Some(cache_dir) = cache_dir else {
+        return Hint::Unknown;
+    };
+    let age = |modified: SystemTime| now.duration_since(modified).unwrap_or_default();
+    let last_hit = cache_dir.join(LAST_HIT_CACHE_FILE);
+    if let Some(modified) = cache::cached_hit_modified(&last_hit, codexdir, branch) {
+        return Hint::Yes(Some(age(modified)));
+    }
+    let Some(index) = index::Index::load(&cache_dir.join(SESSION_INDEX_FILE), codexdir) else {
+        return Hint::Unknown;
+    };
+    match index
+        .branches()
+        .branches
+        .into_iter()
+        .find(|(indexed, _)| indexed == branch)
+    {
+        Some((_, modified)) => Hint::Yes(modified.map(age)),
+        None => Hint::No,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::{SessionFile, temp_dir};
+    use rstest::rstest;
+
+    const ID: &str = "0199a1b2-c3d4-7e5f-8a9b-0c1d2e3f4a5b";
+    const SESSION: &str = "2025/03/09/rollout-a.jsonl";
+
+    fn write_last_hit(cache_dir: &Path, branch: &str, path: &Path) {
+        let toml = format!(
+            "[hits.{branch:?}]\npath = {:?}\nmtime_ns = 0\n",
+            path.to_str().unwrap()
+        );
+        fs::write(cache_dir.join(LAST_HIT_CACHE_FILE), toml).unwrap();
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[rstest]
+    #[case(Hint::Yes(Some(Duration::from_secs(720))), "yes:12m")]
+    #[case(Hint::Yes(None), "yes:?")]
+    #[case(Hint::No, "no")]
+    #[case(Hint::Unknown, "unknown")]
+    fn renders_one_token(#[case] hint: Hint, #[case] expected: &str) {
+        assert_eq!(hint.to_string(), expected);
+    }
+
+    #[rstest]
+    fn without_caches_the_answer_is_unknown(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = dir.join("codex");
+        SessionFile::new("main", ID)
+            .modified(at(1_000))
+            .write(codexdir.join(SESSIONS_DIR).join(SESSION));
+
+        assert_eq!(
+            hint(&codexdir, "main", Some(&dir.join("cache")), at(2_000)),
+            Hint::Unknown
+        );
+        assert_eq!(hint(&codexdir, "main", None, at(2_000)), Hint::Unknown);
+    }
+
+    #[rstest]
+    fn answers_from_the_last_hit(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = dir.join("codex");
+        let session = SessionFile::new("main", ID)
+            .modified(at(1_000))
+            .write(codexdir.join(SESSIONS_DIR).join(SESSION));
+        write_last_hit(&dir, "main", &session);
+
+        assert_eq!(
+            hint(&codexdir, "main", Some(&dir), at(4_600)),
+            Hint::Yes(Some(Duration::from_secs(3_600)))
+        );
+        assert_eq!(
+            hint(&codexdir, "other", Some(&dir), at(4_600)),
+            Hint::Unknown
+        );
+    }
+
+    #[rstest]
+    fn a_missing_last_hit_file_falls_back_to_the_index(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = dir.join("codex");
+        let session = SessionFile::new("main", ID)
+            .modified(at(1_000))
+            .write(codexdir.join(SESSIONS_DIR).join(SESSION));
+        index::Index::build(&codexdir, at(1_000))
+            .unwrap()
+            .save(&dir.join(SESSION_INDEX_FILE))
+            .unwrap();
+        write_last_hit(&dir, "main", &session);
+        fs::remove_file(&session).unwrap();
+
+        assert_eq!(
+            hint(&codexdir, "main", Some(&dir), at(1_060)),
+            Hint::Yes(Some(Duration::from_secs(60)))
+        );
+        assert_eq!(hint(&codexdir, "other", Some(&dir), at(1_060)), Hint::No);
+    }
+}
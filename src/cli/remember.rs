@@ -0,0 +1,335 @@
+//! Options remembered per branch.
+//!
+//! After `amg resume BRANCH` launches codex successfully (a tmux window opened, or an inline
+//! run that exited with success), the options it ran with are stored for the branch in the
+//! amg state file. The next `amg resume BRANCH` uses them for every option the command line
+//! leaves unset, so a branch always resumed with `--since 2d --tmux never` does not need
+//! them spelled out again.
+//!
+//! Precedence, highest first: command-line flags, then the repo or global config, then the
+//! remembered options, then the built-in defaults. `--fresh` ignores what was remembered (and
+//! replaces it with the options of that run).
+//!
+//! Remembered are the selection filters (`--since`, `--under`, `--tag`, `--originator`,
+//! `--model-was`), `--tmux`, and `--wrap`. Boolean flags are not remembered, because an
+//! explicit "off" cannot be told apart from a flag left out. Nothing is remembered for
+//! `--any`, `--id`, or `--file`, dry runs, or plans. This module also implements the
+//! `remembered` subcommand, which shows or clears the stored options.
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use super::args::{RememberedArgs, SelectionArgs, TmuxMode};
+use super::prelude::*;
+use super::state::{self, State};
+use super::{alias, output, policy, process, util};
+
+/// The options remembered for one branch.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct Remembered {
+    /// `--tmux`, by its command-line name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tmux: Option<String>,
+    /// `--wrap`, as given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wrap: Option<String>,
+    /// `--under`, canonicalized.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    under: Option<PathBuf>,
+    /// `--since`, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    since_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    originator: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    model_was: Option<String>,
+}
+
+impl Remembered {
+    /// Captures the options of a resume that is about to be remembered in place of these.
+    ///
+    /// The `--tmux` and `--wrap` remembered here are kept when this run did not give them.
+    ///
+    /// # Arguments
+    ///
+    /// * `selection` - The selection options the resume ran with, remembered ones included
+    /// * `tmux` - `--tmux` as given (`--no-tmux` counts as `never`)
+    /// * `wrap` - `--wrap` as given
+    pub(super) fn capture(
+        &self,
+        selection: &SelectionArgs,
+        tmux: Option<TmuxMode>,
+        wrap: Option<&str>,
+    ) -> Self {
+        Self {
+            tmux: tmux
+                .and_then(|mode| mode.to_possible_value())
+                .map(|value| value.get_name().to_owned())
+                .or_else(|| self.tmux.clone()),
+            wrap: wrap.map(str::to_owned).or_else(|| self.wrap.clone()),
+            under: selection
+                .under
+                .as_ref()
+                .map(|dir| fs::canonicalize(dir).unwrap_or_else(|_| dir.clone())),
+            since_secs: selection.since.map(|since| since.as_secs()),
+            tag: selection.tag.clone(),
+            originator: selection.originator.clone(),
+            model_was: selection.model_was.clone(),
+        }
+    }
+
+    /// Fills the selection options left unset on the command line with remembered ones.
+    ///
+    /// A remembered `--under` directory that no longer exists is dropped rather than making
+    /// the resume fail.
+    pub(super) fn fill_selection(&self, selection: &mut SelectionArgs) {
+        if selection.under.is_none() {
+            selection.under = self.under.clone().filter(|dir| {
+                let exists = dir.is_dir();
+                if !exists {
+                    warn!(under = %util::human_path(dir), "the remembered --under no longer exists; ignoring it");
+                }
+                exists
+            });
+        }
+        if selection.since.is_none() {
+            selection.since = self.since_secs.map(Duration::from_secs);
+        }
+        fill(&mut selection.tag, &self.tag);
+        fill(&mut selection.originator, &self.originator);
+        fill(&mut selection.model_was, &self.model_was);
+    }
+
+    /// Returns the remembered `--tmux` mode, if any (an unknown name is ignored).
+    pub(super) fn tmux_mode(&self) -> Option<TmuxMode> {
+        self.tmux
+            .as_deref()
+            .and_then(|name| TmuxMode::from_str(name, false).ok())
+    }
+
+    /// Returns the remembered `--wrap`, if any.
+    pub(super) fn wrap(&self) -> Option<&str> {
+        self.wrap.as_deref()
+    }
+
+    /// Returns whether nothing is remembered.
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Renders the remembered options as the flags that would set them.
+    fn flags(&self) -> String {
+        let mut flags = Vec::new();
+        let mut push = |flag: &str, value: Option<String>| {
+            if let Some(value) = value {
+                flags.push(format!("--{flag} {}", quote(&value)));
+            }
+        };
+        push("since", self.since_secs.map(|secs| format!("{secs}s")));
+        push(
+            "under",
+            self.under.as_ref().map(|dir| dir.display().to_string()),
+        );
+        push("tag", self.tag.clone());
+        push("originator", self.originator.clone());
+        push("model-was", self.model_was.clone());
+        push("tmux", self.tmux.clone());
+        push("wrap", self.wrap.clone());
+        flags.join(" ")
+    }
+}
+
+/// Quotes `value` for the shell unless it is made of characters that need no quoting.
+fn quote(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:@%+=,".contains(c));
+    if plain {
+        value.to_owned()
+    } else {
+        process::sh_quote_lossy(OsStr::new(value))
+    }
+}
+
+fn fill(value: &mut Option<String>, remembered: &Option<String>) {
+    if value.is_none() {
+        value.clone_from(remembered);
+    }
+}
+
+/// Returns the options remembered for `branch`.
+///
+/// A state file that cannot be loaded is logged as a warning and treated as remembering
+/// nothing, so a broken state file never blocks a resume. In portable mode nothing is read.
+pub(super) fn load(branch: &str) -> Remembered {
+    if policy::is_portable() {
+        return Remembered::default();
+    }
+    let state = state::state_path().and_then(|path| State::load(&path));
+    let remembered = match state {
+        Ok(mut state) => state.remembered.remove(branch).unwrap_or_default(),
+        Err(err) => {
+            warn!("ignoring remembered options: {err:#}");
+            return Remembered::default();
+        }
+    };
+    if !remembered.is_empty() {
+        info!(branch = %branch, options = %remembered.flags(), "using remembered options");
+    }
+    remembered
+}
+
+/// Remembers `options` for `branch`, forgetting the branch when they are empty.
+///
+/// Failures are logged as warnings; the resume they follow already succeeded. In portable
+/// mode nothing is saved.
+pub(super) fn save(branch: &str, options: Remembered) {
+    if policy::is_portable() {
+        return;
+    }
+    let saved = state::update(|state| {
+        remember_in(state, branch, options);
+        Ok(())
+    });
+    if let Err(err) = saved {
+        warn!("failed to remember the resume options: {err:#}");
+    }
+}
+
+fn remember_in(state: &mut State, branch: &str, options: Remembered) {
+    if options.is_empty() {
+        state.remembered.remove(branch);
+    } else {
+        state.remembered.insert(branch.to_owned(), options);
+    }
+}
+
+/// Handles the `remembered` subcommand: prints `branch<TAB>flags` lines, or forgets a
+/// branch's options with `--clear`.
+///
+/// # Errors
+///
+/// Returns an error if the state file cannot be read or written.
+pub(super) fn run_remembered(args: RememberedArgs) -> Result<ExitCode> {
+    let branch = args.branch.as_deref().map(alias::resolve_branch);
+    if let (Some(branch), true) = (&branch, args.clear) {
+        match state::update(|state| Ok(state.remembered.remove(branch)))? {
+            Some(_) => info!(branch = %branch, "forgot the remembered options"),
+            None => info!(branch = %branch, "nothing is remembered for this branch"),
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let state = State::load(&state::state_path()?)?;
+    match &branch {
+        Some(branch) => match state.remembered.get(branch) {
+            Some(options) => output::line(format_args!("{branch}\t{}", options.flags()))?,
+            None => info!(branch = %branch, "nothing is remembered for this branch"),
+        },
+        None => {
+            for (branch, options) in &state.remembered {
+                output::line(format_args!("{branch}\t{}", options.flags()))?;
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::temp_dir;
+    use rstest::rstest;
+
+    fn remembered() -> Remembered {
+        Remembered {
+            tmux: Some("never".to_owned()),
+            wrap: Some("docker exec -it devbox".to_owned()),
+            since_secs: Some(7_200),
+            originator: Some("codex_cli_rs".to_owned()),
+            ..Remembered::default()
+        }
+    }
+
+    #[test]
+    fn fills_only_unset_selection_options() {
+        let mut selection = SelectionArgs {
+            originator: Some("codex_vscode".to_owned()),
+            ..SelectionArgs::default()
+        };
+
+        remembered().fill_selection(&mut selection);
+
+        assert_eq!(selection.since, Some(Duration::from_secs(7_200)));
+        assert_eq!(selection.originator.as_deref(), Some("codex_vscode"));
+        assert_eq!(selection.tag, None);
+    }
+
+    #[rstest]
+    fn drops_a_remembered_under_that_no_longer_exists(#[from(temp_dir)] dir: PathBuf) {
+        let gone = Remembered {
+            under: Some(dir.join("gone")),
+            ..Remembered::default()
+        };
+        let mut selection = SelectionArgs::default();
+
+        gone.fill_selection(&mut selection);
+
+        assert_eq!(selection.under, None);
+    }
+
+    #[test]
+    fn captures_given_options_over_previous_ones() {
+        let selection = SelectionArgs {
+            since: Some(Duration::from_secs(60)),
+            ..SelectionArgs::default()
+        };
+
+        let captured = remembered().capture(&selection, Some(TmuxMode::Always), None);
+
+        assert_eq!(captured.tmux_mode(), Some(TmuxMode::Always));
+        assert_eq!(captured.wrap(), Some("docker exec -it devbox"));
+        assert_eq!(captured.since_secs, Some(60));
+        assert_eq!(captured.originator, None);
+    }
+
+    #[test]
+    fn renders_the_flags() {
+        assert_eq!(
+            remembered().flags(),
+            "--since 7200s --originator codex_cli_rs --tmux never --wrap 'docker exec -it devbox'"
+        );
+        assert_eq!(Remembered::default().flags(), "");
+    }
+
+    #[test]
+    fn empty_options_forget_the_branch() {
+        let mut state = State::default();
+        remember_in(&mut state, "main", remembered());
+        assert_eq!(state.remembered["main"], remembered());
+
+        remember_in(&mut state, "main", Remembered::default());
+
+        assert!(state.remembered.is_empty());
+    }
+
+    #[rstest]
+    fn round_trips_through_the_state_file(#[from(temp_dir)] dir: PathBuf) {
+        let path = dir.join("state.json");
+
+        State::update(&path, |state| {
+            remember_in(state, "main", remembered());
+            Ok(())
+        })
+        .unwrap();
+        let loaded = State::load(&path).unwrap();
+
+        assert_eq!(loaded.remembered["main"], remembered());
+        let raw = fs::read_to_string(&path).unwrap();
+        assert!(raw.contains("\"since_secs\": 7200"), "{raw}");
+        assert!(!raw.contains("under"), "{raw}");
+    }
+}
@@ -0,0 +1,184 @@
+//! Machine-readable resume results (`--result-json`).
+//!
+//! After `amg resume` completes its action (the dry-run command printed, the tmux window
+//! created, or the inline codex exited), `--result-json` emits one JSON line describing what
+//! happened, so automation wrapping amg does not have to scrape the logs. The line goes to
+//! stdout, or to file descriptor 3 when a dry run has already printed the command there.
+
+use std::io::Write;
+use std::sync::OnceLock;
+
+use serde::Serialize;
+
+use super::prelude::*;
+
+/// File descriptor the result is written to when stdout carries the dry-run command.
+#[cfg(unix)]
+const RESULT_FD: i32 = 3;
+
+/// Whether [`RESULT_FD`] was inherited from the parent, recorded by [`probe_result_fd`].
+static RESULT_FD_INHERITED: OnceLock<bool> = OnceLock::new();
+
+/// What `amg resume` did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) enum ResultAction {
+    /// The command was printed (`--dry-run`).
+    DryRun,
+    /// codex was started in a new tmux window.
+    Tmux,
+    /// codex ran inline and exited.
+    Inline,
+}
+
+/// The single-line summary emitted by `--result-json`.
+#[derive(Debug, Serialize)]
+pub(super) struct ResumeResult<'a> {
+    /// What was done.
+    pub(super) action: ResultAction,
+    /// The resumed session's id.
+    pub(super) session_id: &'a str,
+    /// The branch the session was matched for (empty for an `--any` session without one).
+    pub(super) branch: &'a str,
+    /// The branch the session file records, if any; it differs from `branch` for a match
+    /// behind a `--strip-prefix`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) recorded_branch: Option<&'a str>,
+    /// The session's working directory.
+    pub(super) cwd: &'a Path,
+    /// The session file's size in bytes, if it could be read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) size: Option<u64>,
+    /// The session file's last modification time (RFC 3339, UTC), if it could be read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) mtime: Option<String>,
+    /// Number of `.jsonl` files the branch scan walked up to the match, when it scanned.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) files_scanned: Option<usize>,
+    /// How many of those had their first line parsed, when the branch scan ran.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) files_parsed: Option<usize>,
+    /// The new tmux window's id, when tmux reported one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) window_id: Option<String>,
+    /// codex's exit code when it ran inline (absent if it was killed by a signal).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) exit_code: Option<i32>,
+    /// Whether the inline codex was stopped by `--timeout` (present only when it was).
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub(super) timed_out: bool,
+}
+
+/// Records whether file descriptor 3 was inherited.
+///
+/// Must run before amg opens any file of its own (such as the log file), which could
+/// otherwise be handed descriptor 3 and then receive the result.
+pub(super) fn probe_result_fd() {
+    #[cfg(unix)]
+    // SAFETY: fcntl(F_GETFD) has no preconditions; it only reports whether the fd is open.
+    let open = unsafe { libc::fcntl(RESULT_FD, libc::F_GETFD) } != -1;
+    #[cfg(not(unix))]
+    let open = false;
+    let _ = RESULT_FD_INHERITED.set(open);
+}
+
+/// Emits `result` as one JSON line.
+///
+/// # Arguments
+///
+/// * `result` - The result to emit
+/// * `stdout_taken` - Whether stdout already carries other output (the dry-run command), in
+///   which case the line goes to file descriptor 3
+///
+/// # Errors
+///
+/// Returns an error if the result cannot be serialized or written, or if `stdout_taken` is
+/// set and file descriptor 3 was not open when amg started.
+pub(super) fn emit_result(result: &ResumeResult<'_>, stdout_taken: bool) -> Result<()> {
+    let line = serde_json::to_string(result).context("failed to serialize the result")?;
+    if !stdout_taken {
+        let mut stdout = io::stdout().lock();
+        return writeln!(stdout, "{line}").context("failed to write the result to stdout");
+    }
+    if !RESULT_FD_INHERITED.get().copied().unwrap_or(false) {
+        bail!(
+            "--result-json with a dry run printing to stdout writes to file descriptor 3, \
+             which is not open (redirect it, e.g. `3>result.json`, or use --output)"
+        );
+    }
+    write_result_fd(&line)
+}
+
+#[cfg(unix)]
+fn write_result_fd(line: &str) -> Result<()> {
+    use std::os::fd::FromRawFd;
+
+    // SAFETY: the descriptor was inherited open (checked by `probe_result_fd`) and nothing
+    // else in amg owns it; `ManuallyDrop` leaves it open for the parent.
+    let mut file = std::mem::ManuallyDrop::new(unsafe { fs::File::from_raw_fd(RESULT_FD) });
+    writeln!(file, "{line}").context("failed to write the result to file descriptor 3")
+}
+
+#[cfg(not(unix))]
+fn write_result_fd(_line: &str) -> Result<()> {
+    bail!("--result-json cannot use file descriptor 3 on this platform; use --output")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn result(action: ResultAction) -> ResumeResult<'static> {
+        ResumeResult {
+            action,
+            session_id: "abc",
+            branch: "main",
+            recorded_branch: None,
+            cwd: Path::new("/w"),
+            size: Some(300),
+            mtime: Some("2025-10-01T12:00:00Z".into()),
+            files_scanned: None,
+            files_parsed: None,
+            window_id: None,
+            exit_code: None,
+            timed_out: false,
+        }
+    }
+
+    #[rstest]
+    #[case::dry_run(result(ResultAction::DryRun), r#""action":"dry-run""#)]
+    #[case::tmux(
+        ResumeResult { window_id: Some("@7".into()), ..result(ResultAction::Tmux) },
+        r#""action":"tmux","session_id":"abc","branch":"main","cwd":"/w","size":300,"mtime":"2025-10-01T12:00:00Z","window_id":"@7"}"#
+    )]
+    #[case::inline(
+        ResumeResult { exit_code: Some(3), ..result(ResultAction::Inline) },
+        r#""mtime":"2025-10-01T12:00:00Z","exit_code":3}"#
+    )]
+    #[case::scan_counters(
+        ResumeResult { files_scanned: Some(30), files_parsed: Some(3), ..result(ResultAction::DryRun) },
+        r#""mtime":"2025-10-01T12:00:00Z","files_scanned":30,"files_parsed":3}"#
+    )]
+    #[case::timed_out(
+        ResumeResult { timed_out: true, ..result(ResultAction::Inline) },
+        r#""mtime":"2025-10-01T12:00:00Z","timed_out":true}"#
+    )]
+    #[case::recorded_branch(
+        ResumeResult { recorded_branch: Some("origin/main"), ..result(ResultAction::DryRun) },
+        r#""branch":"main","recorded_branch":"origin/main","cwd":"/w""#
+    )]
+    #[case::unknown_file_metadata(
+        ResumeResult { size: None, mtime: None, ..result(ResultAction::DryRun) },
+        r#""cwd":"/w"}"#
+    )]
+    fn serializes_only_the_fields_that_apply(
+        #[case] result: ResumeResult<'static>,
+        #[case] expected: &str,
+    ) {
+        let line = serde_json::to_string(&result).unwrap();
+
+        assert!(line.contains(expected), "{line}");
+        assert!(!line.contains('\n'));
+    }
+}
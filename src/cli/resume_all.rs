@@ -0,0 +1,264 @@
+//! Batch resume.
+//!
+//! This module implements the `resume-all` subcommand: it matches several branches in one
+//! pass over the codex directory and opens one tmux window per matched session, named after
+//! the requested branch. Running inline is refused, since several blocking codex processes
+//! only make sense inside a multiplexer.
+
+use super::args::{ResumeAllArgs, SandboxArgs};
+use super::prelude::*;
+use super::state::LaunchTarget;
+use super::{alias, codex_cmd, config, launch, output, process, scan, usage, util};
+
+/// Settings shared by every branch of a batch.
+struct Batch<'a> {
+    repo: &'a Path,
+    codexdir: &'a Path,
+    config: &'a config::Config,
+    sandbox: &'a SandboxArgs,
+    home: Option<&'a Path>,
+    wrapper: &'a [String],
+    dry_run: bool,
+    notify: bool,
+}
+
+/// Handles the `resume-all` subcommand.
+///
+/// # Returns
+///
+/// Returns [`ExitCode::SUCCESS`] if at least one branch was resumed.
+///
+/// # Errors
+///
+/// Returns an error if no branches are given, the branch file cannot be read, the repo or
+/// codexdir is invalid, amg is not running inside tmux (outside `--dry-run`), the scan fails,
+/// or every branch failed.
+pub(super) fn run_resume_all(args: ResumeAllArgs) -> Result<ExitCode> {
+    let mut requested = args.branches;
+    if let Some(file) = &args.from_file {
+        let content = fs::read_to_string(file)
+            .with_context(|| format!("failed to read branch list {}", util::human_path(file)))?;
+        requested.extend(parse_branch_list(&content));
+    }
+    let requested = dedup(requested);
+    if requested.is_empty() {
+        bail!("no branches to resume; pass --branches or --from-file");
+    }
+    // The batch was asked for explicitly and has no inline fallback, so it only needs tmux.
+    if !args.dry_run && !util::inside_tmux() {
+        bail!("resume-all opens one tmux window per branch and must be run inside tmux");
+    }
+
+    let codexdir = args
+        .location
+        .codexdir
+        .map(Ok)
+        .unwrap_or_else(config::default_codexdir)?;
+    util::require_dir(&args.repo, "repo", Some("CODEX_REPO"))?;
+    util::require_dir(&codexdir, "codexdir", Some("CODEX_CODEXDIR"))?;
+    super::check_overlap(&args.repo, &codexdir, args.overlap.allow_overlap)?;
+    super::check_codexdir(&codexdir, args.selection.walk.force_scan)?;
+    let config = config::Config::load(&args.repo)?;
+    let home = util::home_dir();
+    let wrapper = super::wrapper(args.wrapper.wrap.as_deref(), None, &config)?;
+
+    let targets: Vec<(String, String)> = requested
+        .into_iter()
+        .map(|name| {
+            let branch = alias::resolve_branch(&name);
+            (name, branch)
+        })
+        .collect();
+    let branches = dedup(targets.iter().map(|(_, branch)| branch.clone()).collect());
+    let scan_options = super::scan_options(&args.selection)?;
+    let sessions = scan::find_first_sessions(&codexdir, &branches, &scan_options)?;
+    usage::note_match(!sessions.is_empty());
+
+    let batch = Batch {
+        repo: &args.repo,
+        codexdir: &codexdir,
+        config: &config,
+        sandbox: &args.sandbox,
+        home: home.as_deref(),
+        wrapper: &wrapper,
+        dry_run: args.dry_run,
+        notify: args.notify,
+    };
+    // With --force-new-window, a second window for a branch (say, from an earlier run) gets a
+    // numbered name. Listing windows only reads tmux state, so dry runs ask too and print the
+    // names a real run would use.
+    let mut windows = if args.force_new_window && util::inside_tmux() {
+        process::tmux_window_names()
+    } else {
+        Vec::new()
+    };
+    let mut resumed = Vec::new();
+    let mut failed = Vec::new();
+    for (name, branch) in &targets {
+        let window_name = process::unique_window_name(name, &windows);
+        if window_name != *name {
+            info!(window = %window_name, "a window named {name} already exists; numbering the new one");
+        }
+        let outcome = sessions
+            .get(branch)
+            .context("no matching session")
+            .and_then(|session| resume_one(&window_name, branch, session, &batch));
+        match outcome {
+            Ok(()) => {
+                if args.force_new_window {
+                    windows.push(window_name);
+                }
+                resumed.push(name.as_str());
+            }
+            Err(err) => failed.push(format!("{name} ({err:#})")),
+        }
+    }
+
+    if !resumed.is_empty() {
+        info!(
+            "resumed {} of {}: {}",
+            resumed.len(),
+            targets.len(),
+            resumed.join(", ")
+        );
+    }
+    if !failed.is_empty() {
+        warn!("not resumed: {}", failed.join(", "));
+    }
+    if resumed.is_empty() {
+        bail!("none of the {} branch(es) could be resumed", targets.len());
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Builds the codex command for one matched session and opens (or prints) its tmux window.
+///
+/// Opened windows are recorded so `amg kill` can close them, and announced with
+/// `tmux display-message` (plus a desktop notification with `--notify`).
+fn resume_one(name: &str, branch: &str, session: &scan::Session, batch: &Batch<'_>) -> Result<()> {
+    util::require_dir(&session.cwd, "session cwd", None)?;
+    let repo = super::choose_repo(batch.repo.to_owned(), session, false);
+    batch.config.check_allowed(&repo, "repo")?;
+    let optional_dirs = super::optional_dirs(
+        batch.sandbox,
+        batch.config,
+        config::Vars {
+            repo: &repo,
+            home: batch.home,
+            cwd: &session.cwd,
+        },
+    )?;
+    let options = codex_cmd::CodexOptions {
+        mode: codex_cmd::SandboxMode::WorkspaceWrite,
+        model: batch.config.model(),
+        network_access: batch.config.network_access.unwrap_or(true),
+        strict: false,
+        canonicalize: !batch.sandbox.no_canonicalize,
+        repo_discovery: !batch.sandbox.no_repo_discovery,
+        minimize_dirs: batch.sandbox.minimize_dirs,
+        narrow_codexdir: batch.sandbox.narrow_codexdir,
+        add_worktrees: batch.sandbox.add_worktrees,
+    };
+    super::check_repo_grants(&repo, options, batch.config)?;
+    let cmd = codex_cmd::build_codex_cmd(
+        codex_cmd::ResumeTarget {
+            repo: &repo,
+            codexdir: batch.codexdir,
+            session,
+            start_dir: &session.cwd,
+            optional_dirs: &optional_dirs,
+        },
+        options,
+    )?
+    .wrapped(batch.wrapper);
+    info!(
+        branch = session.branch_label(),
+        looked_up = session.other_branch(name),
+        id = %session.id,
+        cwd = %util::human_path(&session.cwd),
+        size = session.size,
+        mtime = session.modified_rfc3339(),
+        "matched session"
+    );
+
+    let window = process::WindowOptions {
+        name: Some(name),
+        detach: false,
+    };
+    if batch.dry_run {
+        let tmux = process::tmux_new_window_cmd(&session.cwd, window, &cmd);
+        output::line(format_args!("{}", tmux.as_shell_string()))?;
+        Ok(())
+    } else {
+        if let Some(window_id) = process::run_tmux_new_window(&session.cwd, window, &cmd)? {
+            // The windows open in the current tmux session, so they are annotated by default.
+            if batch.config.tmux_annotate != Some(false) {
+                let annotations = process::WindowAnnotations {
+                    session_id: &session.id,
+                    branch,
+                    cwd: &session.cwd,
+                };
+                process::annotate_window(&window_id, annotations);
+            }
+            launch::record_launch(
+                &session.id,
+                branch,
+                session.branch.as_deref(),
+                LaunchTarget::TmuxWindow { window_id },
+            );
+        }
+        let notice = config::NoticeVars {
+            branch,
+            id: &session.id,
+            cwd: &session.cwd,
+        };
+        let notices = super::Notices {
+            status_line: true,
+            desktop: batch.notify,
+        };
+        super::notify_launch(batch.config, notice, notices);
+        Ok(())
+    }
+}
+
+/// Parses a branch list file: one branch per line, ignoring blank lines and `#` comments.
+fn parse_branch_list(content: &str) -> impl Iterator<Item = String> + '_ {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+}
+
+/// Removes empty and repeated entries, keeping the first occurrence order.
+fn dedup(items: Vec<String>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::with_capacity(items.len());
+    for item in items {
+        let item = item.trim();
+        if !item.is_empty() && !out.iter().any(|seen| seen == item) {
+            out.push(item.to_owned());
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_branch_list_skipping_comments_and_blanks() {
+        let content = "# monday\nmain\n\n  feature/a  \n#feature/old\nfeature/b\n";
+
+        let branches: Vec<_> = parse_branch_list(content).collect();
+
+        assert_eq!(branches, ["main", "feature/a", "feature/b"]);
+    }
+
+    #[test]
+    fn dedup_keeps_first_occurrence_order() {
+        let items = ["b", "a", " b ", "", "c", "a"].map(String::from).to_vec();
+
+        assert_eq!(dedup(items), ["b", "a", "c"]);
+    }
+}
@@ -3,10 +3,25 @@
 //! This module provides functionality to scan Codex session files (JSONL format) and find
 //! sessions that match a given git branch name. It performs a lexicographically sorted walk
 //! through the Codex directory to find matching sessions.
+//!
+//! By default only files owned by the current user are considered (see
+//! [`ScanOptions::owner`]), so a codexdir that bind-mounts other users' stores can still be
+//! walked without matching sessions that would fail to resume under this account.
+
+use std::collections::BTreeSet;
+#[cfg(feature = "cli")]
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::time::Instant;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "cli")]
+use super::datetime::UtcDateTime;
+use super::ignore::Exclusions;
+use super::jsonl::{self, JsonlReader, Limits, RecordError};
 use super::prelude::*;
+use super::util;
 
 /// Represents a matched Codex session.
 ///
@@ -18,8 +33,679 @@ pub(super) struct Session {
     pub(super) cwd: PathBuf,
     /// The unique session identifier.
     pub(super) id: String,
+    /// The git branch the session records (`.payload.git.branch`), if any. It is what the
+    /// session file says, which can differ from the branch looked up (`--strip-prefix`).
+    #[cfg_attr(not(feature = "cli"), expect(dead_code))]
+    pub(super) branch: Option<String>,
     /// The path to the JSONL file containing this session.
     pub(super) source_jsonl: PathBuf,
+    /// The git commit recorded when the session was created, if any.
+    pub(super) commit_hash: Option<String>,
+    /// The client that created the session (`.payload.originator`), if recorded.
+    pub(super) originator: Option<String>,
+    /// The model the session was created with (`.payload.model`), if recorded.
+    pub(super) model: Option<String>,
+    /// The session file's size in bytes, read once the session is matched.
+    pub(super) size: Option<u64>,
+    /// The session file's last modification time, read once the session is matched.
+    pub(super) modified: Option<SystemTime>,
+    /// The first line amg parsed (without a byte order mark or trailing carriage return),
+    /// kept only with [`ScanOptions::keep_first_line`].
+    pub(super) first_line: Option<String>,
+}
+
+impl Session {
+    /// Fills in [`Session::size`] and [`Session::modified`] with one metadata call.
+    ///
+    /// Only matched sessions pay for the call; candidates rejected during the walk never do.
+    fn with_file_metadata(mut self) -> Self {
+        if let Ok(meta) = fs::metadata(&self.source_jsonl) {
+            self.size = Some(meta.len());
+            self.modified = meta.modified().ok();
+        }
+        self
+    }
+
+    /// Returns [`Session::branch`], or `(none)` when the session records no branch, for logs.
+    #[cfg(feature = "cli")]
+    pub(super) fn branch_label(&self) -> &str {
+        self.branch.as_deref().unwrap_or("(none)")
+    }
+
+    /// Returns `looked_up` unless it is empty or the branch the session records, so a log
+    /// event can name both only when they differ.
+    #[cfg(feature = "cli")]
+    pub(super) fn other_branch<'a>(&self, looked_up: &'a str) -> Option<&'a str> {
+        Some(looked_up).filter(|b| !b.is_empty() && self.branch.as_deref() != Some(*b))
+    }
+
+    /// Returns [`Session::modified`] as an RFC 3339 timestamp in UTC.
+    #[cfg(feature = "cli")]
+    pub(super) fn modified_rfc3339(&self) -> Option<String> {
+        self.modified
+            .map(|modified| UtcDateTime::from_system_time(modified).rfc3339())
+    }
+}
+
+/// Session fields parsed from the first line of a JSONL file.
+#[derive(Debug, PartialEq, Eq)]
+struct SessionMeta {
+    cwd: PathBuf,
+    id: String,
+    branch: String,
+    commit_hash: Option<String>,
+    originator: Option<String>,
+    model: Option<String>,
+}
+
+/// What the first line of a session file says about the branch looked up.
+///
+/// Returned by [`parse_session_first_line`]; [`FirstLine::accept`] turns it into the matched
+/// session metadata or the [`Decision`] skipping the file.
+#[derive(Debug, PartialEq, Eq)]
+enum FirstLine {
+    /// The line records the branch and every field a session needs.
+    Matches(SessionMeta, BranchMatch),
+    /// The line records another branch (`None` if it never mentions the branch).
+    OtherBranch(Option<String>),
+    /// The line is a JSON object without a branch, cwd, or session id.
+    MissingFields,
+    /// The line is valid JSON, but not an object; the kind of value it is (`an array`).
+    NotAnObject(&'static str),
+    /// The line is not valid JSON.
+    InvalidJson(String),
+}
+
+impl FirstLine {
+    /// Returns the matched session metadata, or the [`Decision`] skipping the file.
+    fn accept(self) -> Result<(SessionMeta, BranchMatch), Decision> {
+        match self {
+            Self::Matches(meta, kind) => Ok((meta, kind)),
+            Self::OtherBranch(found) => Err(Decision::BranchMismatch { found }),
+            Self::MissingFields => Err(Decision::MissingFields),
+            Self::NotAnObject(found) => Err(Decision::NotAnObject { found }),
+            Self::InvalidJson(error) => Err(Decision::InvalidJson { error }),
+        }
+    }
+}
+
+/// A session file seen by a full scan, with its metadata and first-line fields.
+#[cfg(feature = "cli")]
+#[derive(Debug)]
+pub(super) struct SessionFile {
+    /// The path to the JSONL file.
+    pub(super) path: PathBuf,
+    /// The file size in bytes (0 if the metadata could not be read).
+    pub(super) size: u64,
+    /// The last modification time, if available.
+    pub(super) modified: Option<SystemTime>,
+    /// The fields parsed from the first line, or a description of why parsing failed.
+    pub(super) fields: std::result::Result<SessionFields, String>,
+}
+
+/// Fields of interest from the first line of a session file, all optional.
+///
+/// Unlike [`SessionMeta`], nothing is required: a full scan reports whatever is present.
+#[cfg(feature = "cli")]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(super) struct SessionFields {
+    /// The session identifier (`.payload.id`).
+    pub(super) id: Option<String>,
+    /// The session working directory (`.payload.cwd`).
+    pub(super) cwd: Option<String>,
+    /// The git branch (`.payload.git.branch`, or the older `.payload.git.branch_name`).
+    pub(super) branch: Option<String>,
+    /// The git remote URL (`.payload.git.repository_url`).
+    pub(super) repository_url: Option<String>,
+    /// The commit checked out when the session was created (`.payload.git.commit_hash`).
+    pub(super) commit_hash: Option<String>,
+    /// The client that created the session (`.payload.originator`).
+    pub(super) originator: Option<String>,
+    /// The model the session was created with (`.payload.model`).
+    pub(super) model: Option<String>,
+}
+
+/// Maximum number of non-JSONL file paths kept as examples in [`ScanStats`].
+const MAX_SAMPLE_FILES: usize = 3;
+
+/// Maximum number of directory entries read by [`probe_codexdir`].
+#[cfg(feature = "cli")]
+const PROBE_ENTRIES: usize = 256;
+
+/// What a shallow look at a directory suggests about it.
+#[cfg(feature = "cli")]
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Probe {
+    /// The directory looks like a Codex home or its `sessions/` directory.
+    Codex,
+    /// Nothing Codex-like was found among the first entries.
+    Unrecognized {
+        /// A `.codex` child that looks like a Codex home (the directory is probably `$HOME`).
+        nested: Option<PathBuf>,
+    },
+}
+
+/// Checks cheaply whether `dir` looks like a Codex directory before a deep walk.
+///
+/// Only the first [`PROBE_ENTRIES`] entries of `dir` itself are read. The directory looks like
+/// a Codex directory if one of them is a `sessions/` directory, a `rollout-*.jsonl` file, or a
+/// four-digit year directory (the `sessions/YYYY/MM/DD` layout).
+///
+/// # Errors
+///
+/// Returns an error if `dir` cannot be read.
+#[cfg(feature = "cli")]
+pub(super) fn probe_codexdir(dir: &Path) -> io::Result<Probe> {
+    for entry in fs::read_dir(dir)?.take(PROBE_ENTRIES) {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        let path = entry.path();
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let is_year = name.len() == 4 && name.bytes().all(|b| b.is_ascii_digit());
+        if (is_dir && (name == SESSIONS_DIR || is_year))
+            || (is_jsonl(&path) && is_rollout_file_name(&path))
+        {
+            return Ok(Probe::Codex);
+        }
+    }
+    let nested = dir.join(DOT_CODEX_DIR);
+    let nested = nested.join(SESSIONS_DIR).is_dir().then_some(nested);
+    Ok(Probe::Unrecognized { nested })
+}
+
+/// Result of scanning a Codex directory for a branch.
+#[derive(Debug)]
+pub(super) struct ScanOutcome {
+    /// The matched session, if any.
+    pub(super) session: Option<Session>,
+    /// Counters gathered while walking the directory.
+    #[cfg_attr(not(any(feature = "cli", test)), expect(dead_code))]
+    pub(super) stats: ScanStats,
+}
+
+/// Counters gathered while walking a Codex directory.
+///
+/// Lets callers tell "no JSONL files at all" (likely a wrong codexdir) apart from
+/// "JSONL files present but none matched".
+#[derive(Debug, Default)]
+pub(super) struct ScanStats {
+    /// Number of `.jsonl` files seen up to the match (or the end of the walk).
+    pub(super) files_scanned: usize,
+    /// How many of those had their first line read and parsed, the match included.
+    pub(super) files_parsed: usize,
+    /// A few non-JSONL files seen during the walk, for diagnostics.
+    pub(super) sample_files: Vec<PathBuf>,
+    /// Number of branch matches rejected by [`ScanOptions::under`].
+    pub(super) excluded_by_prefix: usize,
+    /// Number of session files skipped for being older than [`ScanOptions::modified_after`].
+    pub(super) excluded_by_age: usize,
+    /// The `--tag` filter and the number of branch matches it rejected, if any were.
+    pub(super) excluded_by_tag: Option<(String, usize)>,
+    /// Number of branch matches rejected by `--originator` or `--model-was`.
+    pub(super) excluded_by_metadata: usize,
+    /// Number of session files skipped because another user owns them.
+    pub(super) skipped_by_owner: usize,
+}
+
+impl ScanStats {
+    /// Records a walked file and returns whether it is a `.jsonl` candidate.
+    fn record(&mut self, path: &Path) -> bool {
+        if is_jsonl(path) {
+            self.files_scanned += 1;
+            true
+        } else {
+            if self.sample_files.len() < MAX_SAMPLE_FILES {
+                self.sample_files.push(path.to_owned());
+            }
+            false
+        }
+    }
+
+    /// Counts the [`Decision`] made for a `.jsonl` file: whether its first line was parsed
+    /// and, for a skipped file, whether one of the [`ScanOptions`] filters rejected it.
+    fn note(&mut self, decision: &Decision) {
+        self.files_parsed += usize::from(decision.parsed_first_line());
+        match decision {
+            Decision::OutsideUnder { cwd } => {
+                debug!(cwd = %util::human_path(cwd), "skipping session outside --under prefix");
+                self.excluded_by_prefix += 1;
+            }
+            Decision::MissingTag { tag } => {
+                self.excluded_by_tag
+                    .get_or_insert_with(|| (tag.clone(), 0))
+                    .1 += 1;
+            }
+            Decision::OtherMetadata { .. } => self.excluded_by_metadata += 1,
+            Decision::TooOld => self.excluded_by_age += 1,
+            Decision::ForeignOwner { .. } => self.skipped_by_owner += 1,
+            _ => {}
+        }
+    }
+}
+
+/// Logs how many files the ownership filter skipped during a walk.
+fn log_owner_skips(skipped: usize) {
+    if skipped > 0 {
+        debug!(
+            skipped,
+            "skipped session files owned by other users (see --any-owner)"
+        );
+    }
+}
+
+/// Why the scan accepted or skipped a file while looking for a branch.
+///
+/// Reported per file by [`find_first_session_observed`] (for `--explain`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub(super) enum Decision {
+    /// The file does not have a `.jsonl` extension.
+    NotJsonl,
+    /// The file is not named `rollout-*.jsonl` (and `--any-filename` is not set).
+    NotRolloutName,
+    /// The file could not be read.
+    Unreadable { error: String },
+    /// The file is owned by another user (and `--any-owner` is not set).
+    ForeignOwner { uid: u32 },
+    /// The file was last modified before the `--since` window.
+    TooOld,
+    /// The file is empty.
+    Empty,
+    /// The first line is not valid JSON.
+    InvalidJson { error: String },
+    /// The first line is valid JSON, but not an object (such as an array left by an export
+    /// script); `found` names the kind of value.
+    NotAnObject { found: &'static str },
+    /// The first line lacks a branch, cwd, or session id.
+    MissingFields,
+    /// The recorded branch differs (`found` is `None` if the line never mentions the branch).
+    BranchMismatch { found: Option<String> },
+    /// The session id does not look like a UUID.
+    NotUuid { id: String },
+    /// The session cwd is an absolute path of another platform (a Windows drive path off
+    /// Windows) that no `--map-path` translates.
+    ForeignCwd { cwd: PathBuf },
+    /// The session cwd is outside the `--under` directory.
+    OutsideUnder { cwd: PathBuf },
+    /// The session does not carry the `--tag` tag.
+    MissingTag { tag: String },
+    /// The session's originator or model differs from `--originator` or `--model-was`
+    /// (`found` is `None` if it is not recorded and `--require-metadata` is set).
+    OtherMetadata {
+        field: &'static str,
+        found: Option<String>,
+    },
+    /// The cwd or id is unusable: relative, or containing control characters (and
+    /// `--allow-weird-paths` is not set).
+    WeirdFields { problem: String },
+    /// The file name carries a different UUID than the session id (with `--strict`).
+    IdMismatch { file_id: String, id: String },
+    /// The recorded branch matches only after stripping a `--strip-prefix` prefix; the file is
+    /// used if no exact match follows.
+    StrippedMatch { found: String },
+    /// The file is the selected session.
+    Matched,
+}
+
+impl Decision {
+    /// Whether the file got as far as having its first line parsed.
+    fn parsed_first_line(&self) -> bool {
+        !matches!(
+            self,
+            Self::NotJsonl
+                | Self::NotRolloutName
+                | Self::Unreadable { .. }
+                | Self::ForeignOwner { .. }
+                | Self::TooOld
+                | Self::Empty
+        )
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotJsonl => f.write_str("not a .jsonl file"),
+            Self::NotRolloutName => f.write_str("not named rollout-*.jsonl"),
+            Self::Unreadable { error } => write!(f, "unreadable: {error}"),
+            Self::ForeignOwner { uid } => write!(f, "owned by another user (uid {uid})"),
+            Self::TooOld => f.write_str("modified before the --since window"),
+            Self::Empty => f.write_str("empty file"),
+            Self::InvalidJson { error } => write!(f, "first line is not valid JSON: {error}"),
+            Self::NotAnObject { found } => {
+                write!(f, "first line is valid JSON but not an object ({found})")
+            }
+            Self::MissingFields => f.write_str("first line lacks branch, cwd, or id"),
+            Self::BranchMismatch { found: Some(found) } => write!(f, "branch is {found:?}"),
+            Self::BranchMismatch { found: None } => f.write_str("branch does not match"),
+            Self::NotUuid { id } => write!(f, "session id {id:?} is not a UUID"),
+            Self::ForeignCwd { cwd } => write!(
+                f,
+                "cwd {} is from another platform (see --map-path)",
+                util::human_path(cwd)
+            ),
+            Self::OutsideUnder { cwd } => {
+                write!(f, "cwd {} is outside --under", util::human_path(cwd))
+            }
+            Self::MissingTag { tag } => write!(f, "session is not tagged {tag:?}"),
+            Self::OtherMetadata {
+                field,
+                found: Some(found),
+            } => write!(f, "{field} is {found:?}"),
+            Self::OtherMetadata { field, found: None } => {
+                write!(f, "{field} is unknown (--require-metadata)")
+            }
+            Self::WeirdFields { problem } => write!(f, "{problem}"),
+            Self::IdMismatch { file_id, id } => {
+                write!(f, "file name has session id {file_id}, first line has {id}")
+            }
+            Self::StrippedMatch { found } => {
+                write!(
+                    f,
+                    "branch is {found:?} (matches without its prefix; an exact match wins)"
+                )
+            }
+            Self::Matched => f.write_str("first matching session"),
+        }
+    }
+}
+
+/// Returns whether a walk of `codexdir` with `options` visits the file at `path`.
+///
+/// The file must be under the codexdir, neither it nor a directory above it may be excluded
+/// by the codexdir's `.amgignore` or [`ScanOptions::exclude`], and it must be a regular file
+/// rather than a symlink (unless [`ScanOptions::skip_symlink_check`] is set). Used to vet a
+/// path found without walking, such as a cached hit.
+#[cfg(feature = "cli")]
+pub(super) fn walk_visits(codexdir: &Path, path: &Path, options: &ScanOptions) -> bool {
+    let Ok(relative) = path.strip_prefix(codexdir) else {
+        return false;
+    };
+    let mut exclusions = Exclusions::load(codexdir);
+    exclusions.extend(&options.exclude);
+    if exclusions.excludes_file(relative) {
+        debug!(path = %util::human_path(path), "excluded from the walk");
+        return false;
+    }
+    options.skip_symlink_check || fs::symlink_metadata(path).is_ok_and(|meta| meta.is_file())
+}
+
+/// Options that narrow which files are considered Codex sessions.
+#[derive(Debug, Clone, Default)]
+pub(super) struct ScanOptions {
+    /// Accept session files with any `.jsonl` name instead of only `rollout-*.jsonl`.
+    pub(super) any_filename: bool,
+    /// Only accept sessions whose cwd is under this (canonicalized) directory.
+    pub(super) under: Option<PathBuf>,
+    /// Only accept sessions carrying this tag.
+    pub(super) tagged: Option<TagFilter>,
+    /// Only accept sessions created by this originator (`--originator`).
+    pub(super) originator: Option<String>,
+    /// Only accept sessions created with this model (`--model-was`).
+    pub(super) model: Option<String>,
+    /// Reject sessions that do not record a field filtered on by [`ScanOptions::originator`]
+    /// or [`ScanOptions::model`] (by default an unknown value is accepted).
+    pub(super) require_metadata: bool,
+    /// Only accept session files modified at or after this time.
+    pub(super) modified_after: Option<SystemTime>,
+    /// Only accept session files owned by this uid (`None` accepts any owner).
+    pub(super) owner: Option<u32>,
+    /// Accept sessions whose cwd is relative or whose cwd or id contains control characters.
+    pub(super) allow_weird_paths: bool,
+    /// Take every `.jsonl` entry to be a regular file without checking its type, for stores
+    /// known to be free of symlinks. Saves a `stat` per session file on filesystems whose
+    /// directory listings carry no entry types (some network mounts).
+    pub(super) skip_symlink_check: bool,
+    /// Keep the parsed first line of matched sessions in [`Session::first_line`] instead of
+    /// discarding it, so `show --raw` does not read the file again.
+    pub(super) keep_first_line: bool,
+    /// Reject sessions whose file name carries a different UUID than their session id
+    /// (`resume --strict`); by default the mismatch is only warned about.
+    pub(super) strict_file_ids: bool,
+    /// Prefixes (`--strip-prefix`, config `strip_prefixes`) removed from a session's recorded
+    /// branch when it does not match exactly: with `me/`, `me/feature-x` matches `feature-x`.
+    /// An exact match is always preferred.
+    pub(super) strip_prefixes: Vec<String>,
+    /// Paths skipped during the walk (`--exclude`), on top of the codexdir's `.amgignore`.
+    pub(super) exclude: Exclusions,
+    /// Translations applied to every recorded cwd before it is checked (`--map-path`).
+    pub(super) map_paths: Vec<util::PathMapping>,
+    /// Look for copies of the matched session's id in the whole codexdir instead of only its
+    /// file's directory (`--dedup-ids`).
+    pub(super) dedup_ids: bool,
+}
+
+/// How a session's recorded branch matched the branch looked up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BranchMatch {
+    /// The recorded branch is the branch looked up.
+    Exact,
+    /// The recorded branch (`found`) is the branch looked up behind a
+    /// [`ScanOptions::strip_prefixes`] entry.
+    Stripped { found: String },
+}
+
+/// Restricts matches to the session ids carrying a tag (`--tag`).
+#[derive(Debug, Clone)]
+pub(super) struct TagFilter {
+    /// The tag, for diagnostics.
+    pub(super) tag: String,
+    /// The ids of the sessions carrying the tag.
+    pub(super) ids: BTreeSet<String>,
+}
+
+impl ScanOptions {
+    /// Describes the options that decide which session a lookup finds first.
+    ///
+    /// The last-hit cache only reuses a hit found under the same key: re-validating the hit
+    /// shows it is still admitted, but not that no file before it is admitted, which differs
+    /// between, say, a `--under` lookup and a plain one. Every field that changes which files
+    /// are walked or admitted is part of the key.
+    #[cfg(feature = "cli")]
+    pub(super) fn cache_key(&self) -> String {
+        let Self {
+            any_filename,
+            under,
+            tagged,
+            originator,
+            model,
+            require_metadata,
+            modified_after,
+            owner,
+            allow_weird_paths,
+            skip_symlink_check,
+            keep_first_line: _,
+            strict_file_ids,
+            strip_prefixes,
+            exclude,
+            map_paths,
+            dedup_ids,
+        } = self;
+        let mut parts = Vec::new();
+        let mut flag = |set: bool, name: &str| {
+            if set {
+                parts.push(name.to_owned());
+            }
+        };
+        flag(*any_filename, "any-filename");
+        flag(*require_metadata, "require-metadata");
+        flag(*allow_weird_paths, "allow-weird-paths");
+        flag(*skip_symlink_check, "no-symlink-check");
+        flag(*strict_file_ids, "strict");
+        flag(*dedup_ids, "dedup-ids");
+        parts.push(match owner {
+            Some(uid) => format!("owner={uid}"),
+            None => "any-owner".to_owned(),
+        });
+        if let Some(under) = under {
+            parts.push(format!("under={}", under.display()));
+        }
+        if let Some(tagged) = tagged {
+            parts.push(format!("tag={}", tagged.tag));
+        }
+        if let Some(originator) = originator {
+            parts.push(format!("originator={originator}"));
+        }
+        if let Some(model) = model {
+            parts.push(format!("model={model}"));
+        }
+        if let Some(cutoff) = modified_after {
+            let secs = cutoff
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs());
+            parts.push(format!("since={secs}"));
+        }
+        parts.extend(
+            strip_prefixes
+                .iter()
+                .map(|prefix| format!("strip={prefix}")),
+        );
+        parts.extend(
+            exclude
+                .sources()
+                .map(|pattern| format!("exclude={pattern}")),
+        );
+        parts.extend(map_paths.iter().map(|mapping| format!("map={mapping}")));
+        parts.join("\n")
+    }
+
+    /// Checks the filters that apply to an otherwise matching session.
+    ///
+    /// This is quiet, for re-validating a file a walk already vetted (see
+    /// [`ScanOptions::vet`]).
+    pub(super) fn admits(&self, session: &Session) -> bool {
+        self.check(session).is_ok()
+    }
+
+    /// Like [`ScanOptions::check`], but warns about a suspicious session: skipped for a weird
+    /// cwd or id, or kept or skipped for a file named after another session.
+    ///
+    /// Only the walks call this, so each file is warned about once per scan; a file checked
+    /// again (a cached hit, another copy of the matched session) is not.
+    fn vet(&self, session: &Session) -> Result<(), Decision> {
+        let checked = self.check(session);
+        if let Err(Decision::WeirdFields { problem }) = &checked {
+            warn!(
+                "skipping session file {}: {problem} (pass --allow-weird-paths to accept it)",
+                util::human_path(&session.source_jsonl)
+            );
+        } else if let Some(file_id) = mismatched_file_id(session) {
+            warn!(
+                "session file {} is named for session {file_id} but records session {}{}",
+                util::human_path(&session.source_jsonl),
+                session.id,
+                if matches!(checked, Err(Decision::IdMismatch { .. })) {
+                    "; skipping it (--strict)"
+                } else {
+                    ""
+                }
+            );
+        }
+        checked
+    }
+
+    /// Stores `line` in the session when [`ScanOptions::keep_first_line`] is set.
+    fn retain_first_line(&self, mut session: Session, line: String) -> Session {
+        if self.keep_first_line {
+            session.first_line = Some(line);
+        }
+        session
+    }
+
+    /// Like [`ScanOptions::admits`], but returns the [`Decision`] rejecting the session.
+    fn check(&self, session: &Session) -> Result<(), Decision> {
+        if !self.allow_weird_paths
+            && let Some(problem) =
+                util::cwd_problem(&session.cwd).or_else(|| util::id_problem(&session.id))
+        {
+            return Err(Decision::WeirdFields { problem });
+        }
+        if self.strict_file_ids
+            && let Some(file_id) = mismatched_file_id(session)
+        {
+            return Err(Decision::IdMismatch {
+                file_id: file_id.to_owned(),
+                id: session.id.clone(),
+            });
+        }
+        if let Some(prefix) = &self.under
+            && !util::is_under(&session.cwd, prefix)
+        {
+            return Err(Decision::OutsideUnder {
+                cwd: session.cwd.clone(),
+            });
+        }
+        if let Some(filter) = &self.tagged
+            && !filter.ids.contains(&session.id)
+        {
+            debug!(id = %session.id, tag = %filter.tag, "skipping untagged session");
+            return Err(Decision::MissingTag {
+                tag: filter.tag.clone(),
+            });
+        }
+        let metadata = [
+            ("originator", &self.originator, &session.originator),
+            ("model", &self.model, &session.model),
+        ];
+        for (field, wanted, recorded) in metadata {
+            let Some(wanted) = wanted else {
+                continue;
+            };
+            match recorded {
+                Some(recorded) if recorded == wanted => {}
+                None if !self.require_metadata => {}
+                found => {
+                    debug!(id = %session.id, field, found = ?found, "skipping session with other metadata");
+                    return Err(Decision::OtherMetadata {
+                        field,
+                        found: found.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks [`ScanOptions::owner`] against the uid reported by `owner_of` for `path`.
+    ///
+    /// `owner_of` is the metadata seam: [`file_owner`] in production, a fake in tests.
+    fn check_owner(
+        &self,
+        path: &Path,
+        owner_of: impl FnOnce(&Path) -> io::Result<Option<u32>>,
+    ) -> Result<(), Decision> {
+        let Some(expected) = self.owner else {
+            return Ok(());
+        };
+        match owner_of(path) {
+            Ok(Some(uid)) if uid != expected => {
+                debug!(path = %util::human_path(path), uid, "skipping file owned by another user");
+                Err(Decision::ForeignOwner { uid })
+            }
+            Ok(_) => Ok(()),
+            Err(err) => Err(Decision::Unreadable {
+                error: err.to_string(),
+            }),
+        }
+    }
+}
+
+/// Returns the uid owning `path`, or `None` on platforms without uids.
+fn file_owner(path: &Path) -> io::Result<Option<u32>> {
+    let meta = fs::metadata(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Ok(Some(meta.uid()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = meta;
+        Ok(None)
+    }
 }
 
 /// Finds the first Codex session matching the given branch name.
@@ -27,16 +713,22 @@ pub(super) struct Session {
 /// Scans through all JSONL files in the codex directory in lexicographic order and returns
 /// the first session whose first JSONL line has `.payload.git.branch == branch`.
 ///
+/// To avoid matching unrelated JSONL files (notes, exported transcripts), a candidate must be
+/// named `rollout-*.jsonl` (unless [`ScanOptions::any_filename`] is set) and its session id
+/// must look like a UUID, and files owned by another user are skipped when
+/// [`ScanOptions::owner`] is set. Rejected candidates are logged at debug level. Matches are
+/// then filtered by [`ScanOptions::admits`].
+///
 /// # Arguments
 ///
 /// * `codexdir` - The Codex directory to search in
 /// * `branch` - The git branch name to match against
+/// * `options` - Options narrowing which files count as sessions
 ///
 /// # Returns
 ///
-/// Returns [`Result<Option<Session>>`] containing:
-/// * `Some(Session)` - If a matching session is found
-/// * `None` - If no matching session is found
+/// Returns [`Result<ScanOutcome>`] containing the matched session (if any) and the
+/// [`ScanStats`] gathered while walking.
 ///
 /// # Errors
 ///
@@ -48,152 +740,1131 @@ pub(super) struct Session {
 ///
 /// * [`Session`] - Session structure
 /// * [`SortedWalk`] - Directory walker implementation
-pub(super) fn find_first_session(codexdir: &Path, branch: &str) -> Result<Option<Session>> {
-    Ok(SortedWalk::new(codexdir)?
-        .filter(|p| is_jsonl(p))
-        .find_map(|p| session_from_jsonl(p, branch)))
+pub(super) fn find_first_session(
+    codexdir: &Path,
+    branch: &str,
+    options: &ScanOptions,
+) -> Result<ScanOutcome> {
+    find_first_session_observed(codexdir, branch, options, |_, _| {})
 }
 
-/// Checks if a path has a `.jsonl` extension.
-///
-/// # Arguments
+/// Like [`find_first_session`], but reports the [`Decision`] for every walked file, up to and
+/// including the match, to `observe`.
 ///
-/// * `path` - The path to check
+/// When other files record the matched session's id, the newest copy is returned instead
+/// (see [`prefer_newest_copy`]).
 ///
-/// # Returns
+/// # Errors
 ///
-/// Returns `true` if the path has a `.jsonl` extension, `false` otherwise.
-fn is_jsonl(path: &Path) -> bool {
-    path.extension() == Some(OsStr::new("jsonl"))
+/// Returns an error if the codexdir cannot be read.
+pub(super) fn find_first_session_observed(
+    codexdir: &Path,
+    branch: &str,
+    options: &ScanOptions,
+    observe: impl FnMut(&Path, &Decision),
+) -> Result<ScanOutcome> {
+    let mut outcome = first_match(SortedWalk::new(codexdir)?, branch, options, observe)?;
+    outcome.session = outcome
+        .session
+        .map(|session| prefer_newest_copy(session, codexdir, branch, options));
+    Ok(outcome)
 }
 
-/// Attempts to create a [`Session`] from a JSONL file if it matches the branch.
+/// Returns the newest file recording the same session id as `session`, which may be
+/// `session` itself.
 ///
-/// Reads the first line of the JSONL file and parses it to extract session information.
-/// Returns `Some(Session)` if the branch matches, `None` otherwise.
+/// Sync tools leave conflicted copies of a session file behind, and which one the walk meets
+/// first is path-order luck; resuming a stale copy loses the latest turns. To bound the cost,
+/// only the other candidates for `branch` in the matched file's directory are read, or every
+/// file of the codexdir with [`ScanOptions::dedup_ids`]. The newest copy by modification time
+/// wins, then by file name (whose timestamp sorts), and the duplicates are logged as a
+/// warning.
+fn prefer_newest_copy(
+    session: Session,
+    codexdir: &Path,
+    branch: &str,
+    options: &ScanOptions,
+) -> Session {
+    let walk = match session.source_jsonl.parent() {
+        Some(dir) if !options.dedup_ids => SortedWalk::entries_of(codexdir, dir, options),
+        _ => SortedWalk::new(codexdir).map(|walk| walk.with_options(options)),
+    };
+    let candidates: Vec<PathBuf> = match walk {
+        Ok(walk) => walk.collect(),
+        Err(err) => {
+            debug!("cannot walk the codexdir for duplicate session ids: {err:#}");
+            Vec::new()
+        }
+    };
+    let mut copies: Vec<Session> = candidates
+        .into_iter()
+        .filter(|path| is_jsonl(path) && *path != session.source_jsonl)
+        .filter_map(|path| {
+            // Read quietly: warning about a file is left to the walks.
+            let line = candidate_first_line(&path, options).ok()?;
+            let (copy, _) = session_from_line(path, &line, branch, options).ok()?;
+            (copy.id == session.id && options.admits(&copy))
+                .then(|| options.retain_first_line(copy, line))
+        })
+        .map(Session::with_file_metadata)
+        .collect();
+    if copies.is_empty() {
+        return session;
+    }
+    copies.push(session);
+    copies.sort_by(|a, b| {
+        (a.modified, a.source_jsonl.file_name()).cmp(&(b.modified, b.source_jsonl.file_name()))
+    });
+    let newest = copies
+        .pop()
+        .expect("the matched session is among the copies");
+    let stale: Vec<String> = copies
+        .iter()
+        .map(|copy| util::human_path(&copy.source_jsonl))
+        .collect();
+    warn!(
+        id = %newest.id,
+        file = %util::human_path(&newest.source_jsonl),
+        duplicates = %stale.join(", "),
+        "several files record this session; resuming the newest"
+    );
+    newest
+}
+
+/// Finds the newest Codex session matching the given branch name.
 ///
-/// # Arguments
+/// Like [`find_first_session`], but walks the codexdir in descending order (newest dated
+/// directory first, see [`SortedWalk::newest_first`]) and stops at the first exact match, so
+/// only the files newer than the match are read.
 ///
-/// * `source_jsonl` - Path to the JSONL file
-/// * `branch` - The git branch name to match against
+/// # Errors
 ///
-/// # Returns
+/// Returns an error if the codexdir cannot be read.
+#[cfg(feature = "cli")]
+pub(super) fn find_newest_session(
+    codexdir: &Path,
+    branch: &str,
+    options: &ScanOptions,
+) -> Result<ScanOutcome> {
+    first_match(
+        SortedWalk::newest_first(codexdir)?,
+        branch,
+        options,
+        |_, _| {},
+    )
+}
+
+/// Finds the first session for `branch` among first lines piped in (`--stdin-jsonl`).
 ///
-/// Returns [`Option<Session>`] if a matching session is found, `None` otherwise.
+/// Unlike the walks above, nothing is sorted: the lines are taken in input order, and the
+/// first one matching exactly wins, else the first one matching behind a stripped prefix.
+/// Lines are parsed and filtered like the first lines of session files; blank lines, and lines
+/// that are too long or not UTF-8, are skipped. With no file behind them, sessions are
+/// labelled `stdin:<line number>` in [`Session::source_jsonl`].
 ///
-/// # See Also
+/// # Errors
 ///
-/// * [`read_first_line`] - Reads the first line of a file
-/// * [`parse_session_first_line`] - Parses session data from JSON
-fn session_from_jsonl(source_jsonl: PathBuf, branch: &str) -> Option<Session> {
-    let line = read_first_line(&source_jsonl).ok().flatten()?;
-    let (cwd, id) = parse_session_first_line(&line, branch)?;
-    Some(Session {
-        cwd,
-        id,
-        source_jsonl,
+/// Returns an error if the input cannot be read.
+#[cfg(feature = "cli")]
+pub(super) fn find_in_lines(
+    input: impl BufRead,
+    branch: &str,
+    options: &ScanOptions,
+) -> Result<Option<Session>> {
+    // The first match behind a stripped prefix, used only if no exact match follows.
+    let mut stripped = None;
+    for line in JsonlReader::new(input, Limits::default()) {
+        let (number, line) = match line {
+            Ok(line) => line,
+            Err(err) if err.is_fatal() => {
+                return Err(err).context("failed to read session lines from stdin");
+            }
+            Err(err) => {
+                debug!("skipping piped line: {err}");
+                continue;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let label = PathBuf::from(format!("stdin:{number}"));
+        let matched = session_from_line(label, &line, branch, options).and_then(|matched| {
+            options.vet(&matched.0)?;
+            Ok(matched)
+        });
+        match matched {
+            Ok((session, BranchMatch::Exact)) => {
+                return Ok(Some(options.retain_first_line(session, line)));
+            }
+            Ok((session, BranchMatch::Stripped { .. })) => {
+                stripped.get_or_insert_with(|| options.retain_first_line(session, line));
+            }
+            Err(decision) => debug!(line = number, ?decision, "skipping piped line"),
+        }
+    }
+    Ok(stripped)
+}
+
+/// Returns the first session of `walk` for `branch`, or the first one matching behind a
+/// stripped prefix if there is no exact match.
+fn first_match(
+    walk: SortedWalk,
+    branch: &str,
+    options: &ScanOptions,
+    mut observe: impl FnMut(&Path, &Decision),
+) -> Result<ScanOutcome> {
+    let mut stats = ScanStats::default();
+    // The first match behind a stripped prefix, used only if no exact match follows.
+    let mut stripped = None;
+    for path in walk.with_options(options) {
+        if !stats.record(&path) {
+            observe(&path, &Decision::NotJsonl);
+            continue;
+        }
+        match evaluate(&path, branch, options) {
+            Ok((session, BranchMatch::Exact)) => {
+                stats.note(&Decision::Matched);
+                observe(&path, &Decision::Matched);
+                log_owner_skips(stats.skipped_by_owner);
+                return Ok(ScanOutcome {
+                    session: Some(session.with_file_metadata()),
+                    stats,
+                });
+            }
+            Ok((session, BranchMatch::Stripped { found })) => {
+                let decision = Decision::StrippedMatch { found };
+                stats.note(&decision);
+                observe(&path, &decision);
+                stripped.get_or_insert((path, session));
+            }
+            Err(decision) => {
+                stats.note(&decision);
+                observe(&path, &decision);
+            }
+        }
+    }
+    if let Some((path, _)) = &stripped {
+        observe(path, &Decision::Matched);
+    }
+    log_owner_skips(stats.skipped_by_owner);
+    Ok(ScanOutcome {
+        session: stripped.map(|(_, session)| session.with_file_metadata()),
+        stats,
     })
 }
 
-/// Reads the first line from a file.
+/// Like [`find_first_session`], but reads the first lines of up to `batch` files at a time,
+/// in parallel, while keeping the walk order: the first match of the earliest batch that has
+/// one is returned, or its newest copy as for [`find_first_session`] (see
+/// [`prefer_newest_copy`]). Used by the async API.
 ///
-/// # Arguments
+/// # Errors
 ///
-/// * `path` - The path to the file to read
+/// Returns an error if the codexdir cannot be read.
+#[cfg(feature = "async")]
+pub(super) fn find_first_session_batched(
+    codexdir: &Path,
+    branch: &str,
+    options: &ScanOptions,
+    batch: usize,
+) -> Result<Option<Session>> {
+    let mut walk = SortedWalk::new(codexdir)?
+        .with_options(options)
+        .filter(|p| is_jsonl(p));
+    let mut stripped = None;
+    let found = loop {
+        let paths: Vec<PathBuf> = walk.by_ref().take(batch.max(1)).collect();
+        if paths.is_empty() {
+            break stripped.map(Session::with_file_metadata);
+        }
+        let matches: Vec<_> = std::thread::scope(|scope| {
+            let reads: Vec<_> = paths
+                .iter()
+                .map(|path| scope.spawn(move || evaluate(path, branch, options).ok()))
+                .collect();
+            reads
+                .into_iter()
+                .filter_map(|read| read.join().ok().flatten())
+                .collect()
+        });
+        let exact = matches.into_iter().find_map(|(session, kind)| match kind {
+            BranchMatch::Exact => Some(session),
+            BranchMatch::Stripped { .. } => {
+                stripped.get_or_insert(session);
+                None
+            }
+        });
+        if let Some(session) = exact {
+            break Some(session.with_file_metadata());
+        }
+    };
+    Ok(found.map(|session| prefer_newest_copy(session, codexdir, branch, options)))
+}
+
+/// Decides whether a `.jsonl` file is a session for `branch` admitted by `options`.
+fn evaluate(
+    path: &Path,
+    branch: &str,
+    options: &ScanOptions,
+) -> Result<(Session, BranchMatch), Decision> {
+    let line = candidate_first_line(path, options)?;
+    let (session, kind) = session_from_line(path.to_owned(), &line, branch, options)?;
+    options.vet(&session)?;
+    Ok((options.retain_first_line(session, line), kind))
+}
+
+/// Finds every Codex session matching the given branch name, in walk order, exact matches
+/// before those matching only behind a [`ScanOptions::strip_prefixes`] entry.
+///
+/// Applies the same checks and filters as [`find_first_session`] but walks the whole
+/// directory instead of stopping at the first match. Used by the interactive picker.
+///
+/// # Errors
+///
+/// Returns an error if the codexdir cannot be read.
+#[cfg(feature = "cli")]
+pub(super) fn find_sessions(
+    codexdir: &Path,
+    branch: &str,
+    options: &ScanOptions,
+) -> Result<(Vec<Session>, ScanStats)> {
+    let mut stats = ScanStats::default();
+    let mut sessions = Vec::new();
+    let mut stripped = Vec::new();
+    for path in SortedWalk::new(codexdir)?.with_options(options) {
+        if !stats.record(&path) {
+            continue;
+        }
+        match evaluate(&path, branch, options) {
+            Ok((session, kind)) => {
+                stats.note(&Decision::Matched);
+                match kind {
+                    BranchMatch::Exact => sessions.push(session.with_file_metadata()),
+                    BranchMatch::Stripped { .. } => stripped.push(session.with_file_metadata()),
+                }
+            }
+            Err(decision) => stats.note(&decision),
+        }
+    }
+    sessions.append(&mut stripped);
+    log_owner_skips(stats.skipped_by_owner);
+    Ok((sessions, stats))
+}
+
+/// Finds the newest session in the codex directory, whatever its branch.
+///
+/// Walks newest first (see [`SortedWalk::newest_first`]), so only the most recent dated
+/// directories are read, and stops at the first file with a UUID session id and a cwd that
+/// [`ScanOptions::admits`].
 ///
 /// # Returns
 ///
-/// Returns [`io::Result<Option<String>>`] containing:
-/// * `Some(String)` - The first line if the file is not empty
-/// * `None` - If the file is empty
+/// Returns the session and its recorded branch (`None` if the session has no git branch), or
+/// `None` if no session was found.
 ///
 /// # Errors
 ///
-/// Returns an error if the file cannot be opened or read.
-fn read_first_line(path: &Path) -> io::Result<Option<String>> {
-    let f = fs::File::open(path)?;
-    let mut lines = io::BufReader::new(f).lines();
-    match lines.next() {
-        Some(line) => line.map(Some),
-        None => Ok(None),
+/// Returns an error if the codexdir cannot be read.
+#[cfg(feature = "cli")]
+pub(super) fn find_latest_session(
+    codexdir: &Path,
+    options: &ScanOptions,
+) -> Result<Option<(Session, Option<String>)>> {
+    let mut skipped_by_owner = 0;
+    for path in SortedWalk::newest_first(codexdir)?
+        .with_options(options)
+        .filter(|p| is_jsonl(p))
+    {
+        let line = match candidate_first_line(&path, options) {
+            Ok(line) => line,
+            Err(decision) => {
+                skipped_by_owner += usize::from(matches!(decision, Decision::ForeignOwner { .. }));
+                continue;
+            }
+        };
+        let Some((session, branch)) = any_branch_session(path, &line) else {
+            continue;
+        };
+        if options.vet(&session).is_ok() {
+            log_owner_skips(skipped_by_owner);
+            return Ok(Some((session.with_file_metadata(), branch)));
+        }
     }
+    log_owner_skips(skipped_by_owner);
+    Ok(None)
 }
 
-/// Parses the first line of a JSONL session file to extract session information.
+/// Session files already looked at by [`find_changed_sessions`], with the modification time
+/// they had then.
+#[cfg(feature = "cli")]
+pub(super) type SeenFiles = HashMap<PathBuf, Option<SystemTime>>;
+
+/// Finds the sessions for `branch` among the files that are new or modified since they were
+/// recorded in `seen`, newest first, and records every file walked.
 ///
-/// Performs a fast-path check to avoid JSON parsing unless the branch name appears in the line.
-/// Then parses the JSON to extract git branch, working directory, and session ID.
+/// Used by `amg watch` between polls: an unchanged file is never read again, whether it
+/// matched or not, so a poll costs a walk and a `stat` per file. A file rejected because it
+/// was still incomplete is read again once its modification time changes. Exact and
+/// prefix-stripped branch matches are both returned.
 ///
-/// # Arguments
+/// # Errors
 ///
-/// * `line` - The first line of the JSONL file
-/// * `branch` - The git branch name to match against
+/// Returns an error if the codexdir cannot be read.
+#[cfg(feature = "cli")]
+pub(super) fn find_changed_sessions(
+    codexdir: &Path,
+    branch: &str,
+    options: &ScanOptions,
+    seen: &mut SeenFiles,
+) -> Result<Vec<Session>> {
+    let mut sessions = Vec::new();
+    for path in SortedWalk::newest_first(codexdir)?
+        .with_options(options)
+        .filter(|p| is_jsonl(p))
+    {
+        let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+        if seen.get(&path) == Some(&modified) {
+            continue;
+        }
+        seen.insert(path.clone(), modified);
+        if let Ok((session, _)) = evaluate(&path, branch, options) {
+            sessions.push(session.with_file_metadata());
+        }
+    }
+    Ok(sessions)
+}
+
+/// Finds the sessions whose id starts with `prefix` (case-insensitively), in walk order.
+///
+/// Unlike [`find_first_session`], the walk continues past the first hit so an ambiguous
+/// prefix can be reported, but it stops once more than `limit` sessions matched.
 ///
 /// # Returns
 ///
-/// Returns [`Option<(PathBuf, String)>`] containing:
-/// * `Some((cwd, id))` - If the branch matches and all required fields are present
-/// * `None` - If the branch doesn't match or required fields are missing
-fn parse_session_first_line(line: &str, branch: &str) -> Option<(PathBuf, String)> {
-    // Fast-path: avoid JSON parsing unless the branch appears on the line.
-    if !line.contains(branch) {
-        return None;
+/// Returns up to `limit + 1` matching sessions with their branches; more than `limit` means
+/// the list was cut short.
+///
+/// # Errors
+///
+/// Returns an error if the codexdir cannot be read.
+#[cfg(feature = "cli")]
+pub(super) fn find_sessions_by_id_prefix(
+    codexdir: &Path,
+    prefix: &str,
+    options: &ScanOptions,
+    limit: usize,
+) -> Result<Vec<(Session, Option<String>)>> {
+    let prefix = prefix.to_ascii_lowercase();
+    let mut found = Vec::new();
+    for path in SortedWalk::new(codexdir)?
+        .with_options(options)
+        .filter(|p| is_jsonl(p))
+    {
+        let Ok(line) = candidate_first_line(&path, options) else {
+            continue;
+        };
+        let Some((session, branch)) = any_branch_session(path, &line) else {
+            continue;
+        };
+        if session.id.to_ascii_lowercase().starts_with(&prefix) && options.vet(&session).is_ok() {
+            found.push((session.with_file_metadata(), branch));
+            if found.len() > limit {
+                break;
+            }
+        }
     }
-
-    let Event {
-        payload:
-            Some(Payload {
-                git: Some(Git {
-                    branch: Some(got_branch),
-                }),
-                cwd: Some(cwd),
-                id: Some(id),
-            }),
-    } = serde_json::from_str(line).ok()?
-    else {
-        return None;
-    };
-
-    let cwd = cwd.trim();
-    let id = id.trim();
-    (got_branch == branch && !cwd.is_empty() && !id.is_empty())
-        .then(|| (PathBuf::from(cwd), id.to_owned()))
+    Ok(found)
 }
 
-/// JSON deserialization structure for Codex event payload.
-#[derive(Debug, Deserialize)]
-struct Event {
-    /// The event payload containing session information.
-    payload: Option<Payload>,
+/// Builds a [`Session`] and its branch (if any) from a first line with a UUID id and a cwd,
+/// whatever the branch.
+#[cfg(feature = "cli")]
+fn any_branch_session(path: PathBuf, line: &str) -> Option<(Session, Option<String>)> {
+    let shown = util::human_path(&path);
+    session_with_branch(path, line)
+        .inspect_err(|problem| debug!(path = %shown, "skipping file: {problem}"))
+        .ok()
 }
 
-/// JSON deserialization structure for Codex payload.
-#[derive(Debug, Deserialize)]
-struct Payload {
-    /// Git-related information.
-    git: Option<Git>,
-    /// The working directory where the session was created.
-    cwd: Option<String>,
-    /// The unique session identifier.
-    id: Option<String>,
+/// Reads a session and its branch (if any) from one specific file, whatever its name or
+/// location (`resume --file`).
+///
+/// # Errors
+///
+/// Returns an error naming the problem if the file cannot be read, is empty, or its first line
+/// is not JSON or lacks a cwd or UUID session id.
+#[cfg(feature = "cli")]
+pub(super) fn session_from_file(path: &Path) -> Result<(Session, Option<String>)> {
+    let line = read_first_line(path)
+        .with_context(|| format!("failed to read {}", util::human_path(path)))?
+        .with_context(|| format!("{} is empty", util::human_path(path)))?;
+    session_with_branch(path.to_owned(), &line)
+        .map(|(session, branch)| (session.with_file_metadata(), branch))
+        .map_err(|problem| {
+            anyhow::anyhow!(
+                "{} is not a usable session: {problem}",
+                util::human_path(path)
+            )
+        })
 }
 
-/// JSON deserialization structure for git information.
+/// Checks that the file of `session` still starts with the same session, so it is safe to
+/// hand to codex after time has passed since the scan read it.
+///
+/// # Errors
+///
+/// Returns a description of what changed: the file is gone, empty, or unreadable, or its
+/// first line records another session id (or none).
+#[cfg(feature = "cli")]
+pub(super) fn verify_unchanged(session: &Session) -> std::result::Result<(), String> {
+    let path = &session.source_jsonl;
+    let line = match read_first_line(path) {
+        Ok(Some(line)) => line,
+        Ok(None) => return Err(format!("{} is now empty", util::human_path(path))),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(format!("{} no longer exists", util::human_path(path)));
+        }
+        Err(err) => {
+            return Err(format!(
+                "{} can no longer be read: {err}",
+                util::human_path(path)
+            ));
+        }
+    };
+    match parse_session_fields(&line)
+        .ok()
+        .and_then(|fields| fields.id)
+    {
+        Some(id) if id.trim() == session.id => Ok(()),
+        Some(id) => Err(format!(
+            "{} now records session {}",
+            util::human_path(path),
+            id.trim()
+        )),
+        None => Err(format!(
+            "{} no longer records a session id",
+            util::human_path(path)
+        )),
+    }
+}
+
+/// Like [`any_branch_session`], but describes what is missing from the first line.
+#[cfg(feature = "cli")]
+fn session_with_branch(
+    path: PathBuf,
+    line: &str,
+) -> std::result::Result<(Session, Option<String>), String> {
+    let fields = parse_session_fields(line).map_err(|err| format!("the first line is {err}"))?;
+    let id = fields.id.as_deref().map(str::trim).unwrap_or_default();
+    let cwd = fields.cwd.as_deref().map(str::trim).unwrap_or_default();
+    if id.is_empty() {
+        return Err("the first line has no session id (.payload.id)".into());
+    }
+    if !is_plausible_uuid(id) {
+        return Err(format!("the session id {id:?} is not a UUID"));
+    }
+    if cwd.is_empty() {
+        return Err("the first line has no working directory (.payload.cwd)".into());
+    }
+    let branch = fields.branch.filter(|b| !b.trim().is_empty());
+    let session = Session {
+        cwd: PathBuf::from(cwd),
+        id: id.to_owned(),
+        branch: branch.clone(),
+        source_jsonl: path,
+        commit_hash: non_empty(fields.commit_hash),
+        originator: non_empty(fields.originator),
+        model: non_empty(fields.model),
+        size: None,
+        modified: None,
+        first_line: None,
+    };
+    Ok((session, branch))
+}
+
+/// Finds the first session for each of several branches in a single directory walk.
+///
+/// Each file's first line is read once and matched against every branch that has no session
+/// yet, with the same sanity checks and [`ScanOptions::admits`] filters as
+/// [`find_first_session`]. The walk stops early once every branch has a match.
+///
+/// # Returns
+///
+/// Returns a map from branch to its first matching session; unmatched branches are absent.
+///
+/// # Errors
+///
+/// Returns an error if the codexdir cannot be read.
+#[cfg(feature = "cli")]
+pub(super) fn find_first_sessions(
+    codexdir: &Path,
+    branches: &[String],
+    options: &ScanOptions,
+) -> Result<BTreeMap<String, Session>> {
+    let mut found = BTreeMap::new();
+    // First matches behind a stripped prefix, used for branches without an exact match.
+    let mut stripped = BTreeMap::new();
+    let mut skipped_by_owner = 0;
+    for path in SortedWalk::new(codexdir)?
+        .with_options(options)
+        .filter(|p| is_jsonl(p))
+    {
+        if branches.iter().all(|b| found.contains_key(b)) {
+            break;
+        }
+        let line = match candidate_first_line(&path, options) {
+            Ok(line) => line,
+            Err(decision) => {
+                skipped_by_owner += usize::from(matches!(decision, Decision::ForeignOwner { .. }));
+                continue;
+            }
+        };
+        // A line matches at most one branch exactly, but may match others without a prefix.
+        let pending: Vec<&String> = branches
+            .iter()
+            .filter(|b| !found.contains_key(*b))
+            .collect();
+        for branch in pending {
+            let Some((session, kind)) = session_from_line(path.clone(), &line, branch, options)
+                .ok()
+                .filter(|(s, _)| options.vet(s).is_ok())
+            else {
+                continue;
+            };
+            match kind {
+                BranchMatch::Exact => {
+                    found.insert(branch.clone(), session.with_file_metadata());
+                    break;
+                }
+                BranchMatch::Stripped { .. } => {
+                    stripped.entry(branch.clone()).or_insert(session);
+                }
+            }
+        }
+    }
+    for (branch, session) in stripped {
+        found
+            .entry(branch)
+            .or_insert_with(|| session.with_file_metadata());
+    }
+    log_owner_skips(skipped_by_owner);
+    Ok(found)
+}
+
+/// Distinct branches found by [`recent_branches`].
+#[cfg(feature = "cli")]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(super) struct BranchList {
+    /// Each branch with the modification time of its newest session file, newest first.
+    pub(super) branches: Vec<(String, Option<SystemTime>)>,
+    /// Whether the deadline passed or the cap was reached before the walk finished, so
+    /// branches may be missing.
+    pub(super) truncated: bool,
+}
+
+#[cfg(feature = "cli")]
+impl BranchList {
+    /// Keeps the `max` most recent branches (`--max-matches`), marking the list truncated if
+    /// any were dropped.
+    pub(super) fn cap(&mut self, max: Option<usize>) {
+        if let Some(max) = max
+            && self.branches.len() > max
+        {
+            self.branches.truncate(max);
+            self.truncated = true;
+        }
+    }
+}
+
+/// Lists the branches of the codexdir's rollout files, most recent session first.
+///
+/// The walk visits the dated layout newest first, so a branch is listed at its newest
+/// session. When `deadline` passes, the walk stops at the next file or directory and returns
+/// what it has found so far. With `max`, the walk also stops at the first branch past the
+/// `max` listed, which is left out.
+///
+/// # Errors
+///
+/// Returns an error if the codexdir cannot be read.
+#[cfg(feature = "cli")]
+pub(super) fn recent_branches(
+    codexdir: &Path,
+    deadline: Option<Instant>,
+    max: Option<usize>,
+) -> Result<BranchList> {
+    let mut walk = SortedWalk::newest_first(codexdir)?;
+    walk.deadline = deadline;
+    let mut list = BranchList::default();
+    let mut seen = BTreeSet::new();
+    for path in walk.by_ref().filter(|p| is_rollout_file_name(p)) {
+        let Ok(Some(line)) = read_first_line(&path) else {
+            continue;
+        };
+        let Some(branch) = parse_session_fields(&line)
+            .ok()
+            .and_then(|fields| non_empty(fields.branch))
+        else {
+            continue;
+        };
+        if seen.insert(branch.clone()) {
+            if max.is_some_and(|max| list.branches.len() >= max) {
+                list.truncated = true;
+                return Ok(list);
+            }
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+            list.branches.push((branch, modified));
+        }
+    }
+    list.truncated = walk.truncated;
+    Ok(list)
+}
+
+/// Lists the codexdir's rollout files, newest first (the order [`recent_branches`] visits
+/// them in).
+///
+/// # Errors
+///
+/// Returns an error if the codexdir cannot be read.
+#[cfg(feature = "cli")]
+pub(super) fn rollout_files_newest_first(codexdir: &Path) -> Result<Vec<PathBuf>> {
+    Ok(SortedWalk::newest_first(codexdir)?
+        .filter(|path| is_rollout_file_name(path))
+        .collect())
+}
+
+/// Iterates over every session file in the codex directory, in lexicographic order.
+///
+/// Unlike [`find_first_session`], nothing is filtered out: files whose first line cannot be
+/// read or parsed are yielded with [`SessionFile::fields`] set to an error description.
+///
+/// # Errors
+///
+/// Returns an error if the codexdir cannot be read.
+#[cfg(feature = "cli")]
+pub(super) fn session_files(codexdir: &Path) -> Result<impl Iterator<Item = SessionFile>> {
+    Ok(SortedWalk::new(codexdir)?
+        .filter(|p| is_jsonl(p))
+        .map(read_session_file))
+}
+
+#[cfg(feature = "cli")]
+fn read_session_file(path: PathBuf) -> SessionFile {
+    let meta = fs::metadata(&path).ok();
+    let fields = match read_first_line(&path) {
+        Ok(Some(line)) => parse_session_fields(&line),
+        Ok(None) => Err("empty file".to_owned()),
+        Err(err) => Err(format!("failed to read first line: {err}")),
+    };
+    SessionFile {
+        size: meta.as_ref().map_or(0, fs::Metadata::len),
+        modified: meta.and_then(|m| m.modified().ok()),
+        path,
+        fields,
+    }
+}
+
+/// Parses the first line of a session file into [`SessionFields`] without requiring any field.
+///
+/// # Errors
+///
+/// Returns why the line is not a JSON object: `not valid JSON: ...`, or `valid JSON but not an
+/// object (an array)`.
+#[cfg(feature = "cli")]
+pub(super) fn parse_session_fields(line: &str) -> std::result::Result<SessionFields, String> {
+    let event = jsonl::parse_record::<Event>(line).map_err(|err| err.to_string())?;
+    let Some(payload) = event.payload else {
+        return Ok(SessionFields::default());
+    };
+    let mut git = payload.git.unwrap_or_default();
+    Ok(SessionFields {
+        id: payload.id,
+        cwd: payload.cwd,
+        branch: git.take_branch(),
+        repository_url: git.repository_url,
+        commit_hash: git.commit_hash,
+        originator: payload.originator,
+        model: payload.model,
+    })
+}
+
+/// Trims an optional first-line value, treating a blank one as absent.
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_owned())
+        .filter(|value| !value.is_empty())
+}
+
+/// Checks if a path has a `.jsonl` extension.
+///
+/// # Arguments
+///
+/// * `path` - The path to check
+///
+/// # Returns
+///
+/// Returns `true` if the path has a `.jsonl` extension, `false` otherwise.
+pub(super) fn is_jsonl(path: &Path) -> bool {
+    path.extension() == Some(OsStr::new("jsonl"))
+}
+
+/// Checks if a file is named like a Codex rollout file (`rollout-*.jsonl`).
+pub(super) fn is_rollout_file_name(path: &Path) -> bool {
+    path.file_name()
+        .and_then(OsStr::to_str)
+        .is_some_and(|name| name.starts_with("rollout-") && name.ends_with(".jsonl"))
+}
+
+/// Returns the last UUID embedded in a file name (Codex names its files
+/// `rollout-<timestamp>-<uuid>.jsonl`), if there is one.
+pub(super) fn file_name_uuid(path: &Path) -> Option<&str> {
+    const UUID_LEN: usize = 36;
+    let stem = path.file_stem()?.to_str()?;
+    (0..=stem.len().checked_sub(UUID_LEN)?)
+        .rev()
+        .filter_map(|start| stem.get(start..start + UUID_LEN))
+        .find(|candidate| is_plausible_uuid(candidate))
+}
+
+/// Returns the UUID a session's file is named for, if it is not the session's own id.
+fn mismatched_file_id(session: &Session) -> Option<&str> {
+    file_name_uuid(&session.source_jsonl)
+        .filter(|file_id| !file_id.eq_ignore_ascii_case(&session.id))
+}
+
+/// Checks if a session id looks like a UUID (`8-4-4-4-12` hexadecimal groups).
+pub(super) fn is_plausible_uuid(id: &str) -> bool {
+    const GROUP_LENS: [usize; 5] = [8, 4, 4, 4, 12];
+    let groups: Vec<&str> = id.split('-').collect();
+    groups.len() == GROUP_LENS.len()
+        && groups
+            .iter()
+            .zip(GROUP_LENS)
+            .all(|(group, len)| group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Attempts to create a [`Session`] from a JSONL file if it matches the branch.
+///
+/// Reads the first line of the JSONL file and parses it to extract session information.
+/// Returns `Some(Session)` if the branch matches and the file passes the structural sanity
+/// checks (rollout file name, owner, UUID session id), `None` otherwise.
+///
+/// # Arguments
+///
+/// * `source_jsonl` - Path to the JSONL file
+/// * `branch` - The git branch name to match against
+/// * `options` - Options relaxing the sanity checks
+///
+/// # Returns
+///
+/// Returns [`Option<Session>`] if a matching session is found, `None` otherwise.
+///
+/// # See Also
+///
+/// * [`candidate_first_line`] - Reads the first line of a plausible session file
+/// * [`session_from_line`] - Matches the first line against the branch
+#[cfg(feature = "cli")]
+pub(super) fn session_from_jsonl(
+    source_jsonl: PathBuf,
+    branch: &str,
+    options: &ScanOptions,
+) -> Option<Session> {
+    let line = candidate_first_line(&source_jsonl, options).ok()?;
+    session_from_line(source_jsonl, &line, branch, options)
+        .ok()
+        .map(|(session, _)| options.retain_first_line(session.with_file_metadata(), line))
+}
+
+/// Reads the first line of `path` if it passes the rollout naming and ownership checks, or
+/// returns the [`Decision`] rejecting it.
+///
+/// Files whose modification time cannot be read are not rejected by `--since`.
+fn candidate_first_line(path: &Path, options: &ScanOptions) -> Result<String, Decision> {
+    if !options.any_filename && !is_rollout_file_name(path) {
+        debug!(path = %util::human_path(path), "skipping file not named rollout-*.jsonl");
+        return Err(Decision::NotRolloutName);
+    }
+    options.check_owner(path, file_owner)?;
+    if let Some(cutoff) = options.modified_after {
+        let modified = fs::metadata(path).and_then(|meta| meta.modified());
+        if modified.is_ok_and(|modified| modified < cutoff) {
+            debug!(path = %util::human_path(path), "skipping file modified before --since");
+            return Err(Decision::TooOld);
+        }
+    }
+    match read_first_line(path) {
+        Ok(Some(line)) => Ok(line),
+        Ok(None) => Err(Decision::Empty),
+        Err(err) => Err(Decision::Unreadable {
+            error: err.to_string(),
+        }),
+    }
+}
+
+/// Builds a [`Session`] from an already read first line if it matches the branch and has a
+/// UUID session id, or returns the [`Decision`] rejecting it.
+///
+/// # See Also
+///
+/// * [`parse_session_first_line`] - Parses session data from JSON
+fn session_from_line(
+    source_jsonl: PathBuf,
+    line: &str,
+    branch: &str,
+    options: &ScanOptions,
+) -> Result<(Session, BranchMatch), Decision> {
+    let (
+        SessionMeta {
+            cwd,
+            id,
+            branch: recorded,
+            commit_hash,
+            originator,
+            model,
+        },
+        kind,
+    ) = parse_session_first_line(line, branch, &options.strip_prefixes).accept()?;
+    if !is_plausible_uuid(&id) {
+        debug!(path = %util::human_path(&source_jsonl), id = %id, "skipping file whose session id is not a UUID");
+        return Err(Decision::NotUuid { id });
+    }
+    let cwd = util::map_path(&cwd, &options.map_paths).unwrap_or(cwd);
+    if util::is_foreign_absolute(&cwd) {
+        debug!(path = %util::human_path(&source_jsonl), cwd = %util::human_path(&cwd), "skipping session with foreign-platform cwd");
+        return Err(Decision::ForeignCwd { cwd });
+    }
+    let session = Session {
+        cwd,
+        id,
+        branch: Some(recorded),
+        source_jsonl,
+        commit_hash,
+        originator,
+        model,
+        size: None,
+        modified: None,
+        first_line: None,
+    };
+    Ok((session, kind))
+}
+
+/// Reads the first line from a file with a [`JsonlReader`] and its default limits.
+///
+/// A leading UTF-8 byte order mark and a trailing `\r` (CRLF line endings) are removed, so
+/// files written on Windows parse like their Unix equivalents.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file to read
+///
+/// # Returns
+///
+/// Returns [`io::Result<Option<String>>`] containing:
+/// * `Some(String)` - The first line if the file is not empty
+/// * `None` - If the file is empty
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened or read, or its first line is longer than
+/// [`jsonl::DEFAULT_MAX_LINE_BYTES`] or not UTF-8.
+pub(super) fn read_first_line(path: &Path) -> io::Result<Option<String>> {
+    match JsonlReader::open(path, Limits::default())?.next() {
+        Some(line) => Ok(Some(line?.1)),
+        None => Ok(None),
+    }
+}
+
+/// Parses the first line of a JSONL session file to extract session information.
+///
+/// Performs a fast-path check to avoid JSON parsing unless the branch name appears in the line.
+/// Then parses the JSON to extract git branch, working directory, session ID, and the
+/// optional commit hash.
+///
+/// # Arguments
+///
+/// * `line` - The first line of the JSONL file
+/// * `branch` - The git branch name to match against
+/// * `strip_prefixes` - Prefixes the recorded branch may carry in front of `branch`
+///
+/// # Returns
+///
+/// Returns the [`FirstLine`] outcome: the session metadata and how the branch matched, or
+/// whether the line records another branch, lacks fields, is a JSON value other than an
+/// object, or is not JSON at all.
+fn parse_session_first_line(line: &str, branch: &str, strip_prefixes: &[String]) -> FirstLine {
+    // Fast-path: avoid JSON parsing unless the branch appears on the line. A branch recorded
+    // behind a stripped prefix still contains `branch`, so this holds for those too.
+    if !line.contains(branch) {
+        return FirstLine::OtherBranch(None);
+    }
+
+    let event = match jsonl::parse_record::<Event>(line) {
+        Ok(event) => event,
+        Err(RecordError::InvalidJson(error)) => return FirstLine::InvalidJson(error),
+        Err(RecordError::NotAnObject(found)) => return FirstLine::NotAnObject(found),
+    };
+    let Event {
+        payload:
+            Some(Payload {
+                git: Some(mut git),
+                cwd: Some(cwd),
+                id: Some(id),
+                originator,
+                model,
+            }),
+    } = event
+    else {
+        return FirstLine::MissingFields;
+    };
+    let Some(got_branch) = git.take_branch() else {
+        return FirstLine::MissingFields;
+    };
+    let commit_hash = git.commit_hash;
+
+    let Some(kind) = match_branch(&got_branch, branch, strip_prefixes) else {
+        return FirstLine::OtherBranch(Some(got_branch));
+    };
+    let cwd = cwd.trim();
+    let id = id.trim();
+    if cwd.is_empty() || id.is_empty() {
+        return FirstLine::MissingFields;
+    }
+    let meta = SessionMeta {
+        cwd: PathBuf::from(cwd),
+        id: id.to_owned(),
+        branch: got_branch,
+        commit_hash: non_empty(commit_hash),
+        originator: non_empty(originator),
+        model: non_empty(model),
+    };
+    FirstLine::Matches(meta, kind)
+}
+
+/// Compares a recorded branch with the branch looked up, also trying it without each of
+/// `strip_prefixes` (empty prefixes are ignored).
+fn match_branch(recorded: &str, branch: &str, strip_prefixes: &[String]) -> Option<BranchMatch> {
+    if recorded == branch {
+        return Some(BranchMatch::Exact);
+    }
+    strip_prefixes
+        .iter()
+        .filter(|prefix| !prefix.is_empty())
+        .any(|prefix| recorded.strip_prefix(prefix.as_str()) == Some(branch))
+        .then(|| BranchMatch::Stripped {
+            found: recorded.to_owned(),
+        })
+}
+
+/// JSON deserialization structure for Codex event payload.
+#[derive(Debug, Deserialize)]
+struct Event {
+    /// The event payload containing session information.
+    payload: Option<Payload>,
+}
+
+/// JSON deserialization structure for Codex payload.
 #[derive(Debug, Deserialize)]
+struct Payload {
+    /// Git-related information.
+    git: Option<Git>,
+    /// The working directory where the session was created.
+    cwd: Option<String>,
+    /// The unique session identifier.
+    id: Option<String>,
+    /// The client that created the session (e.g. `codex_cli_rs`, `codex_exec`).
+    originator: Option<String>,
+    /// The model the session was created with.
+    model: Option<String>,
+}
+
+/// JSON deserialization structure for git information.
+///
+/// Older codex versions recorded the branch as `branch_name`; read both with
+/// [`Git::take_branch`].
+#[derive(Debug, Default, Deserialize)]
 struct Git {
     /// The git branch name.
     branch: Option<String>,
+    /// The git branch name, as recorded by older codex versions.
+    branch_name: Option<String>,
+    /// The commit checked out when the session was created.
+    commit_hash: Option<String>,
+    /// The remote URL of the repository.
+    #[cfg_attr(not(feature = "cli"), expect(dead_code))]
+    repository_url: Option<String>,
+}
+
+impl Git {
+    /// Takes the recorded branch: `branch`, else the older `branch_name`.
+    ///
+    /// When both are recorded and differ, `branch` wins and the discrepancy is logged at
+    /// debug level.
+    fn take_branch(&mut self) -> Option<String> {
+        let legacy = self.branch_name.take();
+        match (self.branch.take(), legacy) {
+            (Some(branch), Some(legacy)) => {
+                if branch != legacy {
+                    debug!(branch = %branch, branch_name = %legacy, "the first line records two different branches; using `branch`");
+                }
+                Some(branch)
+            }
+            (branch, legacy) => branch.or(legacy),
+        }
+    }
 }
 
 /// A lexicographically sorted directory walker.
 ///
 /// Performs a depth-first traversal of a directory tree, returning files in lexicographic
 /// order by their full path. This roughly matches `fd`'s default output ordering.
+/// [`SortedWalk::newest_first`] walks in descending order instead, which visits Codex's dated
+/// `YYYY/MM/DD` directories newest first.
 ///
-/// Symlinks are skipped during traversal.
+/// Symlinks are skipped during traversal. Entry types come from
+/// [`fs::DirEntry::file_type`], which most platforms answer from the directory listing, so
+/// the walk costs one `read_dir` per directory and no `stat` per entry; on a network mount
+/// each of those would be a round trip.
 struct SortedWalk {
     /// Binary heap used to maintain sorted order of paths.
-    heap: BinaryHeap<Reverse<PathBuf>>,
+    heap: BinaryHeap<WalkEntry>,
+    /// Whether paths are visited in descending order.
+    descending: bool,
+    /// Whether `.jsonl` entries are taken to be regular files without checking their type
+    /// (see [`ScanOptions::skip_symlink_check`]).
+    trust_jsonl_names: bool,
+    /// When set, the walk ends early once this instant has passed.
+    deadline: Option<Instant>,
+    /// Whether the walk ended at [`SortedWalk::deadline`] with paths left to visit.
+    truncated: bool,
+    /// The directory walked, which [`SortedWalk::exclusions`] are relative to.
+    root: PathBuf,
+    /// Paths not visited: the root's `.amgignore` plus [`ScanOptions::exclude`].
+    exclusions: Exclusions,
+}
+
+/// A path in the walk heap, ordered so the heap pops the next path in walk order.
+#[derive(Debug, PartialEq, Eq)]
+struct WalkEntry {
+    path: PathBuf,
+    /// Whether the path is a directory to descend into (otherwise a file to yield).
+    is_dir: bool,
+    descending: bool,
+}
+
+impl Ord for WalkEntry {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let order = self.path.cmp(&other.path);
+        if self.descending {
+            order
+        } else {
+            order.reverse()
+        }
+    }
+}
+
+impl PartialOrd for WalkEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl SortedWalk {
@@ -212,13 +1883,106 @@ impl SortedWalk {
     /// Returns an error if the root directory cannot be read. Other unreadable directories
     /// encountered during traversal are simply skipped.
     fn new(root: &Path) -> Result<Self> {
+        Self::with_order(root, false)
+    }
+
+    /// Creates a walker visiting paths in descending lexicographic order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the root directory cannot be read.
+    #[cfg(feature = "cli")]
+    fn newest_first(root: &Path) -> Result<Self> {
+        Self::with_order(root, true)
+    }
+
+    fn with_order(root: &Path, descending: bool) -> Result<Self> {
         // Fail fast for the root dir; other unreadable dirs are simply skipped during traversal.
         fs::read_dir(root)
-            .with_context(|| format!("failed to read directory {}", root.display()))?;
+            .with_context(|| format!("failed to read directory {}", util::human_path(root)))?;
+
+        let mut walk = Self {
+            heap: BinaryHeap::new(),
+            descending,
+            trust_jsonl_names: false,
+            deadline: None,
+            truncated: false,
+            root: root.to_owned(),
+            exclusions: Exclusions::load(root),
+        };
+        if let Ok(meta) = fs::symlink_metadata(root) {
+            walk.push_typed(root.to_owned(), meta.file_type());
+        }
+        Ok(walk)
+    }
+
+    /// Creates a walker over the files directly in `dir`, a directory under `root`, that a
+    /// walk of `root` with `options` would visit: the same exclusions and symlink checks
+    /// apply, and subdirectories are not descended into.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `root` or `dir` cannot be read.
+    fn entries_of(root: &Path, dir: &Path, options: &ScanOptions) -> Result<Self> {
+        let mut walk = Self::with_order(root, false)?.with_options(options);
+        walk.heap.clear();
+        fs::read_dir(dir)
+            .with_context(|| format!("failed to read directory {}", util::human_path(dir)))?
+            .flatten()
+            .for_each(|entry| walk.push_entry(entry));
+        walk.heap.retain(|entry| !entry.is_dir);
+        Ok(walk)
+    }
+
+    /// Applies [`ScanOptions::skip_symlink_check`] and [`ScanOptions::exclude`] to the walk.
+    fn with_options(mut self, options: &ScanOptions) -> Self {
+        self.trust_jsonl_names = options.skip_symlink_check;
+        self.exclusions.extend(&options.exclude);
+        self
+    }
+
+    /// Queues a directory entry, skipping symlinks, special files, and excluded paths.
+    fn push_entry(&mut self, entry: fs::DirEntry) {
+        let path = entry.path();
+        if self.trust_jsonl_names && is_jsonl(&path) {
+            if !self.excluded(&path, false) {
+                self.push(path, false);
+            }
+            return;
+        }
+        if let Ok(file_type) = entry.file_type()
+            && !self.excluded(&path, file_type.is_dir())
+        {
+            self.push_typed(path, file_type);
+        }
+    }
 
-        let mut heap = BinaryHeap::new();
-        heap.push(Reverse(root.to_owned()));
-        Ok(Self { heap })
+    /// Returns whether `path` matches [`SortedWalk::exclusions`].
+    fn excluded(&self, path: &Path, is_dir: bool) -> bool {
+        if self.exclusions.is_empty() {
+            return false;
+        }
+        let excluded = path
+            .strip_prefix(&self.root)
+            .is_ok_and(|relative| self.exclusions.excludes(relative, is_dir));
+        if excluded {
+            debug!(path = %util::human_path(path), "excluded from the walk");
+        }
+        excluded
+    }
+
+    fn push_typed(&mut self, path: PathBuf, file_type: fs::FileType) {
+        if file_type.is_dir() || file_type.is_file() {
+            self.push(path, file_type.is_dir());
+        }
+    }
+
+    fn push(&mut self, path: PathBuf, is_dir: bool) {
+        self.heap.push(WalkEntry {
+            path,
+            is_dir,
+            descending: self.descending,
+        });
     }
 }
 
@@ -226,29 +1990,1551 @@ impl Iterator for SortedWalk {
     type Item = PathBuf;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some(Reverse(path)) = self.heap.pop() {
-            let meta = match fs::symlink_metadata(&path) {
-                Ok(m) => m,
-                Err(_) => continue,
-            };
-            if meta.file_type().is_symlink() {
-                continue;
-            }
-
-            if meta.is_dir() {
-                let rd = match fs::read_dir(&path) {
-                    Ok(rd) => rd,
-                    Err(_) => continue,
-                };
-                rd.flatten().for_each(|e| self.heap.push(Reverse(e.path())));
-                continue;
+        while let Some(WalkEntry { path, is_dir, .. }) = self.heap.pop() {
+            // Checked once per file and per directory, so a slow store cannot overrun it much.
+            if self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                self.truncated = true;
+                self.heap.clear();
+                return None;
             }
-
-            if meta.is_file() {
+            if !is_dir {
                 return Some(path);
             }
+            if let Ok(rd) = fs::read_dir(&path) {
+                rd.flatten().for_each(|entry| self.push_entry(entry));
+            }
         }
 
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::temp_dir;
+    use rstest::rstest;
+    use std::time::Duration;
+
+    fn write_file(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).expect("Failed to create parent dir");
+        fs::write(path, content).expect("Failed to write file");
+    }
+
+    const ID_A: &str = "0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b";
+    const ID_B: &str = "0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6c";
+    const ID_C: &str = "0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6d";
+
+    fn find(dir: &Path, branch: &str) -> ScanOutcome {
+        find_first_session(dir, branch, &ScanOptions::default()).expect("scan should succeed")
+    }
+
+    fn session_line(branch: &str, cwd: &str, id: &str) -> String {
+        serde_json::json!({
+            "payload": { "git": { "branch": branch }, "cwd": cwd, "id": id }
+        })
+        .to_string()
+    }
+
+    #[rstest]
+    #[case(
+        r#"{"payload":{"git":{"branch":"main","commit_hash":"abc123"},"cwd":"/w","id":"i"}}"#,
+        Some("abc123")
+    )]
+    #[case(
+        r#"{"payload":{"git":{"branch":"main","commit_hash":"  "},"cwd":"/w","id":"i"}}"#,
+        None
+    )]
+    #[case(r#"{"payload":{"git":{"branch":"main"},"cwd":"/w","id":"i"}}"#, None)]
+    fn parses_optional_commit_hash(#[case] line: &str, #[case] expected: Option<&str>) {
+        let (meta, _) = parse_session_first_line(line, "main", &[])
+            .accept()
+            .expect("line should match");
+
+        assert_eq!(meta.commit_hash.as_deref(), expected);
+        assert_eq!(meta.cwd, PathBuf::from("/w"));
+        assert_eq!(meta.id, "i");
+    }
+
+    #[rstest]
+    #[case(
+        r#"{"payload":{"git":{"branch":"dev"},"cwd":"/w","id":"i"}}"#,
+        FirstLine::OtherBranch(None)
+    )]
+    #[case(
+        r#"{"payload":{"git":{"branch":"domain"},"cwd":"/w","id":"i"}}"#,
+        FirstLine::OtherBranch(Some("domain".into()))
+    )]
+    #[case(
+        r#"{"payload":{"git":{"branch":"main"},"cwd":" ","id":"i"}}"#,
+        FirstLine::MissingFields
+    )]
+    #[case(
+        r#"{"payload":{"git":{"branch":"main"},"cwd":"/w"}}"#,
+        FirstLine::MissingFields
+    )]
+    #[case(r#"{"payload":{"cwd":"/main","id":"i"}}"#, FirstLine::MissingFields)]
+    #[case(r#"{"main":true}"#, FirstLine::MissingFields)]
+    #[case(
+        r#"[{"git":{"branch":"main"}}, "/w", "i"]"#,
+        FirstLine::NotAnObject("an array")
+    )]
+    #[case(r#"  "main""#, FirstLine::NotAnObject("a string"))]
+    fn classifies_non_matching_or_incomplete_lines(
+        #[case] line: &str,
+        #[case] expected: FirstLine,
+    ) {
+        assert_eq!(parse_session_first_line(line, "main", &[]), expected);
+    }
+
+    #[rstest]
+    #[case::not_json("not json main")]
+    #[case::truncated(r#"{"payload":{"git":{"branch":"main""#)]
+    #[case::truncated_array(r#"["main", "#)]
+    #[case::wrong_field_type(r#"{"payload":{"git":{"branch":"main"},"cwd":"/w","id":1}}"#)]
+    fn classifies_invalid_json(#[case] line: &str) {
+        assert!(matches!(
+            parse_session_first_line(line, "main", &[]),
+            FirstLine::InvalidJson(_)
+        ));
+    }
+
+    #[rstest]
+    #[case(FirstLine::OtherBranch(None), Decision::BranchMismatch { found: None })]
+    #[case(FirstLine::MissingFields, Decision::MissingFields)]
+    #[case(FirstLine::NotAnObject("null"), Decision::NotAnObject { found: "null" })]
+    #[case(
+        FirstLine::InvalidJson("EOF".into()),
+        Decision::InvalidJson { error: "EOF".into() }
+    )]
+    fn rejected_lines_become_skip_decisions(
+        #[case] outcome: FirstLine,
+        #[case] expected: Decision,
+    ) {
+        assert_eq!(outcome.accept(), Err(expected));
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    #[case("[1, 2]", "valid JSON but not an object (an array)")]
+    #[case("42", "valid JSON but not an object (a number)")]
+    #[case("true", "valid JSON but not an object (a boolean)")]
+    #[case("null", "valid JSON but not an object (null)")]
+    fn session_fields_name_non_object_lines(#[case] line: &str, #[case] expected: &str) {
+        assert_eq!(parse_session_fields(line), Err(expected.to_owned()));
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    #[case::only_old_key(r#"{"branch_name":"main"}"#, Some("main"))]
+    #[case::only_new_key(r#"{"branch":"main"}"#, Some("main"))]
+    #[case::both_agreeing(r#"{"branch":"main","branch_name":"main"}"#, Some("main"))]
+    #[case::both_disagreeing(r#"{"branch":"main","branch_name":"old-main"}"#, Some("main"))]
+    #[case::neither(r#"{"commit_hash":"abc"}"#, None)]
+    fn reads_the_branch_from_either_key(#[case] git: &str, #[case] expected: Option<&str>) {
+        let line = format!(r#"{{"payload":{{"git":{git},"cwd":"/w","id":"i"}}}}"#);
+
+        let parsed = parse_session_first_line(&line, "main", &[]);
+        let fields = parse_session_fields(&line).unwrap();
+
+        assert_eq!(fields.branch.as_deref(), expected);
+        match expected {
+            Some(_) => assert!(matches!(parsed, FirstLine::Matches(_, BranchMatch::Exact))),
+            None => assert!(!matches!(parsed, FirstLine::Matches(..))),
+        }
+    }
+
+    #[test]
+    fn the_new_key_wins_when_the_keys_disagree() {
+        let line =
+            r#"{"payload":{"git":{"branch":"main","branch_name":"old-main"},"cwd":"/w","id":"i"}}"#;
+
+        assert_eq!(
+            parse_session_first_line(line, "old-main", &[]),
+            FirstLine::OtherBranch(Some("main".into()))
+        );
+    }
+
+    #[rstest]
+    fn observes_a_decision_for_every_file_up_to_the_match(#[from(temp_dir)] dir: PathBuf) {
+        write_file(&dir.join("a-notes.txt"), "main");
+        write_file(&dir.join("b.jsonl"), &session_line("main", "/w", ID_A));
+        write_file(&dir.join("rollout-c.jsonl"), "");
+        write_file(
+            &dir.join("rollout-d.jsonl"),
+            &session_line("main", "/w", "not-a-uuid"),
+        );
+        write_file(
+            &dir.join("rollout-e.jsonl"),
+            &session_line("main", "/w", ID_A),
+        );
+        write_file(
+            &dir.join("rollout-f.jsonl"),
+            &session_line("main", "/w", ID_B),
+        );
+
+        let mut decisions = Vec::new();
+        let outcome = find_first_session_observed(&dir, "main", &ScanOptions::default(), |p, d| {
+            decisions.push((p.file_name().unwrap().to_owned(), d.clone()));
+        })
+        .expect("scan should succeed");
+
+        assert_eq!(outcome.session.expect("should match").id, ID_A);
+        assert_eq!(
+            decisions,
+            [
+                ("a-notes.txt".into(), Decision::NotJsonl),
+                ("b.jsonl".into(), Decision::NotRolloutName),
+                ("rollout-c.jsonl".into(), Decision::Empty),
+                (
+                    "rollout-d.jsonl".into(),
+                    Decision::NotUuid {
+                        id: "not-a-uuid".into()
+                    }
+                ),
+                ("rollout-e.jsonl".into(), Decision::Matched),
+            ]
+        );
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    #[case(
+        r#"{"payload":{"git":{"branch":"main","repository_url":"git@host:r.git","commit_hash":"abc"},"cwd":"/w","id":"i"}}"#,
+        SessionFields {
+            id: Some("i".into()),
+            cwd: Some("/w".into()),
+            branch: Some("main".into()),
+            repository_url: Some("git@host:r.git".into()),
+            commit_hash: Some("abc".into()),
+            ..Default::default()
+        }
+    )]
+    #[case(
+        r#"{"payload":{"id":"i","originator":"codex_exec","model":"gpt-5-codex"}}"#,
+        SessionFields {
+            id: Some("i".into()),
+            originator: Some("codex_exec".into()),
+            model: Some("gpt-5-codex".into()),
+            ..Default::default()
+        }
+    )]
+    #[case(r#"{"payload":{"id":"i"}}"#, SessionFields { id: Some("i".into()), ..Default::default() })]
+    #[case(r#"{"type":"other"}"#, SessionFields::default())]
+    fn parses_available_session_fields(#[case] line: &str, #[case] expected: SessionFields) {
+        assert_eq!(parse_session_fields(line), Ok(expected));
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    #[case("not json")]
+    #[case(r#"{"payload":{"id":42}}"#)]
+    fn reports_unparseable_first_lines(#[case] line: &str) {
+        assert!(parse_session_fields(line).is_err());
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn session_files_yields_every_jsonl_file_in_order(#[from(temp_dir)] dir: PathBuf) {
+        write_file(&dir.join("b.jsonl"), &session_line("dev", "/tmp/b", ID_B));
+        write_file(&dir.join("a.jsonl"), "garbage\n");
+        write_file(&dir.join("c.jsonl"), "");
+        write_file(&dir.join("notes.txt"), "ignored");
+
+        let files: Vec<_> = session_files(&dir).expect("scan should succeed").collect();
+
+        let paths: Vec<_> = files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(
+            paths,
+            [
+                dir.join("a.jsonl"),
+                dir.join("b.jsonl"),
+                dir.join("c.jsonl")
+            ]
+        );
+        assert!(files[0].fields.is_err());
+        assert_eq!(files[0].size, 8);
+        assert!(files[0].modified.is_some());
+        assert_eq!(
+            files[1].fields.as_ref().unwrap().branch.as_deref(),
+            Some("dev")
+        );
+        assert_eq!(files[2].fields, Err("empty file".to_owned()));
+    }
+
+    #[rstest]
+    fn counts_zero_jsonl_files_and_keeps_samples(#[from(temp_dir)] dir: PathBuf) {
+        write_file(&dir.join("codex-tui.log"), "log line");
+        write_file(&dir.join("nested/history.txt"), "text");
+
+        let outcome = find(&dir, "main");
+
+        assert!(outcome.session.is_none());
+        assert_eq!(outcome.stats.files_scanned, 0);
+        assert_eq!(
+            outcome.stats.sample_files,
+            [dir.join("codex-tui.log"), dir.join("nested/history.txt")]
+        );
+    }
+
+    #[rstest]
+    fn caps_sample_files(#[from(temp_dir)] dir: PathBuf) {
+        (0..MAX_SAMPLE_FILES + 2).for_each(|i| write_file(&dir.join(format!("{i}.log")), ""));
+
+        let outcome = find(&dir, "main");
+
+        assert_eq!(outcome.stats.sample_files.len(), MAX_SAMPLE_FILES);
+    }
+
+    #[rstest]
+    fn counts_jsonl_files_when_none_match(#[from(temp_dir)] dir: PathBuf) {
+        write_file(
+            &dir.join("rollout-a.jsonl"),
+            &session_line("dev", "/tmp", ID_A),
+        );
+        write_file(
+            &dir.join("rollout-b.jsonl"),
+            &session_line("dev", "/tmp", ID_B),
+        );
+
+        let outcome = find(&dir, "main");
+
+        assert!(outcome.session.is_none());
+        assert_eq!(outcome.stats.files_scanned, 2);
+        assert_eq!(outcome.stats.files_parsed, 2);
+        assert!(outcome.stats.sample_files.is_empty());
+    }
+
+    #[rstest]
+    fn returns_first_match_in_lexicographic_order(#[from(temp_dir)] dir: PathBuf) {
+        write_file(
+            &dir.join("rollout-b.jsonl"),
+            &session_line("main", "/tmp/b", ID_B),
+        );
+        write_file(
+            &dir.join("rollout-a.jsonl"),
+            &session_line("main", "/tmp/a", ID_A),
+        );
+
+        let outcome = find(&dir, "main");
+        let session = outcome.session.expect("should match a session");
+
+        assert_eq!(session.id, ID_A);
+        assert_eq!(session.source_jsonl, dir.join("rollout-a.jsonl"));
+        assert_eq!(outcome.stats.files_scanned, 1);
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn newest_first_stops_at_the_newest_match(#[from(temp_dir)] dir: PathBuf) {
+        let sessions = dir.join(SESSIONS_DIR);
+        write_file(
+            &sessions.join("2025/03/08/rollout-a.jsonl"),
+            &session_line("main", "/tmp/a", ID_A),
+        );
+        write_file(
+            &sessions.join("2025/03/09/rollout-b.jsonl"),
+            &session_line("main", "/tmp/b", ID_B),
+        );
+        write_file(
+            &sessions.join("2025/03/10/rollout-c.jsonl"),
+            &session_line("dev", "/tmp/c", ID_C),
+        );
+
+        let outcome = find_newest_session(&dir, "main", &ScanOptions::default()).unwrap();
+
+        assert_eq!(outcome.session.unwrap().id, ID_B);
+        assert_eq!(outcome.stats.files_scanned, 2);
+        assert_eq!(outcome.stats.files_parsed, 2);
+        let none = find_newest_session(&dir, "gone", &ScanOptions::default()).unwrap();
+        assert!(none.session.is_none());
+        assert_eq!(none.stats.files_scanned, 3);
+    }
+
+    #[rstest]
+    #[case::first(0)]
+    #[case::third(2)]
+    #[case::last(9)]
+    fn counts_files_up_to_the_match(#[from(temp_dir)] dir: PathBuf, #[case] position: usize) {
+        for i in 0..10 {
+            let branch = if i == position { "main" } else { "dev" };
+            let name = format!("rollout-{i}.jsonl");
+            write_file(&dir.join(name), &session_line(branch, "/tmp", ID_A));
+        }
+        // Read but never parsed: empty, and not named like a rollout file.
+        write_file(&dir.join("rollout-00.jsonl"), "");
+        write_file(
+            &dir.join("notes.jsonl"),
+            &session_line("main", "/tmp", ID_A),
+        );
+
+        let outcome = find(&dir, "main");
+
+        assert!(outcome.session.is_some());
+        let skipped_before = if position == 0 { 1 } else { 2 };
+        assert_eq!(outcome.stats.files_scanned, position + 1 + skipped_before);
+        assert_eq!(outcome.stats.files_parsed, position + 1);
+    }
+
+    #[cfg(feature = "cli")]
+    #[cfg(unix)]
+    #[rstest]
+    fn walk_visits_only_what_the_walk_would(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = dir.join("codex");
+        let file = codexdir.join("sessions/rollout-a.jsonl");
+        write_file(&file, &session_line("main", "/w", ID_A));
+        let link = codexdir.join("sessions/rollout-link.jsonl");
+        std::os::unix::fs::symlink(&file, &link).unwrap();
+        let outside = dir.join("rollout-outside.jsonl");
+        write_file(&outside, &session_line("main", "/w", ID_B));
+        let options = ScanOptions::default();
+
+        assert!(walk_visits(&codexdir, &file, &options));
+        assert!(!walk_visits(&codexdir, &link, &options));
+        assert!(!walk_visits(&codexdir, &outside, &options));
+        let trusting = ScanOptions {
+            skip_symlink_check: true,
+            ..ScanOptions::default()
+        };
+        assert!(walk_visits(&codexdir, &link, &trusting));
+        let excluding = ScanOptions {
+            exclude: Exclusions::from_args(&["sessions/".to_owned()]).unwrap(),
+            ..ScanOptions::default()
+        };
+        assert!(!walk_visits(&codexdir, &file, &excluding));
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn cache_keys_tell_filters_apart() {
+        let plain = ScanOptions::default();
+        let keys = [
+            ScanOptions {
+                under: Some(PathBuf::from("/w")),
+                ..ScanOptions::default()
+            },
+            ScanOptions {
+                owner: Some(1000),
+                ..ScanOptions::default()
+            },
+            ScanOptions {
+                strip_prefixes: vec!["me/".to_owned()],
+                ..ScanOptions::default()
+            },
+            ScanOptions {
+                exclude: Exclusions::from_args(&["archived".to_owned()]).unwrap(),
+                ..ScanOptions::default()
+            },
+            ScanOptions {
+                modified_after: Some(SystemTime::UNIX_EPOCH),
+                ..ScanOptions::default()
+            },
+        ]
+        .map(|options| options.cache_key());
+
+        assert!(keys.iter().all(|key| *key != plain.cache_key()), "{keys:?}");
+        let kept = ScanOptions {
+            keep_first_line: true,
+            ..ScanOptions::default()
+        };
+        assert_eq!(kept.cache_key(), plain.cache_key());
+    }
+
+    #[rstest]
+    fn under_skips_sessions_outside_prefix(#[from(temp_dir)] dir: PathBuf) {
+        let acme = dir.join("work/acme/api");
+        let other = dir.join("work/other/api");
+        fs::create_dir_all(&acme).unwrap();
+        fs::create_dir_all(&other).unwrap();
+        write_file(
+            &dir.join("codex/rollout-a.jsonl"),
+            &session_line("main", other.to_str().unwrap(), ID_A),
+        );
+        write_file(
+            &dir.join("codex/rollout-b.jsonl"),
+            &session_line("main", acme.to_str().unwrap(), ID_B),
+        );
+        let options = ScanOptions {
+            under: Some(fs::canonicalize(dir.join("work/acme")).unwrap()),
+            ..Default::default()
+        };
+
+        let outcome =
+            find_first_session(&dir.join("codex"), "main", &options).expect("scan should succeed");
+
+        assert_eq!(outcome.session.expect("should match").id, ID_B);
+        assert_eq!(outcome.stats.excluded_by_prefix, 1);
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    #[case::codex_home(&["sessions/2025/10/14/rollout-a.jsonl", "config.toml"], true)]
+    #[case::sessions_dir(&["2025/10/14/rollout-a.jsonl"], true)]
+    #[case::flat_rollouts(&["rollout-a.jsonl"], true)]
+    #[case::empty(&[], false)]
+    #[case::home_like_decoy(
+        &["Documents/notes.txt", ".bashrc", "projects/app/src/main.rs", "export.jsonl"],
+        false
+    )]
+    fn probes_for_codex_layouts(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] files: &[&str],
+        #[case] codex: bool,
+    ) {
+        for file in files {
+            write_file(&dir.join(file), "{}");
+        }
+
+        let probe = probe_codexdir(&dir).unwrap();
+
+        assert_eq!(probe == Probe::Codex, codex, "{probe:?}");
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn probe_points_at_a_nested_codex_home(#[from(temp_dir)] dir: PathBuf) {
+        write_file(&dir.join(".bashrc"), "");
+        write_file(&dir.join(".codex/sessions/2025/rollout-a.jsonl"), "{}");
+
+        assert_eq!(
+            probe_codexdir(&dir).unwrap(),
+            Probe::Unrecognized {
+                nested: Some(dir.join(".codex"))
+            }
+        );
+    }
+
+    #[rstest]
+    fn since_skips_files_modified_before_the_window(#[from(temp_dir)] dir: PathBuf) {
+        let old = dir.join("rollout-a.jsonl");
+        write_file(&old, &session_line("main", "/tmp/a", ID_A));
+        write_file(
+            &dir.join("rollout-b.jsonl"),
+            &session_line("main", "/tmp/b", ID_B),
+        );
+        let now = SystemTime::now();
+        fs::File::options()
+            .write(true)
+            .open(&old)
+            .and_then(|file| file.set_modified(now - Duration::from_secs(3_600)))
+            .unwrap();
+        let options = ScanOptions {
+            modified_after: Some(now - Duration::from_secs(60)),
+            ..Default::default()
+        };
+
+        let outcome = find_first_session(&dir, "main", &options).expect("scan should succeed");
+
+        assert_eq!(outcome.session.expect("should match").id, ID_B);
+        assert_eq!(outcome.stats.excluded_by_age, 1);
+    }
+
+    #[rstest]
+    #[case::relative_cwd("tmp/a")]
+    #[case::newline_in_cwd("/tmp/a\nb")]
+    #[case::escape_in_cwd("/tmp/\u{1b}[2Ja")]
+    fn weird_cwds_are_skipped_unless_allowed(#[from(temp_dir)] dir: PathBuf, #[case] cwd: &str) {
+        write_file(
+            &dir.join("rollout-a.jsonl"),
+            &session_line("main", cwd, ID_A),
+        );
+        write_file(
+            &dir.join("rollout-b.jsonl"),
+            &session_line("main", "/tmp/b", ID_B),
+        );
+
+        let strict = find_first_session(&dir, "main", &ScanOptions::default()).unwrap();
+        let allowed = ScanOptions {
+            allow_weird_paths: true,
+            ..Default::default()
+        };
+        let relaxed = find_first_session(&dir, "main", &allowed).unwrap();
+
+        assert_eq!(strict.session.expect("should match").id, ID_B);
+        let relaxed = relaxed.session.expect("should match");
+        assert_eq!(
+            (relaxed.id.as_str(), relaxed.cwd),
+            (ID_A, PathBuf::from(cwd))
+        );
+    }
+
+    #[cfg(feature = "cli")]
+    #[cfg(unix)]
+    #[rstest]
+    fn foreign_cwds_are_skipped_unless_mapped(#[from(temp_dir)] dir: PathBuf) {
+        write_file(
+            &dir.join("rollout-a.jsonl"),
+            &session_line("main", r"C:\Users\dev\proj", ID_A),
+        );
+        write_file(
+            &dir.join("rollout-b.jsonl"),
+            &session_line("main", "/tmp/b", ID_B),
+        );
+        let mut decisions = Vec::new();
+
+        let native = find_first_session_observed(&dir, "main", &ScanOptions::default(), |_, d| {
+            decisions.push(d.clone());
+        })
+        .unwrap();
+        let mapped = ScanOptions {
+            map_paths: vec![util::parse_path_mapping(r"C:\Users\dev=/home/me").unwrap()],
+            ..Default::default()
+        };
+        let mapped = find_first_session(&dir, "main", &mapped).unwrap();
+
+        assert_eq!(native.session.expect("should match").id, ID_B);
+        assert!(
+            matches!(decisions[0], Decision::ForeignCwd { .. }),
+            "{decisions:?}"
+        );
+        let mapped = mapped.session.expect("should match");
+        assert_eq!(
+            (mapped.id.as_str(), mapped.cwd),
+            (ID_A, PathBuf::from("/home/me/proj"))
+        );
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn tag_filter_skips_untagged_sessions(#[from(temp_dir)] dir: PathBuf) {
+        write_file(
+            &dir.join("rollout-a.jsonl"),
+            &session_line("main", "/tmp/a", ID_A),
+        );
+        write_file(
+            &dir.join("rollout-b.jsonl"),
+            &session_line("main", "/tmp/b", ID_B),
+        );
+        let options = ScanOptions {
+            tagged: Some(TagFilter {
+                tag: "demo-prep".into(),
+                ids: BTreeSet::from([ID_B.to_owned()]),
+            }),
+            ..Default::default()
+        };
+
+        let outcome = find_first_session(&dir, "main", &options).expect("scan should succeed");
+        let latest = find_latest_session(&dir, &options).expect("scan should succeed");
+
+        assert_eq!(outcome.session.expect("should match").id, ID_B);
+        assert_eq!(outcome.stats.excluded_by_tag, Some(("demo-prep".into(), 1)));
+        assert_eq!(latest.expect("should match").0.id, ID_B);
+    }
+
+    #[rstest]
+    #[case::any(None, None, false, Some(ID_A))]
+    #[case::originator(Some("codex_cli_rs"), None, false, Some(ID_B))]
+    #[case::model(None, Some("gpt-5-codex"), false, Some(ID_B))]
+    #[case::unknown_is_kept(Some("codex_cli_rs"), Some("o3"), false, Some(ID_C))]
+    #[case::unknown_is_required(Some("codex_cli_rs"), Some("o3"), true, None)]
+    fn metadata_filters_treat_missing_fields_as_unknown(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] originator: Option<&str>,
+        #[case] model: Option<&str>,
+        #[case] require_metadata: bool,
+        #[case] expected: Option<&str>,
+    ) {
+        let line = |id: &str, originator: &str, model: Option<&str>| {
+            serde_json::json!({
+                "payload": {
+                    "git": { "branch": "main" },
+                    "cwd": "/w",
+                    "id": id,
+                    "originator": originator,
+                    "model": model,
+                }
+            })
+            .to_string()
+        };
+        write_file(
+            &dir.join("rollout-a.jsonl"),
+            &line(ID_A, "ci-bot", Some("gpt-5")),
+        );
+        write_file(
+            &dir.join("rollout-b.jsonl"),
+            &line(ID_B, "codex_cli_rs", Some("gpt-5-codex")),
+        );
+        write_file(
+            &dir.join("rollout-c.jsonl"),
+            &line(ID_C, "codex_cli_rs", None),
+        );
+        let options = ScanOptions {
+            originator: originator.map(str::to_owned),
+            model: model.map(str::to_owned),
+            require_metadata,
+            ..Default::default()
+        };
+
+        let outcome = find_first_session(&dir, "main", &options).expect("scan should succeed");
+
+        assert_eq!(outcome.session.map(|s| s.id).as_deref(), expected);
+        if expected.is_none() {
+            assert_eq!(outcome.stats.excluded_by_metadata, 3);
+        }
+    }
+
+    #[rstest]
+    #[case("\u{feff}{line}\r\n{\"type\":\"message\"}\r\n")]
+    #[case("\u{feff}{line}\n")]
+    #[case("{line}\r\n")]
+    #[case("{line}\r")]
+    fn windows_encoded_files_match_like_unix_ones(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] template: &str,
+    ) {
+        let line = session_line("main", "/work/api", ID_A);
+        write_file(&dir.join("unix/rollout-a.jsonl"), &format!("{line}\n"));
+        write_file(
+            &dir.join("windows/rollout-a.jsonl"),
+            &template.replace("{line}", &line),
+        );
+
+        let unix = find(&dir.join("unix"), "main")
+            .session
+            .expect("unix file should match");
+        let windows = find(&dir.join("windows"), "main")
+            .session
+            .expect("windows file should match");
+
+        assert_eq!(windows.id, unix.id);
+        assert_eq!(windows.cwd, unix.cwd);
+        assert_eq!(windows.commit_hash, unix.commit_hash);
+    }
+
+    fn set_mtime(path: &Path, secs: u64) {
+        fs::File::options()
+            .append(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[rstest]
+    fn prefers_the_newest_copy_of_a_duplicated_session(#[from(temp_dir)] dir: PathBuf) {
+        let day = dir.join("sessions/2025/01/02");
+        let original = day.join(format!("rollout-2025-01-02T08-00-00-{ID_A}.jsonl"));
+        let conflicted = day.join(format!("rollout-2025-01-02T08-00-00-{ID_A} (1).jsonl"));
+        let other = day.join(format!("rollout-2025-01-02T09-00-00-{ID_B}.jsonl"));
+        write_file(&original, &session_line("main", "/w", ID_A));
+        write_file(&conflicted, &session_line("main", "/w", ID_A));
+        write_file(&other, &session_line("main", "/w", ID_B));
+        set_mtime(&original, 1_000);
+        set_mtime(&conflicted, 2_000);
+        set_mtime(&other, 3_000);
+
+        let session = find(&dir, "main").session.unwrap();
+
+        assert_eq!(session.source_jsonl, conflicted);
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn excluded_copies_are_not_preferred(#[from(temp_dir)] dir: PathBuf) {
+        let day = dir.join("sessions/2025/01/02");
+        let original = day.join(format!("rollout-2025-01-02T08-00-00-{ID_A}.jsonl"));
+        let conflicted = day.join(format!(
+            "rollout-2025-01-02T08-00-00-{ID_A}.sync-conflict.jsonl"
+        ));
+        write_file(&original, &session_line("main", "/w", ID_A));
+        write_file(&conflicted, &session_line("main", "/w", ID_A));
+        set_mtime(&original, 1_000);
+        set_mtime(&conflicted, 2_000);
+        let options = ScanOptions {
+            exclude: Exclusions::from_args(&["*sync-conflict*".to_owned()]).unwrap(),
+            ..ScanOptions::default()
+        };
+
+        let session = find_first_session(&dir, "main", &options)
+            .unwrap()
+            .session
+            .unwrap();
+
+        assert_eq!(session.source_jsonl, original);
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    fn symlinked_copies_are_not_preferred(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = dir.join("codex");
+        let day = codexdir.join("sessions/2025/01/02");
+        let original = day.join(format!("rollout-2025-01-02T08-00-00-{ID_A}.jsonl"));
+        let outside = dir.join(format!("rollout-2025-01-02T08-00-00-{ID_A}.jsonl"));
+        write_file(&original, &session_line("main", "/w", ID_A));
+        write_file(&outside, &session_line("main", "/w", ID_A));
+        set_mtime(&original, 1_000);
+        set_mtime(&outside, 2_000);
+        std::os::unix::fs::symlink(&outside, day.join(format!("rollout-{ID_A}-link.jsonl")))
+            .unwrap();
+
+        let session = find(&codexdir, "main").session.unwrap();
+
+        assert_eq!(session.source_jsonl, original);
+    }
+
+    #[rstest]
+    #[case(false, "2025/01/02")]
+    #[case(true, "2025/01/03")]
+    fn looks_beyond_the_directory_only_with_dedup_ids(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] dedup_ids: bool,
+        #[case] expected_day: &str,
+    ) {
+        let name = format!("rollout-2025-01-02T08-00-00-{ID_A}.jsonl");
+        let first = dir.join("sessions/2025/01/02").join(&name);
+        let later = dir.join("sessions/2025/01/03").join(&name);
+        write_file(&first, &session_line("main", "/w", ID_A));
+        write_file(&later, &session_line("main", "/w", ID_A));
+        set_mtime(&first, 1_000);
+        set_mtime(&later, 2_000);
+        let options = ScanOptions {
+            dedup_ids,
+            ..ScanOptions::default()
+        };
+
+        let session = find_first_session(&dir, "main", &options)
+            .unwrap()
+            .session
+            .unwrap();
+
+        assert!(
+            session
+                .source_jsonl
+                .starts_with(dir.join("sessions").join(expected_day)),
+            "{}",
+            session.source_jsonl.display()
+        );
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn piped_lines_match_in_input_order() {
+        let input = [
+            String::new(),
+            "not json".to_owned(),
+            session_line("other", "/w/other", ID_A),
+            session_line("main", "/w/first", ID_B),
+            session_line("main", "/w/second", ID_C),
+        ]
+        .join("\n");
+
+        let session = find_in_lines(input.as_bytes(), "main", &ScanOptions::default())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(session.cwd, Path::new("/w/first"));
+        assert_eq!(session.source_jsonl, Path::new("stdin:4"));
+        assert!(
+            find_in_lines(input.as_bytes(), "gone", &ScanOptions::default())
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn piped_lines_that_are_not_utf8_are_skipped() {
+        let mut input = b"\xff\xfe garbage main\n".to_vec();
+        input.extend_from_slice(session_line("main", "/w/first", ID_B).as_bytes());
+
+        let session = find_in_lines(&input[..], "main", &ScanOptions::default())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(session.source_jsonl, Path::new("stdin:2"));
+    }
+
+    #[rstest]
+    fn a_first_line_that_is_not_utf8_is_unreadable(#[from(temp_dir)] dir: PathBuf) {
+        let path = dir.join("rollout-a.jsonl");
+        fs::write(&path, b"\xff\xfe\n{}\n").unwrap();
+
+        let err = read_first_line(&path).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "line 1 is not valid UTF-8 (after byte 0)");
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn piped_lines_prefer_a_later_exact_match_over_a_stripped_one() {
+        let input = [
+            session_line("me/feature-x", "/w/prefixed", ID_A),
+            session_line("feature-x", "/w/exact", ID_B),
+        ]
+        .join("\r\n");
+
+        let session = find_in_lines(input.as_bytes(), "feature-x", &strip_me())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(session.id, ID_B);
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn finds_the_newest_session_on_any_branch(#[from(temp_dir)] dir: PathBuf) {
+        let old = dir.join("sessions/2024/12/31");
+        let new = dir.join("sessions/2025/01/02");
+        write_file(
+            &old.join("rollout-2024-12-31T09-00-00-x.jsonl"),
+            &session_line("main", "/w/old", ID_A),
+        );
+        write_file(
+            &new.join("rollout-2025-01-02T08-00-00-x.jsonl"),
+            &session_line("feature/a", "/w/morning", ID_A),
+        );
+        write_file(
+            &new.join("rollout-2025-01-02T17-00-00-x.jsonl"),
+            &session_line("feature/b", "/w/evening", ID_B),
+        );
+        write_file(&new.join("rollout-2025-01-02T18-00-00-x.jsonl"), "not json");
+
+        let (session, branch) = find_latest_session(&dir, &ScanOptions::default())
+            .expect("scan should succeed")
+            .expect("should find a session");
+
+        assert_eq!(session.id, ID_B);
+        assert_eq!(session.cwd, PathBuf::from("/w/evening"));
+        assert_eq!(branch.as_deref(), Some("feature/b"));
+        assert_eq!(session.branch, branch);
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn latest_session_may_lack_a_branch(#[from(temp_dir)] dir: PathBuf) {
+        let line = serde_json::json!({ "payload": { "cwd": "/w", "id": ID_A } });
+        write_file(&dir.join("rollout-a.jsonl"), &line.to_string());
+
+        let (session, branch) = find_latest_session(&dir, &ScanOptions::default())
+            .unwrap()
+            .expect("should find a session");
+
+        assert_eq!(session.id, ID_A);
+        assert_eq!(branch, None);
+        assert_eq!(session.branch_label(), "(none)");
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn matched_sessions_carry_file_metadata(#[from(temp_dir)] dir: PathBuf) {
+        let line = session_line("main", "/w", ID_A);
+        write_file(&dir.join("rollout-a.jsonl"), &line);
+
+        let session = find(&dir, "main").session.expect("should match");
+
+        assert_eq!(session.size, Some(line.len() as u64));
+        let mtime = session.modified_rfc3339().expect("mtime should be known");
+        assert!(UtcDateTime::parse_rfc3339(&mtime).is_some(), "{mtime}");
+    }
+
+    #[rstest]
+    fn keeps_the_first_line_only_when_asked(#[from(temp_dir)] dir: PathBuf) {
+        let line = session_line("main", "/w", ID_A);
+        write_file(
+            &dir.join("rollout-a.jsonl"),
+            &format!("\u{feff}{line}\r\nsecond\n"),
+        );
+        let keep = ScanOptions {
+            keep_first_line: true,
+            ..ScanOptions::default()
+        };
+
+        let kept = find_first_session(&dir, "main", &keep).unwrap().session;
+        let dropped = find(&dir, "main").session;
+
+        assert_eq!(kept.unwrap().first_line.as_deref(), Some(line.as_str()));
+        assert_eq!(dropped.unwrap().first_line, None);
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn lists_branches_newest_session_first(#[from(temp_dir)] dir: PathBuf) {
+        let day = |d: &str, name: &str| dir.join("sessions/2025/10").join(d).join(name);
+        write_file(
+            &day("01", "rollout-1.jsonl"),
+            &session_line("old", "/w", ID_A),
+        );
+        write_file(
+            &day("01", "rollout-2.jsonl"),
+            &session_line("main", "/w", ID_A),
+        );
+        write_file(
+            &day("02", "rollout-1.jsonl"),
+            &session_line("dev", "/w", ID_B),
+        );
+        write_file(
+            &day("02", "rollout-2.jsonl"),
+            &session_line("main", "/w", ID_C),
+        );
+        write_file(
+            &day("02", "notes.jsonl"),
+            &session_line("ignored", "/w", ID_C),
+        );
+
+        let list = recent_branches(&dir, None, None).unwrap();
+
+        let names: Vec<&str> = list
+            .branches
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, ["main", "dev", "old"]);
+        assert!(list.branches.iter().all(|(_, modified)| modified.is_some()));
+        assert!(!list.truncated);
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    #[case(1, &["main"], true)]
+    #[case(2, &["main", "dev"], false)]
+    #[case(3, &["main", "dev"], false)]
+    fn branch_listing_stops_at_max_matches(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] max: usize,
+        #[case] expected: &[&str],
+        #[case] truncated: bool,
+    ) {
+        let day = |d: &str, name: &str| dir.join("sessions/2025/01").join(d).join(name);
+        write_file(
+            &day("01", "rollout-1.jsonl"),
+            &session_line("dev", "/w", ID_A),
+        );
+        write_file(
+            &day("02", "rollout-1.jsonl"),
+            &session_line("main", "/w", ID_B),
+        );
+        write_file(
+            &day("02", "rollout-2.jsonl"),
+            &session_line("main", "/w", ID_C),
+        );
+
+        let list = recent_branches(&dir, None, Some(max)).unwrap();
+
+        let names: Vec<&str> = list
+            .branches
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, expected);
+        assert_eq!(list.truncated, truncated);
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn branch_listing_stops_at_the_deadline(#[from(temp_dir)] dir: PathBuf) {
+        write_file(
+            &dir.join("rollout-a.jsonl"),
+            &session_line("main", "/w", ID_A),
+        );
+
+        let list = recent_branches(&dir, Some(Instant::now()), None).unwrap();
+
+        assert!(list.branches.is_empty());
+        assert!(list.truncated);
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn reads_a_session_from_any_file(#[from(temp_dir)] dir: PathBuf) {
+        let path = dir.join("found-with-rg.jsonl");
+        write_file(
+            &path,
+            &format!("{}\nmore\n", session_line("main", "/w", ID_A)),
+        );
+
+        let (session, branch) = session_from_file(&path).unwrap();
+
+        assert_eq!(session.id, ID_A);
+        assert_eq!(session.source_jsonl, path);
+        assert_eq!(branch.as_deref(), Some("main"));
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    #[case::empty("", "is empty")]
+    #[case::not_json("nope", "not valid JSON")]
+    #[case::no_id(r#"{"payload":{"cwd":"/w"}}"#, ".payload.id")]
+    #[case::not_uuid(r#"{"payload":{"cwd":"/w","id":"abc"}}"#, "not a UUID")]
+    #[case::no_cwd(
+        r#"{"payload":{"id":"0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b"}}"#,
+        ".payload.cwd"
+    )]
+    fn names_what_a_session_file_lacks(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] content: &str,
+        #[case] expected: &str,
+    ) {
+        let path = dir.join("s.jsonl");
+        write_file(&path, content);
+
+        let err = session_from_file(&path).unwrap_err().to_string();
+
+        assert!(err.contains(expected), "{err}");
+    }
+
+    fn walked(walk: SortedWalk, root: &Path) -> Vec<PathBuf> {
+        walk.map(|p| p.strip_prefix(root).unwrap().to_owned())
+            .collect()
+    }
+
+    #[rstest]
+    fn walk_yields_files_in_path_order(#[from(temp_dir)] dir: PathBuf) {
+        for name in [
+            "b/2.jsonl",
+            "a/z/1.jsonl",
+            "a/notes.txt",
+            "a.jsonl",
+            "b/1.jsonl",
+        ] {
+            write_file(&dir.join(name), "");
+        }
+
+        // Paths compare per component, so everything under `a/` comes before `a.jsonl`.
+        assert_eq!(
+            walked(SortedWalk::new(&dir).unwrap(), &dir),
+            [
+                "a/notes.txt",
+                "a/z/1.jsonl",
+                "a.jsonl",
+                "b/1.jsonl",
+                "b/2.jsonl"
+            ]
+            .map(PathBuf::from)
+        );
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    #[case::checked(false, &["real/rollout-a.jsonl"])]
+    #[case::trusted(true, &["link.jsonl", "real/rollout-a.jsonl"])]
+    fn walk_skips_symlinks_unless_trusted(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] skip_symlink_check: bool,
+        #[case] expected: &[&str],
+    ) {
+        use std::os::unix::fs::symlink;
+
+        write_file(&dir.join("real/rollout-a.jsonl"), "");
+        symlink(dir.join("real/rollout-a.jsonl"), dir.join("link.jsonl")).unwrap();
+        symlink(dir.join("real"), dir.join("linked-dir")).unwrap();
+        symlink(dir.join("real/rollout-a.jsonl"), dir.join("link.txt")).unwrap();
+        let options = ScanOptions {
+            skip_symlink_check,
+            ..Default::default()
+        };
+
+        let walk = SortedWalk::new(&dir).unwrap().with_options(&options);
+
+        assert_eq!(
+            walked(walk, &dir),
+            expected.iter().map(PathBuf::from).collect::<Vec<_>>()
+        );
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    #[case::ignore_file_only(&[], &["keep/1.jsonl", "keep/scratch.jsonl"])]
+    #[case::with_exclude(&["scratch.jsonl"], &["keep/1.jsonl"])]
+    #[case::exclude_is_anchored_with_a_slash(&["/keep"], &[])]
+    fn walk_prunes_ignored_and_excluded_paths(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] exclude: &[&str],
+        #[case] expected: &[&str],
+    ) {
+        for name in [
+            "archived/2023/1.jsonl",
+            "keep/1.jsonl",
+            "keep/scratch.jsonl",
+            "keep/old.bak",
+        ] {
+            write_file(&dir.join(name), "");
+        }
+        write_file(
+            &dir.join(AMGIGNORE_FILE),
+            "# experiments\narchived/\n\n*.bak\n",
+        );
+        let options = ScanOptions {
+            exclude: Exclusions::from_args(
+                &exclude.iter().map(|p| p.to_string()).collect::<Vec<_>>(),
+            )
+            .unwrap(),
+            ..Default::default()
+        };
+
+        let walk = SortedWalk::new(&dir).unwrap().with_options(&options);
+
+        let mut expected: Vec<_> = expected.iter().map(PathBuf::from).collect();
+        expected.insert(0, PathBuf::from(AMGIGNORE_FILE));
+        assert_eq!(walked(walk, &dir), expected);
+    }
+
+    #[rstest]
+    fn ignored_sessions_are_never_matched(#[from(temp_dir)] dir: PathBuf) {
+        let line = session_line("main", "/w", ID_A);
+        write_file(&dir.join("sessions/archived/rollout-a.jsonl"), &line);
+        write_file(&dir.join(AMGIGNORE_FILE), "archived\n");
+
+        let outcome = find(&dir, "main");
+
+        assert!(outcome.session.is_none());
+        assert_eq!(outcome.stats.files_scanned, 0);
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn newest_first_walk_reverses_order(#[from(temp_dir)] dir: PathBuf) {
+        for name in ["a/1.jsonl", "a/2.jsonl", "b/1.jsonl"] {
+            write_file(&dir.join(name), "");
+        }
+
+        let walked: Vec<_> = SortedWalk::newest_first(&dir)
+            .unwrap()
+            .map(|p| p.strip_prefix(&dir).unwrap().to_owned())
+            .collect();
+
+        assert_eq!(
+            walked,
+            ["b/1.jsonl", "a/2.jsonl", "a/1.jsonl"].map(PathBuf::from)
+        );
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn finds_every_matching_session_in_walk_order(#[from(temp_dir)] dir: PathBuf) {
+        write_file(
+            &dir.join("rollout-1.jsonl"),
+            &session_line("main", "/tmp/a", ID_A),
+        );
+        write_file(
+            &dir.join("rollout-2.jsonl"),
+            &session_line("dev", "/tmp/b", ID_A),
+        );
+        write_file(
+            &dir.join("rollout-3.jsonl"),
+            &session_line("main", "/tmp/c", ID_B),
+        );
+
+        let (sessions, stats) =
+            find_sessions(&dir, "main", &ScanOptions::default()).expect("scan should succeed");
+
+        let ids: Vec<_> = sessions.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, [ID_A, ID_B]);
+        assert_eq!(stats.files_scanned, 3);
+        assert_eq!(stats.files_parsed, 3);
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn finds_sessions_for_several_branches_in_one_pass(#[from(temp_dir)] dir: PathBuf) {
+        write_file(
+            &dir.join("rollout-1.jsonl"),
+            &session_line("b", "/tmp/b", ID_A),
+        );
+        write_file(
+            &dir.join("rollout-2.jsonl"),
+            &session_line("a", "/tmp/a", ID_B),
+        );
+        write_file(
+            &dir.join("rollout-3.jsonl"),
+            &session_line("a", "/tmp/a2", ID_A),
+        );
+        let branches = ["a", "b", "missing"].map(String::from);
+
+        let found = find_first_sessions(&dir, &branches, &ScanOptions::default())
+            .expect("scan should succeed");
+
+        assert_eq!(found.len(), 2);
+        assert_eq!(found["a"].source_jsonl, dir.join("rollout-2.jsonl"));
+        assert_eq!(found["b"].source_jsonl, dir.join("rollout-1.jsonl"));
+        assert!(!found.contains_key("missing"));
+    }
+
+    #[rstest]
+    #[case("0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b", true)]
+    #[case("0199A6B2-3C4D-7E8F-9A0B-1C2D3E4F5A6B", true)]
+    #[case("id-a", false)]
+    #[case("0199a6b23c4d7e8f9a0b1c2d3e4f5a6b", false)]
+    #[case("0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6", false)]
+    #[case("0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6g", false)]
+    fn recognizes_plausible_uuids(#[case] id: &str, #[case] expected: bool) {
+        assert_eq!(is_plausible_uuid(id), expected);
+    }
+
+    #[rstest]
+    #[case::rollout(
+        "rollout-2025-03-09T08-07-06-0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b.jsonl",
+        Some(ID_A)
+    )]
+    #[case::bare("0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6c.jsonl", Some(ID_B))]
+    #[case::suffixed("copy-of-0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6d-old.jsonl", Some(ID_C))]
+    #[case::no_uuid("rollout-2025-03-09T08-07-06.jsonl", None)]
+    #[case::short("a.jsonl", None)]
+    fn extracts_uuids_from_file_names(#[case] name: &str, #[case] expected: Option<&str>) {
+        assert_eq!(
+            file_name_uuid(Path::new("/codex").join(name).as_path()),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::exact("feature-x", "feature-x", &[], Some(BranchMatch::Exact))]
+    #[case::exact_wins("me/x", "me/x", &["me/"], Some(BranchMatch::Exact))]
+    #[case::stripped("me/x", "x", &["you/", "me/"], Some(BranchMatch::Stripped { found: "me/x".into() }))]
+    #[case::no_prefixes("me/x", "x", &[], None)]
+    #[case::other_prefix("you/x", "x", &["me/"], None)]
+    #[case::partial("me/xy", "x", &["me/"], None)]
+    #[case::empty_prefix("x", "x", &[""], Some(BranchMatch::Exact))]
+    #[case::empty_prefix_ignored("yx", "x", &[""], None)]
+    fn matches_branches_behind_prefixes(
+        #[case] recorded: &str,
+        #[case] branch: &str,
+        #[case] prefixes: &[&str],
+        #[case] expected: Option<BranchMatch>,
+    ) {
+        let prefixes: Vec<String> = prefixes.iter().map(|p| (*p).to_owned()).collect();
+
+        assert_eq!(match_branch(recorded, branch, &prefixes), expected);
+    }
+
+    fn strip_me() -> ScanOptions {
+        ScanOptions {
+            strip_prefixes: vec!["me/".to_owned()],
+            ..ScanOptions::default()
+        }
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    fn matches_a_prefixed_branch_past_the_fast_path(#[from(temp_dir)] dir: PathBuf) {
+        write_file(
+            &dir.join("rollout-1.jsonl"),
+            &session_line("me/feature-x", "/w", ID_A),
+        );
+
+        let session = find_first_session(&dir, "feature-x", &strip_me())
+            .unwrap()
+            .session
+            .unwrap();
+
+        assert_eq!(session.id, ID_A);
+        assert_eq!(session.branch.as_deref(), Some("me/feature-x"));
+        assert_eq!(session.other_branch("feature-x"), Some("feature-x"));
+        assert!(find(&dir, "feature-x").session.is_none());
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    #[case::stripped_first("rollout-1.jsonl", "rollout-2.jsonl")]
+    #[case::exact_first("rollout-2.jsonl", "rollout-1.jsonl")]
+    fn prefers_exact_branch_matches(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] stripped_name: &str,
+        #[case] exact_name: &str,
+    ) {
+        write_file(&dir.join(stripped_name), &session_line("me/x", "/w", ID_A));
+        write_file(&dir.join(exact_name), &session_line("x", "/w", ID_B));
+        let options = strip_me();
+
+        let first = find_first_session(&dir, "x", &options)
+            .unwrap()
+            .session
+            .unwrap();
+        let (all, _) = find_sessions(&dir, "x", &options).unwrap();
+        let many = find_first_sessions(&dir, &["x".to_owned()], &options).unwrap();
+
+        assert_eq!(first.id, ID_B);
+        let ids: Vec<&str> = all.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, [ID_B, ID_A]);
+        let branches: Vec<_> = all.iter().map(|s| s.branch.as_deref()).collect();
+        assert_eq!(branches, [Some("x"), Some("me/x")]);
+        assert_eq!(many["x"].id, ID_B);
+        assert_eq!(first.other_branch("x"), None);
+    }
+
+    #[rstest]
+    fn explains_stripped_matches_before_using_them(#[from(temp_dir)] dir: PathBuf) {
+        write_file(
+            &dir.join("rollout-1.jsonl"),
+            &session_line("me/x", "/w", ID_A),
+        );
+        write_file(&dir.join("rollout-2.jsonl"), &session_line("y", "/w", ID_B));
+        let mut decisions = Vec::new();
+
+        let outcome = find_first_session_observed(&dir, "x", &strip_me(), |path, decision| {
+            decisions.push((path.file_name().unwrap().to_owned(), decision.clone()));
+        })
+        .unwrap();
+
+        assert_eq!(outcome.session.unwrap().id, ID_A);
+        let found = Decision::StrippedMatch {
+            found: "me/x".into(),
+        };
+        assert_eq!(
+            decisions,
+            [
+                ("rollout-1.jsonl".into(), found),
+                (
+                    "rollout-2.jsonl".into(),
+                    Decision::BranchMismatch { found: None }
+                ),
+                ("rollout-1.jsonl".into(), Decision::Matched),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::matching(ID_A, false, true)]
+    #[case::matching_strict(ID_A, true, true)]
+    #[case::mismatching(ID_B, false, true)]
+    #[case::mismatching_strict(ID_B, true, false)]
+    fn cross_checks_ids_against_file_names(
+        #[from(temp_dir)] dir: PathBuf,
+        #[case] id: &str,
+        #[case] strict_file_ids: bool,
+        #[case] matches: bool,
+    ) {
+        let named = dir.join(format!("rollout-2025-03-09T08-07-06-{ID_A}.jsonl"));
+        write_file(&named, &session_line("main", "/w", id));
+        let options = ScanOptions {
+            strict_file_ids,
+            ..ScanOptions::default()
+        };
+
+        let outcome = find_first_session(&dir, "main", &options).unwrap();
+
+        assert_eq!(outcome.session.is_some(), matches);
+        assert_eq!(
+            evaluate(&named, "main", &options).err(),
+            (!matches).then(|| Decision::IdMismatch {
+                file_id: ID_A.to_owned(),
+                id: id.to_owned(),
+            })
+        );
+    }
+
+    #[rstest]
+    fn file_names_without_uuids_are_not_cross_checked(#[from(temp_dir)] dir: PathBuf) {
+        write_file(
+            &dir.join("rollout-a.jsonl"),
+            &session_line("main", "/w", ID_B),
+        );
+        let options = ScanOptions {
+            strict_file_ids: true,
+            ..ScanOptions::default()
+        };
+
+        assert!(
+            find_first_session(&dir, "main", &options)
+                .unwrap()
+                .session
+                .is_some()
+        );
+    }
+
+    #[rstest]
+    fn rejects_look_alike_jsonl_by_default(#[from(temp_dir)] dir: PathBuf) {
+        write_file(
+            &dir.join("notes/transcript.jsonl"),
+            &session_line("main", "/tmp/notes", ID_A),
+        );
+
+        assert!(find(&dir, "main").session.is_none());
+    }
+
+    #[rstest]
+    fn any_filename_accepts_look_alike_jsonl(#[from(temp_dir)] dir: PathBuf) {
+        write_file(
+            &dir.join("notes/transcript.jsonl"),
+            &session_line("main", "/tmp/notes", ID_A),
+        );
+        let options = ScanOptions {
+            any_filename: true,
+            ..Default::default()
+        };
+
+        let outcome = find_first_session(&dir, "main", &options).expect("scan should succeed");
+
+        assert_eq!(outcome.session.expect("should match").id, ID_A);
+    }
+
+    #[rstest]
+    #[case(None, Some(1000), Ok(()))]
+    #[case(None, Some(1001), Ok(()))]
+    #[case(Some(1000), Some(1000), Ok(()))]
+    #[case(Some(1000), Some(1001), Err(Decision::ForeignOwner { uid: 1001 }))]
+    #[case(Some(1000), None, Ok(()))]
+    fn filters_by_owner(
+        #[case] owner: Option<u32>,
+        #[case] file_uid: Option<u32>,
+        #[case] expected: Result<(), Decision>,
+    ) {
+        let options = ScanOptions {
+            owner,
+            ..Default::default()
+        };
+
+        let checked = options.check_owner(Path::new("/c/rollout-a.jsonl"), |_| Ok(file_uid));
+
+        assert_eq!(checked, expected);
+    }
+
+    #[test]
+    fn owner_lookup_failures_are_unreadable() {
+        let options = ScanOptions {
+            owner: Some(1000),
+            ..Default::default()
+        };
+
+        let checked = options.check_owner(Path::new("/c/rollout-a.jsonl"), |_| {
+            Err(io::Error::other("stat failed"))
+        });
+
+        assert_eq!(
+            checked,
+            Err(Decision::Unreadable {
+                error: "stat failed".into()
+            })
+        );
+    }
+
+    #[cfg(unix)]
+    #[rstest]
+    #[case(false)]
+    #[case(true)]
+    fn skips_files_owned_by_other_users(#[from(temp_dir)] dir: PathBuf, #[case] foreign: bool) {
+        let path = dir.join("rollout-a.jsonl");
+        write_file(&path, &session_line("main", "/tmp/a", ID_A));
+        let uid = file_owner(&path).unwrap().unwrap();
+        let options = ScanOptions {
+            owner: Some(if foreign { uid.wrapping_add(1) } else { uid }),
+            ..Default::default()
+        };
+
+        let outcome = find_first_session(&dir, "main", &options).expect("scan should succeed");
+
+        assert_eq!(outcome.session.is_none(), foreign);
+        assert_eq!(outcome.stats.skipped_by_owner, usize::from(foreign));
+    }
+
+    #[rstest]
+    #[case(false)]
+    #[case(true)]
+    fn rejects_non_uuid_session_ids(#[from(temp_dir)] dir: PathBuf, #[case] any_filename: bool) {
+        write_file(
+            &dir.join("rollout-a.jsonl"),
+            &session_line("main", "/tmp/a", "not-a-uuid"),
+        );
+        let options = ScanOptions {
+            any_filename,
+            ..Default::default()
+        };
+
+        let outcome = find_first_session(&dir, "main", &options).expect("scan should succeed");
+
+        assert!(outcome.session.is_none());
+    }
+}
@@ -0,0 +1,258 @@
+//! Scratch Codex home for read-only session stores (`--codexdir-readonly`).
+//!
+//! Codex appends to the session file it resumes, so a codexdir mounted read-only (a synced
+//! backup, a container volume) cannot be resumed in place. This module builds a writable
+//! stand-in under `$XDG_STATE_HOME/amg/codex-home`: the matched session file is copied to
+//! the same relative path (so `codex resume <id>` finds it under `sessions/`), and the
+//! read-only home's `auth.json` and `config.toml` are linked in so codex still signs in with
+//! the same settings. codex is pointed at the stand-in with `CODEX_HOME`.
+//!
+//! The original store is never written to.
+
+use super::policy;
+use super::prelude::*;
+use super::scan::Session;
+use super::util;
+
+/// Environment variable name for the XDG state directory.
+const ENV_XDG_STATE_HOME: &str = "XDG_STATE_HOME";
+
+/// Directory name of the scratch Codex home (under `$XDG_STATE_HOME/amg`).
+const SCRATCH_HOME_DIR: &str = "codex-home";
+
+/// Files shared with the read-only home rather than copied.
+const SHARED_FILES: [&str; 2] = ["auth.json", "config.toml"];
+
+/// Returns the scratch Codex home (`$XDG_STATE_HOME/amg/codex-home`, falling back to
+/// `~/.local/state/amg/codex-home`).
+///
+/// # Errors
+///
+/// Returns an error in portable mode, or if neither `$XDG_STATE_HOME` nor `$HOME` is set.
+pub(super) fn scratch_home() -> Result<PathBuf> {
+    policy::require_home_files("the scratch Codex home for --codexdir-readonly")?;
+    let state_home = util::non_empty_env(ENV_XDG_STATE_HOME)
+        .or_else(|| util::non_empty_env(ENV_HOME).map(|home| home.join(".local/state")))
+        .with_context(|| {
+            format!("cannot locate a scratch directory for --codexdir-readonly; set ${ENV_XDG_STATE_HOME} or $HOME")
+        })?;
+    Ok(state_home.join(AMG_DIR).join(SCRATCH_HOME_DIR))
+}
+
+/// Copies a session out of a read-only codexdir into the scratch home.
+///
+/// An existing copy is kept unless it is shorter than the original: codex only appends, so a
+/// longer copy holds earlier resumes that must not be thrown away.
+///
+/// # Arguments
+///
+/// * `codexdir` - The read-only Codex directory the session was found in
+/// * `session` - The session to stage
+/// * `home` - The scratch Codex home (see [`scratch_home`])
+///
+/// # Returns
+///
+/// Returns the path of the writable copy.
+///
+/// # Errors
+///
+/// Returns an error if the scratch home cannot be created or the session file cannot be
+/// copied.
+pub(super) fn stage_session(codexdir: &Path, session: &Session, home: &Path) -> Result<PathBuf> {
+    let staged = home.join(staged_path(codexdir, &session.source_jsonl)?);
+    if let Some(parent) = staged.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", util::human_path(parent)))?;
+    }
+
+    let len = |path: &Path| fs::metadata(path).map(|m| m.len());
+    let source_len = len(&session.source_jsonl)
+        .with_context(|| format!("failed to stat {}", util::human_path(&session.source_jsonl)))?;
+    if len(&staged).is_ok_and(|staged_len| staged_len >= source_len) {
+        debug!(staged = %util::human_path(&staged), "reusing staged session copy");
+    } else {
+        fs::copy(&session.source_jsonl, &staged).with_context(|| {
+            format!(
+                "failed to copy {} to {}",
+                util::human_path(&session.source_jsonl),
+                util::human_path(&staged)
+            )
+        })?;
+        make_writable(&staged)?;
+    }
+
+    link_shared_files(codex_home(codexdir), home);
+    Ok(staged)
+}
+
+/// Returns the wrapper that points codex at `home` (`env CODEX_HOME=<home>`).
+///
+/// # Errors
+///
+/// Returns an error if `home` is not valid UTF-8.
+pub(super) fn env_wrapper(home: &Path) -> Result<Vec<String>> {
+    let home = home.to_str().with_context(|| {
+        format!(
+            "scratch directory is not valid UTF-8: {}",
+            util::human_path(home)
+        )
+    })?;
+    Ok(vec!["env".into(), format!("{ENV_CODEX_HOME}={home}")])
+}
+
+/// Returns the session's path relative to a Codex home, always under `sessions/`.
+///
+/// Accepts codexdirs pointing at a Codex home as well as at its `sessions` directory.
+fn staged_path(codexdir: &Path, source: &Path) -> Result<PathBuf> {
+    let relative = match source.strip_prefix(codexdir) {
+        Ok(relative) => relative.to_path_buf(),
+        Err(_) => PathBuf::from(
+            source
+                .file_name()
+                .with_context(|| format!("not a file path: {}", util::human_path(source)))?,
+        ),
+    };
+    if relative.starts_with(SESSIONS_DIR) {
+        Ok(relative)
+    } else {
+        Ok(Path::new(SESSIONS_DIR).join(relative))
+    }
+}
+
+/// Returns the Codex home a codexdir belongs to.
+fn codex_home(codexdir: &Path) -> &Path {
+    match codexdir.parent() {
+        Some(parent) if codexdir.ends_with(SESSIONS_DIR) => parent,
+        _ => codexdir,
+    }
+}
+
+/// Links (or, off Unix, copies) the shared files of `source` into `home`.
+///
+/// Files already present in `home` are left alone. Failures only warn: codex can still run
+/// without them, it will just ask to sign in again.
+fn link_shared_files(source: &Path, home: &Path) {
+    for name in SHARED_FILES {
+        let (from, to) = (source.join(name), home.join(name));
+        if !from.is_file() || fs::symlink_metadata(&to).is_ok() {
+            continue;
+        }
+        #[cfg(unix)]
+        let result = std::os::unix::fs::symlink(&from, &to);
+        #[cfg(not(unix))]
+        let result = fs::copy(&from, &to).map(drop);
+        if let Err(err) = result {
+            warn!(
+                "failed to link {} into {}: {err}",
+                util::human_path(&from),
+                util::human_path(home)
+            );
+        }
+    }
+}
+
+/// Makes a copied session file writable by its owner (copies keep the source's read-only
+/// permissions).
+fn make_writable(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    let permissions = {
+        use std::os::unix::fs::PermissionsExt;
+        fs::Permissions::from_mode(0o600)
+    };
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    let permissions = {
+        let mut permissions = fs::metadata(path)?.permissions();
+        permissions.set_readonly(false);
+        permissions
+    };
+    fs::set_permissions(path, permissions)
+        .with_context(|| format!("failed to make {} writable", util::human_path(path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::{matched_session, temp_dir};
+    use rstest::rstest;
+
+    fn session(source: PathBuf) -> Session {
+        Session {
+            source_jsonl: source,
+            ..matched_session("0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b")
+        }
+    }
+
+    #[rstest]
+    #[case::codex_home(
+        "codex",
+        "codex/sessions/2025/10/14/r.jsonl",
+        "sessions/2025/10/14/r.jsonl"
+    )]
+    #[case::sessions_dir(
+        "codex/sessions",
+        "codex/sessions/2025/r.jsonl",
+        "sessions/2025/r.jsonl"
+    )]
+    #[case::flat("store", "store/r.jsonl", "sessions/r.jsonl")]
+    #[case::elsewhere("store", "other/r.jsonl", "sessions/r.jsonl")]
+    fn keeps_the_layout_codex_expects(
+        #[case] codexdir: &str,
+        #[case] source: &str,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(
+            staged_path(Path::new(codexdir), Path::new(source)).unwrap(),
+            PathBuf::from(expected)
+        );
+    }
+
+    #[rstest]
+    fn stages_a_writable_copy(#[from(temp_dir)] dir: PathBuf) {
+        let codexdir = dir.join("codex");
+        let source = codexdir.join("sessions/2025/r.jsonl");
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+        fs::write(&source, "{}\n").unwrap();
+        fs::write(codexdir.join("auth.json"), "{}").unwrap();
+        let mut permissions = fs::metadata(&source).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&source, permissions).unwrap();
+
+        let home = dir.join("scratch");
+        let staged = stage_session(&codexdir, &session(source.clone()), &home).unwrap();
+
+        assert_eq!(staged, home.join("sessions/2025/r.jsonl"));
+        assert_eq!(fs::read_to_string(&staged).unwrap(), "{}\n");
+        assert!(!fs::metadata(&staged).unwrap().permissions().readonly());
+        assert!(home.join("auth.json").is_file());
+        assert!(!home.join("config.toml").exists());
+    }
+
+    #[rstest]
+    fn keeps_a_copy_that_codex_extended(#[from(temp_dir)] dir: PathBuf) {
+        let source = dir.join("codex/r.jsonl");
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+        fs::write(&source, "a\n").unwrap();
+        let home = dir.join("scratch");
+        let staged = stage_session(&dir.join("codex"), &session(source.clone()), &home).unwrap();
+        fs::write(&staged, "a\nresumed\n").unwrap();
+
+        stage_session(&dir.join("codex"), &session(source.clone()), &home).unwrap();
+        assert_eq!(fs::read_to_string(&staged).unwrap(), "a\nresumed\n");
+
+        fs::write(&source, "a\nb\nupdated upstream\n").unwrap();
+        stage_session(&dir.join("codex"), &session(source), &home).unwrap();
+        assert_eq!(
+            fs::read_to_string(&staged).unwrap(),
+            "a\nb\nupdated upstream\n"
+        );
+    }
+
+    #[test]
+    fn points_codex_at_the_scratch_home() {
+        assert_eq!(
+            env_wrapper(Path::new("/s/codex-home")).unwrap(),
+            ["env", "CODEX_HOME=/s/codex-home"]
+        );
+    }
+}
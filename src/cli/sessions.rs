@@ -0,0 +1,370 @@
+//! Session lookup and resume commands for embedders.
+//!
+//! This module exposes the branch matching and codex command building behind `amg resume` to
+//! other programs, such as a GUI that lists a repo's sessions. It is available without the
+//! default `cli` feature, which leaves out argument parsing (`clap`) and logging setup
+//! (`tracing-subscriber`); amg still logs through the `tracing` facade, so the embedder's
+//! subscriber (if any) receives its events.
+//!
+//! [`find_first`] scans like `amg resume` without the CLI's optional filters, and
+//! [`resume_command`] builds the codex command `amg resume` would run with its default
+//! sandbox options.
+//!
+//! With the `async` feature, `find_first_async` does the lookup without blocking an async
+//! executor, such as a daemon running on a tokio runtime. The directory walk runs on a
+//! dedicated thread; the first-line reads are done in ordered batches of at most
+//! `MAX_CONCURRENT_READS` files at a time, so the result is exactly the session the
+//! synchronous scan would choose. The futures do not depend on any particular runtime.
+
+#[cfg(feature = "async")]
+use std::future::Future;
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(feature = "async")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "async")]
+use std::task::{Context as TaskContext, Poll, Waker};
+#[cfg(feature = "async")]
+use std::thread;
+
+use super::prelude::*;
+use super::scan::{self, ScanOptions};
+use super::{codex_cmd, util};
+
+/// Maximum number of session files whose first line is read concurrently.
+#[cfg(feature = "async")]
+pub const MAX_CONCURRENT_READS: usize = 8;
+
+/// A Codex session matched by [`find_first`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    /// The working directory where the session was created.
+    pub cwd: PathBuf,
+    /// The unique session identifier.
+    pub id: String,
+    /// The path to the JSONL file containing this session.
+    pub source_jsonl: PathBuf,
+    /// The git commit recorded when the session was created, if any.
+    pub commit_hash: Option<String>,
+}
+
+impl From<scan::Session> for Session {
+    fn from(session: scan::Session) -> Self {
+        Self {
+            cwd: session.cwd,
+            id: session.id,
+            source_jsonl: session.source_jsonl,
+            commit_hash: session.commit_hash,
+        }
+    }
+}
+
+impl From<&Session> for scan::Session {
+    fn from(session: &Session) -> Self {
+        Self {
+            cwd: session.cwd.clone(),
+            id: session.id.clone(),
+            branch: None,
+            source_jsonl: session.source_jsonl.clone(),
+            commit_hash: session.commit_hash.clone(),
+            originator: None,
+            model: None,
+            size: None,
+            modified: None,
+            first_line: None,
+        }
+    }
+}
+
+/// Finds the first Codex session matching `branch`.
+///
+/// Applies the same sanity checks and lexicographic selection order as `amg resume` (without
+/// the CLI's optional filters).
+///
+/// # Arguments
+///
+/// * `codexdir` - The Codex directory to search in
+/// * `branch` - The git branch name to match against
+///
+/// # Returns
+///
+/// Returns the first matching session, or `None` if no session matches.
+///
+/// # Errors
+///
+/// Returns an error if the codexdir cannot be read.
+pub fn find_first(codexdir: &Path, branch: &str) -> Result<Option<Session>> {
+    let outcome = scan::find_first_session(codexdir, branch, &ScanOptions::default())?;
+    Ok(outcome.session.map(Session::from))
+}
+
+/// Builds the command that resumes `session` in codex, as `amg resume` would run it inline.
+///
+/// Codex gets the default workspace-write sandbox with network access and amg's default
+/// model, with `repo` (and its git directory), the codexdir, and the session cwd granted, plus
+/// the usual home-directory and system directories that exist. The command starts in the
+/// session's cwd. Config files are not read, so `model`, `network_access`, and
+/// `extra_add_dirs` keep their defaults.
+///
+/// # Arguments
+///
+/// * `session` - The session to resume, as returned by [`find_first`]
+/// * `repo` - The repository to grant codex access to
+/// * `codexdir` - The Codex directory the session was found in
+///
+/// # Returns
+///
+/// Returns the command, ready to be spawned.
+///
+/// # Errors
+///
+/// Returns an error if the session cwd is not a directory or a repo's `.git` entry cannot be
+/// read.
+pub fn resume_command(session: &Session, repo: &Path, codexdir: &Path) -> Result<Command> {
+    util::require_dir(&session.cwd, "session cwd", None)?;
+    let home = util::home_dir();
+    let optional_dirs = codex_cmd::optional_sandbox_dirs(home.as_deref(), &HOME_SANDBOX_DIRS, true);
+    let cmd = codex_cmd::build_codex_cmd(
+        codex_cmd::ResumeTarget {
+            repo,
+            codexdir,
+            session: &scan::Session::from(session),
+            start_dir: &session.cwd,
+            optional_dirs: &optional_dirs,
+        },
+        codex_cmd::CodexOptions {
+            mode: codex_cmd::SandboxMode::WorkspaceWrite,
+            model: DEFAULT_MODEL,
+            network_access: true,
+            strict: false,
+            canonicalize: true,
+            repo_discovery: true,
+            minimize_dirs: false,
+            narrow_codexdir: false,
+            add_worktrees: false,
+        },
+    )?;
+    let mut command = Command::new(&cmd.program);
+    command.args(&cmd.args).current_dir(&session.cwd);
+    Ok(command)
+}
+
+/// Finds the first Codex session matching `branch`, without blocking the caller's executor.
+///
+/// Applies the same sanity checks and lexicographic selection order as the synchronous scan
+/// behind `amg resume` (without the CLI's optional filters).
+///
+/// # Arguments
+///
+/// * `codexdir` - The Codex directory to search in
+/// * `branch` - The git branch name to match against
+///
+/// # Returns
+///
+/// Returns the first matching session, or `None` if no session matches.
+///
+/// # Errors
+///
+/// Returns an error if the codexdir cannot be read or the scan thread fails.
+#[cfg(feature = "async")]
+pub async fn find_first_async(
+    codexdir: impl Into<PathBuf>,
+    branch: impl Into<String>,
+) -> Result<Option<Session>> {
+    let codexdir = codexdir.into();
+    let branch = branch.into();
+    spawn_blocking(move || {
+        scan::find_first_session_batched(
+            &codexdir,
+            &branch,
+            &ScanOptions::default(),
+            MAX_CONCURRENT_READS,
+        )
+    })
+    .await?
+    .map(|session| session.map(Session::from))
+}
+
+/// Runs `f` on a new thread and returns a future resolving to its result.
+#[cfg(feature = "async")]
+fn spawn_blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> Blocking<T> {
+    let slot = Arc::new(Mutex::new(Slot {
+        result: None,
+        waker: None,
+    }));
+    let shared = Arc::clone(&slot);
+    thread::spawn(move || {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f))
+            .map_err(|_| anyhow::anyhow!("the session scan thread panicked"));
+        let mut slot = shared
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        slot.result = Some(result);
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+    });
+    Blocking { slot }
+}
+
+/// The result slot shared between a [`Blocking`] future and its thread.
+#[cfg(feature = "async")]
+struct Slot<T> {
+    result: Option<Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// Future returned by [`spawn_blocking`].
+#[cfg(feature = "async")]
+struct Blocking<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+#[cfg(feature = "async")]
+impl<T> Future for Blocking<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        let mut slot = self
+            .slot
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match slot.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::{SessionFile, temp_dir};
+    use rstest::rstest;
+    #[cfg(feature = "async")]
+    use std::task::Wake;
+
+    /// Wakes the test thread blocked in [`block_on`].
+    #[cfg(feature = "async")]
+    struct ThreadWaker(thread::Thread);
+
+    #[cfg(feature = "async")]
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    #[cfg(feature = "async")]
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = TaskContext::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    fn id(n: usize) -> String {
+        format!("0199a6b2-3c4d-7e8f-9a0b-{n:012x}")
+    }
+
+    #[rstest]
+    fn finds_the_first_session_for_the_branch(#[from(temp_dir)] dir: PathBuf) {
+        SessionFile::new("other", &id(1)).write(dir.join("a/rollout-1.jsonl"));
+        SessionFile::new("main", &id(2)).write(dir.join("b/rollout-1.jsonl"));
+        SessionFile::new("main", &id(3)).write(dir.join("b/rollout-2.jsonl"));
+
+        let found = find_first(&dir, "main").unwrap().expect("should match");
+
+        assert_eq!(found.id, id(2));
+        assert_eq!(found.source_jsonl, dir.join("b/rollout-1.jsonl"));
+        assert_eq!(find_first(&dir, "gone").unwrap(), None);
+    }
+
+    #[rstest]
+    fn resume_commands_start_in_the_session_cwd(#[from(temp_dir)] dir: PathBuf) {
+        let session = Session {
+            cwd: dir.clone(),
+            id: id(7),
+            source_jsonl: dir.join("rollout-7.jsonl"),
+            commit_hash: None,
+        };
+
+        let command = resume_command(&session, &dir, &dir).unwrap();
+        let args: Vec<_> = command.get_args().collect();
+
+        assert_eq!(command.get_program(), "codex");
+        assert_eq!(command.get_current_dir(), Some(dir.as_path()));
+        assert!(args.contains(&OsStr::new("workspace-write")), "{args:?}");
+        assert_eq!(args[args.len() - 2..], ["resume", id(7).as_str()]);
+    }
+
+    #[rstest]
+    fn resume_commands_need_the_session_cwd(#[from(temp_dir)] dir: PathBuf) {
+        let session = Session {
+            cwd: dir.join("gone"),
+            id: id(7),
+            source_jsonl: dir.join("rollout-7.jsonl"),
+            commit_hash: None,
+        };
+
+        assert!(resume_command(&session, &dir, &dir).is_err());
+    }
+
+    #[cfg(feature = "async")]
+    #[rstest]
+    #[case::first_batch(0)]
+    #[case::later_batch(2 * MAX_CONCURRENT_READS + 3)]
+    fn matches_the_sync_scan(#[from(temp_dir)] dir: PathBuf, #[case] decoys: usize) {
+        for n in 0..decoys {
+            SessionFile::new("other", &id(n)).write(dir.join(format!("a/rollout-{n:03}.jsonl")));
+        }
+        SessionFile::new("main", &id(1_000)).write(dir.join("b/rollout-1.jsonl"));
+        SessionFile::new("main", &id(1_001)).write(dir.join("b/rollout-2.jsonl"));
+
+        let found = block_on(find_first_async(&dir, "main")).unwrap();
+        let sync = scan::find_first_session(&dir, "main", &ScanOptions::default()).unwrap();
+
+        let found = found.expect("should match");
+        assert_eq!(found.id, id(1_000));
+        assert_eq!(Some(found), sync.session.map(Session::from));
+    }
+
+    #[cfg(feature = "async")]
+    #[rstest]
+    fn prefers_the_same_copy_as_the_sync_api(#[from(temp_dir)] dir: PathBuf) {
+        let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        SessionFile::new("main", &id(1))
+            .modified(at(1_000))
+            .write(dir.join("b/rollout-1.jsonl"));
+        let conflicted = SessionFile::new("main", &id(1))
+            .modified(at(2_000))
+            .write(dir.join("b/rollout-1 (1).jsonl"));
+
+        let found = block_on(find_first_async(&dir, "main")).unwrap();
+
+        assert_eq!(found, find_first(&dir, "main").unwrap());
+        assert_eq!(found.unwrap().source_jsonl, conflicted);
+    }
+
+    #[cfg(feature = "async")]
+    #[rstest]
+    fn reports_no_match(#[from(temp_dir)] dir: PathBuf) {
+        SessionFile::new("other", &id(1)).write(dir.join("rollout-1.jsonl"));
+
+        assert_eq!(block_on(find_first_async(&dir, "main")).unwrap(), None);
+    }
+
+    #[cfg(feature = "async")]
+    #[rstest]
+    fn fails_for_a_missing_codexdir(#[from(temp_dir)] dir: PathBuf) {
+        assert!(block_on(find_first_async(dir.join("missing"), "main")).is_err());
+    }
+}
@@ -0,0 +1,252 @@
+//! Matched session details.
+//!
+//! This module implements the `show` subcommand, which prints what amg parsed from the
+//! session `resume` would use for a branch: its id, cwd, file, size, modification time, and
+//! the optional first-line fields. When those look wrong, `--raw` also prints the first line
+//! exactly as amg read it (only a byte order mark and a trailing carriage return are removed),
+//! and `--raw-only` prints nothing else. The scan keeps the line it already read (see
+//! [`ScanOptions::keep_first_line`](super::scan::ScanOptions::keep_first_line)), so the file
+//! is not read twice. With `--json`, the summary is one JSON object and the raw line is
+//! embedded as a JSON string.
+//!
+//! `--print-session-dir` prints only the directory containing the session file, and
+//! `--open-dir` opens that directory in the platform file manager (see
+//! [`process::open_directory`]), printing it instead when no file manager can be opened.
+
+use std::io::Write;
+
+use serde::Serialize;
+
+use super::args::ShowArgs;
+use super::prelude::*;
+use super::scan::Session;
+use super::{alias, cache, config, process, util};
+
+/// `--json` output of `amg show`.
+#[derive(Debug, Serialize)]
+struct ShowJson<'a> {
+    id: &'a str,
+    branch: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recorded_branch: Option<&'a str>,
+    cwd: &'a Path,
+    source_jsonl: &'a Path,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mtime: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    commit_hash: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    originator: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw: Option<&'a str>,
+}
+
+/// What `amg show` prints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum View {
+    /// The parsed summary only.
+    Summary,
+    /// The summary followed by the raw first line (`--raw`).
+    SummaryAndRaw,
+    /// The raw first line only (`--raw-only`).
+    RawOnly,
+}
+
+/// Handles the `show` subcommand.
+///
+/// # Errors
+///
+/// Returns an error if the codexdir is invalid, no matching session is found, or stdout
+/// cannot be written.
+pub(super) fn run_show(args: ShowArgs) -> Result<ExitCode> {
+    let codexdir = args
+        .location
+        .codexdir
+        .map(Ok)
+        .unwrap_or_else(config::default_codexdir)?;
+    util::require_dir(&codexdir, "codexdir", Some("CODEX_CODEXDIR"))?;
+    super::check_codexdir(&codexdir, args.selection.walk.force_scan)?;
+
+    let view = match (args.raw, args.raw_only) {
+        (_, true) => View::RawOnly,
+        (true, false) => View::SummaryAndRaw,
+        (false, false) => View::Summary,
+    };
+    let branch = alias::resolve_branch(&args.branch);
+    let mut scan_options = super::scan_options(&args.selection)?;
+    scan_options.keep_first_line = view != View::Summary;
+    let outcome = cache::find_session(&codexdir, &branch, &scan_options, !args.cache.no_cache)?;
+    let Some(session) = outcome.session else {
+        bail!(
+            "{}",
+            super::no_session_message(&codexdir, &branch, &outcome.stats)
+        );
+    };
+
+    let mut out = io::stdout().lock();
+    if args.print_session_dir || args.open_dir {
+        let dir = session_dir(&session);
+        if args.open_dir {
+            match process::open_directory(dir) {
+                Ok(()) => return Ok(ExitCode::SUCCESS),
+                Err(err) => {
+                    warn!("cannot open the session directory; printing it instead: {err:#}")
+                }
+            }
+        }
+        writeln!(out, "{}", dir.display()).context("failed to write the session directory")?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.json {
+        serde_json::to_writer(&mut out, &show_json(&session, &branch, view))?;
+        writeln!(out)
+    } else {
+        write_text(&mut out, &session, &branch, view)
+    }
+    .context("failed to write the session")?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Returns the directory containing the session file.
+fn session_dir(session: &Session) -> &Path {
+    session
+        .source_jsonl
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+}
+
+fn show_json<'a>(session: &'a Session, branch: &'a str, view: View) -> ShowJson<'a> {
+    ShowJson {
+        id: &session.id,
+        branch,
+        recorded_branch: session.branch.as_deref(),
+        cwd: &session.cwd,
+        source_jsonl: &session.source_jsonl,
+        size: session.size,
+        mtime: session.modified_rfc3339(),
+        commit_hash: session.commit_hash.as_deref(),
+        originator: session.originator.as_deref(),
+        model: session.model.as_deref(),
+        raw: match view {
+            View::Summary => None,
+            View::SummaryAndRaw | View::RawOnly => session.first_line.as_deref(),
+        },
+    }
+}
+
+/// Prints the summary as aligned `key: value` lines and, for the raw views, the first line
+/// as read (after a blank line when it follows the summary).
+fn write_text(out: &mut impl Write, session: &Session, branch: &str, view: View) -> io::Result<()> {
+    if view != View::RawOnly {
+        let rows = [
+            ("id", Some(session.id.clone())),
+            ("branch", Some(branch.to_owned())),
+            ("cwd", Some(util::human_path(&session.cwd))),
+            ("file", Some(util::human_path(&session.source_jsonl))),
+            ("size", session.size.map(|size| format!("{size} bytes"))),
+            ("modified", session.modified_rfc3339()),
+            ("commit", session.commit_hash.clone()),
+            ("originator", session.originator.clone()),
+            ("model", session.model.clone()),
+        ];
+        for (key, value) in rows {
+            if let Some(value) = value {
+                writeln!(out, "{:<11} {value}", format!("{key}:"))?;
+            }
+        }
+    }
+    if view == View::Summary {
+        return Ok(());
+    }
+    if view == View::SummaryAndRaw {
+        writeln!(out)?;
+    }
+    writeln!(out, "{}", session.first_line.as_deref().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::matched_session;
+
+    const LINE: &str = r#"{"payload":{"id":"0199","cwd":"/w","note":"café ☕"}}"#;
+
+    fn session() -> Session {
+        Session {
+            source_jsonl: PathBuf::from("/codex/rollout-a.jsonl"),
+            originator: Some("codex_cli_rs".to_owned()),
+            size: Some(300),
+            first_line: Some(LINE.to_owned()),
+            ..matched_session("0199")
+        }
+    }
+
+    fn text(view: View) -> String {
+        let mut out = Vec::new();
+        write_text(&mut out, &session(), "main", view).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn summary_skips_unknown_fields() {
+        assert_eq!(
+            text(View::Summary),
+            "id:         0199\nbranch:     main\ncwd:        /w\n\
+             file:       /codex/rollout-a.jsonl\nsize:       300 bytes\n\
+             originator: codex_cli_rs\n"
+        );
+    }
+
+    #[test]
+    fn raw_views_print_the_line_verbatim() {
+        assert_eq!(text(View::RawOnly), format!("{LINE}\n"));
+        assert_eq!(
+            text(View::SummaryAndRaw),
+            format!("{}\n{LINE}\n", text(View::Summary))
+        );
+    }
+
+    #[test]
+    fn the_session_dir_is_the_file_s_parent() {
+        assert_eq!(session_dir(&session()), Path::new("/codex"));
+        let relative = Session {
+            source_jsonl: PathBuf::from("rollout-a.jsonl"),
+            ..session()
+        };
+        assert_eq!(session_dir(&relative), Path::new("."));
+    }
+
+    #[test]
+    fn json_embeds_the_raw_line_as_a_string() {
+        let session = session();
+
+        let json = serde_json::to_value(show_json(&session, "main", View::SummaryAndRaw)).unwrap();
+
+        assert_eq!(json["raw"], LINE);
+        assert_eq!(json["size"], 300);
+        assert!(json.get("mtime").is_none());
+        let summary = serde_json::to_value(show_json(&session, "main", View::Summary)).unwrap();
+        assert!(summary.get("raw").is_none());
+    }
+
+    #[test]
+    fn json_names_the_branch_the_session_records() {
+        let prefixed = Session {
+            branch: Some("me/main".to_owned()),
+            ..session()
+        };
+
+        let json = serde_json::to_value(show_json(&prefixed, "main", View::Summary)).unwrap();
+
+        assert_eq!(json["branch"], "main");
+        assert_eq!(json["recorded_branch"], "me/main");
+        let unrecorded =
+            serde_json::to_value(show_json(&session(), "main", View::Summary)).unwrap();
+        assert!(unrecorded.get("recorded_branch").is_none());
+    }
+}
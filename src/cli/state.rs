@@ -0,0 +1,372 @@
+//! Persistent amg state.
+//!
+//! This module loads and saves amg's own state file (JSON), which holds data such as
+//! branch aliases, session tags, the tmux windows / processes amg launched, and the options
+//! remembered per branch. Codex's session files are
+//! never modified.
+//!
+//! The state file lives at `$AMG_STATE_FILE` if set, otherwise
+//! `$XDG_DATA_HOME/amg/state.json`, falling back to `$HOME/.local/share/amg/state.json`.
+//!
+//! Several amg invocations may run at once (one per tmux pane), so every change goes through
+//! [`update`] (or [`State::update`]): it holds an advisory lock on a `state.json.lock` file
+//! next to the state file while it loads, modifies, and saves, so concurrent changes are
+//! applied one after another instead of overwriting each other. The lock lives in its own file
+//! because saving replaces the state file with an atomic rename, so readers never see a
+//! partially written file and [`State::load`] needs no lock.
+//!
+//! The file records the schema version it was written with (`version`; absent before
+//! versioning). Sections this amg does not know, such as those written by a newer amg, are
+//! kept as they are when the file is saved again.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::policy;
+use super::prelude::*;
+use super::remember::Remembered;
+use super::util;
+
+/// The state file schema version this amg writes.
+const STATE_VERSION: u32 = 1;
+
+/// amg's persisted state.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(super) struct State {
+    /// The schema version the file was written with (0 before versioning).
+    #[serde(default)]
+    version: u32,
+    /// Friendly names mapped to the git branches they stand for.
+    #[serde(default)]
+    pub(super) aliases: BTreeMap<String, String>,
+    /// Tags attached to sessions, keyed by session id.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(super) tags: BTreeMap<String, BTreeSet<String>>,
+    /// Running codex instances launched by amg, keyed by session id.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(super) launches: BTreeMap<String, Launch>,
+    /// Options remembered from each branch's last successful resume.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(super) remembered: BTreeMap<String, Remembered>,
+    /// Sections this amg does not know, kept as they were.
+    #[serde(flatten)]
+    unknown: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A codex instance launched by amg.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct Launch {
+    /// The branch the session was resumed for.
+    pub(super) branch: String,
+    /// The branch the session file records, if any (absent in records written before it was
+    /// kept).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) recorded_branch: Option<String>,
+    /// Where the instance runs.
+    #[serde(flatten)]
+    pub(super) target: LaunchTarget,
+}
+
+/// Where a launched codex instance runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(super) enum LaunchTarget {
+    /// A tmux window, identified by its window id (such as `@12`).
+    TmuxWindow { window_id: String },
+    /// An inline process, identified by its pid.
+    Process { pid: u32 },
+}
+
+impl State {
+    /// Loads the state from `path`, returning the default state if the file does not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed.
+    pub(super) fn load(path: &Path) -> Result<Self> {
+        let state: Self = match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).with_context(|| {
+                format!("failed to parse state file {}", util::human_path(path))
+            })?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(err).with_context(|| {
+                    format!("failed to read state file {}", util::human_path(path))
+                });
+            }
+        };
+        if state.version > STATE_VERSION {
+            debug!(
+                version = state.version,
+                supported = STATE_VERSION,
+                "the state file was written by a newer amg; keeping the sections it added"
+            );
+        }
+        Ok(state)
+    }
+
+    /// Loads the state from `path`, applies `modify`, and saves the result if it changed,
+    /// holding the state lock throughout.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The state file
+    /// * `modify` - Changes the state; an error leaves the file untouched
+    ///
+    /// # Returns
+    ///
+    /// Returns what `modify` returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock cannot be taken, the file cannot be loaded or saved, or
+    /// `modify` fails.
+    pub(super) fn update<T>(path: &Path, modify: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let _lock = lock(path)?;
+        let mut state = Self::load(path)?;
+        let loaded = state.clone();
+        let output = modify(&mut state)?;
+        if state != loaded {
+            state.version = state.version.max(STATE_VERSION);
+            state.save(path)?;
+        }
+        Ok(output)
+    }
+
+    /// Saves the state to `path` atomically, creating parent directories as needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be created or the file cannot be written.
+    fn save(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        util::write_atomic(path, &(content + "\n"))
+            .with_context(|| format!("failed to save state file {}", util::human_path(path)))
+    }
+}
+
+/// Applies `modify` to the state file at [`state_path`] (see [`State::update`]).
+///
+/// # Errors
+///
+/// Returns an error if the state file cannot be located, locked, loaded, or saved, or
+/// `modify` fails.
+pub(super) fn update<T>(modify: impl FnOnce(&mut State) -> Result<T>) -> Result<T> {
+    State::update(&state_path()?, modify)
+}
+
+/// Takes the advisory lock guarding changes to the state file at `path`, waiting for other
+/// amg processes to release it. The lock is released when the returned file is dropped.
+///
+/// # Errors
+///
+/// Returns an error if the lock file cannot be created or locked.
+fn lock(path: &Path) -> Result<fs::File> {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(".lock");
+    let lock_path = path.with_file_name(name);
+    if let Some(parent) = lock_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create directory {}", util::human_path(parent)))?;
+    }
+    let file = fs::File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("failed to open {}", util::human_path(&lock_path)))?;
+    file.lock()
+        .with_context(|| format!("failed to lock {}", util::human_path(&lock_path)))?;
+    Ok(file)
+}
+
+/// Resolves the state file path.
+///
+/// # Errors
+///
+/// Returns an error in portable mode, or if neither `$AMG_STATE_FILE`, `$XDG_DATA_HOME`, nor
+/// `$HOME` is set.
+pub(super) fn state_path() -> Result<PathBuf> {
+    policy::require_home_files("the amg state file (aliases, tags, launches, remembered options)")?;
+    if let Some(path) = util::non_empty_env(ENV_AMG_STATE_FILE) {
+        return Ok(path);
+    }
+    let data_home = util::non_empty_env(ENV_XDG_DATA_HOME)
+        .or_else(|| util::non_empty_env(ENV_HOME).map(|home| home.join(".local/share")))
+        .with_context(|| {
+            format!("cannot locate the amg state file; set {ENV_AMG_STATE_FILE} or $HOME")
+        })?;
+    Ok(data_home.join(AMG_DIR).join(STATE_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::temp_dir;
+    use rstest::rstest;
+
+    #[rstest]
+    fn missing_file_loads_default_state(#[from(temp_dir)] dir: PathBuf) {
+        let state = State::load(&dir.join("state.json")).expect("load should succeed");
+
+        assert!(state.aliases.is_empty());
+    }
+
+    #[rstest]
+    fn round_trips_through_save_and_load(#[from(temp_dir)] dir: PathBuf) {
+        let path = dir.join("nested/state.json");
+        let mut state = State::default();
+        state
+            .aliases
+            .insert("ingestion".into(), "feature/PROJ-4821".into());
+
+        state.save(&path).expect("save should succeed");
+        let loaded = State::load(&path).expect("load should succeed");
+
+        assert_eq!(loaded.aliases, state.aliases);
+    }
+
+    #[rstest]
+    fn round_trips_launch_records(#[from(temp_dir)] dir: PathBuf) {
+        let path = dir.join("state.json");
+        let mut state = State::default();
+        state.launches.insert(
+            "id-1".into(),
+            Launch {
+                branch: "main".into(),
+                recorded_branch: Some("origin/main".into()),
+                target: LaunchTarget::TmuxWindow {
+                    window_id: "@7".into(),
+                },
+            },
+        );
+        state.launches.insert(
+            "id-2".into(),
+            Launch {
+                branch: "dev".into(),
+                recorded_branch: None,
+                target: LaunchTarget::Process { pid: 4242 },
+            },
+        );
+
+        state.save(&path).expect("save should succeed");
+        let loaded = State::load(&path).expect("load should succeed");
+
+        assert_eq!(loaded.launches, state.launches);
+        let raw = fs::read_to_string(&path).unwrap();
+        assert!(raw.contains("\"kind\": \"tmux_window\""), "{raw}");
+    }
+
+    #[rstest]
+    fn tolerates_missing_sections(#[from(temp_dir)] dir: PathBuf) {
+        let path = dir.join("state.json");
+        fs::write(&path, "{}").unwrap();
+
+        let state = State::load(&path).expect("load should succeed");
+
+        assert!(state.aliases.is_empty());
+        assert!(state.launches.is_empty());
+    }
+
+    #[rstest]
+    fn rejects_corrupt_file(#[from(temp_dir)] dir: PathBuf) {
+        let path = dir.join("state.json");
+        fs::write(&path, "not json").unwrap();
+
+        let err = State::load(&path).unwrap_err();
+
+        assert!(err.to_string().contains("failed to parse state file"));
+    }
+
+    #[rstest]
+    fn save_leaves_no_temporary_files(#[from(temp_dir)] dir: PathBuf) {
+        let path = dir.join("state.json");
+
+        State::default().save(&path).expect("save should succeed");
+
+        let names: Vec<_> = fs::read_dir(&dir).unwrap().flatten().collect();
+        assert_eq!(names.len(), 1);
+    }
+
+    fn add_alias(path: &Path, name: &str) {
+        State::update(path, |state| {
+            state.aliases.insert(name.to_owned(), "main".to_owned());
+            Ok(())
+        })
+        .expect("update should succeed");
+    }
+
+    #[rstest]
+    fn updates_save_only_changes(#[from(temp_dir)] dir: PathBuf) {
+        let path = dir.join("state.json");
+
+        let count = State::update(&path, |state| Ok(state.aliases.len())).unwrap();
+        assert_eq!(count, 0);
+        assert!(!path.exists());
+
+        add_alias(&path, "a");
+        let raw = fs::read_to_string(&path).unwrap();
+        assert!(raw.contains("\"version\": 1"), "{raw}");
+        assert!(dir.join("state.json.lock").exists());
+    }
+
+    #[rstest]
+    fn a_failed_update_leaves_the_file_untouched(#[from(temp_dir)] dir: PathBuf) {
+        let path = dir.join("state.json");
+        add_alias(&path, "a");
+        let before = fs::read_to_string(&path).unwrap();
+
+        let result = State::update(&path, |state| -> Result<()> {
+            state.aliases.clear();
+            bail!("refused")
+        });
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), before);
+    }
+
+    #[rstest]
+    fn keeps_unknown_sections_and_newer_versions(#[from(temp_dir)] dir: PathBuf) {
+        let path = dir.join("state.json");
+        fs::write(
+            &path,
+            r#"{"version": 7, "pins": {"main": ["id-1"]}, "aliases": {}}"#,
+        )
+        .unwrap();
+
+        add_alias(&path, "a");
+
+        let raw: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(raw["version"], 7);
+        assert_eq!(raw["pins"], serde_json::json!({ "main": ["id-1"] }));
+        assert_eq!(raw["aliases"]["a"], "main");
+    }
+
+    #[rstest]
+    fn concurrent_updates_are_not_lost(#[from(temp_dir)] dir: PathBuf) {
+        const WRITERS: usize = 8;
+        const UPDATES: usize = 25;
+        let path = dir.join("state.json");
+
+        std::thread::scope(|scope| {
+            for writer in 0..WRITERS {
+                let path = &path;
+                scope.spawn(move || {
+                    for update in 0..UPDATES {
+                        add_alias(path, &format!("w{writer}-{update}"));
+                    }
+                });
+            }
+            scope.spawn(|| {
+                for _ in 0..WRITERS * UPDATES {
+                    State::load(&path).expect("a reader should never see a partial file");
+                }
+            });
+        });
+
+        let state = State::load(&path).unwrap();
+        assert_eq!(state.aliases.len(), WRITERS * UPDATES);
+    }
+}
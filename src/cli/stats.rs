@@ -0,0 +1,336 @@
+//! Session statistics.
+//!
+//! This module implements the `stats` subcommand: a single pass over the codexdir's session
+//! files that counts them (optionally for one branch) and, with `--trend`, buckets them by
+//! day for a quick activity overview. A file's day comes from its rollout file name
+//! (`rollout-YYYY-MM-DDTHH-MM-SS-...`), which Codex writes in UTC, or else from its
+//! modification time, also taken in UTC, so the two sources agree.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+
+use serde::Serialize;
+
+use super::args::StatsArgs;
+use super::config;
+use super::datetime::UtcDateTime;
+use super::prelude::*;
+use super::scan::{self, SessionFile};
+use super::util;
+
+/// Length of the longest bar in the `--trend` view, in characters.
+const TREND_BAR_WIDTH: usize = 40;
+
+/// Seconds per day, for stepping through the `--trend` window.
+const SECS_PER_DAY: u64 = 86_400;
+
+/// A UTC calendar day, ordered chronologically.
+pub(super) type Day = (i64, u32, u32);
+
+/// The result of the aggregation pass.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Stats {
+    /// Session files counted.
+    sessions: usize,
+    /// Counted files whose first line could not be parsed.
+    unparseable: usize,
+    /// Distinct branches among the counted files.
+    branches: BTreeSet<String>,
+    /// Per-day counts over the `--trend` window (every day present, oldest first).
+    trend: Option<BTreeMap<Day, usize>>,
+}
+
+/// One day of the `--trend` view in `--json` output.
+#[derive(Debug, Serialize)]
+struct DayCount {
+    day: String,
+    count: usize,
+}
+
+/// `--json` output of `amg stats`.
+#[derive(Debug, Serialize)]
+struct StatsJson {
+    sessions: usize,
+    unparseable: usize,
+    branches: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trend: Option<Vec<DayCount>>,
+}
+
+/// Handles the `stats` subcommand.
+///
+/// # Errors
+///
+/// Returns an error if the codexdir is invalid or stdout cannot be written.
+pub(super) fn run_stats(args: StatsArgs) -> Result<ExitCode> {
+    let codexdir = args
+        .location
+        .codexdir
+        .map(Ok)
+        .unwrap_or_else(config::default_codexdir)?;
+    util::require_dir(&codexdir, "codexdir", Some("CODEX_CODEXDIR"))?;
+    super::check_codexdir(&codexdir, args.walk.force_scan)?;
+
+    let window = args
+        .trend
+        .map(|trend| trend_window(SystemTime::now(), trend));
+    let stats = aggregate(
+        scan::session_files(&codexdir)?,
+        args.branch.as_deref(),
+        window.as_deref(),
+    );
+    let mut out = io::stdout().lock();
+    if args.json {
+        serde_json::to_writer(&mut out, &stats_json(&stats))?;
+        writeln!(out)
+    } else {
+        write_text(&mut out, &stats)
+    }
+    .context("failed to write stats")?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Returns every UTC day from `now - trend` to `now`, oldest first.
+fn trend_window(now: SystemTime, trend: Duration) -> Vec<Day> {
+    let start = now.checked_sub(trend).unwrap_or(SystemTime::UNIX_EPOCH);
+    let today = day_of(now);
+    let mut days = Vec::new();
+    let mut at = start;
+    loop {
+        let day = day_of(at);
+        days.push(day);
+        if day >= today {
+            return days;
+        }
+        at += Duration::from_secs(SECS_PER_DAY);
+    }
+}
+
+/// Counts `files` in one pass, keeping those on `branch` (all if `None`) and bucketing them
+/// into `window` when given.
+fn aggregate(
+    files: impl IntoIterator<Item = SessionFile>,
+    branch: Option<&str>,
+    window: Option<&[Day]>,
+) -> Stats {
+    let mut stats = Stats {
+        trend: window.map(|days| days.iter().map(|&day| (day, 0)).collect()),
+        ..Stats::default()
+    };
+    for file in files {
+        let file_branch = file.fields.as_ref().ok().and_then(|f| f.branch.as_deref());
+        if branch.is_some_and(|want| file_branch != Some(want)) {
+            continue;
+        }
+        stats.sessions += 1;
+        match file_branch {
+            _ if file.fields.is_err() => stats.unparseable += 1,
+            Some(name) if !name.trim().is_empty() => {
+                stats.branches.insert(name.to_owned());
+            }
+            _ => {}
+        }
+        if let Some(trend) = &mut stats.trend
+            && let Some(count) = file_day(&file).and_then(|day| trend.get_mut(&day))
+        {
+            *count += 1;
+        }
+    }
+    stats
+}
+
+/// Returns the UTC day a session file belongs to: from its rollout file name, or else from
+/// its modification time.
+fn file_day(file: &SessionFile) -> Option<Day> {
+    file.path
+        .file_name()
+        .and_then(OsStr::to_str)
+        .and_then(day_from_file_name)
+        .or_else(|| file.modified.map(day_of))
+}
+
+/// Parses the date of a `rollout-YYYY-MM-DDTHH-MM-SS-...` file name.
+pub(super) fn day_from_file_name(name: &str) -> Option<Day> {
+    let stamp = name.strip_prefix("rollout-")?.get(..19)?;
+    if !stamp.is_ascii() || stamp.as_bytes()[13] != b'-' || stamp.as_bytes()[16] != b'-' {
+        return None;
+    }
+    // Codex writes the time with dashes; restore the colons RFC 3339 expects.
+    let stamp = format!("{}:{}:{}", &stamp[..13], &stamp[14..16], &stamp[17..]);
+    UtcDateTime::parse_rfc3339(&stamp).map(|dt| (dt.year, dt.month, dt.day))
+}
+
+/// Returns the UTC day of `time`.
+fn day_of(time: SystemTime) -> Day {
+    let dt = UtcDateTime::from_system_time(time);
+    (dt.year, dt.month, dt.day)
+}
+
+/// Formats a day as `YYYY-MM-DD`.
+fn format_day((year, month, day): Day) -> String {
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Returns a bar for `count`, scaled so that `max` fills [`TREND_BAR_WIDTH`]; any non-zero
+/// count gets at least one block.
+fn bar(count: usize, max: usize) -> String {
+    let width = match max {
+        0 => 0,
+        max => (count * TREND_BAR_WIDTH).div_ceil(max),
+    };
+    "█".repeat(width)
+}
+
+fn stats_json(stats: &Stats) -> StatsJson {
+    StatsJson {
+        sessions: stats.sessions,
+        unparseable: stats.unparseable,
+        branches: stats.branches.len(),
+        trend: stats.trend.as_ref().map(|trend| {
+            trend
+                .iter()
+                .map(|(&day, &count)| DayCount {
+                    day: format_day(day),
+                    count,
+                })
+                .collect()
+        }),
+    }
+}
+
+fn write_text(out: &mut impl Write, stats: &Stats) -> io::Result<()> {
+    writeln!(
+        out,
+        "{} sessions ({} unparseable) on {} branches",
+        stats.sessions,
+        stats.unparseable,
+        stats.branches.len()
+    )?;
+    let Some(trend) = &stats.trend else {
+        return Ok(());
+    };
+    let max = trend.values().copied().max().unwrap_or(0);
+    for (&day, &count) in trend {
+        writeln!(out, "{}  {count:>4}  {}", format_day(day), bar(count, max))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::scan::SessionFields;
+    use rstest::rstest;
+
+    /// 2026-10-14T12:00:00Z.
+    fn noon() -> SystemTime {
+        SystemTime::UNIX_EPOCH
+            + Duration::from_secs(1_792_000_000 / SECS_PER_DAY * SECS_PER_DAY)
+            + Duration::from_secs(SECS_PER_DAY / 2)
+    }
+
+    fn file(name: &str, branch: Option<&str>, modified: SystemTime) -> SessionFile {
+        SessionFile {
+            path: PathBuf::from("/codex/sessions").join(name),
+            size: 1,
+            modified: Some(modified),
+            fields: Ok(SessionFields {
+                branch: branch.map(str::to_owned),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[rstest]
+    #[case("rollout-2025-03-09T23-59-59-abc.jsonl", Some((2025, 3, 9)))]
+    #[case("rollout-2025-03-09T08-07-06.jsonl", Some((2025, 3, 9)))]
+    #[case("rollout-2025-13-09T08-07-06-abc.jsonl", None)]
+    #[case("rollout-short.jsonl", None)]
+    #[case("session.jsonl", None)]
+    fn parses_days_from_rollout_names(#[case] name: &str, #[case] expected: Option<Day>) {
+        assert_eq!(day_from_file_name(name), expected);
+    }
+
+    #[test]
+    fn falls_back_to_the_utc_mtime() {
+        let plain = file("escalation.jsonl", None, noon());
+        let named = file("rollout-2025-03-09T08-07-06-abc.jsonl", None, noon());
+
+        assert_eq!(file_day(&plain), Some((2026, 10, 14)));
+        assert_eq!(file_day(&named), Some((2025, 3, 9)));
+    }
+
+    #[test]
+    fn trend_window_covers_every_day() {
+        let days = trend_window(noon(), Duration::from_secs(2 * SECS_PER_DAY));
+
+        assert_eq!(days, [(2026, 10, 12), (2026, 10, 13), (2026, 10, 14)]);
+        assert_eq!(trend_window(noon(), Duration::ZERO), [(2026, 10, 14)]);
+    }
+
+    #[test]
+    fn aggregates_and_buckets_in_one_pass() {
+        let day = Duration::from_secs(SECS_PER_DAY);
+        let files = vec![
+            file("a.jsonl", Some("main"), noon()),
+            file("b.jsonl", Some("main"), noon()),
+            file("c.jsonl", Some("dev"), noon() - day),
+            file("d.jsonl", Some("main"), noon() - day * 30),
+            SessionFile {
+                fields: Err("bad".into()),
+                ..file("e.jsonl", None, noon())
+            },
+        ];
+        let window = trend_window(noon(), day * 2);
+
+        let stats = aggregate(files, None, Some(&window));
+
+        assert_eq!(stats.sessions, 5);
+        assert_eq!(stats.unparseable, 1);
+        assert_eq!(stats.branches.len(), 2);
+        let counts: Vec<usize> = stats.trend.unwrap().into_values().collect();
+        assert_eq!(counts, [0, 1, 3]);
+    }
+
+    #[test]
+    fn filters_by_branch() {
+        let files = vec![
+            file("a.jsonl", Some("main"), noon()),
+            file("b.jsonl", Some("dev"), noon()),
+        ];
+
+        let stats = aggregate(files, Some("dev"), None);
+
+        assert_eq!(stats.sessions, 1);
+        assert_eq!(stats.trend, None);
+    }
+
+    #[rstest]
+    #[case(0, 10, "")]
+    #[case(1, 100, "█")]
+    #[case(5, 10, &"█".repeat(TREND_BAR_WIDTH / 2))]
+    #[case(10, 10, &"█".repeat(TREND_BAR_WIDTH))]
+    #[case(0, 0, "")]
+    fn scales_bars(#[case] count: usize, #[case] max: usize, #[case] expected: &str) {
+        assert_eq!(bar(count, max), expected);
+    }
+
+    #[test]
+    fn json_lists_raw_day_counts() {
+        let window = trend_window(noon(), Duration::from_secs(SECS_PER_DAY));
+        let stats = aggregate(vec![file("a.jsonl", None, noon())], None, Some(&window));
+
+        assert_eq!(
+            serde_json::to_value(stats_json(&stats)).unwrap(),
+            serde_json::json!({
+                "sessions": 1,
+                "unparseable": 0,
+                "branches": 0,
+                "trend": [
+                    { "day": "2026-10-13", "count": 0 },
+                    { "day": "2026-10-14", "count": 1 },
+                ],
+            })
+        );
+    }
+}
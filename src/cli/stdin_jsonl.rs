@@ -0,0 +1,162 @@
+//! Matching session metadata piped on stdin (`--stdin-jsonl`).
+//!
+//! When the sessions live on another machine, their first lines can be read there and piped
+//! in, so amg matches them without a local codexdir:
+//!
+//! ```sh
+//! ssh host 'head -qn1 ~/.codex/sessions/*/*/*/rollout-*.jsonl' \
+//!     | amg resume mybranch --stdin-jsonl --path-prefix host: --wrap 'ssh -t host'
+//! ```
+//!
+//! Each line is parsed like the first line of a session file and filtered by the same
+//! selection options, but no walk orders them: the first matching line wins, in input order
+//! (see [`scan::find_in_lines`]). The shell glob above lists the oldest day first, so pipe
+//! the lines newest first (`ls -r`, `tac`) when the newest session should win.
+//!
+//! Nothing local is granted or entered: `resume` builds a codex command without `--add-dir`
+//! grants that starts in the session's recorded cwd ([`codex_cmd::build_remote_codex_cmd`]),
+//! and either prints it (`--dry-run`) or runs it inline through `--wrap` (or the config's
+//! `wrap_command`), which is what reaches the other machine. `which` prints the session's
+//! cwd and id instead of a file.
+
+use super::args::{ResumeArgs, WhichArgs};
+use super::prelude::*;
+use super::{alias, codex_cmd, config, output, process, scan, usage, util};
+
+/// Handles `resume --stdin-jsonl`.
+///
+/// # Returns
+///
+/// Returns success after a dry run, or codex's exit code (through the wrapper) otherwise.
+///
+/// # Errors
+///
+/// Returns an error if neither `--dry-run` nor a wrapper is given, stdin cannot be read, no
+/// line matches, or the wrapped command cannot be run.
+pub(super) fn run_resume(args: &ResumeArgs) -> Result<ExitCode> {
+    let config = config::Config::load_global()?;
+    let wrapper = super::wrapper(args.codex.wrapper.wrap.as_deref(), None, &config)?;
+    require_launcher(args.execution.dry_run, &wrapper)?;
+    let branch = alias::resolve_branch(args.branch.as_deref().unwrap_or_default());
+    let session = find(&branch, args)?;
+    info!(
+        branch = session.branch_label(),
+        looked_up = session.other_branch(&branch),
+        id = %session.id,
+        cwd = %prefixed_cwd(args.path_prefix.as_deref(), &session.cwd),
+        line = %util::human_path(&session.source_jsonl),
+        "matched a piped session"
+    );
+    let cmd = codex_cmd::build_remote_codex_cmd(
+        &session,
+        config.model(),
+        config.network_access.unwrap_or(true),
+    )
+    .wrapped(&wrapper);
+
+    if args.execution.dry_run {
+        let command = if args.execution.pretty {
+            cmd.as_pretty_shell_string(util::terminal_width(), None)
+        } else {
+            cmd.as_shell_string()
+        };
+        if args.execution.copy {
+            super::copy_command(&command);
+        }
+        match &args.execution.output {
+            Some(path) => super::emit_command(&mut util::create_private_file(path)?, &command)
+                .with_context(|| format!("failed to write {}", util::human_path(path)))?,
+            None => super::emit_command(&mut io::stdout().lock(), &command)
+                .context("failed to write to stdout")?,
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.execution.copy {
+        super::copy_command(&cmd.as_shell_string());
+    }
+    let exit = process::run_on_terminal(Path::new("."), &cmd, args.execution.timeout)
+        .context("failed to run codex")?;
+    match exit {
+        process::Exit::Status(status) => Ok(process::exit_code(status)),
+        process::Exit::TimedOut => {
+            error!("codex was stopped after running longer than --timeout");
+            Ok(ExitCode::from(EXIT_TIMED_OUT))
+        }
+    }
+}
+
+/// Handles `which --stdin-jsonl`: prints `<cwd>\t<id>` for the first matching piped line,
+/// the cwd behind `--path-prefix`.
+///
+/// # Errors
+///
+/// Returns an error if stdin cannot be read, no line matches, or stdout cannot be written.
+pub(super) fn run_which(args: &WhichArgs) -> Result<ExitCode> {
+    let branch = alias::resolve_branch(&args.branch);
+    let options = super::scan_options(&args.selection)?;
+    let session = scan::find_in_lines(io::stdin().lock(), &branch, &options)?
+        .with_context(|| no_match_message(&branch))?;
+    output::line(format_args!(
+        "{}\t{}",
+        prefixed_cwd(args.path_prefix.as_deref(), &session.cwd),
+        session.id
+    ))?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Finds the first piped session for `branch` with the selection options of `args`.
+fn find(branch: &str, args: &ResumeArgs) -> Result<scan::Session> {
+    let options = super::scan_options(&args.selection)?;
+    let session = scan::find_in_lines(io::stdin().lock(), branch, &options)?;
+    usage::note_match(session.is_some());
+    session.with_context(|| no_match_message(branch))
+}
+
+fn no_match_message(branch: &str) -> String {
+    format!("no session line on stdin matches branch {branch}")
+}
+
+/// Refuses to launch codex locally: without `--dry-run`, a wrapper must carry the command to
+/// the machine the session lives on.
+fn require_launcher(dry_run: bool, wrapper: &[String]) -> Result<()> {
+    if !dry_run && wrapper.is_empty() {
+        bail!(
+            "--stdin-jsonl sessions live elsewhere; pass --wrap (such as 'ssh -t host') to \
+             resume one there, or --dry-run to print the command"
+        );
+    }
+    Ok(())
+}
+
+/// Renders `cwd` behind `--path-prefix` (such as `host:` for `host:/home/dev/app`).
+fn prefixed_cwd(prefix: Option<&str>, cwd: &Path) -> String {
+    format!("{}{}", prefix.unwrap_or_default(), cwd.display())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(false, &[], false)]
+    #[case(true, &[], true)]
+    #[case(false, &["ssh", "-t", "host"], true)]
+    fn launches_only_through_a_wrapper_or_dry_run(
+        #[case] dry_run: bool,
+        #[case] wrapper: &[&str],
+        #[case] allowed: bool,
+    ) {
+        let wrapper: Vec<String> = wrapper.iter().map(|word| (*word).to_owned()).collect();
+
+        assert_eq!(require_launcher(dry_run, &wrapper).is_ok(), allowed);
+    }
+
+    #[test]
+    fn prefixes_the_cwd() {
+        let cwd = Path::new("/home/dev/app");
+
+        assert_eq!(prefixed_cwd(Some("host:"), cwd), "host:/home/dev/app");
+        assert_eq!(prefixed_cwd(None, cwd), "/home/dev/app");
+    }
+}
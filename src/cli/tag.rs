@@ -0,0 +1,174 @@
+//! Session tags.
+//!
+//! This module implements the `tag` subcommand and the tag lookup behind `--tag`. Tags are
+//! free-form labels ("demo-prep", "bug-hunt") attached to session ids and stored in the amg
+//! state file; Codex's session files are never modified. Selecting by tag narrows the
+//! candidates to tagged session ids before the usual first-match ordering applies.
+
+use std::collections::BTreeSet;
+
+use super::args::TagCommand;
+use super::output;
+use super::prelude::*;
+use super::state::{self, State};
+
+/// Handles the `tag` subcommand.
+///
+/// # Errors
+///
+/// Returns an error if the tag or session id is invalid, or the state file cannot be read or
+/// written.
+pub(super) fn run_tag(command: TagCommand) -> Result<ExitCode> {
+    match command {
+        TagCommand::Add { session_id, tag } => {
+            state::update(|state| add_tag(state, &session_id, &tag))?;
+            info!(id = %session_id, tag = %tag, "tagged session");
+        }
+        TagCommand::List { session_id } => {
+            let state = State::load(&state::state_path()?)?;
+            for (id, tags) in &state.tags {
+                if session_id.as_ref().is_none_or(|wanted| wanted == id) {
+                    for tag in tags {
+                        output::line(format_args!("{id}\t{tag}"))?;
+                    }
+                }
+            }
+        }
+        TagCommand::Rm { session_id, tag } => {
+            if state::update(|state| Ok(remove_tag(state, &session_id, &tag)))? {
+                info!(id = %session_id, tag = %tag, "removed tag");
+            } else {
+                warn!("session {session_id:?} is not tagged {tag:?}; nothing to remove");
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Returns the ids of the sessions tagged `tag`.
+///
+/// # Errors
+///
+/// Returns an error if the tag name is invalid, the state file cannot be read, or no session
+/// carries the tag.
+pub(super) fn tagged_sessions(tag: &str) -> Result<BTreeSet<String>> {
+    validate_tag(tag)?;
+    let state = State::load(&state::state_path()?)?;
+    let ids = sessions_with(&state, tag);
+    if ids.is_empty() {
+        bail!("no session is tagged {tag:?} (see `amg tag list`)");
+    }
+    Ok(ids)
+}
+
+fn sessions_with(state: &State, tag: &str) -> BTreeSet<String> {
+    state
+        .tags
+        .iter()
+        .filter(|(_, tags)| tags.contains(tag))
+        .map(|(id, _)| id.clone())
+        .collect()
+}
+
+fn add_tag(state: &mut State, session_id: &str, tag: &str) -> Result<()> {
+    validate_tag(tag)?;
+    let session_id = session_id.trim();
+    if session_id.is_empty() {
+        bail!("session id must not be empty");
+    }
+    state
+        .tags
+        .entry(session_id.to_owned())
+        .or_default()
+        .insert(tag.to_owned());
+    Ok(())
+}
+
+/// Removes `tag` from a session, returning whether the session carried it.
+fn remove_tag(state: &mut State, session_id: &str, tag: &str) -> bool {
+    let Some(tags) = state.tags.get_mut(session_id) else {
+        return false;
+    };
+    let removed = tags.remove(tag);
+    if tags.is_empty() {
+        state.tags.remove(session_id);
+    }
+    removed
+}
+
+fn validate_tag(tag: &str) -> Result<()> {
+    if tag.is_empty() {
+        bail!("tag must not be empty");
+    }
+    if tag.chars().any(char::is_whitespace) {
+        bail!("tag must not contain whitespace: {tag:?}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn state_with(tags: &[(&str, &str)]) -> State {
+        let mut state = State::default();
+        for (id, tag) in tags {
+            add_tag(&mut state, id, tag).expect("tag should be valid");
+        }
+        state
+    }
+
+    #[test]
+    fn finds_sessions_by_tag() {
+        let state = state_with(&[("a", "demo-prep"), ("b", "bug-hunt"), ("c", "demo-prep")]);
+
+        assert_eq!(
+            sessions_with(&state, "demo-prep"),
+            BTreeSet::from(["a".to_owned(), "c".to_owned()])
+        );
+        assert!(sessions_with(&state, "other").is_empty());
+    }
+
+    #[test]
+    fn adding_a_tag_twice_keeps_one() {
+        let state = state_with(&[("a", "x"), ("a", "x"), ("a", "y")]);
+
+        assert_eq!(state.tags["a"], BTreeSet::from(["x".into(), "y".into()]));
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("two words")]
+    #[case("tab\tname")]
+    fn rejects_invalid_tags(#[case] tag: &str) {
+        let mut state = State::default();
+
+        assert!(add_tag(&mut state, "a", tag).is_err());
+    }
+
+    #[test]
+    fn rejects_empty_session_ids() {
+        let mut state = State::default();
+
+        assert!(add_tag(&mut state, " ", "x").is_err());
+    }
+
+    #[test]
+    fn removing_the_last_tag_forgets_the_session() {
+        let mut state = state_with(&[("a", "x")]);
+
+        assert!(remove_tag(&mut state, "a", "x"));
+        assert!(state.tags.is_empty());
+    }
+
+    #[rstest]
+    #[case("a", "missing")]
+    #[case("unknown-session", "x")]
+    fn removing_an_absent_tag_is_a_no_op(#[case] id: &str, #[case] tag: &str) {
+        let mut state = state_with(&[("a", "x")]);
+
+        assert!(!remove_tag(&mut state, id, tag));
+        assert_eq!(state.tags["a"], BTreeSet::from(["x".into()]));
+    }
+}
@@ -0,0 +1,102 @@
+//! Shared fixtures for unit tests across CLI modules.
+
+use rstest::fixture;
+
+use super::prelude::*;
+use super::scan;
+
+pub(crate) fn unique_suffix() -> String {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{}_{nanos}_{count}", std::process::id())
+}
+
+#[fixture]
+pub(crate) fn temp_dir() -> PathBuf {
+    let temp_dir = std::env::temp_dir().join(format!("amg_test_{}", unique_suffix()));
+    fs::create_dir_all(&temp_dir).expect("Failed to create temp dir");
+    temp_dir
+}
+
+/// A matched session for tests: session id `id`, the cwd `/w`, the file
+/// `/codex/rollout-<id>.jsonl`, and nothing else recorded or read.
+///
+/// Tests set other fields with struct update syntax (`Session { cwd, ..matched_session(ID) }`),
+/// so a new [`scan::Session`] field only needs a default here.
+pub(super) fn matched_session(id: &str) -> scan::Session {
+    scan::Session {
+        cwd: PathBuf::from("/w"),
+        id: id.to_owned(),
+        branch: None,
+        source_jsonl: PathBuf::from(format!("/codex/rollout-{id}.jsonl")),
+        commit_hash: None,
+        originator: None,
+        model: None,
+        size: None,
+        modified: None,
+        first_line: None,
+    }
+}
+
+/// A session file for tests: a `session_meta` first line recording a branch, a session id,
+/// and the cwd `/w` unless [`SessionFile::cwd`] sets another.
+pub(crate) struct SessionFile {
+    line: serde_json::Value,
+    modified: Option<SystemTime>,
+}
+
+impl SessionFile {
+    /// Starts a session file recording `branch` and session id `id`.
+    pub(crate) fn new(branch: &str, id: &str) -> Self {
+        Self {
+            line: serde_json::json!({
+                "type": "session_meta",
+                "payload": { "id": id, "cwd": "/w", "git": { "branch": branch } }
+            }),
+            modified: None,
+        }
+    }
+
+    /// Records `cwd` as the session's working directory.
+    #[cfg(feature = "cli")]
+    pub(crate) fn cwd(mut self, cwd: &Path) -> Self {
+        self.line["payload"]["cwd"] = cwd.to_string_lossy().as_ref().into();
+        self
+    }
+
+    /// Records `timestamp` on the `session_meta` line.
+    #[cfg(feature = "cli")]
+    pub(crate) fn timestamp(mut self, timestamp: &str) -> Self {
+        self.line["timestamp"] = timestamp.into();
+        self
+    }
+
+    /// Sets the file's modification time once written.
+    #[cfg(feature = "cli")]
+    pub(crate) fn modified(mut self, modified: SystemTime) -> Self {
+        self.modified = Some(modified);
+        self
+    }
+
+    /// Writes the file at `path`, creating its parent directories, and returns the path.
+    pub(crate) fn write(&self, path: impl AsRef<Path>) -> PathBuf {
+        let path = path.as_ref();
+        fs::create_dir_all(path.parent().unwrap()).expect("Failed to create session dir");
+        fs::write(path, format!("{}\n", self.line)).expect("Failed to write session file");
+        if let Some(modified) = self.modified {
+            fs::File::options()
+                .write(true)
+                .open(path)
+                .and_then(|file| file.set_modified(modified))
+                .expect("Failed to set the session file's mtime");
+        }
+        path.to_owned()
+    }
+}
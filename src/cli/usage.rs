@@ -0,0 +1,225 @@
+//! Usage records for observability (`--stats-file`).
+//!
+//! With `--stats-file PATH` (or `AMG_STATS_FILE`), every invocation appends one JSON line to
+//! `PATH` on its way out, so a team can aggregate how amg is used:
+//!
+//! ```json
+//! {"version":"0.1.0","command":"resume","started_at":"2025-10-01T12:00:00Z","duration_ms":42,"files_scanned":120,"matched":true,"exit_code":0}
+//! ```
+//!
+//! `files_scanned` counts the `.jsonl` files walked by the command's branch lookups and
+//! `matched` tells whether they found a session; both are absent for commands that look
+//! nothing up. `exit_code` is what amg exits with, including codex's own code after an inline
+//! run.
+//!
+//! The line is appended with a single write to a file opened with `O_APPEND`, so concurrent
+//! invocations do not interleave their records. A stats file that cannot be written only
+//! produces a warning; it never changes the command's outcome.
+
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use super::args::Commands;
+use super::datetime::UtcDateTime;
+use super::prelude::*;
+use super::scan::ScanStats;
+use super::util;
+
+/// What the command's session lookups found so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Observed {
+    /// `.jsonl` files walked by the lookups that report [`ScanStats`].
+    files_scanned: Option<usize>,
+    /// Whether any lookup found a session.
+    matched: Option<bool>,
+}
+
+impl Observed {
+    /// Nothing looked up yet.
+    const NONE: Self = Self {
+        files_scanned: None,
+        matched: None,
+    };
+
+    fn note_match(&mut self, matched: bool) {
+        self.matched = Some(self.matched == Some(true) || matched);
+    }
+}
+
+/// The lookups of this process, noted by [`note_scan`] and [`note_match`].
+static OBSERVED: Mutex<Observed> = Mutex::new(Observed::NONE);
+
+/// Notes a branch lookup that walked the codexdir and whether it found a session.
+pub(super) fn note_scan(stats: &ScanStats, matched: bool) {
+    observe(|observed| {
+        observed.files_scanned = Some(observed.files_scanned.unwrap_or(0) + stats.files_scanned);
+        observed.note_match(matched);
+    });
+}
+
+/// Notes a lookup without scan counters (such as `--file` or `--id`) and whether it found a
+/// session.
+pub(super) fn note_match(matched: bool) {
+    observe(|observed| observed.note_match(matched));
+}
+
+fn observe(update: impl FnOnce(&mut Observed)) {
+    let mut observed = OBSERVED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    update(&mut observed);
+}
+
+/// One line of the stats file.
+#[derive(Debug, Serialize)]
+struct UsageRecord<'a> {
+    /// The amg version.
+    version: &'a str,
+    /// The subcommand (`resume`, `which`, ...).
+    command: &'a str,
+    /// When the command started (RFC 3339, UTC).
+    started_at: String,
+    /// How long the command ran, in milliseconds.
+    duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    files_scanned: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matched: Option<bool>,
+    /// The process exit code.
+    exit_code: u8,
+}
+
+/// Times a command and appends its record to the stats file.
+#[derive(Debug)]
+pub(super) struct Recorder {
+    path: PathBuf,
+    command: &'static str,
+    started_at: SystemTime,
+    started: Instant,
+}
+
+impl Recorder {
+    /// Starts timing `command`, or returns `None` when no stats file is configured.
+    pub(super) fn start(path: Option<PathBuf>, command: &Commands) -> Option<Self> {
+        Some(Self {
+            path: path?,
+            command: command_name(command),
+            started_at: SystemTime::now(),
+            started: Instant::now(),
+        })
+    }
+
+    /// Appends the record for a command that exits with `code`, warning if it cannot.
+    pub(super) fn finish(self, code: ExitCode) {
+        let observed = *OBSERVED
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let record = UsageRecord {
+            version: env!("CARGO_PKG_VERSION"),
+            command: self.command,
+            started_at: UtcDateTime::from_system_time(self.started_at).rfc3339(),
+            duration_ms: u64::try_from(self.started.elapsed().as_millis()).unwrap_or(u64::MAX),
+            files_scanned: observed.files_scanned,
+            matched: observed.matched,
+            exit_code: exit_code_value(code),
+        };
+        if let Err(err) = append(&self.path, &record) {
+            warn!("could not append to the stats file: {err:#}");
+        }
+    }
+}
+
+/// Appends `record` to `path` as one line, with a single write.
+///
+/// # Errors
+///
+/// Returns an error if the record cannot be serialized or the file cannot be opened or
+/// written.
+fn append(path: &Path, record: &UsageRecord<'_>) -> Result<()> {
+    let mut line = serde_json::to_string(record).context("failed to serialize the record")?;
+    line.push('\n');
+    let mut file = fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .with_context(|| format!("failed to open {}", util::human_path(path)))?;
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("failed to write {}", util::human_path(path)))
+}
+
+/// Returns the numeric value of `code`, which [`ExitCode`] does not expose.
+fn exit_code_value(code: ExitCode) -> u8 {
+    (0..=u8::MAX)
+        .find(|&value| code == ExitCode::from(value))
+        .unwrap_or(1)
+}
+
+/// Returns the name `command` is invoked by.
+fn command_name(command: &Commands) -> &'static str {
+    match command {
+        Commands::ResumeBranch(_) => "resume",
+        Commands::ResumeAll(_) => "resume-all",
+        Commands::Watch(_) => "watch",
+        Commands::Kill(_) => "kill",
+        Commands::Remembered(_) => "remembered",
+        Commands::Which(_) => "which",
+        Commands::PromptHint(_) => "prompt-hint",
+        Commands::Show(_) => "show",
+        Commands::Branches(_) => "branches",
+        Commands::Find(_) => "find",
+        Commands::Dump(_) => "dump",
+        Commands::Stats(_) => "stats",
+        Commands::Import(_) => "import",
+        Commands::MigrateLayout(_) => "migrate-layout",
+        Commands::ExecPlan(_) => "exec-plan",
+        Commands::Alias { .. } => "alias",
+        Commands::Tag { .. } => "tag",
+        Commands::Config { .. } => "config",
+        Commands::Init(_) => "init",
+        Commands::Doctor(_) => "doctor",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args::Args;
+    use clap::{CommandFactory, Parser};
+    use rstest::rstest;
+
+    #[test]
+    fn lookups_add_up() {
+        let mut observed = Observed::NONE;
+
+        observed.note_match(false);
+        assert_eq!(observed.matched, Some(false));
+        observed.note_match(true);
+        observed.note_match(false);
+        assert_eq!(observed.matched, Some(true));
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(2)]
+    #[case(130)]
+    #[case(255)]
+    fn reads_back_exit_codes(#[case] value: u8) {
+        assert_eq!(exit_code_value(ExitCode::from(value)), value);
+    }
+
+    #[rstest]
+    #[case(&["amg", "resume", "main", "--repo", "/r"], "resume")]
+    #[case(&["amg", "rb", "main", "--repo", "/r"], "resume")]
+    #[case(&["amg", "migrate-layout"], "migrate-layout")]
+    #[case(&["amg", "alias", "list"], "alias")]
+    fn names_commands_as_invoked(#[case] argv: &[&str], #[case] expected: &str) {
+        let args = Args::try_parse_from(argv).unwrap();
+        let name = command_name(&args.command);
+
+        assert_eq!(name, expected);
+        assert!(Args::command().find_subcommand(name).is_some());
+    }
+}
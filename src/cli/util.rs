@@ -6,25 +6,11 @@
 //! * Tmux detection
 //! * Home directory resolution
 
-use super::prelude::*;
+use std::fmt;
 
-/// Gets the default Codex directory path.
-///
-/// Returns `$HOME/.codex` if `$HOME` is set and non-empty.
-///
-/// # Returns
-///
-/// Returns [`Result<PathBuf>`] containing the default codex directory path.
-///
-/// # Errors
-///
-/// Returns an error if `$HOME` is not set or empty.
-pub(super) fn default_codexdir() -> Result<PathBuf> {
-    match std::env::var_os(ENV_HOME) {
-        Some(home) if !home.is_empty() => Ok(PathBuf::from(home).join(DOT_CODEX_DIR)),
-        _ => bail!("CODEX_CODEXDIR is not set and $HOME is empty; please set CODEX_CODEXDIR"),
-    }
-}
+#[cfg(feature = "cli")]
+use super::args::TmuxMode;
+use super::prelude::*;
 
 /// Validates that a path exists and is a directory.
 ///
@@ -40,7 +26,9 @@ pub(super) fn default_codexdir() -> Result<PathBuf> {
 ///
 /// # Errors
 ///
-/// Returns an error if the path does not exist or is not a directory.
+/// Returns an error if the path does not exist or is not a directory. For a file, the error
+/// suggests its parent directory; for a missing path, it names the deepest ancestor that
+/// exists, to show where the path stops matching the filesystem.
 pub(super) fn require_dir(
     path: &Path,
     label: &'static str,
@@ -49,17 +37,41 @@ pub(super) fn require_dir(
     if path.is_dir() {
         Ok(())
     } else {
-        let msg = match env_var {
+        let mut msg = match env_var {
             Some(var) => format!(
                 "{label} (from {var}) is not a directory: {}",
-                path.display()
+                human_path(path)
             ),
-            None => format!("{label} is not a directory: {}", path.display()),
+            None => format!("{label} is not a directory: {}", human_path(path)),
         };
+        if path.exists() {
+            let parent = path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            msg.push_str(&format!(
+                "; it is a file, did you mean {}?",
+                human_path(parent)
+            ));
+        } else if let Some(ancestor) = deepest_existing_ancestor(path) {
+            msg.push_str(&format!(
+                "; it does not exist past {}",
+                human_path(ancestor)
+            ));
+        }
         bail!("{msg}");
     }
 }
 
+/// Returns the deepest proper ancestor of `path` that exists, or `None` if none does (as for
+/// a relative path whose first component is missing).
+pub(super) fn deepest_existing_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors()
+        .skip(1)
+        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+        .find(|ancestor| ancestor.exists())
+}
+
 /// Gets the user's home directory path.
 ///
 /// Returns `$HOME` if it's set and non-empty.
@@ -73,21 +85,553 @@ pub(super) fn home_dir() -> Option<PathBuf> {
         .map(PathBuf::from)
 }
 
+/// Reads an environment variable as a path, treating an empty value as unset.
+#[cfg(feature = "cli")]
+pub(super) fn non_empty_env(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Describes why a session cwd read from a session file is unsafe to use, if it is.
+///
+/// The cwd must be absolute and free of control characters: a corrupted file can decode to
+/// an embedded newline or escape sequence that later breaks tmux's `-c` argument.
+pub(super) fn cwd_problem(cwd: &Path) -> Option<String> {
+    control_char_problem("cwd", &cwd.to_string_lossy()).or_else(|| {
+        (!cwd.is_absolute()).then(|| format!("cwd {} is not an absolute path", human_path(cwd)))
+    })
+}
+
+/// Describes why a session id read from a session file is unsafe to use, if it is.
+pub(super) fn id_problem(id: &str) -> Option<String> {
+    control_char_problem("session id", id)
+}
+
+fn control_char_problem(field: &str, value: &str) -> Option<String> {
+    value
+        .chars()
+        .find(|c| c.is_control())
+        .map(|c| format!("{field} {value:?} contains control character {c:?}"))
+}
+
+/// A `--map-path OLD=NEW` translation of recorded cwd prefixes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathMapping {
+    /// The recorded prefix, with `\` separators turned into `/` and no trailing separator.
+    from: String,
+    /// The local directory replacing it.
+    to: PathBuf,
+}
+
+impl fmt::Display for PathMapping {
+    /// Formats the mapping as `OLD=NEW`, as `--map-path` takes it.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.from, self.to.display())
+    }
+}
+
+/// Parses a `--map-path` value, `OLD=NEW` (split at the first `=`).
+///
+/// `OLD` may use either platform's syntax (`C:\Users\dev`, `/mnt/share`); `NEW` must be an
+/// absolute local path.
+///
+/// # Errors
+///
+/// Returns an error if the `=` is missing, `OLD` is empty or only separators, or `NEW` is not
+/// absolute.
+#[cfg(feature = "cli")]
+pub(super) fn parse_path_mapping(value: &str) -> Result<PathMapping> {
+    let Some((from, to)) = value.split_once('=') else {
+        bail!("invalid path mapping {value:?}: expected OLD=NEW");
+    };
+    let from = from.replace('\\', "/").trim_end_matches('/').to_owned();
+    if from.is_empty() {
+        bail!("invalid path mapping {value:?}: OLD must name a directory below the root");
+    }
+    let to = PathBuf::from(to);
+    if !to.is_absolute() {
+        bail!(
+            "invalid path mapping {value:?}: NEW must be an absolute path, not {}",
+            human_path(&to)
+        );
+    }
+    Ok(PathMapping { from, to })
+}
+
+/// Translates `cwd` with the first mapping whose `OLD` prefix it starts with.
+///
+/// Prefixes match whole components, with `/` and `\` as separators alike; a Windows drive
+/// or UNC prefix matches case-insensitively, as Windows does. The rest of the path is joined
+/// onto `NEW` component by component.
+///
+/// # Returns
+///
+/// Returns the translated path, or `None` if no mapping applies.
+pub(super) fn map_path(cwd: &Path, mappings: &[PathMapping]) -> Option<PathBuf> {
+    let cwd = cwd.to_string_lossy().replace('\\', "/");
+    mappings.iter().find_map(|mapping| {
+        let head = cwd.get(..mapping.from.len())?;
+        // Normalized, a Windows prefix is the one not starting with a single `/`.
+        let windows = !mapping.from.starts_with('/') || mapping.from.starts_with("//");
+        let same = if windows {
+            head.eq_ignore_ascii_case(&mapping.from)
+        } else {
+            head == mapping.from
+        };
+        let rest = &cwd[mapping.from.len()..];
+        if !same || !(rest.is_empty() || rest.starts_with('/')) {
+            return None;
+        }
+        let mut mapped = mapping.to.clone();
+        mapped.extend(rest.split('/').filter(|part| !part.is_empty()));
+        Some(mapped)
+    })
+}
+
+/// Checks whether `path` is a Windows drive (`C:\`) or UNC (`\\server`) path recorded on
+/// another machine, so it cannot name a local directory. Always `false` on Windows, where a
+/// `/` path from Unix is already rejected as not absolute (see [`cwd_problem`]).
+pub(super) fn is_foreign_absolute(path: &Path) -> bool {
+    cfg!(not(windows)) && is_windows_absolute(&path.to_string_lossy())
+}
+
+/// Checks whether `path` is a Windows drive path (`C:\`, `C:/`) or UNC path (`\\server`),
+/// whatever the local platform.
+fn is_windows_absolute(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    let drive = bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && matches!(bytes[2], b'\\' | b'/');
+    drive || path.starts_with("\\\\")
+}
+
+/// Checks whether `path` is `prefix` or lies beneath it.
+///
+/// `prefix` should already be canonical. The comparison is per path component (so trailing
+/// slashes don't matter and `/work/acme2` is not under `/work/acme`), and `path` is also
+/// compared after canonicalization so sessions recorded through a symlink still match.
+pub(super) fn is_under(path: &Path, prefix: &Path) -> bool {
+    path.starts_with(prefix) || fs::canonicalize(path).is_ok_and(|p| p.starts_with(prefix))
+}
+
+/// How a repo and a codexdir overlap on disk.
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Overlap {
+    /// They are the same directory.
+    Same,
+    /// The codexdir lies inside the repo.
+    CodexdirInRepo,
+    /// The repo lies inside the codexdir, so scanning the codexdir walks the whole repo.
+    RepoInCodexdir,
+}
+
+/// Checks whether `repo` and `codexdir` are the same directory or one contains the other.
+///
+/// Both are compared canonicalized (as given when they cannot be resolved), using
+/// [`is_under`].
+#[cfg(feature = "cli")]
+pub(super) fn overlap(repo: &Path, codexdir: &Path) -> Option<Overlap> {
+    let repo = fs::canonicalize(repo).unwrap_or_else(|_| repo.to_owned());
+    let codexdir = fs::canonicalize(codexdir).unwrap_or_else(|_| codexdir.to_owned());
+    match (is_under(&repo, &codexdir), is_under(&codexdir, &repo)) {
+        (true, true) => Some(Overlap::Same),
+        (true, false) => Some(Overlap::RepoInCodexdir),
+        (false, true) => Some(Overlap::CodexdirInRepo),
+        (false, false) => None,
+    }
+}
+
+/// Formats a path for display, replacing a leading home directory with `~`.
+///
+/// The prefix only matches at path-component boundaries, so `/home/matheusx` is left
+/// untouched when `home` is `/home/matheus`.
+///
+/// # Arguments
+///
+/// * `path` - The path to display
+/// * `home` - The home directory to collapse, or `None` to display the path unchanged
+pub(super) fn display_path(path: &Path, home: Option<&Path>) -> String {
+    match home.and_then(|home| path.strip_prefix(home).ok()) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_owned(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+/// Formats `path` for people to read (logs, listings, `show`, error messages), collapsing
+/// `$HOME` to `~` with [`display_path`].
+///
+/// Paths that end up in a command (`--add-dir`, `--cd`, the tmux `-c`) or in output meant for
+/// scripts stay absolute and never go through this.
+pub(super) fn human_path(path: &Path) -> String {
+    display_path(path, home_dir().as_deref())
+}
+
+/// Parses a human-friendly duration such as `90s`, `15m`, `2h`, `7d`, or `2w`.
+///
+/// A bare number is interpreted as seconds.
+///
+/// # Errors
+///
+/// Returns an error if the value is empty, not a whole number, overflows, or has an
+/// unknown unit.
+#[cfg(feature = "cli")]
+pub(super) fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    if number.is_empty() {
+        bail!("invalid duration {value:?}: expected a number followed by s, m, h, d, or w");
+    }
+    let number: u64 = number
+        .parse()
+        .with_context(|| format!("invalid duration {value:?}"))?;
+    let unit_secs = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => bail!("invalid duration unit {unit:?} in {value:?}: expected s, m, h, d, or w"),
+    };
+    number
+        .checked_mul(unit_secs)
+        .map(Duration::from_secs)
+        .with_context(|| format!("duration {value:?} is too large"))
+}
+
+/// Splits a command line into words like a POSIX shell, without expanding anything.
+///
+/// Words are separated by unquoted whitespace. Single quotes preserve everything literally;
+/// inside double quotes a backslash only escapes `\`, `"`, `$`, and `` ` ``; elsewhere a
+/// backslash escapes the next character.
+///
+/// # Errors
+///
+/// Returns an error if a quote is left open or the line ends with a lone backslash.
+#[cfg(feature = "cli")]
+pub(super) fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\\' => {
+                let escaped = chars
+                    .next()
+                    .with_context(|| format!("trailing backslash in {line:?}"))?;
+                word.get_or_insert_default().push(escaped);
+            }
+            '\'' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => bail!("unterminated single quote in {line:?}"),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('\\' | '"' | '$' | '`')) => word.push(c),
+                            Some(c) => word.extend(['\\', c]),
+                            None => bail!("unterminated double quote in {line:?}"),
+                        },
+                        Some(c) => word.push(c),
+                        None => bail!("unterminated double quote in {line:?}"),
+                    }
+                }
+            }
+            c => word.get_or_insert_default().push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Writes `content` to `path` atomically, creating parent directories as needed.
+///
+/// The content is written to a temporary sibling and renamed into place, so readers never
+/// observe a partially written file. The sibling's name is unique to the call, so concurrent
+/// writers never share one, and a file being replaced keeps its permissions. The sibling is
+/// removed again if the write fails.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be created or the file cannot be written.
+#[cfg(feature = "cli")]
+pub(super) fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create directory {}", human_path(parent)))?;
+    }
+    let name = path
+        .file_name()
+        .with_context(|| format!("cannot write {}: it has no file name", human_path(path)))?;
+    let permissions = fs::metadata(path)
+        .ok()
+        .map(|metadata| metadata.permissions());
+    loop {
+        let mut tmp_name = name.to_owned();
+        tmp_name.push(format!(
+            ".tmp.{}.{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let tmp = path.with_file_name(tmp_name);
+        let file = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp)
+        {
+            Ok(file) => file,
+            // Left behind by a crashed process that had the same pid.
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to create {}", human_path(&tmp)));
+            }
+        };
+        let replaced = replace_with(file, &tmp, path, content, permissions);
+        if replaced.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        return replaced;
+    }
+}
+
+/// Fills the freshly created `tmp` file and renames it over `path`, for [`write_atomic`].
+#[cfg(feature = "cli")]
+fn replace_with(
+    mut file: fs::File,
+    tmp: &Path,
+    path: &Path,
+    content: &str,
+    permissions: Option<fs::Permissions>,
+) -> Result<()> {
+    use std::io::Write;
+
+    if let Some(permissions) = permissions {
+        file.set_permissions(permissions)
+            .with_context(|| format!("failed to set permissions of {}", human_path(tmp)))?;
+    }
+    file.write_all(content.as_bytes())
+        .with_context(|| format!("failed to write {}", human_path(tmp)))?;
+    drop(file);
+    fs::rename(tmp, path).with_context(|| format!("failed to replace {}", human_path(path)))
+}
+
+/// Creates (or truncates) `path` for writing, readable and writable only by the owner.
+///
+/// On Unix the file has mode 0600, including when it already existed with a looser mode.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be created or its permissions cannot be set.
+#[cfg(feature = "cli")]
+pub(super) fn create_private_file(path: &Path) -> Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        let file = options
+            .open(path)
+            .with_context(|| format!("failed to create {}", human_path(path)))?;
+        file.set_permissions(fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("failed to restrict permissions of {}", human_path(path)))?;
+        Ok(file)
+    }
+    #[cfg(not(unix))]
+    options
+        .open(path)
+        .with_context(|| format!("failed to create {}", human_path(&path)))
+}
+
+/// Reads the last `lines` lines of `path`, looking at no more than its last `max_bytes` bytes.
+///
+/// When the file is longer than `max_bytes`, the first (cut) line of the window is dropped.
+/// Invalid UTF-8 is replaced rather than rejected, since this is meant for log files.
+///
+/// # Returns
+///
+/// Returns the lines oldest first, without their line endings.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened or read.
+#[cfg(feature = "cli")]
+pub(super) fn tail_lines(path: &Path, max_bytes: u64, lines: usize) -> io::Result<Vec<String>> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    // Read one byte before the window so a line starting exactly at its edge is kept.
+    let start = len.saturating_sub(max_bytes.saturating_add(1));
+    file.seek(SeekFrom::Start(start))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let mut window = bytes.as_slice();
+    if len > max_bytes {
+        let cut = window
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(window.len(), |i| i + 1);
+        window = &window[cut..];
+    }
+    let text = String::from_utf8_lossy(window);
+    let all: Vec<&str> = text.lines().collect();
+    let skip = all.len().saturating_sub(lines);
+    Ok(all[skip..].iter().map(|line| (*line).to_owned()).collect())
+}
+
+/// An iterator yielding at most `max` items of another (`--max-matches`).
+///
+/// The cap is applied lazily: once `max` items were yielded, the inner iterator is advanced
+/// exactly once more, only to tell whether anything was cut off (see [`Capped::truncated`]).
+#[cfg(feature = "cli")]
+#[derive(Debug)]
+pub(super) struct Capped<I> {
+    inner: I,
+    /// How many more items may be yielded, or `None` without a cap.
+    left: Option<usize>,
+    truncated: bool,
+}
+
+#[cfg(feature = "cli")]
+impl<I: Iterator> Capped<I> {
+    /// Caps `inner` at `max` items; `None` leaves it uncapped.
+    pub(super) fn new(inner: I, max: Option<usize>) -> Self {
+        Self {
+            inner,
+            left: max,
+            truncated: false,
+        }
+    }
+
+    /// Returns whether the cap was reached with items left over.
+    pub(super) fn truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+#[cfg(feature = "cli")]
+impl<I: Iterator> Iterator for Capped<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        match &mut self.left {
+            None => self.inner.next(),
+            Some(0) => {
+                if !self.truncated && self.inner.next().is_some() {
+                    self.truncated = true;
+                }
+                None
+            }
+            Some(left) => {
+                let item = self.inner.next()?;
+                *left -= 1;
+                Some(item)
+            }
+        }
+    }
+}
+
+/// Returns the real user id of the running process, or `None` on platforms without uids.
+#[cfg(feature = "cli")]
+pub(super) fn current_uid() -> Option<u32> {
+    #[cfg(unix)]
+    {
+        // SAFETY: getuid has no preconditions and cannot fail.
+        Some(unsafe { libc::getuid() })
+    }
+    #[cfg(not(unix))]
+    None
+}
+
+/// Returns the terminal width in columns from `$COLUMNS`, or 80 if unset or invalid.
+#[cfg(feature = "cli")]
+pub(super) fn terminal_width() -> usize {
+    const DEFAULT_WIDTH: usize = 80;
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.trim().parse().ok())
+        .filter(|&columns| columns > 0)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// Where codex runs, as decided by [`should_use_tmux`].
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum TmuxTarget {
+    /// Inline, in amg's own terminal.
+    Inline,
+    /// In a new window of the tmux session amg runs in.
+    CurrentSession,
+    /// In a background window opened from outside tmux (`--tmux always` without `$TMUX`).
+    Detached,
+}
+
 /// Determines whether to use tmux for command execution.
 ///
-/// Returns `true` if tmux should be used, which is when:
-/// * `no_tmux` is `false` (tmux is not explicitly disabled)
-/// * `$TMUX` environment variable is set and non-empty
+/// `auto` opens a window only inside tmux (`$TMUX` is set and non-empty) and when amg is
+/// [interactive](is_interactive), so a git hook or cron job that inherited `$TMUX` does not
+/// pop up a window; `always` opens one either way, and `never` runs inline.
 ///
 /// # Arguments
 ///
-/// * `no_tmux` - If `true`, tmux will not be used regardless of environment
+/// * `mode` - The `--tmux` mode
 ///
 /// # Returns
 ///
-/// Returns `true` if tmux should be used, `false` otherwise.
-pub(super) fn should_use_tmux(no_tmux: bool) -> bool {
-    !no_tmux && env_present(ENV_TMUX)
+/// Returns where codex should run.
+#[cfg(feature = "cli")]
+pub(super) fn should_use_tmux(mode: TmuxMode) -> TmuxTarget {
+    tmux_target(mode, inside_tmux(), is_interactive())
+}
+
+#[cfg(feature = "cli")]
+fn tmux_target(mode: TmuxMode, inside_tmux: bool, interactive: bool) -> TmuxTarget {
+    match (mode, inside_tmux) {
+        (TmuxMode::Never, _) | (TmuxMode::Auto, false) => TmuxTarget::Inline,
+        (TmuxMode::Auto, true) if !interactive => {
+            info!(
+                "neither stdin nor stderr is a terminal; running inline instead of in a tmux \
+                 window (pass --tmux always to open one)"
+            );
+            TmuxTarget::Inline
+        }
+        (TmuxMode::Auto | TmuxMode::Always, true) => TmuxTarget::CurrentSession,
+        (TmuxMode::Always, false) => TmuxTarget::Detached,
+    }
+}
+
+/// Returns whether amg runs inside tmux (`$TMUX` is set and non-empty).
+#[cfg(feature = "cli")]
+pub(super) fn inside_tmux() -> bool {
+    env_present(ENV_TMUX)
+}
+
+/// Returns whether amg has a user at a terminal: stdin or stderr is a terminal.
+///
+/// stdout is not considered, since it is often piped on purpose (`amg which ... | xargs`).
+#[cfg(feature = "cli")]
+pub(super) fn is_interactive() -> bool {
+    io::stdin().is_terminal() || io::stderr().is_terminal()
 }
 
 /// Checks if an environment variable is present and non-empty.
@@ -99,6 +643,7 @@ pub(super) fn should_use_tmux(no_tmux: bool) -> bool {
 /// # Returns
 ///
 /// Returns `true` if the environment variable is set and non-empty, `false` otherwise.
+#[cfg(feature = "cli")]
 fn env_present(name: &str) -> bool {
     std::env::var_os(name).is_some_and(|v| !v.is_empty())
 }
@@ -113,22 +658,9 @@ mod tests {
 
     mod fixtures {
         use super::*;
-        use std::time::{SystemTime, UNIX_EPOCH};
+        use crate::cli::test_support::unique_suffix;
 
-        fn unique_suffix() -> String {
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_nanos()
-                .to_string()
-        }
-
-        #[fixture]
-        pub fn temp_dir() -> PathBuf {
-            let temp_dir = std::env::temp_dir().join(format!("amg_test_{}", unique_suffix()));
-            fs::create_dir_all(&temp_dir).expect("Failed to create temp dir");
-            temp_dir
-        }
+        pub(crate) use crate::cli::test_support::temp_dir;
 
         #[fixture]
         pub fn temp_file() -> PathBuf {
@@ -160,6 +692,465 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "cli")]
+    mod tail_lines {
+        use super::*;
+
+        #[rstest]
+        #[case(1024, 2, &["three", "four"])]
+        #[case(1024, 10, &["one", "two", "three", "four"])]
+        #[case(12, 10, &["three", "four"])]
+        #[case(5, 10, &["four"])]
+        #[case(4, 10, &[])]
+        fn keeps_the_last_complete_lines(
+            #[from(fixtures::temp_dir)] dir: PathBuf,
+            #[case] max_bytes: u64,
+            #[case] lines: usize,
+            #[case] expected: &[&str],
+        ) {
+            let path = dir.join("codex-tui.log");
+            fs::write(&path, "one\ntwo\nthree\nfour\n").unwrap();
+
+            assert_eq!(
+                super::tail_lines(&path, max_bytes, lines).unwrap(),
+                expected
+            );
+        }
+
+        #[rstest]
+        fn replaces_invalid_utf8(#[from(fixtures::temp_dir)] dir: PathBuf) {
+            let path = dir.join("codex-tui.log");
+            fs::write(&path, b"ok\nbad \xff byte\r\n").unwrap();
+
+            assert_eq!(
+                super::tail_lines(&path, 1024, 1).unwrap(),
+                ["bad \u{fffd} byte"]
+            );
+        }
+
+        #[rstest]
+        fn fails_for_a_missing_file(#[from(fixtures::temp_dir)] dir: PathBuf) {
+            assert!(super::tail_lines(&dir.join("missing.log"), 1024, 5).is_err());
+        }
+    }
+
+    #[cfg(feature = "cli")]
+    mod capped {
+        use super::*;
+        use std::cell::Cell;
+
+        #[rstest]
+        #[case(None, 5, false)]
+        #[case(Some(3), 3, true)]
+        #[case(Some(5), 5, false)]
+        #[case(Some(9), 5, false)]
+        #[case(Some(0), 0, true)]
+        fn yields_at_most_max_items(
+            #[case] max: Option<usize>,
+            #[case] yielded: usize,
+            #[case] truncated: bool,
+        ) {
+            let mut capped = Capped::new(1..=5, max);
+
+            assert_eq!(capped.by_ref().count(), yielded);
+            assert_eq!(capped.truncated(), truncated);
+            assert_eq!(capped.next(), None);
+        }
+
+        #[test]
+        fn pulls_only_one_item_past_the_cap() {
+            let pulled = Cell::new(0);
+            let endless = std::iter::repeat(()).inspect(|()| pulled.set(pulled.get() + 1));
+            let mut capped = Capped::new(endless, Some(3));
+
+            assert_eq!(capped.by_ref().count(), 3);
+            assert!(capped.truncated());
+            assert_eq!(pulled.get(), 4);
+        }
+    }
+
+    #[cfg(feature = "cli")]
+    mod parse_duration {
+        use super::*;
+
+        #[rstest]
+        #[case("90s", 90)]
+        #[case("45", 45)]
+        #[case("15m", 900)]
+        #[case("2h", 7_200)]
+        #[case("7d", 604_800)]
+        #[case("2w", 1_209_600)]
+        #[case(" 0s ", 0)]
+        fn parses_supported_units(#[case] value: &str, #[case] secs: u64) {
+            assert_eq!(
+                super::parse_duration(value).unwrap(),
+                Duration::from_secs(secs)
+            );
+        }
+
+        #[rstest]
+        #[case("")]
+        #[case("s")]
+        #[case("1.5h")]
+        #[case("10y")]
+        #[case("-5m")]
+        #[case("99999999999999999999w")]
+        fn rejects_invalid_durations(#[case] value: &str) {
+            assert!(super::parse_duration(value).is_err());
+        }
+    }
+
+    #[cfg(feature = "cli")]
+    mod split_words {
+        use super::*;
+
+        #[rstest]
+        #[case("docker exec -it devbox", &["docker", "exec", "-it", "devbox"])]
+        #[case("  a   b\t", &["a", "b"])]
+        #[case("ssh 'my host'", &["ssh", "my host"])]
+        #[case("env \"A=1 2\" b", &["env", "A=1 2", "b"])]
+        #[case("a\\ b", &["a b"])]
+        #[case("'it'\\''s'", &["it's"])]
+        #[case("\"a\\\"b\\$c\\d\"", &["a\"b$c\\d"])]
+        #[case("x '' y", &["x", "", "y"])]
+        #[case("", &[])]
+        fn splits_like_a_shell(#[case] line: &str, #[case] expected: &[&str]) {
+            assert_eq!(super::split_words(line).unwrap(), expected);
+        }
+
+        #[rstest]
+        #[case("docker 'exec")]
+        #[case("docker \"exec")]
+        #[case("docker exec\\")]
+        fn rejects_unbalanced_input(#[case] line: &str) {
+            assert!(super::split_words(line).is_err());
+        }
+    }
+
+    mod session_fields {
+        use super::*;
+
+        #[rstest]
+        #[case::absolute("/work/acme", None)]
+        #[case::spaces_and_unicode("/work/my repo/ünï", None)]
+        #[case::relative("work/acme", Some("not an absolute path"))]
+        #[case::empty("", Some("not an absolute path"))]
+        #[case::newline("/work/a\nb", Some("control character '\\n'"))]
+        #[case::nul("/work/\u{0}x", Some("control character '\\0'"))]
+        #[case::escape_sequence("/work/\u{1b}[31mred", Some("control character '\\u{1b}'"))]
+        #[case::delete("/work/\u{7f}", Some("control character '\\u{7f}'"))]
+        #[case::relative_with_tab("a\tb", Some("control character '\\t'"))]
+        fn flags_unusable_cwds(#[case] cwd: &str, #[case] expected: Option<&str>) {
+            let problem = super::cwd_problem(Path::new(cwd));
+
+            match expected {
+                None => assert_eq!(problem, None),
+                Some(expected) => {
+                    let problem = problem.expect("cwd should be rejected");
+                    assert!(problem.contains(expected), "{problem}");
+                }
+            }
+        }
+
+        #[rstest]
+        #[case::uuid("0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b", false)]
+        #[case::carriage_return("abc\r", true)]
+        #[case::nul("a\u{0}b", true)]
+        fn flags_control_characters_in_ids(#[case] id: &str, #[case] rejected: bool) {
+            assert_eq!(super::id_problem(id).is_some(), rejected);
+        }
+    }
+
+    mod foreign_paths {
+        use super::*;
+
+        #[rstest]
+        #[case::drive_backslash(r"C:\Users\dev\proj", true)]
+        #[case::drive_slash("d:/work", true)]
+        #[case::unc(r"\\server\share\proj", true)]
+        #[case::posix("/home/dev/proj", false)]
+        #[case::drive_relative("C:proj", false)]
+        #[case::relative(r"Users\dev", false)]
+        #[case::colon_in_name("C:", false)]
+        fn detects_windows_absolute_paths(#[case] path: &str, #[case] expected: bool) {
+            assert_eq!(super::is_windows_absolute(path), expected);
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn only_windows_paths_are_foreign_here() {
+            assert!(super::is_foreign_absolute(Path::new(r"C:\Users\dev")));
+            assert!(!super::is_foreign_absolute(Path::new("/home/dev")));
+        }
+
+        #[cfg(feature = "cli")]
+        #[rstest]
+        #[case(r"C:\Users\dev=/home/me", "C:/Users/dev", "/home/me")]
+        #[case(r"C:\Users\dev\=/home/me", "C:/Users/dev", "/home/me")]
+        #[case("/mnt/share=/srv/share", "/mnt/share", "/srv/share")]
+        #[case("/a=/b=c", "/a", "/b=c")]
+        fn parses_mappings(#[case] value: &str, #[case] from: &str, #[case] to: &str) {
+            let mapping = super::parse_path_mapping(value).unwrap();
+            assert_eq!(
+                (mapping.from.as_str(), mapping.to.as_path()),
+                (from, Path::new(to))
+            );
+        }
+
+        #[cfg(feature = "cli")]
+        #[rstest]
+        #[case::no_separator("/home/me")]
+        #[case::empty_old("=/home/me")]
+        #[case::root_only(r"\=/home/me")]
+        #[case::relative_new(r"C:\Users\dev=home/me")]
+        fn rejects_invalid_mappings(#[case] value: &str) {
+            assert!(super::parse_path_mapping(value).is_err());
+        }
+
+        #[cfg(feature = "cli")]
+        #[rstest]
+        #[case::nested(r"C:\Users\dev\proj\api", Some("/home/me/proj/api"))]
+        #[case::exact(r"C:\Users\dev", Some("/home/me"))]
+        #[case::drive_case(r"c:\users\DEV\proj", Some("/home/me/proj"))]
+        #[case::mixed_separators("C:/Users/dev/proj", Some("/home/me/proj"))]
+        #[case::partial_component(r"C:\Users\developer", None)]
+        #[case::posix_case_matters("/MNT/share/x", None)]
+        #[case::posix("/mnt/share/x", Some("/srv/share/x"))]
+        #[case::unmapped(r"D:\work", None)]
+        fn maps_recorded_prefixes(#[case] cwd: &str, #[case] expected: Option<&str>) {
+            let mappings = [
+                super::parse_path_mapping(r"C:\Users\dev=/home/me").unwrap(),
+                super::parse_path_mapping("/mnt/share=/srv/share").unwrap(),
+            ];
+
+            assert_eq!(
+                super::map_path(Path::new(cwd), &mappings),
+                expected.map(PathBuf::from)
+            );
+        }
+
+        #[cfg(feature = "cli")]
+        #[test]
+        fn the_first_matching_mapping_wins() {
+            let mappings = [
+                super::parse_path_mapping("/mnt=/a").unwrap(),
+                super::parse_path_mapping("/mnt/share=/b").unwrap(),
+            ];
+
+            assert_eq!(
+                super::map_path(Path::new("/mnt/share/x"), &mappings),
+                Some(PathBuf::from("/a/share/x"))
+            );
+        }
+    }
+
+    mod is_under {
+        use super::*;
+
+        #[rstest]
+        #[case("/work/acme", "/work/acme", true)]
+        #[case("/work/acme/api", "/work/acme", true)]
+        #[case("/work/acme/api/", "/work/acme/", true)]
+        #[case("/work/acme2", "/work/acme", false)]
+        #[case("/work", "/work/acme", false)]
+        fn compares_whole_components(
+            #[case] path: &str,
+            #[case] prefix: &str,
+            #[case] expected: bool,
+        ) {
+            assert_eq!(
+                super::is_under(Path::new(path), Path::new(prefix)),
+                expected
+            );
+        }
+
+        #[cfg(unix)]
+        #[rstest]
+        fn follows_symlinked_paths(#[from(fixtures::temp_dir)] dir: PathBuf) {
+            let real = dir.join("real");
+            fs::create_dir_all(real.join("api")).unwrap();
+            std::os::unix::fs::symlink(&real, dir.join("link")).unwrap();
+
+            let prefix = fs::canonicalize(dir.join("link")).unwrap();
+
+            assert!(super::is_under(&dir.join("link/api"), &prefix));
+            assert!(super::is_under(&real.join("api"), &prefix));
+        }
+    }
+
+    #[cfg(feature = "cli")]
+    #[rstest]
+    #[case::auto_outside(TmuxMode::Auto, false, true, TmuxTarget::Inline)]
+    #[case::auto_inside(TmuxMode::Auto, true, true, TmuxTarget::CurrentSession)]
+    #[case::auto_inside_without_terminal(TmuxMode::Auto, true, false, TmuxTarget::Inline)]
+    #[case::always_outside(TmuxMode::Always, false, true, TmuxTarget::Detached)]
+    #[case::always_inside(TmuxMode::Always, true, true, TmuxTarget::CurrentSession)]
+    #[case::always_without_terminal(TmuxMode::Always, true, false, TmuxTarget::CurrentSession)]
+    #[case::always_outside_without_terminal(TmuxMode::Always, false, false, TmuxTarget::Detached)]
+    #[case::never_outside(TmuxMode::Never, false, true, TmuxTarget::Inline)]
+    #[case::never_inside(TmuxMode::Never, true, true, TmuxTarget::Inline)]
+    fn picks_the_tmux_target(
+        #[case] mode: TmuxMode,
+        #[case] inside_tmux: bool,
+        #[case] interactive: bool,
+        #[case] expected: TmuxTarget,
+    ) {
+        assert_eq!(tmux_target(mode, inside_tmux, interactive), expected);
+    }
+
+    #[cfg(feature = "cli")]
+    mod overlap {
+        use super::*;
+
+        #[rstest]
+        #[case::equal("/work/all", "/work/all", Some(Overlap::Same))]
+        #[case::trailing_slash("/work/all/", "/work/all", Some(Overlap::Same))]
+        #[case::repo_inside("/work/all/api", "/work/all", Some(Overlap::RepoInCodexdir))]
+        #[case::codexdir_inside("/work/api", "/work/api/.codex", Some(Overlap::CodexdirInRepo))]
+        #[case::disjoint("/work/api", "/home/me/.codex", None)]
+        #[case::sibling_prefix("/work/api2", "/work/api", None)]
+        fn classifies_paths(
+            #[case] repo: &str,
+            #[case] codexdir: &str,
+            #[case] expected: Option<Overlap>,
+        ) {
+            assert_eq!(
+                super::overlap(Path::new(repo), Path::new(codexdir)),
+                expected
+            );
+        }
+
+        #[cfg(unix)]
+        #[rstest]
+        fn resolves_symlinks(#[from(fixtures::temp_dir)] dir: PathBuf) {
+            fs::create_dir_all(dir.join("real/api")).unwrap();
+            std::os::unix::fs::symlink(dir.join("real"), dir.join("link")).unwrap();
+
+            assert_eq!(
+                super::overlap(&dir.join("real/api"), &dir.join("link")),
+                Some(Overlap::RepoInCodexdir)
+            );
+        }
+    }
+
+    #[cfg(feature = "cli")]
+    mod write_atomic {
+        use super::*;
+
+        #[rstest]
+        fn concurrent_writers_each_land_a_whole_file(#[from(fixtures::temp_dir)] dir: PathBuf) {
+            let path = dir.join("state.json");
+            let contents: Vec<String> = (0..8).map(|n| format!("{n}").repeat(4096)).collect();
+            std::thread::scope(|scope| {
+                for content in &contents {
+                    let path = &path;
+                    scope.spawn(move || super::write_atomic(path, content).unwrap());
+                }
+            });
+
+            assert!(contents.contains(&fs::read_to_string(&path).unwrap()));
+            let names: Vec<_> = fs::read_dir(&dir)
+                .unwrap()
+                .map(|e| e.unwrap().file_name())
+                .collect();
+            assert_eq!(names, ["state.json"]);
+        }
+
+        #[rstest]
+        fn keeps_siblings_with_the_same_stem_apart(#[from(fixtures::temp_dir)] dir: PathBuf) {
+            super::write_atomic(&dir.join("a.json"), "json").unwrap();
+            super::write_atomic(&dir.join("a.toml"), "toml").unwrap();
+
+            assert_eq!(fs::read_to_string(dir.join("a.json")).unwrap(), "json");
+            assert_eq!(fs::read_to_string(dir.join("a.toml")).unwrap(), "toml");
+        }
+
+        #[cfg(unix)]
+        #[rstest]
+        fn keeps_the_permissions_of_the_replaced_file(#[from(fixtures::temp_dir)] dir: PathBuf) {
+            use std::os::unix::fs::PermissionsExt;
+
+            let path = dir.join("config.toml");
+            fs::write(&path, "old").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+            super::write_atomic(&path, "new").unwrap();
+
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+            assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        }
+
+        #[rstest]
+        fn removes_the_temporary_file_on_failure(#[from(fixtures::temp_dir)] dir: PathBuf) {
+            let path = dir.join("taken");
+            fs::create_dir(&path).unwrap();
+            fs::write(path.join("inside"), "").unwrap();
+
+            assert!(super::write_atomic(&path, "content").is_err());
+            let mut names: Vec<_> = fs::read_dir(&dir)
+                .unwrap()
+                .map(|e| e.unwrap().file_name())
+                .collect();
+            names.sort();
+            assert_eq!(names, ["taken"]);
+        }
+    }
+
+    #[cfg(feature = "cli")]
+    #[cfg(unix)]
+    mod create_private_file {
+        use super::*;
+        use std::os::unix::fs::PermissionsExt;
+
+        #[rstest]
+        fn creates_and_tightens_owner_only_files(#[from(fixtures::temp_dir)] dir: PathBuf) {
+            let path = dir.join("cmd.sh");
+            fs::write(&path, "stale content that is longer").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+            let mut file = super::create_private_file(&path).unwrap();
+            file.write_all(b"new").unwrap();
+            drop(file);
+
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+            assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        }
+    }
+
+    mod display_path {
+        use super::*;
+
+        #[rstest]
+        #[case("/home/matheus", "~")]
+        #[case("/home/matheus/", "~")]
+        #[case("/home/matheus/project", "~/project")]
+        #[case("/home/matheus/a b/c", "~/a b/c")]
+        #[case("/home/matheusx/project", "/home/matheusx/project")]
+        #[case("/srv/home/matheus", "/srv/home/matheus")]
+        #[case("/tmp", "/tmp")]
+        fn collapses_home_at_component_boundaries(#[case] path: &str, #[case] expected: &str) {
+            let home = Path::new("/home/matheus");
+            assert_eq!(super::display_path(Path::new(path), Some(home)), expected);
+        }
+
+        #[test]
+        fn leaves_path_unchanged_without_home() {
+            let path = Path::new("/home/matheus/project");
+            assert_eq!(super::display_path(path, None), "/home/matheus/project");
+        }
+
+        #[test]
+        fn human_paths_collapse_the_current_home() {
+            let Some(home) = home_dir() else {
+                return;
+            };
+
+            assert_eq!(human_path(&home.join("project")), "~/project");
+        }
+    }
+
     mod success {
         use super::*;
 
@@ -199,6 +1190,51 @@ mod tests {
         }
     }
 
+    mod deepest_existing_ancestor {
+        use super::*;
+
+        #[rstest]
+        fn a_file_reports_its_directory(#[from(fixtures::temp_dir)] dir: PathBuf) {
+            let file = dir.join("Cargo.toml");
+            fs::write(&file, "").expect("Failed to write file");
+
+            assert_eq!(super::deepest_existing_ancestor(&file), Some(dir.as_path()));
+        }
+
+        #[rstest]
+        fn a_missing_leaf_reports_its_parent(#[from(fixtures::temp_dir)] dir: PathBuf) {
+            let path = dir.join("gone");
+
+            assert_eq!(super::deepest_existing_ancestor(&path), Some(dir.as_path()));
+        }
+
+        #[rstest]
+        fn a_missing_subtree_reports_where_it_diverges(#[from(fixtures::temp_dir)] dir: PathBuf) {
+            fs::create_dir_all(dir.join("a")).expect("Failed to create dir");
+            let path = dir.join("a/b/c");
+
+            assert_eq!(
+                super::deepest_existing_ancestor(&path),
+                Some(dir.join("a").as_path())
+            );
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn a_bogus_absolute_path_reports_the_root() {
+            let path = Path::new("/amg-no-such-dir/x/y");
+
+            assert_eq!(super::deepest_existing_ancestor(path), Some(Path::new("/")));
+        }
+
+        #[test]
+        fn a_bogus_relative_path_has_no_ancestor() {
+            let path = Path::new("amg-no-such-dir/x/y");
+
+            assert_eq!(super::deepest_existing_ancestor(path), None);
+        }
+    }
+
     mod failure {
         use super::*;
 
@@ -308,6 +1344,29 @@ mod tests {
             }
         }
 
+        #[rstest]
+        fn suggests_the_parent_of_a_file(#[from(fixtures::temp_dir)] dir: PathBuf) {
+            let file = dir.join("Cargo.toml");
+            fs::write(&file, "").expect("Failed to write file");
+
+            let error_msg = require_dir(&file, "repo", Some("CODEX_REPO"))
+                .unwrap_err()
+                .to_string();
+
+            let expected = format!("it is a file, did you mean {}?", human_path(&dir));
+            assert!(error_msg.contains(&expected), "got: {error_msg}");
+        }
+
+        #[rstest]
+        fn names_where_a_missing_path_diverges(#[from(fixtures::temp_dir)] dir: PathBuf) {
+            let path = dir.join("stale/repo");
+
+            let error_msg = require_dir(&path, "repo", None).unwrap_err().to_string();
+
+            let expected = format!("it does not exist past {}", human_path(&dir));
+            assert!(error_msg.contains(&expected), "got: {error_msg}");
+        }
+
         #[rstest]
         fn includes_path_in_error_message(#[from(fixtures::nonexistent_path)] path: PathBuf) {
             let result = require_dir(&path, "test", None);
@@ -0,0 +1,316 @@
+//! Waiting for a session to appear.
+//!
+//! This module implements the `watch` subcommand. It polls the codexdir every `--interval`
+//! until a session for the branch appears that was not there when the watch started (for
+//! example one a teammate started on another machine, arriving through a synced store), and
+//! then resumes it exactly like `amg resume --file <session>` would. With `--once`, amg exits
+//! after that launch; otherwise it keeps watching and reports every newer session for the
+//! branch, with a desktop notification when `--notify` is given.
+//!
+//! Each poll walks the codexdir newest first and only reads files that are new or whose
+//! modification time changed (see [`scan::find_changed_sessions`]); a session already seen is
+//! not reported again when codex appends to its file.
+//!
+//! `--timeout` ends the watch with [`EXIT_TIMED_OUT`] if no session was resumed by then, and
+//! Ctrl-C ends it with [`EXIT_CANCELLED`].
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+use super::args::{CacheArgs, CodexArgs, ExecutionArgs, LocationArgs, ResumeArgs, WatchArgs};
+use super::prelude::*;
+use super::{alias, config, explain, process, scan, util};
+
+/// How often a wait between polls checks for Ctrl-C and the deadline.
+const WAIT_SLICE: Duration = Duration::from_millis(100);
+
+/// Set by the `SIGINT` handler installed for the watch.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// What ended a wait between polls.
+#[derive(Debug, PartialEq, Eq)]
+enum Wake {
+    /// The interval passed; poll again.
+    Poll,
+    /// The `--timeout` deadline passed.
+    TimedOut,
+    /// Ctrl-C was pressed.
+    Interrupted,
+}
+
+/// The sessions of a branch seen so far.
+struct Watcher<'a> {
+    codexdir: &'a Path,
+    branch: &'a str,
+    options: &'a scan::ScanOptions,
+    /// Files already read, by modification time.
+    files: scan::SeenFiles,
+    /// Ids of the sessions already reported.
+    ids: HashSet<String>,
+}
+
+impl<'a> Watcher<'a> {
+    /// Starts watching, taking the sessions that already exist as seen.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the codexdir cannot be read.
+    fn start(codexdir: &'a Path, branch: &'a str, options: &'a scan::ScanOptions) -> Result<Self> {
+        let mut watcher = Self {
+            codexdir,
+            branch,
+            options,
+            files: scan::SeenFiles::new(),
+            ids: HashSet::new(),
+        };
+        let existing = watcher.poll()?.len();
+        debug!(existing, "sessions already present are not resumed");
+        Ok(watcher)
+    }
+
+    /// Returns the sessions that appeared since the last poll, newest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the codexdir cannot be read.
+    fn poll(&mut self) -> Result<Vec<scan::Session>> {
+        let changed =
+            scan::find_changed_sessions(self.codexdir, self.branch, self.options, &mut self.files)?;
+        Ok(changed
+            .into_iter()
+            .filter(|session| self.ids.insert(session.id.clone()))
+            .collect())
+    }
+}
+
+/// Handles the `watch` subcommand.
+///
+/// # Returns
+///
+/// With `--once`, returns what resuming the session returned. Otherwise returns success
+/// once a session was resumed and the watch ends, [`EXIT_TIMED_OUT`] if `--timeout` passed
+/// first, or [`EXIT_CANCELLED`] on Ctrl-C.
+///
+/// # Errors
+///
+/// Returns an error if the repo or codexdir is invalid, a poll cannot read the codexdir, or
+/// resuming the session fails.
+pub(super) fn run_watch(args: WatchArgs) -> Result<ExitCode> {
+    let codexdir = args
+        .location
+        .codexdir
+        .clone()
+        .map(Ok)
+        .unwrap_or_else(config::default_codexdir)?;
+    util::require_dir(&args.repo, "repo", Some("CODEX_REPO"))?;
+    util::require_dir(&codexdir, "codexdir", Some("CODEX_CODEXDIR"))?;
+    super::check_overlap(&args.repo, &codexdir, args.overlap.allow_overlap)?;
+    super::check_codexdir(&codexdir, args.selection.walk.force_scan)?;
+
+    let branch = alias::resolve_branch(&args.branch);
+    let options = super::scan_options(&args.selection)?;
+    let mut watcher = Watcher::start(&codexdir, &branch, &options)?;
+    let deadline = args.timeout.map(|timeout| Instant::now() + timeout);
+    catch_interrupts();
+    info!(
+        branch = %branch,
+        "watching {} for a new session (Ctrl-C to stop)",
+        util::human_path(&codexdir)
+    );
+
+    let mut resumed = false;
+    loop {
+        match wait(args.interval, deadline) {
+            Wake::Poll => {}
+            Wake::TimedOut if resumed => return Ok(ExitCode::SUCCESS),
+            Wake::TimedOut => {
+                error!(branch = %branch, "no new session appeared before --timeout");
+                return Ok(ExitCode::from(EXIT_TIMED_OUT));
+            }
+            Wake::Interrupted => {
+                info!("watch interrupted");
+                return Ok(ExitCode::from(EXIT_CANCELLED));
+            }
+        }
+        let sessions = watcher.poll()?;
+        let Some(newest) = sessions.first() else {
+            continue;
+        };
+        if resumed {
+            for session in &sessions {
+                report_newer(session, &branch, args.notify);
+            }
+            continue;
+        }
+        info!(branch = %branch, id = %newest.id, "a new session appeared; resuming it");
+        let exit = super::run_resume_branch(resume_args(&args, &codexdir, newest)?)?;
+        if args.once {
+            return Ok(exit);
+        }
+        resumed = true;
+        info!(branch = %branch, "still watching for newer sessions (Ctrl-C to stop)");
+    }
+}
+
+/// Builds the `resume` arguments that resume `session` with the watch's launch options.
+///
+/// # Errors
+///
+/// Returns an error if the session file's path is not valid UTF-8.
+fn resume_args(args: &WatchArgs, codexdir: &Path, session: &scan::Session) -> Result<ResumeArgs> {
+    let file = session.source_jsonl.to_str().with_context(|| {
+        format!(
+            "{} is not valid UTF-8",
+            session.source_jsonl.to_string_lossy()
+        )
+    })?;
+    Ok(ResumeArgs {
+        branch: None,
+        any: false,
+        id: None,
+        file: Some(file.to_owned()),
+        force: false,
+        stdin_jsonl: false,
+        path_prefix: None,
+        repo: Some(args.repo.clone()),
+        location: LocationArgs {
+            codexdir: Some(codexdir.to_owned()),
+        },
+        overlap: args.overlap.clone(),
+        selection: args.selection.clone(),
+        relax_on_empty: false,
+        cache: CacheArgs { no_cache: true },
+        fresh: false,
+        pick: false,
+        pick_tmux: false,
+        no_fzf: false,
+        explain: false,
+        json: false,
+        explain_limit: explain::DEFAULT_LIMIT,
+        codex: CodexArgs {
+            wrapper: args.wrapper.clone(),
+            ..CodexArgs::default()
+        },
+        execution: ExecutionArgs {
+            dry_run: args.dry_run,
+            tmux: args.tmux,
+            tmux_detach: args.tmux_detach,
+            notify: args.notify,
+            ..ExecutionArgs::default()
+        },
+        sandbox: args.sandbox.clone(),
+        backup: super::args::BackupArgs {
+            backup: false,
+            backup_keep: 1,
+            backup_best_effort: false,
+        },
+    })
+}
+
+/// Reports a session that appeared after the watch already resumed one.
+fn report_newer(session: &scan::Session, branch: &str, notify: bool) {
+    let message = format!("amg: newer session {} for {branch}", session.id);
+    info!(
+        branch = %branch,
+        id = %session.id,
+        file = %util::human_path(&session.source_jsonl),
+        "a newer session appeared"
+    );
+    if notify && let Err(err) = process::desktop_notify(&message) {
+        warn!("could not show desktop notification: {err:#}");
+    }
+}
+
+/// Sleeps for `interval`, waking early for Ctrl-C or the deadline.
+fn wait(interval: Duration, deadline: Option<Instant>) -> Wake {
+    let until = Instant::now() + interval;
+    loop {
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            return Wake::Interrupted;
+        }
+        let now = Instant::now();
+        if deadline.is_some_and(|deadline| now >= deadline) {
+            return Wake::TimedOut;
+        }
+        if now >= until {
+            return Wake::Poll;
+        }
+        let mut slice = WAIT_SLICE.min(until - now);
+        if let Some(deadline) = deadline {
+            slice = slice.min(deadline - now);
+        }
+        std::thread::sleep(slice);
+    }
+}
+
+/// Makes Ctrl-C set [`INTERRUPTED`] instead of killing amg, so the watch can exit with
+/// [`EXIT_CANCELLED`]. An inline codex still receives its own `SIGINT`.
+fn catch_interrupts() {
+    #[cfg(unix)]
+    {
+        extern "C" fn on_interrupt(_: libc::c_int) {
+            INTERRUPTED.store(true, Ordering::Relaxed);
+        }
+        let handler: extern "C" fn(libc::c_int) = on_interrupt;
+        // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+        unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::test_support::{SessionFile, temp_dir};
+    use rstest::rstest;
+
+    const ID_A: &str = "0199a1b2-c3d4-7e5f-8a9b-0c1d2e3f4a5b";
+    const ID_B: &str = "0199a1b2-c3d4-7e5f-8a9b-0c1d2e3f4a5c";
+
+    #[rstest]
+    fn reports_only_sessions_that_appear_after_the_start(#[from(temp_dir)] dir: PathBuf) {
+        let day = dir.join(SESSIONS_DIR).join("2025/03/09");
+        SessionFile::new("main", ID_A).write(day.join("rollout-a.jsonl"));
+        let options = scan::ScanOptions::default();
+        let mut watcher = Watcher::start(&dir, "main", &options).unwrap();
+
+        assert!(watcher.poll().unwrap().is_empty());
+
+        let new = day.join("rollout-b.jsonl");
+        SessionFile::new("other", ID_B).write(day.join("rollout-other.jsonl"));
+        SessionFile::new("main", ID_B).write(&new);
+        let sessions = watcher.poll().unwrap();
+
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].source_jsonl, new);
+    }
+
+    #[rstest]
+    fn a_growing_session_file_is_reported_once(#[from(temp_dir)] dir: PathBuf) {
+        let options = scan::ScanOptions::default();
+        let mut watcher = Watcher::start(&dir, "main", &options).unwrap();
+        let file = dir.join(SESSIONS_DIR).join("rollout-a.jsonl");
+        SessionFile::new("main", ID_A).write(&file);
+
+        assert_eq!(watcher.poll().unwrap().len(), 1);
+        let mut content = fs::read_to_string(&file).unwrap();
+        content.push_str("{}\n");
+        fs::write(&file, content).unwrap();
+        let touched = SystemTime::now() + Duration::from_secs(5);
+        fs::File::options()
+            .append(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(touched)
+            .unwrap();
+
+        assert!(watcher.poll().unwrap().is_empty());
+    }
+
+    #[test]
+    fn wait_ends_at_the_interval_or_the_deadline() {
+        assert_eq!(wait(Duration::ZERO, None), Wake::Poll);
+        let past = Instant::now();
+        assert_eq!(wait(Duration::from_secs(60), Some(past)), Wake::TimedOut);
+    }
+}
@@ -16,16 +16,44 @@
 //! ## Example
 //!
 //! ```no_run
+//! # #[cfg(feature = "cli")]
 //! use amg::cli::entry;
 //!
+//! # #[cfg(feature = "cli")]
 //! fn main() -> std::process::ExitCode {
 //!     entry()
 //! }
+//! # #[cfg(not(feature = "cli"))]
+//! # fn main() {}
 //! ```
 //!
 //! ## Modules
 //!
 //! * [`cli`] - Command-line interface implementation
+//! * [`sessions`] - Session lookup and resume commands for embedders
+//!
+//! ## Features
+//!
+//! * `cli` (default) - The `amg` binary and `cli::entry`, with argument parsing (`clap`) and
+//!   logging setup (`tracing-subscriber`)
+//! * `async` - `sessions::find_first_async`, for async runtimes
+//!
+//! An embedder that only scans sessions and builds codex commands can depend on amg with
+//! `default-features = false`:
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use std::path::Path;
+//!
+//! let codexdir = Path::new("/home/dev/.codex");
+//! if let Some(session) = amg::sessions::find_first(codexdir, "main")? {
+//!     let status = amg::sessions::resume_command(&session, Path::new("/home/dev/app"), codexdir)?
+//!         .status()?;
+//!     println!("codex exited with {status}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
 //!
 //! ## See Also
 //!
@@ -35,3 +63,5 @@
 #![warn(missing_docs)]
 
 pub mod cli;
+
+pub use cli::sessions;
@@ -80,6 +80,7 @@ mod constants {
     pub const ARG_REPO: &str = "repo";
     pub const ARG_CODEXDIR: &str = "codexdir";
     pub const ARG_DRY_RUN: &str = "dry_run";
+    pub const ARG_TMUX: &str = "tmux";
     pub const ARG_NO_TMUX: &str = "no_tmux";
 
     // Expected help text snippets
@@ -91,8 +92,8 @@ mod constants {
     pub const HELP_CODEXDIR: &str = "Codex directory";
     pub const HELP_JSONL: &str = "JSONL sessions";
     pub const HELP_DRY_RUN: &str = "Print the exact command";
-    pub const HELP_NO_TMUX: &str = "TMUX";
-    pub const HELP_NO_TMUX_ACTION: &str = "do NOT open";
+    pub const HELP_TMUX: &str = "TMUX";
+    pub const HELP_TMUX_NEVER: &str = "`never` (run";
 
     // Flag forms
     pub const SHORT_REPO: char = 'r';
@@ -100,6 +101,7 @@ mod constants {
     pub const LONG_REPO: &str = "repo";
     pub const LONG_CODEXDIR: &str = "codexdir";
     pub const LONG_DRY_RUN: &str = "dry-run";
+    pub const LONG_TMUX: &str = "tmux";
     pub const LONG_NO_TMUX: &str = "no-tmux";
 
     // Aliases
@@ -195,12 +197,20 @@ mod arguments {
     }
 
     #[test]
-    fn no_tmux_has_help_text_and_flag() {
+    fn tmux_has_help_text_and_flag() {
+        let cmd = get_resume_branch_cmd();
+        let arg = get_argument_by_id(&cmd, ARG_TMUX);
+        let help_text = assert_help_text_exists(arg.get_help(), "tmux flag");
+        assert_help_text_contains(&help_text, HELP_TMUX, "tmux");
+        assert_help_text_contains(&help_text, HELP_TMUX_NEVER, "tmux");
+        assert_flag_has_long(&arg, LONG_TMUX, "tmux");
+    }
+
+    #[test]
+    fn no_tmux_is_a_hidden_alias() {
         let cmd = get_resume_branch_cmd();
         let arg = get_argument_by_id(&cmd, ARG_NO_TMUX);
-        let help_text = assert_help_text_exists(arg.get_help(), "no-tmux flag");
-        assert_help_text_contains(&help_text, HELP_NO_TMUX, "no-tmux");
-        assert_help_text_contains(&help_text, HELP_NO_TMUX_ACTION, "no-tmux");
+        assert!(arg.is_hide_set(), "no-tmux should be hidden");
         assert_flag_has_long(&arg, LONG_NO_TMUX, "no-tmux");
     }
 }
@@ -286,8 +296,8 @@ mod help_output {
             output
         );
         assert!(
-            output.contains("--no-tmux"),
-            "resume help output should mention no-tmux flag. Got output:\n{}",
+            output.contains("--tmux <WHEN>"),
+            "resume help output should mention tmux flag. Got output:\n{}",
             output
         );
         assert!(
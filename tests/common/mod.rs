@@ -0,0 +1,100 @@
+//! Helpers shared by the integration tests: a throwaway sandbox holding one session, and an
+//! `amg` command isolated from the user's environment.
+
+// Each test crate uses only some of the helpers.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The id of the one session in a [`Sandbox`].
+pub const SESSION_ID: &str = "0199a6b2-3c4d-7e8f-9a0b-1c2d3e4f5a6b";
+
+/// A throwaway root with a faked `home`, a `repo`, and a `codex` directory holding one session
+/// for `main` in `repo`.
+pub struct Sandbox {
+    pub root: PathBuf,
+}
+
+impl Sandbox {
+    /// Creates the sandbox under the temp dir, in a directory named after `name` (the test
+    /// crate) so leftovers can be told apart.
+    pub fn new(name: &str) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let root = std::env::temp_dir().join(format!(
+            "amg_{name}_{}_{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let sandbox = Self { root };
+        let sessions = sandbox.codexdir().join("sessions/2025/03/09");
+        fs::create_dir_all(sandbox.home()).expect("Failed to create home dir");
+        fs::create_dir_all(sandbox.repo()).expect("Failed to create repo dir");
+        fs::create_dir_all(&sessions).expect("Failed to create sessions dir");
+        let line = serde_json::json!({
+            "payload": { "git": { "branch": "main" }, "cwd": sandbox.repo(), "id": SESSION_ID }
+        });
+        fs::write(sessions.join("rollout-a.jsonl"), format!("{line}\n"))
+            .expect("Failed to write session file");
+        sandbox
+    }
+
+    pub fn home(&self) -> PathBuf {
+        self.root.join("home")
+    }
+
+    pub fn repo(&self) -> PathBuf {
+        self.root.join("repo")
+    }
+
+    pub fn codexdir(&self) -> PathBuf {
+        self.root.join("codex")
+    }
+
+    /// Builds an `amg` command with this sandbox's home, see [`amg`].
+    #[cfg(feature = "cli")]
+    pub fn amg(&self) -> Command {
+        amg(&self.home())
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Builds an `amg` command with `home` as its `$HOME`, see [`isolate`].
+#[cfg(feature = "cli")]
+pub fn amg(home: &Path) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_amg"));
+    isolate(&mut cmd, home);
+    cmd
+}
+
+/// Isolates `cmd` from the user's environment: `home` becomes its `$HOME`, and every variable
+/// that would point amg at the user's config, state, cache, repo, codexdir, log, or tmux is
+/// removed, so amg only finds what the test put under `home`.
+pub fn isolate<'a>(cmd: &'a mut Command, home: &Path) -> &'a mut Command {
+    cmd.env("HOME", home);
+    for var in [
+        "AMG_CONFIG",
+        "AMG_STATE_FILE",
+        "AMG_STATS_FILE",
+        "AMG_LOG_FILE",
+        "AMG_PORTABLE",
+        "XDG_CONFIG_HOME",
+        "XDG_CACHE_HOME",
+        "XDG_DATA_HOME",
+        "XDG_STATE_HOME",
+        "CODEX_HOME",
+        "CODEX_REPO",
+        "CODEX_CODEXDIR",
+        "TMUX",
+    ] {
+        cmd.env_remove(var);
+    }
+    cmd
+}
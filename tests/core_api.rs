@@ -0,0 +1,55 @@
+/// Integration tests for the library API in `amg::sessions`.
+///
+/// These tests only use what the crate exposes without the default `cli` feature, so they run
+/// (and must pass) under both `cargo test` and `cargo test --no-default-features`:
+/// - Finding the first session for a branch
+/// - Building the codex command that resumes it
+use common::{SESSION_ID, Sandbox};
+
+mod common;
+
+#[test]
+fn finds_the_session_for_a_branch() {
+    let sandbox = Sandbox::new("core_api");
+    let codexdir = sandbox.codexdir();
+
+    let session = amg::sessions::find_first(&codexdir, "main")
+        .expect("scan should succeed")
+        .expect("main should have a session");
+
+    assert_eq!(session.id, SESSION_ID);
+    assert_eq!(session.cwd, sandbox.repo());
+    assert_eq!(
+        amg::sessions::find_first(&codexdir, "other").expect("scan should succeed"),
+        None
+    );
+}
+
+#[test]
+fn builds_the_resume_command() {
+    let sandbox = Sandbox::new("core_api");
+    let codexdir = sandbox.codexdir();
+    let repo = sandbox.repo();
+    let session = amg::sessions::find_first(&codexdir, "main")
+        .expect("scan should succeed")
+        .expect("main should have a session");
+
+    let command =
+        amg::sessions::resume_command(&session, &repo, &codexdir).expect("command should build");
+    let args: Vec<String> = command
+        .get_args()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+
+    assert_eq!(command.get_program(), "codex");
+    assert_eq!(command.get_current_dir(), Some(repo.as_path()));
+    assert!(
+        args.windows(2)
+            .any(|pair| pair == ["-s", "workspace-write"]),
+        "{args:?}"
+    );
+    assert!(
+        args.ends_with(&["resume".to_owned(), SESSION_ID.to_owned()]),
+        "{args:?}"
+    );
+}
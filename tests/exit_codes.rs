@@ -0,0 +1,184 @@
+/// Integration tests for amg's exit code scheme.
+///
+/// These tests run the `amg` binary with a stub in place of codex (via `--wrap`) and verify
+/// that:
+/// - The stub's exit code is forwarded verbatim when it runs inline
+/// - amg's own failures exit 1 by default, or above the `--exit-code-offset` base when given
+/// - A listing whose reader closes the pipe early exits 0 without a panic
+/// - `resume --print-id` prints only the session id, and exits 2 when nothing matches
+use std::fs;
+use std::io::Read;
+use std::process::Stdio;
+
+use common::{SESSION_ID, Sandbox};
+
+mod common;
+
+/// Runs `amg resume <branch>` inline in `sandbox` with `codex` replaced by `stub`.
+fn resume(sandbox: &Sandbox, branch: &str, stub: &str, extra: &[&str]) -> Option<i32> {
+    sandbox
+        .amg()
+        .args(["resume", branch, "--no-tmux", "--no-cache", "--wrap", stub])
+        .arg("--repo")
+        .arg(sandbox.repo())
+        .arg("--codexdir")
+        .arg(sandbox.codexdir())
+        .args(extra)
+        .status()
+        .expect("Failed to run amg")
+        .code()
+}
+
+mod forwarding {
+    use super::*;
+
+    #[test]
+    fn forwards_child_exit_codes_verbatim() {
+        let sandbox = Sandbox::new("exit_codes");
+        for code in [0, 1, 3, 42, 100] {
+            let stub = format!("sh -c 'exit {code}'");
+            assert_eq!(resume(&sandbox, "main", &stub, &[]), Some(code));
+            assert_eq!(
+                resume(&sandbox, "main", &stub, &["--exit-code-offset"]),
+                Some(code),
+                "the offset must not change the child's code"
+            );
+        }
+    }
+}
+
+mod amg_failures {
+    use super::*;
+
+    #[test]
+    fn exit_one_by_default() {
+        let sandbox = Sandbox::new("exit_codes");
+        assert_eq!(resume(&sandbox, "missing", "true", &[]), Some(1));
+    }
+
+    #[test]
+    fn a_missing_session_exits_two_above_the_base() {
+        let sandbox = Sandbox::new("exit_codes");
+        assert_eq!(
+            resume(&sandbox, "missing", "true", &["--exit-code-offset"]),
+            Some(102)
+        );
+        assert_eq!(
+            resume(&sandbox, "missing", "true", &["--exit-code-offset=120"]),
+            Some(122)
+        );
+    }
+
+    #[test]
+    fn a_broken_environment_is_one_amg_failure() {
+        let sandbox = Sandbox::new("exit_codes");
+        let output = sandbox
+            .amg()
+            .args(["which", "main", "--exit-code-offset"])
+            .env_remove("PATH")
+            .env_remove("HOME")
+            .output()
+            .expect("Failed to run amg");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        assert_eq!(output.status.code(), Some(101));
+        assert!(stderr.contains("$PATH is unset"), "{stderr}");
+        assert!(stderr.contains("$HOME is unset"), "{stderr}");
+    }
+
+    #[test]
+    fn failing_to_spawn_the_child_is_an_amg_failure() {
+        let sandbox = Sandbox::new("exit_codes");
+        assert_eq!(
+            resume(
+                &sandbox,
+                "main",
+                "/nonexistent/stub",
+                &["--exit-code-offset"]
+            ),
+            Some(101)
+        );
+    }
+}
+
+mod broken_pipe {
+    use super::*;
+
+    #[test]
+    fn a_listing_into_a_closed_pipe_exits_quietly() {
+        let sandbox = Sandbox::new("exit_codes");
+        // Far more than a pipe buffer holds, so amg is still writing when the pipe closes.
+        let aliases: serde_json::Map<_, _> = (0..20_000)
+            .map(|n| {
+                (
+                    format!("alias-{n:05}"),
+                    format!("feature/branch-{n:05}").into(),
+                )
+            })
+            .collect();
+        let state = sandbox.home().join(".local/share/amg/state.json");
+        fs::create_dir_all(state.parent().unwrap()).expect("Failed to create state dir");
+        fs::write(
+            &state,
+            serde_json::json!({ "aliases": aliases }).to_string(),
+        )
+        .expect("Failed to write state file");
+
+        let mut child = sandbox
+            .amg()
+            .args(["alias", "list"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("Failed to run amg");
+        let mut first = [0; 16];
+        child
+            .stdout
+            .take()
+            .expect("stdout is piped")
+            .read_exact(&mut first)
+            .expect("Failed to read the listing");
+        let output = child.wait_with_output().expect("Failed to wait for amg");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        assert_eq!(&first, b"alias-00000\tfeat");
+        assert_eq!(output.status.code(), Some(0), "{stderr}");
+        assert!(!stderr.contains("panicked"), "{stderr}");
+    }
+}
+
+mod print_id {
+    use super::*;
+
+    fn print_id(sandbox: &Sandbox, branch: &str) -> std::process::Output {
+        sandbox
+            .amg()
+            .args(["resume", branch, "--print-id", "--no-cache", "--codexdir"])
+            .arg(sandbox.codexdir())
+            .output()
+            .expect("Failed to run amg")
+    }
+
+    #[test]
+    fn prints_only_the_session_id() {
+        let sandbox = Sandbox::new("exit_codes");
+        let output = print_id(&sandbox, "main");
+
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!("{SESSION_ID}\n")
+        );
+    }
+
+    #[test]
+    fn exits_two_when_no_session_matches() {
+        let sandbox = Sandbox::new("exit_codes");
+        let output = print_id(&sandbox, "missing");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        assert_eq!(output.status.code(), Some(2), "{stderr}");
+        assert!(output.stdout.is_empty());
+        assert!(stderr.contains("No matching session"), "{stderr}");
+    }
+}
@@ -0,0 +1,211 @@
+/// Integration tests for `--portable` mode.
+///
+/// These tests run the `amg` binary against a faked `$HOME` that holds an unreadable config, a
+/// state file, and a home sandbox directory, and verify that in portable mode:
+/// - Representative commands succeed without reading the config or the state file
+/// - No file under `$HOME` is created or changed by any of them
+/// - Home directories are not granted to the sandbox
+/// - The commands that need a home file, or the default `$HOME/.codex`, fail with a hint
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+use common::Sandbox;
+
+mod common;
+
+mod helpers {
+    use super::*;
+
+    /// Creates a sandbox whose home also has a config file that fails to parse, a state file
+    /// aliasing `m` to `main`, and a `~/.cargo` directory.
+    pub fn seeded_sandbox() -> Sandbox {
+        let sandbox = Sandbox::new("portable");
+        let home = sandbox.home();
+        let config = home.join(".config/amg/config.toml");
+        fs::create_dir_all(config.parent().unwrap()).expect("Failed to create config dir");
+        fs::write(&config, "this is = [not toml").expect("Failed to write config");
+        let state = home.join(".local/share/amg/state.json");
+        fs::create_dir_all(state.parent().unwrap()).expect("Failed to create state dir");
+        fs::write(&state, r#"{"aliases":{"m":"main"}}"#).expect("Failed to write state");
+        fs::create_dir_all(home.join(".cargo")).expect("Failed to create ~/.cargo");
+        sandbox
+    }
+
+    /// Runs `amg` with `args` in `sandbox`, in portable mode when `portable` is set, with
+    /// `--repo` and `--codexdir` appended when `dirs` is set.
+    pub fn run(sandbox: &Sandbox, portable: bool, args: &[&str], dirs: bool) -> Output {
+        let mut cmd = sandbox.amg();
+        cmd.args(args);
+        if portable {
+            cmd.env("AMG_PORTABLE", "1");
+        }
+        if dirs {
+            cmd.arg("--repo")
+                .arg(sandbox.repo())
+                .arg("--codexdir")
+                .arg(sandbox.codexdir());
+        }
+        cmd.output().expect("Failed to run amg")
+    }
+
+    /// Lists every file under the sandbox's faked home with its content, sorted by path.
+    pub fn home_files(sandbox: &Sandbox) -> Vec<(PathBuf, Vec<u8>)> {
+        fn walk(dir: &Path, files: &mut Vec<(PathBuf, Vec<u8>)>) {
+            for entry in fs::read_dir(dir).expect("Failed to read dir") {
+                let path = entry.expect("Failed to read entry").path();
+                if path.is_dir() {
+                    walk(&path, files);
+                } else {
+                    let content = fs::read(&path).expect("Failed to read file");
+                    files.push((path, content));
+                }
+            }
+        }
+        let mut files = Vec::new();
+        walk(&sandbox.home(), &mut files);
+        files.sort();
+        files
+    }
+
+    pub fn stdout(output: &Output) -> String {
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    pub fn stderr(output: &Output) -> String {
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    }
+}
+
+mod portable {
+    use super::helpers::{home_files, run, seeded_sandbox, stderr, stdout};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(&["which", "main", "--codexdir"])]
+    #[case(&["branches", "--cached", "--codexdir"])]
+    #[case(&["find", "mai", "--codexdir"])]
+    #[case(&["stats", "--codexdir"])]
+    #[case(&["show", "main", "--codexdir"])]
+    fn listings_leave_the_home_untouched(#[case] args: &[&str]) {
+        let sandbox = seeded_sandbox();
+        let before = home_files(&sandbox);
+        let mut args = args.to_vec();
+        let codexdir = sandbox.codexdir();
+        args.push(codexdir.to_str().unwrap());
+
+        let output = run(&sandbox, true, &args, false);
+
+        assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+        assert_eq!(home_files(&sandbox), before);
+    }
+
+    #[rstest]
+    #[case(&["resume", "main", "--no-tmux", "--wrap", "true"])]
+    #[case(&["resume", "main", "--dry-run", "--tmux", "never"])]
+    #[case(&["resume-all", "--branches", "main", "--dry-run"])]
+    fn resumes_leave_the_home_untouched(#[case] args: &[&str]) {
+        let sandbox = seeded_sandbox();
+        let before = home_files(&sandbox);
+
+        let output = run(&sandbox, true, args, true);
+
+        assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+        assert_eq!(home_files(&sandbox), before);
+    }
+
+    #[test]
+    fn home_directories_are_not_granted() {
+        let sandbox = seeded_sandbox();
+        let cargo = sandbox.home().join(".cargo");
+        let cargo = cargo.to_str().unwrap();
+
+        let output = run(
+            &sandbox,
+            true,
+            &["resume", "main", "--dry-run", "--tmux", "never"],
+            true,
+        );
+
+        assert!(stdout(&output).contains("resume"), "{}", stderr(&output));
+        assert!(!stdout(&output).contains(cargo), "{}", stdout(&output));
+    }
+
+    #[test]
+    fn aliases_are_not_applied() {
+        let sandbox = seeded_sandbox();
+
+        let output = run(&sandbox, true, &["resume", "m", "--dry-run"], true);
+
+        assert_ne!(output.status.code(), Some(0));
+        assert!(
+            stderr(&output).contains(r#"branch "m""#),
+            "{}",
+            stderr(&output)
+        );
+    }
+
+    #[rstest]
+    #[case(&["alias", "add", "x", "main"], "the amg state file")]
+    #[case(&["tag", "list"], "the amg state file")]
+    #[case(&["config", "list"], "the global config file")]
+    #[case(&["which", "main"], "--portable needs the codexdir")]
+    fn commands_that_need_home_files_fail(#[case] args: &[&str], #[case] expected: &str) {
+        let sandbox = seeded_sandbox();
+        let before = home_files(&sandbox);
+
+        let output = run(&sandbox, true, args, false);
+
+        assert_eq!(output.status.code(), Some(1));
+        assert!(
+            stderr(&output).contains(expected) && stderr(&output).contains("--portable"),
+            "{}",
+            stderr(&output)
+        );
+        assert_eq!(home_files(&sandbox), before);
+    }
+}
+
+mod default {
+    use super::Sandbox;
+    use super::helpers::{run, stderr, stdout};
+
+    /// Without `--portable` the same run writes under the home, so the tests above would
+    /// notice a portable run that does.
+    #[test]
+    fn which_writes_the_last_hit_cache() {
+        let sandbox = Sandbox::new("portable");
+        let codexdir = sandbox.codexdir();
+
+        let output = run(
+            &sandbox,
+            false,
+            &["which", "main", "--codexdir", codexdir.to_str().unwrap()],
+            false,
+        );
+
+        assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+        assert!(stdout(&output).contains("rollout-a.jsonl"));
+        assert!(sandbox.home().join(".cache/amg/last-hit.toml").is_file());
+    }
+
+    #[test]
+    fn home_directories_are_granted() {
+        let sandbox = Sandbox::new("portable");
+        let cargo = sandbox.home().join(".cargo");
+        std::fs::create_dir_all(&cargo).unwrap();
+
+        let output = run(
+            &sandbox,
+            false,
+            &["resume", "main", "--dry-run", "--tmux", "never"],
+            true,
+        );
+
+        assert!(
+            stdout(&output).contains(cargo.to_str().unwrap()),
+            "{}",
+            stderr(&output)
+        );
+    }
+}
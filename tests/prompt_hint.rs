@@ -0,0 +1,87 @@
+/// Integration tests for `amg prompt-hint`.
+///
+/// These tests run the `amg` binary with the session tree made unwalkable (replaced by a
+/// regular file, which no permission can get around) and verify that:
+/// - With an index, the answer is the same as with the tree in place
+/// - Without any cache, the answer is `unknown` even though a walk would find the session
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::Sandbox;
+
+mod common;
+
+/// Runs `amg prompt-hint` for the work tree at `dir` and returns its one line of output.
+fn prompt_hint(sandbox: &Sandbox, dir: &Path) -> String {
+    let output = sandbox
+        .amg()
+        .arg("prompt-hint")
+        .arg("--codexdir")
+        .arg(sandbox.codexdir())
+        .arg("--dir")
+        .arg(dir)
+        .output()
+        .expect("Failed to run amg");
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout)
+        .expect("Output is not UTF-8")
+        .trim_end()
+        .to_owned()
+}
+
+/// Creates a work tree named `name` under the sandbox root with `branch` checked out.
+fn work_tree(sandbox: &Sandbox, name: &str, branch: &str) -> PathBuf {
+    let dir = sandbox.root.join(name);
+    fs::create_dir_all(dir.join(".git")).expect("Failed to create .git");
+    fs::write(dir.join(".git/HEAD"), format!("ref: refs/heads/{branch}\n"))
+        .expect("Failed to write HEAD");
+    dir
+}
+
+/// Replaces the codexdir's `sessions/` tree with a regular file, so any walk finds nothing.
+fn replace_sessions_with_a_file(sandbox: &Sandbox) {
+    let sessions = sandbox.codexdir().join("sessions");
+    fs::remove_dir_all(&sessions).expect("Failed to remove sessions");
+    fs::write(&sessions, "not a directory\n").expect("Failed to write sessions file");
+}
+
+#[test]
+fn answers_from_the_index_without_walking() {
+    let sandbox = Sandbox::new("prompt_hint");
+    let main = work_tree(&sandbox, "main", "main");
+    let other = work_tree(&sandbox, "other", "other");
+    let status = sandbox
+        .amg()
+        .args(["branches", "--cached", "--codexdir"])
+        .arg(sandbox.codexdir())
+        .output()
+        .expect("Failed to run amg")
+        .status;
+    assert!(status.success());
+    let before = (prompt_hint(&sandbox, &main), prompt_hint(&sandbox, &other));
+
+    replace_sessions_with_a_file(&sandbox);
+
+    assert!(before.0.starts_with("yes:"), "{before:?}");
+    assert_eq!(before.1, "no");
+    assert_eq!(
+        (prompt_hint(&sandbox, &main), prompt_hint(&sandbox, &other)),
+        before
+    );
+}
+
+#[test]
+fn without_caches_the_answer_is_unknown_rather_than_a_scan() {
+    let sandbox = Sandbox::new("prompt_hint");
+    let main = work_tree(&sandbox, "main", "main");
+
+    assert_eq!(prompt_hint(&sandbox, &main), "unknown");
+    assert!(
+        !sandbox.home().join(".cache").exists(),
+        "prompt-hint must not build a cache"
+    );
+
+    replace_sessions_with_a_file(&sandbox);
+
+    assert_eq!(prompt_hint(&sandbox, &main), "unknown");
+}
@@ -0,0 +1,206 @@
+#![cfg(unix)]
+
+/// Integration tests for `amg resume --result-json`.
+///
+/// These tests run the `amg` binary against a throwaway codex directory and verify the final
+/// JSON line for each action:
+/// - A dry run writes it to fd 3 (stdout carries the command), or to stdout with `--output`
+/// - `--redact-home` collapses the home directory in the result and `--explain --json`
+/// - A tmux launch (with a stub `tmux` on `PATH`) reports the new window id
+/// - Inside tmux but without a terminal, `--tmux auto` runs inline instead
+/// - An inline run (with a stub in place of codex) reports the child's exit code
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use common::{SESSION_ID, Sandbox};
+
+mod common;
+
+mod helpers {
+    use super::*;
+
+    /// Builds `amg resume main --result-json ...` for `sandbox` as a shell command line.
+    pub fn resume_line(sandbox: &Sandbox, extra: &str) -> String {
+        format!(
+            "{} resume main --no-cache --result-json --repo {} --codexdir {} {extra}",
+            env!("CARGO_BIN_EXE_amg"),
+            sandbox.repo().display(),
+            sandbox.codexdir().display()
+        )
+    }
+
+    /// Runs a shell command line in `sandbox`, with amg isolated from the user's environment
+    /// and, with `tmux`, inside a stub tmux.
+    pub fn sh(sandbox: &Sandbox, line: &str, tmux: bool) -> Output {
+        let mut cmd = Command::new("sh");
+        common::isolate(cmd.arg("-c").arg(line), &sandbox.home());
+        if tmux {
+            cmd.env("TMUX", "/tmp/tmux-stub,1,0")
+                .env("PATH", stub_path(sandbox));
+        }
+        cmd.output().expect("Failed to run amg")
+    }
+
+    /// Installs a `tmux` stub that prints a window id, returning a `PATH` that finds it.
+    fn stub_path(sandbox: &Sandbox) -> String {
+        let bin = sandbox.root.join("bin");
+        fs::create_dir_all(&bin).expect("Failed to create stub dir");
+        let tmux = bin.join("tmux");
+        fs::write(&tmux, "#!/bin/sh\necho @7\n").expect("Failed to write tmux stub");
+        make_executable(&tmux);
+        format!(
+            "{}:{}",
+            bin.display(),
+            std::env::var("PATH").unwrap_or_default()
+        )
+    }
+
+    fn make_executable(path: &Path) {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+            .expect("Failed to make stub executable");
+    }
+
+    /// Parses the single JSON line in `text`.
+    pub fn result_line(text: &str) -> serde_json::Value {
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 1, "expected one result line, got {text:?}");
+        serde_json::from_str(lines[0]).expect("result should be JSON")
+    }
+}
+
+mod actions {
+    use super::helpers::{result_line, resume_line, sh};
+    use super::*;
+
+    #[test]
+    fn dry_run_writes_the_result_to_fd_3() {
+        let sandbox = Sandbox::new("result_json");
+        let fd3 = sandbox.root.join("fd3.json");
+        let line = format!(
+            "{} --dry-run --no-tmux 3>{}",
+            resume_line(&sandbox, ""),
+            fd3.display()
+        );
+
+        let output = sh(&sandbox, &line, false);
+
+        assert!(output.status.success(), "{output:?}");
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(
+            stdout.contains("resume"),
+            "stdout keeps the command: {stdout}"
+        );
+        let result = result_line(&fs::read_to_string(fd3).unwrap());
+        assert_eq!(result["action"], "dry-run");
+        assert_eq!(result["session_id"], SESSION_ID);
+        assert_eq!(result["branch"], "main");
+        assert_eq!(result["cwd"], sandbox.repo().to_str().unwrap());
+    }
+
+    #[test]
+    fn dry_run_without_fd_3_fails() {
+        let sandbox = Sandbox::new("result_json");
+
+        let output = sh(
+            &sandbox,
+            &format!("{} --dry-run --no-tmux", resume_line(&sandbox, "")),
+            false,
+        );
+
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("file descriptor 3"));
+    }
+
+    #[test]
+    fn dry_run_with_output_uses_stdout() {
+        let sandbox = Sandbox::new("result_json");
+        let command = sandbox.root.join("cmd.sh");
+        let line = format!(
+            "{} --dry-run --no-tmux --output {}",
+            resume_line(&sandbox, ""),
+            command.display()
+        );
+
+        let output = sh(&sandbox, &line, false);
+
+        assert!(output.status.success(), "{output:?}");
+        let result = result_line(&String::from_utf8(output.stdout).unwrap());
+        assert_eq!(result["action"], "dry-run");
+        assert!(fs::read_to_string(command).unwrap().contains("resume"));
+    }
+
+    #[test]
+    fn redact_home_collapses_the_cwd_and_explained_paths() {
+        let sandbox = Sandbox::new("result_json");
+        let command = sandbox.root.join("cmd.sh");
+        // With the sandbox root as home, the session cwd and the codexdir are under it.
+        let line = format!(
+            "HOME={} {} --dry-run --no-tmux --explain --json --redact-home --output {}",
+            sandbox.root.display(),
+            resume_line(&sandbox, ""),
+            command.display()
+        );
+
+        let output = sh(&sandbox, &line, false);
+
+        assert!(output.status.success(), "{output:?}");
+        let result = result_line(&String::from_utf8(output.stdout).unwrap());
+        assert_eq!(result["cwd"], "~/repo");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        let explanation: serde_json::Value = stderr
+            .lines()
+            .find_map(|line| serde_json::from_str(line).ok())
+            .expect("the explanation should be a JSON line");
+        assert_eq!(
+            explanation["candidates"][0]["path"],
+            "~/codex/sessions/2025/03/09/rollout-a.jsonl"
+        );
+    }
+
+    #[test]
+    fn tmux_reports_the_window_id() {
+        let sandbox = Sandbox::new("result_json");
+
+        // The captured stdio is not a terminal, so only `always` opens the window.
+        let output = sh(&sandbox, &resume_line(&sandbox, "--tmux always"), true);
+
+        assert!(output.status.success(), "{output:?}");
+        let result = result_line(&String::from_utf8(output.stdout).unwrap());
+        assert_eq!(result["action"], "tmux");
+        assert_eq!(result["window_id"], "@7");
+        assert!(result.get("exit_code").is_none());
+    }
+
+    #[test]
+    fn tmux_without_a_terminal_runs_inline() {
+        let sandbox = Sandbox::new("result_json");
+
+        let output = sh(
+            &sandbox,
+            &resume_line(&sandbox, "--wrap true </dev/null"),
+            true,
+        );
+
+        assert!(output.status.success(), "{output:?}");
+        let result = result_line(&String::from_utf8(output.stdout).unwrap());
+        assert_eq!(result["action"], "inline");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("pass --tmux always"), "{stderr}");
+    }
+
+    #[test]
+    fn inline_reports_the_exit_code() {
+        let sandbox = Sandbox::new("result_json");
+        let line = resume_line(&sandbox, "--no-tmux --wrap \"sh -c 'exit 3'\"");
+
+        let output = sh(&sandbox, &line, false);
+
+        assert_eq!(output.status.code(), Some(3));
+        let result = result_line(&String::from_utf8(output.stdout).unwrap());
+        assert_eq!(result["action"], "inline");
+        assert_eq!(result["exit_code"], 3);
+        assert!(result.get("window_id").is_none());
+    }
+}
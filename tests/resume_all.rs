@@ -0,0 +1,72 @@
+#![cfg(unix)]
+
+/// Integration tests for `amg resume-all`.
+///
+/// These tests run the `amg` binary inside a stub tmux whose `list-windows` reports existing
+/// window names, and verify that:
+/// - A dry run with `--force-new-window` prints the numbered name a real run would open
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::process::Output;
+
+use common::Sandbox;
+
+mod common;
+
+/// Runs `amg resume-all --dry-run --force-new-window` for `branches` inside a stub tmux
+/// whose session already has windows named `windows`.
+fn dry_run(sandbox: &Sandbox, branches: &str, windows: &[&str]) -> Output {
+    let bin = sandbox.root.join("bin");
+    fs::create_dir_all(&bin).expect("Failed to create stub dir");
+    let tmux = bin.join("tmux");
+    let script = format!("#!/bin/sh\nprintf '%s\\n' {}\n", windows.join(" "));
+    fs::write(&tmux, script).expect("Failed to write tmux stub");
+    fs::set_permissions(&tmux, fs::Permissions::from_mode(0o755))
+        .expect("Failed to make stub executable");
+    let path = format!(
+        "{}:{}",
+        bin.display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+    sandbox
+        .amg()
+        .args([
+            "resume-all",
+            "--dry-run",
+            "--force-new-window",
+            "--branches",
+        ])
+        .arg(branches)
+        .arg("--repo")
+        .arg(sandbox.repo())
+        .arg("--codexdir")
+        .arg(sandbox.codexdir())
+        .env("TMUX", "/tmp/tmux-stub,1,0")
+        .env("PATH", path)
+        .output()
+        .expect("Failed to run amg")
+}
+
+/// Returns the `-n` window names in the printed `tmux new-window` commands.
+fn window_names(output: &Output) -> Vec<String> {
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let words: Vec<&str> = line.split(' ').collect();
+            let at = words
+                .iter()
+                .position(|word| word.trim_matches('\'') == "-n")?;
+            Some(words.get(at + 1)?.trim_matches('\'').to_owned())
+        })
+        .collect()
+}
+
+#[test]
+fn dry_run_prints_the_numbered_name_for_a_taken_window() {
+    let sandbox = Sandbox::new("resume_all");
+
+    let output = dry_run(&sandbox, "main", &["main", "main-2", "editor"]);
+
+    assert_eq!(window_names(&output), ["main-3"]);
+}
@@ -0,0 +1,78 @@
+/// Integration tests for `--stats-file` usage records.
+///
+/// These tests run the `amg` binary against a throwaway codex directory and verify that:
+/// - Each run appends exactly one JSON line with the documented fields
+/// - The lookup counters and exit code describe the run
+/// - An unwritable stats file does not change the command's outcome
+use std::fs;
+use std::path::Path;
+use std::process::Output;
+
+use common::Sandbox;
+
+mod common;
+
+mod helpers {
+    use super::*;
+
+    /// Runs `amg which <branch>` in `sandbox` with `AMG_STATS_FILE` set to `stats_file`.
+    pub fn which(sandbox: &Sandbox, branch: &str, stats_file: &Path) -> Output {
+        sandbox
+            .amg()
+            .args(["which", branch, "--no-cache", "--codexdir"])
+            .arg(sandbox.codexdir())
+            .env("AMG_STATS_FILE", stats_file)
+            .output()
+            .expect("Failed to run amg")
+    }
+}
+
+mod records {
+    use super::helpers::which;
+    use super::*;
+
+    #[test]
+    fn each_run_appends_one_record() {
+        let sandbox = Sandbox::new("stats_file");
+        let stats = sandbox.root.join("stats.jsonl");
+
+        let hit = which(&sandbox, "main", &stats);
+        let miss = which(&sandbox, "missing", &stats);
+
+        assert_eq!(hit.status.code(), Some(0));
+        assert_eq!(miss.status.code(), Some(1));
+        let content = fs::read_to_string(&stats).expect("the stats file should exist");
+        let records: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("each line should be JSON"))
+            .collect();
+        assert_eq!(records.len(), 2, "{content}");
+        for record in &records {
+            assert_eq!(record["command"], "which");
+            assert_eq!(record["version"], env!("CARGO_PKG_VERSION"));
+            assert!(record["started_at"].as_str().unwrap().ends_with('Z'));
+            assert!(record["duration_ms"].is_u64());
+            assert_eq!(record["files_scanned"], 1);
+        }
+        assert_eq!(records[0]["matched"], true);
+        assert_eq!(records[0]["exit_code"], 0);
+        assert_eq!(records[1]["matched"], false);
+        assert_eq!(records[1]["exit_code"], 1);
+    }
+
+    #[test]
+    fn an_unwritable_stats_file_only_warns() {
+        let sandbox = Sandbox::new("stats_file");
+        let stats = sandbox.root.join("missing-dir/stats.jsonl");
+
+        let output = which(&sandbox, "main", &stats);
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(0), "{stderr}");
+        assert!(
+            stderr.contains("could not append to the stats file"),
+            "{stderr}"
+        );
+        assert!(String::from_utf8_lossy(&output.stdout).contains("rollout-a.jsonl"));
+    }
+}